@@ -0,0 +1,128 @@
+## Status report
+report-title = Typst Font Manager
+report-action = - Action: { $action }
+action-checking = Checking
+action-updating = Updating
+action-repairing = Repairing
+action-benchmarking = Benchmarking
+report-config-file = - Config file: { $path }
+report-library-dirs = - Font library directories:
+report-font-dir = - Project font directory: { $path }
+report-packs = - Font packs: { $packs }
+report-packages = - Fonts required by Typst packages:
+report-done = === Done ===
+report-up-to-date = { $path } is up to date: nothing changed since its fonts were last found complete (--force checks again)
+
+## Legend
+legend-title = ※ Legend:
+legend-present = Font is required and exists in the project
+legend-embedded = Font is required and is embedded in the compiler
+legend-redundant = Font is not required but exists in the project
+legend-fixable = Font is missing but can be fixed (available in font library)
+legend-faux = Font is missing, but another variant of its family exists in the project
+legend-missing = Font is missing
+
+## Font sets
+set-current = Current fonts
+set-required = Required fonts
+set-missing = Missing fonts
+set-redundant = Redundant fonts
+set-synthesized = Synthesized variants
+set-fonts = Fonts
+set-total = (total { $total })
+set-total-families = (total { $total } in { $families } { $families ->
+    [one] family
+   *[other] families
+})
+family-variants = { $family } ({ $count } { $count ->
+    [one] variant
+   *[other] variants
+})
+
+## Table report
+column-family = Family
+column-style = Style
+column-weight = Weight
+column-stretch = Stretch
+column-status = Status
+column-source = Source
+column-size = Size
+column-version = Version
+column-license = License
+html-filter = Filter by family or source
+html-libraries = Font libraries
+html-warnings = Warnings
+status-missing = missing
+status-faux = faux
+status-fixable = fixable
+status-redundant = redundant
+status-embedded = embedded
+status-ok = ok
+
+## Summary
+summary = { $missing } missing, { $fixable } fixable, { $redundant } redundant
+summary-with-faux = { $missing } missing ({ $faux } faux), { $fixable } fixable, { $redundant } redundant
+
+## Synthesized variants
+faux-warning = { $font }: Typst will render it with { $file } { $variant }, synthesizing or substituting the { $difference }
+faux-note = Glyph shapes and metrics will differ from the real face.
+difference-italic = italic
+difference-oblique = oblique
+difference-normal = upright style
+difference-bold = bold
+difference-lighter = lighter weight
+difference-width = width
+difference-join = { " " }and{ " " }
+
+## Downloadable macOS fonts
+set-inactive-system = Available via macOS but not activated
+inactive-system-note = Activate them in Font Book to use them as system fonts, or add a copy to the font library.
+
+## Embedding permissions
+set-no-embedding = Fonts that may not be embedded in a PDF
+error-no-embedding = { $count } required { $count ->
+    [one] font forbids
+   *[other] fonts forbid
+} embedding in a PDF (OS/2 fsType)
+
+## System font versions
+set-shadowed-system = Project fonts with another version installed on this system
+shadow-family = { $family }: { $version } in the project, { $system_version } on the system
+shadow-face = { $font }: { $version } in { $file }, { $system_version } in { $system_file }
+shadow-note = Local previews that use the system fonts may differ from CI output, which uses the project fonts.
+
+## Weight inference
+weight-inferred = Matching { $path } as weight { $weight }: its subfamily "{ $style }" contradicts the declared weight { $declared }
+
+## Updates
+update-none = No missing fonts to update
+update-dry-run-title = Dry run: planned font updates
+update-title = Updating fonts
+
+## Index staleness
+warning = Warning:
+stale-index = Project fonts were resolved from the { $repo } index at commit { $commit } ({ $date }), which is { $days } days older than the current HEAD.
+stale-hint = Running `typfont update` may fetch newer font versions.
+stale-failed = Could not check index staleness of { $repo }: { $error }
+
+## Errors
+error = Error:
+error-library = Failed to read font library: { $error }
+error-update = Failed to update fonts: { $error }
+error-write = Failed to write { $path }: { $error }
+error-incomplete = The project's fonts are incomplete: { $summary }
+error-config-not-found = Config file not found: { $path }
+error-config-parse = Failed to parse font config file { $path }: { $error }
+error-init = Error initializing font manager: { $error }
+error-record-revisions = Error recording library index revisions: { $error }
+error-record-journal = Error recording the run in the project journal: { $error }
+error-record-fingerprint = Error recording the project fingerprint: { $error }
+
+## Cancellation
+cancel-requested = Stopping after the current file, press Ctrl-C again to abort it
+cancel-aborted = Aborted, the file being installed was removed
+cancel-stopped = Stopped after installing { $installed } of { $planned } files
+cancel-resume = The update was cancelled, run it again to install the remaining fonts
+cancel-rolled-back = Removed { $path }, left incomplete by an interrupted update
+lock-busy = { $path } is in use by another typfont process (remove --no-wait to wait for it)
+lock-waiting = Waiting for another typfont process using { $path }...
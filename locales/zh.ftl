@@ -0,0 +1,119 @@
+## Status report
+report-title = Typst 字体管理器
+report-action = - 操作：{ $action }
+action-checking = 检查
+action-updating = 更新
+action-repairing = 修复
+action-benchmarking = 基准测试
+report-config-file = - 配置文件：{ $path }
+report-library-dirs = - 字体库目录：
+report-font-dir = - 项目字体目录：{ $path }
+report-packs = - 字体包：{ $packs }
+report-packages = - Typst 包所需的字体：
+report-done = === 完成 ===
+report-up-to-date = { $path } 已是最新：自上次确认字体齐全以来没有任何变化（使用 --force 重新检查）
+
+## Legend
+legend-title = ※ 图例：
+legend-present = 项目需要该字体，且项目中已存在
+legend-embedded = 项目需要该字体，且编译器已内置
+legend-redundant = 项目不需要该字体，但项目中存在
+legend-fixable = 字体缺失，但可以修复（字体库中可用）
+legend-faux = 字体缺失，但项目中存在同一字族的其他变体
+legend-missing = 字体缺失
+
+## Font sets
+set-current = 当前字体
+set-required = 所需字体
+set-missing = 缺失字体
+set-redundant = 多余字体
+set-synthesized = 合成变体
+set-fonts = 字体
+set-total = （共 { $total } 个）
+set-total-families = （共 { $total } 个，{ $families } 个字族）
+family-variants = { $family }（{ $count } 个变体）
+
+## Table report
+column-family = 字族
+column-style = 样式
+column-weight = 字重
+column-stretch = 宽度
+column-status = 状态
+column-source = 来源
+column-size = 大小
+column-version = 版本
+column-license = 许可证
+html-filter = 按字族或来源筛选
+html-libraries = 字体库
+html-warnings = 警告
+status-missing = 缺失
+status-faux = 合成
+status-fixable = 可修复
+status-redundant = 多余
+status-embedded = 内置
+status-ok = 正常
+
+## Summary
+summary = 缺失 { $missing } 个，可修复 { $fixable } 个，多余 { $redundant } 个
+summary-with-faux = 缺失 { $missing } 个（其中 { $faux } 个将被合成），可修复 { $fixable } 个，多余 { $redundant } 个
+
+## Synthesized variants
+faux-warning = { $font }：Typst 将使用 { $file } { $variant } 渲染，并合成或替代其{ $difference }
+faux-note = 字形和度量将与真实字体不同。
+difference-italic = 斜体
+difference-oblique = 倾斜体
+difference-normal = 正体
+difference-bold = 粗体
+difference-lighter = 较细字重
+difference-width = 宽度
+difference-join = 和
+
+## Downloadable macOS fonts
+set-inactive-system = macOS 可提供但尚未激活
+inactive-system-note = 在“字体册”中激活后即可作为系统字体使用，或将其副本加入字体库。
+
+## Embedding permissions
+set-no-embedding = 不允许嵌入 PDF 的字体
+error-no-embedding = { $count } 个必需字体禁止嵌入 PDF（OS/2 fsType）
+
+## System font versions
+set-shadowed-system = 系统中安装了其他版本的项目字体
+shadow-family = { $family }：项目中为 { $version }，系统中为 { $system_version }
+shadow-face = { $font }：{ $file } 为 { $version }，{ $system_file } 为 { $system_version }
+shadow-note = 使用系统字体的本地预览可能与使用项目字体的 CI 输出不同。
+
+## Weight inference
+weight-inferred = 将 { $path } 按字重 { $weight } 匹配：其子族名“{ $style }”与声明的字重 { $declared } 矛盾
+
+## Updates
+update-none = 没有需要更新的缺失字体
+update-dry-run-title = 试运行：计划的字体更新
+update-title = 正在更新字体
+
+## Index staleness
+warning = 警告：
+stale-index = 项目字体是根据 { $repo } 索引在提交 { $commit }（{ $date }）时解析的，比当前 HEAD 早 { $days } 天。
+stale-hint = 运行 `typfont update` 可能会获取更新的字体版本。
+stale-failed = 无法检查 { $repo } 的索引是否过期：{ $error }
+
+## Errors
+error = 错误：
+error-library = 无法读取字体库：{ $error }
+error-update = 更新字体失败：{ $error }
+error-write = 无法写入 { $path }：{ $error }
+error-incomplete = 项目字体不完整：{ $summary }
+error-config-not-found = 找不到配置文件：{ $path }
+error-config-parse = 无法解析字体配置文件 { $path }：{ $error }
+error-init = 初始化字体管理器时出错：{ $error }
+error-record-revisions = 记录字体库索引版本时出错：{ $error }
+error-record-journal = 记录运行日志时出错：{ $error }
+error-record-fingerprint = 记录项目指纹时出错：{ $error }
+
+## Cancellation
+cancel-requested = 将在当前文件完成后停止，再次按 Ctrl-C 立即中止
+cancel-aborted = 已中止，正在安装的文件已被删除
+cancel-stopped = 已在安装 { $planned } 个文件中的 { $installed } 个后停止
+cancel-resume = 更新已取消，再次运行以安装剩余的字体
+cancel-rolled-back = 已删除被中断的更新留下的不完整文件 { $path }
+lock-busy = { $path } 正被另一个 typfont 进程使用（去掉 --no-wait 以等待其完成）
+lock-waiting = 正在等待使用 { $path } 的另一个 typfont 进程...
@@ -0,0 +1,172 @@
+//! The `adopt` subcommand: copy faces of system fonts into a managed library
+//! under canonical names, `<family>/<PostScript name>.<ext>`, so a library that
+//! can be shared with a project is seeded from the fonts already installed.
+
+use crate::color::Colorize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::DiscoveredFont;
+use crate::pdf_fonts::postscript_name;
+use crate::process_font::FontLicense;
+
+/// A system font file to copy into the library
+#[derive(Debug)]
+pub(crate) struct Adoption<'a> {
+    pub(crate) source: &'a Path,
+    /// Path relative to the library directory
+    pub(crate) target: PathBuf,
+    /// The adopted faces of the file
+    pub(crate) faces: Vec<&'a DiscoveredFont>,
+}
+
+/// The files of the `entries` matched by `matches` to copy into `library`,
+/// leaving out those already inside it. A collection keeps its file name, as
+/// its faces have different PostScript names.
+pub(crate) fn plan_adoptions<'a>(
+    entries: &'a [DiscoveredFont],
+    matches: impl Fn(&DiscoveredFont) -> bool,
+    library: &Path,
+) -> Vec<Adoption<'a>> {
+    let library = library
+        .canonicalize()
+        .unwrap_or_else(|_| library.to_path_buf());
+    let mut files = BTreeMap::<&Path, Vec<&DiscoveredFont>>::new();
+    for entry in entries.iter().filter(|entry| matches(entry)) {
+        if !entry.path.starts_with(&library) {
+            files.entry(&entry.path).or_default().push(entry);
+        }
+    }
+
+    files
+        .into_iter()
+        .map(|(source, faces)| {
+            let family = canonical_name(&faces[0].font.family_name);
+            let file_name = match &faces[..] {
+                [face] => fs::read(source)
+                    .ok()
+                    .and_then(|data| postscript_name(&data, face.index))
+                    .zip(source.extension())
+                    .map(|(name, extension)| {
+                        format!("{}.{}", canonical_name(&name), extension.to_string_lossy())
+                    }),
+                _ => None,
+            };
+            let file_name = file_name.unwrap_or_else(|| {
+                source
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned()
+            });
+            Adoption {
+                source,
+                target: Path::new(&family).join(file_name),
+                faces,
+            }
+        })
+        .collect()
+}
+
+/// `name` with every character other than ASCII letters, digits, `-` and `_`
+/// left out, e.g. "NotoSans" for "Noto Sans"
+fn canonical_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect()
+}
+
+/// Warn once per license that isn't known to allow redistribution, naming the
+/// families under it
+pub(crate) fn print_license_warnings(adoptions: &[Adoption]) {
+    let mut licenses = BTreeMap::<Option<FontLicense>, Vec<&str>>::new();
+    for face in adoptions.iter().flat_map(|adoption| &adoption.faces) {
+        let families = licenses.entry(face.metadata.license).or_default();
+        if !families.contains(&face.font.family_name.as_str()) {
+            families.push(&face.font.family_name);
+        }
+    }
+
+    for (license, families) in licenses {
+        let license = match license {
+            Some(FontLicense::Proprietary) => "proprietary license",
+            None => "no declared license",
+            Some(_) => continue,
+        };
+        println!(
+            "{} {license} for {}. Make sure it allows redistribution before sharing the library.",
+            "Warning:".yellow().bold(),
+            families.join(", ")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_font_config::TypstFont;
+    use crate::process_font::FontMetadata;
+    use typst::text::{FontStretch, FontStyle, FontWeight};
+
+    fn entry(family: &str, path: &str, index: u32) -> DiscoveredFont {
+        DiscoveredFont {
+            font: TypstFont {
+                family_name: family.to_string(),
+                style: FontStyle::Normal,
+                weight: FontWeight::REGULAR,
+                stretch: FontStretch::NORMAL,
+            },
+            path: PathBuf::from(path),
+            index,
+            axes: Vec::new(),
+            metadata: FontMetadata::default(),
+        }
+    }
+
+    #[test]
+    fn test_adoptions_use_canonical_names() {
+        let entries = [
+            entry("Noto Sans CJK SC", "/usr/share/fonts/NotoSansCJK.ttc", 0),
+            entry("Noto Sans CJK SC", "/usr/share/fonts/NotoSansCJK.ttc", 1),
+            entry(
+                "Noto Sans",
+                "/usr/share/fonts/missing/NotoSans-Regular.ttf",
+                0,
+            ),
+            entry("Noto Sans", "/srv/library/NotoSans/NotoSans-Bold.ttf", 0),
+            entry("Lato", "/usr/share/fonts/Lato-Regular.ttf", 0),
+        ];
+        let adoptions = plan_adoptions(
+            &entries,
+            |entry| entry.font.family_name.starts_with("Noto"),
+            Path::new("/srv/library"),
+        );
+        let targets = adoptions
+            .iter()
+            .map(|adoption| (adoption.target.clone(), adoption.faces.len()))
+            .collect::<Vec<_>>();
+        // Unreadable files keep their name too
+        assert_eq!(
+            targets,
+            [
+                (PathBuf::from("NotoSansCJKSC/NotoSansCJK.ttc"), 2),
+                (PathBuf::from("NotoSans/NotoSans-Regular.ttf"), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_single_faces_are_named_after_their_postscript_name() {
+        let system = Path::new("/usr/share/fonts/truetype/dejavu/DejaVuSans-Bold.ttf");
+        if !system.is_file() {
+            return;
+        }
+        let entries = [entry("DejaVu Sans", &system.display().to_string(), 0)];
+        let adoptions = plan_adoptions(&entries, |_| true, Path::new("/srv/library"));
+        assert_eq!(
+            adoptions[0].target,
+            PathBuf::from("DejaVuSans/DejaVuSans-Bold.ttf")
+        );
+    }
+}
@@ -0,0 +1,145 @@
+//! The `badge` subcommand: the project's font status as a shields.io endpoint
+//! JSON or a standalone SVG badge, e.g. "fonts | 12/12 ok" or
+//! "fonts | 3 missing", for READMEs of Typst templates.
+
+use serde::Serialize;
+
+use crate::font_manager::FontManager;
+
+/// Approximate width of a character of 11px Verdana, the font of shields.io badges
+const CHAR_WIDTH: f64 = 6.5;
+/// Horizontal padding on each side of the label and the message
+const PADDING: f64 = 5.0;
+
+/// The font status shown on a badge
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct BadgeStatus {
+    pub(crate) message: String,
+    /// A shields.io color name
+    pub(crate) color: &'static str,
+}
+
+impl BadgeStatus {
+    pub(crate) fn new(required: usize, missing: usize) -> Self {
+        if required == 0 {
+            BadgeStatus {
+                message: "none required".to_string(),
+                color: "lightgrey",
+            }
+        } else if missing == 0 {
+            BadgeStatus {
+                message: format!("{required}/{required} ok"),
+                color: "brightgreen",
+            }
+        } else {
+            BadgeStatus {
+                message: format!("{missing} missing"),
+                color: if missing == required { "red" } else { "orange" },
+            }
+        }
+    }
+
+    pub(crate) fn of(font_manager: &FontManager) -> Self {
+        let (required, missing) = font_manager.installed_counts();
+        Self::new(required, missing)
+    }
+}
+
+/// The shields.io endpoint schema, see <https://shields.io/badges/endpoint-badge>
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Endpoint<'a> {
+    schema_version: u8,
+    label: &'a str,
+    message: &'a str,
+    color: &'a str,
+}
+
+pub(crate) fn endpoint_json(label: &str, status: &BadgeStatus) -> String {
+    let endpoint = Endpoint {
+        schema_version: 1,
+        label,
+        message: &status.message,
+        color: status.color,
+    };
+    // Serializing plain strings can't fail
+    serde_json::to_string_pretty(&endpoint).unwrap_or_default() + "\n"
+}
+
+/// A flat badge in the style of shields.io
+pub(crate) fn svg(label: &str, status: &BadgeStatus) -> String {
+    let text_width = |text: &str| text.chars().count() as f64 * CHAR_WIDTH + 2.0 * PADDING;
+    let label_width = text_width(label).round();
+    let message_width = text_width(&status.message).round();
+    let width = label_width + message_width;
+    let color = hex_color(status.color);
+    let label_x = label_width / 2.0;
+    let message_x = label_width + message_width / 2.0;
+    let label = escape_xml(label);
+    let message = escape_xml(&status.message);
+
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {message}">
+  <title>{label}: {message}</title>
+  <linearGradient id="s" x2="0" y2="100%">
+    <stop offset="0" stop-color="#bbb" stop-opacity=".1"/>
+    <stop offset="1" stop-opacity=".1"/>
+  </linearGradient>
+  <clipPath id="r"><rect width="{width}" height="20" rx="3" fill="#fff"/></clipPath>
+  <g clip-path="url(#r)">
+    <rect width="{label_width}" height="20" fill="#555"/>
+    <rect x="{label_width}" width="{message_width}" height="20" fill="{color}"/>
+    <rect width="{width}" height="20" fill="url(#s)"/>
+  </g>
+  <g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11">
+    <text x="{label_x}" y="15" fill="#010101" fill-opacity=".3">{label}</text>
+    <text x="{label_x}" y="14">{label}</text>
+    <text x="{message_x}" y="15" fill="#010101" fill-opacity=".3">{message}</text>
+    <text x="{message_x}" y="14">{message}</text>
+  </g>
+</svg>
+"##
+    )
+}
+
+fn hex_color(color: &str) -> &'static str {
+    match color {
+        "brightgreen" => "#4c1",
+        "orange" => "#fe7d37",
+        "red" => "#e05d44",
+        _ => "#9f9f9f",
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_badge_status_and_endpoint() {
+        assert_eq!(BadgeStatus::new(12, 0).message, "12/12 ok");
+        assert_eq!(BadgeStatus::new(12, 3).color, "orange");
+        assert_eq!(BadgeStatus::new(3, 3).color, "red");
+        assert_eq!(BadgeStatus::new(0, 0).color, "lightgrey");
+
+        let json: serde_json::Value =
+            serde_json::from_str(&endpoint_json("fonts", &BadgeStatus::new(12, 3))).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "schemaVersion": 1,
+                "label": "fonts",
+                "message": "3 missing",
+                "color": "orange"
+            })
+        );
+        assert!(svg("a<b", &BadgeStatus::new(1, 0)).contains("a&lt;b: 1/1 ok"));
+    }
+}
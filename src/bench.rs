@@ -0,0 +1,188 @@
+//! The `bench` subcommand: times library indexing, project resolution and
+//! update planning, and reports the results as JSON.
+
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::time::{Duration, Instant};
+
+use crate::command::BenchCommand;
+use crate::create_font_entries_from_dirs;
+use crate::error::Error;
+use crate::font_manager::FontManager;
+use crate::t;
+
+#[derive(Debug, Serialize)]
+struct BenchReport {
+    version: &'static str,
+    iterations: usize,
+    library: LibrarySize,
+    project: ProjectSize,
+    phases: Phases,
+}
+
+#[derive(Debug, Serialize)]
+struct LibrarySize {
+    sources: usize,
+    files: usize,
+    font_entries: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct ProjectSize {
+    missing: usize,
+    planned_updates: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct Phases {
+    indexing: PhaseTimings,
+    resolution: PhaseTimings,
+    planning: PhaseTimings,
+}
+
+/// Timings of one phase. The first iteration runs with cold file system and
+/// process caches and is reported on its own; the others are summarized as warm.
+#[derive(Debug, PartialEq, Serialize)]
+struct PhaseTimings {
+    cold_ms: f64,
+    warm: Option<WarmTimings>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct WarmTimings {
+    min_ms: f64,
+    mean_ms: f64,
+    median_ms: f64,
+    max_ms: f64,
+}
+
+impl PhaseTimings {
+    fn from_samples(samples: &[Duration]) -> Self {
+        // Microsecond resolution is plenty and keeps the JSON readable
+        let millis = |duration: &Duration| (duration.as_secs_f64() * 1e6).round() / 1e3;
+        let mut warm = samples.iter().skip(1).map(millis).collect::<Vec<_>>();
+        warm.sort_by(f64::total_cmp);
+
+        let summary = (!warm.is_empty()).then(|| {
+            let middle = warm.len() / 2;
+            let median = if warm.len() % 2 == 0 {
+                (warm[middle - 1] + warm[middle]) / 2.0
+            } else {
+                warm[middle]
+            };
+            WarmTimings {
+                min_ms: warm[0],
+                mean_ms: (warm.iter().sum::<f64>() / warm.len() as f64 * 1e3).round() / 1e3,
+                median_ms: median,
+                max_ms: warm[warm.len() - 1],
+            }
+        });
+
+        Self {
+            cold_ms: samples.first().map_or(0.0, millis),
+            warm: summary,
+        }
+    }
+}
+
+fn timed<T>(samples: &mut Vec<Duration>, run: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = run();
+    samples.push(start.elapsed());
+    result
+}
+
+pub(crate) fn run_bench(args: &BenchCommand) -> Result<String, Error> {
+    args.font.validate().map_err(Error::Usage)?;
+
+    let mut indexing = Vec::with_capacity(args.iterations);
+    let mut resolution = Vec::with_capacity(args.iterations);
+    let mut planning = Vec::with_capacity(args.iterations);
+    let mut sizes = None;
+
+    for iteration in 1..=args.iterations {
+        eprintln!(
+            "Iteration {iteration}/{}{}",
+            args.iterations,
+            if iteration == 1 { " (cold)" } else { "" }
+        );
+
+        let library_dirs = FontManager::library_dirs(&args.font).map_err(Error::Usage)?;
+        let sources = library_dirs.into_iter().count();
+
+        let library_entries = timed(&mut indexing, || {
+            create_font_entries_from_dirs(&library_dirs)
+        })?;
+        let library = LibrarySize {
+            sources,
+            files: library_entries
+                .iter()
+                .map(|entry| &entry.path)
+                .collect::<BTreeSet<_>>()
+                .len(),
+            font_entries: library_entries.len(),
+        };
+
+        let action = t!("action-benchmarking");
+        let font_manager = timed(&mut resolution, || {
+            FontManager::with_library_entries(&args.font, &action, library_dirs, library_entries)
+        })
+        .map_err(Error::Project)?;
+
+        let planned_updates = timed(&mut planning, || {
+            font_manager
+                .plan_updates()
+                .iter()
+                .filter(|(_, entry)| entry.is_some())
+                .count()
+        });
+
+        sizes = Some((
+            library,
+            ProjectSize {
+                missing: font_manager.summary().missing,
+                planned_updates,
+            },
+        ));
+    }
+
+    let (library, project) =
+        sizes.ok_or_else(|| Error::Usage("At least one iteration is required".to_string()))?;
+    let report = BenchReport {
+        version: env!("CARGO_PKG_VERSION"),
+        iterations: args.iterations,
+        library,
+        project,
+        phases: Phases {
+            indexing: PhaseTimings::from_samples(&indexing),
+            resolution: PhaseTimings::from_samples(&resolution),
+            planning: PhaseTimings::from_samples(&planning),
+        },
+    };
+
+    serde_json::to_string_pretty(&report)
+        .map_err(|e| Error::Other(format!("Failed to serialize results: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phase_timings_separate_cold_run() {
+        let samples = [400, 30, 10, 20].map(Duration::from_millis);
+        let timings = PhaseTimings::from_samples(&samples);
+
+        assert_eq!(timings.cold_ms, 400.0);
+        assert_eq!(
+            timings.warm,
+            Some(WarmTimings {
+                min_ms: 10.0,
+                mean_ms: 20.0,
+                median_ms: 20.0,
+                max_ms: 30.0,
+            })
+        );
+        assert_eq!(PhaseTimings::from_samples(&samples[..1]).warm, None);
+    }
+}
@@ -0,0 +1,593 @@
+//! Cache of GitHub library indexes, per user or shared by the users of a
+//! machine. Each index is stored after it has been parsed and validated,
+//! together with the ETag and hash of the file it was read from, so that other
+//! projects on the same machine resolving against the same library get the
+//! entries back without parsing the TOML again.
+//!
+//! The faces of local font files are cached as well, see [`ScanCache`], so that
+//! scanning the system font directories again only parses the files that
+//! changed.
+
+use crate::color::Colorize;
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use typst::text::FontAxis;
+use walkdir::WalkDir;
+
+use crate::DiscoveredFont;
+use crate::file_lock::{self, LOCK_FILE};
+use crate::lockfile::sha256_hex;
+use crate::parse_font_config::TypstFont;
+use crate::process_font::FontMetadata;
+use crate::shared;
+use crate::table::{Table, format_size};
+use crate::user_config;
+
+/// Overrides the cache location, e.g. for CI or tests
+const CACHE_DIR_VAR: &str = "TYPFONT_CACHE_DIR";
+
+/// A cache shared by the users of a machine, see [`shared_cache_dir`]
+const SHARED_CACHE_DIR_VAR: &str = "TYPFONT_SHARED_CACHE_DIR";
+
+/// A parsed library index, as stored in the cache
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct CachedIndex<T> {
+    /// The library source, e.g. "owner/repo" or "owner/repo::path/to/index.toml"
+    pub(crate) source: String,
+    pub(crate) etag: Option<String>,
+    /// SHA-256 of the index file the entries were parsed from
+    pub(crate) sha256: String,
+    pub(crate) fetched_at: String,
+    pub(crate) fonts: Vec<T>,
+}
+
+/// The directory holding typfont's caches: `$TYPFONT_CACHE_DIR`, the shared
+/// cache if one is configured, or `typfont` in the platform's user cache directory
+pub(crate) fn cache_dir() -> Option<PathBuf> {
+    let var = |name| env::var_os(name).filter(|value| !value.is_empty());
+    if let Some(dir) = var(CACHE_DIR_VAR) {
+        return Some(PathBuf::from(dir));
+    }
+    if let Some(dir) = shared_cache_dir() {
+        return Some(dir);
+    }
+    let base = if cfg!(target_os = "windows") {
+        var("LOCALAPPDATA").map(PathBuf::from)?
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from(var("HOME")?).join("Library/Caches")
+    } else {
+        var("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| Some(PathBuf::from(var("HOME")?).join(".cache")))?
+    };
+    Some(base.join("typfont"))
+}
+
+/// The cache shared by every user of the machine, e.g.
+/// `/var/cache/typst-font-manager`: `$TYPFONT_SHARED_CACHE_DIR`, or the
+/// `shared_dir` of the `[cache]` settings. Its files are created writable by
+/// the group, see [`crate::shared`].
+fn shared_cache_dir() -> Option<PathBuf> {
+    env::var_os(SHARED_CACHE_DIR_VAR)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| user_config::get().cache.shared_dir.clone())
+}
+
+/// Whether [`cache_dir`] is the shared cache
+fn is_shared() -> bool {
+    env::var_os(CACHE_DIR_VAR).is_none_or(|value| value.is_empty()) && shared_cache_dir().is_some()
+}
+
+fn index_dir() -> Option<PathBuf> {
+    cache_dir().map(|dir| dir.join("indexes"))
+}
+
+/// The cache file of `source`. Sources are hashed, as they contain slashes.
+fn index_path(source: &str) -> Option<PathBuf> {
+    index_dir().map(|dir| dir.join(format!("{}.json", &sha256_hex(source.as_bytes())[..16])))
+}
+
+/// The cached index of `source`, if there is a readable one. Its modification
+/// time is updated, so that `cache gc` removes the least recently used files.
+pub(crate) fn load_index<T: DeserializeOwned>(source: &str) -> Option<CachedIndex<T>> {
+    let path = index_path(source)?;
+    let content = fs::read(&path).ok()?;
+    let index = serde_json::from_slice::<CachedIndex<T>>(&content)
+        .ok()
+        .filter(|index| index.source == source)?;
+    let _ = File::options()
+        .write(true)
+        .open(&path)
+        .and_then(|file| file.set_modified(SystemTime::now()));
+    Some(index)
+}
+
+pub(crate) fn store_index<T: Serialize>(index: &CachedIndex<T>) -> Result<(), String> {
+    let path = index_path(&index.source).ok_or("No cache directory found")?;
+    let content = serde_json::to_vec(index).map_err(|e| format!("Failed to encode cache: {e}"))?;
+    write_cache_file(&path, &content)
+}
+
+/// Write `content` to `path` in the cache directory, under the cache lock
+fn write_cache_file(path: &Path, content: &[u8]) -> Result<(), String> {
+    let dir = cache_dir().ok_or("No cache directory found")?;
+    if is_shared() {
+        let _lock = file_lock::lock_shared_dir(&dir)?;
+        if let Some(parent) = path.parent() {
+            shared::create_dir_all(parent)?;
+        }
+        return shared::write_file(path, content);
+    }
+    let _lock = file_lock::lock_dir(&dir)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directories {parent:?}: {e}"))?;
+    }
+    fs::write(path, content).map_err(|e| format!("Failed to write {path:?}: {e}"))
+}
+
+fn scan_path() -> Option<PathBuf> {
+    cache_dir().map(|dir| dir.join(SCAN_FILE))
+}
+
+const SCAN_FILE: &str = "scans.json";
+
+/// The faces of the local font files parsed by earlier scans, by path. An entry
+/// is used while the size and modification time of its file are unchanged.
+/// With `--infer-weights`, the weights of the faces depend on the run, so the
+/// cache is neither read nor written.
+#[derive(Default)]
+pub(crate) struct ScanCache {
+    files: BTreeMap<PathBuf, ScannedFile>,
+    enabled: bool,
+    /// Whether cached faces are used, unless `--no-cache` is given
+    read: bool,
+    changed: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ScannedFile {
+    size: u64,
+    /// Nanoseconds since the Unix epoch
+    modified: u128,
+    faces: Vec<ScannedFace>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ScannedFace {
+    font: TypstFont,
+    index: u32,
+    axes: Vec<FontAxis>,
+    metadata: FontMetadata,
+}
+
+impl ScanCache {
+    /// The cache of earlier scans. With `--no-cache`, its faces are parsed again
+    /// and replaced.
+    pub(crate) fn load() -> Self {
+        let enabled = !crate::infer_weights() && scan_path().is_some();
+        let files = scan_path()
+            .filter(|_| enabled)
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|content| serde_json::from_slice(&content).ok())
+            .unwrap_or_default();
+        Self {
+            files,
+            enabled,
+            read: !crate::no_cache(),
+            changed: false,
+        }
+    }
+
+    /// The cached faces of the file at `path`, if it is unchanged since it was parsed
+    pub(crate) fn faces(
+        &self,
+        path: &Path,
+        metadata: &fs::Metadata,
+    ) -> Option<Vec<DiscoveredFont>> {
+        let file = self.files.get(path).filter(|_| self.read)?;
+        if (file.size, file.modified) != (metadata.len(), modified_nanos(metadata)?) {
+            return None;
+        }
+        Some(
+            file.faces
+                .iter()
+                .map(|face| DiscoveredFont {
+                    font: face.font.clone(),
+                    path: path.to_path_buf(),
+                    index: face.index,
+                    axes: face.axes.clone(),
+                    metadata: face.metadata.clone(),
+                })
+                .collect(),
+        )
+    }
+
+    /// Record the faces parsed from the file at `path`
+    pub(crate) fn insert(
+        &mut self,
+        path: &Path,
+        metadata: &fs::Metadata,
+        faces: &[DiscoveredFont],
+    ) {
+        let Some(modified) = modified_nanos(metadata).filter(|_| self.enabled) else {
+            return;
+        };
+        let faces = faces
+            .iter()
+            .map(|face| ScannedFace {
+                font: face.font.clone(),
+                index: face.index,
+                axes: face.axes.clone(),
+                metadata: face.metadata.clone(),
+            })
+            .collect();
+        self.files.insert(
+            path.to_path_buf(),
+            ScannedFile {
+                size: metadata.len(),
+                modified,
+                faces,
+            },
+        );
+        self.changed = true;
+    }
+
+    /// Store the cache if a scan added to it, leaving out the files that are gone
+    pub(crate) fn save(mut self) -> Result<(), String> {
+        let Some(path) = scan_path().filter(|_| self.enabled && self.changed) else {
+            return Ok(());
+        };
+        self.files.retain(|file, _| file.is_file());
+        let content =
+            serde_json::to_vec(&self.files).map_err(|e| format!("Failed to encode cache: {e}"))?;
+        write_cache_file(&path, &content)
+    }
+}
+
+fn modified_nanos(metadata: &fs::Metadata) -> Option<u128> {
+    let modified = metadata.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_nanos())
+}
+
+/// `cache clear`: remove every cached file
+pub(crate) fn clear() -> Result<(), String> {
+    let Some(dir) = cache_dir() else {
+        println!("No cache directory found");
+        return Ok(());
+    };
+    let lock = if is_shared() {
+        file_lock::lock_shared_dir
+    } else {
+        file_lock::lock_dir
+    };
+    let _lock = lock(&dir)?;
+    let files = cached_files(&dir);
+    for file in &files {
+        fs::remove_file(&file.path)
+            .map_err(|e| format!("Failed to remove {:?}: {e}", file.path))?;
+    }
+    let freed = files.iter().map(|file| file.size).sum();
+    println!("Removed {} files ({})", files.len(), format_size(freed));
+    Ok(())
+}
+
+/// `cache status`: list the cached library indexes
+pub(crate) fn print_status() {
+    let Some(dir) = cache_dir() else {
+        println!("No cache directory found; set {CACHE_DIR_VAR} to enable caching");
+        return;
+    };
+    println!("{} {}", "Cache directory:".bold(), dir.display());
+    if is_shared() {
+        println!("  Shared by the users of this machine");
+    }
+    if let Ok(metadata) = fs::metadata(dir.join(SCAN_FILE)) {
+        println!(
+            "  Faces of scanned local font files: {}",
+            format_size(metadata.len())
+        );
+    }
+
+    let indexes = list_indexes(&dir.join("indexes"));
+    if indexes.is_empty() {
+        println!("  No library indexes are cached");
+        return;
+    }
+
+    let mut table = Table::new(vec!["Source", "Entries", "ETag", "Fetched", "Size"]);
+    let mut total_size = 0;
+    for (index, size) in &indexes {
+        total_size += size;
+        table.push_row(vec![
+            index.source.as_str().into(),
+            index.fonts.len().to_string().into(),
+            index.etag.as_deref().unwrap_or("-").into(),
+            index.fetched_at.as_str().into(),
+            format_size(*size).into(),
+        ]);
+    }
+    println!("{}", table.render());
+    println!(
+        "  {} cached indexes, {}",
+        indexes.len(),
+        format_size(total_size)
+    );
+}
+
+/// `cache stats`: disk usage of the cache, per library source
+pub(crate) fn print_stats() {
+    let Some(dir) = cache_dir() else {
+        println!("No cache directory found; set {CACHE_DIR_VAR} to enable caching");
+        return;
+    };
+    println!("{} {}", "Cache directory:".bold(), dir.display());
+
+    let files = cached_files(&dir);
+    // Files that aren't library indexes are counted in the total only
+    let mut sources = BTreeMap::<String, (usize, u64, SystemTime)>::new();
+    for file in &files {
+        let Some(source) = fs::read(&file.path)
+            .ok()
+            .and_then(|content| serde_json::from_slice::<CachedIndex<IgnoredAny>>(&content).ok())
+            .map(|index| index.source)
+        else {
+            continue;
+        };
+        let repo = source
+            .split_once("::")
+            .map_or(source.as_str(), |(repo, _)| repo)
+            .to_string();
+        let usage = sources
+            .entry(repo)
+            .or_insert((0, 0, SystemTime::UNIX_EPOCH));
+        usage.0 += 1;
+        usage.1 += file.size;
+        usage.2 = usage.2.max(file.last_used);
+    }
+
+    let mut table = Table::new(vec!["Source", "Files", "Size", "Last used"]);
+    for (source, (count, size, last_used)) in &sources {
+        table.push_row(vec![
+            source.as_str().into(),
+            count.to_string().into(),
+            format_size(*size).into(),
+            format_age(*last_used).into(),
+        ]);
+    }
+    if !sources.is_empty() {
+        println!("{}", table.render());
+    }
+
+    let total = files.iter().map(|file| file.size).sum();
+    println!("  Total: {} files, {}", files.len(), format_size(total));
+}
+
+/// A file in the cache directory
+struct CachedFile {
+    path: PathBuf,
+    size: u64,
+    last_used: SystemTime,
+}
+
+/// All files below `dir`, least recently used first
+fn cached_files(dir: &Path) -> Vec<CachedFile> {
+    let mut files = WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file() && entry.file_name() != LOCK_FILE)
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            Some(CachedFile {
+                path: entry.into_path(),
+                size: metadata.len(),
+                last_used: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            })
+        })
+        .collect::<Vec<_>>();
+    files.sort_by_key(|file| file.last_used);
+    files
+}
+
+/// The files to remove so that none is older than `max_age` and the remaining
+/// ones take at most `max_size` bytes, removing the least recently used first
+fn select_garbage(
+    files: &[CachedFile],
+    max_size: Option<u64>,
+    max_age: Option<Duration>,
+    now: SystemTime,
+) -> Vec<usize> {
+    let mut total = files.iter().map(|file| file.size).sum::<u64>();
+    let mut garbage = Vec::new();
+    // `files` is sorted from the least recently used
+    for (index, file) in files.iter().enumerate() {
+        let expired = max_age.is_some_and(|max_age| {
+            now.duration_since(file.last_used)
+                .is_ok_and(|age| age > max_age)
+        });
+        let over_size = max_size.is_some_and(|max_size| total > max_size);
+        if expired || over_size {
+            total -= file.size;
+            garbage.push(index);
+        }
+    }
+    garbage
+}
+
+/// `cache gc`: trim the cache to the given size and age limits
+pub(crate) fn collect_garbage(
+    max_size: Option<u64>,
+    max_age: Option<Duration>,
+    dry_run: bool,
+) -> Result<(), String> {
+    let Some(dir) = cache_dir() else {
+        println!("No cache directory found");
+        return Ok(());
+    };
+
+    let lock = if is_shared() {
+        file_lock::lock_shared_dir
+    } else {
+        file_lock::lock_dir
+    };
+    let _lock = (!dry_run).then(|| lock(&dir)).transpose()?;
+    let files = cached_files(&dir);
+    let garbage = select_garbage(&files, max_size, max_age, SystemTime::now());
+    let mut freed = 0;
+    for &index in &garbage {
+        let file = &files[index];
+        if dry_run {
+            println!("  Would remove {}", file.path.display());
+        } else {
+            fs::remove_file(&file.path)
+                .map_err(|e| format!("Failed to remove {:?}: {e}", file.path))?;
+        }
+        freed += file.size;
+    }
+
+    let total = files.iter().map(|file| file.size).sum::<u64>();
+    println!(
+        "{} {} files ({}); the cache {} {}",
+        if dry_run { "Would remove" } else { "Removed" },
+        garbage.len(),
+        format_size(freed),
+        if dry_run { "would take" } else { "takes" },
+        format_size(total - freed)
+    );
+    Ok(())
+}
+
+/// Parse an age such as `90d`, `2w`, `12h` or `30m`
+pub(crate) fn parse_age(s: &str) -> Result<Duration, String> {
+    let value = s.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid age {s:?}, expected e.g. 90d, 2w or 12h"))?;
+    let seconds = match unit.trim() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(format!("invalid age {s:?}, expected e.g. 90d, 2w or 12h")),
+    };
+    Ok(Duration::from_secs(number * seconds))
+}
+
+fn format_age(time: SystemTime) -> String {
+    let Ok(age) = SystemTime::now().duration_since(time) else {
+        return "just now".to_string();
+    };
+    match age.as_secs() {
+        seconds if seconds < 60 * 60 => format!("{} minutes ago", seconds / 60),
+        seconds if seconds < 24 * 60 * 60 => format!("{} hours ago", seconds / (60 * 60)),
+        seconds => format!("{} days ago", seconds / (24 * 60 * 60)),
+    }
+}
+
+/// The readable cached indexes in `dir` with their file sizes, without decoding
+/// their entries
+fn list_indexes(dir: &Path) -> Vec<(CachedIndex<IgnoredAny>, u64)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut indexes = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|entry| {
+            let content = fs::read(entry.path()).ok()?;
+            let index = serde_json::from_slice(&content).ok()?;
+            Some((index, content.len() as u64))
+        })
+        .collect::<Vec<(CachedIndex<IgnoredAny>, u64)>>();
+    indexes.sort_by(|a, b| a.0.source.cmp(&b.0.source));
+    indexes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cached_indexes_are_listed_without_their_entries() {
+        let dir = env::temp_dir().join(format!("typfont-cache-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let index = CachedIndex {
+            source: "owner/repo".to_string(),
+            etag: Some("\"abc\"".to_string()),
+            sha256: sha256_hex(b"fonts = []"),
+            fetched_at: "2026-01-01T00:00:00Z".to_string(),
+            fonts: vec![1, 2, 3],
+        };
+        fs::write(dir.join("a.json"), serde_json::to_vec(&index).unwrap()).unwrap();
+        fs::write(dir.join("b.json"), "not json").unwrap();
+
+        let indexes = list_indexes(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(indexes.len(), 1);
+        assert_eq!(indexes[0].0.source, "owner/repo");
+        assert_eq!(indexes[0].0.fonts.len(), 3);
+    }
+
+    #[test]
+    fn test_scanned_faces_are_reused_while_the_file_is_unchanged() {
+        let dir = env::temp_dir().join(format!("typfont-scan-cache-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("TypfontFixture-Regular.ttf");
+        let data = include_bytes!("../fixtures/fonts/TypfontFixture-Regular.ttf");
+        fs::write(&path, data).unwrap();
+        let faces = crate::discover_faces(&path, data);
+        assert!(!faces.is_empty());
+
+        let mut cache = ScanCache {
+            enabled: true,
+            read: true,
+            ..ScanCache::default()
+        };
+        cache.insert(&path, &fs::metadata(&path).unwrap(), &faces);
+        // As stored and loaded again
+        let cache = ScanCache {
+            files: serde_json::from_slice(&serde_json::to_vec(&cache.files).unwrap()).unwrap(),
+            ..cache
+        };
+        let cached = cache.faces(&path, &fs::metadata(&path).unwrap()).unwrap();
+        assert_eq!(cached.len(), faces.len());
+        assert_eq!(cached[0].font, faces[0].font);
+        assert_eq!(cached[0].metadata, faces[0].metadata);
+
+        fs::write(&path, &data[..data.len() - 1]).unwrap();
+        assert!(cache.faces(&path, &fs::metadata(&path).unwrap()).is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_garbage_is_expired_or_least_recently_used() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(100 * 24 * 60 * 60);
+        let file = |days_ago: u64, size| CachedFile {
+            path: PathBuf::new(),
+            size,
+            last_used: now - Duration::from_secs(days_ago * 24 * 60 * 60),
+        };
+        // Least recently used first, as returned by `cached_files`
+        let files = [file(95, 10), file(30, 40), file(20, 40), file(1, 40)];
+
+        assert_eq!(
+            select_garbage(&files, None, Some(parse_age("90d").unwrap()), now),
+            [0]
+        );
+        assert_eq!(select_garbage(&files, Some(80), None, now), [0, 1]);
+        assert!(select_garbage(&files, Some(1000), Some(Duration::MAX), now).is_empty());
+        assert!(parse_age("90 days").is_err());
+    }
+}
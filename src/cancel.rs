@@ -0,0 +1,163 @@
+//! Ctrl-C during `update`. The first Ctrl-C lets the file being installed
+//! finish and then stops; a second one rolls the file back and exits at once.
+//! The lockfile records every completed file as it is installed, and the file
+//! in flight is noted in an update state file next to the config, so that a run
+//! killed outright is rolled back by the next one, which then continues with
+//! the fonts still missing.
+
+use crate::color::Colorize;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, Once};
+
+use crate::t;
+
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+/// The files being installed, removed if the run is aborted
+static IN_FLIGHT: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Path of the update state file belonging to `config_file`, e.g. font_config.update
+pub(crate) fn state_path(config_file: &Path) -> PathBuf {
+    config_file.with_extension("update")
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+struct UpdateState {
+    in_flight: Vec<PathBuf>,
+}
+
+/// Handle Ctrl-C from now on, see the module documentation
+pub(crate) fn install_handler() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let _ = ctrlc::set_handler(|| {
+            if CANCELLED.swap(true, Ordering::SeqCst) {
+                if let Ok(in_flight) = IN_FLIGHT.lock() {
+                    for path in in_flight.iter() {
+                        let _ = fs::remove_file(path);
+                    }
+                }
+                eprintln!("\n{}", t!("cancel-aborted"));
+                std::process::exit(130);
+            }
+            eprintln!("\n{}", t!("cancel-requested").yellow());
+        });
+    });
+}
+
+/// Whether Ctrl-C was pressed
+pub(crate) fn cancelled() -> bool {
+    CANCELLED.load(Ordering::SeqCst)
+}
+
+/// Roll back the files an interrupted update left in flight, as recorded in the
+/// state file at `state_path`, returning them
+pub(crate) fn roll_back_interrupted(state_path: &Path) -> Result<Vec<PathBuf>, String> {
+    if !state_path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(state_path)
+        .map_err(|e| format!("Failed to read {state_path:?}: {e}"))?;
+    let state: UpdateState =
+        toml::from_str(&content).map_err(|e| format!("Failed to parse {state_path:?}: {e}"))?;
+    let mut rolled_back = Vec::new();
+    for path in state.in_flight {
+        if fs::symlink_metadata(&path).is_ok() {
+            fs::remove_file(&path).map_err(|e| format!("Failed to remove {path:?}: {e}"))?;
+            rolled_back.push(path);
+        }
+    }
+    fs::remove_file(state_path).map_err(|e| format!("Failed to remove {state_path:?}: {e}"))?;
+    Ok(rolled_back)
+}
+
+/// A file being installed. Unless [`InFlight::finish`] is called, the files it
+/// covers are removed when it is dropped, e.g. when installing fails.
+pub(crate) struct InFlight {
+    state_path: PathBuf,
+    paths: Vec<PathBuf>,
+}
+
+impl InFlight {
+    pub(crate) fn begin(state_path: &Path, path: PathBuf) -> Result<Self, String> {
+        let mut in_flight = Self {
+            state_path: state_path.to_path_buf(),
+            paths: Vec::new(),
+        };
+        in_flight.add(path)?;
+        Ok(in_flight)
+    }
+
+    /// Cover another file written for the same font, e.g. a converted copy
+    pub(crate) fn add(&mut self, path: PathBuf) -> Result<(), String> {
+        self.paths.push(path);
+        if let Ok(mut in_flight) = IN_FLIGHT.lock() {
+            in_flight.clone_from(&self.paths);
+        }
+        let state = UpdateState {
+            in_flight: self.paths.clone(),
+        };
+        let content = toml::to_string(&state).map_err(|e| e.to_string())?;
+        fs::write(&self.state_path, content)
+            .map_err(|e| format!("Failed to write {:?}: {e}", self.state_path))
+    }
+
+    /// The files are complete
+    pub(crate) fn finish(mut self) {
+        self.paths.clear();
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        for path in &self.paths {
+            let _ = fs::remove_file(path);
+        }
+        if let Ok(mut in_flight) = IN_FLIGHT.lock() {
+            in_flight.clear();
+        }
+        let _ = fs::remove_file(&self.state_path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interrupted_files_are_rolled_back() {
+        let dir = std::env::temp_dir().join(format!("typfont-cancel-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let state = state_path(&dir.join("font_config.toml"));
+        assert_eq!(state, dir.join("font_config.update"));
+
+        let partial = dir.join("Partial.ttf");
+        fs::write(&partial, b"half").unwrap();
+        let in_flight = InFlight::begin(&state, partial.clone()).unwrap();
+        assert!(state.exists());
+        // A run killed outright never drops its guard
+        std::mem::forget(in_flight);
+
+        assert_eq!(roll_back_interrupted(&state).unwrap(), [partial.as_path()]);
+        assert!(!partial.exists() && !state.exists());
+        assert!(roll_back_interrupted(&state).unwrap().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_finished_files_are_kept() {
+        let dir = std::env::temp_dir().join(format!("typfont-finish-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let state = state_path(&dir.join("font_config.toml"));
+        let (done, failed) = (dir.join("Done.ttf"), dir.join("Failed.ttf"));
+        fs::write(&done, b"font").unwrap();
+        fs::write(&failed, b"font").unwrap();
+
+        InFlight::begin(&state, done.clone()).unwrap().finish();
+        drop(InFlight::begin(&state, failed.clone()).unwrap());
+        assert!(done.exists() && !failed.exists() && !state.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
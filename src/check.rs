@@ -0,0 +1,207 @@
+//! `check` and `update` of a project against local font libraries, for tools
+//! that manage a project's fonts themselves. Both report every font they
+//! resolve and every file they install to a [`FontObserver`].
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use typst::text::{FontInfo, FontVariant};
+
+use crate::events::{FontObserver, Resolution};
+use crate::parse_font_config::TypstFont;
+use crate::world::{ProjectConfig, info_satisfies, installed_font_files};
+
+/// The outcome of [`check_project`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CheckReport {
+    /// The project font directory.
+    pub font_dir: PathBuf,
+    /// The number of required fonts that are installed or embedded in Typst.
+    pub satisfied: usize,
+    /// The library files providing the required fonts that aren't installed.
+    pub fixable: Vec<PathBuf>,
+    /// The required fonts found nowhere.
+    pub missing: Vec<String>,
+}
+
+impl CheckReport {
+    /// Whether every required font is installed or embedded in Typst.
+    pub fn is_complete(&self) -> bool {
+        self.fixable.is_empty() && self.missing.is_empty()
+    }
+}
+
+/// Check which fonts required by the `font_config.toml` at `config_file` are
+/// installed, and which of the others the `library_dirs` provide.
+pub fn check_project(
+    config_file: &Path,
+    library_dirs: &[PathBuf],
+    observer: &mut dyn FontObserver,
+) -> Result<CheckReport, String> {
+    let ProjectConfig {
+        font_config,
+        font_dir,
+        embedded,
+    } = ProjectConfig::load(config_file)?;
+    let installed = faces_below(std::slice::from_ref(&font_dir));
+    let library = faces_below(library_dirs);
+    let find = |faces: &'_ [(PathBuf, FontInfo)], font| {
+        faces
+            .iter()
+            .find(|(_, info)| info_satisfies(info, font))
+            .map(|(path, _)| path.clone())
+    };
+
+    let mut report = CheckReport {
+        font_dir: font_dir.clone(),
+        ..CheckReport::default()
+    };
+    for font in &font_config.fonts {
+        let variant = FontVariant::new(font.style, font.weight, font.stretch);
+        if let Some(path) = find(&installed, font) {
+            observer.font_resolved(&font.family_name, variant, Resolution::Installed(&path));
+            report.satisfied += 1;
+        } else if embedded.contains(font) {
+            observer.font_resolved(&font.family_name, variant, Resolution::Embedded);
+            report.satisfied += 1;
+        } else if let Some(path) = find(&library, font) {
+            observer.font_resolved(&font.family_name, variant, Resolution::Library(&path));
+            if !report.fixable.contains(&path) {
+                report.fixable.push(path);
+            }
+        } else {
+            let font = describe(font);
+            observer.warning(&format!(
+                "{font} is neither installed in {font_dir:?} nor in the font libraries"
+            ));
+            report.missing.push(font);
+        }
+    }
+    Ok(report)
+}
+
+/// Install the library files providing the required fonts that aren't
+/// installed, see [`check_project`], returning the paths of the installed
+/// files. Fonts found nowhere are reported to the observer but don't fail the
+/// update.
+pub fn update_project(
+    config_file: &Path,
+    library_dirs: &[PathBuf],
+    observer: &mut dyn FontObserver,
+) -> Result<Vec<PathBuf>, String> {
+    let report = check_project(config_file, library_dirs, observer)?;
+    if report.fixable.is_empty() {
+        return Ok(Vec::new());
+    }
+    fs::create_dir_all(&report.font_dir)
+        .map_err(|e| format!("Failed to create directories {:?}: {e}", report.font_dir))?;
+
+    let mut installed = Vec::new();
+    for from in &report.fixable {
+        let Some(file_name) = from.file_name() else {
+            continue;
+        };
+        let to = report.font_dir.join(file_name);
+        observer.download_started(from, &to);
+        fs::copy(from, &to).map_err(|e| format!("Failed to copy {from:?} to {to:?}: {e}"))?;
+        observer.file_copied(from, &to);
+        installed.push(to);
+    }
+    Ok(installed)
+}
+
+/// The faces of the font files below `dirs`
+fn faces_below(dirs: &[PathBuf]) -> Vec<(PathBuf, FontInfo)> {
+    let mut faces = Vec::new();
+    for path in dirs.iter().flat_map(|dir| installed_font_files(dir)) {
+        let Ok(data) = fs::read(&path) else {
+            continue;
+        };
+        let count = ttf_parser::fonts_in_collection(&data).unwrap_or(1);
+        faces.extend(
+            (0..count).filter_map(|index| Some((path.clone(), FontInfo::new(&data, index)?))),
+        );
+    }
+    faces
+}
+
+fn describe(font: &TypstFont) -> String {
+    format!("{} {}", font.family_name, font.variant_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REGULAR: &[u8] = include_bytes!("../fixtures/fonts/TypfontFixture-Regular.ttf");
+
+    /// Records the events as text
+    #[derive(Default)]
+    struct Recorder(Vec<String>);
+
+    impl FontObserver for Recorder {
+        fn font_resolved(&mut self, family: &str, variant: FontVariant, resolution: Resolution) {
+            let resolution = match resolution {
+                Resolution::Installed(_) => "installed",
+                Resolution::Embedded => "embedded",
+                Resolution::Library(_) => "library",
+            };
+            self.0.push(format!(
+                "{family} {} {resolution}",
+                variant.weight.to_number()
+            ));
+        }
+
+        fn download_started(&mut self, from: &Path, _to: &Path) {
+            self.0
+                .push(format!("download {}", from.file_name().unwrap().display()));
+        }
+
+        fn file_copied(&mut self, _from: &Path, to: &Path) {
+            self.0
+                .push(format!("copied {}", to.file_name().unwrap().display()));
+        }
+
+        fn warning(&mut self, message: &str) {
+            self.0
+                .push(format!("warning {}", message.split(' ').next().unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_update_reports_its_progress() {
+        let dir = std::env::temp_dir().join(format!("typfont-check-{}", std::process::id()));
+        let library = dir.join("lib");
+        fs::create_dir_all(&library).unwrap();
+        fs::write(library.join("TypfontFixture-Regular.ttf"), REGULAR).unwrap();
+        let config = dir.join("font_config.toml");
+        fs::write(
+            &config,
+            "[[fonts]]\nfamily_name = \"Typfont Fixture\"\n\n\
+             [[fonts]]\nfamily_name = \"New Computer Modern\"\n\n\
+             [[fonts]]\nfamily_name = \"Lato\"\n",
+        )
+        .unwrap();
+
+        let mut recorder = Recorder::default();
+        let installed =
+            update_project(&config, std::slice::from_ref(&library), &mut recorder).unwrap();
+        assert_eq!(installed, [dir.join("fonts/TypfontFixture-Regular.ttf")]);
+        assert_eq!(
+            recorder.0,
+            [
+                "Typfont Fixture 400 library",
+                "New Computer Modern 400 embedded",
+                "warning Lato",
+                "download TypfontFixture-Regular.ttf",
+                "copied TypfontFixture-Regular.ttf",
+            ]
+        );
+
+        let report = check_project(&config, &[library], &mut ()).unwrap();
+        assert_eq!(report.satisfied, 2);
+        assert!(report.fixable.is_empty());
+        assert_eq!(report.missing.len(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,82 @@
+//! Colored terminal output. Builds without the `color` feature print the same
+//! text uncolored, with a stand-in for the part of `colored` used here.
+
+#[cfg(feature = "color")]
+pub(crate) use colored::{ColoredString, Colorize};
+
+#[cfg(not(feature = "color"))]
+pub(crate) use plain::{ColoredString, Colorize};
+
+#[cfg(not(feature = "color"))]
+mod plain {
+    use std::fmt;
+    use std::ops::Deref;
+
+    /// Text that would have been colored
+    #[derive(Clone, Debug, Default, Eq, PartialEq)]
+    pub(crate) struct ColoredString(String);
+
+    impl Deref for ColoredString {
+        type Target = str;
+
+        fn deref(&self) -> &str {
+            &self.0
+        }
+    }
+
+    impl fmt::Display for ColoredString {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.pad(&self.0)
+        }
+    }
+
+    impl From<&str> for ColoredString {
+        fn from(text: &str) -> Self {
+            Self(text.to_string())
+        }
+    }
+
+    impl From<String> for ColoredString {
+        fn from(text: String) -> Self {
+            Self(text)
+        }
+    }
+
+    macro_rules! styles {
+        ($($style:ident),*) => {
+            /// The styles of `colored::Colorize`, all of which leave the text as is
+            pub(crate) trait Colorize: Sized {
+                fn normal(self) -> ColoredString;
+                $(
+                    fn $style(self) -> ColoredString {
+                        self.normal()
+                    }
+                )*
+            }
+        };
+    }
+
+    styles!(
+        red,
+        green,
+        yellow,
+        blue,
+        magenta,
+        cyan,
+        bright_green,
+        bold,
+        dimmed
+    );
+
+    impl Colorize for ColoredString {
+        fn normal(self) -> ColoredString {
+            self
+        }
+    }
+
+    impl Colorize for &str {
+        fn normal(self) -> ColoredString {
+            self.into()
+        }
+    }
+}
@@ -1,30 +1,324 @@
-use clap::{Args, Subcommand, ValueHint};
+use clap::{Args, Subcommand, ValueEnum, ValueHint};
 use std::path::PathBuf;
+use std::time::Duration;
+use typst::text::{FontStretch, FontStyle};
+
+use crate::cache;
+use crate::convert::DEFAULT_CONVERTER;
+use crate::lockfile::HashAlgo;
+use crate::parse_font_config::{RedistributionPolicy, font_stretch_serde};
+use crate::process_font::{FontClass, FontFeature};
+use crate::rate_limit::RateLimit;
+use crate::remote_font::DEFAULT_PREFIX_KIB;
+use crate::table::parse_size;
 
 #[derive(Subcommand, Debug)]
 pub(crate) enum Commands {
     /// Check font configuration
-    Check(FontCommand),
+    #[command(visible_alias = "c")]
+    Check(CheckCommand),
     /// Update font configuration
+    #[command(visible_alias = "u")]
     Update(UpdateCommand),
+    /// Print the project's font status as a single line, as `check --summary-only` does
+    #[command(visible_alias = "st")]
+    Status(StatusCommand),
     /// Show font library information
     CheckLib(CheckLibCommand),
+    /// Flag library files whose names disagree with their weight or style, share
+    /// a full name, or lack a typographic family, with the fix for each
+    LintLib(LintLibCommand),
+    /// Convert the `typst fonts --variants` listing into a font config or library index
+    ImportTypstFonts(ImportTypstFontsCommand),
+    /// Search the font library by family name, designer or foundry
+    Search(SearchCommand),
+    /// Show the variants and metadata of a font family in the library
+    Info(InfoCommand),
+    /// Summarize the font library by foundry
+    Stats(LibraryArgs),
+    /// Time library indexing, project resolution and update planning, printing JSON results
+    Bench(BenchCommand),
+    /// Inspect the per-user cache of GitHub library indexes
+    Cache(CacheCommand),
+    /// Maintain the shared font store of a workspace
+    Store(StoreCommand),
+    /// List the update runs recorded in the project journal
+    History(HistoryCommand),
+    /// Write an SPDX software bill of materials of the project's font files
+    Sbom(SbomCommand),
+    /// Explain which library file `update` installs for the required fonts of a family
+    Why(WhyCommand),
+    /// Require a font family in font_config.toml, creating it if missing; weights
+    /// are merged into an entry of the same family, style and stretch
+    Add(AddCommand),
+    /// Remove weights or whole entries of a font family from font_config.toml
+    Remove(RemoveCommand),
+    /// Infer the required font families from the `text` set rules and calls of the
+    /// project's `.typ` files and propose a font_config.toml, or merge them into it
+    Scan(ScanCommand),
+    /// Create a Typst project with a starter font config from a template and install its fonts
+    New(NewCommand),
+    /// Write a shields.io badge of the project's font status, e.g. "fonts | 12/12 ok"
+    Badge(BadgeCommand),
+    /// Write the project's fonts as a reproducible tar layer for container images
+    ExportLayer(ExportLayerCommand),
+    /// Move the fonts awaiting review in the quarantine of the font directory into
+    /// it, or list them
+    Promote(PromoteCommand),
+    /// Manage the typfont installation itself
+    #[command(name = "self")]
+    SelfManagement(SelfCommand),
+    /// Compare the metrics of two faces to judge whether one can substitute the other
+    Compare(CompareCommand),
+    /// Suggest available families similar to required fonts that no library source has
+    Suggest(SuggestCommand),
+    /// Report the fonts a compiled PDF embeds and flag those the project doesn't
+    /// require, or write the font status as an HTML page
+    Report(ReportCommand),
+    /// Extract the complete fonts embedded in a PDF into a library directory and index them
+    Extract(ExtractCommand),
+    /// Copy system fonts of the matching families into a library directory and index them
+    Adopt(AdoptCommand),
+    /// Print the required fonts of a project as a tree of the packs they come from
+    Tree(FontCommand),
+    /// Install the project fonts that no longer match font_config.lock again, from
+    /// the library files they were installed from
+    Repair(RepairCommand),
+    /// Check and update a sandbox project against a library of bundled fonts, to
+    /// see how typfont works or that it works on this machine
+    #[cfg(feature = "test-fixtures")]
+    Demo(DemoCommand),
 }
 
-#[derive(Args, Debug)]
+#[derive(Args, Clone, Debug)]
 pub(crate) struct FontCommand {
     /// Project root directory or path to font_config.toml
     #[arg(default_value = ".", value_name = "PROJECT_OR_CONFIG")]
     pub(crate) project_or_config: PathBuf,
 
     /// Source font library directory paths
-    /// For GitHub repositories, use the format "owner/repo"
+    /// For GitHub repositories, use the format "owner/repo", or
+    /// "owner/repo::path/to/index.toml" to read an index other than font_library.toml;
+    /// "owner/repo@v1.2.0" reads a branch, tag or commit instead of the default branch
     #[arg(short, long, num_args = 1.., value_name = "DIR")]
     pub(crate) library: Option<Vec<PathBuf>>,
 
     /// Whether source font libraries are GitHub repositories
     #[arg(short, long, default_value = "false")]
     pub(crate) github: bool,
+
+    /// Layout of the printed font report
+    #[arg(long, value_enum, default_value_t = OutputLayout::Tree)]
+    pub(crate) output: OutputLayout,
+
+    /// Column to sort the table layout by
+    #[arg(long, value_enum, default_value_t = SortKey::Family)]
+    pub(crate) sort_by: SortKey,
+
+    /// Ignore a library source, e.g. "github:owner/repo" or "local:/path/to/fonts";
+    /// may be repeated
+    #[arg(long, value_name = "SOURCE")]
+    pub(crate) disable_source: Vec<String>,
+
+    /// Prefix library fonts in reports with the source they come from
+    #[arg(long, default_value = "false")]
+    pub(crate) namespace_sources: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum OutputLayout {
+    /// Font sets grouped by family
+    Tree,
+    /// One aligned row per font with its status, source and size
+    Table,
+}
+
+/// How `check`, `update` and `check-lib` print their report
+#[derive(ValueEnum, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) enum ReportFormat {
+    /// Text for people to read
+    #[default]
+    Plain,
+    /// A single JSON document on standard output, for CI pipelines and editor
+    /// plugins; progress and warnings go to standard error
+    Json,
+    /// Like `json`, as a TOML document
+    Toml,
+}
+
+/// The files `check --emit` writes for build systems
+#[derive(ValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum EmitKind {
+    /// A dependency rule in the Makefile syntax, which ninja reads as a `depfile`
+    MakeDeps,
+}
+
+/// A file `check --emit` writes, given as `KIND=FILE`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Emit {
+    pub(crate) kind: EmitKind,
+    pub(crate) path: PathBuf,
+}
+
+fn parse_emit(value: &str) -> Result<Emit, String> {
+    let kinds = || {
+        EmitKind::value_variants()
+            .iter()
+            .filter_map(ValueEnum::to_possible_value)
+            .map(|kind| kind.get_name().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let (kind, path) = value
+        .split_once('=')
+        .filter(|(_, path)| !path.is_empty())
+        .ok_or_else(|| format!("expected KIND=FILE, with KIND one of: {}", kinds()))?;
+    let kind = EmitKind::from_str(kind, false)
+        .map_err(|_| format!("unknown kind {kind:?}, expected one of: {}", kinds()))?;
+    Ok(Emit {
+        kind,
+        path: PathBuf::from(path),
+    })
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum SortKey {
+    Family,
+    Style,
+    Weight,
+    Stretch,
+    Status,
+    Source,
+    Size,
+}
+
+/// What `check-lib --group-by` splits the library index by
+#[derive(ValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum IndexGroup {
+    License,
+    Foundry,
+    Family,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct CheckCommand {
+    #[command(flatten)]
+    pub(crate) font: FontCommand,
+
+    /// Warn when the GitHub library index used by the last update is this many days
+    /// older than the repository's current HEAD
+    #[arg(long, default_value = "30", value_name = "DAYS")]
+    pub(crate) stale_after: i64,
+
+    /// Only report missing fonts
+    #[arg(long, default_value = "false", conflicts_with = "only_redundant")]
+    pub(crate) only_missing: bool,
+
+    /// Only report redundant fonts
+    #[arg(long, default_value = "false")]
+    pub(crate) only_redundant: bool,
+
+    /// Only report fonts whose family name matches this glob (case-insensitive)
+    #[arg(long, value_name = "GLOB")]
+    pub(crate) family: Option<String>,
+
+    /// Print a single summary line, e.g. "3 missing, 2 fixable, 1 redundant"
+    #[arg(long, default_value = "false")]
+    pub(crate) summary_only: bool,
+
+    /// Keep running and report again whenever the font status changes
+    #[arg(short, long, default_value = "false")]
+    pub(crate) watch: bool,
+
+    /// Seconds between two checks in watch mode. Local libraries and the project
+    /// are watched for changes in between, unless `--poll` is given.
+    #[arg(long, default_value = "5", value_name = "SECONDS", requires = "watch")]
+    pub(crate) interval: u64,
+
+    /// Scan the libraries again at every check in watch mode instead of following
+    /// their changes through file system notifications, e.g. on network file systems
+    #[arg(long, default_value = "false", requires = "watch")]
+    pub(crate) poll: bool,
+
+    /// Raise a desktop notification when watch mode detects a change in font status
+    #[arg(long, default_value = "false", requires = "watch")]
+    pub(crate) notify: bool,
+
+    /// Serve the font status in watch mode on this port of 127.0.0.1: `GET /status`
+    /// as JSON and `GET /metrics` in the Prometheus format
+    #[arg(long, value_name = "PORT", requires = "watch")]
+    pub(crate) status_port: Option<u16>,
+
+    /// POST the font status as JSON to this URL when fonts are missing or redundant,
+    /// e.g. from a scheduled CI job
+    #[arg(long, value_name = "URL", conflicts_with = "watch")]
+    pub(crate) notify_webhook: Option<String>,
+
+    /// Check several projects against a single scan of the library, e.g. all projects
+    /// of a monorepo; replaces PROJECT_OR_CONFIG
+    #[arg(long, num_args = 1.., value_name = "CONFIG", conflicts_with = "watch")]
+    pub(crate) configs: Vec<PathBuf>,
+
+    /// What to do about required fonts whose OS/2 fsType forbids embedding them in a
+    /// PDF: `deny` fails the check
+    #[arg(long, value_enum, value_name = "POLICY", default_value_t = RedistributionPolicy::Warn)]
+    pub(crate) embedding_policy: RedistributionPolicy,
+
+    /// Warn when required fonts installed in the project have another version in
+    /// the system font directories, where local previews take them from; `faces`
+    /// lists every differing face with its files
+    #[arg(
+        long,
+        value_enum,
+        value_name = "DETAIL",
+        num_args = 0..=1,
+        default_missing_value = "families"
+    )]
+    pub(crate) system_versions: Option<VersionDetail>,
+
+    /// Check in full even if nothing changed since the project's fonts were last
+    /// found complete
+    #[arg(long, default_value = "false")]
+    pub(crate) force: bool,
+
+    /// Also fail when the project has redundant fonts, with exit code 7
+    #[arg(long, default_value = "false", conflicts_with = "watch")]
+    pub(crate) strict: bool,
+
+    /// Format of the report: the font sets as a JSON or TOML document instead of text
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = ReportFormat::Plain,
+        conflicts_with_all = ["watch", "configs", "summary_only"]
+    )]
+    pub(crate) format: ReportFormat,
+
+    /// Write a file for build systems when the check is done: `make-deps=FILE` writes
+    /// a Makefile/ninja dependency rule of the compiled document on the resolved font
+    /// files, so that it is rebuilt when one of them changes
+    #[arg(
+        long,
+        value_name = "KIND=FILE",
+        value_parser = parse_emit,
+        conflicts_with_all = ["watch", "configs", "summary_only"]
+    )]
+    pub(crate) emit: Vec<Emit>,
+
+    /// The compiled document the dependency rule of `--emit make-deps=FILE` is for
+    /// [default: main.pdf in the project directory]
+    #[arg(long, value_name = "FILE", requires = "emit")]
+    pub(crate) deps_target: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct StatusCommand {
+    #[command(flatten)]
+    pub(crate) font: FontCommand,
+
+    /// Also fail when the project has redundant fonts, with exit code 7
+    #[arg(long, default_value = "false")]
+    pub(crate) strict: bool,
 }
 
 #[derive(Args, Debug)]
@@ -35,6 +329,97 @@ pub(crate) struct UpdateCommand {
     /// Print the planned font updates without copying or downloading files
     #[arg(long, default_value = "false")]
     pub(crate) dry_run: bool,
+
+    /// With --dry-run, print the planned changes as a diff of the font directory:
+    /// `+` added, `-` no longer needed, `~` replaced files
+    #[arg(long, default_value = "false", requires = "dry_run")]
+    pub(crate) diff: bool,
+
+    /// Convert library fonts between OTF and TTF when only the other format satisfies
+    /// the project's `font_format` policy
+    #[arg(long, default_value = "false")]
+    pub(crate) convert: bool,
+
+    /// Remove bytecode hinting, DSIG and other tables Typst doesn't use from installed fonts
+    #[arg(long, default_value = "false")]
+    pub(crate) strip: bool,
+
+    /// Converter command line for --convert; {input} and {output} are replaced by the file paths
+    #[arg(long, value_name = "COMMAND", default_value = DEFAULT_CONVERTER, requires = "convert")]
+    pub(crate) converter: String,
+
+    /// Maximum download bandwidth, e.g. 2MiB/s or 500K
+    #[arg(long, value_name = "RATE")]
+    pub(crate) limit_rate: Option<RateLimit>,
+
+    /// Require the fonts to resolve to the files recorded in font_config.lock, with
+    /// the recorded hashes, and leave the lockfile unchanged
+    #[arg(long, default_value = "false")]
+    pub(crate) locked: bool,
+
+    /// Like --locked, and additionally forbid network access: GitHub library indexes
+    /// are read from the cache, and files that would have to be downloaded are an error
+    #[arg(long, default_value = "false")]
+    pub(crate) frozen: bool,
+
+    /// Install the fonts into `.incoming/` in the font directory for review, as
+    /// `quarantine = true` in font_config.toml does; see `promote`
+    #[arg(long, default_value = "false")]
+    pub(crate) quarantine: bool,
+
+    /// Also record the BLAKE3 of the installed files in font_config.lock, which
+    /// `repair`, `promote` and `store verify` check faster, and verify it with
+    /// --locked where it is recorded
+    #[arg(long, value_enum, value_name = "ALGO", default_value_t = HashAlgo::Sha256)]
+    pub(crate) hash_algo: HashAlgo,
+
+    /// Commit the changed font files, lockfile and journal on a new branch, push it
+    /// to origin and open a GitHub pull request against the current branch, with
+    /// the token of --token, GITHUB_TOKEN or GH_TOKEN
+    #[arg(long, default_value = "false", conflicts_with_all = ["dry_run", "quarantine"])]
+    pub(crate) pr: bool,
+
+    /// Format of the report: the font sets before the update and the installed
+    /// files as a JSON or TOML document instead of text
+    #[arg(long, value_enum, default_value_t = ReportFormat::Plain)]
+    pub(crate) format: ReportFormat,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct RepairCommand {
+    #[command(flatten)]
+    pub(crate) font: FontCommand,
+
+    /// Only report the damaged files
+    #[arg(long, default_value = "false")]
+    pub(crate) dry_run: bool,
+
+    /// Converter command line for files the project's `font_format` policy
+    /// required converting; {input} and {output} are replaced by the file paths
+    #[arg(long, value_name = "COMMAND", default_value = DEFAULT_CONVERTER)]
+    pub(crate) converter: String,
+
+    /// Maximum download bandwidth, e.g. 2MiB/s or 500K
+    #[arg(long, value_name = "RATE")]
+    pub(crate) limit_rate: Option<RateLimit>,
+
+    /// Verify the files with BLAKE3 where font_config.lock records it (see
+    /// `update --hash-algo`), and with SHA-256 otherwise
+    #[arg(long, value_enum, value_name = "ALGO", default_value_t = HashAlgo::Sha256)]
+    pub(crate) hash_algo: HashAlgo,
+}
+
+#[cfg(feature = "test-fixtures")]
+#[derive(Args, Debug)]
+pub(crate) struct DemoCommand {
+    /// Create the sandbox in this new or empty directory and keep it, instead of
+    /// a temporary directory
+    #[arg(long, value_name = "DIR")]
+    pub(crate) dir: Option<PathBuf>,
+
+    /// Keep the temporary sandbox after the demo
+    #[arg(long, default_value = "false", conflicts_with = "dir")]
+    pub(crate) keep: bool,
 }
 
 #[derive(Args, Debug)]
@@ -50,9 +435,172 @@ pub(crate) struct CheckLibCommand {
     /// Output path for the results (optional, can be specified without a value)
     #[arg(short, long, value_name = "OUTPUT", num_args = 0..=1, value_hint = ValueHint::FilePath)]
     pub(crate) output: Option<Option<PathBuf>>,
+
+    /// Write one index per license, foundry or family, e.g. font_library.ofl.toml
+    /// and font_library.proprietary.toml, instead of a single font_library.toml
+    #[arg(long, value_enum, value_name = "KEY", requires = "output")]
+    pub(crate) group_by: Option<IndexGroup>,
+
+    /// What to do about fonts that may not be redistributed when the index is
+    /// written into a directory tracked by git
+    #[arg(long, value_enum, value_name = "POLICY", default_value_t = RedistributionPolicy::Warn)]
+    pub(crate) redistribution: RedistributionPolicy,
+
+    /// Verify the existing font_library.toml of each library against its font files
+    /// and fail if an entry is wrong or a font file is not indexed
+    #[arg(long, default_value = "false", conflicts_with = "output")]
+    pub(crate) validate: bool,
+
+    /// Format of the report: the library fonts as a JSON or TOML document instead of text
+    #[arg(long, value_enum, default_value_t = ReportFormat::Plain, conflicts_with = "validate")]
+    pub(crate) format: ReportFormat,
+
+    /// Kilobytes requested first from each font file when indexing or validating a
+    /// GitHub library. Tables beyond them are requested separately
+    #[arg(long, value_name = "KIB", default_value_t = DEFAULT_PREFIX_KIB)]
+    pub(crate) range_prefix: usize,
+
+    /// Convert the Type 1 fonts (.pfb/.pfa) of local libraries to OpenType files next to them
+    #[arg(long, default_value = "false", conflicts_with = "github")]
+    pub(crate) convert_legacy: bool,
+
+    /// Converter command line for --convert-legacy; {input} and {output} are replaced by the file paths
+    #[arg(long, value_name = "COMMAND", default_value = DEFAULT_CONVERTER, requires = "convert_legacy")]
+    pub(crate) converter: String,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct ImportTypstFontsCommand {
+    /// File containing the output of `typst fonts --variants`, or "-" for standard input.
+    /// Piped standard input is used automatically; otherwise Typst is run directly
+    #[arg(short, long, value_name = "FILE")]
+    pub(crate) input: Option<PathBuf>,
+
+    /// Typst executable to run when no listing is provided
+    #[arg(long, default_value = "typst", value_name = "TYPST")]
+    pub(crate) typst: PathBuf,
+
+    /// Kind of file to generate
+    #[arg(short, long, value_enum, default_value_t = ImportKind::Config)]
+    pub(crate) kind: ImportKind,
+
+    /// Font library directories searched for the files of the listed fonts (library kind only)
+    #[arg(short, long, num_args = 1.., value_name = "DIR")]
+    pub(crate) library: Option<Vec<PathBuf>>,
+
+    /// Output file; the result is printed to standard output if omitted
+    #[arg(short, long, value_name = "OUTPUT", value_hint = ValueHint::FilePath)]
+    pub(crate) output: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct LintLibCommand {
+    #[command(flatten)]
+    pub(crate) library: LibraryArgs,
+
+    /// Also write the findings as a Markdown report for the library maintainers
+    #[arg(long, value_name = "FILE")]
+    pub(crate) report: Option<PathBuf>,
+
+    /// Kilobytes requested first from each font file of a GitHub library. Tables
+    /// beyond them are requested separately
+    #[arg(long, value_name = "KIB", default_value_t = DEFAULT_PREFIX_KIB)]
+    pub(crate) range_prefix: usize,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct LibraryArgs {
+    /// Source font library directory paths
+    /// For GitHub repositories, use the format "owner/repo", or
+    /// "owner/repo::path/to/index.toml" to read an index other than font_library.toml;
+    /// "owner/repo@v1.2.0" reads a branch, tag or commit instead of the default branch
+    #[arg(short, long, num_args = 1.., value_name = "DIR")]
+    pub(crate) library: Option<Vec<PathBuf>>,
+
+    /// Whether source font libraries are GitHub repositories
+    #[arg(short, long, default_value = "false")]
+    pub(crate) github: bool,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct SearchCommand {
+    /// Family name glob (case-insensitive)
+    #[arg(default_value = "*", value_name = "FAMILY")]
+    pub(crate) query: String,
+
+    /// Only list fonts whose designer, manufacturer or vendor ID matches this glob
+    /// (case-insensitive), e.g. "Adobe*"
+    #[arg(long, value_name = "GLOB")]
+    pub(crate) foundry: Option<String>,
+
+    /// Only list fonts with this feature; may be repeated
+    #[arg(long, value_enum, value_name = "FEATURE")]
+    pub(crate) feature: Vec<FontFeature>,
+
+    /// Only list fonts of this typographic class, as declared by their OS/2 table
+    #[arg(long, value_enum, value_name = "CLASS")]
+    pub(crate) class: Option<FontClass>,
+
+    /// Only list monospaced fonts; the same as `--feature monospace`
+    #[arg(long)]
+    pub(crate) monospace: bool,
+
+    /// Only list families with at least this many weights among the matches
+    #[arg(long, value_name = "N")]
+    pub(crate) min_weights: Option<usize>,
+
+    #[command(flatten)]
+    pub(crate) library: LibraryArgs,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct InfoCommand {
+    /// Family name (case-insensitive)
+    #[arg(value_name = "FAMILY")]
+    pub(crate) family: String,
+
+    #[command(flatten)]
+    pub(crate) library: LibraryArgs,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct BenchCommand {
+    #[command(flatten)]
+    pub(crate) font: FontCommand,
+
+    /// Number of iterations; the first one runs with cold caches
+    #[arg(
+        short = 'n',
+        long,
+        default_value = "5",
+        value_name = "N",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    pub(crate) iterations: usize,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum ImportKind {
+    /// A font_config.toml skeleton requiring every listed font
+    Config,
+    /// A font_library.toml index of the listed fonts found in the library directories
+    Library,
 }
 
 impl FontCommand {
+    /// The arguments of a report on `project_or_config` with the default options
+    pub(crate) fn project(project_or_config: PathBuf) -> Self {
+        FontCommand {
+            project_or_config,
+            library: None,
+            github: false,
+            output: OutputLayout::Tree,
+            sort_by: SortKey::Family,
+            disable_source: Vec::new(),
+            namespace_sources: false,
+        }
+    }
+
     /// Validate the configuration
     pub(crate) fn validate(&self) -> Result<(), String> {
         if self.github && self.library.is_none() {
@@ -60,13 +608,422 @@ impl FontCommand {
                 "When '--github' is set to true, '--library' must also be provided.".to_string(),
             );
         }
+        if self.github {
+            for source in self.library.iter().flatten() {
+                crate::github::GitHubSource::parse(source)?;
+            }
+        }
         Ok(())
     }
 }
 
+#[derive(Args, Debug)]
+pub(crate) struct HistoryCommand {
+    /// Project root directory or path to font_config.toml
+    #[arg(default_value = ".", value_name = "PROJECT_OR_CONFIG")]
+    pub(crate) project_or_config: PathBuf,
+
+    #[command(subcommand)]
+    pub(crate) action: Option<HistoryAction>,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum HistoryAction {
+    /// Print every action of one run
+    Show {
+        /// Run number, as listed by `history`
+        id: u64,
+    },
+    /// Return the project fonts to the state after one run: remove the files later
+    /// runs installed, and install the files of the run again from the library
+    /// sources the journal records, with their recorded hashes
+    Rollback {
+        /// Run number, as listed by `history`
+        id: u64,
+
+        #[command(flatten)]
+        library: LibraryArgs,
+
+        /// Only list the files that would be removed or installed again
+        #[arg(long, default_value = "false")]
+        dry_run: bool,
+
+        /// Converter command line for files the project's `font_format` policy
+        /// required converting; {input} and {output} are replaced by the file paths
+        #[arg(long, value_name = "COMMAND", default_value = DEFAULT_CONVERTER)]
+        converter: String,
+    },
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct SbomCommand {
+    /// Project root directory or path to font_config.toml
+    #[arg(default_value = ".", value_name = "PROJECT_OR_CONFIG")]
+    pub(crate) project_or_config: PathBuf,
+
+    /// File to write the SPDX JSON document to instead of standard output
+    #[arg(short, long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub(crate) output: Option<PathBuf>,
+
+    /// Algorithm of the checksums of the font files
+    #[arg(long, value_enum, value_name = "ALGO", default_value_t = HashAlgo::Sha256)]
+    pub(crate) hash_algo: HashAlgo,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct WhyCommand {
+    /// Family name of the required fonts (case-insensitive)
+    pub(crate) family: String,
+
+    #[command(flatten)]
+    pub(crate) font: FontCommand,
+
+    /// Only explain the variant of this style
+    #[arg(long, value_enum)]
+    pub(crate) style: Option<StyleArg>,
+
+    /// Only explain the variant of this weight
+    #[arg(long)]
+    pub(crate) weight: Option<u16>,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct NewCommand {
+    /// Directory of the new project; created if it doesn't exist
+    pub(crate) dir: PathBuf,
+
+    /// Built-in template (article, thesis or cjk) or the URL of a font_config.toml
+    #[arg(short, long, default_value = "article", value_name = "NAME_OR_URL")]
+    pub(crate) template: String,
+
+    /// Source font libraries to install the template's fonts from
+    #[arg(short, long, num_args = 1.., value_name = "DIR")]
+    pub(crate) library: Option<Vec<PathBuf>>,
+
+    /// Whether source font libraries are GitHub repositories
+    #[arg(short, long, default_value = "false")]
+    pub(crate) github: bool,
+
+    /// Also write a GitHub Actions workflow that installs the fonts and compiles main.typ
+    #[arg(long, default_value = "false")]
+    pub(crate) ci: bool,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct BadgeCommand {
+    /// Project root directory or path to font_config.toml
+    #[arg(default_value = ".", value_name = "PROJECT_OR_CONFIG")]
+    pub(crate) project_or_config: PathBuf,
+
+    /// Badge format: a shields.io endpoint JSON, or a standalone SVG image
+    #[arg(short, long, value_enum, default_value_t = BadgeFormat::Json)]
+    pub(crate) format: BadgeFormat,
+
+    /// Text on the left side of the badge
+    #[arg(long, default_value = "fonts")]
+    pub(crate) label: String,
+
+    /// File to write the badge to instead of standard output
+    #[arg(short, long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub(crate) output: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct AddCommand {
+    /// Family name of the font, e.g. "Noto Sans"
+    #[arg(value_name = "FAMILY")]
+    pub(crate) family: String,
+
+    /// Project root directory or path to font_config.toml
+    #[arg(default_value = ".", value_name = "PROJECT_OR_CONFIG")]
+    pub(crate) project_or_config: PathBuf,
+
+    /// Required weights, e.g. `400,700`
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "400",
+        value_parser = clap::value_parser!(u16).range(100..=900)
+    )]
+    pub(crate) weight: Vec<u16>,
+
+    /// Required style
+    #[arg(long, value_enum, default_value_t = StyleArg::Normal)]
+    pub(crate) style: StyleArg,
+
+    /// Required stretch, as a name like `condensed` or a percentage like `87.5%`
+    #[arg(long, default_value = "normal", value_parser = font_stretch_serde::parse)]
+    pub(crate) stretch: FontStretch,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct ScanCommand {
+    /// Project root directory or path to font_config.toml
+    #[arg(default_value = ".", value_name = "PROJECT_OR_CONFIG")]
+    pub(crate) project_or_config: PathBuf,
+
+    /// Merge the discovered fonts into font_config.toml, creating it if missing,
+    /// instead of printing the proposed config
+    #[arg(long)]
+    pub(crate) write: bool,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct PromoteCommand {
+    /// Project root directory or path to font_config.toml
+    #[arg(default_value = ".", value_name = "PROJECT_OR_CONFIG")]
+    pub(crate) project_or_config: PathBuf,
+
+    /// Approved files awaiting review, by file name
+    #[arg(long, num_args = 1.., value_name = "FILE", group = "approved")]
+    pub(crate) file: Vec<String>,
+
+    /// Approve all files awaiting review
+    #[arg(long, default_value = "false", group = "approved")]
+    pub(crate) all: bool,
+
+    /// Verify the approved files with BLAKE3 where their records hold it (see
+    /// `update --hash-algo`), and with SHA-256 otherwise
+    #[arg(long, value_enum, value_name = "ALGO", default_value_t = HashAlgo::Sha256)]
+    pub(crate) hash_algo: HashAlgo,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct RemoveCommand {
+    /// Family name of the font, ignoring ASCII case
+    #[arg(value_name = "FAMILY")]
+    pub(crate) family: String,
+
+    /// Project root directory or path to font_config.toml
+    #[arg(default_value = ".", value_name = "PROJECT_OR_CONFIG")]
+    pub(crate) project_or_config: PathBuf,
+
+    /// Weights to remove, e.g. `700`; all weights of the family by default
+    #[arg(long, value_delimiter = ',', value_parser = clap::value_parser!(u16).range(100..=900))]
+    pub(crate) weight: Vec<u16>,
+
+    /// Only remove the entries of this style
+    #[arg(long, value_enum)]
+    pub(crate) style: Option<StyleArg>,
+
+    /// Delete the project font files that no required font uses any more
+    #[arg(long, default_value = "false")]
+    pub(crate) purge: bool,
+}
+
+#[derive(Args, Debug)]
+#[command(group = clap::ArgGroup::new("output").required(true).multiple(true))]
+pub(crate) struct ExportLayerCommand {
+    /// Project root directory or path to font_config.toml
+    #[arg(default_value = ".", value_name = "PROJECT_OR_CONFIG")]
+    pub(crate) project_or_config: PathBuf,
+
+    /// Directory to write the layer to as a single-layer OCI image layout
+    #[arg(long, value_name = "DIR", value_hint = ValueHint::DirPath, group = "output")]
+    pub(crate) oci: Option<PathBuf>,
+
+    /// File to write the layer to as a plain tar, e.g. to `ADD` in a Dockerfile
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath, group = "output")]
+    pub(crate) tar: Option<PathBuf>,
+
+    /// Directory of the fonts in the image
+    #[arg(long, default_value = "/usr/share/fonts/typfont", value_name = "DIR")]
+    pub(crate) prefix: String,
+
+    /// Tag of the image in the OCI layout
+    #[arg(long, default_value = "latest", requires = "oci")]
+    pub(crate) tag: String,
+}
+
+/// How `check --system-versions` reports differing versions
+#[derive(ValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum VersionDetail {
+    /// One line per family with the versions in the project and on the system
+    Families,
+    /// One line per face with its files
+    Faces,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum BadgeFormat {
+    Json,
+    Svg,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct CompareCommand {
+    /// Font file, or family name in the library (case-insensitive)
+    #[arg(value_name = "FONT_A")]
+    pub(crate) a: String,
+
+    /// Font file, or family name in the library (case-insensitive)
+    #[arg(value_name = "FONT_B")]
+    pub(crate) b: String,
+
+    #[command(flatten)]
+    pub(crate) library: LibraryArgs,
+
+    /// Style of the variants compared for family names
+    #[arg(long, value_enum, default_value_t = StyleArg::Normal)]
+    pub(crate) style: StyleArg,
+
+    /// Weight of the variants compared for family names; the closest one is used
+    #[arg(long, default_value_t = 400)]
+    pub(crate) weight: u16,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct ReportCommand {
+    /// Project root directory or path to font_config.toml
+    #[arg(default_value = ".", value_name = "PROJECT_OR_CONFIG")]
+    pub(crate) project_or_config: PathBuf,
+
+    /// PDF compiled from the project
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath, required_unless_present = "html")]
+    pub(crate) pdf: Option<PathBuf>,
+
+    /// Write the project's font status as a standalone HTML page to this file
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub(crate) html: Option<PathBuf>,
+
+    /// Libraries the HTML report looks up missing fonts in
+    #[command(flatten)]
+    pub(crate) library: LibraryArgs,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct ExtractCommand {
+    /// PDF to extract the fonts of
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub(crate) pdf: PathBuf,
+
+    /// Library directory to extract the fonts into; its font_library.toml is regenerated
+    #[arg(long, value_name = "DIR", value_hint = ValueHint::DirPath)]
+    pub(crate) to: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct AdoptCommand {
+    /// Family name pattern, e.g. "Noto Sans*"; `*` and `?` are wildcards, case is ignored
+    #[arg(value_name = "FAMILY")]
+    pub(crate) family: String,
+
+    /// Library directory to copy the fonts into; its font_library.toml is regenerated
+    #[arg(long, value_name = "DIR", value_hint = ValueHint::DirPath)]
+    pub(crate) to: PathBuf,
+
+    /// Only list the files that would be copied
+    #[arg(long, default_value = "false")]
+    pub(crate) dry_run: bool,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct SuggestCommand {
+    #[command(flatten)]
+    pub(crate) font: FontCommand,
+
+    /// Maximum number of families suggested for each missing family
+    #[arg(long, default_value_t = 5)]
+    pub(crate) limit: usize,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum StyleArg {
+    Normal,
+    Italic,
+    Oblique,
+}
+
+impl StyleArg {
+    pub(crate) fn font_style(self) -> FontStyle {
+        match self {
+            StyleArg::Normal => FontStyle::Normal,
+            StyleArg::Italic => FontStyle::Italic,
+            StyleArg::Oblique => FontStyle::Oblique,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct CacheCommand {
+    #[command(subcommand)]
+    pub(crate) action: CacheAction,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum CacheAction {
+    /// List the cached library indexes
+    Status,
+    /// Show the disk usage of the cache per library source
+    Stats,
+    /// Remove every cached file: library indexes and scanned font files
+    Clear,
+    /// Remove cached files, least recently used first
+    #[command(group = clap::ArgGroup::new("limit").required(true).multiple(true))]
+    Gc {
+        /// Remove the least recently used files until the cache fits, e.g. `2GiB`
+        #[arg(long, value_parser = parse_size, group = "limit")]
+        max_size: Option<u64>,
+        /// Remove files not used for this long, e.g. `90d`, `2w` or `12h`
+        #[arg(long, value_parser = cache::parse_age, group = "limit")]
+        max_age: Option<Duration>,
+        /// Only list the files that would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct StoreCommand {
+    #[command(subcommand)]
+    pub(crate) action: StoreAction,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum StoreAction {
+    /// Remove stored files that no project lockfile in the workspace refers to
+    Gc {
+        /// Directory in the workspace, i.e. at or below its font_workspace.toml
+        #[arg(long, value_name = "DIR", num_args = 0..=1, default_value = ".", default_missing_value = ".")]
+        workspace: PathBuf,
+        /// Only list the stored files that would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Check that every stored file still has the content hash it is stored under
+    Verify {
+        /// Directory in the workspace, i.e. at or below its font_workspace.toml
+        #[arg(long, value_name = "DIR", num_args = 0..=1, default_value = ".", default_missing_value = ".")]
+        workspace: PathBuf,
+        /// Check with BLAKE3 where the project lockfiles record it (see
+        /// `update --hash-algo`), and with SHA-256 otherwise
+        #[arg(long, value_enum, value_name = "ALGO", default_value_t = HashAlgo::Sha256)]
+        hash_algo: HashAlgo,
+    },
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct SelfCommand {
+    #[command(subcommand)]
+    pub(crate) action: SelfAction,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum SelfAction {
+    /// Replace this executable with the binary of the latest GitHub release,
+    /// after verifying its checksum
+    Update {
+        /// Only report whether a newer release is available
+        #[arg(long)]
+        check: bool,
+    },
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Commands;
+    use super::{CacheAction, Commands, Emit, EmitKind, HistoryAction, SelfAction, StoreAction};
+    use crate::parse_font_config::RedistributionPolicy;
+    use crate::process_font::FontFeature;
     use clap::Parser;
     use std::path::PathBuf;
 
@@ -76,6 +1033,33 @@ mod tests {
         command: Commands,
     }
 
+    #[test]
+    fn new_defaults_to_the_article_template() {
+        let cli = TestCli::parse_from(["typfont", "new", "paper", "--ci"]);
+
+        match cli.command {
+            Commands::New(args) => {
+                assert_eq!(args.dir, PathBuf::from("paper"));
+                assert_eq!(args.template, "article");
+                assert!(args.ci);
+                assert_eq!(args.library, None);
+            }
+            _ => panic!("expected new command"),
+        }
+    }
+
+    #[test]
+    fn self_update_accepts_check() {
+        let cli = TestCli::parse_from(["typfont", "self", "update", "--check"]);
+
+        match cli.command {
+            Commands::SelfManagement(args) => {
+                assert!(matches!(args.action, SelfAction::Update { check: true }));
+            }
+            _ => panic!("expected self command"),
+        }
+    }
+
     #[test]
     fn update_accepts_dry_run() {
         let cli = TestCli::parse_from([
@@ -98,6 +1082,294 @@ mod tests {
         }
     }
 
+    #[test]
+    fn check_accepts_stale_after() {
+        let cli = TestCli::parse_from(["typfont", "check", "--stale-after", "7"]);
+
+        match cli.command {
+            Commands::Check(args) => {
+                assert_eq!(args.stale_after, 7);
+                assert_eq!(args.font.project_or_config, PathBuf::from("."));
+            }
+            _ => panic!("expected check command"),
+        }
+    }
+
+    #[test]
+    fn check_embedding_policy_defaults_to_warn() {
+        let cli = TestCli::parse_from(["typfont", "check"]);
+        match cli.command {
+            Commands::Check(args) => {
+                assert_eq!(args.embedding_policy, RedistributionPolicy::Warn)
+            }
+            _ => panic!("expected check command"),
+        }
+        let cli = TestCli::parse_from(["typfont", "check", "--embedding-policy", "deny"]);
+        match cli.command {
+            Commands::Check(args) => {
+                assert_eq!(args.embedding_policy, RedistributionPolicy::Deny)
+            }
+            _ => panic!("expected check command"),
+        }
+    }
+
+    #[test]
+    fn repair_takes_font_arguments() {
+        let cli = TestCli::parse_from(["typfont", "repair", "paper", "-l", "lib", "--dry-run"]);
+        match cli.command {
+            Commands::Repair(args) => {
+                assert!(args.dry_run);
+                assert_eq!(args.font.project_or_config, PathBuf::from("paper"));
+                assert_eq!(args.font.library, Some(vec![PathBuf::from("lib")]));
+            }
+            _ => panic!("expected repair command"),
+        }
+    }
+
+    #[test]
+    fn history_show_takes_a_run_id() {
+        let cli = TestCli::parse_from(["typfont", "history", "show", "3"]);
+        match cli.command {
+            Commands::History(args) => {
+                assert_eq!(args.project_or_config, PathBuf::from("."));
+                assert!(matches!(args.action, Some(HistoryAction::Show { id: 3 })));
+            }
+            _ => panic!("expected history command"),
+        }
+
+        let cli = TestCli::parse_from(["typfont", "history", "thesis"]);
+        match cli.command {
+            Commands::History(args) => {
+                assert_eq!(args.project_or_config, PathBuf::from("thesis"));
+                assert!(args.action.is_none());
+            }
+            _ => panic!("expected history command"),
+        }
+    }
+
+    #[test]
+    fn why_takes_the_family_before_the_project() {
+        let cli = TestCli::parse_from([
+            "typfont",
+            "why",
+            "Noto Sans CJK SC",
+            "thesis",
+            "--weight",
+            "700",
+        ]);
+        match cli.command {
+            Commands::Why(args) => {
+                assert_eq!(args.family, "Noto Sans CJK SC");
+                assert_eq!(args.font.project_or_config, PathBuf::from("thesis"));
+                assert_eq!(args.weight, Some(700));
+            }
+            _ => panic!("expected why command"),
+        }
+    }
+
+    #[test]
+    fn cache_gc_requires_a_limit() {
+        assert!(TestCli::try_parse_from(["typfont", "cache", "gc"]).is_err());
+        let cli = TestCli::parse_from(["typfont", "cache", "gc", "--max-size", "2GiB"]);
+        match cli.command {
+            Commands::Cache(args) => assert!(matches!(
+                args.action,
+                CacheAction::Gc {
+                    max_size: Some(2_147_483_648),
+                    max_age: None,
+                    dry_run: false
+                }
+            )),
+            _ => panic!("expected cache command"),
+        }
+    }
+
+    #[test]
+    fn check_emit_takes_a_kind_and_a_file() {
+        let cli = TestCli::parse_from(["typfont", "check", "--emit", "make-deps=out/fonts.d"]);
+        match cli.command {
+            Commands::Check(args) => assert_eq!(
+                args.emit,
+                [Emit {
+                    kind: EmitKind::MakeDeps,
+                    path: PathBuf::from("out/fonts.d")
+                }]
+            ),
+            _ => panic!("expected check command"),
+        }
+        for value in ["json=deps.json", "make-deps", "make-deps="] {
+            let error = TestCli::try_parse_from(["typfont", "check", "--emit", value]).unwrap_err();
+            assert!(error.to_string().contains("make-deps"), "{error}");
+        }
+    }
+
+    #[test]
+    fn store_gc_workspace_defaults_to_the_current_directory() {
+        for (args, dir) in [
+            (vec!["--workspace"], "."),
+            (vec!["--workspace", "docs", "--dry-run"], "docs"),
+        ] {
+            let cli = TestCli::parse_from(["typfont", "store", "gc"].into_iter().chain(args));
+            match cli.command {
+                Commands::Store(args) => match args.action {
+                    StoreAction::Gc { workspace, .. } => assert_eq!(workspace, PathBuf::from(dir)),
+                    StoreAction::Verify { .. } => panic!("expected store gc"),
+                },
+                _ => panic!("expected store command"),
+            }
+        }
+    }
+
+    #[test]
+    fn import_typst_fonts_defaults_to_config() {
+        let cli = TestCli::parse_from(["typfont", "import-typst-fonts", "-i", "-"]);
+
+        match cli.command {
+            Commands::ImportTypstFonts(args) => {
+                assert_eq!(args.kind, super::ImportKind::Config);
+                assert_eq!(args.input, Some(PathBuf::from("-")));
+                assert_eq!(args.typst, PathBuf::from("typst"));
+            }
+            _ => panic!("expected import-typst-fonts command"),
+        }
+    }
+
+    #[test]
+    fn check_accepts_table_output() {
+        let cli = TestCli::parse_from([
+            "typfont",
+            "check",
+            "--output",
+            "table",
+            "--sort-by",
+            "status",
+        ]);
+
+        match cli.command {
+            Commands::Check(args) => {
+                assert_eq!(args.font.output, super::OutputLayout::Table);
+                assert_eq!(args.font.sort_by, super::SortKey::Status);
+            }
+            _ => panic!("expected check command"),
+        }
+    }
+
+    #[test]
+    fn check_filters_are_exclusive() {
+        assert!(
+            TestCli::try_parse_from(["typfont", "check", "--only-missing", "--only-redundant"])
+                .is_err()
+        );
+
+        let cli = TestCli::parse_from(["typfont", "check", "--only-missing", "--family", "Noto*"]);
+        match cli.command {
+            Commands::Check(args) => {
+                assert!(args.only_missing);
+                assert_eq!(args.family.as_deref(), Some("Noto*"));
+            }
+            _ => panic!("expected check command"),
+        }
+    }
+
+    #[test]
+    fn check_notify_requires_watch() {
+        assert!(TestCli::try_parse_from(["typfont", "check", "--notify"]).is_err());
+
+        let cli =
+            TestCli::parse_from(["typfont", "check", "--watch", "--notify", "--summary-only"]);
+        match cli.command {
+            Commands::Check(args) => {
+                assert!(args.watch && args.notify && args.summary_only);
+                assert_eq!(args.interval, 5);
+            }
+            _ => panic!("expected check command"),
+        }
+    }
+
+    #[test]
+    fn check_accepts_several_configs() {
+        let cli = TestCli::parse_from([
+            "typfont",
+            "check",
+            "--configs",
+            "a/font_config.toml",
+            "b",
+            "-l",
+            "fonts",
+        ]);
+        match cli.command {
+            Commands::Check(args) => {
+                assert_eq!(
+                    args.configs,
+                    [PathBuf::from("a/font_config.toml"), PathBuf::from("b")]
+                );
+                assert!(args.font.library.is_some());
+            }
+            _ => panic!("expected check command"),
+        }
+        assert!(
+            TestCli::try_parse_from(["typfont", "check", "--watch", "--configs", "a"]).is_err()
+        );
+    }
+
+    #[test]
+    fn search_accepts_foundry() {
+        let cli = TestCli::parse_from([
+            "typfont",
+            "search",
+            "--foundry",
+            "Adobe*",
+            "--feature",
+            "math",
+            "-l",
+            "fonts",
+        ]);
+
+        match cli.command {
+            Commands::Search(args) => {
+                assert_eq!(args.query, "*");
+                assert_eq!(args.foundry.as_deref(), Some("Adobe*"));
+                assert_eq!(args.feature, vec![FontFeature::Math]);
+                assert_eq!(args.library.library, Some(vec![PathBuf::from("fonts")]));
+            }
+            _ => panic!("expected search command"),
+        }
+    }
+
+    #[test]
+    fn update_accepts_disabled_sources() {
+        let cli = TestCli::parse_from([
+            "typfont",
+            "update",
+            "-g",
+            "-l",
+            "owner/fonts",
+            "org/print",
+            "--disable-source",
+            "github:org/print",
+            "--namespace-sources",
+        ]);
+
+        match cli.command {
+            Commands::Update(args) => {
+                assert_eq!(args.font.disable_source, ["github:org/print"]);
+                assert!(args.font.namespace_sources);
+            }
+            _ => panic!("expected update command"),
+        }
+    }
+
+    #[test]
+    fn bench_requires_an_iteration() {
+        assert!(TestCli::try_parse_from(["typfont", "bench", "-n", "0"]).is_err());
+
+        let cli = TestCli::parse_from(["typfont", "bench", "-n", "3", "-l", "fonts"]);
+        match cli.command {
+            Commands::Bench(args) => assert_eq!(args.iterations, 3),
+            _ => panic!("expected bench command"),
+        }
+    }
+
     #[test]
     fn check_does_not_accept_dry_run() {
         assert!(TestCli::try_parse_from(["typfont", "check", "--dry-run"]).is_err());
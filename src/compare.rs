@@ -0,0 +1,255 @@
+//! The `compare` subcommand: the vertical and horizontal metrics of two faces
+//! side by side, to judge whether one is a metric-compatible substitute for the
+//! other (e.g. TeX Gyre Heros for Helvetica) before adding a substitution rule.
+
+use crate::color::{ColoredString, Colorize};
+use std::fs;
+use std::path::Path;
+use typst::text::FontStyle;
+
+use crate::DiscoveredFont;
+use crate::font_manager::{LibraryDirs, github_file_url};
+use crate::http;
+use crate::remote_font::{DEFAULT_PREFIX_KIB, fetch_partial_font, is_partially_readable};
+use crate::table::Table;
+
+/// Relative differences up to this are shown as compatible
+const CLOSE_DELTA: f64 = 0.02;
+/// Relative differences beyond this are shown as incompatible
+const FAR_DELTA: f64 = 0.05;
+
+/// The metrics of a face that matter for substitution. Lengths are in font
+/// units; [`Self::per_mille`] scales them to a common 1000-unit em.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct FaceMetrics {
+    /// The file, or the library entry, the face was read from
+    pub(crate) name: String,
+    pub(crate) units_per_em: u16,
+    pub(crate) weight_class: u16,
+    pub(crate) x_height: Option<i16>,
+    pub(crate) cap_height: Option<i16>,
+    /// `xAvgCharWidth` of the OS/2 table
+    pub(crate) avg_width: Option<i16>,
+    pub(crate) ascender: i16,
+    pub(crate) descender: i16,
+}
+
+impl FaceMetrics {
+    pub(crate) fn parse(name: String, data: &[u8], index: u32) -> Result<Self, String> {
+        let face = ttf_parser::Face::parse(data, index)
+            .map_err(|e| format!("Failed to parse {name}: {e}"))?;
+        let avg_width = face
+            .raw_face()
+            .table(ttf_parser::Tag::from_bytes(b"OS/2"))
+            .and_then(|os2| os2.get(2..4))
+            .map(|bytes| i16::from_be_bytes([bytes[0], bytes[1]]));
+        Ok(Self {
+            name,
+            units_per_em: face.units_per_em(),
+            weight_class: face.weight().to_number(),
+            x_height: face.x_height().or_else(|| glyph_top(&face, 'x')),
+            cap_height: face.capital_height().or_else(|| glyph_top(&face, 'H')),
+            avg_width,
+            ascender: face.ascender(),
+            descender: face.descender(),
+        })
+    }
+
+    /// `value` in thousandths of an em
+    fn per_mille(&self, value: i16) -> f64 {
+        f64::from(value) * 1000.0 / f64::from(self.units_per_em)
+    }
+}
+
+/// The top of the outline of `c`, for fonts whose OS/2 table predates the
+/// x-height and cap height fields. Not available for remote fonts, whose
+/// outlines aren't downloaded.
+fn glyph_top(face: &ttf_parser::Face, c: char) -> Option<i16> {
+    let glyph = face.glyph_index(c)?;
+    face.glyph_bounding_box(glyph)
+        .map(|bbox| bbox.y_max)
+        .filter(|&top| top > 0)
+}
+
+/// The face a `compare` argument names: a font file, or else a family in the
+/// library, of which the variant with `style` closest to `weight` is used
+pub(crate) fn resolve_face(
+    spec: &str,
+    library_dirs: &LibraryDirs,
+    entries: &[DiscoveredFont],
+    style: FontStyle,
+    weight: u16,
+) -> Result<FaceMetrics, String> {
+    let path = Path::new(spec);
+    if path.is_file() {
+        let data = fs::read(path).map_err(|e| format!("Failed to read {spec}: {e}"))?;
+        return FaceMetrics::parse(spec.to_string(), &data, 0);
+    }
+
+    let entry = entries
+        .iter()
+        .filter(|entry| entry.font.family_name.eq_ignore_ascii_case(spec))
+        .min_by_key(|entry| {
+            (
+                entry.font.style != style,
+                entry.font.weight.to_number().abs_diff(weight),
+            )
+        })
+        .ok_or_else(|| format!("{spec:?} is neither a font file nor a family in the library"))?;
+    let data = read_entry(entry, library_dirs)?;
+    FaceMetrics::parse(
+        format!("{} ({})", entry.font.family_name, entry.display_path()),
+        &data,
+        entry.index,
+    )
+}
+
+/// The data of a library font: a local file, or the headers of a GitHub one,
+/// which hold all the metrics compared
+fn read_entry(entry: &DiscoveredFont, library_dirs: &LibraryDirs) -> Result<Vec<u8>, String> {
+    if entry.path.is_file() {
+        return fs::read(&entry.path).map_err(|e| format!("Failed to read {:?}: {e}", entry.path));
+    }
+    let (_, url) = github_file_url(&entry.path, library_dirs.github_reference(&entry.path))?;
+    let client = http::client()?;
+    if is_partially_readable(&entry.path) {
+        Ok(fetch_partial_font(&client, &url, DEFAULT_PREFIX_KIB * 1024)?.data)
+    } else {
+        crate::remote_font::fetch_whole(&client, &url)
+    }
+}
+
+/// The relative difference of `b` from `a`
+fn relative_delta(a: f64, b: f64) -> Option<f64> {
+    (a != 0.0).then(|| (b - a) / a.abs())
+}
+
+fn format_delta(delta: Option<f64>) -> ColoredString {
+    match delta {
+        None => "-".dimmed(),
+        Some(delta) => {
+            let text = format!("{:+.1}%", delta * 100.0);
+            if delta.abs() <= CLOSE_DELTA {
+                text.green()
+            } else if delta.abs() <= FAR_DELTA {
+                text.yellow()
+            } else {
+                text.red()
+            }
+        }
+    }
+}
+
+/// The rows of the comparison: metric, value of `a`, value of `b`, difference
+fn comparison_rows(a: &FaceMetrics, b: &FaceMetrics) -> Vec<[ColoredString; 4]> {
+    let mut rows = vec![
+        [
+            "Units per em".into(),
+            a.units_per_em.to_string().into(),
+            b.units_per_em.to_string().into(),
+            format!(
+                "{:+}",
+                i32::from(b.units_per_em) - i32::from(a.units_per_em)
+            )
+            .normal(),
+        ],
+        [
+            "Weight class".into(),
+            a.weight_class.to_string().into(),
+            b.weight_class.to_string().into(),
+            format!(
+                "{:+}",
+                i32::from(b.weight_class) - i32::from(a.weight_class)
+            )
+            .normal(),
+        ],
+    ];
+
+    // Lengths are compared per 1000 units, as the two ems may differ
+    let lengths = [
+        ("x-height", a.x_height, b.x_height),
+        ("Cap height", a.cap_height, b.cap_height),
+        ("Average width", a.avg_width, b.avg_width),
+        ("Ascender", Some(a.ascender), Some(b.ascender)),
+        ("Descender", Some(a.descender), Some(b.descender)),
+    ];
+    for (label, value_a, value_b) in lengths {
+        let value_a = value_a.map(|value| a.per_mille(value));
+        let value_b = value_b.map(|value| b.per_mille(value));
+        let show = |value: Option<f64>| {
+            value.map_or_else(|| "-".dimmed(), |value| format!("{value:.0}").normal())
+        };
+        let delta = value_a
+            .zip(value_b)
+            .and_then(|(value_a, value_b)| relative_delta(value_a, value_b));
+        rows.push([
+            label.into(),
+            show(value_a),
+            show(value_b),
+            format_delta(delta),
+        ]);
+    }
+    rows
+}
+
+pub(crate) fn print_comparison(a: &FaceMetrics, b: &FaceMetrics) {
+    println!("{} {}", "A:".bold(), a.name);
+    println!("{} {}\n", "B:".bold(), b.name);
+    let mut table = Table::new(vec!["Metric", "A", "B", "B - A"]);
+    for row in comparison_rows(a, b) {
+        table.push_row(row.into());
+    }
+    println!("{}", table.render());
+    println!(
+        "\nLengths are per 1000 units of the em; differences within 2% are usually unnoticeable."
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics(units_per_em: u16, x_height: i16) -> FaceMetrics {
+        FaceMetrics {
+            name: String::new(),
+            units_per_em,
+            weight_class: 400,
+            x_height: Some(x_height),
+            cap_height: None,
+            avg_width: Some(units_per_em as i16 / 2),
+            ascender: 800,
+            descender: -200,
+        }
+    }
+
+    #[test]
+    fn test_lengths_are_compared_per_em() {
+        // 1000 of 2000 units and 500 of 1000 units are the same proportion
+        let rows = comparison_rows(&metrics(2000, 1040), &metrics(1000, 500));
+        let row = |label: &str| {
+            rows.iter()
+                .find(|row| &*row[0] == label)
+                .unwrap()
+                .iter()
+                .map(|cell| String::from(&**cell))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(row("Units per em")[3], "-1000");
+        assert_eq!(row("Average width")[3], "+0.0%");
+        assert_eq!(row("x-height")[1], "520");
+        assert_eq!(row("x-height")[3], "-3.8%");
+        assert_eq!(row("Cap height")[3], "-");
+    }
+
+    #[test]
+    fn test_parse_metrics_of_a_system_font() {
+        let path = Path::new("/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf");
+        let Ok(data) = fs::read(path) else {
+            return;
+        };
+        let metrics = FaceMetrics::parse("DejaVu Sans".to_string(), &data, 0).unwrap();
+        assert_eq!(metrics.units_per_em, 2048);
+        assert_eq!(metrics.weight_class, 400);
+        assert!(metrics.avg_width.is_some());
+    }
+}
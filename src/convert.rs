@@ -0,0 +1,151 @@
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+/// Default converter command. FontForge picks the output format from the
+/// extension of the generated file, so the same script converts both ways.
+pub(crate) const DEFAULT_CONVERTER: &str =
+    "fontforge -lang=ff -c 'Open($1); Generate($2)' {input} {output}";
+
+/// Convert the outlines of the font at `input` into the format implied by the
+/// extension of `output`, using a converter command line in which `{input}` and
+/// `{output}` are replaced by the respective paths.
+pub(crate) fn convert_font_file(
+    converter: &str,
+    input: &Path,
+    output: &Path,
+) -> Result<(), String> {
+    let mut words = split_command_line(converter)?.into_iter().map(|word| {
+        word.replace("{input}", &input.to_string_lossy())
+            .replace("{output}", &output.to_string_lossy())
+    });
+
+    let program = words
+        .next()
+        .ok_or_else(|| "The converter command is empty".to_string())?;
+
+    let result = Command::new(&program)
+        .args(words)
+        .output()
+        .map_err(|e| format!("Failed to run font converter `{program}`: {e}"))?;
+
+    if !result.status.success() || !output.exists() {
+        return Err(format!(
+            "Font converter `{program}` failed to convert {:?}: {}",
+            input,
+            String::from_utf8_lossy(&result.stderr).trim()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Check that the program of the converter command line can be run, so that an
+/// update that needs it fails before installing anything. The default converter,
+/// FontForge, is rarely installed, so its error says where to get it.
+pub(crate) fn check_converter(converter: &str) -> Result<(), String> {
+    let words = split_command_line(converter)?;
+    let program = words
+        .first()
+        .ok_or_else(|| "The converter command is empty".to_string())?;
+    if is_runnable(Path::new(program)) {
+        return Ok(());
+    }
+    if converter == DEFAULT_CONVERTER {
+        Err(format!(
+            "Converting fonts requires FontForge, but `{program}` isn't installed or not on \
+             the PATH: install it from https://fontforge.org, or pass another converter \
+             with --converter"
+        ))
+    } else {
+        Err(format!(
+            "Font converter `{program}` isn't installed or not on the PATH"
+        ))
+    }
+}
+
+/// Whether `program` names an existing file, or one in a directory of the PATH
+fn is_runnable(program: &Path) -> bool {
+    let exists = |path: &Path| {
+        path.is_file()
+            || (cfg!(windows) && path.extension().is_none() && path.with_extension("exe").is_file())
+    };
+    if program.components().count() > 1 {
+        return exists(program);
+    }
+    env::var_os("PATH")
+        .is_some_and(|paths| env::split_paths(&paths).any(|dir| exists(&dir.join(program))))
+}
+
+/// Split a command line into words, honoring single and double quotes
+pub(crate) fn split_command_line(command: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+
+    for c in command.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        return Err(format!(
+            "Unterminated quote in converter command: {command}"
+        ));
+    }
+    if in_word {
+        words.push(word);
+    }
+
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_command_line_honors_quotes() {
+        assert_eq!(
+            split_command_line(DEFAULT_CONVERTER).unwrap(),
+            vec![
+                "fontforge",
+                "-lang=ff",
+                "-c",
+                "Open($1); Generate($2)",
+                "{input}",
+                "{output}"
+            ]
+        );
+        assert!(split_command_line("otf2ttf 'unterminated").is_err());
+    }
+
+    #[test]
+    fn test_check_converter_finds_the_program() {
+        let program = env::current_exe().unwrap();
+        assert!(check_converter(&format!("'{}' {{input}}", program.display())).is_ok());
+        let error = check_converter("typfont-missing-converter {input} {output}").unwrap_err();
+        assert!(
+            error.contains("`typfont-missing-converter` isn't installed"),
+            "{error}"
+        );
+        assert!(check_converter("").is_err());
+    }
+}
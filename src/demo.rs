@@ -0,0 +1,125 @@
+//! `demo`: create a sandbox with a library of the bundled fixture fonts and a
+//! project requiring them, then check the project, update it and check it again.
+//! It shows what the commands do, and that they work on the machine at hand.
+
+use crate::color::Colorize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::command::{DemoCommand, FontCommand, ReportFormat};
+use crate::error::Error;
+use crate::font_manager::{FontManager, ReportFilter, UpdateOptions};
+use crate::lockfile::HashAlgo;
+use crate::{fixtures, process_command, t};
+
+pub(crate) fn run_demo(args: &DemoCommand) -> Result<(), Error> {
+    let (dir, keep) = match &args.dir {
+        Some(dir) => {
+            let occupied = fs::read_dir(dir).is_ok_and(|mut entries| entries.next().is_some());
+            if occupied {
+                return Err(Error::Usage(format!(
+                    "{dir:?} is not empty, choose a new or empty directory for the demo"
+                )));
+            }
+            (dir.clone(), true)
+        }
+        None => (sandbox_dir(), args.keep),
+    };
+
+    let result = run_in(&dir);
+    if keep {
+        println!("\nThe sandbox is kept in {}", dir.display());
+    } else {
+        let _ = fs::remove_dir_all(&dir);
+    }
+    result
+}
+
+fn sandbox_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("typfont-demo-{}", std::process::id()))
+}
+
+fn run_in(dir: &Path) -> Result<(), Error> {
+    let library = dir.join("library");
+    let project = dir.join("project");
+    fixtures::write_fonts(&library).map_err(Error::Other)?;
+    fs::create_dir_all(&project)
+        .and_then(|_| {
+            fs::write(
+                project.join("font_config.toml"),
+                format!(
+                    "font_dir = \"fonts\"\n\n[[fonts]]\nfamily_name = \"{}\"\nweight = [400, 700]\n",
+                    fixtures::FAMILY
+                ),
+            )
+        })
+        .map_err(|e| Error::Other(format!("Failed to create the demo project: {e}")))?;
+    println!(
+        "Sandbox {}:\n  library/  the {} fonts, Regular and Bold\n  project/  a font_config.toml requiring both",
+        dir.display(),
+        fixtures::FAMILY
+    );
+
+    let args = FontCommand {
+        library: Some(vec![library]),
+        ..FontCommand::project(project)
+    };
+
+    step(
+        1,
+        "check reports the required fonts, which the project doesn't have yet",
+    );
+    process_command(
+        &args,
+        &t!("action-checking"),
+        ReportFilter::default(),
+        None,
+        None,
+        ReportFormat::Plain,
+    )?;
+
+    step(2, "update copies them from the library into project/fonts");
+    let options = UpdateOptions {
+        dry_run: false,
+        converter: None,
+        strip: false,
+        limit_rate: None,
+        diff: false,
+        locked: false,
+        only_sources: None,
+        quarantine: false,
+        hash_algo: HashAlgo::Sha256,
+    };
+    process_command(
+        &args,
+        &t!("action-updating"),
+        ReportFilter::default(),
+        Some(&options),
+        None,
+        ReportFormat::Plain,
+    )?;
+
+    step(3, "check again finds every required font in the project");
+    let action = t!("action-checking");
+    let font_manager = FontManager::new(&args, &action)?;
+    font_manager.print_status();
+    let summary = font_manager.summary();
+    if summary.missing > 0 {
+        return Err(Error::Other(format!(
+            "The demo project still misses {} fonts after update",
+            summary.missing
+        )));
+    }
+    println!(
+        "\n{}",
+        "✓ The demo finished, typfont works on this machine".green()
+    );
+    Ok(())
+}
+
+fn step(number: usize, description: &str) {
+    println!(
+        "\n{}",
+        format!("=== Step {number}/3: {description} ===").bold()
+    );
+}
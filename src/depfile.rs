@@ -0,0 +1,75 @@
+//! `check --emit make-deps=FILE`: a dependency file in the Makefile syntax that
+//! make, ninja (`depfile`) and other incremental build systems read, so that
+//! the compiled document is rebuilt when a font it uses changes:
+//!
+//! ```make
+//! main.pdf: fonts/Lato-Bold.ttf fonts/Lato-Regular.ttf
+//!
+//! fonts/Lato-Bold.ttf:
+//!
+//! fonts/Lato-Regular.ttf:
+//! ```
+//!
+//! Like `gcc -MP`, every font gets an empty rule of its own, so that a build
+//! doesn't stop when a font is removed from the project.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::utils::path_utils::portable_path;
+
+/// The dependency rule of `target` on the `files`, in the given order
+pub(crate) fn make_deps(target: &Path, files: &[PathBuf]) -> String {
+    let mut deps = escape(target);
+    deps.push(':');
+    for file in files {
+        deps.push_str(" \\\n  ");
+        deps.push_str(&escape(file));
+    }
+    deps.push('\n');
+    for file in files {
+        deps.push('\n');
+        deps.push_str(&escape(file));
+        deps.push_str(":\n");
+    }
+    deps
+}
+
+/// Write the dependency rule of `target` on the `files` to `path`
+pub(crate) fn write_make_deps(path: &Path, target: &Path, files: &[PathBuf]) -> Result<(), String> {
+    fs::write(path, make_deps(target, files)).map_err(|e| format!("Failed to write {path:?}: {e}"))
+}
+
+/// `path` as a word of a rule, with the characters make treats specially escaped
+fn escape(path: &Path) -> String {
+    let mut escaped = String::new();
+    for c in portable_path(path).chars() {
+        match c {
+            ' ' | '\t' | '#' => escaped.push('\\'),
+            '$' => escaped.push('$'),
+            _ => {}
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_make_deps() {
+        let files = [
+            PathBuf::from("fonts/Lato-Regular.ttf"),
+            PathBuf::from("fonts/My Font #1$.otf"),
+        ];
+        assert_eq!(
+            make_deps(Path::new("out/main.pdf"), &files),
+            "out/main.pdf: \\\n  fonts/Lato-Regular.ttf \\\n  fonts/My\\ Font\\ \\#1$$.otf\n\
+             \nfonts/Lato-Regular.ttf:\n\
+             \nfonts/My\\ Font\\ \\#1$$.otf:\n"
+        );
+        assert_eq!(make_deps(Path::new("main.pdf"), &[]), "main.pdf:\n");
+    }
+}
@@ -0,0 +1,108 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use crate::font_manager::CheckFailure;
+use crate::t;
+
+/// Errors reported by the CLI. Each kind of failure exits with its own code,
+/// so scripts can tell a broken config from a network outage.
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum Error {
+    /// Invalid combination of command-line arguments
+    #[error("{0}")]
+    Usage(String),
+    /// The project's font_config.toml, or another configuration file such as
+    /// the user settings, is missing or invalid
+    #[error("{0}")]
+    Project(String),
+    /// A font library could not be read, e.g. a GitHub index failed to download
+    #[error("{}", t!("error-library", error = .0.as_str()))]
+    Library(String),
+    /// Installing fonts into the project failed
+    #[error("{}", t!("error-update", error = .0.as_str()))]
+    Update(String),
+    #[error("{}", t!("error-write", path = format!("{path:?}"), error = source.to_string()))]
+    Write {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// `check-lib --validate` found an index that doesn't match its library, or
+    /// `lint-lib` found naming problems
+    #[error("{0}")]
+    InvalidLibrary(String),
+    /// `check` found fonts missing, or redundant with `--strict`; the message is
+    /// the summary line
+    #[error("{}", t!("error-incomplete", summary = .summary.as_str()))]
+    Incomplete {
+        failure: CheckFailure,
+        summary: String,
+    },
+    #[error("{0}")]
+    Other(String),
+}
+
+impl Error {
+    /// The exit code of invalid command-line arguments, including those clap
+    /// rejects, whose own code 2 is that of unavailable fonts
+    pub(crate) const USAGE_EXIT_CODE: u8 = 8;
+
+    pub(crate) fn exit_code(&self) -> ExitCode {
+        ExitCode::from(match self {
+            Error::Incomplete { failure, .. } => match failure {
+                CheckFailure::Fixable => 1,
+                CheckFailure::Unavailable => 2,
+                CheckFailure::Redundant => 7,
+            },
+            Error::Project(_) => 3,
+            Error::Library(_) => 4,
+            Error::Update(_) | Error::Write { .. } => 5,
+            Error::InvalidLibrary(_) => 6,
+            Error::Usage(_) => Self::USAGE_EXIT_CODE,
+            Error::Other(_) => 9,
+        })
+    }
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error::Other(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_exit_codes_are_distinct() {
+        let errors = [
+            Error::Other(String::new()),
+            Error::Usage(String::new()),
+            Error::Project(String::new()),
+            Error::Library(String::new()),
+            Error::Update(String::new()),
+            Error::InvalidLibrary(String::new()),
+            Error::Incomplete {
+                failure: CheckFailure::Redundant,
+                summary: String::new(),
+            },
+            Error::Incomplete {
+                failure: CheckFailure::Fixable,
+                summary: String::new(),
+            },
+            Error::Incomplete {
+                failure: CheckFailure::Unavailable,
+                summary: String::new(),
+            },
+        ];
+        let codes = errors
+            .iter()
+            .map(|error| format!("{:?}", error.exit_code()))
+            .collect::<std::collections::BTreeSet<_>>();
+        assert_eq!(codes.len(), errors.len());
+        assert_eq!(
+            Error::Library("HTTP 404".to_string()).to_string(),
+            "Failed to read font library: HTTP 404"
+        );
+    }
+}
@@ -0,0 +1,40 @@
+//! Callbacks through which [`check_project`](crate::check::check_project) and
+//! [`update_project`](crate::check::update_project) report their progress, so
+//! that embedders can render it in their own UI instead of capturing the
+//! output of the `typfont` binary.
+
+use std::path::Path;
+
+use typst::text::FontVariant;
+
+/// Where a required font was found.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Resolution<'a> {
+    /// Installed in the project font directory, in this file.
+    Installed(&'a Path),
+    /// Embedded in the Typst compiler, so it doesn't have to be installed.
+    Embedded,
+    /// Not installed, but available in this file of a font library.
+    Library(&'a Path),
+}
+
+/// Receives the events of a check or an update. Every method does nothing by
+/// default, so an observer only implements those it shows; `()` ignores all
+/// events.
+pub trait FontObserver {
+    /// A required font was found, in the project or in a library. Fonts found
+    /// nowhere are reported as a [`warning`](Self::warning) instead.
+    fn font_resolved(&mut self, _family: &str, _variant: FontVariant, _resolution: Resolution) {}
+
+    /// The library file `from` starts being fetched into the project at `to`.
+    fn download_started(&mut self, _from: &Path, _to: &Path) {}
+
+    /// The library file `from` was installed in the project at `to`.
+    fn file_copied(&mut self, _from: &Path, _to: &Path) {}
+
+    /// Something the user should know about, such as a required font that is
+    /// neither installed nor in any library.
+    fn warning(&mut self, _message: &str) {}
+}
+
+impl FontObserver for () {}
@@ -0,0 +1,95 @@
+//! Advisory locks keeping simultaneous typfont processes apart. `update` and
+//! `repair` lock the project font directory, and writers of the shared caches
+//! (the index cache and a workspace store) lock those. A process finding a lock
+//! taken waits for it, or fails at once with `--no-wait`.
+
+use crate::color::Colorize;
+use std::fs::{self, File, TryLockError};
+use std::io::ErrorKind;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{shared, t};
+
+/// Name of the lock file in a locked directory
+pub(crate) const LOCK_FILE: &str = ".typfont.lock";
+
+/// Set by `--no-wait`: fail instead of waiting for a lock
+static NO_WAIT: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn set_no_wait() {
+    NO_WAIT.store(true, Ordering::Relaxed);
+}
+
+/// A held lock, released when dropped
+#[derive(Debug)]
+pub(crate) struct DirLock {
+    _file: File,
+}
+
+/// Lock the directory `dir`, creating it if needed
+pub(crate) fn lock_dir(dir: &Path) -> Result<DirLock, String> {
+    lock_dir_with(dir, NO_WAIT.load(Ordering::Relaxed), false)
+}
+
+/// Lock the directory `dir` shared by several users, creating it and the lock
+/// file writable by the group if needed, see [`crate::shared`]
+pub(crate) fn lock_shared_dir(dir: &Path) -> Result<DirLock, String> {
+    lock_dir_with(dir, NO_WAIT.load(Ordering::Relaxed), true)
+}
+
+fn lock_dir_with(dir: &Path, no_wait: bool, shared: bool) -> Result<DirLock, String> {
+    if shared {
+        shared::create_dir_all(dir)?;
+    } else {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create directories {dir:?}: {e}"))?;
+    }
+    let path = dir.join(LOCK_FILE);
+    let created = !path.exists();
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        // A lock file created by another user may be read-only to this one,
+        // which is enough to lock it
+        .or_else(|e| match e.kind() {
+            ErrorKind::PermissionDenied => File::open(&path),
+            _ => Err(e),
+        })
+        .map_err(|e| format!("Failed to open lock file {path:?}: {e}"))?;
+    if shared && created {
+        shared::share_file(&path)?;
+    }
+    match file.try_lock() {
+        Ok(()) => return Ok(DirLock { _file: file }),
+        Err(TryLockError::Error(e)) => return Err(format!("Failed to lock {path:?}: {e}")),
+        Err(TryLockError::WouldBlock) => {}
+    }
+    if no_wait {
+        return Err(t!("lock-busy", path = format!("{dir:?}")));
+    }
+    eprintln!("{}", t!("lock-waiting", path = format!("{dir:?}")).yellow());
+    file.lock()
+        .map_err(|e| format!("Failed to lock {path:?}: {e}"))?;
+    Ok(DirLock { _file: file })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_second_lock_fails_without_waiting() {
+        let dir = std::env::temp_dir().join(format!("typfont-lock-{}", std::process::id()));
+        let held = lock_dir_with(&dir, true, false).unwrap();
+        let error = lock_dir_with(&dir, true, false).unwrap_err();
+        assert!(error.contains("typfont-lock"), "{error}");
+
+        drop(held);
+        let again = lock_dir_with(&dir, true, false).unwrap();
+        drop(again);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,198 @@
+//! Freshness of a project, like cargo's fingerprints. When a run finds the
+//! project complete, see [`FontManager::is_complete`], a fingerprint of what
+//! that result depends on is stored in `.typst-font-manager/fingerprint`:
+//!
+//! - font_config.toml, the configs of parent directories it inherits from,
+//!   font_config.lock and the user settings,
+//! - the library sources and their revisions: the index and packs files of
+//!   local libraries and of the local mirrors of GitHub libraries, and the
+//!   commits of GitHub libraries in the lockfile,
+//! - the size and modification time of the files in the project font
+//!   directory, and of the project's `.typ` files, whose imports may require
+//!   fonts,
+//! - the version of typfont.
+//!
+//! A later `check` computing the same fingerprint reports the project up to
+//! date without reading the libraries or parsing a single font. The contents of
+//! the libraries are left out: they don't change a result without missing fonts.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use walkdir::WalkDir;
+
+use crate::command::FontCommand;
+use crate::font_manager::{FontManager, LibraryDirs};
+use crate::github::{DEFAULT_INDEX_PATH, GitHubSource};
+use crate::journal::STATE_DIR;
+use crate::lockfile::{lockfile_path, sha256_hex};
+use crate::packs::PACKS_FILE;
+use crate::parse_font_config::parent_config_files;
+use crate::user_config;
+
+const FINGERPRINT_FILE: &str = "fingerprint";
+
+/// Path of the stored fingerprint of the project configured by `config_file`
+pub(crate) fn fingerprint_path(config_file: &Path) -> PathBuf {
+    config_file
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(STATE_DIR)
+        .join(FINGERPRINT_FILE)
+}
+
+/// The fingerprint of the project of `args` as it is now
+pub(crate) fn compute(args: &FontCommand) -> Result<String, String> {
+    let config_file = FontManager::resolve_config_file(&args.project_or_config);
+    let font_dir = FontManager::project_font_dir(&config_file)?;
+    let library_dirs = FontManager::library_dirs(args)?;
+
+    let mut manifest = format!("typfont {}\n", env!("CARGO_PKG_VERSION"));
+    add_content(&mut manifest, "config", &config_file);
+    for path in parent_config_files(&config_file) {
+        add_content(&mut manifest, "inherited", &path);
+    }
+    add_content(&mut manifest, "lock", &lockfile_path(&config_file));
+    if let Some(path) = user_config::config_path() {
+        add_content(&mut manifest, "settings", &path);
+    }
+    for dir in &library_dirs {
+        let _ = writeln!(manifest, "source {}", library_dirs.source_id(dir));
+        match library_dirs {
+            LibraryDirs::Local(_) => {
+                for file in [DEFAULT_INDEX_PATH, PACKS_FILE] {
+                    add_metadata(&mut manifest, "revision", &dir.join(file));
+                }
+            }
+            LibraryDirs::GitHub(_) => {
+                if let Ok(source) = GitHubSource::parse(dir)
+                    && source.reference.is_none()
+                    && let Some(mirror) = user_config::get().library.local_mirror(source.repo)
+                {
+                    add_metadata(&mut manifest, "mirror", &mirror.join(source.index_path));
+                }
+            }
+        }
+    }
+
+    for path in files_below(&font_dir, |_| true) {
+        add_metadata(&mut manifest, "font", &path);
+    }
+    let project_dir = match config_file.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let is_typ = |path: &Path| path.extension().is_some_and(|ext| ext == "typ");
+    for path in files_below(project_dir, is_typ) {
+        if !path.starts_with(&font_dir) {
+            add_metadata(&mut manifest, "typ", &path);
+        }
+    }
+    Ok(sha256_hex(manifest.as_bytes()))
+}
+
+/// Whether `fingerprint` is the one stored for the project of `config_file`
+pub(crate) fn is_fresh(config_file: &Path, fingerprint: &str) -> bool {
+    fs::read_to_string(fingerprint_path(config_file))
+        .is_ok_and(|stored| stored.trim() == fingerprint)
+}
+
+/// Store `fingerprint` for the project of `config_file`
+pub(crate) fn record(config_file: &Path, fingerprint: &str) -> Result<(), String> {
+    let path = fingerprint_path(config_file);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directories {parent:?}: {e}"))?;
+    }
+    fs::write(&path, format!("{fingerprint}\n"))
+        .map_err(|e| format!("Failed to write {path:?}: {e}"))
+}
+
+/// Forget the stored fingerprint, so that the next check runs in full
+pub(crate) fn clear(config_file: &Path) {
+    let _ = fs::remove_file(fingerprint_path(config_file));
+}
+
+/// The files below `dir` whose path passes `filter`, in a stable order and
+/// leaving out hidden entries such as typfont's own state
+fn files_below(dir: &Path, filter: impl Fn(&Path) -> bool) -> Vec<PathBuf> {
+    WalkDir::new(dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| {
+            entry.depth() == 0 || !entry.file_name().to_string_lossy().starts_with('.')
+        })
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file() && filter(entry.path()))
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+/// A line with the hash of the file at `path`, or noting that it is absent
+fn add_content(manifest: &mut String, kind: &str, path: &Path) {
+    let hash = fs::read(path).map_or_else(|_| "-".to_string(), |data| sha256_hex(&data));
+    let _ = writeln!(manifest, "{kind} {} {hash}", path.display());
+}
+
+/// A line with the size and modification time of the file at `path`, which
+/// change when the file is replaced, or noting that it is absent
+fn add_metadata(manifest: &mut String, kind: &str, path: &Path) {
+    let metadata = fs::metadata(path).ok().map(|metadata| {
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |time| time.as_nanos());
+        format!("{} {modified}", metadata.len())
+    });
+    let _ = writeln!(
+        manifest,
+        "{kind} {} {}",
+        path.display(),
+        metadata.as_deref().unwrap_or("-")
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint_follows_project_changes() {
+        let dir = std::env::temp_dir().join(format!("typfont-fingerprint-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("fonts")).unwrap();
+        let config_file = dir.join("font_config.toml");
+        fs::write(&config_file, "[[fonts]]\nfamily_name = \"A\"\n").unwrap();
+        let args = FontCommand::project(dir.clone());
+        let library = FontCommand {
+            library: Some(vec![dir.join("lib")]),
+            ..args.clone()
+        };
+
+        let fingerprint = compute(&library).unwrap();
+        assert_eq!(fingerprint, compute(&library).unwrap());
+        assert_ne!(fingerprint, compute(&args).unwrap());
+
+        assert!(!is_fresh(&config_file, &fingerprint));
+        record(&config_file, &fingerprint).unwrap();
+        assert!(is_fresh(&config_file, &fingerprint));
+        // The stored fingerprint itself is hidden state, not a project file
+        assert_eq!(fingerprint, compute(&library).unwrap());
+
+        fs::write(dir.join("fonts/A.ttf"), b"font").unwrap();
+        let with_font = compute(&library).unwrap();
+        assert_ne!(fingerprint, with_font);
+        fs::write(dir.join("main.typ"), "#import \"@preview/pkg:0.1.0\"").unwrap();
+        let with_typ = compute(&library).unwrap();
+        assert_ne!(with_font, with_typ);
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        fs::write(dir.join("lib").join(PACKS_FILE), "").unwrap();
+        assert_ne!(with_typ, compute(&library).unwrap());
+
+        clear(&config_file);
+        assert!(!is_fresh(&config_file, &fingerprint));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
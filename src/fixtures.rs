@@ -0,0 +1,59 @@
+//! Tiny fonts bundled with the `test-fixtures` feature, for tests and `demo`:
+//! Typfont Fixture Regular and Bold, under the SIL Open Font License (see
+//! fixtures/fonts/OFL.txt). Every printable ASCII character is drawn as a box,
+//! so the files take a couple of kilobytes.
+
+use std::fs;
+use std::path::Path;
+
+pub(crate) const FAMILY: &str = "Typfont Fixture";
+
+/// File names and contents of the fixture fonts
+pub(crate) const FONTS: [(&str, &[u8]); 2] = [
+    (
+        "TypfontFixture-Regular.ttf",
+        include_bytes!("../fixtures/fonts/TypfontFixture-Regular.ttf"),
+    ),
+    (
+        "TypfontFixture-Bold.ttf",
+        include_bytes!("../fixtures/fonts/TypfontFixture-Bold.ttf"),
+    ),
+];
+
+const LICENSE: &str = include_str!("../fixtures/fonts/OFL.txt");
+
+/// Write the fixture fonts and their license into `dir`
+pub(crate) fn write_fonts(dir: &Path) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create directories {dir:?}: {e}"))?;
+    let files = FONTS.into_iter().chain([("OFL.txt", LICENSE.as_bytes())]);
+    for (name, data) in files {
+        let path = dir.join(name);
+        fs::write(&path, data).map_err(|e| format!("Failed to write {path:?}: {e}"))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discover_faces;
+    use crate::process_font::FontLicense;
+
+    #[test]
+    fn test_fixture_fonts_parse() {
+        let faces = FONTS
+            .iter()
+            .flat_map(|(name, data)| discover_faces(Path::new(name), data))
+            .collect::<Vec<_>>();
+        let weights = faces
+            .iter()
+            .map(|face| (face.font.family_name.as_str(), face.font.weight.to_number()))
+            .collect::<Vec<_>>();
+        assert_eq!(weights, [(FAMILY, 400), (FAMILY, 700)]);
+        assert!(
+            faces
+                .iter()
+                .all(|face| face.metadata.license == Some(FontLicense::Ofl))
+        );
+    }
+}
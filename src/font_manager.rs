@@ -1,65 +1,156 @@
-use crate::command::FontCommand;
+use crate::cache::{self, CachedIndex};
+use crate::cancel::{self, InFlight};
+use crate::color::{ColoredString, Colorize};
+use crate::command::{FontCommand, OutputLayout, ReportFormat, SortKey};
+use crate::convert::{check_converter, convert_font_file};
+use crate::error::Error;
+use crate::github::{self, days_behind, format_timestamp, parse_timestamp};
+use crate::html_report::{HtmlReport, HtmlRow};
+use crate::http::header::{ETAG, IF_NONE_MATCH};
+use crate::http::{self, StatusCode};
+use crate::journal::{self, JournalAction, journal_path};
+use crate::lockfile::{
+    FontLock, HashAlgo, LockedFile, LockedSource, Provenance, SourceType, hash_files,
+    lockfile_path, sha256_hex, tool_version,
+};
+use crate::macos_assets;
+use crate::packs::{self, NodeKind, PackNode};
 use crate::parse_font_config::{
-    FontConfig, TypstFont, deserialize_fonts_from_file, deserialize_fonts_from_toml,
+    EMBEDDED_FONTS, FontConfig, FontFormat, RedistributionPolicy, SourcePriority, TypstFont,
+    deserialize_fonts_from_file, deserialize_fonts_from_toml,
+};
+use crate::process_font::{Embedding, FontClass, FontFeature, FontLicense, FontMetadata};
+use crate::quarantine;
+use crate::rate_limit::RateLimit;
+use crate::redistribution;
+use crate::strip::{extract_face, strip_font_tables};
+use crate::table::{Table, format_size};
+use crate::trace;
+use crate::typst_packages;
+use crate::user_config;
+use crate::utils::path_utils::{portable_path, relative_path};
+use crate::workspace::Workspace;
+use crate::{
+    DiscoveredFont, create_font_entries, create_font_entries_from_dirs, progress, system_font_dirs,
+    t, utils,
 };
-use crate::{DiscoveredFont, create_font_entries, create_font_entries_from_dirs, utils};
-use colored::Colorize;
-use reqwest::blocking::{Client, get};
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::slice::Iter;
+use time::OffsetDateTime;
 use typst::text::{AxisValue, FontAxis, FontStretch, FontStyle, FontWeight, StandardAxes, Tag};
 
-const EMBEDDED_FONTS: &str = r#"
-[[fonts]]
-family_name = "DejaVu Sans Mono"
-style = "Normal"
-weight = [400, 700]
-stretch = 1000
+#[derive(Clone)]
+pub(crate) enum LibraryDirs {
+    Local(Vec<PathBuf>),  // Local font library directories, like /usr/share/fonts
+    GitHub(Vec<PathBuf>), // GitHub repositories, like "owner/repo"
+}
 
-[[fonts]]
-family_name = "DejaVu Sans Mono"
-style = "Italic"
-weight = [400, 700]
-stretch = 1000
+impl LibraryDirs {
+    /// Library directories given on the command line; without `--library`, the
+    /// system's font directories are used, see `--system-dirs`
+    pub(crate) fn new(library: Option<&[PathBuf]>, github: bool) -> Result<Self, String> {
+        match (library, github) {
+            (Some(repos), true) => {
+                // Font files are told apart by their repository, not by its revision
+                let mut references = BTreeMap::new();
+                for source in repos
+                    .iter()
+                    .filter_map(|repo| github::GitHubSource::parse(repo).ok())
+                {
+                    if let Some(other) = references.insert(source.repo, source.reference)
+                        && other != source.reference
+                    {
+                        return Err(format!(
+                            "{} is given at two different revisions",
+                            source.repo
+                        ));
+                    }
+                }
+                Ok(Self::GitHub(repos.to_vec()))
+            }
+            (None, true) => Err("GitHub repository not provided".to_string()),
+            (Some(dirs), false) => Ok(Self::Local(dirs.to_vec())),
+            (None, false) => Ok(Self::Local(system_font_dirs())),
+        }
+    }
+}
 
-[[fonts]]
-family_name = "Linux Libertine"
-style = "Normal"
-weight = [400, 700]
-stretch = 1000
+impl LibraryDirs {
+    /// Name of a library source in reports and for `--disable-source`, e.g.
+    /// `github:owner/repo` or `local:/usr/share/fonts`
+    pub(crate) fn source_id(&self, dir: &Path) -> String {
+        match self {
+            LibraryDirs::Local(_) => format!("local:{}", dir.display()),
+            LibraryDirs::GitHub(_) => format!("github:{}", dir.display()),
+        }
+    }
 
-[[fonts]]
-family_name = "Linux Libertine"
-style = "Italic"
-weight = [400, 700]
-stretch = 1000
+    /// Drop the sources named by `disabled`, given either as source IDs or as
+    /// plain directories or repositories
+    pub(crate) fn disable_sources(&mut self, disabled: &[String]) -> Result<(), String> {
+        for name in disabled {
+            if !self.into_iter().any(|dir| self.source_matches(dir, name)) {
+                return Err(format!("Unknown library source to disable: {name}"));
+            }
+        }
 
-[[fonts]]
-family_name = "New Computer Modern"
-style = "Normal"
-weight = [400, 700]
-stretch = 1000
+        let keep = self
+            .into_iter()
+            .map(|dir| !disabled.iter().any(|name| self.source_matches(dir, name)))
+            .collect::<Vec<_>>();
+        let (LibraryDirs::Local(dirs) | LibraryDirs::GitHub(dirs)) = self;
+        let mut keep = keep.into_iter();
+        dirs.retain(|_| keep.next().unwrap_or(true));
+        Ok(())
+    }
 
-[[fonts]]
-family_name = "New Computer Modern"
-style = "Italic"
-weight = [400, 700]
-stretch = 1000
+    fn source_matches(&self, dir: &Path, name: &str) -> bool {
+        self.source_id(dir) == name || Path::new(name) == dir
+    }
 
-[[fonts]]
-family_name = "New Computer Modern Math"
-style = "Normal"
-weight = [400, 450]
-stretch = 1000
-"#;
+    /// The source a library font file was discovered in, and the path of the
+    /// file relative to that source
+    pub(crate) fn locate<'p>(&self, path: &'p Path) -> Option<(String, &'p Path)> {
+        self.into_iter().find_map(|dir| {
+            let relative = path.strip_prefix(self.source_root(dir)?).ok()?;
+            Some((self.source_id(dir), relative))
+        })
+    }
 
-pub(crate) enum LibraryDirs {
-    Local(Vec<PathBuf>),  // Local font library directories, like /usr/share/fonts
-    GitHub(Vec<PathBuf>), // GitHub repositories, like "owner/repo"
+    /// The source directory or repository a library font file was discovered in
+    fn source_of(&self, path: &Path) -> Option<&Path> {
+        self.into_iter()
+            .find(|dir| {
+                self.source_root(dir)
+                    .is_some_and(|root| path.starts_with(root))
+            })
+            .map(PathBuf::as_path)
+    }
+
+    fn source_root<'d>(&self, dir: &'d Path) -> Option<&'d Path> {
+        match self {
+            LibraryDirs::Local(_) => Some(dir),
+            LibraryDirs::GitHub(_) => Some(Path::new(github::GitHubSource::parse(dir).ok()?.repo)),
+        }
+    }
+
+    /// The branch, tag or commit the GitHub library a font file was discovered in
+    /// is pinned to
+    pub(crate) fn github_reference(&self, path: &Path) -> Option<&str> {
+        match self {
+            LibraryDirs::Local(_) => None,
+            LibraryDirs::GitHub(_) => {
+                github::GitHubSource::parse(self.source_of(path)?)
+                    .ok()?
+                    .reference
+            }
+        }
+    }
 }
 
 // Implement IntoIterator for `&LibraryDirs`
@@ -75,13 +166,302 @@ impl<'a> IntoIterator for &'a LibraryDirs {
     }
 }
 
+/// Options controlling how `update` installs missing fonts
+#[derive(Debug, Default)]
+pub(crate) struct UpdateOptions<'a> {
+    pub(crate) dry_run: bool,
+    /// Converter command line used to satisfy the project's `font_format` policy
+    pub(crate) converter: Option<&'a str>,
+    /// Strip hinting and other tables Typst doesn't use from installed fonts
+    pub(crate) strip: bool,
+    /// Maximum download bandwidth for fonts fetched from GitHub
+    pub(crate) limit_rate: Option<RateLimit>,
+    /// Print the planned changes as a diff of the font directory (dry runs only)
+    pub(crate) diff: bool,
+    /// Require the planned files and their hashes to match the lockfile, and leave
+    /// it unchanged
+    pub(crate) locked: bool,
+    /// Only install the files from these library sources, as the lockfile
+    /// records them (`repair`)
+    pub(crate) only_sources: Option<&'a BTreeSet<String>>,
+    /// Install into the quarantine of the font directory for review, as the
+    /// `quarantine` key of font_config.toml does
+    pub(crate) quarantine: bool,
+    /// Also record the BLAKE3 of installed files with `Blake3`, and verify them
+    /// with it against a lockfile that records it (`--locked`)
+    pub(crate) hash_algo: HashAlgo,
+}
+
 pub(crate) struct FontManager<'a> {
     config_file: PathBuf,       // Path to the configuration file
     font_config: FontConfig,    // Font configuration deserialized from font_config.toml
+    pack_tree: Vec<PackNode>,   // The required packs, expanded
     library_dirs: LibraryDirs,  // Source font library directory paths
     absolute_font_dir: PathBuf, // Absolute path of the project's font directory
     font_sets: FontSets,        // Font sets to manage
     action: &'a str,
+    output: OutputLayout,
+    sort_by: SortKey,
+    report_filter: ReportFilter,
+    namespace_sources: bool, // Whether reports name the source of library fonts
+}
+
+/// Counts of problematic fonts, printed by `check --summary-only`
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+pub(crate) struct StatusSummary {
+    pub(crate) missing: usize,
+    /// Missing variants of families otherwise present in the project
+    pub(crate) faux: usize,
+    pub(crate) fixable: usize,
+    pub(crate) redundant: usize,
+}
+
+impl std::fmt::Display for StatusSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let summary = if self.faux > 0 {
+            t!(
+                "summary-with-faux",
+                missing = self.missing,
+                faux = self.faux,
+                fixable = self.fixable,
+                redundant = self.redundant
+            )
+        } else {
+            t!(
+                "summary",
+                missing = self.missing,
+                fixable = self.fixable,
+                redundant = self.redundant
+            )
+        };
+        f.write_str(&summary)
+    }
+}
+
+/// Families of the missing, fixable and redundant fonts, which `check --watch`
+/// compares to report a change that leaves the counts alone
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct StatusFamilies {
+    missing: BTreeSet<String>,
+    fixable: BTreeSet<String>,
+    redundant: BTreeSet<String>,
+}
+
+/// Why `check` fails, from the least to the most severe
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub(crate) enum CheckFailure {
+    /// Nothing is missing, but fonts are redundant (`--strict` only)
+    Redundant,
+    /// Fonts are missing, and `update` can install all of them
+    Fixable,
+    /// Fonts are missing that no source provides
+    Unavailable,
+}
+
+impl StatusSummary {
+    /// Why `check` fails with these counts, if it does. Redundant fonts only
+    /// fail it when `strict`.
+    pub(crate) fn failure(&self, strict: bool) -> Option<CheckFailure> {
+        if self.missing > self.fixable {
+            Some(CheckFailure::Unavailable)
+        } else if self.missing > 0 {
+            Some(CheckFailure::Fixable)
+        } else if strict && self.redundant > 0 {
+            Some(CheckFailure::Redundant)
+        } else {
+            None
+        }
+    }
+}
+
+/// The project's font status, posted by `check --notify-webhook`
+#[derive(Debug, Serialize)]
+pub(crate) struct StatusReport<'a> {
+    /// Path of the project's font_config.toml
+    pub(crate) project: String,
+    /// The summary line of `check --summary-only`
+    pub(crate) summary: String,
+    #[serde(flatten)]
+    pub(crate) counts: StatusSummary,
+    pub(crate) missing_fonts: Vec<&'a TypstFont>,
+    pub(crate) redundant_fonts: Vec<&'a TypstFont>,
+}
+
+/// The report of `check` and `update` with `--format json` or `--format toml`:
+/// the font sets of the project, and one row per font as in the table layout
+#[derive(Debug, Serialize)]
+pub(crate) struct FontReport<'a> {
+    /// Path of the project's font_config.toml
+    pub(crate) project: String,
+    pub(crate) font_dir: String,
+    pub(crate) library_dirs: Vec<String>,
+    /// The summary line of `check --summary-only`
+    pub(crate) summary: String,
+    pub(crate) counts: StatusSummary,
+    pub(crate) required: Vec<&'a TypstFont>,
+    pub(crate) current: Vec<ReportedFace<'a>>,
+    pub(crate) missing: Vec<&'a TypstFont>,
+    pub(crate) redundant: Vec<&'a TypstFont>,
+    pub(crate) library: Vec<ReportedFace<'a>>,
+    pub(crate) fonts: Vec<ReportedRow<'a>>,
+    /// The files installed by `update`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) installed: Vec<LockedFile>,
+}
+
+/// A face of a font file, in a [`FontReport`]
+#[derive(Debug, Serialize)]
+pub(crate) struct ReportedFace<'a> {
+    #[serde(flatten)]
+    pub(crate) font: &'a TypstFont,
+    pub(crate) path: String,
+    /// Index of the face inside a .ttc/.otc collection
+    pub(crate) index: u32,
+}
+
+impl<'a> From<&'a DiscoveredFont> for ReportedFace<'a> {
+    fn from(entry: &'a DiscoveredFont) -> Self {
+        Self {
+            font: &entry.font,
+            path: portable_path(&entry.path),
+            index: entry.index,
+        }
+    }
+}
+
+/// A row of the table layout, in a [`FontReport`]
+#[derive(Debug, Serialize)]
+pub(crate) struct ReportedRow<'a> {
+    #[serde(flatten)]
+    pub(crate) font: &'a TypstFont,
+    /// `missing`, `faux`, `fixable`, `redundant`, `embedded` or `present`
+    pub(crate) status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) source: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) size: Option<u64>,
+}
+
+/// A report as a document of `format`, or `None` for plain text, which the
+/// command prints itself
+pub(crate) fn render_report(
+    report: &impl Serialize,
+    format: ReportFormat,
+) -> Result<Option<String>, String> {
+    match format {
+        ReportFormat::Plain => Ok(None),
+        ReportFormat::Json => serde_json::to_string_pretty(report)
+            .map(|json| Some(json + "\n"))
+            .map_err(|e| e.to_string()),
+        ReportFormat::Toml => toml::to_string(report).map(Some).map_err(|e| e.to_string()),
+    }
+}
+
+/// Which font sets a report shows
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) enum ReportSets {
+    #[default]
+    All,
+    Missing,
+    Redundant,
+}
+
+/// Restricts the fonts shown in reports, e.g. `check --only-missing --family "Noto*"`
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ReportFilter {
+    pub(crate) sets: ReportSets,
+    pub(crate) family: Option<glob::Pattern>,
+}
+
+impl ReportFilter {
+    pub(crate) fn new(sets: ReportSets, family: Option<&str>) -> Result<Self, String> {
+        let family = family
+            .map(glob::Pattern::new)
+            .transpose()
+            .map_err(|e| format!("Invalid family pattern: {e}"))?;
+        Ok(Self { sets, family })
+    }
+
+    fn includes_family(&self, font: &TypstFont) -> bool {
+        let options = glob::MatchOptions {
+            case_sensitive: false,
+            ..Default::default()
+        };
+        self.family
+            .as_ref()
+            .is_none_or(|pattern| pattern.matches_with(&font.family_name, options))
+    }
+
+    fn includes_status(&self, status: FontStatus) -> bool {
+        match self.sets {
+            ReportSets::All => true,
+            ReportSets::Missing => matches!(
+                status,
+                FontStatus::Missing | FontStatus::Faux | FontStatus::Fixable
+            ),
+            ReportSets::Redundant => status == FontStatus::Redundant,
+        }
+    }
+}
+
+/// Status of a font in the project, ordered from most to least severe
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+enum FontStatus {
+    Missing,
+    /// Missing, but another variant of the family is present, so Typst will
+    /// synthesize or substitute it
+    Faux,
+    Fixable,
+    Redundant,
+    Embedded,
+    Present,
+}
+
+impl FontStatus {
+    fn name(self) -> String {
+        match self {
+            FontStatus::Missing => t!("status-missing"),
+            FontStatus::Faux => t!("status-faux"),
+            FontStatus::Fixable => t!("status-fixable"),
+            FontStatus::Redundant => t!("status-redundant"),
+            FontStatus::Embedded => t!("status-embedded"),
+            FontStatus::Present => t!("status-ok"),
+        }
+    }
+
+    fn label(self) -> ColoredString {
+        let name = self.name();
+        match self {
+            FontStatus::Missing => name.red(),
+            FontStatus::Faux => name.magenta(),
+            FontStatus::Fixable => name.yellow(),
+            FontStatus::Redundant => name.blue(),
+            FontStatus::Embedded => name.bright_green(),
+            FontStatus::Present => name.green(),
+        }
+    }
+
+    /// Class of the status in the HTML report, also used to filter by it and as
+    /// the status in JSON and TOML reports
+    fn css_class(self) -> &'static str {
+        match self {
+            FontStatus::Missing => "missing",
+            FontStatus::Faux => "faux",
+            FontStatus::Fixable => "fixable",
+            FontStatus::Redundant => "redundant",
+            FontStatus::Embedded => "embedded",
+            FontStatus::Present => "present",
+        }
+    }
+}
+
+/// One row of the table report
+struct StatusRow<'a> {
+    font: &'a TypstFont,
+    status: FontStatus,
+    entry: Option<&'a DiscoveredFont>,
+    source: Option<&'a Path>,
+    size: Option<u64>,
 }
 
 struct FontSets {
@@ -94,7 +474,14 @@ struct FontSets {
     library_entries: Vec<DiscoveredFont>,
 }
 
-fn get_first_two_segments<P>(repo: &P) -> Option<&Path>
+fn file_name_string(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
+fn get_first_two_segments<P>(repo: &P) -> Option<PathBuf>
 where
     P: AsRef<Path> + ?Sized,
 {
@@ -108,11 +495,29 @@ where
         return None;
     }
 
-    // Go up 2 parent directories to remove the last two components.
+    // Keep the first two components, however deep the rest of the path is.
     // Example:
-    //   "user_name/my_repo/dir/sad.txt".parent() -> "user_name/my_repo/dir"
-    //                              .parent() -> "user_name/my_repo"
-    p.parent().and_then(|one_up| one_up.parent())
+    //   "user_name/my_repo/dir/sad.txt" -> "user_name/my_repo"
+    Some(p.components().take(2).collect())
+}
+
+/// The repository and the raw download URL of a GitHub library path such as
+/// "owner/repo/Lato/Lato-Regular.ttf", on the default branch or at the branch,
+/// tag or commit `reference` the library is pinned to
+pub(crate) fn github_file_url(
+    path: &Path,
+    reference: Option<&str>,
+) -> Result<(String, String), String> {
+    let invalid_path = || format!("Invalid GitHub font path {:?}", path);
+    let github_repo = get_first_two_segments(path).ok_or_else(invalid_path)?;
+    let font_relative_path = get_remaining_after_two_segments(path).ok_or_else(invalid_path)?;
+    let repo = github_repo.to_string_lossy().replace('\\', "/");
+    let url = github::raw_file_url(
+        &repo,
+        reference,
+        &font_relative_path.to_string_lossy().replace('\\', "/"),
+    );
+    Ok((repo, url))
 }
 
 fn get_remaining_after_two_segments<P>(repo: &P) -> Option<&Path>
@@ -135,6 +540,25 @@ where
     }
 }
 
+/// The Typst packages of `pack_tree`, those they import included, each with
+/// the families it requires
+fn package_provenance(pack_tree: &[PackNode]) -> Vec<String> {
+    let mut lines = Vec::new();
+    for node in pack_tree {
+        if node.kind == NodeKind::Package && !node.fonts.is_empty() {
+            let mut families = node
+                .fonts
+                .iter()
+                .map(|font| font.family_name.as_str())
+                .collect::<Vec<_>>();
+            families.dedup();
+            lines.push(format!("{}: {}", node.name, families.join(", ")));
+        }
+        lines.extend(package_provenance(&node.packs));
+    }
+    lines
+}
+
 fn font_entries_to_set(entries: &[DiscoveredFont]) -> BTreeSet<TypstFont> {
     entries.iter().map(|entry| entry.font.clone()).collect()
 }
@@ -145,7 +569,7 @@ fn font_is_satisfied_by_entries(font: &TypstFont, entries: &[DiscoveredFont]) ->
         .any(|entry| font_entry_satisfies(entry, font))
 }
 
-fn font_entry_satisfies(entry: &DiscoveredFont, intent: &TypstFont) -> bool {
+pub(crate) fn font_entry_satisfies(entry: &DiscoveredFont, intent: &TypstFont) -> bool {
     if entry.font.family_name != intent.family_name {
         return false;
     }
@@ -199,7 +623,7 @@ fn entry_has_variant_axis(entry: &DiscoveredFont) -> bool {
         || standard.wdth.is_some()
 }
 
-fn format_discovered_font(entry: &DiscoveredFont) -> String {
+pub(crate) fn format_discovered_variant(entry: &DiscoveredFont) -> String {
     let standard = StandardAxes::parse(&entry.axes);
     let weight = standard
         .wght
@@ -211,8 +635,8 @@ fn format_discovered_font(entry: &DiscoveredFont) -> String {
         .unwrap_or_else(|| stretch_to_number(entry.font.stretch).to_string());
 
     format!(
-        "{:<30}    (style: {:?}, weight: {}, stretch: {})",
-        entry.font.family_name, entry.font.style, weight, stretch
+        "(style: {:?}, weight: {}, stretch: {})",
+        entry.font.style, weight, stretch
     )
 }
 
@@ -242,13 +666,161 @@ fn stretch_to_number(stretch: FontStretch) -> u16 {
     (stretch.to_ratio().get() * 1000.0) as u16
 }
 
+fn sort_status_rows(rows: &mut [StatusRow], sort_by: SortKey) {
+    match sort_by {
+        SortKey::Family => rows.sort_by_key(|row| row.font.family_name.to_lowercase()),
+        SortKey::Style => rows.sort_by_key(|row| row.font.style),
+        SortKey::Weight => rows.sort_by_key(|row| row.font.weight),
+        SortKey::Stretch => rows.sort_by_key(|row| row.font.stretch),
+        SortKey::Status => rows.sort_by_key(|row| row.status),
+        SortKey::Source => rows.sort_by_key(|row| row.source.map(Path::to_path_buf)),
+        SortKey::Size => rows.sort_by_key(|row| std::cmp::Reverse(row.size)),
+    }
+}
+
+/// How far `actual` is from the `intent`ed variant: a different style weighs
+/// more than any difference in weight or stretch
+fn variant_distance(actual: &TypstFont, intent: &TypstFont) -> (bool, u16, u16) {
+    (
+        actual.style != intent.style,
+        actual.weight.distance(intent.weight),
+        stretch_to_number(actual.stretch).abs_diff(stretch_to_number(intent.stretch)),
+    )
+}
+
+/// Describe what Typst has to fake when rendering `intent` with `actual`
+fn faux_difference(actual: &TypstFont, intent: &TypstFont) -> String {
+    let mut differences = Vec::new();
+    if actual.style != intent.style {
+        differences.push(match intent.style {
+            FontStyle::Italic => t!("difference-italic"),
+            FontStyle::Oblique => t!("difference-oblique"),
+            FontStyle::Normal => t!("difference-normal"),
+        });
+    }
+    if actual.weight < intent.weight {
+        differences.push(t!("difference-bold"));
+    } else if actual.weight > intent.weight {
+        differences.push(t!("difference-lighter"));
+    }
+    if actual.stretch != intent.stretch {
+        differences.push(t!("difference-width"));
+    }
+    differences.join(&t!("difference-join"))
+}
+
+fn group_by_family<'a, I>(fonts: I) -> BTreeMap<&'a str, Vec<&'a TypstFont>>
+where
+    I: IntoIterator<Item = &'a TypstFont>,
+{
+    let mut families = BTreeMap::<&str, Vec<&TypstFont>>::new();
+    for font in fonts {
+        families.entry(&font.family_name).or_default().push(font);
+    }
+    families
+}
+
+/// The version in the name table of the font file at `path`, e.g. "2.37"
+fn font_version(path: &Path) -> Option<String> {
+    face_version(path, 0)
+}
+
+/// The version of the face at `index` of the font file at `path`, see [`font_version`]
+fn face_version(path: &Path, index: u32) -> Option<String> {
+    let data = fs::read(path).ok()?;
+    let face = ttf_parser::Face::parse(&data, index).ok()?;
+    let version = face
+        .names()
+        .into_iter()
+        .filter(|name| name.name_id == ttf_parser::name_id::VERSION && name.is_unicode())
+        .find_map(|name| name.to_string())?;
+    let version = version.trim();
+    let version = version.strip_prefix("Version ").unwrap_or(version);
+    Some(
+        version
+            .split([' ', ';'])
+            .next()
+            .unwrap_or(version)
+            .to_string(),
+    )
+}
+
+/// A required font installed in the project with another version than a
+/// system font satisfying it, see [`FontManager::print_system_version_mismatches`]
+#[derive(Debug)]
+struct VersionMismatch<'a> {
+    font: &'a TypstFont,
+    version: String,
+    installed: &'a DiscoveredFont,
+    system_version: String,
+    system: &'a DiscoveredFont,
+}
+
+/// The faces among `system_entries` that satisfy one of the `required` fonts
+/// with another version than the face of `installed` satisfying it. System
+/// entries in the project font directory `font_dir` are the installed ones.
+fn system_version_mismatches<'a>(
+    required: impl IntoIterator<Item = &'a TypstFont>,
+    installed: &'a [DiscoveredFont],
+    system_entries: &'a [DiscoveredFont],
+    font_dir: &Path,
+) -> Vec<VersionMismatch<'a>> {
+    let mut mismatches = Vec::new();
+    for font in required {
+        let Some(entry) = installed
+            .iter()
+            .find(|entry| font_entry_satisfies(entry, font))
+        else {
+            continue;
+        };
+        let Some(version) = face_version(&entry.path, entry.index) else {
+            continue;
+        };
+        let systems = system_entries.iter().filter(|system| {
+            font_entry_satisfies(system, font) && !system.path.starts_with(font_dir)
+        });
+        for system in systems {
+            if let Some(system_version) = face_version(&system.path, system.index)
+                && system_version != version
+            {
+                mismatches.push(VersionMismatch {
+                    font,
+                    version: version.clone(),
+                    installed: entry,
+                    system_version,
+                    system,
+                });
+            }
+        }
+    }
+    mismatches
+}
+
+/// Sort key of the library files satisfying a font, see [`FontManager::candidate_rank`]
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd)]
+struct CandidateRank {
+    pinned: bool,
+    priority: std::cmp::Reverse<i32>,
+    other_format: bool,
+    static_font: bool,
+    path: String,
+}
+
 fn select_best_font_entry<'a>(
     font: &TypstFont,
     entries: &'a [DiscoveredFont],
+) -> Option<&'a DiscoveredFont> {
+    select_best_font_entry_by(font, entries, |_| true)
+}
+
+fn select_best_font_entry_by<'a>(
+    font: &TypstFont,
+    entries: &'a [DiscoveredFont],
+    accept: impl Fn(&DiscoveredFont) -> bool,
 ) -> Option<&'a DiscoveredFont> {
     entries
         .iter()
-        .filter(|entry| font_entry_satisfies(entry, font))
+        .filter(|entry| accept(entry) && font_entry_satisfies(entry, font))
         .min_by_key(|entry| {
             (
                 !entry_has_variant_axis(entry),
@@ -258,52 +830,108 @@ fn select_best_font_entry<'a>(
 }
 
 impl<'a> FontManager<'a> {
-    pub(crate) fn new(args: &'a FontCommand, action: &'a str) -> Result<Self, String> {
+    pub(crate) fn new(args: &'a FontCommand, action: &'a str) -> Result<Self, Error> {
         let config_file = Self::resolve_config_file(&args.project_or_config);
 
+        // Fail fast, before a possibly slow library scan
         if !config_file.exists() {
-            return Err(format!("Config file not found: {:?}", config_file));
+            return Err(Error::Project(t!(
+                "error-config-not-found",
+                path = format!("{config_file:?}")
+            )));
         }
 
+        let library_dirs = Self::library_dirs(args).map_err(Error::Usage)?;
+        let library_entries = create_font_entries_from_dirs(&library_dirs)?;
+        Self::with_library_entries(args, action, library_dirs, library_entries)
+            .map_err(Error::Project)
+    }
+
+    /// The library sources selected on the command line
+    pub(crate) fn library_dirs(args: &FontCommand) -> Result<LibraryDirs, String> {
         // use user-specified font directories (args.library) if provided,
         // otherwise, use the system's default font directories.
-        let library_dirs = if args.github {
-            LibraryDirs::GitHub(
-                args.library
-                    .clone()
-                    .expect("GitHub repository not provided"),
+        let mut library_dirs = LibraryDirs::new(args.library.as_deref(), args.github)?;
+        library_dirs.disable_sources(&args.disable_source)?;
+        Ok(library_dirs)
+    }
+
+    /// Resolve the project against already indexed library fonts
+    pub(crate) fn with_library_entries(
+        args: &'a FontCommand,
+        action: &'a str,
+        library_dirs: LibraryDirs,
+        library_entries: Vec<DiscoveredFont>,
+    ) -> Result<Self, String> {
+        let _span = trace::span("resolve", "resolve project");
+        let config_file = Self::resolve_config_file(&args.project_or_config);
+
+        if !config_file.exists() {
+            return Err(t!(
+                "error-config-not-found",
+                path = format!("{config_file:?}")
+            ));
+        }
+
+        // Deserialize the font configuration from font_config.toml
+        let font_config = deserialize_fonts_from_file(&config_file).map_err(|e| {
+            t!(
+                "error-config-parse",
+                path = format!("{config_file:?}"),
+                error = format!("{e:#}")
             )
+        })?;
+
+        // The members of the required packs are required like the listed fonts
+        let mut pack_tree = if font_config.pack.is_empty() {
+            Vec::new()
         } else {
-            LibraryDirs::Local(
-                args.library
-                    .clone()
-                    .unwrap_or_else(utils::font_utils::get_system_font_directories),
-            )
+            let packs = packs::load_packs(&library_dirs)?;
+            packs::resolve_packs(&font_config.pack, &packs, &library_entries)?
         };
 
-        // Deserialize the font configuration from font_config.toml
-        let font_config = deserialize_fonts_from_file(&config_file)
-            .map_err(|_| "Failed to parse font config file")?;
-
         // Resolve the absolute path of the project's font directory if specified in font_config.toml
         // Otherwise, use the default relative path "fonts"
         let absolute_font_dir = Self::resolve_font_directory(&config_file, &font_config)?;
 
+        // So are the fonts declared by the Typst packages the project imports
+        if font_config.package_fonts != Some(false) {
+            let project_dir = config_file.parent().unwrap_or(Path::new("."));
+            let project_dir = if project_dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                project_dir
+            };
+            pack_tree.extend(typst_packages::package_tree(
+                project_dir,
+                &absolute_font_dir,
+            )?);
+        }
+
         // Initialize the FontSets struct
-        let font_sets =
-            Self::initialize_font_sets(&library_dirs, &font_config, &absolute_font_dir)?;
+        let font_sets = Self::initialize_font_sets(
+            library_entries,
+            &font_config,
+            &pack_tree,
+            &absolute_font_dir,
+        )?;
 
         Ok(FontManager {
             config_file,
             font_config,
+            pack_tree,
             library_dirs,
             absolute_font_dir,
             font_sets,
             action,
+            output: args.output,
+            sort_by: args.sort_by,
+            report_filter: ReportFilter::default(),
+            namespace_sources: args.namespace_sources,
         })
     }
 
-    fn resolve_config_file(project_or_config: &Path) -> PathBuf {
+    pub(crate) fn resolve_config_file(project_or_config: &Path) -> PathBuf {
         if project_or_config.is_dir() {
             project_or_config.join("font_config.toml")
         } else {
@@ -311,6 +939,24 @@ impl<'a> FontManager<'a> {
         }
     }
 
+    /// The font directory of the project configured by `config_file`
+    pub(crate) fn project_font_dir(config_file: &Path) -> Result<PathBuf, String> {
+        if !config_file.exists() {
+            return Err(t!(
+                "error-config-not-found",
+                path = format!("{config_file:?}")
+            ));
+        }
+        let font_config = deserialize_fonts_from_file(config_file).map_err(|e| {
+            t!(
+                "error-config-parse",
+                path = format!("{config_file:?}"),
+                error = format!("{e:#}")
+            )
+        })?;
+        Self::resolve_font_directory(config_file, &font_config)
+    }
+
     fn resolve_font_directory(
         config_file: &Path,
         font_config: &FontConfig,
@@ -338,12 +984,20 @@ impl<'a> FontManager<'a> {
     }
 
     fn initialize_font_sets(
-        library_dirs: &LibraryDirs,
+        library_entries: Vec<DiscoveredFont>,
         font_config: &FontConfig,
+        pack_tree: &[PackNode],
         font_dir: &Path,
     ) -> Result<FontSets, String> {
-        let required = BTreeSet::from_iter(font_config.fonts.clone());
-        let current_entries = create_font_entries(font_dir);
+        let mut required = BTreeSet::from_iter(font_config.fonts.clone());
+        required.extend(pack_tree.iter().flat_map(PackNode::all_fonts).cloned());
+        let ignored = |entry: &DiscoveredFont| font_config.ignores_family(&entry.font.family_name);
+        let mut current_entries = create_font_entries(font_dir);
+        // Files awaiting review aren't part of the project yet
+        let incoming = quarantine::incoming_dir(font_dir);
+        current_entries.retain(|entry| !ignored(entry) && !entry.path.starts_with(&incoming));
+        let mut library_entries = library_entries;
+        library_entries.retain(|entry| !ignored(entry));
         let current = font_entries_to_set(&current_entries);
         let embedded: BTreeSet<TypstFont> = deserialize_fonts_from_toml(EMBEDDED_FONTS)
             .map_err(|_| "Failed to parse embedded fonts")?
@@ -369,8 +1023,6 @@ impl<'a> FontManager<'a> {
             .map(|entry| entry.font.clone())
             .collect();
 
-        let library_entries = create_font_entries_from_dirs(&library_dirs);
-
         Ok(FontSets {
             required,
             current,
@@ -382,117 +1034,740 @@ impl<'a> FontManager<'a> {
         })
     }
 
-    pub(crate) fn print_status(&self) {
-        self.print_header();
-        self.print_directories(); // Print the directories used by the font manager
-        self.print_legend();
-        self.print_font_sets();
+    /// Restrict the fonts shown by [`Self::print_status`]
+    pub(crate) fn with_report_filter(mut self, report_filter: ReportFilter) -> Self {
+        self.report_filter = report_filter;
+        self
     }
 
-    fn print_header(&self) {
-        println!("\n=== {} ===\n", "Typst Font Manager".bold());
-        println!("- Action: {}\n", self.action);
+    /// The missing fonts that no library file satisfies, by family
+    pub(crate) fn unresolvable_fonts(&self) -> BTreeMap<&str, Vec<&TypstFont>> {
+        let mut families = BTreeMap::<&str, Vec<&TypstFont>>::new();
+        for font in &self.font_sets.missing {
+            if self.select_library_candidate(font).is_none() {
+                families.entry(&font.family_name).or_default().push(font);
+            }
+        }
+        families
     }
 
-    fn print_directories(&self) {
-        println!("- Config file: {:?}", self.config_file);
-        println!("\n- Font library directories:");
-        for dir in &self.library_dirs {
-            println!("  {dir:?}");
+    pub(crate) fn required_fonts(&self) -> &BTreeSet<TypstFont> {
+        &self.font_sets.required
+    }
+
+    /// The faces installed in the project's font directory
+    pub(crate) fn installed_entries(&self) -> &[DiscoveredFont] {
+        &self.font_sets.current_entries
+    }
+
+    pub(crate) fn library_entries(&self) -> &[DiscoveredFont] {
+        &self.font_sets.library_entries
+    }
+
+    /// The numbers of required and of missing fonts
+    pub(crate) fn installed_counts(&self) -> (usize, usize) {
+        (self.font_sets.required.len(), self.font_sets.missing.len())
+    }
+
+    pub(crate) fn summary(&self) -> StatusSummary {
+        StatusSummary {
+            missing: self.font_sets.missing.len(),
+            fixable: self
+                .font_sets
+                .missing
+                .iter()
+                .filter(|font| self.select_library_candidate(font).is_some())
+                .count(),
+            redundant: self.font_sets.redundant.len(),
+            faux: self
+                .font_sets
+                .missing
+                .iter()
+                .filter(|font| self.faux_fallback(font).is_some())
+                .count(),
         }
-        println!(
-            "\n- Project font directory: {:?}",
-            self.font_config.font_dir.as_deref().unwrap_or("fonts")
-        );
     }
 
-    fn print_legend(&self) {
-        if !self.font_sets.required.is_empty() {
-            println!("\n※ Legend:");
-            println!(
-                "  {} - Font is required and exists in the project",
-                "●".green()
-            );
-            println!(
-                "  {} - Font is required and is embedded in the compiler",
-                "◆".bright_green()
-            );
-            println!(
-                "  {} - Font is not required but exists in the project",
-                "●".blue()
-            );
-            println!(
-                "  {} - Font is missing but can be fixed (available in font library)",
-                "○".yellow()
-            );
-            println!("  {} - Font is missing", "○".red());
+    pub(crate) fn status_families(&self) -> StatusFamilies {
+        let family_names = |fonts: Vec<&TypstFont>| {
+            fonts
+                .into_iter()
+                .map(|font| font.family_name.clone())
+                .collect()
+        };
+        let missing = self.font_sets.missing.iter();
+        StatusFamilies {
+            missing: family_names(missing.clone().collect()),
+            fixable: family_names(
+                missing
+                    .filter(|font| self.select_library_candidate(font).is_some())
+                    .collect(),
+            ),
+            redundant: family_names(self.font_sets.redundant.iter().collect()),
         }
     }
 
-    fn print_font_sets(&self) {
-        self.print_font_set_with(
-            "Current fonts",
-            &self.font_sets.current,
-            |font| {
-                if self.font_sets.required.contains(font)
-                    || self.current_entry_satisfies_required(font)
-                {
-                    "●".green()
-                } else {
-                    "●".blue()
-                }
-            },
-            |font| self.format_current_font(font),
-        );
+    /// The installed files none of whose faces the project requires
+    pub(crate) fn redundant_files(&self) -> Vec<&Path> {
+        let mut files = BTreeMap::<&Path, bool>::new();
+        for entry in &self.font_sets.current_entries {
+            *files.entry(&entry.path).or_insert(true) &=
+                self.font_sets.redundant.contains(&entry.font);
+        }
+        files
+            .into_iter()
+            .filter(|(_, redundant)| *redundant)
+            .map(|(path, _)| path)
+            .collect()
+    }
 
-        self.print_font_set("Required fonts", &self.font_sets.required, |font| {
-            if self.font_sets.embedded.contains(font) {
-                "◆".bright_green()
-            } else if font_is_satisfied_by_entries(font, &self.font_sets.current_entries) {
-                "●".green()
-            } else if self.select_library_candidate(font).is_some() {
+    /// Whether every required font is installed and may be embedded, and no
+    /// font is redundant, see [`crate::fingerprint`]
+    pub(crate) fn is_complete(&self) -> bool {
+        self.font_sets.missing.is_empty()
+            && self.font_sets.redundant.is_empty()
+            && self.fonts_forbidding_embedding().is_empty()
+    }
+
+    pub(crate) fn status_report(&self) -> StatusReport<'_> {
+        let counts = self.summary();
+        StatusReport {
+            project: portable_path(&self.config_file),
+            summary: counts.to_string(),
+            counts,
+            missing_fonts: self.font_sets.missing.iter().collect(),
+            redundant_fonts: self.font_sets.redundant.iter().collect(),
+        }
+    }
+
+    /// The font sets as a [`FontReport`], with the rows the report filter selects
+    pub(crate) fn report(&self) -> FontReport<'_> {
+        let mut rows = self.status_rows();
+        rows.retain(|row| {
+            self.report_filter.includes_status(row.status)
+                && self.report_filter.includes_family(row.font)
+        });
+        sort_status_rows(&mut rows, self.sort_by);
+        let counts = self.summary();
+        FontReport {
+            project: portable_path(&self.config_file),
+            font_dir: portable_path(&self.absolute_font_dir),
+            library_dirs: self
+                .library_dirs
+                .into_iter()
+                .map(|dir| portable_path(dir))
+                .collect(),
+            summary: counts.to_string(),
+            counts,
+            required: self.font_sets.required.iter().collect(),
+            current: self
+                .font_sets
+                .current_entries
+                .iter()
+                .map(Into::into)
+                .collect(),
+            missing: self.font_sets.missing.iter().collect(),
+            redundant: self.font_sets.redundant.iter().collect(),
+            library: self
+                .font_sets
+                .library_entries
+                .iter()
+                .map(Into::into)
+                .collect(),
+            fonts: rows
+                .iter()
+                .map(|row| ReportedRow {
+                    font: row.font,
+                    status: row.status.css_class(),
+                    source: row.source.map(portable_path),
+                    size: row.size,
+                })
+                .collect(),
+            installed: Vec::new(),
+        }
+    }
+
+    pub(crate) fn print_status(&self) {
+        self.print_header();
+        self.print_directories(); // Print the directories used by the font manager
+        match self.output {
+            OutputLayout::Tree => {
+                self.print_legend();
+                self.print_font_sets();
+            }
+            OutputLayout::Table => self.print_font_table(),
+        }
+        if self.report_filter.sets != ReportSets::Redundant {
+            self.print_faux_warnings();
+            if cfg!(target_os = "macos") {
+                self.print_inactive_system_fonts(Path::new(macos_assets::ASSETS_ROOT));
+            }
+        }
+    }
+
+    /// Print the fonts the project requires as a tree: those listed in `fonts`
+    /// and those of each required pack, with the packs it includes
+    pub(crate) fn print_requirement_tree(&self) {
+        let label = |font: &TypstFont| {
+            let bullet = if !self.font_sets.missing.contains(font) {
+                "●".green()
+            } else if self.select_library_candidate(font).is_some() {
                 "○".yellow()
             } else {
                 "○".red()
+            };
+            format!("{bullet} {} {}", font.family_name, font.variant_string())
+        };
+        let root = self
+            .config_file
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        progress!(
+            "{}",
+            packs::render_tree(&root, &self.font_config.fonts, &self.pack_tree, &label)
+        );
+    }
+
+    /// The required fonts whose face, installed or else the library file that
+    /// `update` would install, forbids embedding it in a PDF
+    fn fonts_forbidding_embedding(&self) -> Vec<(&TypstFont, &DiscoveredFont)> {
+        self.font_sets
+            .required
+            .iter()
+            .filter_map(|font| {
+                let entry = self
+                    .font_sets
+                    .current_entries
+                    .iter()
+                    .find(|entry| font_entry_satisfies(entry, font))
+                    .or_else(|| self.select_library_candidate(font))?;
+                let fs_type = entry.metadata.fs_type?;
+                Embedding::forbids_pdf_embedding(fs_type).then_some((font, entry))
+            })
+            .collect()
+    }
+
+    /// `check --embedding-policy`: report the required fonts that may not be
+    /// embedded in a PDF, and fail if the policy denies them
+    pub(crate) fn check_embedding(&self, policy: RedistributionPolicy) -> Result<(), String> {
+        if policy == RedistributionPolicy::Allow {
+            return Ok(());
+        }
+        let fonts = self.fonts_forbidding_embedding();
+        if fonts.is_empty() {
+            return Ok(());
+        }
+
+        progress!(
+            "\n- {} {}:",
+            t!("set-no-embedding").bold(),
+            t!("set-total", total = fonts.len())
+        );
+        for (font, entry) in &fonts {
+            progress!(
+                "  {} {} {}: {} ({})",
+                "✗".red(),
+                font.family_name,
+                font.variant_string(),
+                entry.display_path(),
+                Embedding::describe(entry.metadata.fs_type.unwrap_or_default())
+            );
+        }
+        if policy == RedistributionPolicy::Deny {
+            return Err(t!("error-no-embedding", count = fonts.len()));
+        }
+        Ok(())
+    }
+
+    /// Point out the unresolvable families that macOS offers as downloadable
+    /// fonts in the asset catalogs below `assets_root`, which only need to be
+    /// activated to become system fonts
+    fn print_inactive_system_fonts(&self, assets_root: &Path) {
+        let downloadable = macos_assets::downloadable_families(assets_root);
+        let mut families = self
+            .unresolvable_fonts()
+            .into_keys()
+            .filter(|family| downloadable.contains(*family))
+            .collect::<Vec<_>>();
+        if families.is_empty() {
+            return;
+        }
+        // Activated fonts are system fonts, just not part of the given library
+        let activated = create_font_entries_from_dirs(&LibraryDirs::Local(
+            utils::font_utils::macos_asset_font_dirs(assets_root),
+        ))
+        .unwrap_or_default();
+        families.retain(|family| {
+            !activated
+                .iter()
+                .any(|entry| entry.font.family_name == *family)
+        });
+        if families.is_empty() {
+            return;
+        }
+
+        progress!(
+            "\n- {} {}:",
+            t!("set-inactive-system").bold(),
+            t!("set-total", total = families.len())
+        );
+        for family in families {
+            progress!("  {} {family}", "◌".blue());
+        }
+        progress!("  {}", t!("inactive-system-note"));
+    }
+
+    /// The project font Typst falls back to for a missing variant of a family
+    /// that is otherwise present in the project
+    fn faux_fallback(&self, font: &TypstFont) -> Option<&DiscoveredFont> {
+        if !self.font_sets.missing.contains(font) {
+            return None;
+        }
+        self.font_sets
+            .current_entries
+            .iter()
+            .filter(|entry| entry.font.family_name == font.family_name)
+            .min_by_key(|entry| variant_distance(&entry.font, font))
+    }
+
+    /// Warn about the required fonts installed in the project whose family is
+    /// also among the `system_entries` with another version: local previews,
+    /// where Typst uses the system fonts, may then differ from CI output, where
+    /// it uses the project fonts. With `faces`, every differing face is listed
+    /// with its files, otherwise one line per family gives the versions.
+    pub(crate) fn print_system_version_mismatches(
+        &self,
+        system_entries: &[DiscoveredFont],
+        faces: bool,
+    ) {
+        let mismatches = system_version_mismatches(
+            self.font_sets
+                .required
+                .iter()
+                .filter(|font| self.report_filter.includes_family(font)),
+            &self.font_sets.current_entries,
+            system_entries,
+            &self.absolute_font_dir,
+        );
+        if mismatches.is_empty() {
+            return;
+        }
+
+        let lines = if faces {
+            mismatches
+                .iter()
+                .map(|mismatch| {
+                    t!(
+                        "shadow-face",
+                        font = format!(
+                            "{} {}",
+                            mismatch.font.family_name,
+                            mismatch.font.variant_string()
+                        ),
+                        version = mismatch.version.as_str(),
+                        file = mismatch.installed.display_path(),
+                        system_version = mismatch.system_version.as_str(),
+                        system_file = mismatch.system.display_path()
+                    )
+                })
+                .collect::<Vec<_>>()
+        } else {
+            let mut families = BTreeMap::<&str, (BTreeSet<&str>, BTreeSet<&str>)>::new();
+            for mismatch in &mismatches {
+                let (versions, system_versions) =
+                    families.entry(&mismatch.font.family_name).or_default();
+                versions.insert(&mismatch.version);
+                system_versions.insert(&mismatch.system_version);
             }
+            let join =
+                |versions: BTreeSet<&str>| versions.into_iter().collect::<Vec<_>>().join(", ");
+            families
+                .into_iter()
+                .map(|(family, (versions, system_versions))| {
+                    t!(
+                        "shadow-family",
+                        family = family,
+                        version = join(versions),
+                        system_version = join(system_versions)
+                    )
+                })
+                .collect()
+        };
+        progress!(
+            "\n- {} {}:",
+            t!("set-shadowed-system").bold(),
+            t!("set-total", total = lines.len())
+        );
+        for line in lines {
+            progress!("  {} {line}", "⚠".yellow());
+        }
+        progress!("  {}", t!("shadow-note"));
+    }
+
+    /// Warn about missing bold or italic variants that Typst will fake from
+    /// another variant of the family, which silently changes the metrics
+    fn print_faux_warnings(&self) {
+        let warnings = self.faux_warnings();
+        if warnings.is_empty() {
+            return;
+        }
+
+        progress!(
+            "\n- {} {}:",
+            t!("set-synthesized").bold(),
+            t!("set-total", total = warnings.len())
+        );
+        for warning in warnings {
+            progress!("  {} {warning}", "⚠".magenta());
+        }
+        progress!("  {}", t!("faux-note"));
+    }
+
+    /// The warnings of [`Self::print_faux_warnings`], one per missing font
+    fn faux_warnings(&self) -> Vec<String> {
+        self.font_sets
+            .missing
+            .iter()
+            .filter(|font| self.report_filter.includes_family(font))
+            .filter_map(|font| {
+                let fallback = self.faux_fallback(font)?;
+                Some(t!(
+                    "faux-warning",
+                    font = format!("{} {}", font.family_name, font.variant_string()),
+                    file = fallback
+                        .path
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .into_owned(),
+                    variant = format_discovered_variant(fallback),
+                    difference = faux_difference(&fallback.font, font)
+                ))
+            })
+            .collect()
+    }
+
+    /// The status of `report --html`: every row of the table layout with the
+    /// version and license of its file, and the warnings of `check`
+    pub(crate) fn html_report(&self) -> HtmlReport {
+        let mut rows = self.status_rows();
+        sort_status_rows(&mut rows, self.sort_by);
+        let rows = rows
+            .iter()
+            .map(|row| HtmlRow {
+                family: row.font.family_name.clone(),
+                style: format!("{:?}", row.font.style),
+                weight: row.font.weight.to_number(),
+                stretch: stretch_to_number(row.font.stretch),
+                status: row.status.css_class(),
+                status_label: row.status.name(),
+                source: row.source.map(|source| self.format_source(source)),
+                version: row.entry.and_then(|entry| font_version(&entry.path)),
+                license: row
+                    .entry
+                    .and_then(|entry| entry.metadata.license)
+                    .map(|license| license.name().to_string()),
+                size: row.size.map(format_size),
+            })
+            .collect();
+
+        let mut warnings = self.faux_warnings();
+        warnings.extend(
+            self.fonts_forbidding_embedding()
+                .into_iter()
+                .map(|(font, entry)| {
+                    format!(
+                        "{}: {} {}: {} ({})",
+                        t!("set-no-embedding"),
+                        font.family_name,
+                        font.variant_string(),
+                        entry.display_path(),
+                        Embedding::describe(entry.metadata.fs_type.unwrap_or_default())
+                    )
+                }),
+        );
+        HtmlReport {
+            project: portable_path(&self.config_file),
+            library_dirs: (&self.library_dirs)
+                .into_iter()
+                .map(|dir| dir.display().to_string())
+                .collect(),
+            summary: self.summary().to_string(),
+            rows,
+            warnings,
+        }
+    }
+
+    fn print_font_table(&self) {
+        let mut rows = self.status_rows();
+        rows.retain(|row| {
+            self.report_filter.includes_status(row.status)
+                && self.report_filter.includes_family(row.font)
         });
+        sort_status_rows(&mut rows, self.sort_by);
+
+        let mut table = Table::new(vec![
+            t!("column-family"),
+            t!("column-style"),
+            t!("column-weight"),
+            t!("column-stretch"),
+            t!("column-status"),
+            t!("column-source"),
+            t!("column-size"),
+        ]);
+        for row in &rows {
+            table.push_row(vec![
+                row.font.family_name.normal(),
+                format!("{:?}", row.font.style).normal(),
+                row.font.weight.to_number().to_string().normal(),
+                stretch_to_number(row.font.stretch).to_string().normal(),
+                row.status.label(),
+                row.source
+                    .map_or_else(|| "-".to_string(), |source| self.format_source(source))
+                    .normal(),
+                row.size
+                    .map_or_else(|| "-".to_string(), format_size)
+                    .normal(),
+            ]);
+        }
+
+        progress!(
+            "\n- {} {}:",
+            t!("set-fonts").bold(),
+            t!("set-total", total = rows.len())
+        );
+        progress!("{}", table.render());
+    }
 
-        self.print_font_set("Missing fonts", &self.font_sets.missing, |font| {
-            if self.select_library_candidate(font).is_some() {
+    /// One row per required font plus one per redundant project font
+    fn status_rows(&self) -> Vec<StatusRow<'_>> {
+        let mut rows = Vec::new();
+
+        for font in &self.font_sets.required {
+            let current = select_best_font_entry(font, &self.font_sets.current_entries);
+            let (status, source) = if self.font_sets.embedded.contains(font) {
+                (FontStatus::Embedded, None)
+            } else if let Some(entry) = current {
+                (FontStatus::Present, Some(entry))
+            } else if let Some(entry) = self.select_library_candidate(font) {
+                (FontStatus::Fixable, Some(entry))
+            } else if let Some(entry) = self.faux_fallback(font) {
+                (FontStatus::Faux, Some(entry))
+            } else {
+                (FontStatus::Missing, None)
+            };
+            rows.push(self.status_row(font, status, source));
+        }
+
+        for font in &self.font_sets.redundant {
+            let entry = self
+                .font_sets
+                .current_entries
+                .iter()
+                .find(|entry| entry.font == *font);
+            rows.push(self.status_row(font, FontStatus::Redundant, entry));
+        }
+
+        rows
+    }
+
+    fn status_row<'s>(
+        &'s self,
+        font: &'s TypstFont,
+        status: FontStatus,
+        entry: Option<&'s DiscoveredFont>,
+    ) -> StatusRow<'s> {
+        let path = entry.map(|entry| entry.path.as_path());
+        StatusRow {
+            font,
+            status,
+            entry,
+            // Show project files relative to the project font directory
+            source: path.map(|path| path.strip_prefix(&self.absolute_font_dir).unwrap_or(path)),
+            size: path
+                .and_then(|path| fs::metadata(path).ok())
+                .map(|metadata| metadata.len()),
+        }
+    }
+
+    /// Display a font file path, prefixed with its library source if requested
+    fn format_source(&self, path: &Path) -> String {
+        match self.library_dirs.locate(path) {
+            Some((source, relative)) if self.namespace_sources => {
+                format!("[{source}] {}", relative.display())
+            }
+            _ => path.display().to_string(),
+        }
+    }
+
+    fn print_header(&self) {
+        progress!("\n=== {} ===\n", t!("report-title").bold());
+        progress!("{}\n", t!("report-action", action = self.action));
+    }
+
+    fn print_directories(&self) {
+        progress!(
+            "{}",
+            t!(
+                "report-config-file",
+                path = format!("{:?}", self.config_file)
+            )
+        );
+        progress!("\n{}", t!("report-library-dirs"));
+        for dir in &self.library_dirs {
+            progress!("  {dir:?}");
+        }
+        let font_dir = self.font_config.font_dir.as_deref().unwrap_or("fonts");
+        progress!(
+            "\n{}",
+            t!("report-font-dir", path = format!("{font_dir:?}"))
+        );
+        if !self.font_config.pack.is_empty() {
+            progress!(
+                "\n{}",
+                t!("report-packs", packs = self.font_config.pack.join(", "))
+            );
+        }
+        let packages = package_provenance(&self.pack_tree);
+        if !packages.is_empty() {
+            progress!("\n{}", t!("report-packages"));
+            for package in packages {
+                progress!("  {package}");
+            }
+        }
+    }
+
+    fn print_legend(&self) {
+        if !self.font_sets.required.is_empty() {
+            progress!("\n{}", t!("legend-title"));
+            progress!("  {} - {}", "●".green(), t!("legend-present"));
+            progress!("  {} - {}", "◆".bright_green(), t!("legend-embedded"));
+            progress!("  {} - {}", "●".blue(), t!("legend-redundant"));
+            progress!("  {} - {}", "○".yellow(), t!("legend-fixable"));
+            progress!("  {} - {}", "◐".magenta(), t!("legend-faux"));
+            progress!("  {} - {}", "○".red(), t!("legend-missing"));
+        }
+    }
+
+    fn print_font_sets(&self) {
+        let sets = self.report_filter.sets;
+        if sets == ReportSets::All {
+            self.print_current_and_required_sets();
+        }
+        if sets != ReportSets::Redundant {
+            self.print_font_set_with(
+                &t!("set-missing"),
+                &self.font_sets.missing,
+                |font| {
+                    if self.select_library_candidate(font).is_some() {
+                        "○".yellow()
+                    } else if self.faux_fallback(font).is_some() {
+                        "◐".magenta()
+                    } else {
+                        "○".red()
+                    }
+                },
+                |font| self.format_missing_font(font),
+            );
+        }
+        if sets != ReportSets::Missing {
+            self.print_font_set(&t!("set-redundant"), &self.font_sets.redundant, |_| {
+                "●".blue()
+            });
+        }
+    }
+
+    fn print_current_and_required_sets(&self) {
+        self.print_font_set_with(
+            &t!("set-current"),
+            &self.font_sets.current,
+            |font| {
+                if self.font_sets.required.contains(font)
+                    || self.current_entry_satisfies_required(font)
+                {
+                    "●".green()
+                } else {
+                    "●".blue()
+                }
+            },
+            |font| self.format_current_font(font),
+        );
+
+        self.print_font_set(&t!("set-required"), &self.font_sets.required, |font| {
+            if self.font_sets.embedded.contains(font) {
+                "◆".bright_green()
+            } else if font_is_satisfied_by_entries(font, &self.font_sets.current_entries) {
+                "●".green()
+            } else if self.select_library_candidate(font).is_some() {
                 "○".yellow()
+            } else if self.faux_fallback(font).is_some() {
+                "◐".magenta()
             } else {
                 "○".red()
             }
         });
-
-        self.print_font_set("Redundant fonts", &self.font_sets.redundant, |_| "●".blue());
     }
 
     fn print_font_set<F>(&self, title: &str, fonts: &BTreeSet<TypstFont>, get_bullet: F)
     where
-        F: Fn(&TypstFont) -> colored::ColoredString,
+        F: Fn(&TypstFont) -> ColoredString,
     {
-        self.print_font_set_with(title, fonts, get_bullet, |font| font.to_string());
+        self.print_font_set_with(title, fonts, get_bullet, TypstFont::variant_string);
     }
 
+    /// Print a font set grouped by family, with the variants of each family
+    /// indented beneath its name
     fn print_font_set_with<F, G>(
         &self,
         title: &str,
         fonts: &BTreeSet<TypstFont>,
         get_bullet: F,
-        format_font: G,
+        format_variant: G,
     ) where
-        F: Fn(&TypstFont) -> colored::ColoredString,
+        F: Fn(&TypstFont) -> ColoredString,
         G: Fn(&TypstFont) -> String,
     {
-        println!(
-            "\n- {} (total {}){}",
+        let families = group_by_family(
+            fonts
+                .iter()
+                .filter(|font| self.report_filter.includes_family(font)),
+        );
+        let total = families.values().map(Vec::len).sum::<usize>();
+
+        progress!(
+            "\n- {} {}{}",
             title.bold(),
-            fonts.len(),
-            if fonts.is_empty() { "" } else { ":" }
+            t!(
+                "set-total-families",
+                total = total,
+                families = families.len()
+            ),
+            if total == 0 { "" } else { ":" }
         );
-        for font in fonts {
-            println!("  {} {}", get_bullet(font), format_font(font));
+        for (family_name, variants) in &families {
+            progress!(
+                "  {}",
+                t!(
+                    "family-variants",
+                    family = *family_name,
+                    count = variants.len()
+                )
+            );
+            for font in variants {
+                progress!("    {} {}", get_bullet(font), format_variant(font));
+            }
+        }
+    }
+
+    fn format_missing_font(&self, font: &TypstFont) -> String {
+        let source = self
+            .select_library_candidate(font)
+            .filter(|_| self.namespace_sources)
+            .and_then(|entry| self.library_dirs.locate(&entry.path));
+        match source {
+            Some((source, _)) => format!("{} [{source}]", font.variant_string()),
+            None => font.variant_string(),
         }
     }
 
@@ -501,7 +1776,7 @@ impl<'a> FontManager<'a> {
             .current_entries
             .iter()
             .find(|entry| entry.font == *font)
-            .map_or_else(|| font.to_string(), format_discovered_font)
+            .map_or_else(|| font.variant_string(), format_discovered_variant)
     }
 
     fn current_entry_satisfies_required(&self, current: &TypstFont) -> bool {
@@ -518,139 +1793,1133 @@ impl<'a> FontManager<'a> {
     }
 
     fn select_library_candidate(&self, font: &TypstFont) -> Option<&DiscoveredFont> {
-        select_best_font_entry(font, &self.font_sets.library_entries)
+        self.library_candidates(font).into_iter().next()
+    }
+
+    /// The library files satisfying `font` that may be installed, best first
+    fn library_candidates(&self, font: &TypstFont) -> Vec<&DiscoveredFont> {
+        let choice = self.font_config.file_choices.get(font);
+        let mut candidates = self
+            .font_sets
+            .library_entries
+            .iter()
+            .filter(|entry| font_entry_satisfies(entry, font))
+            .filter(|entry| !choice.is_some_and(|choice| choice.avoids(&entry.path)))
+            .collect::<Vec<_>>();
+        candidates.sort_by_cached_key(|entry| self.candidate_rank(font, entry));
+        candidates
+    }
+
+    /// How a library file ranks among the candidates for `font`, lowest first: a
+    /// file pinned by `prefer_file` wins, then the source with the highest priority,
+    /// files already in the format required by the project, variable fonts, and
+    /// finally the path
+    fn candidate_rank(&self, font: &TypstFont, entry: &DiscoveredFont) -> CandidateRank {
+        let pinned = self
+            .font_config
+            .file_choices
+            .get(font)
+            .is_some_and(|choice| choice.prefers(&entry.path));
+        let other_format = self
+            .font_config
+            .font_format
+            .is_some_and(|format| FontFormat::of_path(&entry.path) != Some(format));
+        CandidateRank {
+            pinned: !pinned,
+            priority: std::cmp::Reverse(self.source_priority(font, entry).0),
+            other_format,
+            static_font: !entry_has_variant_axis(entry),
+            path: entry.path.to_string_lossy().to_string(),
+        }
+    }
+
+    /// The priority of the source of `entry` when installing `font`, with the
+    /// `[[source_priority]]` rule that set it
+    fn source_priority(
+        &self,
+        font: &TypstFont,
+        entry: &DiscoveredFont,
+    ) -> (i32, Option<&SourcePriority>) {
+        let Some(dir) = self.library_dirs.source_of(&entry.path) else {
+            return (0, None);
+        };
+        let rules = self
+            .font_config
+            .source_priority
+            .iter()
+            .filter(|rule| self.library_dirs.source_matches(dir, &rule.source));
+        let rule = rules
+            .clone()
+            .find(|rule| rule.matches_family(&font.family_name))
+            .or_else(|| rules.clone().find(|rule| rule.families.is_empty()));
+        (rule.map_or(0, |rule| rule.priority), rule)
+    }
+
+    /// `why`: explain which library file is chosen for each required font of
+    /// `family`, listing every candidate with the reason it ranks where it does
+    pub(crate) fn explain_choice(
+        &self,
+        family: &str,
+        style: Option<FontStyle>,
+        weight: Option<u16>,
+    ) {
+        let fonts = self
+            .font_config
+            .fonts
+            .iter()
+            .filter(|font| font.family_name.eq_ignore_ascii_case(family))
+            .filter(|font| style.is_none_or(|style| font.style == style))
+            .filter(|font| weight.is_none_or(|weight| font.weight.to_number() == weight))
+            .collect::<BTreeSet<_>>();
+        if fonts.is_empty() {
+            progress!(
+                "No font of the family {family:?} is required by {:?}",
+                self.config_file
+            );
+            return;
+        }
+
+        for font in fonts {
+            progress!("\n{}", font.to_string().bold());
+            if let Some(entry) = select_best_font_entry(font, &self.font_sets.current_entries) {
+                progress!("  Installed in the project: {}", entry.display_path());
+            }
+
+            let candidates = self.library_candidates(font);
+            let choice = self.font_config.file_choices.get(font);
+            let avoided = self
+                .font_sets
+                .library_entries
+                .iter()
+                .filter(|entry| font_entry_satisfies(entry, font))
+                .filter(|entry| choice.is_some_and(|choice| choice.avoids(&entry.path)))
+                .collect::<Vec<_>>();
+            if candidates.is_empty() && avoided.is_empty() {
+                progress!("  No library file satisfies it");
+                continue;
+            }
+
+            let mut table = Table::new(vec!["", "Source", "File", "Priority"]);
+            for (index, entry) in candidates.iter().enumerate() {
+                let (source, file) = self
+                    .library_dirs
+                    .locate(&entry.path)
+                    .map(|(source, file)| (source, file.display().to_string()))
+                    .unwrap_or_else(|| ("-".to_string(), entry.display_path()));
+                let (priority, rule) = self.source_priority(font, entry);
+                let priority = match rule {
+                    Some(rule) if !rule.families.is_empty() => {
+                        format!("{priority} (for {})", rule.families.join(", "))
+                    }
+                    Some(_) => priority.to_string(),
+                    None => format!("{priority} (default)"),
+                };
+                table.push_row(vec![
+                    if index == 0 { "→".green() } else { "".into() },
+                    source.into(),
+                    file.into(),
+                    priority.into(),
+                ]);
+            }
+            for entry in &avoided {
+                table.push_row(vec![
+                    "✗".red(),
+                    "".into(),
+                    entry.display_path().into(),
+                    "avoided by avoid_file_glob".dimmed(),
+                ]);
+            }
+            progress!("{}", table.render());
+
+            if let Some(chosen) = candidates.first() {
+                let reason = match candidates.get(1) {
+                    None => "it is the only candidate".to_string(),
+                    Some(next) => {
+                        let (chosen_rank, next_rank) = (
+                            self.candidate_rank(font, chosen),
+                            self.candidate_rank(font, next),
+                        );
+                        if chosen_rank.pinned != next_rank.pinned {
+                            "it is pinned by prefer_file".to_string()
+                        } else if chosen_rank.priority != next_rank.priority {
+                            format!(
+                                "its source has the highest priority ({})",
+                                chosen_rank.priority.0
+                            )
+                        } else if chosen_rank.other_format != next_rank.other_format {
+                            "it is already in the project's font_format".to_string()
+                        } else if chosen_rank.static_font != next_rank.static_font {
+                            "variable fonts are preferred".to_string()
+                        } else {
+                            "its path comes first among equally ranked files".to_string()
+                        }
+                    }
+                };
+                progress!("  Chosen: {}, as {reason}", chosen.display_path());
+            }
+        }
+    }
+
+    /// The repository and the raw download URL of the GitHub library file at `path`,
+    /// at the revision its library is pinned to
+    fn github_file_url(&self, path: &Path) -> Result<(String, String), String> {
+        github_file_url(path, self.library_dirs.github_reference(path))
+    }
+
+    /// The copy of the GitHub library file at `path` in a local mirror, see
+    /// [`local_mirror_file`]
+    fn local_mirror_file(&self, path: &Path) -> Option<PathBuf> {
+        local_mirror_file(path, self.library_dirs.github_reference(path))
     }
 
+    /// Download the library file at `relative_path` into the project font directory,
+    /// from a mirror if configured, returning the URL that served it and its hash
     pub(crate) fn download_font_from_github_path(
         &self,
         font: &TypstFont,
         relative_path: &Path,
-    ) -> Result<(), String> {
-        let client = Client::new();
+        limit_rate: Option<RateLimit>,
+        expected_sha256: Option<&str>,
+        dest_dir: &Path,
+    ) -> Result<(String, String), String> {
+        let client = http::client()?;
 
-        println!("\n- {}", "Downloading fonts from GitHub".bold());
+        progress!("\n- {}", "Downloading fonts from GitHub".bold());
 
-        let github_repo = get_first_two_segments(&relative_path).expect("Invalid GitHub repo path");
+        let (_, url) = self.github_file_url(relative_path)?;
+        let _span = trace::span("download", file_name_string(relative_path)).arg("url", &url);
+        let dest_path = dest_dir.join(relative_path.file_name().unwrap());
 
-        let font_relative_path =
-            get_remaining_after_two_segments(&relative_path).expect("Invalid font path");
+        match limit_rate {
+            Some(rate) => progress!("  Downloading {url} to {:?} (limited to {rate})", dest_path),
+            None => progress!("  Downloading {url} to {:?}", dest_path),
+        }
+
+        let fetched = http::fetch_verified(&client, &url, expected_sha256, limit_rate)
+            .map_err(|e| format!("Failed to download {}: {}", font, e))?;
 
-        let url = format!(
-            "https://raw.githubusercontent.com/{}/main/{}",
-            github_repo.display(),
-            font_relative_path.display()
+        // Ensure the parent directory exists
+        if let Some(parent) = dest_path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create directories {:?}: {}", parent, e))?;
+        }
+        let mut file = fs::File::create(&dest_path)
+            .map_err(|e| format!("Failed to create file {:?}: {}", dest_path, e))?;
+        file.write_all(&fetched.data)
+            .map_err(|e| format!("Failed to write font file {:?}: {}", dest_path, e))?;
+        progress!("  Successfully downloaded {:?}", font);
+
+        Ok((fetched.url, fetched.sha256))
+    }
+
+    /// Copy a file of the local mirror of a GitHub library into the project font
+    /// directory, returning its hash. It must have the hash recorded in the
+    /// lockfile, if any, like a downloaded file.
+    fn copy_from_local_mirror(
+        &self,
+        mirrored: &Path,
+        expected_sha256: Option<&str>,
+        dest_dir: &Path,
+    ) -> Result<String, String> {
+        let data =
+            fs::read(mirrored).map_err(|e| format!("Failed to read {:?}: {}", mirrored, e))?;
+        let sha256 = sha256_hex(&data);
+        if expected_sha256.is_some_and(|expected| expected != sha256) {
+            return Err(format!(
+                "{mirrored:?} in the local mirror differs from font_config.lock, downloading it"
+            ));
+        }
+        let dest_path = dest_dir.join(mirrored.file_name().unwrap());
+        progress!(
+            "  Copying {mirrored:?} from the local mirror to {:?}",
+            dest_path
         );
-        let dest_path = self
-            .absolute_font_dir
-            .join(relative_path.file_name().unwrap());
+        let _span =
+            trace::span("copy", file_name_string(mirrored)).arg("source", mirrored.display());
+        fs::create_dir_all(dest_dir)
+            .map_err(|e| format!("Failed to create directories {:?}: {}", dest_dir, e))?;
+        fs::write(&dest_path, &data)
+            .map_err(|e| format!("Failed to write font file {:?}: {}", dest_path, e))?;
+        Ok(sha256)
+    }
 
-        println!("  Downloading {url} to {:?}", dest_path);
+    /// The library file chosen for each missing font, or `None` if the libraries
+    /// don't contain it. Fonts whose file was already chosen for another missing
+    /// font are left out, unless each face of a collection is extracted separately.
+    pub(crate) fn plan_updates(&self) -> Vec<(&TypstFont, Option<&DiscoveredFont>)> {
+        let mut chosen_sources = BTreeSet::<(&Path, u32)>::new();
+        self.font_sets
+            .missing
+            .iter()
+            .map(|font| (font, self.select_library_candidate(font)))
+            .filter(|(_, entry)| {
+                entry.is_none_or(|entry| {
+                    let face = if self.extracts_face(&entry.path) {
+                        entry.index
+                    } else {
+                        0
+                    };
+                    chosen_sources.insert((&entry.path, face))
+                })
+            })
+            .collect()
+    }
 
-        // Perform the HTTP GET request to download the font
-        let response = client
-            .get(&url)
-            .send()
-            .map_err(|e| format!("Failed to download {}: {}", font, e))?;
+    /// Whether a single face is extracted from the collection at `source_path`
+    /// instead of installing the whole file, see `font_format`
+    fn extracts_face(&self, source_path: &Path) -> bool {
+        self.required_conversion(source_path).is_some()
+            && utils::font_utils::is_font_collection(source_path)
+    }
+
+    /// Indices of the faces in the collection at `source_path` that the missing
+    /// fonts resolve to
+    fn collection_faces(&self, source_path: &Path) -> Vec<u32> {
+        let faces = self
+            .font_sets
+            .missing
+            .iter()
+            .filter_map(|font| self.select_library_candidate(font))
+            .filter(|entry| entry.path == source_path)
+            .map(|entry| entry.index)
+            .collect::<BTreeSet<_>>();
+        faces.into_iter().collect()
+    }
 
-        if response.status().is_success() {
-            // Ensure the parent directory exists
-            if let Some(parent) = dest_path.parent() {
-                fs::create_dir_all(parent)
-                    .map_err(|e| format!("Failed to create directories {:?}: {}", parent, e))?;
+    /// Install the missing fonts, returning the lockfile entries of the installed files
+    pub(crate) fn update_fonts(&self, options: &UpdateOptions) -> Result<Vec<LockedFile>, String> {
+        let dry_run = options.dry_run;
+        if dry_run && options.diff {
+            self.print_update_diff();
+            return Ok(Vec::new());
+        }
+
+        if self.font_sets.missing.is_empty() {
+            progress!("\n{}", t!("update-none"));
+            return Ok(Vec::new());
+        }
+
+        // Installed for review first, see `promote`
+        let quarantine = self.quarantines(options);
+        let install_dir = if quarantine {
+            // Ignored by git before the first file lands in it
+            if !dry_run {
+                quarantine::create_incoming_dir(&self.absolute_font_dir)?;
             }
-            let mut file = fs::File::create(&dest_path)
-                .map_err(|e| format!("Failed to create file {:?}: {}", dest_path, e))?;
-            let content = response
-                .bytes()
-                .map_err(|e| format!("Failed to read content of {}: {}", font, e))?;
-            file.write_all(&content)
-                .map_err(|e| format!("Failed to write font file {:?}: {}", dest_path, e))?;
-            println!("  Successfully downloaded {:?}", font);
+            quarantine::incoming_dir(&self.absolute_font_dir)
         } else {
-            return Err(format!(
-                "Failed to download {}. HTTP status: {}",
-                font,
-                response.status()
-            ));
+            self.absolute_font_dir.clone()
+        };
+
+        if dry_run {
+            progress!("\n- {}", t!("update-dry-run-title").bold());
+        } else {
+            progress!("\n- {}", t!("update-title").bold());
+        }
+
+        let mut plan = self.plan_updates();
+        if let Some(only_sources) = options.only_sources {
+            plan.retain(|(_, source_entry)| {
+                source_entry.is_some_and(|entry| {
+                    self.lock_source_path(&entry.path)
+                        .is_ok_and(|source| only_sources.contains(&source))
+                })
+            });
+        }
+
+        // Refuse the whole plan up front rather than installing some of the fonts
+        if let LibraryDirs::GitHub(_) = self.library_dirs {
+            for &(_, source_entry) in &plan {
+                if let Some(source_entry) = source_entry
+                    && self.local_mirror_file(&source_entry.path).is_none()
+                {
+                    let url = self.github_file_url(&source_entry.path)?.1;
+                    let mirrors = user_config::get().http.mirror_urls(&url);
+                    if !mirrors.iter().any(|mirror| http::check_url(mirror).is_ok()) {
+                        http::check_url(&mirrors[0])?;
+                    }
+                }
+            }
+        }
+        if !dry_run
+            && let Some(converter) = options.converter
+            && plan.iter().any(|&(_, source_entry)| {
+                source_entry.is_some_and(|entry| self.required_conversion(&entry.path).is_some())
+            })
+        {
+            check_converter(converter)?;
+        }
+
+        // Fonts that may not be redistributed must not be committed by accident
+        let policy = self.font_config.redistribution.unwrap_or_default();
+        let guarded = !dry_run
+            && policy != RedistributionPolicy::Allow
+            && redistribution::is_git_tracked(&self.absolute_font_dir);
+        if guarded {
+            let restricted = plan
+                .iter()
+                .filter_map(|&(_, source_entry)| {
+                    let source_entry = source_entry?;
+                    let reason = redistribution::restriction(&source_entry.metadata)?;
+                    Some((file_name_string(&source_entry.path), reason))
+                })
+                .collect::<BTreeSet<_>>();
+            let restricted = restricted.into_iter().collect::<Vec<_>>();
+            redistribution::guard(policy, &restricted, &self.absolute_font_dir)?;
+        }
+
+        // Only one of the files survives a checkout on macOS or Windows
+        let mut file_names = fs::read_dir(&install_dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && utils::font_utils::has_font_extension(path))
+            .map(|path| file_name_string(&path))
+            .collect::<Vec<_>>();
+        for &(_, source_entry) in &plan {
+            let Some(source_entry) = source_entry else {
+                continue;
+            };
+            let source_path = &source_entry.path;
+            file_names.push(file_name_string(source_path));
+            if let (Some(format), Some(_)) =
+                (self.required_conversion(source_path), options.converter)
+            {
+                file_names.push(file_name_string(
+                    &source_path.with_extension(format.extension()),
+                ));
+            }
+        }
+        if let Some((name, other)) = case_conflicts(&file_names).into_iter().next() {
+            let message = format!(
+                "{name:?} and {other:?} differ only by case, so checkouts on macOS and Windows \
+                 would keep only one of them (rename one of the library files, or remove the \
+                 outdated one from {:?})",
+                install_dir
+            );
+            if !dry_run {
+                return Err(message);
+            }
+            progress!("  {} {message}", t!("warning").yellow().bold());
+        }
+
+        let workspace = if dry_run || quarantine {
+            None
+        } else {
+            Workspace::find(&self.config_file)?
+        };
+        if !dry_run {
+            cancel::install_handler();
+        }
+        let state_path = cancel::state_path(&self.config_file);
+        let mut installed = Vec::new();
+        let mut head_commits = BTreeMap::new();
+        let previous_lock = if options.locked {
+            let lock_path = lockfile_path(&self.config_file);
+            let lock = FontLock::load(&lock_path)?.ok_or_else(|| {
+                format!("--locked requires a lockfile, but {lock_path:?} doesn't exist")
+            })?;
+            self.check_locked_plan(&plan, &lock)?;
+            lock
+        } else if dry_run {
+            FontLock::default()
+        } else {
+            FontLock::load(&lockfile_path(&self.config_file))?.unwrap_or_default()
+        };
+
+        // Completed files are recorded one by one, so that an interrupted run
+        // keeps them
+        let lock_path = if quarantine {
+            quarantine::incoming_lock_path(&self.absolute_font_dir)
+        } else {
+            lockfile_path(&self.config_file)
+        };
+        let mut lock = if dry_run || options.locked {
+            None
+        } else {
+            Some(FontLock::load(&lock_path)?.unwrap_or_default())
+        };
+
+        let planned = plan.len();
+        for (font, source_entry) in plan {
+            if cancel::cancelled() {
+                progress!(
+                    "  {}",
+                    t!(
+                        "cancel-stopped",
+                        installed = installed.len(),
+                        planned = planned
+                    )
+                );
+                break;
+            }
+            // Get the path of the font file in the library
+            if let Some(source_entry) = source_entry {
+                let source_path = &source_entry.path;
+                let source_name = source_path.file_name().unwrap();
+                let conversion = self.required_conversion(source_path);
+                if quarantine
+                    && let Some(lock) = &lock
+                    && let source = self.lock_source_path(source_path)?
+                    && let Some(awaiting) = lock.files.iter().find(|file| file.source == source)
+                {
+                    progress!("  {} is awaiting review as {:?}", source, awaiting.file);
+                    continue;
+                }
+                if let Some(format) = conversion
+                    && options.converter.is_none()
+                {
+                    progress!(
+                        "  Skipping {source_path:?}: the project requires .{} files (use --convert to convert it)",
+                        format.extension()
+                    );
+                    continue;
+                }
+
+                // A linked file may be shared with other projects, so replace
+                // the link rather than writing through it
+                let installed_path = install_dir.join(source_name);
+                if workspace.is_some() && fs::symlink_metadata(&installed_path).is_ok() {
+                    fs::remove_file(&installed_path)
+                        .map_err(|e| format!("Failed to remove {installed_path:?}: {e}"))?;
+                }
+                // Rolled back unless the file is installed completely
+                let mut in_flight = if dry_run {
+                    None
+                } else {
+                    Some(InFlight::begin(&state_path, installed_path.clone())?)
+                };
+
+                let mut from_mirror = false;
+                let (served_url, source_sha256) = match self.library_dirs {
+                    LibraryDirs::Local(_) => {
+                        // dest_path is where the font file will be copied to
+                        // it is the project's font directory joined with the file name of the font file
+                        let dest_path = install_dir.join(source_name);
+                        progress!(
+                            "  {} {source_path:?} to {:?}",
+                            if dry_run { "Would copy" } else { "Copying" },
+                            Path::new(
+                                &self
+                                    .font_config
+                                    .font_dir
+                                    .clone()
+                                    .unwrap_or_else(|| "fonts".to_string())
+                            )
+                            .join(if quarantine {
+                                quarantine::INCOMING_DIR
+                            } else {
+                                ""
+                            })
+                            .join(source_name)
+                        );
+                        if dry_run {
+                            self.print_planned_conversion(source_entry, conversion);
+                            continue;
+                        }
+                        fs::create_dir_all(&install_dir).map_err(|e| {
+                            format!("Failed to create directories {:?}: {}", install_dir, e)
+                        })?;
+                        // Copy the font file from the library to the project's font directory
+                        let _span = trace::span("copy", source_name.to_string_lossy())
+                            .arg("source", source_path.display());
+                        fs::copy(source_path, &dest_path)
+                            .map_err(|_| format!("Failed to copy font file: {:?}", font))?;
+                        let data = fs::read(&dest_path).map_err(|e| {
+                            format!("Failed to read font file {:?}: {}", dest_path, e)
+                        })?;
+                        (None, sha256_hex(&data))
+                    }
+                    LibraryDirs::GitHub(_) => {
+                        let mirrored = self.local_mirror_file(source_path);
+                        if dry_run {
+                            let dest_path = install_dir.join(source_name);
+                            match &mirrored {
+                                Some(mirrored) => {
+                                    progress!("  Would copy {mirrored:?} to {:?}", dest_path)
+                                }
+                                None => {
+                                    let (_, url) = self.github_file_url(source_path)?;
+                                    progress!("  Would download {url} to {:?}", dest_path);
+                                }
+                            }
+                            self.print_planned_conversion(source_entry, conversion);
+                            continue;
+                        }
+                        let expected_sha256 = previous_lock
+                            .files
+                            .iter()
+                            .find(|file| file.source == portable_path(source_path))
+                            .and_then(|file| file.provenance.as_ref()?.sha256.as_deref());
+                        // A broken or outdated mirror file is downloaded instead
+                        let copied = mirrored.and_then(|mirrored| {
+                            self.copy_from_local_mirror(&mirrored, expected_sha256, &install_dir)
+                                .inspect_err(|e| progress!("{} {e}", t!("warning").yellow().bold()))
+                                .ok()
+                        });
+                        match copied {
+                            Some(sha256) => {
+                                from_mirror = true;
+                                (None, sha256)
+                            }
+                            None => {
+                                let (url, sha256) = self.download_font_from_github_path(
+                                    font,
+                                    source_path,
+                                    options.limit_rate,
+                                    expected_sha256,
+                                    &install_dir,
+                                )?;
+                                (Some(url), sha256)
+                            }
+                        }
+                    }
+                };
+                if options.locked {
+                    check_locked_hash(
+                        &previous_lock,
+                        &self.lock_source_path(source_path)?,
+                        &source_sha256,
+                    )?;
+                }
+
+                let mut locked = LockedFile {
+                    file: source_name.to_string_lossy().to_string(),
+                    source: self.lock_source_path(source_path)?,
+                    faces: Vec::new(),
+                    converted_from: None,
+                    sha256: String::new(),
+                    blake3: None,
+                    original_sha256: None,
+                    provenance: Some(self.provenance(source_path, &mut head_commits)?),
+                };
+                if let Some(provenance) = &mut locked.provenance {
+                    provenance.url = served_url.or(provenance.url.take());
+                    provenance.sha256 = Some(source_sha256);
+                    if from_mirror {
+                        provenance.source_type = SourceType::Mirror;
+                        provenance.url = None;
+                    }
+                }
+
+                if self.extracts_face(source_path) {
+                    locked.faces = vec![source_entry.index];
+                } else if utils::font_utils::is_font_collection(source_path) {
+                    locked.faces = self.collection_faces(source_path);
+                }
+
+                if let (Some(format), Some(converter)) = (conversion, options.converter) {
+                    let mut copied_path = install_dir.join(source_name);
+                    if self.extracts_face(source_path) {
+                        let extracted_path =
+                            self.extract_installed_face(&copied_path, source_entry.index)?;
+                        if let Some(in_flight) = &mut in_flight {
+                            in_flight.add(extracted_path.clone())?;
+                        }
+                        locked.converted_from = Some(locked.file.clone());
+                        locked.file = file_name_string(&extracted_path);
+                        copied_path = extracted_path;
+                    }
+
+                    if FontFormat::of_path(&copied_path) != Some(format) {
+                        let converted_path = copied_path.with_extension(format.extension());
+                        progress!(
+                            "  Converting {:?} to {:?}",
+                            copied_path.file_name().unwrap(),
+                            converted_path.file_name().unwrap()
+                        );
+                        let _span = trace::span("convert", file_name_string(&copied_path));
+                        if let Some(in_flight) = &mut in_flight {
+                            in_flight.add(converted_path.clone())?;
+                        }
+                        if let Err(e) = convert_font_file(converter, &copied_path, &converted_path)
+                        {
+                            // Neither the unconverted copy nor a partial output is kept
+                            let _ = fs::remove_file(&converted_path);
+                            let _ = fs::remove_file(&copied_path);
+                            return Err(e);
+                        }
+                        fs::remove_file(&copied_path).map_err(|e| {
+                            format!("Failed to remove unconverted file {:?}: {}", copied_path, e)
+                        })?;
+
+                        locked
+                            .converted_from
+                            .get_or_insert_with(|| locked.file.clone());
+                        locked.file = file_name_string(&converted_path);
+                    }
+                }
+
+                self.finish_installed_file(
+                    &mut locked,
+                    options.strip,
+                    options.hash_algo,
+                    &install_dir,
+                )?;
+                if options.locked {
+                    check_locked_file(&previous_lock, &locked, options.hash_algo)?;
+                }
+                // Remote indexes may lack the license, so check the file itself
+                if guarded && redistribution::restriction(&source_entry.metadata).is_none() {
+                    let path = install_dir.join(&locked.file);
+                    let data = fs::read(&path)
+                        .map_err(|e| format!("Failed to read font file {:?}: {}", path, e))?;
+                    if let Some(reason) =
+                        redistribution::restriction(&FontMetadata::parse(&data, 0))
+                    {
+                        let restricted = [(locked.file.clone(), reason)];
+                        if let Err(e) =
+                            redistribution::guard(policy, &restricted, &self.absolute_font_dir)
+                        {
+                            let _ = fs::remove_file(&path);
+                            return Err(e);
+                        }
+                    }
+                }
+                if let Some(workspace) = &workspace {
+                    let path = install_dir.join(&locked.file);
+                    let already_stored = workspace.store_file(&path, &locked.sha256)?;
+                    progress!(
+                        "  Linked {:?} to the workspace store{}",
+                        locked.file,
+                        if already_stored {
+                            " (already stored)"
+                        } else {
+                            ""
+                        }
+                    );
+                }
+                if let Some(in_flight) = in_flight {
+                    in_flight.finish();
+                }
+                if let Some(lock) = &mut lock {
+                    lock.set_file(locked.clone());
+                    lock.save(&lock_path)?;
+                }
+                installed.push(locked);
+            } else {
+                progress!("Font not found in source library: {:?}", font);
+            }
+        }
+
+        if quarantine
+            && !dry_run
+            && let Some(lock) = &lock
+        {
+            quarantine::write_summary(&self.absolute_font_dir, lock)?;
+            quarantine::print_summary(&self.absolute_font_dir, lock);
+        }
+        Ok(installed)
+    }
+
+    /// Whether `update` with `options` installs into the quarantine of the font
+    /// directory. Files verified against font_config.lock with `--locked` were
+    /// reviewed when they were first installed, and are installed directly.
+    pub(crate) fn quarantines(&self, options: &UpdateOptions) -> bool {
+        (options.quarantine || self.font_config.quarantine == Some(true)) && !options.locked
+    }
+
+    /// With `--locked`, every planned file must be one the lockfile records as
+    /// installed; the error names the first one that isn't
+    fn check_locked_plan(
+        &self,
+        plan: &[(&TypstFont, Option<&DiscoveredFont>)],
+        lock: &FontLock,
+    ) -> Result<(), String> {
+        for &(font, source_entry) in plan {
+            let Some(source_entry) = source_entry else {
+                continue;
+            };
+            let source = self.lock_source_path(&source_entry.path)?;
+            let Some(recorded) = lock.files.iter().find(|file| file.source == source) else {
+                return Err(format!(
+                    "{} {} resolves to {source}, which font_config.lock doesn't record \
+                     (run update without --locked to update the lockfile)",
+                    font.family_name,
+                    font.variant_string()
+                ));
+            };
+            let reference = self.library_dirs.github_reference(&source_entry.path);
+            let recorded_reference = recorded
+                .provenance
+                .as_ref()
+                .and_then(|provenance| provenance.reference.as_deref());
+            if recorded_reference != reference {
+                let revision = |reference: Option<&str>| {
+                    reference.map_or("the default branch".to_string(), |reference| {
+                        format!("{reference:?}")
+                    })
+                };
+                return Err(format!(
+                    "font_config.lock records {source} from {}, but the library is read from {} \
+                     (run update without --locked to update the lockfile)",
+                    revision(recorded_reference),
+                    revision(reference)
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// How the lockfile refers to a library file: local files by their path
+    /// relative to the project directory, GitHub files by "owner/repo/path"
+    fn lock_source_path(&self, source_path: &Path) -> Result<String, String> {
+        match self.library_dirs {
+            LibraryDirs::Local(_) => {
+                let project_dir = self.config_file.parent().unwrap_or(Path::new("."));
+                let project_dir = if project_dir.as_os_str().is_empty() {
+                    Path::new(".")
+                } else {
+                    project_dir
+                };
+                relative_path(source_path, project_dir)
+                    .map(|path| portable_path(&path))
+                    .ok_or_else(|| {
+                        format!(
+                            "{:?} has no path relative to the project directory {:?}, \
+                             so it can't be recorded in the lockfile",
+                            source_path, project_dir
+                        )
+                    })
+            }
+            LibraryDirs::GitHub(_) => Ok(portable_path(source_path)),
+        }
+    }
+
+    /// Where the file at `source_path` in the library comes from. The head commit of
+    /// each GitHub library is looked up once per run and kept in `head_commits`.
+    fn provenance(
+        &self,
+        source_path: &Path,
+        head_commits: &mut BTreeMap<String, Option<String>>,
+    ) -> Result<Provenance, String> {
+        let original_file = file_name_string(source_path);
+        match self.library_dirs {
+            LibraryDirs::Local(_) => Ok(Provenance {
+                source_type: SourceType::Local,
+                repo: None,
+                commit: None,
+                reference: None,
+                local_path: Some(self.lock_source_path(source_path)?),
+                original_file,
+                url: None,
+                sha256: None,
+                installed_by: tool_version(),
+            }),
+            LibraryDirs::GitHub(_) => {
+                let reference = self.library_dirs.github_reference(source_path);
+                let (repo, url) = github_file_url(source_path, reference)?;
+                let commit = head_commits
+                    .entry(repo.clone())
+                    .or_insert_with(|| {
+                        http::client()
+                            .and_then(|client| github::fetch_head_commit(&client, &repo, reference))
+                            .map(|head| head.sha)
+                            .inspect_err(|e| progress!("{} {e}", t!("warning").yellow().bold()))
+                            .ok()
+                    })
+                    .clone();
+                Ok(Provenance {
+                    source_type: SourceType::GitHub,
+                    repo: Some(repo),
+                    commit,
+                    reference: reference.map(str::to_string),
+                    local_path: None,
+                    original_file,
+                    url: Some(url),
+                    sha256: None,
+                    installed_by: tool_version(),
+                })
+            }
+        }
+    }
+
+    /// Strip unused tables from the installed file if requested, then hash it.
+    /// With `hash_algo` BLAKE3, its BLAKE3 is recorded as well, computed
+    /// alongside the SHA-256 by [`hash_files`].
+    fn finish_installed_file(
+        &self,
+        locked: &mut LockedFile,
+        strip: bool,
+        hash_algo: HashAlgo,
+        dir: &Path,
+    ) -> Result<(), String> {
+        let _span = trace::span("finish", locked.file.as_str());
+        let path = dir.join(&locked.file);
+        if strip {
+            let data = fs::read(&path)
+                .map_err(|e| format!("Failed to read font file {:?}: {}", path, e))?;
+            match strip_font_tables(&data) {
+                Ok(Some(stripped)) => {
+                    fs::write(&path, &stripped)
+                        .map_err(|e| format!("Failed to write font file {:?}: {}", path, e))?;
+                    progress!(
+                        "  Stripped {:?} ({} KiB -> {} KiB)",
+                        locked.file,
+                        data.len() / 1024,
+                        stripped.len() / 1024
+                    );
+                    locked.original_sha256 = Some(sha256_hex(&data));
+                }
+                Ok(None) => {}
+                Err(e) => progress!("  Not stripping {:?}: {e}", locked.file),
+            }
+        }
+
+        let mut jobs = vec![(path.clone(), HashAlgo::Sha256)];
+        if hash_algo == HashAlgo::Blake3 {
+            jobs.push((path.clone(), HashAlgo::Blake3));
+        }
+        let mut hashes = hash_files(&jobs)
+            .into_iter()
+            .map(|hash| hash.map_err(|e| format!("Failed to read font file {:?}: {}", path, e)));
+        locked.sha256 = hashes.next().expect("the SHA-256 is always computed")?;
+        locked.blake3 = hashes.next().transpose()?;
+        Ok(())
+    }
+
+    /// The format a library file has to be converted to in order to satisfy the
+    /// project's `font_format` policy, if any
+    fn required_conversion(&self, source_path: &Path) -> Option<FontFormat> {
+        let required = self.font_config.font_format?;
+        (FontFormat::of_path(source_path) != Some(required)).then_some(required)
+    }
+
+    /// Replace the installed collection `collection_path` by its face at `index`,
+    /// saved next to it as e.g. `NotoSansCJK-2.otf`
+    fn extract_installed_face(
+        &self,
+        collection_path: &Path,
+        index: u32,
+    ) -> Result<PathBuf, String> {
+        let data = fs::read(collection_path)
+            .map_err(|e| format!("Failed to read font file {:?}: {}", collection_path, e))?;
+        let (face, cff) = extract_face(&data, index)
+            .map_err(|e| format!("Failed to extract {:?}: {e}", collection_path))?;
+
+        let stem = collection_path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let extension = if cff { "otf" } else { "ttf" };
+        let extracted_path = collection_path.with_file_name(format!("{stem}-{index}.{extension}"));
+        progress!(
+            "  Extracting face {index} of {:?} to {:?}",
+            collection_path.file_name().unwrap(),
+            extracted_path.file_name().unwrap()
+        );
+        fs::write(&extracted_path, face)
+            .map_err(|e| format!("Failed to write font file {:?}: {}", extracted_path, e))?;
+        fs::remove_file(collection_path).map_err(|e| {
+            format!(
+                "Failed to remove font collection {:?}: {}",
+                collection_path, e
+            )
+        })?;
+
+        Ok(extracted_path)
+    }
+
+    /// `update --dry-run --diff`: the planned changes of the font directory, one
+    /// line per file: `+` added, `-` only holding fonts the project doesn't need
+    /// any more, `~` replaced by a library file of the same name
+    fn print_update_diff(&self) {
+        let font_dir = self.font_config.font_dir.as_deref().unwrap_or("fonts");
+        let project_path = |name: &Path| portable_path(&Path::new(font_dir).join(name));
+
+        let mut lines = BTreeMap::<String, ColoredString>::new();
+        let (mut added, mut removed, mut replaced) = (0, 0, 0);
+        for (font, entry) in self.plan_updates() {
+            let Some(entry) = entry else { continue };
+            let name = entry.path.file_name().unwrap_or_default();
+            let source = self
+                .library_dirs
+                .locate(&entry.path)
+                .map_or_else(|| entry.path.display().to_string(), |(source, _)| source);
+            let mut detail = format!("{}, from {source}", font.family_name);
+            if self.extracts_face(&entry.path) {
+                detail.push_str(&format!(", face {}", entry.index));
+            }
+            if let Some(format) = self.required_conversion(&entry.path) {
+                detail.push_str(&format!(", converted to .{}", format.extension()));
+            }
+
+            let installed = self.absolute_font_dir.join(name);
+            let line = if installed.exists() {
+                replaced += 1;
+                let version = |path: &Path| font_version(path).unwrap_or_else(|| "?".to_string());
+                format!(
+                    "~ {}  ({} → {}; {detail})",
+                    project_path(name.as_ref()),
+                    version(&installed),
+                    version(&entry.path)
+                )
+                .yellow()
+            } else {
+                added += 1;
+                format!("+ {}  ({detail})", project_path(name.as_ref())).green()
+            };
+            lines.entry(project_path(name.as_ref())).or_insert(line);
+        }
+
+        for path in self.redundant_files() {
+            let name = path.strip_prefix(&self.absolute_font_dir).unwrap_or(path);
+            // A file that is replaced isn't removed as well
+            if let std::collections::btree_map::Entry::Vacant(line) =
+                lines.entry(project_path(name))
+            {
+                removed += 1;
+                line.insert(format!("- {}", project_path(name)).red());
+            }
+        }
+
+        progress!("\n{}", format!("diff {font_dir}/").bold());
+        for line in lines.values() {
+            progress!("{line}");
+        }
+        progress!("{added} to add, {removed} to remove, {replaced} to replace");
+    }
+
+    fn print_planned_conversion(
+        &self,
+        source_entry: &DiscoveredFont,
+        conversion: Option<FontFormat>,
+    ) {
+        let source_path = &source_entry.path;
+        if self.extracts_face(source_path) {
+            progress!("    and extract face {}", source_entry.index);
+        }
+        if let Some(format) = conversion {
+            progress!(
+                "    and convert it to {:?}",
+                source_path
+                    .with_extension(format.extension())
+                    .file_name()
+                    .unwrap()
+            );
+        }
+    }
+
+    /// Record the HEAD revision of each GitHub library in the lockfile, so that later
+    /// checks can tell how old the index used for resolution has become
+    pub(crate) fn record_index_revisions(&self) -> Result<(), String> {
+        let LibraryDirs::GitHub(github_repos) = &self.library_dirs else {
+            return Ok(());
+        };
+
+        let client = http::client()?;
+        let lock_path = lockfile_path(&self.config_file);
+        let mut lock = FontLock::load(&lock_path)?.unwrap_or_default();
+
+        for github_repo in github_repos {
+            let source = github::GitHubSource::parse(github_repo)?;
+            let head = github::fetch_head_commit(&client, source.repo, source.reference)?;
+            lock.set_source(LockedSource {
+                repo: source.repo.to_string(),
+                commit: head.sha,
+                committed_at: format_timestamp(head.committed_at),
+            });
         }
 
-        Ok(())
+        lock.save(&lock_path)
     }
 
-    pub(crate) fn update_fonts(&self, dry_run: bool) -> Result<(), String> {
-        if self.font_sets.missing.is_empty() {
-            println!("\nNo missing fonts to update");
+    /// Append the run of `command` that installed `installed` to the project journal,
+    /// together with the revisions of the GitHub libraries in use
+    pub(crate) fn record_journal(
+        &self,
+        command: &str,
+        installed: Vec<LockedFile>,
+    ) -> Result<(), String> {
+        if installed.is_empty() {
             return Ok(());
         }
 
-        if dry_run {
-            println!("\n- {}", "Dry run: planned font updates".bold());
-        } else {
-            println!("\n- {}", "Updating fonts".bold());
-        }
+        let sources = match &self.library_dirs {
+            LibraryDirs::GitHub(github_repos) => {
+                let lock = FontLock::load(&lockfile_path(&self.config_file))?.unwrap_or_default();
+                let repos = github_repos
+                    .iter()
+                    .filter_map(|github_repo| github::GitHubSource::parse(github_repo).ok())
+                    .map(|source| source.repo)
+                    .collect::<BTreeSet<_>>();
+                lock.sources
+                    .into_iter()
+                    .filter(|source| repos.contains(source.repo.as_str()))
+                    .collect()
+            }
+            LibraryDirs::Local(_) => Vec::new(),
+        };
 
-        let mut copied_sources = BTreeSet::<PathBuf>::new();
+        let actions = installed.into_iter().map(JournalAction::Install).collect();
+        journal::record_run(&journal_path(&self.config_file), command, actions, sources).map(|_| ())
+    }
 
-        for font in &self.font_sets.missing {
-            // Get the path of the font file in the library
-            if let Some(source_entry) = self.select_library_candidate(font) {
-                let source_path = &source_entry.path;
-                if !copied_sources.insert(source_path.clone()) {
-                    continue;
-                }
+    /// Warn if the GitHub library indexes recorded in the lockfile are more than
+    /// `stale_after` days older than the current HEAD of their repositories
+    pub(crate) fn print_index_staleness(&self, stale_after: i64) {
+        let LibraryDirs::GitHub(github_repos) = &self.library_dirs else {
+            return;
+        };
 
-                match self.library_dirs {
-                    LibraryDirs::Local(_) => {
-                        // dest_path is where the font file will be copied to
-                        // it is the project's font directory joined with the file name of the font file
-                        let dest_path = self
-                            .absolute_font_dir
-                            .join(&source_path.file_name().unwrap());
-                        println!(
-                            "  {} {source_path:?} to {:?}",
-                            if dry_run { "Would copy" } else { "Copying" },
-                            Path::new(
-                                &self
-                                    .font_config
-                                    .font_dir
-                                    .clone()
-                                    .unwrap_or_else(|| "fonts".to_string())
-                            )
-                            .join(&source_path.file_name().unwrap())
-                        );
-                        if dry_run {
-                            continue;
-                        }
-                        // Copy the font file from the library to the project's font directory
-                        fs::copy(&source_path, &dest_path)
-                            .map_err(|_| format!("Failed to copy font file: {:?}", font))?;
-                    }
-                    LibraryDirs::GitHub(_) => {
-                        if dry_run {
-                            let github_repo = get_first_two_segments(source_path)
-                                .expect("Invalid GitHub repo path");
-                            let font_relative_path = get_remaining_after_two_segments(source_path)
-                                .expect("Invalid font path");
-                            let url = format!(
-                                "https://raw.githubusercontent.com/{}/main/{}",
-                                github_repo.display(),
-                                font_relative_path.display()
-                            );
-                            let dest_path = self
-                                .absolute_font_dir
-                                .join(source_path.file_name().unwrap());
-                            println!("  Would download {url} to {:?}", dest_path);
-                            continue;
-                        }
-                        self.download_font_from_github_path(font, source_path)
-                            .expect("Failed to download fonts from GitHub");
-                    }
+        let lock = match FontLock::load(&lockfile_path(&self.config_file)) {
+            Ok(Some(lock)) => lock,
+            Ok(None) => return,
+            Err(e) => {
+                progress!("\n{} {e}", t!("warning").yellow().bold());
+                return;
+            }
+        };
+
+        let client = match http::client() {
+            Ok(client) => client,
+            Err(e) => {
+                progress!("\n{} {e}", t!("warning").yellow().bold());
+                return;
+            }
+        };
+
+        // Libraries pinned to a branch, tag or commit stay at it on purpose
+        let repos = github_repos
+            .iter()
+            .filter_map(|github_repo| github::GitHubSource::parse(github_repo).ok())
+            .filter(|source| source.reference.is_none())
+            .map(|source| source.repo)
+            .collect::<BTreeSet<_>>();
+        for repo in repos {
+            let Some(source) = lock.source(repo) else {
+                continue;
+            };
+
+            let staleness = parse_timestamp(&source.committed_at).and_then(|resolved| {
+                github::fetch_head_commit(&client, repo, None)
+                    .map(|head| days_behind(resolved, head.committed_at))
+            });
+
+            match staleness {
+                Ok(days) if days > stale_after => {
+                    progress!(
+                        "\n{} {}",
+                        t!("warning").yellow().bold(),
+                        t!(
+                            "stale-index",
+                            repo = repo,
+                            commit = &source.commit[..source.commit.len().min(7)],
+                            date = source.committed_at.as_str(),
+                            days = days
+                        )
+                    );
+                    progress!("  {}", t!("stale-hint"));
                 }
-            } else {
-                println!("Font not found in source library: {:?}", font);
+                Ok(_) => {}
+                Err(e) => progress!(
+                    "\n{} {}",
+                    t!("warning").yellow().bold(),
+                    t!("stale-failed", repo = repo, error = e.to_string())
+                ),
             }
         }
-        Ok(())
     }
 }
 
@@ -667,7 +2936,7 @@ struct TypstFontLibraryEntries {
     fonts: Vec<FontLibraryEntryDe>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 struct FontLibraryEntryDe {
     family_name: String,
     #[serde(default, with = "crate::parse_font_config::typst_font_serde")]
@@ -680,10 +2949,29 @@ struct FontLibraryEntryDe {
     optical_size: Option<LibraryAxisRange<f32>>,
     #[serde(default)]
     axes: Vec<LibraryCustomAxis>,
+    #[serde(default)]
+    designer: Option<String>,
+    #[serde(default)]
+    manufacturer: Option<String>,
+    #[serde(default)]
+    vendor_id: Option<String>,
+    #[serde(default)]
+    features: Vec<FontFeature>,
+    #[serde(default)]
+    class: Option<FontClass>,
+    #[serde(default)]
+    panose: Option<[u8; 10]>,
+    #[serde(default)]
+    license: Option<FontLicense>,
+    #[serde(default)]
+    fs_type: Option<u16>,
     path: PathBuf,
+    /// Face index inside a .ttc/.otc collection
+    #[serde(default)]
+    index: u32,
 }
 
-#[derive(Clone, Copy, Debug, Deserialize)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 #[serde(untagged)]
 enum LibraryFontValue<T> {
     Fixed(T),
@@ -705,14 +2993,14 @@ impl<T: Copy> LibraryFontValue<T> {
     }
 }
 
-#[derive(Clone, Copy, Debug, Deserialize)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 struct LibraryAxisRange<T> {
     min: T,
     max: T,
     default: T,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 struct LibraryCustomAxis {
     tag: String,
     min: f32,
@@ -766,7 +3054,18 @@ impl FontLibraryEntryDe {
                 stretch: self.stretch.default_value(),
             },
             path: self.path,
+            index: self.index,
             axes,
+            metadata: FontMetadata {
+                designer: self.designer,
+                manufacturer: self.manufacturer,
+                vendor_id: self.vendor_id,
+                features: self.features,
+                class: self.class,
+                panose: self.panose,
+                license: self.license,
+                fs_type: self.fs_type,
+            },
         }
     }
 }
@@ -872,20 +3171,11 @@ pub fn download_font_library_info<P>(github_repo: P) -> Result<String, Box<dyn s
 where
     P: AsRef<Path>,
 {
-    // Convert the input into a string
-    let repo_str = github_repo
-        .as_ref()
-        .to_str()
-        .ok_or_else(|| "Failed to convert path to string")?;
-
-    // Construct the URL to the raw file on GitHub
-    let url = format!(
-        "https://raw.githubusercontent.com/{}/main/font_library.toml",
-        repo_str
-    );
+    // Construct the URL to the raw index file on GitHub
+    let url = github::GitHubSource::parse(github_repo.as_ref())?.index_url();
 
     // Send a GET request to fetch the file
-    let response = get(&url)?;
+    let response = http::get(&http::client()?, &url)?.send()?;
     if !response.status().is_success() {
         return Err(format!("Failed to download file: HTTP {}", response.status()).into());
     }
@@ -904,16 +3194,17 @@ where
     P: AsRef<Path>,
 {
     // Download the font library info
-    let content =
-        download_font_library_info(&github_repo).expect("Failed to download font library info");
+    let content = download_font_library_info(&github_repo)
+        .map_err(|e| format!("Failed to download font library info: {e}"))?;
 
     // deserialize the font_library.toml file
     let mut library: TypstFontLibrary =
-        toml::from_str(&content).expect("Failed to deserialize from TOML");
+        toml::from_str(&content).map_err(|e| format!("Failed to parse font library info: {e}"))?;
 
     // Prepend the github_repo to the font paths
+    let repo = github::GitHubSource::parse(github_repo.as_ref())?.repo;
     for path in library.fonts.values_mut() {
-        *path = PathBuf::from(&github_repo.as_ref()).join(&mut *path);
+        *path = PathBuf::from(repo).join(&mut *path);
     }
 
     Ok(library.fonts)
@@ -925,23 +3216,214 @@ pub fn get_github_font_library_entries<P>(
 where
     P: AsRef<Path>,
 {
-    let content =
-        download_font_library_info(&github_repo).expect("Failed to download font library info");
+    let source = github::GitHubSource::parse(github_repo.as_ref())?;
+    let repo = source.repo;
+    // A mirror may be checked out at any revision, so pinned libraries don't use it
+    let mirrored = user_config::get()
+        .library
+        .local_mirror(repo)
+        .filter(|_| source.reference.is_none())
+        .and_then(|mirror| {
+            let index = mirror.join(source.index_path);
+            fs::read_to_string(&index)
+                .map_err(|e| format!("Failed to read {index:?}: {e}"))
+                .and_then(|content| parse_font_library_entries(&content))
+                .inspect_err(|e| progress!("{} {e}", t!("warning").yellow().bold()))
+                .ok()
+        });
+    let mut entries = match (fetch_cached_library_index(source), mirrored) {
+        (Ok(entries), mirrored) => {
+            let remote = entries.into_iter().map(FontLibraryEntryDe::into_discovered);
+            match mirrored {
+                // The remote index only adds the files the mirror lacks
+                Some(mut mirrored) => {
+                    let paths = mirrored
+                        .iter()
+                        .map(|entry| entry.path.clone())
+                        .collect::<HashSet<_>>();
+                    mirrored.extend(remote.filter(|entry| !paths.contains(&entry.path)));
+                    mirrored
+                }
+                None => remote.collect(),
+            }
+        }
+        (Err(e), Some(mirrored)) => {
+            progress!(
+                "{} {e}; using the local mirror of {repo} only",
+                t!("warning").yellow().bold()
+            );
+            mirrored
+        }
+        (Err(e), None) => return Err(format!("Failed to download font library info: {e}").into()),
+    };
 
-    let library: TypstFontLibraryEntries =
-        toml::from_str(&content).expect("Failed to deserialize from TOML");
+    // Font paths are relative to the repository root, wherever the index is
+    for entry in &mut entries {
+        entry.path = PathBuf::from(source.repo).join(&entry.path);
+    }
+
+    Ok(entries)
+}
+
+/// The copy of the GitHub library file at `path` ("owner/repo/...") in the
+/// local mirror of its repository, if there is one. Files of libraries pinned
+/// to a `reference` have none, as the mirror may be at any revision.
+fn local_mirror_file(path: &Path, reference: Option<&str>) -> Option<PathBuf> {
+    if reference.is_some() {
+        return None;
+    }
+    let mut components = path.components();
+    let repo = components
+        .by_ref()
+        .take(2)
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    let mirror = user_config::get().library.local_mirror(&repo)?;
+    Some(mirror.join(components.as_path())).filter(|file| file.is_file())
+}
+
+/// Pairs of `names` that differ only by case, which case-insensitive file systems
+/// can't hold side by side
+fn case_conflicts(names: &[String]) -> Vec<(&str, &str)> {
+    let mut seen = BTreeMap::new();
+    let mut conflicts = Vec::new();
+    for name in names {
+        match seen.entry(name.to_lowercase()) {
+            Entry::Vacant(entry) => {
+                entry.insert(name.as_str());
+            }
+            Entry::Occupied(entry) if *entry.get() != name => {
+                conflicts.push((*entry.get(), name.as_str()));
+            }
+            Entry::Occupied(_) => {}
+        }
+    }
+    conflicts
+}
+
+/// With `--locked`, a library file must have the hash the lockfile recorded for it
+fn check_locked_hash(lock: &FontLock, source: &str, sha256: &str) -> Result<(), String> {
+    let recorded = lock
+        .files
+        .iter()
+        .filter(|file| file.source == source)
+        .find_map(|file| file.provenance.as_ref()?.sha256.as_deref());
+    match recorded {
+        Some(recorded) if recorded != sha256 => Err(format!(
+            "{source} has SHA-256 {sha256}, but font_config.lock records {recorded}"
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// With `--locked`, the installed file must also be the one the lockfile records,
+/// as converting or stripping it on another machine may produce different bytes
+fn check_locked_file(
+    lock: &FontLock,
+    installed: &LockedFile,
+    hash_algo: HashAlgo,
+) -> Result<(), String> {
+    let recorded = lock
+        .files
+        .iter()
+        .find(|file| file.source == installed.source && file.file == installed.file);
+    let Some(recorded) = recorded else {
+        return Err(format!(
+            "{} was installed as {}, which font_config.lock doesn't record \
+             (run update without --locked to update the lockfile)",
+            installed.source, installed.file
+        ));
+    };
+    // With BLAKE3 asked for, the installed file records it as well
+    let (algo, expected) = recorded.recorded_digest(hash_algo);
+    let actual = installed.recorded_digest(algo).1;
+    if actual != expected {
+        return Err(format!(
+            "{} was installed with {} {actual}, but font_config.lock records {expected}",
+            installed.file,
+            algo.name()
+        ));
+    }
+    Ok(())
+}
+
+/// The entries of the index of `source`, read through the index cache. The index is
+/// requested with the cached ETag; if the server reports it unchanged, or sends the
+/// same content again, the cached entries are used without parsing the file.
+fn fetch_cached_library_index(
+    source: github::GitHubSource,
+) -> Result<Vec<FontLibraryEntryDe>, String> {
+    let key = match source.index_path {
+        github::DEFAULT_INDEX_PATH => source.to_string(),
+        index_path => format!("{source}::{index_path}"),
+    };
+    let cached = cache::load_index::<FontLibraryEntryDe>(&key);
+    if http::offline() {
+        return cached.map(|cached| cached.fonts).ok_or_else(|| {
+            format!("{key} has no cached index, and network access is disabled by --frozen")
+        });
+    }
+
+    let url = source.index_url();
+    let _span = trace::span("download", source.index_path).arg("url", &url);
+    let mut request = http::get(&http::client()?, &url)?;
+    if let Some(etag) = cached.as_ref().and_then(|cached| cached.etag.as_deref()) {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+    let response = request.send().map_err(|e| format!("{url}: {e}"))?;
+
+    if response.status() == StatusCode::NOT_MODIFIED
+        && let Some(cached) = cached
+    {
+        return Ok(cached.fonts);
+    }
+    if !response.status().is_success() {
+        return Err(format!(
+            "Failed to download file: HTTP {}",
+            response.status()
+        ));
+    }
+
+    let etag = response
+        .headers()
+        .get(ETAG)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let content = response.text().map_err(|e| format!("{url}: {e}"))?;
+    let sha256 = sha256_hex(content.as_bytes());
+
+    let fonts = match cached {
+        Some(cached) if cached.sha256 == sha256 => cached.fonts,
+        _ => {
+            let library: TypstFontLibraryEntries = toml::from_str(&content)
+                .map_err(|e| format!("Failed to parse font library info: {e}"))?;
+            library.fonts
+        }
+    };
+
+    let index = CachedIndex {
+        source: key,
+        etag,
+        sha256,
+        fetched_at: format_timestamp(OffsetDateTime::now_utc()),
+        fonts,
+    };
+    if let Err(e) = cache::store_index(&index) {
+        progress!("{} {e}", t!("warning").yellow().bold());
+    }
+    Ok(index.fonts)
+}
 
-    let entries = library
+/// Parse the entries of a font_library.toml, keeping their paths as written
+pub(crate) fn parse_font_library_entries(content: &str) -> Result<Vec<DiscoveredFont>, String> {
+    let library: TypstFontLibraryEntries =
+        toml::from_str(content).map_err(|e| format!("Failed to parse font library info: {e}"))?;
+    Ok(library
         .fonts
         .into_iter()
-        .map(|entry| {
-            let mut entry = entry.into_discovered();
-            entry.path = PathBuf::from(&github_repo.as_ref()).join(&entry.path);
-            entry
-        })
-        .collect();
-
-    Ok(entries)
+        .map(FontLibraryEntryDe::into_discovered)
+        .collect())
 }
 
 #[cfg(test)]
@@ -965,7 +3447,9 @@ mod tests {
         DiscoveredFont {
             font,
             path: PathBuf::from(path),
+            index: 0,
             axes,
+            metadata: FontMetadata::default(),
         }
     }
 
@@ -1039,11 +3523,151 @@ mod tests {
             vec![axis(StandardAxes::WGHT, 100.0, 900.0, 400.0)],
         );
 
-        let formatted = format_discovered_font(&variable);
+        let formatted = format_discovered_variant(&variable);
         assert!(formatted.contains("weight: 100-900"));
         assert!(!formatted.contains("FontWeight"));
     }
 
+    #[test]
+    fn test_group_by_family_keeps_variants_together() {
+        let fonts = BTreeSet::from([
+            font("Noto Sans", FontStyle::Normal, 400, FontStretch::NORMAL),
+            font("Lato", FontStyle::Italic, 700, FontStretch::NORMAL),
+            font("Noto Sans", FontStyle::Italic, 400, FontStretch::NORMAL),
+        ]);
+
+        let families = group_by_family(&fonts);
+
+        assert_eq!(
+            families.keys().copied().collect::<Vec<_>>(),
+            ["Lato", "Noto Sans"]
+        );
+        assert_eq!(families["Noto Sans"].len(), 2);
+    }
+
+    #[test]
+    fn test_sort_status_rows_by_status_puts_missing_first() {
+        let present = font("Lato", FontStyle::Normal, 400, FontStretch::NORMAL);
+        let missing = font("Noto Sans", FontStyle::Normal, 400, FontStretch::NORMAL);
+        let row = |font, status| StatusRow {
+            font,
+            status,
+            entry: None,
+            source: None,
+            size: None,
+        };
+        let mut rows = vec![
+            row(&present, FontStatus::Present),
+            row(&missing, FontStatus::Missing),
+        ];
+
+        sort_status_rows(&mut rows, SortKey::Status);
+        assert_eq!(rows[0].font, &missing);
+
+        sort_status_rows(&mut rows, SortKey::Family);
+        assert_eq!(rows[0].font, &present);
+    }
+
+    #[test]
+    fn test_faux_difference_names_synthesized_properties() {
+        let regular = font("Lato", FontStyle::Normal, 400, FontStretch::NORMAL);
+        let bold_italic = font("Lato", FontStyle::Italic, 700, FontStretch::NORMAL);
+        let italic = font("Lato", FontStyle::Italic, 400, FontStretch::NORMAL);
+
+        assert_eq!(faux_difference(&regular, &bold_italic), "italic and bold");
+        assert_eq!(faux_difference(&italic, &bold_italic), "bold");
+        assert!(variant_distance(&italic, &bold_italic) < variant_distance(&regular, &bold_italic));
+    }
+
+    #[test]
+    fn test_report_filter_matches_family_glob_case_insensitively() {
+        let filter = ReportFilter::new(ReportSets::Missing, Some("noto*")).unwrap();
+
+        assert!(filter.includes_family(&font(
+            "Noto Sans",
+            FontStyle::Normal,
+            400,
+            FontStretch::NORMAL
+        )));
+        assert!(!filter.includes_family(&font(
+            "Lato",
+            FontStyle::Normal,
+            400,
+            FontStretch::NORMAL
+        )));
+        assert!(filter.includes_status(FontStatus::Fixable));
+        assert!(!filter.includes_status(FontStatus::Redundant));
+        assert!(ReportFilter::new(ReportSets::All, Some("[")).is_err());
+    }
+
+    #[test]
+    fn test_disable_sources_by_id_or_path() {
+        let mut library_dirs = LibraryDirs::GitHub(vec![
+            PathBuf::from("owner/fonts"),
+            PathBuf::from("owner/fonts::indexes/cjk.toml"),
+            PathBuf::from("org/print"),
+        ]);
+
+        library_dirs
+            .disable_sources(&["github:owner/fonts".to_string(), "org/print".to_string()])
+            .unwrap();
+        assert_eq!(
+            library_dirs.into_iter().collect::<Vec<_>>(),
+            [&PathBuf::from("owner/fonts::indexes/cjk.toml")]
+        );
+        assert!(
+            library_dirs
+                .disable_sources(&["github:missing/repo".to_string()])
+                .is_err()
+        );
+
+        let (source, relative) = library_dirs
+            .locate(Path::new("owner/fonts/cjk/NotoSerifCJK.otf"))
+            .unwrap();
+        assert_eq!(source, "github:owner/fonts::indexes/cjk.toml");
+        assert_eq!(relative, Path::new("cjk/NotoSerifCJK.otf"));
+    }
+
+    #[test]
+    fn test_status_summary_display() {
+        let summary = StatusSummary {
+            missing: 3,
+            faux: 0,
+            fixable: 2,
+            redundant: 1,
+        };
+        assert_eq!(summary.to_string(), "3 missing, 2 fixable, 1 redundant");
+
+        let summary = StatusSummary { faux: 1, ..summary };
+        assert_eq!(
+            summary.to_string(),
+            "3 missing (1 faux), 2 fixable, 1 redundant"
+        );
+    }
+
+    #[test]
+    fn test_status_summary_failure() {
+        let summary = StatusSummary {
+            missing: 2,
+            faux: 0,
+            fixable: 2,
+            redundant: 1,
+        };
+        assert_eq!(summary.failure(false), Some(CheckFailure::Fixable));
+        let summary = StatusSummary {
+            fixable: 1,
+            ..summary
+        };
+        assert_eq!(summary.failure(false), Some(CheckFailure::Unavailable));
+        let summary = StatusSummary {
+            missing: 0,
+            fixable: 0,
+            ..summary
+        };
+        assert_eq!(summary.failure(false), None);
+        assert_eq!(summary.failure(true), Some(CheckFailure::Redundant));
+    }
+
     #[test]
     fn test_dry_run_update_does_not_copy_local_font() {
         let target_dir = env::var("CARGO_TARGET_DIR")
@@ -1063,9 +3687,19 @@ mod tests {
         let missing_font = font("Example", FontStyle::Normal, 400, FontStretch::NORMAL);
         let manager = FontManager {
             config_file: project_dir.join("font_config.toml"),
+            pack_tree: Vec::new(),
             font_config: FontConfig {
                 font_dir: Some("fonts".to_string()),
+                font_format: None,
+                redistribution: None,
+                pack: Vec::new(),
+                package_fonts: None,
+                quarantine: None,
+                root: None,
                 fonts: vec![missing_font.clone()],
+                file_choices: BTreeMap::new(),
+                source_priority: Vec::new(),
+                ignore_families: Vec::new(),
             },
             library_dirs: LibraryDirs::Local(vec![library_dir]),
             absolute_font_dir: absolute_font_dir.clone(),
@@ -1079,19 +3713,303 @@ mod tests {
                 library_entries: vec![DiscoveredFont {
                     font: missing_font,
                     path: source_path.clone(),
+                    index: 0,
                     axes: Vec::new(),
+                    metadata: FontMetadata::default(),
                 }],
             },
             action: "Updating",
+            output: OutputLayout::Tree,
+            sort_by: SortKey::Family,
+            report_filter: ReportFilter::default(),
+            namespace_sources: false,
         };
 
-        manager.update_fonts(true).unwrap();
+        manager
+            .update_fonts(&UpdateOptions {
+                dry_run: true,
+                ..Default::default()
+            })
+            .unwrap();
 
         assert!(source_path.exists());
         assert!(!absolute_font_dir.exists());
         assert!(!absolute_font_dir.join("Example-Regular.ttf").exists());
     }
 
+    #[cfg(feature = "test-fixtures")]
+    #[test]
+    fn test_status_families_tell_apart_equal_counts() {
+        let dir = env::temp_dir().join(format!("typfont-status-families-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let (library, project) = (dir.join("library"), dir.join("project"));
+        crate::fixtures::write_fonts(&library).unwrap();
+        fs::create_dir_all(&project).unwrap();
+        let args = FontCommand {
+            library: Some(vec![library]),
+            ..FontCommand::project(project.clone())
+        };
+        let status = |family: &str| {
+            fs::write(
+                project.join("font_config.toml"),
+                format!("font_dir = \"fonts\"\n\n[[fonts]]\nfamily_name = \"{family}\"\n"),
+            )
+            .unwrap();
+            let font_manager = FontManager::new(&args, "Checking").unwrap();
+            (font_manager.summary(), font_manager.status_families())
+        };
+
+        let (alpha_summary, alpha) = status("Alpha");
+        let (beta_summary, beta) = status("Beta");
+        assert_eq!(alpha_summary, beta_summary);
+        assert_ne!(alpha, beta);
+        assert_eq!(alpha.missing, BTreeSet::from(["Alpha".to_string()]));
+        assert!(alpha.fixable.is_empty());
+
+        let (_, fixture) = status(crate::fixtures::FAMILY);
+        assert_eq!(fixture.missing, fixture.fixable);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "test-fixtures")]
+    #[test]
+    fn test_system_fonts_of_another_version_are_reported() {
+        let dir = env::temp_dir().join(format!("typfont-versions-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let (project, system) = (dir.join("fonts"), dir.join("system"));
+        crate::fixtures::write_fonts(&project).unwrap();
+        crate::fixtures::write_fonts(&system).unwrap();
+        // Version 1.000 of the Bold face becomes 2.000 on the system
+        let (bold, data) = crate::fixtures::FONTS[1];
+        let utf16 = |text: &str| {
+            text.encode_utf16()
+                .flat_map(u16::to_be_bytes)
+                .collect::<Vec<_>>()
+        };
+        let (old, new) = (utf16("Version 1.000"), utf16("Version 2.000"));
+        let mut patched = data.to_vec();
+        let at = patched
+            .windows(old.len())
+            .position(|window| window == old)
+            .unwrap();
+        patched[at..at + old.len()].copy_from_slice(&new);
+        fs::write(system.join(bold), patched).unwrap();
+
+        let installed = crate::create_font_entries(&project);
+        let system_entries = crate::create_font_entries(&system);
+        let required = installed
+            .iter()
+            .map(|entry| &entry.font)
+            .collect::<Vec<_>>();
+        let mismatches = system_version_mismatches(required, &installed, &system_entries, &project);
+        assert_eq!(mismatches.len(), 1, "{mismatches:?}");
+        assert_eq!(mismatches[0].font.weight.to_number(), 700);
+        assert_eq!(mismatches[0].version, "1.000");
+        assert_eq!(mismatches[0].system_version, "2.000");
+        assert_eq!(mismatches[0].system.path, system.join(bold));
+
+        // Fonts in the project font directory are the installed ones
+        let mismatches = system_version_mismatches(
+            installed.iter().map(|entry| &entry.font),
+            &installed,
+            &installed,
+            &project,
+        );
+        assert!(mismatches.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_font_version_is_read_from_the_name_table() {
+        let path = Path::new("/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf");
+        if !path.exists() {
+            return;
+        }
+        let version = font_version(path).unwrap();
+        assert!(version.starts_with(char::is_numeric), "{version}");
+        assert_eq!(font_version(Path::new("absent.ttf")), None);
+    }
+
+    #[test]
+    fn test_source_priority_overrides_path_order_per_family() {
+        let cjk = font(
+            "Noto Sans CJK SC",
+            FontStyle::Normal,
+            400,
+            FontStretch::NORMAL,
+        );
+        let latin = font("Noto Sans", FontStyle::Normal, 400, FontStretch::NORMAL);
+        let rule = |source: &str, priority, families: &[&str]| SourcePriority {
+            source: source.to_string(),
+            priority,
+            families: families.iter().map(|family| family.to_string()).collect(),
+        };
+        let manager = FontManager {
+            config_file: PathBuf::from("font_config.toml"),
+            pack_tree: Vec::new(),
+            font_config: FontConfig {
+                font_dir: None,
+                font_format: None,
+                redistribution: None,
+                pack: Vec::new(),
+                package_fonts: None,
+                quarantine: None,
+                root: None,
+                fonts: vec![cjk.clone(), latin.clone()],
+                file_choices: BTreeMap::new(),
+                source_priority: vec![
+                    rule("github:corp/mirror", -1, &[]),
+                    rule("corp/mirror", 10, &["*CJK*"]),
+                ],
+                ignore_families: Vec::new(),
+            },
+            library_dirs: LibraryDirs::GitHub(vec![
+                PathBuf::from("corp/mirror"),
+                PathBuf::from("gooduser/font_lib"),
+            ]),
+            absolute_font_dir: PathBuf::from("fonts"),
+            font_sets: FontSets {
+                required: BTreeSet::from([cjk.clone(), latin.clone()]),
+                current: BTreeSet::new(),
+                current_entries: Vec::new(),
+                embedded: BTreeSet::new(),
+                missing: BTreeSet::from([cjk.clone(), latin.clone()]),
+                redundant: BTreeSet::new(),
+                library_entries: vec![
+                    discovered(cjk.clone(), "corp/mirror/NotoSansCJK.ttc", Vec::new()),
+                    discovered(cjk.clone(), "gooduser/font_lib/NotoSansCJK.ttc", Vec::new()),
+                    discovered(latin.clone(), "corp/mirror/NotoSans.ttf", Vec::new()),
+                    discovered(latin.clone(), "gooduser/font_lib/NotoSans.ttf", Vec::new()),
+                ],
+            },
+            action: "Checking",
+            output: OutputLayout::Tree,
+            sort_by: SortKey::Family,
+            report_filter: ReportFilter::default(),
+            namespace_sources: false,
+        };
+
+        let chosen = |font| manager.select_library_candidate(font).unwrap().path.clone();
+        assert_eq!(chosen(&cjk), PathBuf::from("corp/mirror/NotoSansCJK.ttc"));
+        assert_eq!(
+            chosen(&latin),
+            PathBuf::from("gooduser/font_lib/NotoSans.ttf")
+        );
+    }
+
+    #[test]
+    fn test_case_conflicts() {
+        let names = [
+            "NotoSans.ttf",
+            "Inter.otf",
+            "notosans.ttf",
+            "Inter.otf",
+            "NOTOSANS.TTF",
+        ]
+        .map(String::from);
+        assert_eq!(
+            case_conflicts(&names),
+            [
+                ("NotoSans.ttf", "notosans.ttf"),
+                ("NotoSans.ttf", "NOTOSANS.TTF")
+            ]
+        );
+        assert!(case_conflicts(&names[..2]).is_empty());
+    }
+
+    #[test]
+    fn test_locked_hash_must_match_the_recorded_one() {
+        let lock: FontLock = toml::from_str(
+            r#"
+[[files]]
+file = "A.ttf"
+source = "../lib/A.ttf"
+sha256 = "stripped"
+
+[files.provenance]
+source_type = "local"
+original_file = "A.ttf"
+sha256 = "aaaa"
+installed_by = "typfont 0.2.0"
+"#,
+        )
+        .unwrap();
+
+        assert!(check_locked_hash(&lock, "../lib/A.ttf", "aaaa").is_ok());
+        let error = check_locked_hash(&lock, "../lib/A.ttf", "bbbb").unwrap_err();
+        assert!(error.contains("../lib/A.ttf has SHA-256 bbbb"));
+        // Files installed without a recorded hash can't be verified
+        assert!(check_locked_hash(&lock, "../lib/B.ttf", "bbbb").is_ok());
+
+        let mut installed = lock.files[0].clone();
+        assert!(check_locked_file(&lock, &installed, HashAlgo::Sha256).is_ok());
+        installed.sha256 = "converted".to_string();
+        let error = check_locked_file(&lock, &installed, HashAlgo::Sha256).unwrap_err();
+        assert!(error.contains("A.ttf was installed with SHA-256 converted"));
+        // BLAKE3 is compared where the lockfile records it
+        let mut blake3_lock = FontLock::default();
+        blake3_lock.set_file(LockedFile {
+            blake3: Some("b3".to_string()),
+            ..lock.files[0].clone()
+        });
+        installed.blake3 = Some("b3".to_string());
+        assert!(check_locked_file(&blake3_lock, &installed, HashAlgo::Blake3).is_ok());
+        installed.blake3 = Some("other".to_string());
+        let error = check_locked_file(&blake3_lock, &installed, HashAlgo::Blake3).unwrap_err();
+        assert!(error.contains("A.ttf was installed with BLAKE3 other"));
+        installed.file = "A.woff2".to_string();
+        let error = check_locked_file(&lock, &installed, HashAlgo::Sha256).unwrap_err();
+        assert!(error.contains("../lib/A.ttf was installed as A.woff2"));
+    }
+
+    #[test]
+    fn test_status_report_lists_missing_and_redundant_fonts() {
+        let bold = font("Noto Sans", FontStyle::Normal, 700, FontStretch::NORMAL);
+        let thin = font("Noto Sans", FontStyle::Normal, 100, FontStretch::NORMAL);
+        let manager = FontManager {
+            config_file: PathBuf::from("paper/font_config.toml"),
+            pack_tree: Vec::new(),
+            font_config: FontConfig {
+                font_dir: None,
+                font_format: None,
+                redistribution: None,
+                pack: Vec::new(),
+                package_fonts: None,
+                quarantine: None,
+                root: None,
+                fonts: vec![bold.clone()],
+                file_choices: BTreeMap::new(),
+                source_priority: Vec::new(),
+                ignore_families: Vec::new(),
+            },
+            library_dirs: LibraryDirs::Local(Vec::new()),
+            absolute_font_dir: PathBuf::from("paper/fonts"),
+            font_sets: FontSets {
+                required: BTreeSet::from([bold.clone()]),
+                current: BTreeSet::from([thin.clone()]),
+                current_entries: vec![discovered(thin.clone(), "paper/fonts/Thin.ttf", Vec::new())],
+                embedded: BTreeSet::new(),
+                missing: BTreeSet::from([bold]),
+                redundant: BTreeSet::from([thin]),
+                library_entries: Vec::new(),
+            },
+            action: "Checking",
+            output: OutputLayout::Tree,
+            sort_by: SortKey::Family,
+            report_filter: ReportFilter::default(),
+            namespace_sources: false,
+        };
+
+        let report = serde_json::to_value(manager.status_report()).unwrap();
+        assert_eq!(report["project"], "paper/font_config.toml");
+        assert_eq!(report["missing"], 1);
+        assert_eq!(report["fixable"], 0);
+        assert_eq!(report["redundant"], 1);
+        assert_eq!(report["missing_fonts"][0]["family_name"], "Noto Sans");
+        assert_eq!(report["missing_fonts"][0]["weight"], 700);
+        assert_eq!(report["redundant_fonts"][0]["weight"], 100);
+    }
+
     #[test]
     fn test_resolve_config_file_accepts_project_root_or_config_path() {
         let target_dir = env::var("CARGO_TARGET_DIR")
@@ -1152,7 +4070,7 @@ mod tests {
         let toml = toml::to_string_pretty(&library).expect("Failed to serialize to TOML");
         fs::write(&file_path, toml.as_bytes()).expect("Failed to write to file");
 
-        println!("TOML written to: {:?}", file_path);
+        progress!("TOML written to: {:?}", file_path);
 
         // Read and deserialize
         let contents = fs::read_to_string(&file_path).expect("Failed to read file");
@@ -1216,6 +4134,85 @@ path = "NotoSans/NotoSans-Italic-VariableFont_wdth,wght.ttf"
         ));
     }
 
+    #[test]
+    fn test_github_file_url_keeps_nested_paths() {
+        let (repo, url) =
+            github_file_url(Path::new("owner/fonts/Lato/static/Lato-Bold.ttf"), None).unwrap();
+        assert_eq!(repo, "owner/fonts");
+        assert_eq!(
+            url,
+            "https://raw.githubusercontent.com/owner/fonts/main/Lato/static/Lato-Bold.ttf"
+        );
+        let (_, url) = github_file_url(
+            Path::new("owner/fonts/Lato/Lato-Bold.ttf"),
+            Some("release/2024"),
+        )
+        .unwrap();
+        assert_eq!(
+            url,
+            "https://raw.githubusercontent.com/owner/fonts/release%2F2024/Lato/Lato-Bold.ttf"
+        );
+        assert!(github_file_url(Path::new("owner/fonts"), None).is_err());
+    }
+
+    #[test]
+    fn test_collection_entry_keeps_face_index() {
+        let toml = r#"[[fonts]]
+family_name = "Noto Serif CJK SC"
+style = "Normal"
+weight = 700
+stretch = 1000
+path = "NotoSerifCJK/NotoSerifCJK-Bold.ttc"
+index = 2
+"#;
+
+        let library: TypstFontLibrary = toml::from_str(toml).unwrap();
+        assert_eq!(library.fonts.len(), 1);
+
+        let entries: TypstFontLibraryEntries = toml::from_str(toml).unwrap();
+        let discovered = entries
+            .fonts
+            .into_iter()
+            .map(FontLibraryEntryDe::into_discovered)
+            .collect::<Vec<_>>();
+        assert_eq!(discovered[0].index, 2);
+        assert_eq!(
+            discovered[0].display_path(),
+            "NotoSerifCJK/NotoSerifCJK-Bold.ttc [face 2]"
+        );
+    }
+
+    #[test]
+    fn test_cached_index_entries_round_trip() {
+        let toml = r#"[[fonts]]
+family_name = "Inter"
+style = "Italic"
+weight = { min = 100, max = 900, default = 400 }
+stretch = 1000
+axes = [{ tag = "slnt", min = -10.0, max = 0.0, default = 0.0 }]
+designer = "Rasmus Andersson"
+class = "sans"
+panose = [2, 11, 5, 2, 3, 0, 0, 0, 0, 4]
+license = "ofl"
+fs_type = 8
+path = "Inter/Inter-Italic.ttf"
+"#;
+        let entries: TypstFontLibraryEntries = toml::from_str(toml).unwrap();
+        let cached = serde_json::to_vec(&entries.fonts).unwrap();
+        let restored: Vec<FontLibraryEntryDe> = serde_json::from_slice(&cached).unwrap();
+
+        let original = entries.fonts.into_iter().next().unwrap().into_discovered();
+        let restored = restored.into_iter().next().unwrap().into_discovered();
+        assert_eq!(restored.font, original.font);
+        assert_eq!(restored.axes, original.axes);
+        assert_eq!(restored.metadata.designer, original.metadata.designer);
+        assert_eq!(restored.metadata.class, Some(FontClass::Sans));
+        assert_eq!(restored.metadata.panose, original.metadata.panose);
+        assert_eq!(restored.metadata.license, Some(FontLicense::Ofl));
+        assert_eq!(restored.metadata.fs_type, Some(8));
+        assert_eq!(restored.path, original.path);
+    }
+
     #[test]
     #[ignore]
     fn test_local_font_library_serialization() {
@@ -1241,7 +4238,7 @@ path = "NotoSans/NotoSans-Italic-VariableFont_wdth,wght.ttf"
 
         let library_dirs = LibraryDirs::Local(vec![library_dir.clone()]);
 
-        let mut font_lib_map = create_font_path_map_from_dirs(&library_dirs);
+        let mut font_lib_map = create_font_path_map_from_dirs(&library_dirs).unwrap();
 
         strip_library_root_path(&mut font_lib_map, &library_dir);
 
@@ -1252,17 +4249,17 @@ path = "NotoSans/NotoSans-Italic-VariableFont_wdth,wght.ttf"
         let toml = toml::to_string_pretty(&library).expect("Failed to serialize to TOML");
         fs::write(&file_path, toml.as_bytes()).expect("Failed to write to file");
 
-        println!("TOML written to: {:?}", file_path);
+        progress!("TOML written to: {:?}", file_path);
     }
 
     #[test]
     fn test_download_font_library_info() {
         let github_repo = "hooyuser/Font_Library";
         let content = download_font_library_info(github_repo).unwrap();
-        println!("{}", content);
+        progress!("{}", content);
 
         // deserialize the content
         let library: TypstFontLibrary = toml::from_str(&content).unwrap();
-        println!("{:?}", library);
+        progress!("{:?}", library);
     }
 }
@@ -0,0 +1,423 @@
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::OnceLock;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+use crate::http::{self, Client};
+
+const GITHUB_API: &str = "https://api.github.com";
+const GITHUB_RAW: &str = "https://raw.githubusercontent.com";
+const DEFAULT_BRANCH: &str = "main";
+
+/// Index file read from a GitHub library unless the source names another one
+pub(crate) const DEFAULT_INDEX_PATH: &str = "font_library.toml";
+
+/// A GitHub font library, given as `owner/repo` or `owner/repo::path/to/index.toml`
+/// to read a curated index other than the `font_library.toml` at the repository root.
+/// Font paths in the index are always relative to the repository root.
+/// `owner/repo@v1.2.0` pins the library to a branch, tag or commit instead of the
+/// default branch.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct GitHubSource<'a> {
+    pub(crate) repo: &'a str,
+    /// The branch, tag or commit given after `@`, which may contain `/`
+    pub(crate) reference: Option<&'a str>,
+    pub(crate) index_path: &'a str,
+}
+
+impl<'a> GitHubSource<'a> {
+    pub(crate) fn parse(source: &'a Path) -> Result<Self, String> {
+        let source = source
+            .to_str()
+            .ok_or_else(|| format!("Invalid GitHub source {source:?}"))?;
+        let (root, index_path) = match source.split_once("::") {
+            Some((root, index_path)) => (root, index_path.trim_start_matches('/')),
+            None => (source, DEFAULT_INDEX_PATH),
+        };
+        let (repo, reference) = match root.split_once('@') {
+            Some((repo, reference)) => (repo, Some(reference)),
+            None => (root, None),
+        };
+
+        let mut segments = repo.split('/');
+        let valid_repo = matches!(
+            (segments.next(), segments.next(), segments.next()),
+            (Some(owner), Some(name), None) if !owner.is_empty() && !name.is_empty()
+        );
+        if !valid_repo || index_path.is_empty() || reference == Some("") {
+            return Err(format!(
+                "Invalid GitHub source {source:?}, expected \"owner/repo\", \"owner/repo@ref\" or \"owner/repo::path/to/index.toml\""
+            ));
+        }
+
+        Ok(Self {
+            repo,
+            reference,
+            index_path,
+        })
+    }
+
+    /// URL of the raw index file
+    pub(crate) fn index_url(&self) -> String {
+        self.file_url(self.index_path)
+    }
+
+    /// URL of the raw content of `path` in the repository, at the pinned revision
+    pub(crate) fn file_url(&self, path: &str) -> String {
+        raw_file_url(self.repo, self.reference, path)
+    }
+}
+
+/// `owner/repo`, or `owner/repo@ref` when pinned
+impl std::fmt::Display for GitHubSource<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.reference {
+            Some(reference) => write!(f, "{}@{reference}", self.repo),
+            None => f.write_str(self.repo),
+        }
+    }
+}
+
+/// `reference` as a single segment of a URL path, defaulting to the default branch.
+/// Branches such as `release/2024` keep their `/` escaped, so that the rest of
+/// the path isn't taken for part of the branch name.
+fn url_reference(reference: Option<&str>) -> String {
+    reference
+        .unwrap_or(DEFAULT_BRANCH)
+        .replace('%', "%25")
+        .replace('/', "%2F")
+}
+
+/// The server standing in for GitHub, set by `--mock-server`
+static MOCK_SERVER: OnceLock<String> = OnceLock::new();
+
+/// Send every GitHub request to the server at `url` instead, which serves raw
+/// files at `/owner/repo/branch/path` and API responses below `/api`
+pub(crate) fn use_mock_server(url: &str) {
+    let _ = MOCK_SERVER.set(url.trim_end_matches('/').to_string());
+}
+
+/// The token given by `--token`
+static TOKEN: OnceLock<String> = OnceLock::new();
+
+/// Authorize GitHub requests with `token` unless a credential profile applies
+pub(crate) fn set_token(token: &str) {
+    let _ = TOKEN.set(token.to_string());
+}
+
+/// The token of GitHub requests without a credential profile: `--token`, or else
+/// `GITHUB_TOKEN` or `GH_TOKEN`, as in GitHub Actions
+pub(crate) fn default_token() -> Option<String> {
+    TOKEN.get().cloned().or_else(|| {
+        ["GITHUB_TOKEN", "GH_TOKEN"]
+            .into_iter()
+            .find_map(|name| std::env::var(name).ok().filter(|token| !token.is_empty()))
+    })
+}
+
+fn raw_base() -> &'static str {
+    MOCK_SERVER.get().map_or(GITHUB_RAW, String::as_str)
+}
+
+/// The base URL of the GitHub REST API
+pub(crate) fn api_base() -> String {
+    match MOCK_SERVER.get() {
+        Some(url) => format!("{url}/api"),
+        None => GITHUB_API.to_string(),
+    }
+}
+
+/// The repository ("owner/repo") a raw file or REST API URL of GitHub is about
+pub(crate) fn repo_of_url(url: &str) -> Option<&str> {
+    // The API first, as the mock server serves it below the raw files
+    let rest = match url.strip_prefix(&api_base()) {
+        Some(path) => path.strip_prefix("/repos")?,
+        None => url.strip_prefix(raw_base())?,
+    }
+    .strip_prefix('/')?;
+    let mut segments = rest.split(['/', '?', '#']);
+    let (owner, name) = (segments.next()?, segments.next()?);
+    let repo = &rest[..owner.len() + 1 + name.len()];
+    (!owner.is_empty() && !name.is_empty() && repo.as_bytes()[owner.len()] == b'/').then_some(repo)
+}
+
+/// The contents API URL serving the same file as the raw file URL `url`, which
+/// authenticated requests use, as their rate limit is that of the token
+pub(crate) fn contents_api_url(url: &str) -> Option<String> {
+    if url.starts_with(&api_base()) {
+        return None;
+    }
+    let mut segments = url
+        .strip_prefix(raw_base())?
+        .strip_prefix('/')?
+        .splitn(4, '/');
+    let (owner, name, reference, path) = (
+        segments.next()?,
+        segments.next()?,
+        segments.next()?,
+        segments.next()?,
+    );
+    if [owner, name, reference, path].contains(&"") {
+        return None;
+    }
+    Some(format!(
+        "{}/repos/{owner}/{name}/contents/{path}?ref={reference}",
+        api_base()
+    ))
+}
+
+/// URL of the raw content of `path` in `repo` ("owner/repo"), on the default
+/// branch or at the given branch, tag or commit
+pub(crate) fn raw_file_url(repo: &str, reference: Option<&str>, path: &str) -> String {
+    format!("{}/{repo}/{}/{path}", raw_base(), url_reference(reference))
+}
+
+/// A commit of a GitHub font library repository, as reported by the GitHub API
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct RepoCommit {
+    pub(crate) sha: String,
+    pub(crate) committed_at: OffsetDateTime,
+}
+
+#[derive(Deserialize)]
+struct TreeResponse {
+    tree: Vec<TreeEntry>,
+    truncated: bool,
+}
+
+#[derive(Deserialize)]
+struct TreeEntry {
+    path: String,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+#[derive(Deserialize)]
+struct CommitResponse {
+    sha: String,
+    commit: CommitDetails,
+}
+
+#[derive(Deserialize)]
+struct CommitDetails {
+    committer: CommitSignature,
+}
+
+#[derive(Deserialize)]
+struct CommitSignature {
+    date: String,
+}
+
+/// Query the head commit of the default branch of `repo` ("owner/repo"), or the
+/// commit its branch, tag or commit `reference` points to. Only the commit object
+/// is requested, so this is a single cheap API call.
+pub(crate) fn fetch_head_commit(
+    client: &Client,
+    repo: &str,
+    reference: Option<&str>,
+) -> Result<RepoCommit, String> {
+    let url = format!(
+        "{}/repos/{repo}/commits/{}",
+        api_base(),
+        url_reference(reference)
+    );
+
+    let response = http::get(client, &url)?
+        .header("Accept", "application/vnd.github+json")
+        .send()
+        .map_err(|e| format!("Failed to query {repo}: {e}"))?;
+
+    if !response.status().is_success() {
+        return Err(format!(
+            "Failed to query {repo}: HTTP {}",
+            response.status()
+        ));
+    }
+
+    let body: CommitResponse = response
+        .text()
+        .map_err(|e| format!("Failed to read response for {repo}: {e}"))
+        .and_then(|text| {
+            serde_json::from_str(&text).map_err(|e| format!("Unexpected response for {repo}: {e}"))
+        })?;
+
+    Ok(RepoCommit {
+        sha: body.sha,
+        committed_at: parse_timestamp(&body.commit.committer.date)?,
+    })
+}
+
+/// List the paths of all files on the default branch of `repo` ("owner/repo"), or
+/// at its branch, tag or commit `reference`
+pub(crate) fn fetch_file_paths(
+    client: &Client,
+    repo: &str,
+    reference: Option<&str>,
+) -> Result<Vec<String>, String> {
+    let url = format!(
+        "{}/repos/{repo}/git/trees/{}?recursive=1",
+        api_base(),
+        url_reference(reference)
+    );
+
+    let response = http::get(client, &url)?
+        .header("Accept", "application/vnd.github+json")
+        .send()
+        .map_err(|e| format!("Failed to list files of {repo}: {e}"))?;
+
+    if !response.status().is_success() {
+        return Err(format!(
+            "Failed to list files of {repo}: HTTP {}",
+            response.status()
+        ));
+    }
+
+    let body: TreeResponse = response
+        .text()
+        .map_err(|e| format!("Failed to read response for {repo}: {e}"))
+        .and_then(|text| {
+            serde_json::from_str(&text).map_err(|e| format!("Unexpected response for {repo}: {e}"))
+        })?;
+    if body.truncated {
+        return Err(format!(
+            "{repo} has too many files to be listed by the GitHub API"
+        ));
+    }
+
+    Ok(body
+        .tree
+        .into_iter()
+        .filter(|entry| entry.kind == "blob")
+        .map(|entry| entry.path)
+        .collect())
+}
+
+pub(crate) fn parse_timestamp(timestamp: &str) -> Result<OffsetDateTime, String> {
+    OffsetDateTime::parse(timestamp, &Rfc3339)
+        .map_err(|e| format!("Invalid timestamp {timestamp:?}: {e}"))
+}
+
+pub(crate) fn format_timestamp(timestamp: OffsetDateTime) -> String {
+    timestamp
+        .format(&Rfc3339)
+        .expect("RFC 3339 formatting of a valid timestamp cannot fail")
+}
+
+/// Number of whole days by which `head` is newer than `resolved`
+pub(crate) fn days_behind(resolved: OffsetDateTime, head: OffsetDateTime) -> i64 {
+    (head - resolved).whole_days()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repo_of_url() {
+        for url in [
+            "https://raw.githubusercontent.com/owner/fonts/main/font_library.toml",
+            "https://api.github.com/repos/owner/fonts/commits/main",
+            "https://api.github.com/repos/owner/fonts?per_page=1",
+        ] {
+            assert_eq!(repo_of_url(url), Some("owner/fonts"), "{url}");
+        }
+        for url in [
+            "https://api.github.com/rate_limit",
+            "https://api.github.com/repos/owner",
+            "https://fonts.example-corp.com/owner/fonts/main/A.ttf",
+        ] {
+            assert_eq!(repo_of_url(url), None, "{url}");
+        }
+    }
+
+    #[test]
+    fn test_contents_api_url() {
+        assert_eq!(
+            contents_api_url(
+                "https://raw.githubusercontent.com/owner/fonts/v1.2.0/Lato/Lato-Regular.ttf"
+            )
+            .as_deref(),
+            Some(
+                "https://api.github.com/repos/owner/fonts/contents/Lato/Lato-Regular.ttf?ref=v1.2.0"
+            )
+        );
+        for url in [
+            "https://api.github.com/repos/owner/fonts/commits/main",
+            "https://raw.githubusercontent.com/owner/fonts/main",
+            "https://fonts.example-corp.com/owner/fonts/main/A.ttf",
+        ] {
+            assert_eq!(contents_api_url(url), None, "{url}");
+        }
+    }
+
+    #[test]
+    fn test_days_behind_counts_whole_days() {
+        let resolved = parse_timestamp("2024-01-01T12:00:00Z").unwrap();
+        let head = parse_timestamp("2024-02-01T11:00:00Z").unwrap();
+
+        assert_eq!(days_behind(resolved, head), 30);
+        assert_eq!(days_behind(head, resolved), -30);
+    }
+
+    #[test]
+    fn test_github_source_index_path() {
+        let source = GitHubSource::parse(Path::new("owner/fonts")).unwrap();
+        assert_eq!(source.repo, "owner/fonts");
+        assert_eq!(source.index_path, DEFAULT_INDEX_PATH);
+
+        let source = GitHubSource::parse(Path::new("owner/fonts::indexes/print.toml")).unwrap();
+        assert_eq!(source.repo, "owner/fonts");
+        assert_eq!(
+            source.index_url(),
+            "https://raw.githubusercontent.com/owner/fonts/main/indexes/print.toml"
+        );
+
+        assert!(GitHubSource::parse(Path::new("fonts::print.toml")).is_err());
+        assert!(GitHubSource::parse(Path::new("owner/fonts::")).is_err());
+    }
+
+    #[test]
+    fn test_github_source_pinned_to_a_reference() {
+        let source = GitHubSource::parse(Path::new("owner/fonts@v1.2.0::print.toml")).unwrap();
+        assert_eq!(source.repo, "owner/fonts");
+        assert_eq!(source.reference, Some("v1.2.0"));
+        assert_eq!(source.to_string(), "owner/fonts@v1.2.0");
+        assert_eq!(
+            source.index_url(),
+            "https://raw.githubusercontent.com/owner/fonts/v1.2.0/print.toml"
+        );
+        assert_eq!(
+            raw_file_url("owner/fonts", Some("0123abc"), "Lato/Lato-Regular.ttf"),
+            "https://raw.githubusercontent.com/owner/fonts/0123abc/Lato/Lato-Regular.ttf"
+        );
+
+        let source = GitHubSource::parse(Path::new("owner/fonts@release/2024")).unwrap();
+        assert_eq!(source.repo, "owner/fonts");
+        assert_eq!(source.reference, Some("release/2024"));
+        assert_eq!(source.index_path, DEFAULT_INDEX_PATH);
+        assert_eq!(
+            source.file_url("Lato/Lato-Regular.ttf"),
+            "https://raw.githubusercontent.com/owner/fonts/release%2F2024/Lato/Lato-Regular.ttf"
+        );
+        assert_eq!(
+            contents_api_url(&source.file_url("Lato/Lato-Regular.ttf")).as_deref(),
+            Some(
+                "https://api.github.com/repos/owner/fonts/contents/Lato/Lato-Regular.ttf?ref=release%2F2024"
+            )
+        );
+        assert_eq!(
+            GitHubSource::parse(Path::new("owner/fonts"))
+                .unwrap()
+                .reference,
+            None
+        );
+        assert!(GitHubSource::parse(Path::new("owner/fonts@")).is_err());
+    }
+
+    #[test]
+    fn test_timestamp_round_trip() {
+        let timestamp = parse_timestamp("2024-03-05T08:09:10Z").unwrap();
+        assert_eq!(format_timestamp(timestamp), "2024-03-05T08:09:10Z");
+    }
+}
@@ -0,0 +1,228 @@
+//! `report --html`: the project's font status as a standalone HTML page, with
+//! the sets, sources, versions and licenses of the table layout and the
+//! warnings of `check`. The page needs no network or assets, so it can be
+//! attached to CI runs as an artifact, and filters its rows by text and status.
+
+use std::fmt::Write as _;
+
+use crate::lockfile::tool_version;
+use crate::t;
+
+/// One font of the report: a required font or a redundant project font
+#[derive(Clone, Debug, Default)]
+pub(crate) struct HtmlRow {
+    pub(crate) family: String,
+    pub(crate) style: String,
+    pub(crate) weight: u16,
+    pub(crate) stretch: u16,
+    /// Class of the status, e.g. "missing"
+    pub(crate) status: &'static str,
+    pub(crate) status_label: String,
+    pub(crate) source: Option<String>,
+    pub(crate) version: Option<String>,
+    pub(crate) license: Option<String>,
+    pub(crate) size: Option<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct HtmlReport {
+    pub(crate) project: String,
+    pub(crate) library_dirs: Vec<String>,
+    pub(crate) summary: String,
+    pub(crate) rows: Vec<HtmlRow>,
+    pub(crate) warnings: Vec<String>,
+}
+
+const STYLE: &str = "
+body { font-family: system-ui, sans-serif; margin: 2rem; color: #222; }
+h1 { font-size: 1.5rem; margin-bottom: 0.2rem; }
+.meta { color: #666; margin: 0.2rem 0; }
+.controls { margin: 1rem 0; display: flex; flex-wrap: wrap; gap: 0.8rem; align-items: center; }
+.controls input[type=search] { padding: 0.3rem 0.5rem; min-width: 18rem; }
+table { border-collapse: collapse; width: 100%; }
+th, td { text-align: left; padding: 0.3rem 0.6rem; border-bottom: 1px solid #ddd; }
+th { background: #f4f4f4; position: sticky; top: 0; }
+td.number { text-align: right; }
+.status { font-weight: 600; }
+.missing .status { color: #c62828; }
+.faux .status { color: #8e24aa; }
+.fixable .status { color: #b26a00; }
+.redundant .status { color: #1565c0; }
+.embedded .status, .present .status { color: #2e7d32; }
+.warnings li { margin: 0.3rem 0; }
+";
+
+const SCRIPT: &str = "
+const text = document.getElementById('filter');
+const boxes = [...document.querySelectorAll('input[data-status]')];
+function filter() {
+  const query = text.value.toLowerCase();
+  const shown = new Set(boxes.filter(box => box.checked).map(box => box.dataset.status));
+  for (const row of document.querySelectorAll('tbody tr')) {
+    row.hidden = !shown.has(row.className) || !row.dataset.text.includes(query);
+  }
+}
+text.addEventListener('input', filter);
+boxes.forEach(box => box.addEventListener('change', filter));
+";
+
+impl HtmlReport {
+    pub(crate) fn render(&self) -> String {
+        let title = format!("{}: {}", t!("report-title"), self.project);
+        let mut html = String::new();
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n",
+            escape(&title)
+        );
+        let _ = writeln!(html, "<h1>{}</h1>", escape(&title));
+        let _ = writeln!(html, "<p class=\"meta\">{}</p>", escape(&self.summary));
+        if !self.library_dirs.is_empty() {
+            let _ = writeln!(
+                html,
+                "<p class=\"meta\">{}: {}</p>",
+                escape(&t!("html-libraries")),
+                escape(&self.library_dirs.join(", "))
+            );
+        }
+        let _ = writeln!(html, "<p class=\"meta\">{}</p>", escape(&tool_version()));
+
+        if !self.warnings.is_empty() {
+            let _ = writeln!(
+                html,
+                "<h2>{}</h2>\n<ul class=\"warnings\">",
+                escape(&t!("html-warnings"))
+            );
+            for warning in &self.warnings {
+                let _ = writeln!(html, "<li>{}</li>", escape(warning));
+            }
+            html.push_str("</ul>\n");
+        }
+
+        let _ = writeln!(
+            html,
+            "<div class=\"controls\">\n<input type=\"search\" id=\"filter\" placeholder=\"{}\">",
+            escape(&t!("html-filter"))
+        );
+        for (status, label) in self.statuses() {
+            let _ = writeln!(
+                html,
+                "<label><input type=\"checkbox\" data-status=\"{status}\" checked> {}</label>",
+                escape(label)
+            );
+        }
+        html.push_str("</div>\n<table>\n<thead><tr>");
+        for column in [
+            t!("column-family"),
+            t!("column-style"),
+            t!("column-weight"),
+            t!("column-stretch"),
+            t!("column-status"),
+            t!("column-source"),
+            t!("column-version"),
+            t!("column-license"),
+            t!("column-size"),
+        ] {
+            let _ = write!(html, "<th>{}</th>", escape(&column));
+        }
+        html.push_str("</tr></thead>\n<tbody>\n");
+        for row in &self.rows {
+            self.render_row(&mut html, row);
+        }
+        let _ = write!(
+            html,
+            "</tbody>\n</table>\n<script>{SCRIPT}</script>\n</body>\n</html>\n"
+        );
+        html
+    }
+
+    /// The statuses of the rows, in order of appearance, with their labels
+    fn statuses(&self) -> Vec<(&'static str, &str)> {
+        let mut statuses = Vec::<(&str, &str)>::new();
+        for row in &self.rows {
+            if !statuses.iter().any(|(status, _)| *status == row.status) {
+                statuses.push((row.status, &row.status_label));
+            }
+        }
+        statuses
+    }
+
+    fn render_row(&self, html: &mut String, row: &HtmlRow) {
+        let optional = |value: &Option<String>| escape(value.as_deref().unwrap_or("-"));
+        let text = format!(
+            "{} {}",
+            row.family,
+            row.source.as_deref().unwrap_or_default()
+        )
+        .to_lowercase();
+        let _ = writeln!(
+            html,
+            "<tr class=\"{}\" data-text=\"{}\"><td>{}</td><td>{}</td><td class=\"number\">{}</td>\
+             <td class=\"number\">{}</td><td class=\"status\">{}</td><td>{}</td><td>{}</td>\
+             <td>{}</td><td class=\"number\">{}</td></tr>",
+            row.status,
+            escape(&text),
+            escape(&row.family),
+            escape(&row.style),
+            row.weight,
+            row.stretch,
+            escape(&row.status_label),
+            optional(&row.source),
+            optional(&row.version),
+            optional(&row.license),
+            optional(&row.size),
+        );
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_escapes_and_lists_statuses() {
+        let row = |family: &str, status, source: Option<&str>| HtmlRow {
+            family: family.to_string(),
+            style: "Normal".to_string(),
+            weight: 400,
+            stretch: 1000,
+            status,
+            status_label: status.to_string(),
+            source: source.map(str::to_string),
+            ..HtmlRow::default()
+        };
+        let report = HtmlReport {
+            project: "paper/font_config.toml".to_string(),
+            summary: "1 missing".to_string(),
+            rows: vec![
+                row("Lato", "present", Some("Lato-Regular.ttf")),
+                row("<Fancy> & Co", "missing", None),
+                row("Inter", "present", None),
+            ],
+            warnings: vec!["Bold is \"faked\"".to_string()],
+            ..HtmlReport::default()
+        };
+        let html = report.render();
+        assert!(html.contains("&lt;Fancy&gt; &amp; Co"), "{html}");
+        assert!(!html.contains("<Fancy>"));
+        assert!(html.contains("Bold is &quot;faked&quot;"));
+        assert!(html.contains("data-text=\"lato lato-regular.ttf\""));
+        assert_eq!(html.matches("data-status=").count(), 2);
+        assert_eq!(html.matches("<tr class=").count(), 3);
+    }
+}
@@ -0,0 +1,573 @@
+//! The HTTP client used for all downloads and API requests. Requests are built
+//! with [`get`], which refuses hosts the user settings don't allow and adds the
+//! headers configured for the request's host, and for GitHub the token of the
+//! credential profile of the repository, see [`crate::user_config`], or else the
+//! token of `--token` or `GITHUB_TOKEN`. Authorized downloads of raw GitHub files
+//! go through the contents API. Requests that hit a rate limit are sent again
+//! once it lifts, if that is soon enough.
+//!
+//! Builds without the `network` feature, such as the static `minimal` build
+//! for CI containers, have no HTTP client: [`client`] fails, and the types
+//! standing in for `reqwest`'s can't be constructed. Local libraries work the
+//! same either way.
+
+#[cfg(feature = "network")]
+use reqwest::Error as SendError;
+#[cfg(feature = "network")]
+pub(crate) use reqwest::blocking::{Client, RequestBuilder, Response};
+#[cfg(feature = "network")]
+pub(crate) use reqwest::{StatusCode, header};
+
+#[cfg(not(feature = "network"))]
+use offline::Unsupported as SendError;
+#[cfg(not(feature = "network"))]
+pub(crate) use offline::{Client, RequestBuilder, Response, StatusCode, header};
+
+use header::{HeaderMap, HeaderName, HeaderValue};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::github;
+use crate::lockfile::sha256_hex;
+use crate::progress;
+use crate::rate_limit::{RateLimit, read_limited};
+use crate::user_config;
+
+/// How often a request that hit a rate limit is sent again
+const RATE_LIMIT_RETRIES: u32 = 3;
+/// The longest wait for a rate limit to lift; beyond it, the request fails
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+
+/// Set by `update --frozen`: every request is refused
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Forbid all network access for the rest of the run
+pub(crate) fn set_offline() {
+    OFFLINE.store(true, Ordering::Relaxed);
+}
+
+pub(crate) fn offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+}
+
+#[cfg(feature = "network")]
+pub(crate) fn client() -> Result<Client, String> {
+    Client::builder()
+        .user_agent(concat!(
+            env!("CARGO_PKG_NAME"),
+            "/",
+            env!("CARGO_PKG_VERSION")
+        ))
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {e}"))
+}
+
+#[cfg(not(feature = "network"))]
+pub(crate) fn client() -> Result<Client, String> {
+    Err(offline::UNSUPPORTED.to_string())
+}
+
+/// A GET request of `url`, with the headers configured for its host. Fails if
+/// the host may not be contacted, see [`check_url`].
+pub(crate) fn get(client: &Client, url: &str) -> Result<Request, String> {
+    if let Some(api_url) = github::contents_api_url(url)
+        && github_token(url)?.is_some()
+    {
+        let host = check_url(&api_url)?;
+        let request = with_headers(client.get(&api_url), &api_url, &host)?;
+        return Ok(request.header("Accept", "application/vnd.github.raw"));
+    }
+    let host = check_url(url)?;
+    with_headers(client.get(url), url, &host)
+}
+
+/// A POST request of `url`, like [`get`]
+pub(crate) fn post(client: &Client, url: &str) -> Result<Request, String> {
+    let host = check_url(url)?;
+    with_headers(client.post(url), url, &host)
+}
+
+fn with_headers(request: RequestBuilder, url: &str, host: &str) -> Result<Request, String> {
+    let mut headers = configured_headers(host);
+    let token = github_token(url)?;
+    if let Some(token) = &token {
+        insert_bearer(&mut headers, token)?;
+    }
+    Ok(Request {
+        builder: if headers.is_empty() {
+            request
+        } else {
+            request.headers(headers)
+        },
+        url: url.to_string(),
+        authorized: token.is_some(),
+    })
+}
+
+/// The token of a request of `url`. Only GitHub itself gets one, never a mirror.
+fn github_token(url: &str) -> Result<Option<String>, String> {
+    let Some(repo) = github::repo_of_url(url) else {
+        return Ok(None);
+    };
+    Ok(user_config::get()
+        .github_token(repo)?
+        .or_else(github::default_token))
+}
+
+/// A request built by [`get`] or [`post`]
+pub(crate) struct Request {
+    builder: RequestBuilder,
+    url: String,
+    authorized: bool,
+}
+
+impl Request {
+    pub(crate) fn header(self, name: impl AsRef<str>, value: impl AsRef<str>) -> Self {
+        Self {
+            builder: self.builder.header(name.as_ref(), value.as_ref()),
+            ..self
+        }
+    }
+
+    /// Add `headers`, replacing those of the same name, such as the token
+    pub(crate) fn headers(self, headers: HeaderMap) -> Self {
+        Self {
+            authorized: self.authorized || headers.get("authorization").is_some(),
+            builder: self.builder.headers(headers),
+            ..self
+        }
+    }
+
+    pub(crate) fn body(self, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            builder: self.builder.body(body.into()),
+            ..self
+        }
+    }
+
+    /// Send the request. When the server reports a rate limit that lifts within
+    /// a minute, wait for it and send the request again, up to three times.
+    pub(crate) fn send(self) -> Result<Response, SendError> {
+        let mut builder = self.builder;
+        let mut attempt = 0;
+        loop {
+            let retry = builder.try_clone();
+            let response = builder.send()?;
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |now| now.as_secs());
+            let wait = rate_limit_wait(response.status(), response.headers(), attempt, now);
+            match (wait, retry) {
+                (Some(Some(wait)), Some(retry)) if attempt < RATE_LIMIT_RETRIES => {
+                    progress!(
+                        "  Rate limited by {}, retrying in {}s",
+                        url_host(&self.url).unwrap_or_default(),
+                        wait.as_secs()
+                    );
+                    thread::sleep(wait);
+                    builder = retry;
+                    attempt += 1;
+                }
+                (limited, _) => {
+                    if limited.is_some()
+                        && !self.authorized
+                        && github::repo_of_url(&self.url).is_some()
+                    {
+                        progress!(
+                            "  GitHub's rate limit of unauthenticated requests is used up; \
+                             pass --token or set GITHUB_TOKEN to get a higher one"
+                        );
+                    }
+                    return Ok(response);
+                }
+            }
+        }
+    }
+}
+
+/// Whether a response with `status` and `headers` reports a rate limit, and if
+/// so, how long to wait before the `attempt`th retry, or `None` if it lifts too
+/// late. GitHub answers 429, or 403 with the remaining quota at 0 or a
+/// `Retry-After` header; `now` is in seconds since the Unix epoch.
+fn rate_limit_wait(
+    status: StatusCode,
+    headers: &HeaderMap,
+    attempt: u32,
+    now: u64,
+) -> Option<Option<Duration>> {
+    let number = |name| {
+        headers
+            .get(name)
+            .and_then(|value: &HeaderValue| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<u64>().ok())
+    };
+    let retry_after = number("retry-after");
+    let exhausted = number("x-ratelimit-remaining") == Some(0);
+    let limited = status == StatusCode::TOO_MANY_REQUESTS
+        || (status == StatusCode::FORBIDDEN && (exhausted || retry_after.is_some()));
+    if !limited {
+        return None;
+    }
+    let wait = match (retry_after, number("x-ratelimit-reset")) {
+        (Some(seconds), _) => Duration::from_secs(seconds),
+        (None, Some(reset)) if exhausted => Duration::from_secs(reset.saturating_sub(now) + 1),
+        _ => Duration::from_secs(1 << attempt),
+    };
+    Some((wait <= MAX_RATE_LIMIT_WAIT).then_some(wait))
+}
+
+/// An `Authorization` header with the bearer `token`, which replaces any
+/// configured one when passed to `RequestBuilder::headers`
+pub(crate) fn bearer(token: &str) -> Result<HeaderMap, String> {
+    let mut headers = HeaderMap::new();
+    insert_bearer(&mut headers, token)?;
+    Ok(headers)
+}
+
+fn insert_bearer(headers: &mut HeaderMap, token: &str) -> Result<(), String> {
+    let value = HeaderValue::from_str(&format!("Bearer {token}"))
+        .map_err(|_| "Invalid characters in the GitHub token".to_string())?;
+    if let Ok(name) = HeaderName::from_bytes(b"authorization") {
+        headers.insert(name, value);
+    }
+    Ok(())
+}
+
+/// Check that the host of `url` is permitted by the `allow_hosts` and
+/// `deny_hosts` settings and that the network may be used at all, returning
+/// the host
+pub(crate) fn check_url(url: &str) -> Result<String, String> {
+    if offline() {
+        return Err(format!(
+            "Refusing to request {url}: network access is disabled by --frozen"
+        ));
+    }
+    let host = url_host(url)?;
+    user_config::get().http.check_host(&host)?;
+    Ok(host)
+}
+
+#[cfg(feature = "network")]
+fn url_host(url: &str) -> Result<String, String> {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .ok_or_else(|| format!("Invalid URL {url}"))
+}
+
+#[cfg(not(feature = "network"))]
+fn url_host(url: &str) -> Result<String, String> {
+    Err(format!(
+        "Refusing to request {url}: {}",
+        offline::UNSUPPORTED
+    ))
+}
+
+fn configured_headers(host: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    // Names and values were validated when the settings were read
+    for (name, value) in user_config::get().http.headers_for(host) {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            headers.insert(name, value);
+        }
+    }
+    headers
+}
+
+/// A file downloaded by [`fetch_verified`]
+pub(crate) struct Fetched {
+    pub(crate) data: Vec<u8>,
+    /// The URL that served the file
+    pub(crate) url: String,
+    pub(crate) sha256: String,
+}
+
+/// Download `url`, trying its mirrors in order until one serves content whose
+/// SHA-256 is `expected_sha256`. Without an expected hash, the first complete
+/// download is accepted. Mirrors that fail or serve other content are logged
+/// and skipped.
+pub(crate) fn fetch_verified(
+    client: &Client,
+    url: &str,
+    expected_sha256: Option<&str>,
+    limit_rate: Option<RateLimit>,
+) -> Result<Fetched, String> {
+    let mirrors = user_config::get().http.mirror_urls(url);
+    let mut failures = Vec::new();
+
+    for mirror in &mirrors {
+        let data = get(client, mirror).and_then(|request| {
+            let response = request.send().map_err(|e| e.to_string())?;
+            if !response.status().is_success() {
+                return Err(format!("HTTP {}", response.status()));
+            }
+            read_limited(response, limit_rate).map_err(|e| e.to_string())
+        });
+        let data = match data {
+            Ok(data) => data,
+            Err(e) => {
+                progress!("  Skipping {mirror}: {e}");
+                failures.push(format!("{mirror}: {e}"));
+                continue;
+            }
+        };
+
+        let sha256 = sha256_hex(&data);
+        if let Some(expected) = expected_sha256
+            && sha256 != expected
+        {
+            progress!("  Skipping {mirror}: content does not match the expected hash");
+            failures.push(format!("{mirror}: SHA-256 {sha256} instead of {expected}"));
+            continue;
+        }
+
+        if mirrors.len() > 1 || mirror != url {
+            progress!(
+                "  Served by {mirror}{}",
+                if expected_sha256.is_some() {
+                    " (hash verified)"
+                } else {
+                    ""
+                }
+            );
+        }
+        return Ok(Fetched {
+            data,
+            url: mirror.clone(),
+            sha256,
+        });
+    }
+
+    Err(format!(
+        "Failed to download {url} from any mirror: {}",
+        failures.join("; ")
+    ))
+}
+
+/// Stand-ins for the `reqwest` types used by typfont, for builds without
+/// the `network` feature. Requests can't be sent, and as [`client`] always
+/// fails, no client or response is ever constructed.
+#[cfg(not(feature = "network"))]
+#[allow(dead_code)]
+mod offline {
+    use std::fmt;
+    use std::io::{self, Read};
+
+    pub(crate) const UNSUPPORTED: &str =
+        "typfont was built without network support (the `network` feature)";
+
+    #[derive(Debug)]
+    pub(crate) struct Unsupported;
+
+    impl fmt::Display for Unsupported {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(UNSUPPORTED)
+        }
+    }
+
+    impl std::error::Error for Unsupported {}
+
+    pub(crate) struct Client(());
+
+    impl Client {
+        pub(crate) fn get(&self, _url: &str) -> RequestBuilder {
+            RequestBuilder(())
+        }
+
+        pub(crate) fn post(&self, _url: &str) -> RequestBuilder {
+            RequestBuilder(())
+        }
+    }
+
+    pub(crate) struct RequestBuilder(());
+
+    impl RequestBuilder {
+        pub(crate) fn header<K, V>(self, _name: K, _value: V) -> Self {
+            self
+        }
+
+        pub(crate) fn headers(self, _headers: header::HeaderMap) -> Self {
+            self
+        }
+
+        pub(crate) fn body<B>(self, _body: B) -> Self {
+            self
+        }
+
+        pub(crate) fn send(self) -> Result<Response, Unsupported> {
+            Err(Unsupported)
+        }
+
+        pub(crate) fn try_clone(&self) -> Option<Self> {
+            Some(Self(()))
+        }
+    }
+
+    pub(crate) struct Response {
+        status: StatusCode,
+        headers: header::HeaderMap,
+    }
+
+    impl Response {
+        pub(crate) fn status(&self) -> StatusCode {
+            self.status
+        }
+
+        pub(crate) fn headers(&self) -> &header::HeaderMap {
+            &self.headers
+        }
+
+        pub(crate) fn text(self) -> Result<String, Unsupported> {
+            Err(Unsupported)
+        }
+
+        pub(crate) fn bytes(self) -> Result<Vec<u8>, Unsupported> {
+            Err(Unsupported)
+        }
+    }
+
+    impl Read for Response {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other(Unsupported))
+        }
+    }
+    #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+    pub(crate) struct StatusCode(u16);
+
+    impl StatusCode {
+        pub(crate) const OK: Self = Self(200);
+        pub(crate) const PARTIAL_CONTENT: Self = Self(206);
+        pub(crate) const NOT_MODIFIED: Self = Self(304);
+        pub(crate) const FORBIDDEN: Self = Self(403);
+        pub(crate) const NOT_FOUND: Self = Self(404);
+        pub(crate) const TOO_MANY_REQUESTS: Self = Self(429);
+        pub(crate) const RANGE_NOT_SATISFIABLE: Self = Self(416);
+        pub(crate) const UNPROCESSABLE_ENTITY: Self = Self(422);
+
+        pub(crate) fn is_success(&self) -> bool {
+            (200..300).contains(&self.0)
+        }
+    }
+
+    impl fmt::Display for StatusCode {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            self.0.fmt(f)
+        }
+    }
+
+    pub(crate) mod header {
+        use std::collections::BTreeMap;
+
+        pub(crate) const CONTENT_RANGE: &str = "content-range";
+        pub(crate) const CONTENT_TYPE: &str = "content-type";
+        pub(crate) const ETAG: &str = "etag";
+        pub(crate) const IF_NONE_MATCH: &str = "if-none-match";
+        pub(crate) const RANGE: &str = "range";
+
+        #[derive(Clone, Default)]
+        pub(crate) struct HeaderMap(BTreeMap<String, HeaderValue>);
+
+        impl HeaderMap {
+            pub(crate) fn new() -> Self {
+                Self::default()
+            }
+
+            pub(crate) fn insert(&mut self, name: HeaderName, value: HeaderValue) {
+                self.0.insert(name.0, value);
+            }
+
+            pub(crate) fn get(&self, name: &str) -> Option<&HeaderValue> {
+                self.0.get(name)
+            }
+
+            pub(crate) fn is_empty(&self) -> bool {
+                self.0.is_empty()
+            }
+        }
+
+        /// A header name, which like `reqwest`'s is a non-empty HTTP token
+        pub(crate) struct HeaderName(String);
+
+        impl HeaderName {
+            pub(crate) fn from_bytes(name: &[u8]) -> Result<Self, ()> {
+                let token =
+                    |byte: &u8| byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(byte);
+                if name.is_empty() || !name.iter().all(token) {
+                    return Err(());
+                }
+                Ok(Self(String::from_utf8_lossy(name).to_ascii_lowercase()))
+            }
+        }
+
+        /// A header value, which like `reqwest`'s has no control characters
+        /// but tabs
+        #[derive(Clone)]
+        pub(crate) struct HeaderValue(String);
+
+        impl HeaderValue {
+            pub(crate) fn from_str(value: &str) -> Result<Self, ()> {
+                if value
+                    .bytes()
+                    .any(|byte| byte != b'\t' && (byte < b' ' || byte == 0x7f))
+                {
+                    return Err(());
+                }
+                Ok(Self(value.to_string()))
+            }
+
+            pub(crate) fn to_str(&self) -> Result<&str, ()> {
+                Ok(&self.0)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(
+                HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                HeaderValue::from_str(value).unwrap(),
+            );
+        }
+        headers
+    }
+
+    #[test]
+    fn test_rate_limit_wait() {
+        let seconds = |seconds| Some(Some(Duration::from_secs(seconds)));
+        let wait = |status, pairs: &[(&str, &str)], attempt| {
+            rate_limit_wait(status, &headers(pairs), attempt, 1_000)
+        };
+
+        assert_eq!(wait(StatusCode::OK, &[], 0), None);
+        // A 403 is only a rate limit if GitHub says so
+        assert_eq!(wait(StatusCode::FORBIDDEN, &[], 0), None);
+        assert_eq!(
+            wait(StatusCode::FORBIDDEN, &[("Retry-After", "30")], 0),
+            seconds(30)
+        );
+        let exhausted = [
+            ("X-RateLimit-Remaining", "0"),
+            ("X-RateLimit-Reset", "1010"),
+        ];
+        assert_eq!(wait(StatusCode::FORBIDDEN, &exhausted, 0), seconds(11));
+        // Without a hint, the waits double
+        assert_eq!(wait(StatusCode::TOO_MANY_REQUESTS, &[], 0), seconds(1));
+        assert_eq!(wait(StatusCode::TOO_MANY_REQUESTS, &[], 2), seconds(4));
+        // An hour is too long to wait
+        let exhausted = [
+            ("X-RateLimit-Remaining", "0"),
+            ("X-RateLimit-Reset", "4600"),
+        ];
+        assert_eq!(wait(StatusCode::FORBIDDEN, &exhausted, 0), Some(None));
+    }
+}
@@ -0,0 +1,169 @@
+//! Translated CLI messages. The catalogs are Fluent files in `locales/`,
+//! compiled into the binary; English is used for any message a catalog lacks.
+
+use clap::ValueEnum;
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource};
+use std::sync::OnceLock;
+use unic_langid::LanguageIdentifier;
+
+/// Language of the CLI output
+#[derive(ValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Lang {
+    /// English
+    En,
+    /// Simplified Chinese
+    Zh,
+}
+
+impl Lang {
+    fn source(self) -> &'static str {
+        match self {
+            Lang::En => include_str!("../locales/en.ftl"),
+            Lang::Zh => include_str!("../locales/zh.ftl"),
+        }
+    }
+
+    fn identifier(self) -> LanguageIdentifier {
+        match self {
+            Lang::En => "en",
+            Lang::Zh => "zh",
+        }
+        .parse()
+        .expect("language identifiers are valid")
+    }
+
+    /// The language of the user's locale, from `LC_ALL`, `LC_MESSAGES` or `LANG`
+    pub(crate) fn detect() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|locale| !locale.is_empty())
+            .map_or(Lang::En, |locale| Self::from_locale(&locale))
+    }
+
+    fn from_locale(locale: &str) -> Self {
+        if locale.to_ascii_lowercase().starts_with("zh") {
+            Lang::Zh
+        } else {
+            Lang::En
+        }
+    }
+}
+
+struct Catalog {
+    bundle: FluentBundle<FluentResource>,
+    fallback: Option<FluentBundle<FluentResource>>,
+}
+
+impl Catalog {
+    fn new(lang: Lang) -> Self {
+        Self {
+            bundle: bundle(lang),
+            fallback: (lang != Lang::En).then(|| bundle(Lang::En)),
+        }
+    }
+}
+
+fn bundle(lang: Lang) -> FluentBundle<FluentResource> {
+    let resource = FluentResource::try_new(lang.source().to_string())
+        .expect("message catalogs are valid Fluent");
+    let mut bundle = FluentBundle::new_concurrent(vec![lang.identifier()]);
+    // Unicode isolation marks around arguments show up as garbage in terminals
+    bundle.set_use_isolating(false);
+    bundle
+        .add_resource(resource)
+        .expect("message catalogs have no duplicate messages");
+    bundle
+}
+
+static CATALOG: OnceLock<Catalog> = OnceLock::new();
+
+/// Select the output language; must be called before the first message is formatted
+pub(crate) fn set_language(lang: Lang) {
+    let _ = CATALOG.set(Catalog::new(lang));
+}
+
+fn catalog() -> &'static Catalog {
+    CATALOG.get_or_init(|| Catalog::new(Lang::En))
+}
+
+/// Format the message `id`, see [`t!`](crate::t)
+pub(crate) fn message(id: &str, args: Option<&FluentArgs>) -> String {
+    let catalog = catalog();
+    [Some(&catalog.bundle), catalog.fallback.as_ref()]
+        .into_iter()
+        .flatten()
+        .find_map(|bundle| {
+            let pattern = bundle.get_message(id)?.value()?;
+            let mut errors = Vec::new();
+            Some(
+                bundle
+                    .format_pattern(pattern, args, &mut errors)
+                    .into_owned(),
+            )
+        })
+        .unwrap_or_else(|| id.to_string())
+}
+
+/// Translate a message, e.g. `t!("set-total", total = 3)`
+#[macro_export]
+macro_rules! t {
+    ($id:literal) => {
+        $crate::i18n::message($id, None)
+    };
+    ($id:literal, $($name:ident = $value:expr),+ $(,)?) => {{
+        let mut args = fluent_bundle::FluentArgs::new();
+        $(args.set(stringify!($name), $value);)+
+        $crate::i18n::message($id, Some(&args))
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fluent_syntax::ast::Entry;
+
+    fn message_ids(lang: Lang) -> Vec<String> {
+        let resource = FluentResource::try_new(lang.source().to_string()).unwrap();
+        let mut ids = resource
+            .entries()
+            .filter_map(|entry| match entry {
+                Entry::Message(message) => Some(message.id.name.to_string()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_catalogs_have_the_same_messages() {
+        assert_eq!(message_ids(Lang::Zh), message_ids(Lang::En));
+    }
+
+    #[test]
+    fn test_plural_and_arguments() {
+        let catalog = Catalog::new(Lang::En);
+        let mut args = FluentArgs::new();
+        args.set("total", 3);
+        args.set("families", 1);
+        let pattern = catalog
+            .bundle
+            .get_message("set-total-families")
+            .unwrap()
+            .value()
+            .unwrap();
+        let text = catalog
+            .bundle
+            .format_pattern(pattern, Some(&args), &mut Vec::new());
+        assert_eq!(text, "(total 3 in 1 family)");
+    }
+
+    #[test]
+    fn test_locale_detection() {
+        assert_eq!(Lang::from_locale("zh_CN.UTF-8"), Lang::Zh);
+        assert_eq!(Lang::from_locale("en_US.UTF-8"), Lang::En);
+        assert_eq!(Lang::from_locale("C"), Lang::En);
+    }
+}
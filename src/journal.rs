@@ -0,0 +1,306 @@
+//! The run journal of a project: every `update` that changed the project fonts
+//! appends one line to `.typst-font-manager/journal.jsonl` next to its
+//! font_config.toml, so that teams can audit when and where a font file came from.
+
+use crate::color::Colorize;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use time::OffsetDateTime;
+
+use crate::github::format_timestamp;
+use crate::lockfile::{LockedFile, LockedSource};
+use crate::table::Table;
+
+/// Per-project state directory, created next to font_config.toml
+pub(crate) const STATE_DIR: &str = ".typst-font-manager";
+
+const JOURNAL_FILE: &str = "journal.jsonl";
+
+/// One recorded run
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub(crate) struct JournalEntry {
+    /// Sequential number of the run within the project, starting at 1
+    pub(crate) id: u64,
+    pub(crate) command: String,
+    pub(crate) timestamp: String,
+    pub(crate) tool_version: String,
+    pub(crate) actions: Vec<JournalAction>,
+    /// Revisions of the GitHub libraries the fonts were resolved from
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) sources: Vec<LockedSource>,
+}
+
+/// A change made to the project font directory
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub(crate) enum JournalAction {
+    /// A font file was installed, with the same details as its lockfile entry
+    Install(LockedFile),
+}
+
+/// Path of the journal of the project configured by `config_file`
+pub(crate) fn journal_path(config_file: &Path) -> PathBuf {
+    config_file
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(STATE_DIR)
+        .join(JOURNAL_FILE)
+}
+
+/// Read all entries of the journal, oldest first. A missing journal is empty.
+pub(crate) fn read_journal(path: &Path) -> Result<Vec<JournalEntry>, String> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {path:?}: {e}")),
+    };
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(number, line)| {
+            serde_json::from_str(line)
+                .map_err(|e| format!("Invalid entry on line {} of {path:?}: {e}", number + 1))
+        })
+        .collect()
+}
+
+/// Append a run of `command` to the journal at `path`, numbering it after the
+/// last recorded run
+pub(crate) fn record_run(
+    path: &Path,
+    command: &str,
+    actions: Vec<JournalAction>,
+    sources: Vec<LockedSource>,
+) -> Result<JournalEntry, String> {
+    let id = read_journal(path)?.last().map_or(1, |entry| entry.id + 1);
+    let entry = JournalEntry {
+        id,
+        command: command.to_string(),
+        timestamp: format_timestamp(OffsetDateTime::now_utc()),
+        tool_version: env!("CARGO_PKG_VERSION").to_string(),
+        actions,
+        sources,
+    };
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directories {parent:?}: {e}"))?;
+    }
+    let line = serde_json::to_string(&entry).map_err(|e| format!("Failed to encode run: {e}"))?;
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| writeln!(file, "{line}"))
+        .map_err(|e| format!("Failed to write {path:?}: {e}"))?;
+    Ok(entry)
+}
+
+/// What `history rollback <id>` changes to return the project to the state after
+/// run `id`
+#[derive(Debug, Default, Eq, PartialEq)]
+pub(crate) struct Rollback {
+    /// The lockfile records of the files installed up to the run, the latest of
+    /// each file
+    pub(crate) restored: Vec<LockedFile>,
+    /// Files installed only by later runs, which are removed
+    pub(crate) removed: Vec<String>,
+    /// The library revisions the run resolved the fonts from
+    pub(crate) sources: Vec<LockedSource>,
+}
+
+/// The changes undoing every run after `id`, or `None` if there is no run `id`.
+/// Files the journal doesn't know, e.g. those installed before it was kept, are
+/// left alone.
+pub(crate) fn plan_rollback(entries: &[JournalEntry], id: u64) -> Option<Rollback> {
+    entries.iter().find(|entry| entry.id == id)?;
+    let mut restored = BTreeMap::new();
+    let mut sources = BTreeMap::new();
+    let mut installed_later = BTreeSet::new();
+    for entry in entries {
+        for JournalAction::Install(file) in &entry.actions {
+            if entry.id <= id {
+                restored.insert(file.file.clone(), file.clone());
+            } else {
+                installed_later.insert(file.file.clone());
+            }
+        }
+        if entry.id <= id {
+            for source in &entry.sources {
+                sources.insert(source.repo.clone(), source.clone());
+            }
+        }
+    }
+    Some(Rollback {
+        removed: installed_later
+            .into_iter()
+            .filter(|file| !restored.contains_key(file))
+            .collect(),
+        restored: restored.into_values().collect(),
+        sources: sources.into_values().collect(),
+    })
+}
+
+/// `history`: one row per recorded run, most recent first
+pub(crate) fn print_history(entries: &[JournalEntry]) {
+    if entries.is_empty() {
+        println!("No runs recorded yet");
+        return;
+    }
+
+    let mut table = Table::new(vec!["ID", "Date", "Command", "Changes", "Sources"]);
+    for entry in entries.iter().rev() {
+        table.push_row(vec![
+            entry.id.to_string().into(),
+            entry
+                .timestamp
+                .get(..19)
+                .unwrap_or(&entry.timestamp)
+                .replacen('T', " ", 1)
+                .into(),
+            entry.command.as_str().into(),
+            format!("{} installed", entry.actions.len()).into(),
+            entry
+                .sources
+                .iter()
+                .map(format_source)
+                .collect::<Vec<_>>()
+                .join(", ")
+                .into(),
+        ]);
+    }
+    println!("{}", table.render());
+}
+
+/// `history show <id>`: every action of one run
+pub(crate) fn print_run(entry: &JournalEntry) {
+    println!(
+        "{} {} ({}, typfont {})",
+        format!("Run {}:", entry.id).bold(),
+        entry.command,
+        entry.timestamp,
+        entry.tool_version
+    );
+    for source in &entry.sources {
+        println!(
+            "  Library {} ({})",
+            format_source(source),
+            source.committed_at
+        );
+    }
+
+    for action in &entry.actions {
+        match action {
+            JournalAction::Install(file) => {
+                println!("  {} {} from {}", "+".green(), file.file, file.source);
+                if !file.faces.is_empty() {
+                    let faces = file.faces.iter().map(u32::to_string).collect::<Vec<_>>();
+                    println!("      faces:          {}", faces.join(", "));
+                }
+                if let Some(converted_from) = &file.converted_from {
+                    println!("      converted from: {converted_from}");
+                }
+                println!("      sha256:         {}", file.sha256);
+                if let Some(original) = &file.original_sha256 {
+                    println!("      before strip:   {original}");
+                }
+            }
+        }
+    }
+}
+
+fn format_source(source: &LockedSource) -> String {
+    format!(
+        "{}@{}",
+        source.repo,
+        &source.commit[..source.commit.len().min(7)]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runs_are_appended_with_sequential_ids() {
+        let dir = std::env::temp_dir().join(format!("typfont-journal-{}", std::process::id()));
+        let path = journal_path(&dir.join("font_config.toml"));
+        let install = JournalAction::Install(LockedFile {
+            file: "Lato-Regular.ttf".to_string(),
+            source: "Lato/Lato-Regular.ttf".to_string(),
+            faces: Vec::new(),
+            converted_from: None,
+            sha256: "00".repeat(32),
+            blake3: None,
+            original_sha256: None,
+            provenance: None,
+        });
+
+        record_run(&path, "update", vec![install.clone()], Vec::new()).unwrap();
+        record_run(&path, "update", vec![install.clone()], Vec::new()).unwrap();
+        let entries = read_journal(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(path.parent().unwrap().file_name().unwrap(), STATE_DIR);
+        assert_eq!(
+            entries.iter().map(|entry| entry.id).collect::<Vec<_>>(),
+            [1, 2]
+        );
+        assert_eq!(entries[1].actions, [install]);
+    }
+
+    #[test]
+    fn test_rollback_restores_the_files_of_the_run() {
+        let install = |file: &str, sha256: &str| {
+            JournalAction::Install(LockedFile {
+                file: file.to_string(),
+                source: format!("Lato/{file}"),
+                faces: Vec::new(),
+                converted_from: None,
+                sha256: sha256.to_string(),
+                blake3: None,
+                original_sha256: None,
+                provenance: None,
+            })
+        };
+        let source = |commit: &str| LockedSource {
+            repo: "owner/fonts".to_string(),
+            commit: commit.to_string(),
+            committed_at: "2026-01-02T03:04:05Z".to_string(),
+        };
+        let run = |id, actions, sources| JournalEntry {
+            id,
+            command: "update".to_string(),
+            timestamp: "2026-01-02T03:04:05Z".to_string(),
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            actions,
+            sources,
+        };
+        let entries = [
+            run(1, vec![install("A.ttf", "a1")], vec![source("c1")]),
+            run(2, vec![install("B.ttf", "b2")], vec![source("c2")]),
+            run(
+                3,
+                vec![install("A.ttf", "a3"), install("C.ttf", "c3")],
+                vec![source("c3")],
+            ),
+        ];
+
+        let rollback = plan_rollback(&entries, 2).unwrap();
+        assert_eq!(
+            rollback
+                .restored
+                .iter()
+                .map(|file| (file.file.as_str(), file.sha256.as_str()))
+                .collect::<Vec<_>>(),
+            [("A.ttf", "a1"), ("B.ttf", "b2")]
+        );
+        assert_eq!(rollback.removed, ["C.ttf"]);
+        assert_eq!(rollback.sources, [source("c2")]);
+        assert_eq!(plan_rollback(&entries, 4), None);
+    }
+}
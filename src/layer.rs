@@ -0,0 +1,252 @@
+//! `export-layer`: the project's fonts as a container image layer. The tar is
+//! reproducible: entries come in a stable order, with fixed owners, modes and
+//! modification times (`$SOURCE_DATE_EPOCH`, or the Unix epoch), so the same
+//! fonts always give the same layer digest and Docker builds hit their cache.
+//! The layer is written as a plain tar to `ADD` in a Dockerfile, or as a
+//! single-layer OCI image layout that `skopeo`, `crane` or BuildKit can read.
+
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+use crate::lockfile::sha256_hex;
+
+const BLOCK: usize = 512;
+
+const LAYER_MEDIA_TYPE: &str = "application/vnd.oci.image.layer.v1.tar";
+const CONFIG_MEDIA_TYPE: &str = "application/vnd.oci.image.config.v1+json";
+const MANIFEST_MEDIA_TYPE: &str = "application/vnd.oci.image.manifest.v1+json";
+const INDEX_MEDIA_TYPE: &str = "application/vnd.oci.image.index.v1+json";
+
+/// The modification time of the entries: `$SOURCE_DATE_EPOCH`, or 0
+pub(crate) fn source_date_epoch() -> Result<u64, Error> {
+    match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(value) if !value.is_empty() => value
+            .trim()
+            .parse()
+            .map_err(|_| Error::Usage(format!("Invalid SOURCE_DATE_EPOCH {value:?}"))),
+        _ => Ok(0),
+    }
+}
+
+/// A tar of the `files`, given by their path in the layer, below the
+/// directories containing them
+pub(crate) fn build_tar(files: &BTreeMap<String, PathBuf>, mtime: u64) -> Result<Vec<u8>, Error> {
+    let mut dirs = files
+        .keys()
+        .flat_map(|name| {
+            name.match_indices('/')
+                .map(|(end, _)| format!("{}/", &name[..end]))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    dirs.sort();
+    dirs.dedup();
+
+    let mut entries = dirs
+        .into_iter()
+        .map(|dir| (dir, None))
+        .chain(files.iter().map(|(name, path)| (name.clone(), Some(path))))
+        .collect::<Vec<_>>();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut tar = Vec::new();
+    for (name, path) in entries {
+        match path {
+            None => tar.extend(header(&name, 0o755, 0, mtime, b'5')?),
+            Some(path) => {
+                let data = fs::read(path)
+                    .map_err(|e| Error::Other(format!("Failed to read {path:?}: {e}")))?;
+                tar.extend(header(&name, 0o644, data.len() as u64, mtime, b'0')?);
+                tar.extend(&data);
+                tar.resize(tar.len().next_multiple_of(BLOCK), 0);
+            }
+        }
+    }
+    tar.resize(tar.len() + 2 * BLOCK, 0);
+    Ok(tar)
+}
+
+/// A ustar header owned by root, without user or group names
+fn header(name: &str, mode: u32, size: u64, mtime: u64, kind: u8) -> Result<[u8; BLOCK], Error> {
+    let mut header = [0; BLOCK];
+    let (prefix, name) = split_name(name)
+        .ok_or_else(|| Error::Project(format!("Path {name:?} is too long for a tar entry")))?;
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    octal(&mut header[100..108], mode.into());
+    octal(&mut header[108..116], 0);
+    octal(&mut header[116..124], 0);
+    octal(&mut header[124..136], size);
+    octal(&mut header[136..148], mtime);
+    header[156] = kind;
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+
+    header[148..156].fill(b' ');
+    let checksum = header.iter().map(|&byte| u64::from(byte)).sum::<u64>();
+    header[148..155].copy_from_slice(format!("{checksum:06o}\0").as_bytes());
+    Ok(header)
+}
+
+/// Split `name` into the prefix and name fields of a ustar header, which
+/// take up to 155 and 100 bytes
+fn split_name(name: &str) -> Option<(&str, &str)> {
+    if name.len() <= 100 {
+        return Some(("", name));
+    }
+    // Directory names end with a slash, which can't be where they are split
+    let split = name[..name.len() - 1]
+        .match_indices('/')
+        .map(|(index, _)| index)
+        .find(|&index| index <= 155 && name.len() - index - 1 <= 100)?;
+    Some((&name[..split], &name[split + 1..]))
+}
+
+/// Write `value` as a zero-terminated octal number filling `field`
+fn octal(field: &mut [u8], value: u64) {
+    let digits = format!("{value:0width$o}\0", width = field.len() - 1);
+    field.copy_from_slice(digits.as_bytes());
+}
+
+/// Write `layer` as the only layer of an OCI image layout in `dir`, tagged
+/// `tag`. The image has no creation time, so that it is reproducible too.
+pub(crate) fn write_oci_layout(dir: &Path, layer: &[u8], tag: &str) -> Result<(), Error> {
+    let layer_digest = format!("sha256:{}", sha256_hex(layer));
+    let config = json!({
+        "architecture": oci_architecture(),
+        "os": "linux",
+        "config": {},
+        "rootfs": { "type": "layers", "diff_ids": [layer_digest] },
+    })
+    .to_string();
+    let config_digest = format!("sha256:{}", sha256_hex(config.as_bytes()));
+    let manifest = json!({
+        "schemaVersion": 2,
+        "mediaType": MANIFEST_MEDIA_TYPE,
+        "config": {
+            "mediaType": CONFIG_MEDIA_TYPE,
+            "digest": config_digest,
+            "size": config.len(),
+        },
+        "layers": [{
+            "mediaType": LAYER_MEDIA_TYPE,
+            "digest": layer_digest,
+            "size": layer.len(),
+        }],
+    })
+    .to_string();
+    let manifest_digest = format!("sha256:{}", sha256_hex(manifest.as_bytes()));
+    let index = json!({
+        "schemaVersion": 2,
+        "mediaType": INDEX_MEDIA_TYPE,
+        "manifests": [{
+            "mediaType": MANIFEST_MEDIA_TYPE,
+            "digest": manifest_digest,
+            "size": manifest.len(),
+            "annotations": { "org.opencontainers.image.ref.name": tag },
+        }],
+    })
+    .to_string();
+
+    let blobs = dir.join("blobs/sha256");
+    let write = |path: PathBuf, content: &[u8]| {
+        fs::write(&path, content).map_err(|source| Error::Write { path, source })
+    };
+    fs::create_dir_all(&blobs).map_err(|source| Error::Write {
+        path: blobs.clone(),
+        source,
+    })?;
+    for (digest, content) in [
+        (&layer_digest, layer),
+        (&config_digest, config.as_bytes()),
+        (&manifest_digest, manifest.as_bytes()),
+    ] {
+        write(blobs.join(&digest["sha256:".len()..]), content)?;
+    }
+    write(dir.join("oci-layout"), br#"{"imageLayoutVersion":"1.0.0"}"#)?;
+    write(dir.join("index.json"), index.as_bytes())
+}
+
+/// The architecture of this machine, as named by OCI. The fonts run anywhere,
+/// but images are looked up by platform.
+fn oci_architecture() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "x86" => "386",
+        arch => arch,
+    }
+}
+
+/// Write the tar `layer` to `path`
+pub(crate) fn write_tar(path: &Path, layer: &[u8]) -> Result<(), Error> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent).map_err(|source| Error::Write {
+            path: parent.to_path_buf(),
+            source,
+        })?;
+    }
+    fs::write(path, layer).map_err(|source| Error::Write {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tar_is_reproducible_and_lists_parent_directories() {
+        let dir = std::env::temp_dir().join(format!("typfont-layer-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (a, b) = (dir.join("a.ttf"), dir.join("b.otf"));
+        fs::write(&a, b"first").unwrap();
+        fs::write(&b, vec![7; 600]).unwrap();
+        let files = BTreeMap::from([
+            ("usr/share/fonts/typfont/sub/b.otf".to_string(), b.clone()),
+            ("usr/share/fonts/typfont/a.ttf".to_string(), a.clone()),
+        ]);
+
+        let tar = build_tar(&files, 0).unwrap();
+        assert_eq!(tar, build_tar(&files, 0).unwrap());
+        // 5 directories and 2 files with 1 and 2 data blocks, then the end marker
+        assert_eq!(tar.len(), (5 + 2 + 3 + 2) * BLOCK);
+        let names = tar
+            .chunks(BLOCK)
+            .filter(|block| block[257..262] == *b"ustar")
+            .map(|block| {
+                let end = block.iter().position(|&byte| byte == 0).unwrap();
+                String::from_utf8_lossy(&block[..end]).into_owned()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "usr/",
+                "usr/share/",
+                "usr/share/fonts/",
+                "usr/share/fonts/typfont/",
+                "usr/share/fonts/typfont/a.ttf",
+                "usr/share/fonts/typfont/sub/",
+                "usr/share/fonts/typfont/sub/b.otf",
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_long_names_use_the_prefix_field() {
+        let name = format!("{}/{}.ttf", "d".repeat(120), "f".repeat(60));
+        let (prefix, rest) = split_name(&name).unwrap();
+        assert_eq!(prefix.len(), 120);
+        assert_eq!(rest.len(), 64);
+        assert!(split_name(&"x".repeat(200)).is_none());
+    }
+}
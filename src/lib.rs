@@ -0,0 +1,17 @@
+//! Library API of typfont for tools that embed the Typst compiler, such as
+//! custom builders and web services: see [`world::project_fonts`], and
+//! [`check::check_project`] and [`check::update_project`] to manage a
+//! project's fonts with progress reported to a [`FontObserver`].
+
+pub mod check;
+pub mod events;
+// Shared with the `typfont` binary, which uses the rest of them
+#[allow(dead_code)]
+mod parse_font_config;
+#[allow(dead_code)]
+mod utils;
+pub mod world;
+
+pub use check::{CheckReport, check_project, update_project};
+pub use events::{FontObserver, Resolution};
+pub use world::{FontSlot, ProjectFonts, project_fonts};
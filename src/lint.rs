@@ -0,0 +1,553 @@
+//! `lint-lib`: flag library files whose naming is inconsistent or broken in
+//! ways that make projects resolve the wrong face, or none: a subfamily naming
+//! another weight or style than the OS/2 table declares, full names shared by
+//! several faces, and families like "Inter Medium" that lack the typographic
+//! family ("Inter") grouping them with their siblings. Each finding comes with
+//! the change to the name or OS/2 table that fixes it.
+
+use crate::color::Colorize;
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::github::{self, GitHubSource};
+use crate::http;
+use crate::remote_font::{fetch_partial_font, is_partially_readable};
+use crate::utils::font_utils::{has_font_extension, is_font_collection};
+
+/// Weights named by subfamilies, longest first so that "extrabold" wins over "bold"
+const WEIGHT_NAMES: [(&str, u16); 17] = [
+    ("extralight", 200),
+    ("ultralight", 200),
+    ("extrabold", 800),
+    ("ultrabold", 800),
+    ("semibold", 600),
+    ("demibold", 600),
+    ("hairline", 100),
+    ("regular", 400),
+    ("medium", 500),
+    ("normal", 400),
+    ("black", 900),
+    ("heavy", 900),
+    ("light", 300),
+    ("thin", 100),
+    ("book", 400),
+    ("bold", 700),
+    ("roman", 400),
+];
+
+/// The names and OS/2 values of a face that the lints look at
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct FaceNames {
+    /// Name ID 1
+    pub(crate) family: Option<String>,
+    /// Name ID 2
+    pub(crate) subfamily: Option<String>,
+    /// Name ID 4
+    pub(crate) full_name: Option<String>,
+    /// Name ID 16
+    pub(crate) typographic_family: Option<String>,
+    /// Name ID 17
+    pub(crate) typographic_subfamily: Option<String>,
+    /// `usWeightClass` of the OS/2 table
+    pub(crate) weight_class: u16,
+    /// The italic bit of the OS/2 `fsSelection`
+    pub(crate) italic: bool,
+    pub(crate) variable: bool,
+}
+
+impl FaceNames {
+    pub(crate) fn parse(data: &[u8], index: u32) -> Option<Self> {
+        let face = ttf_parser::Face::parse(data, index).ok()?;
+        let name = |name_id| {
+            face.names()
+                .into_iter()
+                .filter(|name| name.name_id == name_id && name.is_unicode())
+                .find_map(|name| name.to_string())
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+        };
+        Some(Self {
+            family: name(ttf_parser::name_id::FAMILY),
+            subfamily: name(ttf_parser::name_id::SUBFAMILY),
+            full_name: name(ttf_parser::name_id::FULL_NAME),
+            typographic_family: name(ttf_parser::name_id::TYPOGRAPHIC_FAMILY),
+            typographic_subfamily: name(ttf_parser::name_id::TYPOGRAPHIC_SUBFAMILY),
+            weight_class: face.weight().to_number(),
+            italic: face.is_italic(),
+            variable: face.is_variable(),
+        })
+    }
+
+    /// The style name applications show: the typographic subfamily, if any
+    pub(crate) fn style_name(&self) -> Option<&str> {
+        self.typographic_subfamily
+            .as_deref()
+            .or(self.subfamily.as_deref())
+    }
+}
+
+/// A naming problem of a face
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Lint {
+    /// Neither a family nor a typographic family name
+    NoFamilyName,
+    /// The style name names another weight than `usWeightClass`
+    WeightMismatch {
+        style: String,
+        named: u16,
+        actual: u16,
+    },
+    /// The style name says italic but the face isn't, or the other way round
+    ItalicMismatch { style: String, italic: bool },
+    /// Other faces have the same full name
+    DuplicateFullName {
+        full_name: String,
+        others: Vec<String>,
+    },
+    /// A family name ending in a style, without a typographic family
+    MissingTypographicFamily {
+        family: String,
+        typographic_family: String,
+        typographic_subfamily: String,
+    },
+}
+
+impl Lint {
+    /// What to change in the font to fix the problem
+    pub(crate) fn suggestion(&self) -> String {
+        match self {
+            Lint::NoFamilyName => "add a family name (name ID 1)".to_string(),
+            Lint::WeightMismatch { style, named, .. } => format!(
+                "set usWeightClass to {named}, or rename the subfamily {style:?} after the weight"
+            ),
+            Lint::ItalicMismatch { italic: false, .. } => {
+                "set the italic bit of fsSelection (and macStyle)".to_string()
+            }
+            Lint::ItalicMismatch {
+                style,
+                italic: true,
+            } => {
+                format!("add \"Italic\" to the subfamily {style:?}")
+            }
+            Lint::DuplicateFullName { .. } => {
+                "give each face its own full name (name ID 4)".to_string()
+            }
+            Lint::MissingTypographicFamily {
+                typographic_family,
+                typographic_subfamily,
+                ..
+            } => format!(
+                "set the typographic family (name ID 16) to {typographic_family:?} and the \
+                 typographic subfamily (name ID 17) to {typographic_subfamily:?}"
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Lint::NoFamilyName => write!(f, "the face has no family name"),
+            Lint::WeightMismatch {
+                style,
+                named,
+                actual,
+            } => write!(
+                f,
+                "subfamily {style:?} names weight {named}, but usWeightClass is {actual}"
+            ),
+            Lint::ItalicMismatch {
+                style,
+                italic: false,
+            } => write!(f, "subfamily {style:?} is italic, but fsSelection isn't"),
+            Lint::ItalicMismatch {
+                style,
+                italic: true,
+            } => write!(f, "fsSelection is italic, but subfamily {style:?} isn't"),
+            Lint::DuplicateFullName { full_name, others } => write!(
+                f,
+                "full name {full_name:?} is also used by {}",
+                others.join(", ")
+            ),
+            Lint::MissingTypographicFamily { family, .. } => write!(
+                f,
+                "family {family:?} names a style but has no typographic family, so it \
+                 isn't grouped with the rest of its family"
+            ),
+        }
+    }
+}
+
+/// A lint found at a face of a library file
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct LintFinding {
+    pub(crate) path: PathBuf,
+    pub(crate) index: u32,
+    pub(crate) lint: Lint,
+}
+
+impl LintFinding {
+    fn location(&self) -> String {
+        if is_font_collection(&self.path) {
+            format!("{} [face {}]", self.path.display(), self.index)
+        } else {
+            self.path.display().to_string()
+        }
+    }
+}
+
+/// The weight a style name such as "SemiBold Italic" names. "Italic" alone
+/// stands for the regular weight.
+fn named_weight(style: &str) -> Option<u16> {
+    let compact = style.to_lowercase().replace([' ', '-', '_'], "");
+    if let Some((_, weight)) = WEIGHT_NAMES.iter().find(|(name, _)| compact.contains(name)) {
+        return Some(*weight);
+    }
+    matches!(compact.as_str(), "italic" | "oblique").then_some(400)
+}
+
+fn names_italic(style: &str) -> bool {
+    let style = style.to_lowercase();
+    style.contains("italic") || style.contains("oblique")
+}
+
+/// The trailing words of `family` that name a style beyond the four of
+/// Regular, Bold, Italic and Bold Italic, e.g. "Light" of "Inter Light"
+fn trailing_style(family: &str) -> Option<(&str, &str)> {
+    let (base, last) = family.rsplit_once(' ')?;
+    let weight = named_weight(last)?;
+    (weight != 400 && weight != 700 && !base.trim().is_empty()).then_some((base.trim(), last))
+}
+
+/// How far apart the declared weight and the one the style name gives must be
+/// for `--infer-weights` to believe the name
+const INFERENCE_MIN_DIFFERENCE: u16 = 200;
+
+/// The weight named by the style of a face whose `usWeightClass` clearly
+/// contradicts it, like "Black" declared as 400. Variable fonts and families
+/// still naming their style, whose subfamily is one of the four linked styles,
+/// are left alone.
+pub(crate) fn inferred_weight(names: &FaceNames) -> Option<u16> {
+    let style_in_family = names.typographic_family.is_none()
+        && names.family.as_deref().and_then(trailing_style).is_some();
+    if names.variable || style_in_family {
+        return None;
+    }
+    let named = named_weight(names.style_name()?)?;
+    (named.abs_diff(names.weight_class) >= INFERENCE_MIN_DIFFERENCE).then_some(named)
+}
+
+/// Lint the `faces` of a library, given by path and index
+pub(crate) fn lint_faces(faces: &[(PathBuf, u32, FaceNames)]) -> Vec<LintFinding> {
+    let mut findings = Vec::new();
+    let mut push = |path: &Path, index, lint| {
+        findings.push(LintFinding {
+            path: path.to_path_buf(),
+            index,
+            lint,
+        })
+    };
+
+    for (path, index, names) in faces {
+        if names.family.is_none() && names.typographic_family.is_none() {
+            push(path, *index, Lint::NoFamilyName);
+        }
+        // Adding the typographic names fixes the weight its style name gives as well
+        let missing_typographic_family = match (&names.typographic_family, &names.family) {
+            (None, Some(family)) => trailing_style(family).map(|style| (family, style)),
+            _ => None,
+        };
+        if let Some((family, (base, weight))) = missing_typographic_family {
+            let typographic_subfamily = match names.subfamily.as_deref() {
+                Some(subfamily) if names_italic(subfamily) => format!("{weight} Italic"),
+                _ => weight.to_string(),
+            };
+            let lint = Lint::MissingTypographicFamily {
+                family: family.clone(),
+                typographic_family: base.to_string(),
+                typographic_subfamily,
+            };
+            push(path, *index, lint);
+        }
+        if let Some(style) = names.style_name() {
+            // The default instance of a variable font is named after one weight only
+            if let Some(named) = named_weight(style)
+                && !names.variable
+                && missing_typographic_family.is_none()
+                && named != names.weight_class
+            {
+                let lint = Lint::WeightMismatch {
+                    style: style.to_string(),
+                    named,
+                    actual: names.weight_class,
+                };
+                push(path, *index, lint);
+            }
+            if names_italic(style) != names.italic {
+                let lint = Lint::ItalicMismatch {
+                    style: style.to_string(),
+                    italic: names.italic,
+                };
+                push(path, *index, lint);
+            }
+        }
+    }
+
+    let mut by_full_name = BTreeMap::<String, Vec<(&Path, u32, &str)>>::new();
+    for (path, index, names) in faces {
+        if let Some(full_name) = &names.full_name {
+            by_full_name
+                .entry(full_name.to_lowercase())
+                .or_default()
+                .push((path, *index, full_name));
+        }
+    }
+    for sharing in by_full_name.values().filter(|sharing| sharing.len() > 1) {
+        for (i, &(path, index, full_name)) in sharing.iter().enumerate() {
+            let others = sharing
+                .iter()
+                .enumerate()
+                .filter(|&(j, _)| j != i)
+                .map(|(_, (path, _, _))| path.display().to_string())
+                .collect();
+            let lint = Lint::DuplicateFullName {
+                full_name: full_name.to_string(),
+                others,
+            };
+            push(path, index, lint);
+        }
+    }
+
+    findings.sort_by(|a, b| (&a.path, a.index).cmp(&(&b.path, b.index)));
+    findings
+}
+
+/// The faces of the font files of the local library `dir`, with paths relative to it
+pub(crate) fn read_local_faces(dir: &Path) -> Vec<(PathBuf, u32, FaceNames)> {
+    let mut faces = Vec::new();
+    let files = WalkDir::new(dir)
+        .follow_links(crate::follow_symlinks())
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file() && has_font_extension(entry.path()));
+    for entry in files {
+        let Ok(data) = fs::read(entry.path()) else {
+            continue;
+        };
+        let path = entry
+            .path()
+            .strip_prefix(dir)
+            .unwrap_or(entry.path())
+            .to_path_buf();
+        faces.extend(file_faces(&path, &data));
+    }
+    faces
+}
+
+/// The faces of the TrueType/OpenType files of a GitHub library, reading only
+/// their headers, with paths relative to the repository root
+pub(crate) fn read_github_faces(
+    source: &Path,
+    prefix_len: usize,
+) -> Result<Vec<(PathBuf, u32, FaceNames)>, String> {
+    let source = GitHubSource::parse(source)?;
+    let client = http::client()?;
+    let mut faces = Vec::new();
+    for path in github::fetch_file_paths(&client, source.repo, source.reference)? {
+        if !is_partially_readable(Path::new(&path)) {
+            continue;
+        }
+        let url = source.file_url(&path);
+        match fetch_partial_font(&client, &url, prefix_len) {
+            Ok(font) => faces.extend(file_faces(Path::new(&path), &font.data)),
+            Err(e) => println!("  Skipping {path}: {e}"),
+        }
+    }
+    Ok(faces)
+}
+
+fn file_faces(path: &Path, data: &[u8]) -> Vec<(PathBuf, u32, FaceNames)> {
+    let count = ttf_parser::fonts_in_collection(data).unwrap_or(1);
+    (0..count)
+        .filter_map(|index| Some((path.to_path_buf(), index, FaceNames::parse(data, index)?)))
+        .collect()
+}
+
+pub(crate) fn print_findings(library: &str, faces: usize, findings: &[LintFinding]) {
+    println!("\n- Linting {library} ({faces} faces):");
+    if findings.is_empty() {
+        println!("  {} No naming problems found", "✓".green());
+    }
+    for finding in findings {
+        println!("  {} {}: {}", "✗".red(), finding.location(), finding.lint);
+        println!("      {} {}", "→".cyan(), finding.lint.suggestion());
+    }
+}
+
+/// A Markdown report of the findings per library, for the library maintainers
+pub(crate) fn render_report(libraries: &[(String, Vec<LintFinding>)]) -> String {
+    let total = libraries
+        .iter()
+        .map(|(_, findings)| findings.len())
+        .sum::<usize>();
+    let mut report = format!("# Font library lint report\n\n{total} naming problems found.\n");
+    for (library, findings) in libraries {
+        let _ = write!(report, "\n## {library}\n\n");
+        if findings.is_empty() {
+            report.push_str("No naming problems found.\n");
+            continue;
+        }
+        report.push_str("| File | Problem | Suggested fix |\n| --- | --- | --- |\n");
+        for finding in findings {
+            let _ = writeln!(
+                report,
+                "| `{}` | {} | {} |",
+                finding.location(),
+                escape_cell(&finding.lint.to_string()),
+                escape_cell(&finding.lint.suggestion())
+            );
+        }
+    }
+    report
+}
+
+fn escape_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(family: &str, subfamily: &str, weight_class: u16, italic: bool) -> FaceNames {
+        FaceNames {
+            family: Some(family.to_string()),
+            subfamily: Some(subfamily.to_string()),
+            full_name: Some(format!("{family} {subfamily}")),
+            weight_class,
+            italic,
+            ..FaceNames::default()
+        }
+    }
+
+    fn lints(faces: Vec<(&str, FaceNames)>) -> Vec<Lint> {
+        let faces = faces
+            .into_iter()
+            .map(|(path, names)| (PathBuf::from(path), 0, names))
+            .collect::<Vec<_>>();
+        lint_faces(&faces)
+            .into_iter()
+            .map(|finding| finding.lint)
+            .collect()
+    }
+
+    #[test]
+    fn test_named_weight() {
+        assert_eq!(named_weight("SemiBold Italic"), Some(600));
+        assert_eq!(named_weight("Extra-Bold"), Some(800));
+        assert_eq!(named_weight("Bold"), Some(700));
+        assert_eq!(named_weight("Italic"), Some(400));
+        assert_eq!(named_weight("Condensed"), None);
+    }
+
+    #[test]
+    fn test_inferred_weight() {
+        assert_eq!(
+            inferred_weight(&names("Lato", "Black", 400, false)),
+            Some(900)
+        );
+        // Close enough to be a deliberate choice
+        assert_eq!(inferred_weight(&names("Lato", "Medium", 400, false)), None);
+        assert_eq!(inferred_weight(&names("Lato", "Black", 900, false)), None);
+        // "Bold" of "Lato Black" is the bold linked to it, not weight 700
+        assert_eq!(
+            inferred_weight(&names("Lato Black", "Bold", 900, false)),
+            None
+        );
+        let mut variable = names("Lato", "Black", 400, false);
+        variable.variable = true;
+        assert_eq!(inferred_weight(&variable), None);
+    }
+
+    #[test]
+    fn test_consistent_faces_pass() {
+        let mut typographic = names("Inter Medium", "Regular", 500, false);
+        typographic.typographic_family = Some("Inter".to_string());
+        typographic.typographic_subfamily = Some("Medium".to_string());
+        assert!(
+            lints(vec![
+                ("Inter-Regular.ttf", names("Inter", "Regular", 400, false)),
+                (
+                    "Inter-BoldItalic.ttf",
+                    names("Inter", "Bold Italic", 700, true)
+                ),
+                ("Inter-Medium.ttf", typographic),
+            ])
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_weight_and_style_mismatches() {
+        assert_eq!(
+            lints(vec![
+                ("A.ttf", names("Lato", "Bold", 400, false)),
+                ("B.ttf", names("Lato", "Italic", 400, false)),
+            ]),
+            [
+                Lint::WeightMismatch {
+                    style: "Bold".to_string(),
+                    named: 700,
+                    actual: 400
+                },
+                Lint::ItalicMismatch {
+                    style: "Italic".to_string(),
+                    italic: false
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_full_names_and_missing_typographic_family() {
+        // "Italic" of a family "Lato Light" is no weight mismatch
+        let mut light = names("Lato Light", "Italic", 300, true);
+        light.full_name = Some("Lato".to_string());
+        let mut regular = names("Lato", "Regular", 400, false);
+        regular.full_name = Some("Lato".to_string());
+        let found = lints(vec![("Lato-LightItalic.ttf", light), ("Lato.ttf", regular)]);
+        assert_eq!(
+            found,
+            [
+                Lint::MissingTypographicFamily {
+                    family: "Lato Light".to_string(),
+                    typographic_family: "Lato".to_string(),
+                    typographic_subfamily: "Light Italic".to_string(),
+                },
+                Lint::DuplicateFullName {
+                    full_name: "Lato".to_string(),
+                    others: vec!["Lato.ttf".to_string()],
+                },
+                Lint::DuplicateFullName {
+                    full_name: "Lato".to_string(),
+                    others: vec!["Lato-LightItalic.ttf".to_string()],
+                },
+            ]
+        );
+        assert!(found[0].suggestion().contains("\"Light Italic\""));
+    }
+
+    #[cfg(feature = "test-fixtures")]
+    #[test]
+    fn test_fixture_fonts_are_named_consistently() {
+        let faces = crate::fixtures::FONTS
+            .iter()
+            .flat_map(|(name, data)| file_faces(Path::new(name), data))
+            .collect::<Vec<_>>();
+        assert_eq!(faces.len(), 2);
+        assert!(lint_faces(&faces).is_empty());
+    }
+}
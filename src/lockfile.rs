@@ -0,0 +1,470 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{self, Read};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::utils::path_utils::{is_absolute_anywhere, portable_path, relative_path};
+
+// This struct represents the lockfile of a project, i.e. font_config.lock,
+// which records how the project fonts were resolved by the last `update`
+#[derive(Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub(crate) struct FontLock {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) sources: Vec<LockedSource>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) files: Vec<LockedFile>,
+}
+
+/// The revision of a GitHub font library whose index was used for resolution
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub(crate) struct LockedSource {
+    pub(crate) repo: String,
+    pub(crate) commit: String,
+    pub(crate) committed_at: String,
+}
+
+/// A font file installed into the project font directory
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub(crate) struct LockedFile {
+    /// File name inside the project font directory
+    pub(crate) file: String,
+    /// Library path, relative to the project directory, or GitHub path
+    /// ("owner/repo/path") the file was installed from
+    pub(crate) source: String,
+    /// Faces of a .ttc/.otc source that the project uses. A single face is
+    /// extracted into its own file when the project requires a `font_format`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) faces: Vec<u32>,
+    /// Original file name if the file was converted to the project's font format
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) converted_from: Option<String>,
+    /// SHA-256 of the installed file
+    #[serde(default)]
+    pub(crate) sha256: String,
+    /// BLAKE3 of the installed file, recorded by `update --hash-algo blake3` so
+    /// that `repair`, `promote` and `store verify` can check it faster
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) blake3: Option<String>,
+    /// SHA-256 of the file before unused tables were stripped from it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) original_sha256: Option<String>,
+    /// Where the file came from; missing for files installed by older versions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) provenance: Option<Provenance>,
+}
+
+impl LockedFile {
+    /// The algorithm a verification with `algo` checks the file with, and the
+    /// digest it expects: BLAKE3 if it is recorded, and SHA-256 otherwise
+    pub(crate) fn recorded_digest(&self, algo: HashAlgo) -> (HashAlgo, &str) {
+        match (algo, &self.blake3) {
+            (HashAlgo::Blake3, Some(blake3)) => (HashAlgo::Blake3, blake3),
+            _ => (HashAlgo::Sha256, &self.sha256),
+        }
+    }
+}
+
+/// Origin of an installed file, as required by artifact provenance policies
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub(crate) struct Provenance {
+    pub(crate) source_type: SourceType,
+    /// Repository ("owner/repo") of a GitHub library
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) repo: Option<String>,
+    /// Head commit of the repository's default branch, or of its pinned
+    /// `reference`, when the file was downloaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) commit: Option<String>,
+    /// Branch, tag or commit the GitHub library was pinned to (`owner/repo@ref`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) reference: Option<String>,
+    /// Path of the file in a local library, relative to the project directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) local_path: Option<String>,
+    /// File name in the library, before any extraction or conversion
+    pub(crate) original_file: String,
+    /// URL the file was downloaded from, which may be a mirror
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) url: Option<String>,
+    /// SHA-256 of the file as found in the library, before any extraction,
+    /// conversion or stripping. Downloads from mirrors are verified against it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) sha256: Option<String>,
+    /// Tool and version that installed the file, e.g. "typfont 0.2.0"
+    pub(crate) installed_by: String,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum SourceType {
+    Local,
+    GitHub,
+    /// The local mirror of a GitHub library, see `library.local_mirrors` in
+    /// the user settings. Its location is left out, as it differs between
+    /// machines; the file is at the same path as in the repository.
+    Mirror,
+}
+
+/// The `installed_by` value of files installed by this build
+pub(crate) fn tool_version() -> String {
+    concat!("typfont ", env!("CARGO_PKG_VERSION")).to_string()
+}
+
+/// Lowercase hex SHA-256 of the given bytes
+pub(crate) fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Lowercase hex SHA-256 of the file at `path`, read in chunks so that large
+/// fonts aren't held in memory
+pub(crate) fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut chunk = vec![0; 64 * 1024];
+    loop {
+        match file.read(&mut chunk)? {
+            0 => break,
+            read => hasher.update(&chunk[..read]),
+        }
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+/// Hash algorithm of checksums and verifications. The lockfile always records
+/// SHA-256, and BLAKE3 as well after `update --hash-algo blake3`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, clap::ValueEnum)]
+pub(crate) enum HashAlgo {
+    #[default]
+    Sha256,
+    Blake3,
+}
+
+impl HashAlgo {
+    /// The name of the algorithm in messages
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Sha256 => "SHA-256",
+            Self::Blake3 => "BLAKE3",
+        }
+    }
+
+    /// Lowercase hex digest of the given bytes
+    #[cfg(test)]
+    pub(crate) fn hex(self, data: &[u8]) -> String {
+        match self {
+            Self::Sha256 => sha256_hex(data),
+            Self::Blake3 => blake3::hash(data).to_hex().to_string(),
+        }
+    }
+
+    /// Lowercase hex digest of the file at `path`
+    pub(crate) fn file_hex(self, path: &Path) -> io::Result<String> {
+        match self {
+            Self::Sha256 => sha256_file(path),
+            Self::Blake3 => {
+                let mut hasher = blake3::Hasher::new();
+                hasher.update_reader(fs::File::open(path)?)?;
+                Ok(hasher.finalize().to_hex().to_string())
+            }
+        }
+    }
+}
+
+/// Digests of the files at the paths, each by its algorithm, in the same order,
+/// hashed on all cores. Each thread takes the next whole file, as font
+/// directories hold few large files.
+pub(crate) fn hash_files(files: &[(PathBuf, HashAlgo)]) -> Vec<io::Result<String>> {
+    let next = AtomicUsize::new(0);
+    let threads = thread::available_parallelism()
+        .map_or(1, NonZeroUsize::get)
+        .min(files.len());
+    let mut hashes = thread::scope(|scope| {
+        let workers = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut hashes = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some((path, algo)) = files.get(index) else {
+                            return hashes;
+                        };
+                        hashes.push((index, algo.file_hex(path)));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("hashing a file doesn't panic"))
+            .collect::<Vec<_>>()
+    });
+    hashes.sort_unstable_by_key(|&(index, _)| index);
+    hashes.into_iter().map(|(_, hash)| hash).collect()
+}
+
+/// Path of the lockfile belonging to `config_file`, e.g. font_config.lock
+pub(crate) fn lockfile_path(config_file: &Path) -> PathBuf {
+    config_file.with_extension("lock")
+}
+
+impl FontLock {
+    /// Read the lockfile, returning `None` if the project has never been updated
+    pub(crate) fn load(path: &Path) -> Result<Option<Self>, String> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read lockfile {:?}: {}", path, e))?;
+        let mut lock: Self = toml::from_str(&content)
+            .map_err(|e| format!("Failed to parse lockfile {:?}: {}", path, e))?;
+        let project_dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        lock.make_relative(project_dir);
+        Ok(Some(lock))
+    }
+
+    /// Older versions recorded local library files by their absolute path;
+    /// rewrite those relative to `project_dir`
+    fn make_relative(&mut self, project_dir: &Path) {
+        let relative = |path: &mut String| {
+            if is_absolute_anywhere(path)
+                && let Some(relative) = relative_path(Path::new(path), project_dir)
+            {
+                *path = portable_path(&relative);
+            }
+        };
+        for file in &mut self.files {
+            relative(&mut file.source);
+            if let Some(local_path) = file
+                .provenance
+                .as_mut()
+                .and_then(|provenance| provenance.local_path.as_mut())
+            {
+                relative(local_path);
+            }
+        }
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> Result<(), String> {
+        self.check_portable()
+            .map_err(|e| format!("Refusing to write lockfile {:?}: {}", path, e))?;
+        let content = toml::to_string(self)
+            .map_err(|e| format!("Failed to serialize lockfile {:?}: {}", path, e))?;
+        fs::write(path, content).map_err(|e| format!("Failed to write lockfile {:?}: {}", path, e))
+    }
+
+    /// Check that no absolute path would be written, as the lockfile is shared
+    /// between machines that keep their projects and libraries in different places
+    pub(crate) fn check_portable(&self) -> Result<(), String> {
+        for file in &self.files {
+            let provenance = file.provenance.as_ref();
+            let paths = [
+                Some(&file.file),
+                Some(&file.source),
+                file.converted_from.as_ref(),
+                provenance.and_then(|provenance| provenance.local_path.as_ref()),
+                provenance.map(|provenance| &provenance.original_file),
+            ];
+            if let Some(path) = paths
+                .into_iter()
+                .flatten()
+                .find(|path| is_absolute_anywhere(path))
+            {
+                return Err(format!("absolute path {path:?} recorded for {}", file.file));
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn source(&self, repo: &str) -> Option<&LockedSource> {
+        self.sources.iter().find(|source| source.repo == repo)
+    }
+
+    /// Insert or replace the record of a source
+    pub(crate) fn set_source(&mut self, source: LockedSource) {
+        self.sources.retain(|existing| existing.repo != source.repo);
+        self.sources.push(source);
+        self.sources.sort_by(|a, b| a.repo.cmp(&b.repo));
+    }
+
+    /// Insert or replace the record of an installed file
+    pub(crate) fn set_file(&mut self, mut file: LockedFile) {
+        file.faces.sort_unstable();
+        file.faces.dedup();
+        self.files.retain(|existing| existing.file != file.file);
+        self.files.push(file);
+        self.files.sort_by(|a, b| a.file.cmp(&b.file));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockfile_path_is_next_to_config() {
+        assert_eq!(
+            lockfile_path(Path::new("project/font_config.toml")),
+            PathBuf::from("project/font_config.lock")
+        );
+    }
+
+    #[test]
+    fn test_set_source_replaces_existing_record() {
+        let mut lock = FontLock::default();
+        lock.set_source(LockedSource {
+            repo: "owner/fonts".to_string(),
+            commit: "aaa".to_string(),
+            committed_at: "2024-01-01T00:00:00Z".to_string(),
+        });
+        lock.set_source(LockedSource {
+            repo: "owner/fonts".to_string(),
+            commit: "bbb".to_string(),
+            committed_at: "2024-02-01T00:00:00Z".to_string(),
+        });
+
+        assert_eq!(lock.sources.len(), 1);
+        assert_eq!(lock.source("owner/fonts").unwrap().commit, "bbb");
+
+        let toml = toml::to_string(&lock).unwrap();
+        assert_eq!(toml::from_str::<FontLock>(&toml).unwrap(), lock);
+    }
+
+    #[test]
+    fn test_hash_files_keeps_the_order_of_the_paths() {
+        let dir = std::env::temp_dir().join(format!("typfont-hash-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut paths = (0..20)
+            .map(|i| {
+                let path = dir.join(format!("{i}.ttf"));
+                fs::write(&path, i.to_string()).unwrap();
+                path
+            })
+            .collect::<Vec<_>>();
+        paths.push(dir.join("missing.ttf"));
+
+        for algo in [HashAlgo::Sha256, HashAlgo::Blake3] {
+            let files = paths
+                .iter()
+                .map(|path| (path.clone(), algo))
+                .collect::<Vec<_>>();
+            let hashes = hash_files(&files);
+            assert_eq!(hashes.len(), 21);
+            for (i, hash) in hashes[..20].iter().enumerate() {
+                assert_eq!(hash.as_ref().unwrap(), &algo.hex(i.to_string().as_bytes()));
+            }
+            assert!(hashes[20].is_err());
+        }
+        assert_eq!(
+            HashAlgo::Blake3.hex(b"abc"),
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_sha256_file_matches_sha256_hex() {
+        let path = std::env::temp_dir().join(format!("typfont-hash-{}", std::process::id()));
+        // Spans several chunks
+        let data = (0..200_000u32).map(|i| i as u8).collect::<Vec<_>>();
+        fs::write(&path, &data).unwrap();
+        assert_eq!(sha256_file(&path).unwrap(), sha256_hex(&data));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_locked_file_round_trip() {
+        let mut lock = FontLock::default();
+        lock.set_file(LockedFile {
+            file: "Example.ttf".to_string(),
+            source: "library/Example.ttc".to_string(),
+            faces: vec![0, 2],
+            converted_from: None,
+            sha256: sha256_hex(b"stripped"),
+            blake3: None,
+            original_sha256: Some(sha256_hex(b"original")),
+            provenance: Some(Provenance {
+                source_type: SourceType::GitHub,
+                repo: Some("owner/fonts".to_string()),
+                commit: Some("0123abc".to_string()),
+                reference: Some("release/2024".to_string()),
+                local_path: None,
+                original_file: "Example.ttc".to_string(),
+                url: Some("https://example.com/Example.ttc".to_string()),
+                sha256: Some(sha256_hex(b"original")),
+                installed_by: tool_version(),
+            }),
+        });
+
+        let toml = toml::to_string(&lock).unwrap();
+        assert_eq!(toml::from_str::<FontLock>(&toml).unwrap(), lock);
+    }
+
+    #[test]
+    fn test_lockfile_does_not_depend_on_install_order() {
+        let file = |name: &str, faces: Vec<u32>| LockedFile {
+            file: name.to_string(),
+            source: format!("../library/{name}"),
+            faces,
+            converted_from: None,
+            sha256: sha256_hex(name.as_bytes()),
+            blake3: None,
+            original_sha256: None,
+            provenance: None,
+        };
+        let mut forward = FontLock::default();
+        forward.set_file(file("A.ttc", vec![0, 2]));
+        forward.set_file(file("B.ttf", Vec::new()));
+        let mut backward = FontLock::default();
+        backward.set_file(file("B.ttf", Vec::new()));
+        backward.set_file(file("A.ttc", vec![2, 0, 2]));
+
+        assert_eq!(
+            toml::to_string(&forward).unwrap(),
+            toml::to_string(&backward).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_absolute_paths_are_not_written() {
+        let mut lock = FontLock::default();
+        lock.set_file(LockedFile {
+            file: "Example.ttf".to_string(),
+            source: "../library/Example.ttf".to_string(),
+            faces: Vec::new(),
+            converted_from: None,
+            sha256: sha256_hex(b"font"),
+            blake3: None,
+            original_sha256: None,
+            provenance: None,
+        });
+        assert!(lock.check_portable().is_ok());
+
+        lock.files[0].source = "/home/alice/library/Example.ttf".to_string();
+        let error = lock.save(Path::new("unwritten.lock")).unwrap_err();
+        assert!(error.contains("/home/alice/library/Example.ttf"));
+        assert!(!Path::new("unwritten.lock").exists());
+    }
+}
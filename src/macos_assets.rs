@@ -0,0 +1,110 @@
+//! Fonts that macOS downloads on demand. They are listed in the mobile asset
+//! catalogs under `/System/Library/AssetsV2`; once activated, in Font Book or
+//! by an app requesting them, their files appear in the `.asset` directories
+//! next to the catalog, where the system font scan finds them. Families that
+//! are only listed can't be used by Typst until they are activated.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use crate::utils::font_utils::macos_asset_font_dirs;
+
+/// Root of the mobile asset catalogs
+pub(crate) const ASSETS_ROOT: &str = "/System/Library/AssetsV2";
+
+/// The families of the downloadable fonts listed by the catalogs below `root`,
+/// whether activated or not
+pub(crate) fn downloadable_families(root: &Path) -> BTreeSet<String> {
+    let mut families = BTreeSet::new();
+    for dir in macos_asset_font_dirs(root) {
+        let Ok(files) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for file in files.filter_map(Result::ok) {
+            let path = file.path();
+            if path.extension().is_some_and(|extension| extension == "xml")
+                && let Ok(xml) = fs::read_to_string(&path)
+            {
+                families.extend(catalog_families(&xml));
+            }
+        }
+    }
+    families
+}
+
+/// The `FontFamilyName` values of a catalog property list
+fn catalog_families(xml: &str) -> Vec<String> {
+    const KEY: &str = "<key>FontFamilyName</key>";
+    let mut families = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(KEY) {
+        rest = rest[start + KEY.len()..].trim_start();
+        if let Some(value) = rest.strip_prefix("<string>")
+            && let Some(end) = value.find("</string>")
+        {
+            families.push(unescape_xml(&value[..end]));
+        }
+    }
+    families
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalog_families_are_read_from_font_info() {
+        let xml = r#"<plist version="1.0"><dict><key>Assets</key><array>
+            <dict>
+                <key>FontInfo4</key>
+                <array>
+                    <dict>
+                        <key>FontFamilyName</key>
+                        <string>Hiragino Mincho ProN</string>
+                        <key>PostScriptFontName</key>
+                        <string>HiraMinProN-W3</string>
+                    </dict>
+                    <dict>
+                        <key>FontFamilyName</key>
+                        <string>Songti &amp; Co</string>
+                    </dict>
+                </array>
+            </dict>
+        </array></dict></plist>"#;
+        assert_eq!(
+            catalog_families(xml),
+            ["Hiragino Mincho ProN", "Songti & Co"]
+        );
+    }
+
+    #[test]
+    fn test_downloadable_families_scan_font_catalogs_only() {
+        let root = std::env::temp_dir().join(format!("typfont-assets-{}", std::process::id()));
+        let font_dir = root.join("com_apple_MobileAsset_Font7");
+        let other_dir = root.join("com_apple_MobileAsset_Other");
+        fs::create_dir_all(&font_dir).unwrap();
+        fs::create_dir_all(&other_dir).unwrap();
+        let entry = |family| format!("<key>FontFamilyName</key><string>{family}</string>");
+        fs::write(
+            font_dir.join("com_apple_MobileAsset_Font7.xml"),
+            entry("Kaiti SC"),
+        )
+        .unwrap();
+        fs::write(other_dir.join("catalog.xml"), entry("Not A Font")).unwrap();
+
+        assert_eq!(
+            downloadable_families(&root),
+            BTreeSet::from(["Kaiti SC".to_string()])
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,26 +1,112 @@
+mod adopt;
+mod badge;
+mod bench;
+mod cache;
+mod cancel;
+mod color;
 mod command;
+mod compare;
+mod convert;
+#[cfg(feature = "test-fixtures")]
+mod demo;
+mod depfile;
+mod error;
+mod file_lock;
+mod fingerprint;
+#[cfg(feature = "test-fixtures")]
+mod fixtures;
 mod font_manager;
+mod github;
+mod html_report;
+mod http;
+mod i18n;
+mod journal;
+mod layer;
+mod lint;
+mod lockfile;
+mod macos_assets;
+mod mock_server;
+mod notify;
+mod packs;
 mod parse_font_config;
+mod pdf_fonts;
 mod process_font;
+mod pull_request;
+mod quarantine;
+mod rate_limit;
+mod redistribution;
+mod remote_font;
+mod repair;
+mod sbom;
+mod scan;
+mod search;
+mod self_update;
+mod shared;
+mod status_server;
+mod strip;
+mod suggest;
+mod table;
+mod template;
+mod trace;
+mod typst_fonts;
+mod typst_packages;
+mod user_config;
 mod utils;
+mod validate;
+mod watch;
+mod workspace;
 
-use clap::Parser;
-use std::collections::BTreeMap;
+use crate::color::Colorize;
+use clap::{CommandFactory, Parser};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::fmt::Write as _;
 use std::fs;
 use std::path::{Path, PathBuf};
-use typst::text::{AxisValue, FontAxis, FontStretch, FontVariant, FontWeight, StandardAxes};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use typst::text::{
+    AxisValue, FontAxis, FontStretch, FontStyle, FontVariant, FontWeight, StandardAxes,
+};
 use walkdir::WalkDir;
 
-use crate::command::{Commands, FontCommand};
-use crate::font_manager::{LibraryDirs, get_github_font_library_entries};
-use crate::parse_font_config::TypstFont;
+use crate::command::{
+    AddCommand, AdoptCommand, BadgeCommand, BadgeFormat, CacheAction, CheckCommand,
+    CheckLibCommand, Commands, CompareCommand, EmitKind, ExportLayerCommand, ExtractCommand,
+    FontCommand, HistoryAction, HistoryCommand, ImportKind, ImportTypstFontsCommand, IndexGroup,
+    LibraryArgs, LintLibCommand, NewCommand, PromoteCommand, RemoveCommand, RepairCommand,
+    ReportCommand, ReportFormat, SbomCommand, ScanCommand, SelfAction, StoreAction, StyleArg,
+    SuggestCommand, VersionDetail,
+};
+use crate::error::Error;
+use crate::font_manager::{
+    LibraryDirs, ReportFilter, ReportSets, UpdateOptions, get_github_font_library_entries,
+};
+use crate::lockfile::{FontLock, HashAlgo, lockfile_path};
+use crate::parse_font_config::{ConfigEdit, RedistributionPolicy, TypstFont};
+use crate::process_font::{FontFeature, FontLicense, FontMetadata};
+use crate::utils::font_utils::LegacyFormat;
+use crate::utils::path_utils::{is_absolute_anywhere, portable_path, relative_path};
 
 #[derive(Clone, Debug)]
 pub(crate) struct DiscoveredFont {
     pub(crate) font: TypstFont,
     pub(crate) path: PathBuf,
+    /// Index of the face inside a .ttc/.otc collection, zero for single-face files
+    pub(crate) index: u32,
     pub(crate) axes: Vec<FontAxis>,
+    pub(crate) metadata: FontMetadata,
+}
+
+impl DiscoveredFont {
+    /// The file path, followed by the face index if the file is a collection
+    pub(crate) fn display_path(&self) -> String {
+        if utils::font_utils::is_font_collection(&self.path) {
+            format!("{} [face {}]", self.path.display(), self.index)
+        } else {
+            self.path.display().to_string()
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -36,7 +122,9 @@ struct FontLibraryEntry {
     stretch: FontProperty<u16>,
     optical_size: Option<AxisRange<AxisNumber>>,
     axes: Vec<CustomAxis>,
+    metadata: FontMetadata,
     path: PathBuf,
+    index: u32,
 }
 
 #[derive(Debug)]
@@ -69,48 +157,195 @@ pub fn create_font_path_map<P: AsRef<Path>>(font_dir: P) -> BTreeMap<TypstFont,
 
 pub(crate) fn create_font_entries<P: AsRef<Path>>(font_dir: P) -> Vec<DiscoveredFont> {
     let mut fonts = Vec::new();
+    let mut scan_cache = cache::ScanCache::load();
+    scan_font_dir(
+        &mut fonts,
+        font_dir.as_ref(),
+        &mut HashSet::new(),
+        &mut scan_cache,
+    );
+    save_scan_cache(scan_cache);
+    fonts
+}
 
-    // Walk through the directory recursively
-    for entry in WalkDir::new(&font_dir).into_iter().filter_map(|e| e.ok()) {
+/// A cache that can't be written only costs the next scan time
+fn save_scan_cache(scan_cache: cache::ScanCache) {
+    if let Err(e) = scan_cache.save() {
+        progress!("{} {e}", t!("warning").yellow().bold());
+    }
+}
+
+/// Walk through `font_dir` recursively, parsing every file that looks like a font.
+/// Symlinks are followed unless `--no-follow-symlinks` is given, and a file reachable
+/// through several paths is only parsed the first time its canonical path is `visited`.
+/// Files unchanged since an earlier scan are taken from `scan_cache` instead.
+fn scan_font_dir(
+    fonts: &mut Vec<DiscoveredFont>,
+    font_dir: &Path,
+    visited: &mut HashSet<PathBuf>,
+    scan_cache: &mut cache::ScanCache,
+) {
+    let _span = trace::span("scan", font_dir.display().to_string());
+    let follow_symlinks = follow_symlinks();
+    let mut font_files = 0;
+    let mut cached = 0;
+    let mut skipped = 0;
+    let mut legacy = 0;
+    let mut duplicates = 0;
+
+    let walker = WalkDir::new(font_dir).follow_links(follow_symlinks);
+    for entry in walker.into_iter().filter_map(|e| e.ok()) {
         let path = entry.path();
+        if !follow_symlinks && entry.path_is_symlink() {
+            continue;
+        }
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        if !utils::font_utils::is_font_file(path) {
+            if utils::font_utils::legacy_font_format(path).is_some() {
+                legacy += 1;
+            } else {
+                skipped += 1;
+            }
+            continue;
+        }
+        let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        if !visited.insert(canonical) {
+            duplicates += 1;
+            continue;
+        }
 
-        font_entries_update(&mut fonts, path);
+        font_files += 1;
+        let metadata = entry.metadata().ok();
+        if let Some(faces) = metadata
+            .as_ref()
+            .and_then(|metadata| scan_cache.faces(path, metadata))
+        {
+            cached += 1;
+            fonts.extend(faces);
+            continue;
+        }
+        let parsed = fonts.len();
+        font_entries_update(fonts, path);
+        if let Some(metadata) = &metadata {
+            scan_cache.insert(path, metadata, &fonts[parsed..]);
+        }
     }
 
-    fonts
+    if verbose() {
+        eprintln!(
+            "Scanned {font_dir:?}: {font_files} font files ({cached} unchanged since the last \
+             scan), {legacy} legacy Type 1 or bitmap fonts and {skipped} other files skipped, \
+             {duplicates} duplicate paths ignored"
+        );
+    }
+}
+
+/// The Type 1 and bitmap fonts in the local `font_dirs`, which Typst can't use
+fn find_legacy_fonts(font_dirs: &[PathBuf]) -> Vec<(PathBuf, LegacyFormat)> {
+    let mut legacy = font_dirs
+        .iter()
+        .flat_map(|font_dir| WalkDir::new(font_dir).follow_links(follow_symlinks()))
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let format = utils::font_utils::legacy_font_format(entry.path())?;
+            Some((entry.into_path(), format))
+        })
+        .collect::<Vec<_>>();
+    legacy.sort();
+    legacy.dedup();
+    legacy
+}
+
+/// Convert the Type 1 fonts among `legacy` to OpenType files next to them, unless
+/// they exist already. Failures are reported, as other fonts can still be used.
+fn convert_type1_fonts(legacy: &[(PathBuf, LegacyFormat)], converter: &str) {
+    let mut checked = false;
+    for (path, _) in legacy
+        .iter()
+        .filter(|(_, format)| *format == LegacyFormat::Type1)
+    {
+        let output = path.with_extension("otf");
+        if output.exists() || path.extension().is_some_and(|ext| ext == "gz") {
+            continue;
+        }
+        if !checked {
+            if let Err(e) = convert::check_converter(converter) {
+                println!("  {} {e}", t!("warning").yellow().bold());
+                return;
+            }
+            checked = true;
+        }
+        println!("  Converting {path:?} to {output:?}");
+        if let Err(e) = convert::convert_font_file(converter, path, &output) {
+            println!("  {} {e}", t!("warning").yellow().bold());
+        }
+    }
+}
+
+/// The `check-lib` section listing the fonts Typst can't use
+fn print_legacy_fonts(legacy: &[(PathBuf, LegacyFormat)]) {
+    if legacy.is_empty() {
+        return;
+    }
+    println!("\n- Legacy fonts (not usable by Typst):");
+    for (path, format) in legacy {
+        let converted = *format == LegacyFormat::Type1 && path.with_extension("otf").exists();
+        println!(
+            "  {} ({}{})",
+            path.display(),
+            format.name(),
+            if converted { ", converted to .otf" } else { "" }
+        );
+    }
+    let type1 = legacy
+        .iter()
+        .filter(|(path, format)| {
+            *format == LegacyFormat::Type1 && !path.with_extension("otf").exists()
+        })
+        .count();
+    if type1 > 0 {
+        println!(
+            "  {type1} Type 1 fonts can be converted to OpenType with `check-lib --convert-legacy`"
+        );
+    }
 }
 
 #[allow(dead_code)]
 pub(crate) fn create_font_path_map_from_dirs(
     library_dirs: &LibraryDirs,
-) -> BTreeMap<TypstFont, PathBuf> {
-    font_entries_to_path_map(create_font_entries_from_dirs(library_dirs))
+) -> Result<BTreeMap<TypstFont, PathBuf>, Error> {
+    create_font_entries_from_dirs(library_dirs).map(font_entries_to_path_map)
 }
 
-pub(crate) fn create_font_entries_from_dirs(library_dirs: &LibraryDirs) -> Vec<DiscoveredFont> {
+pub(crate) fn create_font_entries_from_dirs(
+    library_dirs: &LibraryDirs,
+) -> Result<Vec<DiscoveredFont>, Error> {
     let mut fonts = Vec::new();
 
     match library_dirs {
         LibraryDirs::GitHub(github_repos) => {
             for github_repo in github_repos {
                 // github_repo is a string like "owner/repo"
-                let github_font_entries = get_github_font_library_entries(&github_repo)
-                    .expect("Error Occurs when getting fonts from GitHub");
+                let _span = trace::span("index", github_repo.display().to_string());
+                let github_font_entries = get_github_font_library_entries(github_repo)
+                    .map_err(|e| Error::Library(format!("{}: {e}", github_repo.display())))?;
                 fonts.extend(github_font_entries);
             }
         }
         LibraryDirs::Local(font_dirs) => {
+            let mut visited = HashSet::new();
+            let mut scan_cache = cache::ScanCache::load();
             for font_dir in font_dirs {
-                for entry in WalkDir::new(&font_dir).into_iter().filter_map(|e| e.ok()) {
-                    let path = entry.path();
-
-                    font_entries_update(&mut fonts, path);
-                }
+                scan_font_dir(&mut fonts, font_dir, &mut visited, &mut scan_cache);
             }
+            save_scan_cache(scan_cache);
         }
     }
 
-    fonts
+    Ok(fonts)
 }
 
 fn font_entries_to_path_map<I>(fonts: I) -> BTreeMap<TypstFont, PathBuf>
@@ -126,17 +361,29 @@ where
 fn font_entries_update(fonts: &mut Vec<DiscoveredFont>, path: &Path) {
     if path.is_file() {
         // Print the file name
-        if let Some(_file_name) = path.file_name() {
+        if let Some(file_name) = path.file_name() {
             //println!("Processing [{}]", &file_name.to_string_lossy());
-            let searched = process_font::Fonts::searcher().search_file(&path);
-
-            for info in searched.infos {
+            let _span =
+                trace::span("parse", file_name.to_string_lossy()).arg("path", path.display());
+            let searched = process_font::Fonts::searcher().search_file(path);
+            let data = infer_weights().then(|| fs::read(path).ok()).flatten();
+
+            let faces = searched
+                .infos
+                .into_iter()
+                .zip(searched.metadata)
+                .zip(searched.indices);
+            for ((info, metadata), index) in faces {
                 let FontVariant {
                     style,
                     weight,
                     stretch,
                 } = info.variant;
                 //println!("- Style: {style:?}, Weight: {weight}, Stretch: {stretch}\n");
+                let weight = data
+                    .as_deref()
+                    .and_then(|data| inferred_weight(path, data, index))
+                    .unwrap_or(weight);
 
                 let font = TypstFont {
                     family_name: info.family,
@@ -148,13 +395,64 @@ fn font_entries_update(fonts: &mut Vec<DiscoveredFont>, path: &Path) {
                 fonts.push(DiscoveredFont {
                     font,
                     path: path.to_path_buf(),
+                    index,
                     axes: info.axes,
+                    metadata,
                 });
             }
         }
     }
 }
 
+/// Describe every face of the font file `data`, which was read from `path`
+pub(crate) fn discover_faces(path: &Path, data: &[u8]) -> Vec<DiscoveredFont> {
+    let count = ttf_parser::fonts_in_collection(data).unwrap_or(1);
+    (0..count)
+        .filter_map(|index| {
+            let info = typst::text::FontInfo::new(data, index)?;
+            let weight = infer_weights()
+                .then(|| inferred_weight(path, data, index))
+                .flatten()
+                .unwrap_or(info.variant.weight);
+            Some(DiscoveredFont {
+                font: TypstFont {
+                    family_name: info.family,
+                    style: info.variant.style,
+                    weight,
+                    stretch: info.variant.stretch,
+                },
+                path: path.to_path_buf(),
+                index,
+                axes: info.axes,
+                metadata: FontMetadata::parse(data, index),
+            })
+        })
+        .collect()
+}
+
+/// The weight to match a face by instead of the declared one, see
+/// `--infer-weights`. Each override is reported the first time it is made.
+fn inferred_weight(path: &Path, data: &[u8], index: u32) -> Option<FontWeight> {
+    static REPORTED: Mutex<BTreeSet<(PathBuf, u32)>> = Mutex::new(BTreeSet::new());
+
+    let names = lint::FaceNames::parse(data, index)?;
+    let weight = lint::inferred_weight(&names)?;
+    let first = REPORTED.lock().map_or(true, |mut reported| {
+        reported.insert((path.to_path_buf(), index))
+    });
+    if first {
+        let message = t!(
+            "weight-inferred",
+            path = path.display().to_string(),
+            weight = weight,
+            declared = names.weight_class,
+            style = names.style_name().unwrap_or_default()
+        );
+        println!("{}", message.yellow());
+    }
+    Some(FontWeight::from_number(weight))
+}
+
 fn strip_font_entry_root_paths(fonts: &mut [DiscoveredFont], library_root_path: &Path) {
     for font in fonts {
         if let Ok(stripped) = font.path.strip_prefix(library_root_path) {
@@ -199,28 +497,27 @@ impl From<DiscoveredFont> for FontLibraryEntry {
             stretch,
             optical_size,
             axes,
+            metadata: entry.metadata,
             path: entry.path,
+            index: entry.index,
         }
     }
 }
 
 impl From<Vec<DiscoveredFont>> for FontLibraryExport {
     fn from(mut fonts: Vec<DiscoveredFont>) -> Self {
-        fonts.sort_by(|a, b| {
+        // A total order that doesn't depend on the scan order or the OS, so that
+        // regenerating an index from the same files gives the same bytes
+        fonts.sort_by_cached_key(|font| {
             (
-                a.font.family_name.to_lowercase(),
-                a.font.style,
-                a.font.weight,
-                a.font.stretch,
-                &a.path,
+                font.font.family_name.to_lowercase(),
+                font.font.family_name.clone(),
+                font.font.style,
+                font.font.weight,
+                font.font.stretch,
+                portable_path(&font.path),
+                font.index,
             )
-                .cmp(&(
-                    b.font.family_name.to_lowercase(),
-                    b.font.style,
-                    b.font.weight,
-                    b.font.stretch,
-                    &b.path,
-                ))
         });
 
         Self {
@@ -230,7 +527,20 @@ impl From<Vec<DiscoveredFont>> for FontLibraryExport {
 }
 
 impl FontLibraryExport {
-    fn to_toml_string(&self) -> String {
+    /// The index as TOML. Fails if a path is absolute, as the index is meant
+    /// to be shared with machines that keep the fonts elsewhere.
+    fn to_toml_string(&self) -> Result<String, String> {
+        if let Some(font) = self
+            .fonts
+            .iter()
+            .find(|font| is_absolute_anywhere(&portable_path(&font.path)))
+        {
+            return Err(format!(
+                "Refusing to write the absolute path {:?} to the library index",
+                font.path
+            ));
+        }
+
         let mut toml = String::new();
 
         for (index, font) in self.fonts.iter().enumerate() {
@@ -274,15 +584,49 @@ impl FontLibraryExport {
                 toml.push_str("]\n");
             }
 
-            writeln!(
-                toml,
-                "path = {}",
-                toml_string(font.path.to_string_lossy().as_ref())
-            )
-            .unwrap();
+            let metadata = [
+                ("designer", &font.metadata.designer),
+                ("manufacturer", &font.metadata.manufacturer),
+                ("vendor_id", &font.metadata.vendor_id),
+            ];
+            for (key, value) in metadata {
+                if let Some(value) = value {
+                    writeln!(toml, "{key} = {}", toml_string(value)).unwrap();
+                }
+            }
+
+            if !font.metadata.features.is_empty() {
+                let features = font
+                    .metadata
+                    .features
+                    .iter()
+                    .map(|feature| toml_string(feature.name()))
+                    .collect::<Vec<_>>();
+                writeln!(toml, "features = [{}]", features.join(", ")).unwrap();
+            }
+
+            if let Some(class) = font.metadata.class {
+                writeln!(toml, "class = {}", toml_string(class.name())).unwrap();
+            }
+            if let Some(panose) = font.metadata.panose {
+                let digits = panose.map(|digit| digit.to_string());
+                writeln!(toml, "panose = [{}]", digits.join(", ")).unwrap();
+            }
+            if let Some(license) = font.metadata.license {
+                writeln!(toml, "license = {}", toml_string(license.name())).unwrap();
+            }
+            // Fonts without embedding restrictions, the vast majority, leave it out
+            if let Some(fs_type) = font.metadata.fs_type.filter(|&fs_type| fs_type != 0) {
+                writeln!(toml, "fs_type = {fs_type}").unwrap();
+            }
+
+            writeln!(toml, "path = {}", toml_string(&portable_path(&font.path))).unwrap();
+            if font.index > 0 {
+                writeln!(toml, "index = {}", font.index).unwrap();
+            }
         }
 
-        toml
+        Ok(toml)
     }
 }
 
@@ -357,7 +701,8 @@ fn axis_number_range(axis: &FontAxis) -> AxisRange<AxisNumber> {
 }
 
 fn stretch_to_number(stretch: FontStretch) -> u16 {
-    (stretch.to_ratio().get() * 1000.0) as u16
+    // Rounded, as the ratio isn't exact, e.g. 1.001 * 1000.0 < 1001.0
+    (stretch.to_ratio().get() * 1000.0).round() as u16
 }
 
 fn print_font_variants(fonts: &[DiscoveredFont]) {
@@ -389,7 +734,7 @@ fn print_font_variants(fonts: &[DiscoveredFont]) {
 fn print_font_variant(entry: &DiscoveredFont, last: bool) {
     let marker = if last { '└' } else { '├' };
     let pad = if last { "     " } else { "  │  " };
-    let path = entry.path.display();
+    let path = entry.display_path();
 
     if entry.axes.is_empty() {
         println!("  {marker} {path}");
@@ -417,6 +762,16 @@ fn print_font_variant(entry: &DiscoveredFont, last: bool) {
         }
     }
 
+    if !entry.metadata.features.is_empty() {
+        let features = entry
+            .metadata
+            .features
+            .iter()
+            .map(|feature| feature.name())
+            .collect::<Vec<_>>();
+        println!("{pad} Features: {}", features.join(", "));
+    }
+
     if !last {
         println!("  │");
     }
@@ -459,98 +814,1973 @@ fn format_axis_with(axis: &FontAxis, name: &str, show: impl Fn(AxisValue) -> Str
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Cli {
+    /// Without a subcommand, the `default_command` of the user settings runs
     #[command(subcommand)]
-    command: Commands,
+    command: Option<Commands>,
+
+    /// Print details such as the number of files skipped while scanning
+    #[arg(short, long, global = true, default_value = "false")]
+    verbose: bool,
+
+    /// Neither descend into symlinked directories nor index symlinked font files
+    #[arg(long, global = true, default_value = "false")]
+    no_follow_symlinks: bool,
+
+    /// Language of the output; defaults to the language of the system locale
+    #[arg(long, global = true, value_enum)]
+    lang: Option<i18n::Lang>,
+
+    /// Never fall back to the system font directories when no library is given
+    #[arg(long, global = true, default_value = "false")]
+    no_system_fonts: bool,
+
+    /// Directories used instead of the system font directories when no library is given
+    #[arg(long, global = true, num_args = 1.., value_name = "DIR", conflicts_with = "no_system_fonts")]
+    system_dirs: Option<Vec<PathBuf>>,
+
+    /// Record how long scanning, parsing, resolving and copying took and write it
+    /// to this file in the Chrome trace format
+    #[arg(long, global = true, value_name = "FILE")]
+    trace_out: Option<PathBuf>,
+
+    /// Match faces whose declared weight clearly contradicts their style name,
+    /// like a "Black" face declared as 400, by the weight the name gives
+    #[arg(long, global = true, default_value = "false")]
+    infer_weights: bool,
+
+    /// Fail at once instead of waiting when another typfont process is updating
+    /// the same font directory or cache
+    #[arg(long, global = true, default_value = "false")]
+    no_wait: bool,
+
+    /// Parse every local font file again instead of taking its faces from the
+    /// cache of earlier scans, which receives the new results
+    #[arg(long, global = true, default_value = "false")]
+    no_cache: bool,
+
+    /// GitHub token for repositories without a credential profile in the user
+    /// settings; defaults to GITHUB_TOKEN or GH_TOKEN
+    #[arg(long, global = true, value_name = "TOKEN")]
+    token: Option<String>,
+
+    /// Send GitHub requests to this server, or serve the fixtures in this
+    /// directory locally and send them there (for development and tests)
+    #[arg(long, global = true, value_name = "URL|DIR", hide = true)]
+    mock_server: Option<String>,
 }
 
-fn process_command(args: &FontCommand, action: &str, dry_run: bool) {
-    args.validate().unwrap();
-    match font_manager::FontManager::new(args, action) {
-        Ok(font_manager) => {
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+static FOLLOW_SYMLINKS: AtomicBool = AtomicBool::new(true);
+static INFER_WEIGHTS: AtomicBool = AtomicBool::new(false);
+static NO_CACHE: AtomicBool = AtomicBool::new(false);
+static MACHINE_OUTPUT: AtomicBool = AtomicBool::new(false);
+/// The system font directories set by `--system-dirs`, or `None` after
+/// `--no-system-fonts`; unset if neither was given
+static SYSTEM_FONT_DIRS: OnceLock<Option<Vec<PathBuf>>> = OnceLock::new();
+
+/// Whether `--verbose` was given
+pub(crate) fn verbose() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
+
+/// Whether the report is a JSON or TOML document on standard output, see
+/// `--format`
+pub(crate) fn machine_output() -> bool {
+    MACHINE_OUTPUT.load(Ordering::Relaxed)
+}
+
+/// Print a line of progress or a warning: to standard output, or to standard
+/// error while a JSON or TOML report goes to standard output
+#[macro_export]
+macro_rules! progress {
+    ($($arg:tt)*) => {
+        if $crate::machine_output() {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+
+/// Whether directory scans follow symlinks, see `--no-follow-symlinks`
+pub(crate) fn follow_symlinks() -> bool {
+    FOLLOW_SYMLINKS.load(Ordering::Relaxed)
+}
+
+/// Whether `--infer-weights` was given
+pub(crate) fn infer_weights() -> bool {
+    INFER_WEIGHTS.load(Ordering::Relaxed)
+}
+
+/// Whether `--no-cache` was given
+pub(crate) fn no_cache() -> bool {
+    NO_CACHE.load(Ordering::Relaxed)
+}
+
+/// The font directories standing in for a library when none is given: those of
+/// the system, unless replaced by `--system-dirs`, or none with `--no-system-fonts`
+pub(crate) fn system_font_dirs() -> Vec<PathBuf> {
+    match SYSTEM_FONT_DIRS.get() {
+        Some(dirs) => dirs.clone().unwrap_or_default(),
+        None => utils::font_utils::get_system_font_directories(),
+    }
+}
+
+fn process_command(
+    args: &FontCommand,
+    action: &str,
+    report_filter: ReportFilter,
+    update_options: Option<&UpdateOptions>,
+    check: Option<&CheckCommand>,
+    format: ReportFormat,
+) -> Result<(), Error> {
+    args.validate().map_err(Error::Usage)?;
+    MACHINE_OUTPUT.store(format != ReportFormat::Plain, Ordering::Relaxed);
+    let config_file = font_manager::FontManager::resolve_config_file(&args.project_or_config);
+    // Like cargo's freshness check, without reading the libraries or the fonts.
+    // A JSON or TOML report, or a file for build systems, needs them all the same.
+    if format == ReportFormat::Plain
+        && check.is_some_and(|check| check.emit.is_empty())
+        && check.is_some_and(|check| !check.force && check.system_versions.is_none())
+        && let Ok(fingerprint) = fingerprint::compute(args)
+        && fingerprint::is_fresh(&config_file, &fingerprint)
+    {
+        println!(
+            "{}",
+            t!("report-up-to-date", path = format!("{config_file:?}")).green()
+        );
+        return Ok(());
+    }
+    // Held until the update is done, so that another update of the project waits
+    let _font_dir_lock = if update_options.is_some_and(|options| !options.dry_run) {
+        let font_dir =
+            font_manager::FontManager::project_font_dir(&config_file).map_err(Error::Project)?;
+        let lock = file_lock::lock_dir(&font_dir).map_err(Error::Update)?;
+        // Before the project fonts are read, which a partial file would be among
+        let rolled_back = cancel::roll_back_interrupted(&cancel::state_path(&config_file))
+            .map_err(Error::Update)?;
+        for path in rolled_back {
+            progress!("{}", t!("cancel-rolled-back", path = format!("{path:?}")));
+        }
+        Some(lock)
+    } else {
+        None
+    };
+    let font_manager =
+        font_manager::FontManager::new(args, action)?.with_report_filter(report_filter);
+    if format == ReportFormat::Plain {
+        font_manager.print_status();
+    }
+    let mut installed = Vec::new();
+
+    if let Some(check) = check {
+        font_manager.print_index_staleness(check.stale_after);
+        if let Some(detail) = check.system_versions {
+            let system_entries =
+                create_font_entries_from_dirs(&LibraryDirs::Local(system_font_dirs()))?;
+            font_manager
+                .print_system_version_mismatches(&system_entries, detail == VersionDetail::Faces);
+        }
+        notify_drift(&font_manager, check.notify_webhook.as_deref())?;
+        font_manager
+            .check_embedding(check.embedding_policy)
+            .map_err(Error::Other)?;
+    }
+
+    if let Some(options) = update_options {
+        installed = font_manager.update_fonts(options).map_err(Error::Update)?;
+        if !options.dry_run {
+            // With --locked, the lockfile is left as it is
+            if !options.locked
+                && let Err(e) = font_manager.record_index_revisions()
+            {
+                progress!("{}", t!("error-record-revisions", error = e));
+            }
+            // Quarantined files enter the project, and the journal, when promoted
+            if !font_manager.quarantines(options)
+                && let Err(e) = font_manager.record_journal("update", installed.clone())
+            {
+                progress!("{}", t!("error-record-journal", error = e));
+            }
+            if cancel::cancelled() {
+                return Err(Error::Update(t!("cancel-resume")));
+            }
+        }
+    }
+
+    // Computed last, as an update may have written the lockfile
+    if font_manager.is_complete() {
+        if let Err(e) = fingerprint::compute(args)
+            .and_then(|fingerprint| fingerprint::record(&config_file, &fingerprint))
+        {
+            progress!("{}", t!("error-record-fingerprint", error = e));
+        }
+    } else {
+        fingerprint::clear(&config_file);
+    }
+
+    let report = font_manager::FontReport {
+        installed,
+        ..font_manager.report()
+    };
+    match font_manager::render_report(&report, format).map_err(Error::Other)? {
+        Some(document) => print!("{document}"),
+        None => println!("\n{}", t!("report-done")),
+    }
+    match check {
+        Some(check) => {
+            for emit in &check.emit {
+                match emit.kind {
+                    EmitKind::MakeDeps => {
+                        write_make_deps(&font_manager, &config_file, check, &emit.path)?
+                    }
+                }
+            }
+            check_failure(&font_manager, check.strict)
+        }
+        None => Ok(()),
+    }
+}
+
+/// `check --emit make-deps`: the rule of the compiled document on the installed
+/// files of the required fonts, in the order of their paths
+fn write_make_deps(
+    font_manager: &font_manager::FontManager,
+    config_file: &Path,
+    check: &CheckCommand,
+    path: &Path,
+) -> Result<(), Error> {
+    let target = check.deps_target.clone().unwrap_or_else(|| {
+        config_file
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join("main.pdf")
+    });
+    let files = font_manager
+        .installed_entries()
+        .iter()
+        .filter(|entry| {
+            font_manager
+                .required_fonts()
+                .iter()
+                .any(|font| font_manager::font_entry_satisfies(entry, font))
+        })
+        .map(|entry| entry.path.clone())
+        .collect::<BTreeSet<_>>();
+    depfile::write_make_deps(path, &target, &Vec::from_iter(files)).map_err(Error::Other)?;
+    progress!(
+        "Dependencies of {} written to {}",
+        target.display(),
+        path.display()
+    );
+    Ok(())
+}
+
+/// The error `check` exits with when fonts are missing, or redundant with
+/// `--strict`, so that CI jobs can gate on the font status
+fn check_failure(font_manager: &font_manager::FontManager, strict: bool) -> Result<(), Error> {
+    let summary = font_manager.summary();
+    match summary.failure(strict) {
+        Some(failure) => Err(Error::Incomplete {
+            failure,
+            summary: summary.to_string(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// `status`, or `check --summary-only`: print the summary line of the project and
+/// fail as `check` does
+fn print_summary(
+    font: &FontCommand,
+    strict: bool,
+    notify_webhook: Option<&str>,
+) -> Result<(), Error> {
+    font.validate().map_err(Error::Usage)?;
+    let action = t!("action-checking");
+    let font_manager = font_manager::FontManager::new(font, &action)?;
+    println!("{}", font_manager.summary());
+    notify_drift(&font_manager, notify_webhook)?;
+    check_failure(&font_manager, strict)
+}
+
+/// `update --pr`: propose the changes of the update as a GitHub pull request
+fn open_pull_request(args: &FontCommand, token: &str) -> Result<(), Error> {
+    let config_file = font_manager::FontManager::resolve_config_file(&args.project_or_config);
+    let font_dir =
+        font_manager::FontManager::project_font_dir(&config_file).map_err(Error::Project)?;
+    match pull_request::open(&config_file, &font_dir, token).map_err(Error::Update)? {
+        Some(url) => progress!("Opened the pull request {url}"),
+        None => progress!("The update changed no files, so no pull request was opened"),
+    }
+    Ok(())
+}
+
+/// `check --configs`: report on every config against one scan of the library.
+/// Fails if any config couldn't be checked, after checking the others, and
+/// otherwise with the most severe failure among the projects.
+fn check_configs(args: &CheckCommand, report_filter: ReportFilter) -> Result<(), Error> {
+    args.font.validate().map_err(Error::Usage)?;
+    let action = t!("action-checking");
+    let library_dirs = font_manager::FontManager::library_dirs(&args.font).map_err(Error::Usage)?;
+    let library_entries = create_font_entries_from_dirs(&library_dirs)?;
+
+    let mut failed = 0;
+    // The most severe failure among the projects, with their summaries
+    let mut incomplete = None;
+    let mut summaries = Vec::new();
+    for config in &args.configs {
+        let font = FontCommand {
+            project_or_config: config.clone(),
+            ..args.font.clone()
+        };
+        println!("\n{}", format!("=== {} ===", config.display()).bold());
+        let font_manager = match font_manager::FontManager::with_library_entries(
+            &font,
+            &action,
+            library_dirs.clone(),
+            library_entries.clone(),
+        ) {
+            Ok(font_manager) => font_manager.with_report_filter(report_filter.clone()),
+            Err(e) => {
+                println!("{} {e}", t!("error").red().bold());
+                failed += 1;
+                continue;
+            }
+        };
+        if args.summary_only {
+            println!("{}", font_manager.summary());
+        } else {
             font_manager.print_status();
+            font_manager.print_index_staleness(args.stale_after);
+        }
+        notify_drift(&font_manager, args.notify_webhook.as_deref())?;
+        let summary = font_manager.summary();
+        if let Some(failure) = summary.failure(args.strict) {
+            incomplete = incomplete.max(Some(failure));
+            summaries.push(format!("{}: {summary}", config.display()));
+        }
+    }
+
+    println!("\n{}", t!("report-done"));
+    if failed > 0 {
+        return Err(Error::Project(format!(
+            "{failed} of {} configs could not be checked",
+            args.configs.len()
+        )));
+    }
+    match incomplete {
+        Some(failure) => Err(Error::Incomplete {
+            failure,
+            summary: summaries.join("; "),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Post the project's font status to `webhook` if fonts are missing or redundant
+fn notify_drift(
+    font_manager: &font_manager::FontManager,
+    webhook: Option<&str>,
+) -> Result<(), Error> {
+    let Some(webhook) = webhook else {
+        return Ok(());
+    };
+    let report = font_manager.status_report();
+    if report.counts.missing == 0 && report.counts.redundant == 0 {
+        return Ok(());
+    }
+    notify::post_webhook(webhook, &report).map_err(Error::Other)?;
+    progress!("Posted the font status to {webhook}");
+    Ok(())
+}
+
+fn write_sbom(args: &SbomCommand) -> Result<(), Error> {
+    let config_file = font_manager::FontManager::resolve_config_file(&args.project_or_config);
+    let document = sbom::generate_sbom(&config_file, args.hash_algo).map_err(Error::Project)?;
+    match &args.output {
+        Some(path) => fs::write(path, document).map_err(|source| Error::Write {
+            path: path.clone(),
+            source,
+        })?,
+        None => println!("{document}"),
+    }
+    Ok(())
+}
+
+/// Create a project from a template, then install its fonts if a library is given
+fn new_project(args: &NewCommand) -> Result<(), Error> {
+    let (font_config, main_typ) =
+        template::template_font_config(&args.template).map_err(Error::Usage)?;
+    // The workflow can only fetch fonts from a GitHub library
+    let ci_library = match &args.library {
+        Some(library) if args.github => portable_path(&library[0]),
+        _ => "owner/repo".to_string(),
+    };
+    let workflow = args.ci.then(|| template::ci_workflow(&ci_library));
+    let written = template::create_project(&args.dir, &font_config, main_typ, workflow.as_deref())
+        .map_err(Error::Usage)?;
+    for path in &written {
+        println!("  Created {}", path.display());
+    }
+    if args.ci && ci_library == "owner/repo" {
+        println!("  Set the font library in .github/workflows/fonts.yml");
+    }
+
+    if args.library.is_none() {
+        println!(
+            "\nRun `typfont update -l <LIBRARY>` in {} to install the fonts",
+            args.dir.display()
+        );
+        return Ok(());
+    }
+    let font = FontCommand {
+        library: args.library.clone(),
+        github: args.github,
+        ..FontCommand::project(args.dir.clone())
+    };
+    let options = UpdateOptions {
+        dry_run: false,
+        converter: None,
+        strip: false,
+        limit_rate: None,
+        diff: false,
+        locked: false,
+        only_sources: None,
+        quarantine: false,
+        hash_algo: HashAlgo::Sha256,
+    };
+    process_command(
+        &font,
+        &t!("action-updating"),
+        ReportFilter::default(),
+        Some(&options),
+        None,
+        ReportFormat::Plain,
+    )
+}
+
+/// Add the font of `args` to the project's font_config.toml, which is created
+/// with the default font directory if it doesn't exist yet
+fn add_font(args: &AddCommand) -> Result<(), Error> {
+    let config_file = font_manager::FontManager::resolve_config_file(&args.project_or_config);
+    let content = match fs::read_to_string(&config_file) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => "font_dir = \"fonts\"\n".to_string(),
+        Err(e) => {
+            return Err(Error::Project(format!(
+                "Failed to read {}: {e}",
+                config_file.display()
+            )));
+        }
+    };
+    let (edited, edit) = parse_font_config::add_font_entry(
+        &content,
+        &args.family,
+        args.style.font_style(),
+        args.stretch,
+        &args.weight,
+    )
+    .map_err(|e| Error::Project(format!("{}: {e:#}", config_file.display())))?;
+
+    let font = format!("{} ({:?})", args.family, args.style.font_style());
+    match edit {
+        ConfigEdit::Unchanged => {
+            println!("{font} is already required by {}", config_file.display());
+            return Ok(());
+        }
+        ConfigEdit::Appended => println!("  {} {font}", "+".green()),
+        ConfigEdit::Merged(weights) => {
+            let weights = weights.iter().map(u16::to_string).collect::<Vec<_>>();
+            println!("  {} {font}: weight {}", "~".yellow(), weights.join(", "));
+        }
+    }
+    if let Some(parent) = config_file
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent).map_err(|source| Error::Write {
+            path: parent.to_path_buf(),
+            source,
+        })?;
+    }
+    fs::write(&config_file, edited).map_err(|source| Error::Write {
+        path: config_file.clone(),
+        source,
+    })
+}
+
+/// Remove the fonts of `args` from the project's font_config.toml and report
+/// the installed files no required font uses any more, which `--purge` deletes
+fn remove_font(args: &RemoveCommand) -> Result<(), Error> {
+    let config_file = font_manager::FontManager::resolve_config_file(&args.project_or_config);
+    let content = fs::read_to_string(&config_file)
+        .map_err(|e| Error::Project(format!("Failed to read {}: {e}", config_file.display())))?;
+    let (edited, removed) = parse_font_config::remove_font_entries(
+        &content,
+        &args.family,
+        args.style.map(StyleArg::font_style),
+        &args.weight,
+    )
+    .map_err(|e| Error::Project(format!("{}: {e:#}", config_file.display())))?;
+    if removed.is_empty() {
+        return Err(Error::Usage(format!(
+            "{} requires no such font of {}",
+            config_file.display(),
+            args.family
+        )));
+    }
+
+    let font = FontCommand::project(args.project_or_config.clone());
+    let action = t!("action-checking");
+    let redundant_files = || {
+        let font_manager = font_manager::FontManager::with_library_entries(
+            &font,
+            &action,
+            LibraryDirs::Local(Vec::new()),
+            Vec::new(),
+        )
+        .map_err(Error::Project)?;
+        let files = font_manager.redundant_files().into_iter();
+        Ok::<_, Error>(files.map(Path::to_path_buf).collect::<BTreeSet<_>>())
+    };
+    let before = redundant_files()?;
+    fs::write(&config_file, edited).map_err(|source| Error::Write {
+        path: config_file.clone(),
+        source,
+    })?;
+    for font in &removed {
+        println!(
+            "  {} {} {}",
+            "-".red(),
+            font.family_name,
+            font.variant_string()
+        );
+    }
+
+    let orphaned = redundant_files()?
+        .into_iter()
+        .filter(|path| !before.contains(path))
+        .collect::<Vec<_>>();
+    if orphaned.is_empty() {
+        return Ok(());
+    }
+    let font_dir =
+        font_manager::FontManager::project_font_dir(&config_file).map_err(Error::Project)?;
+    let names = orphaned
+        .iter()
+        .map(|path| portable_path(path.strip_prefix(&font_dir).unwrap_or(path)))
+        .collect::<Vec<_>>();
+    if !args.purge {
+        println!("No longer required, delete them with --purge:");
+        for name in &names {
+            println!("  {name}");
+        }
+        return Ok(());
+    }
 
-            if action == "Updating" {
-                if let Err(e) = font_manager.update_fonts(dry_run) {
-                    println!("Error updating fonts: {e}");
+    for (path, name) in orphaned.iter().zip(&names) {
+        fs::remove_file(path)
+            .map_err(|e| Error::Project(format!("Failed to delete {}: {e}", path.display())))?;
+        println!("  {} {name}", "deleted".red());
+    }
+    let lock_path = lockfile::lockfile_path(&config_file);
+    if let Some(mut lock) = lockfile::FontLock::load(&lock_path).map_err(Error::Project)? {
+        lock.files.retain(|file| !names.contains(&file.file));
+        lock.save(&lock_path).map_err(Error::Project)?;
+    }
+    Ok(())
+}
+
+/// Infer the required fonts from the project's `.typ` files and print the
+/// proposed font_config.toml, or merge them into it with `--write`
+fn scan_fonts(args: &ScanCommand) -> Result<(), Error> {
+    let config_file = font_manager::FontManager::resolve_config_file(&args.project_or_config);
+    let project_dir = match config_file.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let font_dir = if config_file.exists() {
+        font_manager::FontManager::project_font_dir(&config_file).map_err(Error::Project)?
+    } else {
+        project_dir.join("fonts")
+    };
+
+    let scanned = scan::scan_project(project_dir, &font_dir);
+    for (path, usages) in &scanned {
+        let path = portable_path(path.strip_prefix(project_dir).unwrap_or(path));
+        for usage in usages {
+            let math = if usage.math { " (math)" } else { "" };
+            eprintln!("  {path}:{}: {}{math}", usage.line, usage.family);
+        }
+    }
+    let fonts = scan::required_fonts(scanned.iter().flat_map(|(_, usages)| usages));
+    if fonts.is_empty() {
+        eprintln!("No fonts to install are named in the project's .typ files");
+        return Ok(());
+    }
+    if !args.write {
+        print!("{}", typst_fonts::to_config_toml(&fonts));
+        return Ok(());
+    }
+
+    let mut content = match fs::read_to_string(&config_file) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => "font_dir = \"fonts\"\n".to_string(),
+        Err(e) => {
+            return Err(Error::Project(format!(
+                "Failed to read {}: {e}",
+                config_file.display()
+            )));
+        }
+    };
+    let mut grouped = BTreeMap::<(&str, FontStyle), Vec<u16>>::new();
+    for font in &fonts {
+        grouped
+            .entry((&font.family_name, font.style))
+            .or_default()
+            .push(font.weight.to_number());
+    }
+    let mut changed = false;
+    for ((family, style), weights) in grouped {
+        let (edited, edit) = parse_font_config::add_font_entry(
+            &content,
+            family,
+            style,
+            FontStretch::NORMAL,
+            &weights,
+        )
+        .map_err(|e| Error::Project(format!("{}: {e:#}", config_file.display())))?;
+        let font = format!("{family} ({style:?})");
+        match edit {
+            ConfigEdit::Unchanged => continue,
+            ConfigEdit::Appended => println!("  {} {font}", "+".green()),
+            ConfigEdit::Merged(weights) => {
+                let weights = weights.iter().map(u16::to_string).collect::<Vec<_>>();
+                println!("  {} {font}: weight {}", "~".yellow(), weights.join(", "));
+            }
+        }
+        content = edited;
+        changed = true;
+    }
+    if !changed {
+        println!(
+            "{} already requires every font found",
+            config_file.display()
+        );
+        return Ok(());
+    }
+    fs::write(&config_file, content).map_err(|source| Error::Write {
+        path: config_file.clone(),
+        source,
+    })
+}
+
+/// Move the approved fonts out of the quarantine of the project's font directory,
+/// or print the summary of those awaiting review if none are given
+fn promote_fonts(args: &PromoteCommand) -> Result<(), Error> {
+    let config_file = font_manager::FontManager::resolve_config_file(&args.project_or_config);
+    let font_dir =
+        font_manager::FontManager::project_font_dir(&config_file).map_err(Error::Project)?;
+    if args.file.is_empty() && !args.all {
+        let awaiting = quarantine::awaiting(&font_dir).map_err(Error::Project)?;
+        print!("{}", quarantine::summary(&font_dir, &awaiting));
+        return Ok(());
+    }
+
+    let promoted = quarantine::promote(&config_file, &font_dir, &args.file, args.hash_algo)
+        .map_err(Error::Update)?;
+    if promoted.is_empty() {
+        println!("No fonts are awaiting review");
+        return Ok(());
+    }
+    for file in &promoted {
+        println!("  {} {}", "+".green(), file.file);
+    }
+    let actions = promoted
+        .into_iter()
+        .map(journal::JournalAction::Install)
+        .collect();
+    journal::record_run(
+        &journal::journal_path(&config_file),
+        "promote",
+        actions,
+        Vec::new(),
+    )
+    .map_err(Error::Other)?;
+    Ok(())
+}
+
+/// The project's font status as a badge. Only the project is inspected, so no
+/// library is scanned.
+fn write_badge(args: &BadgeCommand) -> Result<(), Error> {
+    let font = FontCommand::project(args.project_or_config.clone());
+    let action = t!("action-checking");
+    let font_manager = font_manager::FontManager::with_library_entries(
+        &font,
+        &action,
+        LibraryDirs::Local(Vec::new()),
+        Vec::new(),
+    )
+    .map_err(Error::Project)?;
+    let status = badge::BadgeStatus::of(&font_manager);
+    let badge = match args.format {
+        BadgeFormat::Json => badge::endpoint_json(&args.label, &status),
+        BadgeFormat::Svg => badge::svg(&args.label, &status),
+    };
+    match &args.output {
+        Some(path) => fs::write(path, badge).map_err(|source| Error::Write {
+            path: path.clone(),
+            source,
+        })?,
+        None => print!("{badge}"),
+    }
+    Ok(())
+}
+
+/// Write the installed files providing the project's required fonts as a
+/// reproducible image layer. Every required font must be installed or
+/// embedded in Typst, so that the image doesn't lack fonts silently.
+fn export_layer(args: &ExportLayerCommand) -> Result<(), Error> {
+    let font = FontCommand::project(args.project_or_config.clone());
+    let action = t!("action-checking");
+    let font_manager = font_manager::FontManager::with_library_entries(
+        &font,
+        &action,
+        LibraryDirs::Local(Vec::new()),
+        Vec::new(),
+    )
+    .map_err(Error::Project)?;
+    let missing = font_manager.unresolvable_fonts();
+    if !missing.is_empty() {
+        let families = missing.keys().copied().collect::<Vec<_>>().join(", ");
+        return Err(Error::Project(format!(
+            "Required fonts of {families} aren't installed; run `typfont update` first"
+        )));
+    }
+
+    let config_file = font_manager::FontManager::resolve_config_file(&args.project_or_config);
+    let font_dir =
+        font_manager::FontManager::project_font_dir(&config_file).map_err(Error::Project)?;
+    let prefix = args.prefix.trim_matches('/');
+    let mut files = BTreeMap::new();
+    for entry in font_manager.installed_entries() {
+        let required = font_manager
+            .required_fonts()
+            .iter()
+            .any(|font| font_manager::font_entry_satisfies(entry, font));
+        if !required {
+            continue;
+        }
+        let relative = entry.path.strip_prefix(&font_dir).unwrap_or(&entry.path);
+        let name = format!("{prefix}/{}", portable_path(relative));
+        files.insert(name.trim_start_matches('/').to_string(), entry.path.clone());
+    }
+
+    let layer = layer::build_tar(&files, layer::source_date_epoch()?)?;
+    let digest = lockfile::sha256_hex(&layer);
+    if let Some(path) = &args.tar {
+        layer::write_tar(path, &layer)?;
+        println!("Layer written to {}", path.display());
+    }
+    if let Some(dir) = &args.oci {
+        layer::write_oci_layout(dir, &layer, &args.tag)?;
+        println!("OCI image layout written to {}", dir.display());
+    }
+    println!("  {} font files, sha256:{digest}", files.len());
+    Ok(())
+}
+
+fn report_fonts(args: &ReportCommand) -> Result<(), Error> {
+    // Written first, so that CI can attach it even when the PDF check fails
+    if let Some(path) = &args.html {
+        write_html_report(args, path)?;
+    }
+    if let Some(pdf) = &args.pdf {
+        report_pdf_fonts(args, pdf)?;
+    }
+    Ok(())
+}
+
+/// `report --html`: the status of `check` with the table layout's details
+fn write_html_report(args: &ReportCommand, path: &Path) -> Result<(), Error> {
+    let font = FontCommand {
+        library: args.library.library.clone(),
+        github: args.library.github,
+        ..FontCommand::project(args.project_or_config.clone())
+    };
+    font.validate().map_err(Error::Usage)?;
+    let action = t!("action-checking");
+    let font_manager = font_manager::FontManager::new(&font, &action)?;
+    let html = font_manager.html_report().render();
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent).map_err(|source| Error::Write {
+            path: parent.to_path_buf(),
+            source,
+        })?;
+    }
+    fs::write(path, html).map_err(|source| Error::Write {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    println!("HTML report written to {}", path.display());
+    Ok(())
+}
+
+/// List the fonts of a compiled PDF against the project's fonts. Fails if Typst
+/// fell back to fonts the project doesn't require.
+fn report_pdf_fonts(args: &ReportCommand, pdf: &Path) -> Result<(), Error> {
+    let font = FontCommand::project(args.project_or_config.clone());
+    let action = t!("action-checking");
+    let font_manager = font_manager::FontManager::with_library_entries(
+        &font,
+        &action,
+        LibraryDirs::Local(Vec::new()),
+        Vec::new(),
+    )
+    .map_err(Error::Project)?;
+    let fonts = pdf_fonts::read_pdf_fonts(pdf)?;
+
+    // PDFs name fonts by their PostScript names
+    let installed = font_manager
+        .installed_entries()
+        .iter()
+        .filter_map(|entry| {
+            let data = fs::read(&entry.path).ok()?;
+            let name = pdf_fonts::postscript_name(&data, entry.index)?;
+            Some((name, entry.clone()))
+        })
+        .collect::<Vec<_>>();
+    let required = font_manager
+        .required_fonts()
+        .iter()
+        .cloned()
+        .collect::<Vec<_>>();
+
+    println!("Fonts in {}\n", pdf.display());
+    let unexpected = pdf_fonts::print_pdf_report(&fonts, &installed, &required);
+    if unexpected > 0 {
+        return Err(Error::Other(format!(
+            "{unexpected} font{} of the PDF {} not required by the project: Typst fell back to {}",
+            if unexpected == 1 { "" } else { "s" },
+            if unexpected == 1 { "is" } else { "are" },
+            if unexpected == 1 { "it" } else { "them" },
+        )));
+    }
+    Ok(())
+}
+
+/// Write the complete, installable fonts of a PDF into a library directory and
+/// index the directory. Existing files are left alone.
+fn extract_pdf_fonts(args: &ExtractCommand) -> Result<(), Error> {
+    let fonts = pdf_fonts::read_pdf_fonts(&args.pdf)?;
+    fs::create_dir_all(&args.to).map_err(|source| Error::Write {
+        path: args.to.clone(),
+        source,
+    })?;
+
+    let mut extracted = 0;
+    for font in &fonts {
+        let (program, format) = match pdf_fonts::extractable_program(font) {
+            Ok(extractable) => extractable,
+            Err(reason) => {
+                println!("  - {}: {}", font.name, reason.dimmed());
+                continue;
+            }
+        };
+        let path = args.to.join(pdf_fonts::extracted_file_name(font, format));
+        if path.exists() {
+            println!("  = {} already exists", path.display());
+            continue;
+        }
+        fs::write(&path, program).map_err(|source| Error::Write {
+            path: path.clone(),
+            source,
+        })?;
+        println!("  {} {}", "+".green(), path.display());
+        extracted += 1;
+    }
+
+    if extracted == 0 {
+        println!("No new font of {} could be extracted", args.pdf.display());
+        return Ok(());
+    }
+    let entries = create_font_entries_from_dirs(&LibraryDirs::Local(vec![args.to.clone()]))?;
+    write_library_index(entries, &args.to.join(github::DEFAULT_INDEX_PATH))?;
+    println!(
+        "Extracted {extracted} font{} and indexed {}",
+        if extracted == 1 { "" } else { "s" },
+        args.to.join(github::DEFAULT_INDEX_PATH).display()
+    );
+    Ok(())
+}
+
+/// Copy the system fonts of the families matching `args.family` into a library
+fn adopt_system_fonts(args: &AdoptCommand) -> Result<(), Error> {
+    let query = search::LibraryQuery::new(&args.family, None, &[], None).map_err(Error::Usage)?;
+    let entries = create_font_entries_from_dirs(&LibraryDirs::Local(system_font_dirs()))?;
+    let adoptions = adopt::plan_adoptions(&entries, |entry| query.matches(entry), &args.to);
+    if adoptions.is_empty() {
+        println!(
+            "No system font outside {} matches {:?}",
+            args.to.display(),
+            args.family
+        );
+        return Ok(());
+    }
+
+    let mut adopted = 0;
+    for adoption in &adoptions {
+        let target = args.to.join(&adoption.target);
+        if target.exists() {
+            println!("  = {} already exists", target.display());
+            continue;
+        }
+        if args.dry_run {
+            println!(
+                "  Would copy {} to {}",
+                adoption.source.display(),
+                target.display()
+            );
+            continue;
+        }
+        let dir = target.parent().unwrap();
+        fs::create_dir_all(dir).map_err(|source| Error::Write {
+            path: dir.to_path_buf(),
+            source,
+        })?;
+        fs::copy(adoption.source, &target).map_err(|source| Error::Write {
+            path: target.clone(),
+            source,
+        })?;
+        println!("  {} {}", "+".green(), target.display());
+        adopted += 1;
+    }
+    adopt::print_license_warnings(&adoptions);
+
+    if adopted == 0 {
+        return Ok(());
+    }
+    let entries = create_font_entries_from_dirs(&LibraryDirs::Local(vec![args.to.clone()]))?;
+    write_library_index(entries, &args.to.join(github::DEFAULT_INDEX_PATH))?;
+    println!(
+        "Adopted {adopted} font file{} and indexed {}",
+        if adopted == 1 { "" } else { "s" },
+        args.to.join(github::DEFAULT_INDEX_PATH).display()
+    );
+    Ok(())
+}
+
+/// Install the project fonts whose hash no longer matches the lockfile again,
+/// from the library files the lockfile records for them
+fn repair_fonts(args: &RepairCommand) -> Result<(), Error> {
+    args.font.validate().map_err(Error::Usage)?;
+    let config_file = font_manager::FontManager::resolve_config_file(&args.font.project_or_config);
+    let font_dir =
+        font_manager::FontManager::project_font_dir(&config_file).map_err(Error::Project)?;
+    let lock_path = lockfile_path(&config_file);
+    let lock = FontLock::load(&lock_path)
+        .map_err(Error::Project)?
+        .ok_or_else(|| {
+            Error::Project(format!(
+                "repair requires a lockfile, but {lock_path:?} doesn't exist (run update first)"
+            ))
+        })?;
+
+    let damaged = repair::damaged_files(&lock, &font_dir, args.hash_algo);
+    if damaged.is_empty() {
+        println!(
+            "All {} installed files match {}",
+            lock.files.len(),
+            lock_path.display()
+        );
+        return Ok(());
+    }
+    let shown_dir = portable_path(
+        font_dir
+            .strip_prefix(config_file.parent().unwrap_or(Path::new("")))
+            .unwrap_or(&font_dir),
+    );
+    println!(
+        "{} of {} installed files don't match {}:",
+        damaged.len(),
+        lock.files.len(),
+        lock_path.display()
+    );
+    repair::print_damaged_files(&damaged, &shown_dir);
+    if args.dry_run {
+        return Ok(());
+    }
+
+    let _font_dir_lock = file_lock::lock_dir(&font_dir).map_err(Error::Update)?;
+    let workspace = workspace::Workspace::find(&config_file).map_err(Error::Project)?;
+    for (file, _) in &damaged {
+        repair::remove_damaged_file(file, &font_dir, workspace.as_ref()).map_err(Error::Update)?;
+    }
+
+    // The removed files are missing now; install them as recorded
+    let sources = damaged
+        .iter()
+        .map(|(file, _)| file.source.clone())
+        .collect::<BTreeSet<_>>();
+    let options = UpdateOptions {
+        dry_run: false,
+        converter: Some(&args.converter),
+        strip: damaged
+            .iter()
+            .any(|(file, _)| file.original_sha256.is_some()),
+        limit_rate: args.limit_rate,
+        diff: false,
+        locked: true,
+        only_sources: Some(&sources),
+        quarantine: false,
+        hash_algo: args.hash_algo,
+    };
+    font_manager::FontManager::new(&args.font, &t!("action-repairing"))?
+        .update_fonts(&options)
+        .map_err(Error::Update)?;
+
+    let still_damaged = repair::damaged_files(&lock, &font_dir, args.hash_algo);
+    for (file, _) in &damaged {
+        if !still_damaged
+            .iter()
+            .any(|(other, _)| other.file == file.file)
+        {
+            println!(
+                "  {} Repaired {shown_dir}/{} from {}",
+                "✓".green(),
+                file.file,
+                file.source
+            );
+        }
+    }
+    if !still_damaged.is_empty() {
+        repair::print_damaged_files(&still_damaged, &shown_dir);
+        return Err(Error::Update(format!(
+            "{} files still don't match {}",
+            still_damaged.len(),
+            lock_path.display()
+        )));
+    }
+    Ok(())
+}
+
+fn compare_fonts(args: &CompareCommand) -> Result<(), Error> {
+    let library_dirs = LibraryDirs::new(args.library.library.as_deref(), args.library.github)
+        .map_err(Error::Usage)?;
+    // The library is only read if one of the fonts isn't a file
+    let entries = if Path::new(&args.a).is_file() && Path::new(&args.b).is_file() {
+        Vec::new()
+    } else {
+        create_font_entries_from_dirs(&library_dirs)?
+    };
+    let style = args.style.font_style();
+    let resolve = |spec| {
+        compare::resolve_face(spec, &library_dirs, &entries, style, args.weight)
+            .map_err(Error::Usage)
+    };
+    let (a, b) = (resolve(&args.a)?, resolve(&args.b)?);
+    compare::print_comparison(&a, &b);
+    Ok(())
+}
+
+/// Suggest replacements for the required families that no library source has.
+/// Their class is read from the system's copy of the family if there is one.
+fn suggest_replacements(args: &SuggestCommand) -> Result<(), Error> {
+    args.font.validate().map_err(Error::Usage)?;
+    let action = t!("action-checking");
+    let font_manager = font_manager::FontManager::new(&args.font, &action)?;
+    let unresolvable = font_manager.unresolvable_fonts();
+    if unresolvable.is_empty() {
+        println!("Every required font is installed or available in the library");
+        return Ok(());
+    }
+
+    // Without --library, the system fonts are the library and can't tell more
+    let system_entries = if args.font.library.is_some() {
+        create_font_entries_from_dirs(&LibraryDirs::Local(system_font_dirs()))?
+    } else {
+        Vec::new()
+    };
+    for (family, missing) in unresolvable {
+        let reference = system_entries
+            .iter()
+            .filter(|entry| entry.font.family_name.eq_ignore_ascii_case(family))
+            .collect::<Vec<_>>();
+        let profile = suggest::Profile::of(family, &reference);
+        let suggestions = suggest::suggest_families(
+            family,
+            profile,
+            &missing,
+            font_manager.library_entries(),
+            args.limit,
+        );
+        suggest::print_suggestions(family, profile, &missing, &suggestions);
+    }
+    Ok(())
+}
+
+fn show_history(args: &HistoryCommand) -> Result<(), Error> {
+    let config_file = font_manager::FontManager::resolve_config_file(&args.project_or_config);
+    let entries = journal::read_journal(&journal::journal_path(&config_file))?;
+    match &args.action {
+        None => journal::print_history(&entries),
+        Some(HistoryAction::Show { id }) => {
+            let entry = entries
+                .iter()
+                .find(|entry| entry.id == *id)
+                .ok_or_else(|| Error::Usage(format!("No run {id} in the project journal")))?;
+            journal::print_run(entry);
+        }
+        Some(HistoryAction::Rollback {
+            id,
+            library,
+            dry_run,
+            converter,
+        }) => {
+            let rollback = journal::plan_rollback(&entries, *id)
+                .ok_or_else(|| Error::Usage(format!("No run {id} in the project journal")))?;
+            let repair = RepairCommand {
+                font: FontCommand {
+                    library: library.library.clone(),
+                    github: library.github,
+                    ..FontCommand::project(config_file)
+                },
+                dry_run: *dry_run,
+                converter: converter.clone(),
+                limit_rate: None,
+                hash_algo: HashAlgo::Sha256,
+            };
+            roll_back(*id, rollback, &repair)?;
+        }
+    }
+    Ok(())
+}
+
+/// `history rollback <id>`: remove the files installed after the run, record the
+/// files of the run in the lockfile again, and then repair the font directory
+/// against it, which installs them from their recorded sources
+fn roll_back(id: u64, rollback: journal::Rollback, repair: &RepairCommand) -> Result<(), Error> {
+    repair.font.validate().map_err(Error::Usage)?;
+    let config_file = &repair.font.project_or_config;
+    let font_dir =
+        font_manager::FontManager::project_font_dir(config_file).map_err(Error::Project)?;
+    let lock_path = lockfile_path(config_file);
+    let mut lock = FontLock::load(&lock_path)
+        .map_err(Error::Project)?
+        .unwrap_or_default();
+
+    println!("{}", format!("Rolling back to run {id}:").bold());
+    for file in &rollback.removed {
+        println!("  {} {file}", "-".red());
+    }
+    for file in &rollback.restored {
+        let recorded = lock.files.iter().find(|other| other.file == file.file);
+        if recorded.is_none_or(|recorded| recorded.sha256 != file.sha256) {
+            println!("  {} {} from {}", "+".green(), file.file, file.source);
+        }
+    }
+    if repair.dry_run {
+        return Ok(());
+    }
+
+    {
+        let _font_dir_lock = file_lock::lock_dir(&font_dir).map_err(Error::Update)?;
+        let workspace = workspace::Workspace::find(config_file).map_err(Error::Project)?;
+        for name in &rollback.removed {
+            if let Some(file) = lock.files.iter().find(|file| file.file == *name) {
+                repair::remove_damaged_file(file, &font_dir, workspace.as_ref())
+                    .map_err(Error::Update)?;
+            }
+        }
+        lock.files
+            .retain(|file| !rollback.removed.contains(&file.file));
+        for file in rollback.restored {
+            lock.set_file(file);
+        }
+        for source in rollback.sources {
+            lock.set_source(source);
+        }
+        lock.save(&lock_path).map_err(Error::Update)?;
+    }
+    repair_fonts(repair)
+}
+
+/// Re-check the project whenever the font status may have changed. Local
+/// libraries are scanned once and then updated from file system events; with
+/// `--poll` or GitHub libraries, everything is read again every `args.interval`
+/// seconds.
+fn watch_fonts(args: &CheckCommand, report_filter: ReportFilter) -> Result<(), Error> {
+    args.font.validate().map_err(Error::Usage)?;
+    let status_server = match args.status_port {
+        Some(port) => {
+            let config_file =
+                font_manager::FontManager::resolve_config_file(&args.font.project_or_config);
+            let server = status_server::StatusServer::start(port, portable_path(&config_file))
+                .map_err(|e| {
+                    Error::Other(format!("Failed to serve the status on port {port}: {e}"))
+                })?;
+            println!(
+                "Serving the font status at {}/status and /metrics",
+                server.url()
+            );
+            Some(server)
+        }
+        None => None,
+    };
+    let status_server = status_server.as_ref();
+    match font_manager::FontManager::library_dirs(&args.font).map_err(Error::Usage)? {
+        LibraryDirs::Local(library_dirs) if !args.poll => {
+            watch_fonts_natively(args, report_filter, library_dirs, status_server)
+        }
+        _ => poll_fonts(args, report_filter, status_server),
+    }
+}
+
+fn poll_fonts(
+    args: &CheckCommand,
+    report_filter: ReportFilter,
+    status_server: Option<&status_server::StatusServer>,
+) -> Result<(), Error> {
+    let action = t!("action-checking");
+    let mut last_status = None;
+    loop {
+        let font_manager = font_manager::FontManager::new(&args.font, &action);
+        report_status_change(
+            args,
+            &report_filter,
+            font_manager,
+            &mut last_status,
+            status_server,
+        );
+        std::thread::sleep(std::time::Duration::from_secs(args.interval));
+    }
+}
+
+/// Watch mode with file system notifications. The project is checked again
+/// on every change and at least every `args.interval` seconds, as its font
+/// directory may not exist yet when watching starts.
+fn watch_fonts_natively(
+    args: &CheckCommand,
+    report_filter: ReportFilter,
+    library_dirs: Vec<PathBuf>,
+    status_server: Option<&status_server::StatusServer>,
+) -> Result<(), Error> {
+    let config_file = font_manager::FontManager::resolve_config_file(&args.font.project_or_config);
+    let project_dir = match config_file.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let mut watched = library_dirs.clone();
+    watched.extend(font_manager::FontManager::project_font_dir(&config_file).ok());
+    let watcher = match watch::FsWatcher::new(&watched, &[project_dir]) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            println!(
+                "{} {e}, scanning periodically instead",
+                t!("warning").yellow().bold()
+            );
+            return poll_fonts(args, report_filter, status_server);
+        }
+    };
+
+    let action = t!("action-checking");
+    let mut index = watch::LibraryIndex::scan(&library_dirs)?;
+    let mut last_status = None;
+    loop {
+        let font_manager = font_manager::FontManager::with_library_entries(
+            &args.font,
+            &action,
+            LibraryDirs::Local(library_dirs.clone()),
+            index.entries(),
+        )
+        .map_err(Error::Project);
+        report_status_change(
+            args,
+            &report_filter,
+            font_manager,
+            &mut last_status,
+            status_server,
+        );
+
+        let changes = watcher
+            .wait(std::time::Duration::from_secs(args.interval))
+            .map_err(Error::Other)?;
+        if changes.rescan {
+            index = watch::LibraryIndex::scan(&library_dirs)?;
+            continue;
+        }
+        for path in changes.paths {
+            if let Some(path) = index.library_path(&path) {
+                index.refresh(&path);
+            }
+        }
+    }
+}
+
+/// Report the status of a watched project if its counts or the families of its
+/// problematic fonts changed since `last_status`
+fn report_status_change(
+    args: &CheckCommand,
+    report_filter: &ReportFilter,
+    font_manager: Result<font_manager::FontManager, Error>,
+    last_status: &mut Option<(font_manager::StatusSummary, font_manager::StatusFamilies)>,
+    status_server: Option<&status_server::StatusServer>,
+) {
+    if let Some(server) = status_server {
+        server.record(font_manager.as_ref().map_err(ToString::to_string));
+    }
+    match font_manager {
+        Ok(font_manager) => {
+            let summary = font_manager.summary();
+            let status = (summary, font_manager.status_families());
+            if last_status.as_ref() == Some(&status) {
+                return;
+            }
+            if args.summary_only {
+                println!("{summary}");
+            } else {
+                font_manager
+                    .with_report_filter(report_filter.clone())
+                    .print_status();
+            }
+
+            if args.notify
+                && last_status.is_some()
+                && let Err(e) = notify::send_desktop_notification(
+                    "Typst font status changed",
+                    &summary.to_string(),
+                )
+            {
+                println!("{e}");
+            }
+            *last_status = Some(status);
+        }
+        // Keep watching; the project may be in the middle of an edit
+        Err(e) => println!("{}", t!("error-init", error = e.to_string())),
+    }
+}
+
+fn import_typst_fonts(args: &ImportTypstFontsCommand) -> Result<(), Error> {
+    let listing = typst_fonts::read_typst_fonts_listing(args.input.as_deref(), &args.typst)?;
+    let fonts = typst_fonts::parse_typst_fonts_listing(&listing)?;
+    eprintln!(
+        "Imported {} font variants from the Typst listing",
+        fonts.len()
+    );
+
+    let toml = match args.kind {
+        ImportKind::Config => typst_fonts::to_config_toml(&fonts),
+        ImportKind::Library => {
+            let library_dirs = args.library.clone().unwrap_or_else(system_font_dirs);
+            let library_entries =
+                create_font_entries_from_dirs(&LibraryDirs::Local(library_dirs.clone()))?;
+
+            let mut matched = Vec::new();
+            for font in &fonts {
+                let entries = library_entries
+                    .iter()
+                    .filter(|entry| entry.font == *font)
+                    .collect::<Vec<_>>();
+                if entries.is_empty() {
+                    eprintln!("  No file found for {font}");
                 }
+                matched.extend(entries.into_iter().cloned());
             }
 
-            println!("\n=== Done ===");
+            // For the output toml file, strip the library root paths
+            for library_dir in &library_dirs {
+                strip_font_entry_root_paths(&mut matched, library_dir);
+            }
+
+            FontLibraryExport::from(matched)
+                .to_toml_string()
+                .map_err(Error::Other)?
+        }
+    };
+
+    match &args.output {
+        Some(output) => fs::write(output, toml.as_bytes()).map_err(|source| Error::Write {
+            path: output.clone(),
+            source,
+        }),
+        None => {
+            print!("{toml}");
+            Ok(())
         }
-        Err(e) => println!("Error initializing font manager: {e}"),
     }
 }
 
-fn main() {
-    #[cfg(debug_assertions)]
+fn library_font_entries(args: &LibraryArgs) -> Result<Vec<DiscoveredFont>, Error> {
+    let library_dirs =
+        LibraryDirs::new(args.library.as_deref(), args.github).map_err(Error::Usage)?;
+    create_font_entries_from_dirs(&library_dirs)
+}
+
+/// The report of `check-lib` with `--format json` or `--format toml`
+#[derive(serde::Serialize)]
+struct LibraryReport<'a> {
+    library_dirs: Vec<String>,
+    fonts: Vec<font_manager::ReportedFace<'a>>,
+    /// Type 1 fonts, which Typst can't use
+    legacy: Vec<String>,
+}
+
+fn check_lib(args: &CheckLibCommand) -> Result<(), Error> {
+    MACHINE_OUTPUT.store(args.format != ReportFormat::Plain, Ordering::Relaxed);
+    let library_dirs =
+        LibraryDirs::new(args.library.as_deref(), args.github).map_err(Error::Usage)?;
+    if args.validate {
+        return validate_libraries(&library_dirs, args.range_prefix * 1024);
+    }
+
+    let legacy_fonts = match &library_dirs {
+        LibraryDirs::Local(font_dirs) => find_legacy_fonts(font_dirs),
+        LibraryDirs::GitHub(_) => Vec::new(),
+    };
+    if args.convert_legacy {
+        convert_type1_fonts(&legacy_fonts, &args.converter);
+    }
+
+    // A new index of a GitHub library is built from its font files, not its current index
+    let font_entries = match &library_dirs {
+        LibraryDirs::GitHub(github_repos) if args.output.is_some() => {
+            let mut fonts = Vec::new();
+            for github_repo in github_repos {
+                fonts.extend(
+                    remote_font::index_github_library(github_repo, args.range_prefix * 1024)
+                        .map_err(|e| Error::Library(format!("{}: {e}", github_repo.display())))?,
+                );
+            }
+            fonts
+        }
+        _ => create_font_entries_from_dirs(&library_dirs)?,
+    };
+
+    let report = LibraryReport {
+        library_dirs: library_dirs
+            .into_iter()
+            .map(|dir| portable_path(dir))
+            .collect(),
+        fonts: font_entries.iter().map(Into::into).collect(),
+        legacy: legacy_fonts
+            .iter()
+            .map(|(path, _)| portable_path(path))
+            .collect(),
+    };
+    if let Some(document) =
+        font_manager::render_report(&report, args.format).map_err(Error::Other)?
     {
-        use colored::Colorize;
-        println!("{}", "Dev Version".bold().red());
+        print!("{document}");
+    } else {
+        println!("\n=== Font Library ===\n");
+
+        println!("\n- Font library directories:");
+        for dir in &library_dirs {
+            println!("  {dir:?}");
+        }
+        println!("\n- Font Info:");
+
+        print_font_variants(&font_entries);
+        print_legacy_fonts(&legacy_fonts);
     }
 
-    let cli = Cli::parse();
-    match &cli.command {
-        Commands::Check(args) => process_command(args, "Checking", false),
-        Commands::Update(args) => process_command(&args.font, "Updating", args.dry_run),
-        Commands::CheckLib(args) => {
-            let library_dirs = if args.github {
-                LibraryDirs::GitHub(args.library.clone().unwrap())
+    if let Some(output_dir_arg) = &args.output {
+        match library_dirs {
+            LibraryDirs::GitHub(github_repos) => {
+                if github_repos.len() > 1 {
+                    return Err(Error::Usage(
+                        "If output directory is provided, there should be only one GitHub repository."
+                            .to_string(),
+                    ));
+                }
+
+                // Name the file like the index it replaces, e.g. cjk.toml for owner/repo::indexes/cjk.toml
+                let source = github::GitHubSource::parse(&github_repos[0]).map_err(Error::Usage)?;
+                let file_name = Path::new(source.index_path)
+                    .file_name()
+                    .unwrap_or(github::DEFAULT_INDEX_PATH.as_ref());
+                let output_dir = output_dir_arg.clone().unwrap_or_else(|| PathBuf::from("."));
+                guard_published_index(&font_entries, &output_dir, args.redistribution)?;
+
+                for (file_name, entries) in
+                    group_index(font_entries, file_name.as_ref(), args.group_by)
+                {
+                    let toml = FontLibraryExport::from(entries)
+                        .to_toml_string()
+                        .map_err(Error::Other)?;
+                    let file_path = output_dir.join(file_name);
+                    fs::write(&file_path, toml.as_bytes()).map_err(|source| Error::Write {
+                        path: file_path,
+                        source,
+                    })?;
+                }
+            }
+            LibraryDirs::Local(library_dirs) => {
+                if library_dirs.len() > 1 {
+                    return Err(Error::Usage(
+                        "If output directory is provided, there should be only one library directory."
+                            .to_string(),
+                    ));
+                }
+
+                // if output_dir is provided, write the font library info to the output directory
+                // otherwise, write to the library_dirs[0]
+                let output_dir = match &output_dir_arg {
+                    Some(dir) => dir.clone(),
+                    None => library_dirs[0].clone(),
+                };
+                guard_published_index(&font_entries, &output_dir, args.redistribution)?;
+                let groups = group_index(
+                    font_entries,
+                    github::DEFAULT_INDEX_PATH.as_ref(),
+                    args.group_by,
+                );
+                for (file_name, entries) in groups {
+                    write_library_index(entries, &output_dir.join(file_name))?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Apply the redistribution guard to the fonts of an index about to be written
+/// into `output_dir`, if that is tracked by git
+fn guard_published_index(
+    entries: &[DiscoveredFont],
+    output_dir: &Path,
+    policy: RedistributionPolicy,
+) -> Result<(), Error> {
+    if policy == RedistributionPolicy::Allow || !redistribution::is_git_tracked(output_dir) {
+        return Ok(());
+    }
+    let restricted = entries
+        .iter()
+        .filter_map(|entry| {
+            let reason = redistribution::restriction(&entry.metadata)?;
+            Some((portable_path(&entry.path), reason))
+        })
+        .collect::<BTreeSet<_>>();
+    let restricted = restricted.into_iter().collect::<Vec<_>>();
+    redistribution::guard(policy, &restricted, output_dir).map_err(Error::Other)
+}
+
+/// Split `entries` into the index files of `check-lib --group-by`, keyed by
+/// their file name: `file_name` with the group inserted before the extension,
+/// e.g. `font_library.ofl.toml`. Without grouping, all go into `file_name`.
+fn group_index(
+    entries: Vec<DiscoveredFont>,
+    file_name: &Path,
+    group_by: Option<IndexGroup>,
+) -> BTreeMap<PathBuf, Vec<DiscoveredFont>> {
+    let mut groups = BTreeMap::<PathBuf, Vec<DiscoveredFont>>::new();
+    for entry in entries {
+        let file_name = match group_by {
+            None => file_name.to_path_buf(),
+            Some(group_by) => {
+                let group = match group_by {
+                    IndexGroup::License => entry
+                        .metadata
+                        .license
+                        .map_or("unknown", FontLicense::name)
+                        .to_string(),
+                    IndexGroup::Foundry => file_name_slug(search::foundry_name(&entry.metadata)),
+                    IndexGroup::Family => file_name_slug(&entry.font.family_name),
+                };
+                let stem = file_name.file_stem().unwrap_or_default().to_string_lossy();
+                PathBuf::from(format!("{stem}.{group}.toml"))
+            }
+        };
+        groups.entry(file_name).or_default().push(entry);
+    }
+    groups
+}
+
+/// `name` in lower case with runs of other characters than letters and digits
+/// replaced by a dash, e.g. "adobe-systems" for "Adobe Systems, Inc."
+fn file_name_slug(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Write the local fonts `entries` into the library index at `file_path`
+fn write_library_index(mut entries: Vec<DiscoveredFont>, file_path: &Path) -> Result<(), Error> {
+    let output_dir = file_path.parent().unwrap_or(Path::new("."));
+    // In the output toml file, paths are relative to the file
+    for entry in &mut entries {
+        if let Some(path) = relative_path(&entry.path, output_dir) {
+            entry.path = path;
+        }
+    }
+
+    let library = FontLibraryExport::from(entries);
+    // Serialize to TOML and write to the target directory
+    let toml = library.to_toml_string().map_err(Error::Other)?;
+
+    fs::write(file_path, toml.as_bytes()).map_err(|source| Error::Write {
+        path: file_path.to_path_buf(),
+        source,
+    })
+}
+
+/// Validate the published index of every library, see `check-lib --validate`
+fn validate_libraries(library_dirs: &LibraryDirs, prefix_len: usize) -> Result<(), Error> {
+    println!("\n=== Font Library Validation ===");
+
+    let mut problems = 0;
+    for dir in library_dirs {
+        let report = match library_dirs {
+            LibraryDirs::Local(_) => validate::validate_local_library(dir),
+            LibraryDirs::GitHub(_) => validate::validate_github_library(dir, prefix_len),
+        }
+        .map_err(Error::Library)?;
+        report.print();
+        problems += report.findings.len();
+    }
+
+    if problems > 0 {
+        return Err(Error::InvalidLibrary(format!(
+            "{problems} {} found in the font library index",
+            if problems == 1 { "problem" } else { "problems" }
+        )));
+    }
+    Ok(())
+}
+
+/// `lint-lib`: report the naming problems of the files of every library
+fn lint_libraries(args: &LintLibCommand) -> Result<(), Error> {
+    let library_dirs = LibraryDirs::new(args.library.library.as_deref(), args.library.github)
+        .map_err(Error::Usage)?;
+    println!("\n=== Font Library Lint ===");
+
+    let mut libraries = Vec::new();
+    for dir in &library_dirs {
+        let faces = match library_dirs {
+            LibraryDirs::Local(_) => lint::read_local_faces(dir),
+            LibraryDirs::GitHub(_) => lint::read_github_faces(dir, args.range_prefix * 1024)
+                .map_err(|e| Error::Library(format!("{}: {e}", dir.display())))?,
+        };
+        let findings = lint::lint_faces(&faces);
+        let library = dir.display().to_string();
+        lint::print_findings(&library, faces.len(), &findings);
+        libraries.push((library, findings));
+    }
+
+    if let Some(report) = &args.report {
+        fs::write(report, lint::render_report(&libraries)).map_err(|source| Error::Write {
+            path: report.clone(),
+            source,
+        })?;
+        println!("\nWrote the report to {}", report.display());
+    }
+    let problems = libraries
+        .iter()
+        .map(|(_, findings)| findings.len())
+        .sum::<usize>();
+    if problems > 0 {
+        return Err(Error::InvalidLibrary(format!(
+            "{problems} naming {} found in the font library",
+            if problems == 1 { "problem" } else { "problems" }
+        )));
+    }
+    Ok(())
+}
+
+/// Parse the command line again with the `default_command` of the user settings
+/// appended, so that global options given before it still apply. Without one,
+/// print the help as clap does for a missing subcommand.
+fn parse_with_default_command() -> Result<Cli, Error> {
+    let usage_error = |message: String| -> ! {
+        exit_with_usage_error(Cli::command().error(clap::error::ErrorKind::InvalidValue, message))
+    };
+    user_config::init().map_err(Error::Project)?;
+    let Some(default_command) = &user_config::get().default_command else {
+        eprint!("{}", Cli::command().render_help());
+        std::process::exit(Error::USAGE_EXIT_CODE.into());
+    };
+    let words = convert::split_command_line(default_command)
+        .unwrap_or_else(|e| usage_error(format!("Invalid default_command: {e}")));
+    let cli = Cli::try_parse_from(std::env::args_os().chain(words.into_iter().map(Into::into)))
+        .unwrap_or_else(|e| exit_with_usage_error(e));
+    if cli.command.is_none() {
+        usage_error(format!(
+            "default_command {default_command:?} doesn't name a subcommand"
+        ));
+    }
+    Ok(cli)
+}
+
+/// Print a command-line error as clap does, but exit with the code of
+/// [`Error::Usage`]: clap's own code 2 is that of unavailable fonts in `check`
+fn exit_with_usage_error(error: clap::Error) -> ! {
+    let _ = error.print();
+    if error.use_stderr() {
+        std::process::exit(Error::USAGE_EXIT_CODE.into());
+    }
+    // `--help` and `--version`
+    std::process::exit(0);
+}
+
+fn run(cli: &Cli) -> Result<(), Error> {
+    user_config::init().map_err(Error::Project)?;
+    if let Some(server) = &cli.mock_server {
+        start_mock_server(server).map_err(Error::Other)?;
+    }
+
+    let command = cli
+        .command
+        .as_ref()
+        .expect("a default command is parsed when none is given");
+    match command {
+        Commands::Check(args) => {
+            let sets = if args.only_missing {
+                ReportSets::Missing
+            } else if args.only_redundant {
+                ReportSets::Redundant
             } else {
-                LibraryDirs::Local(match &args.library {
-                    Some(dirs) => dirs.clone(),
-                    None => utils::font_utils::get_system_font_directories(),
-                })
+                ReportSets::All
             };
-            let font_entries = create_font_entries_from_dirs(&library_dirs);
-
-            println!("\n=== Font Library ===\n");
-
-            println!("\n- Font library directories:");
-            for dir in &library_dirs {
-                println!("  {dir:?}");
-            }
-            println!("\n- Font Info:");
-
-            print_font_variants(&font_entries);
-
-            if let Some(output_dir_arg) = &args.output {
-                match library_dirs {
-                    LibraryDirs::GitHub(_) => {}
-                    LibraryDirs::Local(library_dirs) => {
-                        // if length of library_dirs is greater than 1, print an error message
-                        if library_dirs.len() > 1 {
-                            println!(
-                                "Error: If output directory is provided, there should be only one library directory."
-                            );
-                            return;
-                        }
-
-                        // if output_dir is provided, write the font library info to the output directory
-                        // otherwise, write to the library_dirs[0]
-                        let output_dir = match &output_dir_arg {
-                            Some(dir) => dir.clone(),
-                            None => library_dirs[0].clone(),
-                        };
-
-                        let mut output_entries = font_entries.clone();
-                        // For the output toml file, strip the library root path
-                        strip_font_entry_root_paths(&mut output_entries, &output_dir);
-
-                        let library = FontLibraryExport::from(output_entries);
-                        // Serialize to TOML and write to the target directory
-                        let toml = library.to_toml_string();
-
-                        // Define the file path in target/test_outputs
-                        let file_path = output_dir.join("font_library.toml");
-                        fs::write(&file_path, toml.as_bytes()).expect("Failed to write to file");
-                    }
-                }
+            let report_filter =
+                ReportFilter::new(sets, args.family.as_deref()).map_err(Error::Usage)?;
+            if args.watch {
+                watch_fonts(args, report_filter)
+            } else if !args.configs.is_empty() {
+                check_configs(args, report_filter)
+            } else if args.summary_only {
+                print_summary(&args.font, args.strict, args.notify_webhook.as_deref())
+            } else {
+                process_command(
+                    &args.font,
+                    &t!("action-checking"),
+                    report_filter,
+                    None,
+                    Some(args),
+                    args.format,
+                )
+            }
+        }
+        Commands::Status(args) => print_summary(&args.font, args.strict, None),
+        Commands::Update(args) => {
+            let options = UpdateOptions {
+                dry_run: args.dry_run,
+                converter: args.convert.then_some(args.converter.as_str()),
+                strip: args.strip,
+                limit_rate: args.limit_rate,
+                diff: args.diff,
+                locked: args.locked || args.frozen,
+                only_sources: None,
+                quarantine: args.quarantine,
+                hash_algo: args.hash_algo,
+            };
+            if args.frozen {
+                http::set_offline();
+            }
+            // Before the update, which is pointless without it
+            let token = args
+                .pr
+                .then(pull_request::token)
+                .transpose()
+                .map_err(Error::Usage)?;
+            process_command(
+                &args.font,
+                &t!("action-updating"),
+                ReportFilter::default(),
+                Some(&options),
+                None,
+                args.format,
+            )?;
+            match token {
+                Some(token) => open_pull_request(&args.font, &token),
+                None => Ok(()),
+            }
+        }
+        Commands::ImportTypstFonts(args) => import_typst_fonts(args),
+        Commands::Search(args) => {
+            let mut features = args.feature.clone();
+            if args.monospace {
+                features.push(FontFeature::Monospace);
             }
+            let query = search::LibraryQuery::new(
+                &args.query,
+                args.foundry.as_deref(),
+                &features,
+                args.class,
+            )
+            .map_err(Error::Usage)?;
+            let entries = library_font_entries(&args.library)?;
+            let mut matched = entries
+                .iter()
+                .filter(|entry| query.matches(entry))
+                .collect::<Vec<_>>();
+            if let Some(min_weights) = args.min_weights {
+                matched = search::with_min_weights(matched, min_weights);
+            }
+            search::print_search_results(matched);
+            Ok(())
+        }
+        Commands::Info(args) => {
+            let entries = library_font_entries(&args.library)?;
+            search::print_family_info(&args.family, &entries);
+            Ok(())
+        }
+        Commands::Stats(args) => {
+            let entries = library_font_entries(args)?;
+            search::print_library_stats(&entries);
+            Ok(())
+        }
+        Commands::Bench(args) => {
+            println!("{}", bench::run_bench(args)?);
+            Ok(())
+        }
+        Commands::CheckLib(args) => check_lib(args),
+        Commands::LintLib(args) => lint_libraries(args),
+        Commands::History(args) => show_history(args),
+        Commands::Sbom(args) => write_sbom(args),
+        Commands::Why(args) => {
+            args.font.validate().map_err(Error::Usage)?;
+            let style = args.style.map(StyleArg::font_style);
+            font_manager::FontManager::new(&args.font, &t!("action-checking"))?.explain_choice(
+                &args.family,
+                style,
+                args.weight,
+            );
+            Ok(())
+        }
+        Commands::Tree(args) => {
+            args.validate().map_err(Error::Usage)?;
+            font_manager::FontManager::new(args, &t!("action-checking"))?.print_requirement_tree();
+            Ok(())
+        }
+        Commands::Repair(args) => repair_fonts(args),
+        #[cfg(feature = "test-fixtures")]
+        Commands::Demo(args) => demo::run_demo(args),
+        Commands::Add(args) => add_font(args),
+        Commands::Remove(args) => remove_font(args),
+        Commands::Scan(args) => scan_fonts(args),
+        Commands::Promote(args) => promote_fonts(args),
+        Commands::New(args) => new_project(args),
+        Commands::Badge(args) => write_badge(args),
+        Commands::ExportLayer(args) => export_layer(args),
+        Commands::Compare(args) => compare_fonts(args),
+        Commands::Suggest(args) => suggest_replacements(args),
+        Commands::Report(args) => report_fonts(args),
+        Commands::Extract(args) => extract_pdf_fonts(args),
+        Commands::Adopt(args) => adopt_system_fonts(args),
+        Commands::SelfManagement(args) => match args.action {
+            SelfAction::Update { check } => self_update::self_update(check).map_err(Error::Other),
+        },
+        Commands::Store(args) => match &args.action {
+            StoreAction::Gc { workspace, dry_run } => {
+                workspace::collect_garbage(workspace, *dry_run).map_err(Error::Other)
+            }
+            StoreAction::Verify {
+                workspace,
+                hash_algo,
+            } => workspace::verify_store(workspace, *hash_algo).map_err(Error::Other),
+        },
+        Commands::Cache(args) => {
+            match args.action {
+                CacheAction::Status => cache::print_status(),
+                CacheAction::Stats => cache::print_stats(),
+                CacheAction::Clear => cache::clear().map_err(Error::Other)?,
+                CacheAction::Gc {
+                    max_size,
+                    max_age,
+                    dry_run,
+                } => cache::collect_garbage(max_size, max_age, dry_run).map_err(Error::Other)?,
+            }
+            Ok(())
+        }
+    }
+}
+
+/// `--mock-server`: use the server at a URL, or start one serving a directory
+fn start_mock_server(server: &str) -> Result<(), String> {
+    let url = if server.starts_with("http://") || server.starts_with("https://") {
+        server.to_string()
+    } else {
+        let root = Path::new(server);
+        if !root.is_dir() {
+            return Err(format!("Mock server fixtures {root:?} are not a directory"));
+        }
+        let mock = mock_server::MockServer::start(root)
+            .map_err(|e| format!("Failed to start the mock server: {e}"))?;
+        let url = mock.url();
+        mock.detach();
+        url
+    };
+    if verbose() {
+        println!("Sending GitHub requests to {url}");
+    }
+    github::use_mock_server(&url);
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    #[cfg(debug_assertions)]
+    {
+        eprintln!("{}", "Dev Version".bold().red());
+    }
+
+    let cli = match Cli::try_parse() {
+        Ok(Cli { command: None, .. }) => match parse_with_default_command() {
+            Ok(cli) => cli,
+            Err(e) => {
+                eprintln!("{} {e}", t!("error").red().bold());
+                return e.exit_code();
+            }
+        },
+        Ok(cli) => cli,
+        Err(e) => exit_with_usage_error(e),
+    };
+    VERBOSE.store(cli.verbose, Ordering::Relaxed);
+    FOLLOW_SYMLINKS.store(!cli.no_follow_symlinks, Ordering::Relaxed);
+    INFER_WEIGHTS.store(cli.infer_weights, Ordering::Relaxed);
+    NO_CACHE.store(cli.no_cache, Ordering::Relaxed);
+    if cli.no_wait {
+        file_lock::set_no_wait();
+    }
+    if let Some(token) = &cli.token {
+        github::set_token(token);
+    }
+    if cli.no_system_fonts {
+        SYSTEM_FONT_DIRS.set(None).unwrap();
+    } else if let Some(dirs) = &cli.system_dirs {
+        SYSTEM_FONT_DIRS.set(Some(dirs.clone())).unwrap();
+    }
+    i18n::set_language(cli.lang.unwrap_or_else(i18n::Lang::detect));
+    if cli.trace_out.is_some() {
+        trace::enable();
+    }
+
+    let result = {
+        let _span = trace::span("command", "typfont");
+        run(&cli)
+    };
+    if let Some(trace_out) = &cli.trace_out
+        && let Err(e) = trace::write(trace_out)
+    {
+        eprintln!("{} {e}", t!("warning").yellow().bold());
+    }
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{} {e}", t!("error").red().bold());
+            e.exit_code()
         }
     }
 }
 #[cfg(test)]
 mod tests {
+    use super::*;
     use crate::utils::font_utils::get_system_font_directories;
 
     #[test]
@@ -560,4 +2790,182 @@ mod tests {
             println!("{:?}", font_dir);
         }
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_deduplicates_symlinked_files() {
+        let Ok(font) = fs::read("/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf") else {
+            return;
+        };
+
+        let test_dir = PathBuf::from(
+            std::env::var("CARGO_TARGET_DIR").unwrap_or_else(|_| "target".to_string()),
+        )
+        .join("scan_deduplicates_symlinked_files");
+        fs::remove_dir_all(&test_dir).ok();
+        let real_dir = test_dir.join("real");
+        fs::create_dir_all(&real_dir).unwrap();
+        fs::write(real_dir.join("DejaVuSans.ttf"), font).unwrap();
+        std::os::unix::fs::symlink(&real_dir, test_dir.join("linked")).unwrap();
+        std::os::unix::fs::symlink(real_dir.join("DejaVuSans.ttf"), real_dir.join("Alias.ttf"))
+            .unwrap();
+
+        let fonts = create_font_entries(&test_dir);
+        assert_eq!(fonts.len(), 1);
+    }
+
+    #[test]
+    fn test_library_export_is_deterministic() {
+        let entry = |family: &str, stretch: u16, path: &str| DiscoveredFont {
+            font: TypstFont {
+                family_name: family.to_string(),
+                style: typst::text::FontStyle::Normal,
+                weight: FontWeight::REGULAR,
+                stretch: FontStretch::from_ratio(typst::layout::Ratio::new(
+                    (stretch as f64 + 0.5) / 1000.0,
+                )),
+            },
+            path: PathBuf::from(path),
+            index: 0,
+            axes: Vec::new(),
+            metadata: FontMetadata::default(),
+        };
+        let fonts = vec![
+            entry("Noto Sans", 1001, "noto/NotoSans-Wide.ttf"),
+            entry("noto sans", 1000, "noto/b.ttf"),
+            entry("Noto Sans", 1000, "noto/a.ttf"),
+            entry("Alegreya", 1000, "Alegreya.ttf"),
+        ];
+        let mut reversed = fonts.clone();
+        reversed.reverse();
+
+        let toml = FontLibraryExport::from(fonts).to_toml_string().unwrap();
+        assert_eq!(
+            toml,
+            FontLibraryExport::from(reversed).to_toml_string().unwrap()
+        );
+        let paths = toml
+            .lines()
+            .filter_map(|line| line.strip_prefix("path = "))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [
+                "\"Alegreya.ttf\"",
+                "\"noto/a.ttf\"",
+                "\"noto/NotoSans-Wide.ttf\"",
+                "\"noto/b.ttf\""
+            ]
+        );
+        assert!(toml.contains("stretch = 1001\n"));
+    }
+
+    #[test]
+    fn test_subcommand_aliases() {
+        let cli = Cli::parse_from(["typfont", "c", "--strict"]);
+        assert!(matches!(cli.command, Some(Commands::Check(args)) if args.strict));
+        let cli = Cli::parse_from(["typfont", "-v", "u", "--dry-run"]);
+        assert!(matches!(cli.command, Some(Commands::Update(args)) if args.dry_run));
+        let cli = Cli::parse_from(["typfont", "st", "--strict"]);
+        assert!(matches!(cli.command, Some(Commands::Status(args)) if args.strict));
+        assert!(Cli::parse_from(["typfont", "-v"]).command.is_none());
+    }
+
+    #[test]
+    fn test_system_font_flags() {
+        let cli = Cli::parse_from([
+            "typfont",
+            "check",
+            "--system-dirs",
+            "/opt/fonts",
+            "/srv/fonts",
+        ]);
+        assert_eq!(
+            cli.system_dirs,
+            Some(vec![
+                PathBuf::from("/opt/fonts"),
+                PathBuf::from("/srv/fonts")
+            ])
+        );
+        assert!(Cli::parse_from(["typfont", "--no-system-fonts", "check"]).no_system_fonts);
+        assert!(
+            Cli::try_parse_from([
+                "typfont",
+                "check",
+                "--no-system-fonts",
+                "--system-dirs",
+                "x"
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_index_is_grouped_by_license_and_foundry() {
+        let entry = |family: &str, manufacturer: &str, license: &str| DiscoveredFont {
+            font: TypstFont {
+                family_name: family.to_string(),
+                style: typst::text::FontStyle::Normal,
+                weight: FontWeight::REGULAR,
+                stretch: FontStretch::NORMAL,
+            },
+            path: PathBuf::from(format!("{family}.otf")),
+            index: 0,
+            axes: Vec::new(),
+            metadata: FontMetadata {
+                manufacturer: Some(manufacturer.to_string()),
+                license: FontLicense::from_names(Some(license), None),
+                ..FontMetadata::default()
+            },
+        };
+        let entries = vec![
+            entry(
+                "Inter",
+                "Rasmus Andersson",
+                "This Font Software is licensed under the SIL Open Font License, Version 1.1.",
+            ),
+            entry(
+                "Minion Pro",
+                "Adobe Systems, Inc.",
+                "Licensed for use on up to 5 devices.",
+            ),
+            entry(
+                "Source Serif 4",
+                "Adobe Systems, Inc.",
+                "https://openfontlicense.org",
+            ),
+            entry("Vendor Sans", "Vendor", ""),
+        ];
+        let names = |groups: BTreeMap<PathBuf, Vec<DiscoveredFont>>| {
+            groups
+                .into_iter()
+                .map(|(file, entries)| (file.display().to_string(), entries.len()))
+                .collect::<Vec<_>>()
+        };
+
+        let groups = group_index(
+            entries.clone(),
+            Path::new("font_library.toml"),
+            Some(IndexGroup::License),
+        );
+        assert_eq!(
+            names(groups),
+            [
+                ("font_library.ofl.toml".to_string(), 2),
+                ("font_library.proprietary.toml".to_string(), 1),
+                ("font_library.unknown.toml".to_string(), 1)
+            ]
+        );
+        let groups = group_index(
+            entries.clone(),
+            Path::new("cjk.toml"),
+            Some(IndexGroup::Foundry),
+        );
+        assert_eq!(
+            names(groups)[0],
+            ("cjk.adobe-systems-inc.toml".to_string(), 2)
+        );
+        let groups = group_index(entries, Path::new("font_library.toml"), None);
+        assert_eq!(names(groups), [("font_library.toml".to_string(), 4)]);
+    }
 }
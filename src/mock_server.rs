@@ -0,0 +1,392 @@
+//! A local HTTP server standing in for GitHub, used by the integration tests and
+//! by the hidden `--mock-server` flag. Files below its root are served at their
+//! relative path, the way raw.githubusercontent.com serves repositories:
+//!
+//! ```text
+//! <root>/owner/fonts/main/font_library.toml         raw index of owner/fonts
+//! <root>/owner/fonts/main/Inter/Inter-Regular.otf   a font file of the library
+//! <root>/api/repos/owner/fonts/commits/main         GitHub API response (JSON)
+//! <root>/api/repos/owner/fonts/contents/...          files for authorized requests
+//! ```
+//!
+//! POST requests, such as opening a pull request, are answered with the file at
+//! their path too, as the canned API response.
+//!
+//! It answers conditional requests by ETag and range requests, records every
+//! request, and can fail or rate limit requests or require an `Authorization`
+//! header, so that retries, checksums and authentication are tested without the
+//! real network.
+//! This file only uses the standard library, as the integration tests include
+//! it as a module of their own.
+
+// The tests and the binary each use part of the server
+#![allow(dead_code)]
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// A request received by the server
+#[derive(Clone, Debug)]
+pub(crate) struct MockRequest {
+    pub(crate) method: String,
+    /// The path, without the query
+    pub(crate) path: String,
+    pub(crate) headers: Vec<(String, String)>,
+}
+
+impl MockRequest {
+    /// The value of the header `name`, compared case-insensitively
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Default)]
+struct State {
+    /// Number of upcoming requests answered with 503 Service Unavailable
+    failures: usize,
+    /// Number of upcoming requests answered with 429 Too Many Requests
+    rate_limited: usize,
+    /// The `Authorization` header every request must carry
+    authorization: Option<String>,
+    requests: Vec<MockRequest>,
+}
+
+pub(crate) struct MockServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    shutdown: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockServer {
+    /// Serve the files below `root` on a free port of 127.0.0.1
+    pub(crate) fn start(root: &Path) -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State::default()));
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let (root, state, shutdown) = (root.to_path_buf(), state.clone(), shutdown.clone());
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if shutdown.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    if let Ok(stream) = stream {
+                        let _ = handle(stream, &root, &state);
+                    }
+                }
+            })
+        };
+        Ok(Self {
+            addr,
+            state,
+            shutdown,
+            thread: Some(thread),
+        })
+    }
+
+    /// The base URL, e.g. `http://127.0.0.1:40123`
+    pub(crate) fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Answer the next `count` requests with 503 Service Unavailable
+    pub(crate) fn fail_next(&self, count: usize) {
+        self.state.lock().unwrap().failures = count;
+    }
+
+    /// Answer the next `count` requests with 429 Too Many Requests, to be retried
+    /// at once
+    pub(crate) fn rate_limit_next(&self, count: usize) {
+        self.state.lock().unwrap().rate_limited = count;
+    }
+
+    /// Answer requests without this exact `Authorization` header with 401
+    pub(crate) fn require_authorization(&self, value: &str) {
+        self.state.lock().unwrap().authorization = Some(value.to_string());
+    }
+
+    /// The requests received so far
+    pub(crate) fn requests(&self) -> Vec<MockRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// Keep serving until the process exits, for `--mock-server`
+    pub(crate) fn detach(mut self) {
+        self.thread.take();
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            self.shutdown.store(true, Ordering::SeqCst);
+            // Wake the accepting thread up so that it sees the flag
+            let _ = TcpStream::connect(self.addr);
+            let _ = thread.join();
+        }
+    }
+}
+
+fn handle(stream: TcpStream, root: &Path, state: &Mutex<State>) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Ok(());
+    };
+
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    let request = MockRequest {
+        method: method.to_string(),
+        path: target.split('?').next().unwrap_or_default().to_string(),
+        headers,
+    };
+    // Bodies, such as webhook payloads, are read and dropped
+    if let Some(length) = request
+        .header("Content-Length")
+        .and_then(|length| length.parse::<u64>().ok())
+    {
+        io::copy(&mut reader.by_ref().take(length), &mut io::sink())?;
+    }
+
+    let response = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(request.clone());
+        if state.failures > 0 {
+            state.failures -= 1;
+            Response::status(503, "Service Unavailable")
+        } else if state.rate_limited > 0 {
+            state.rate_limited -= 1;
+            let mut response = Response::status(429, "Too Many Requests");
+            response.headers.push(("Retry-After", "0".to_string()));
+            response
+        } else if state
+            .authorization
+            .as_deref()
+            .is_some_and(|required| request.header("Authorization") != Some(required))
+        {
+            Response::status(401, "Unauthorized")
+        } else {
+            respond(&request, root)
+        }
+    };
+    response.write(stream, request.method == "HEAD")
+}
+
+struct Response {
+    code: u16,
+    reason: &'static str,
+    headers: Vec<(&'static str, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn status(code: u16, reason: &'static str) -> Self {
+        Self {
+            code,
+            reason,
+            headers: Vec::new(),
+            body: format!("{code} {reason}\n").into_bytes(),
+        }
+    }
+
+    fn write(self, mut stream: TcpStream, head: bool) -> io::Result<()> {
+        let mut response = format!("HTTP/1.1 {} {}\r\n", self.code, self.reason);
+        for (name, value) in &self.headers {
+            response.push_str(&format!("{name}: {value}\r\n"));
+        }
+        response.push_str(&format!(
+            "Content-Length: {}\r\nConnection: close\r\n\r\n",
+            self.body.len()
+        ));
+        stream.write_all(response.as_bytes())?;
+        if !head {
+            stream.write_all(&self.body)?;
+        }
+        stream.flush()
+    }
+}
+
+fn respond(request: &MockRequest, root: &Path) -> Response {
+    if !["GET", "HEAD", "POST"].contains(&request.method.as_str()) {
+        return Response::status(405, "Method Not Allowed");
+    }
+    let Some(path) = file_path(root, &request.path) else {
+        return Response::status(400, "Bad Request");
+    };
+    let Ok(data) = fs::read(&path) else {
+        return Response::status(404, "Not Found");
+    };
+
+    let etag = {
+        let mut hasher = DefaultHasher::new();
+        data.hash(&mut hasher);
+        format!("\"{:016x}\"", hasher.finish())
+    };
+    if request.header("If-None-Match") == Some(etag.as_str()) {
+        let mut response = Response::status(304, "Not Modified");
+        response.body.clear();
+        response.headers.push(("ETag", etag));
+        return response;
+    }
+
+    let mut response = Response {
+        code: 200,
+        reason: "OK",
+        headers: vec![("ETag", etag), ("Accept-Ranges", "bytes".to_string())],
+        body: data,
+    };
+    if let Some(range) = request.header("Range") {
+        let total = response.body.len();
+        let Some((start, end)) = parse_range(range, total) else {
+            let mut response = Response::status(416, "Range Not Satisfiable");
+            response
+                .headers
+                .push(("Content-Range", format!("bytes */{total}")));
+            return response;
+        };
+        response.code = 206;
+        response.reason = "Partial Content";
+        response
+            .headers
+            .push(("Content-Range", format!("bytes {start}-{end}/{total}")));
+        response.body = response.body[start..=end].to_vec();
+    }
+    response
+}
+
+/// The file below `root` that the URL path `path` names. Paths leaving the root
+/// are refused.
+fn file_path(root: &Path, path: &str) -> Option<PathBuf> {
+    let relative = PathBuf::from(percent_decode(path.trim_start_matches('/'))?);
+    relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+        .then(|| root.join(relative))
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = s.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// The inclusive byte range of a `Range: bytes=start-end` header, `start-` or
+/// `-suffix`, within a body of `total` bytes
+fn parse_range(header: &str, total: usize) -> Option<(usize, usize)> {
+    let (start, end) = header.strip_prefix("bytes=")?.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix = suffix.parse::<usize>().ok()?.min(total);
+            (total - suffix, total.checked_sub(1)?)
+        }
+        (start, "") => (start.parse().ok()?, total.checked_sub(1)?),
+        (start, end) => (
+            start.parse().ok()?,
+            end.parse::<usize>().ok()?.min(total.checked_sub(1)?),
+        ),
+    };
+    (start <= end && end < total).then_some((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(server: &MockServer, path: &str, headers: &str) -> String {
+        let mut stream = TcpStream::connect(server.addr).unwrap();
+        write!(
+            stream,
+            "GET {path} HTTP/1.1\r\nHost: localhost\r\n{headers}\r\n"
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_files_are_served_with_ranges_and_failures() {
+        let root = std::env::temp_dir().join(format!("typfont-mock-{}", std::process::id()));
+        fs::create_dir_all(root.join("owner/fonts/main")).unwrap();
+        fs::write(root.join("owner/fonts/main/My Font.ttf"), b"0123456789").unwrap();
+        let server = MockServer::start(&root).unwrap();
+
+        let response = get(&server, "/owner/fonts/main/My%20Font.ttf", "");
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+        assert!(response.ends_with("\r\n\r\n0123456789"));
+
+        let response = get(
+            &server,
+            "/owner/fonts/main/My%20Font.ttf",
+            "Range: bytes=2-4\r\n",
+        );
+        assert!(response.starts_with("HTTP/1.1 206"), "{response}");
+        assert!(response.contains("Content-Range: bytes 2-4/10"));
+        assert!(response.ends_with("\r\n\r\n234"));
+
+        assert!(get(&server, "/owner/../../etc/passwd", "").starts_with("HTTP/1.1 400"));
+        assert!(get(&server, "/owner/fonts/main/missing.ttf", "").starts_with("HTTP/1.1 404"));
+
+        server.fail_next(1);
+        assert!(get(&server, "/owner/fonts/main/My%20Font.ttf", "").starts_with("HTTP/1.1 503"));
+        server.require_authorization("Bearer secret");
+        assert!(get(&server, "/owner/fonts/main/My%20Font.ttf", "").starts_with("HTTP/1.1 401"));
+        let response = get(
+            &server,
+            "/owner/fonts/main/My%20Font.ttf",
+            "Authorization: Bearer secret\r\n",
+        );
+        assert!(response.starts_with("HTTP/1.1 200"));
+
+        assert_eq!(server.requests().len(), 7);
+        drop(server);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-0", 10), Some((0, 0)));
+        assert_eq!(parse_range("bytes=5-", 10), Some((5, 9)));
+        assert_eq!(parse_range("bytes=-3", 10), Some((7, 9)));
+        assert_eq!(parse_range("bytes=8-20", 10), Some((8, 9)));
+        assert_eq!(parse_range("bytes=10-", 10), None);
+        assert_eq!(parse_range("bytes=4-2", 10), None);
+        assert_eq!(parse_range("lines=1-2", 10), None);
+    }
+}
@@ -0,0 +1,89 @@
+use serde::Serialize;
+use std::process::Command;
+
+use crate::http;
+
+/// Raise a desktop notification using the tools shipped with each platform:
+/// `notify-send` on Linux, `osascript` on macOS and PowerShell on Windows.
+pub(crate) fn send_desktop_notification(title: &str, body: &str) -> Result<(), String> {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("osascript");
+        command.args([
+            "-e",
+            &format!(
+                "display notification {} with title {}",
+                apple_script_string(body),
+                apple_script_string(title)
+            ),
+        ]);
+        command
+    } else if cfg!(target_os = "windows") {
+        let mut command = Command::new("powershell");
+        command.args([
+            "-NoProfile",
+            "-Command",
+            &format!(
+                "Add-Type -AssemblyName System.Windows.Forms; \
+                 $n = New-Object System.Windows.Forms.NotifyIcon; \
+                 $n.Icon = [System.Drawing.SystemIcons]::Information; $n.Visible = $true; \
+                 $n.ShowBalloonTip(5000, {}, {}, 'Info'); Start-Sleep -Seconds 5; $n.Dispose()",
+                powershell_string(title),
+                powershell_string(body)
+            ),
+        ]);
+        command
+    } else {
+        let mut command = Command::new("notify-send");
+        command.args([title, body]);
+        command
+    };
+
+    let status = command
+        .status()
+        .map_err(|e| format!("Failed to send desktop notification: {e}"))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("Failed to send desktop notification: {status}"))
+    }
+}
+
+/// POST `payload` as JSON to `url`, e.g. a Slack or Teams incoming webhook or a
+/// mail relay
+pub(crate) fn post_webhook(url: &str, payload: &impl Serialize) -> Result<(), String> {
+    let body = serde_json::to_vec(payload)
+        .map_err(|e| format!("Failed to serialize the webhook payload: {e}"))?;
+    let client = http::client()?;
+    let response = http::post(&client, url)?
+        .header(http::header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .send()
+        .map_err(|e| format!("Failed to notify webhook {url}: {e}"))?;
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Failed to notify webhook {url}: HTTP {}",
+            response.status()
+        ))
+    }
+}
+
+fn apple_script_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn powershell_string(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_strings_are_escaped() {
+        assert_eq!(apple_script_string(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(powershell_string("it's"), "'it''s'");
+    }
+}
@@ -0,0 +1,442 @@
+//! Font packs: named sets of fonts, such as "academic-basic" or "cjk-complete",
+//! that a library defines in the `[packs]` table of its `font_library.toml` or
+//! of a `packs.toml` next to it. A project requiring `pack = "academic-basic"`
+//! gets every member resolved and updated along with its own fonts. Packs may
+//! include other packs, e.g. a "thesis" pack with `packs = ["academic-basic"]`.
+//!
+//! ```toml
+//! [packs.thesis]
+//! packs = ["academic-basic", "math-extra"]
+//!
+//! [packs.academic-basic]
+//! description = "Body text and math for papers"
+//! families = ["Libertinus Serif", "Libertinus Math"]
+//!
+//! [[packs.academic-basic.fonts]]
+//! family_name = "Inter"
+//! weight = [400, 700]
+//! ```
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use toml::Value;
+
+use crate::DiscoveredFont;
+use crate::font_manager::LibraryDirs;
+use crate::github::{DEFAULT_INDEX_PATH, GitHubSource};
+use crate::http;
+use crate::parse_font_config::{TypstFont, expand_weight_arrays};
+
+/// File next to the library index that may define packs as well
+pub(crate) const PACKS_FILE: &str = "packs.toml";
+
+/// A pack as defined by a library
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Pack {
+    #[serde(default)]
+    pub(crate) description: Option<String>,
+    /// Other packs whose fonts belong to the pack as well
+    #[serde(default)]
+    pub(crate) packs: Vec<String>,
+    /// Families whose every variant in the library belongs to the pack
+    #[serde(default)]
+    pub(crate) families: Vec<String>,
+    /// Single variants, written like the `fonts` of a font_config.toml
+    #[serde(default)]
+    pub(crate) fonts: Vec<TypstFont>,
+}
+
+/// What a [`PackNode`] stands for
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum NodeKind {
+    /// A pack of the library
+    Pack,
+    /// A Typst package the project imports, see [`crate::typst_packages`]
+    Package,
+}
+
+/// A required pack expanded into its own fonts and the packs it includes
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct PackNode {
+    pub(crate) name: String,
+    pub(crate) kind: NodeKind,
+    pub(crate) fonts: Vec<TypstFont>,
+    pub(crate) packs: Vec<PackNode>,
+}
+
+impl PackNode {
+    /// The fonts of the pack and of every pack it includes
+    pub(crate) fn all_fonts(&self) -> Vec<&TypstFont> {
+        let mut fonts = self.fonts.iter().collect::<Vec<_>>();
+        fonts.extend(self.packs.iter().flat_map(PackNode::all_fonts));
+        fonts
+    }
+}
+
+/// The packs defined in the `[packs]` table of `content`, if it has one
+pub(crate) fn parse_packs(content: &str) -> Result<BTreeMap<String, Pack>, String> {
+    let value: Value = toml::from_str(content).map_err(|e| e.to_string())?;
+    let Some(packs) = value.get("packs") else {
+        return Ok(BTreeMap::new());
+    };
+    let packs = packs.as_table().ok_or("`packs` must be a table of packs")?;
+
+    packs
+        .iter()
+        .map(|(name, pack)| {
+            let mut pack = pack.clone();
+            if let Some(table) = pack.as_table_mut()
+                && let Some(fonts) = table.get("fonts").and_then(Value::as_array)
+            {
+                let fonts = expand_weight_arrays(fonts);
+                table.insert("fonts".to_string(), Value::Array(fonts));
+            }
+            let pack = Pack::deserialize(pack).map_err(|e| format!("Pack {name:?}: {e}"))?;
+            Ok((name.clone(), pack))
+        })
+        .collect()
+}
+
+/// The packs defined by the library sources. Where several sources define a
+/// pack of the same name, the first one wins.
+pub(crate) fn load_packs(library_dirs: &LibraryDirs) -> Result<BTreeMap<String, Pack>, String> {
+    let mut packs = BTreeMap::new();
+    for dir in library_dirs {
+        let definitions = match library_dirs {
+            LibraryDirs::GitHub(_) => fetch_definitions(dir)?,
+            LibraryDirs::Local(_) => read_definitions(dir)?,
+        };
+        for content in definitions {
+            for (name, pack) in parse_packs(&content).map_err(|e| format!("{dir:?}: {e}"))? {
+                packs.entry(name).or_insert(pack);
+            }
+        }
+    }
+    Ok(packs)
+}
+
+/// The index and packs file at the root of a local library, where they exist
+fn read_definitions(dir: &Path) -> Result<Vec<String>, String> {
+    [DEFAULT_INDEX_PATH, PACKS_FILE]
+        .into_iter()
+        .map(|file| dir.join(file))
+        .filter(|path| path.is_file())
+        .map(|path| fs::read_to_string(&path).map_err(|e| format!("{path:?}: {e}")))
+        .collect()
+}
+
+/// The index of a GitHub library and the packs file next to it, if there is one
+fn fetch_definitions(source: &Path) -> Result<Vec<String>, String> {
+    if http::offline() {
+        return Err(format!(
+            "Can't read the packs of {}, network access is disabled by --frozen",
+            source.display()
+        ));
+    }
+    let source = GitHubSource::parse(source)?;
+    let packs_path = Path::new(source.index_path).with_file_name(PACKS_FILE);
+    let urls = [
+        source.index_url(),
+        source.file_url(&packs_path.to_string_lossy()),
+    ];
+
+    let client = http::client()?;
+    let mut definitions = Vec::new();
+    for url in urls {
+        let response = http::get(&client, &url)?
+            .send()
+            .map_err(|e| format!("{url}: {e}"))?;
+        if response.status() == http::StatusCode::NOT_FOUND {
+            continue;
+        }
+        if !response.status().is_success() {
+            return Err(format!("{url}: HTTP {}", response.status()));
+        }
+        definitions.push(response.text().map_err(|e| format!("{url}: {e}"))?);
+    }
+    Ok(definitions)
+}
+
+/// Expand the packs `names` with the packs they include. A family of a pack
+/// stands for every variant of it in the library, or for its regular variant if
+/// the library has none, so that it is reported as missing.
+pub(crate) fn resolve_packs(
+    names: &[String],
+    packs: &BTreeMap<String, Pack>,
+    library_entries: &[DiscoveredFont],
+) -> Result<Vec<PackNode>, String> {
+    names
+        .iter()
+        .map(|name| expand_pack(name, packs, library_entries, &mut Vec::new()))
+        .collect()
+}
+
+/// Expand the pack `name`, included through the packs of `stack`
+fn expand_pack(
+    name: &str,
+    packs: &BTreeMap<String, Pack>,
+    library_entries: &[DiscoveredFont],
+    stack: &mut Vec<String>,
+) -> Result<PackNode, String> {
+    if stack.iter().any(|included| included == name) {
+        return Err(format!("Font pack cycle: {} -> {name}", stack.join(" -> ")));
+    }
+    let pack = packs.get(name).ok_or_else(|| {
+        let defined = packs.keys().cloned().collect::<Vec<_>>();
+        let known = if defined.is_empty() {
+            "the libraries define no packs".to_string()
+        } else {
+            format!("the libraries define: {}", defined.join(", "))
+        };
+        match stack.last() {
+            Some(parent) => format!("Unknown font pack {name:?} included by {parent:?}, {known}"),
+            None => format!("Unknown font pack {name:?}, {known}"),
+        }
+    })?;
+
+    let mut fonts = Vec::new();
+    for family in &pack.families {
+        let mut variants = library_entries
+            .iter()
+            .filter(|entry| entry.font.family_name.eq_ignore_ascii_case(family))
+            .map(|entry| entry.font.clone())
+            .collect::<Vec<_>>();
+        variants.sort();
+        variants.dedup();
+        if variants.is_empty() {
+            fonts.push(TypstFont {
+                family_name: family.clone(),
+                style: Default::default(),
+                weight: Default::default(),
+                stretch: Default::default(),
+            });
+        }
+        fonts.extend(variants);
+    }
+    fonts.extend(pack.fonts.iter().cloned());
+
+    stack.push(name.to_string());
+    let included = pack
+        .packs
+        .iter()
+        .map(|included| expand_pack(included, packs, library_entries, stack))
+        .collect::<Result<Vec<_>, _>>();
+    stack.pop();
+
+    Ok(PackNode {
+        name: name.to_string(),
+        kind: NodeKind::Pack,
+        fonts,
+        packs: included?,
+    })
+}
+
+/// Render the requirements of a project as a tree: the fonts it lists, then
+/// each pack or Typst package with its fonts and included ones. `label` gives
+/// the line of a font.
+pub(crate) fn render_tree(
+    root: &str,
+    fonts: &[TypstFont],
+    packs: &[PackNode],
+    label: &dyn Fn(&TypstFont) -> String,
+) -> String {
+    let mut lines = vec![root.to_string()];
+    let count = fonts.len() + packs.len();
+    for (i, font) in fonts.iter().enumerate() {
+        lines.push(format!("{}{}", branch(i + 1 == count), label(font)));
+    }
+    for (i, pack) in packs.iter().enumerate() {
+        render_pack(&mut lines, "", pack, fonts.len() + i + 1 == count, label);
+    }
+    lines.join("\n")
+}
+
+fn render_pack(
+    lines: &mut Vec<String>,
+    prefix: &str,
+    pack: &PackNode,
+    last: bool,
+    label: &dyn Fn(&TypstFont) -> String,
+) {
+    let kind = match pack.kind {
+        NodeKind::Pack => "pack",
+        NodeKind::Package => "package",
+    };
+    lines.push(format!("{prefix}{}{kind} {}", branch(last), pack.name));
+    let prefix = format!("{prefix}{}", if last { "    " } else { "│   " });
+    let count = pack.fonts.len() + pack.packs.len();
+    for (i, font) in pack.fonts.iter().enumerate() {
+        lines.push(format!("{prefix}{}{}", branch(i + 1 == count), label(font)));
+    }
+    for (i, included) in pack.packs.iter().enumerate() {
+        let last = pack.fonts.len() + i + 1 == count;
+        render_pack(lines, &prefix, included, last, label);
+    }
+}
+
+fn branch(last: bool) -> &'static str {
+    if last { "└── " } else { "├── " }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process_font::FontMetadata;
+    use std::path::PathBuf;
+    use typst::text::{FontStretch, FontStyle, FontWeight};
+
+    fn font(family_name: &str, style: FontStyle, weight: u16) -> TypstFont {
+        TypstFont {
+            family_name: family_name.to_string(),
+            style,
+            weight: FontWeight::from_number(weight),
+            stretch: FontStretch::NORMAL,
+        }
+    }
+
+    fn entry(font: TypstFont) -> DiscoveredFont {
+        DiscoveredFont {
+            font,
+            path: PathBuf::from("lib/font.otf"),
+            index: 0,
+            axes: Vec::new(),
+            metadata: FontMetadata::default(),
+        }
+    }
+
+    #[test]
+    fn test_parse_packs_expands_weights() {
+        let packs = parse_packs(
+            r#"
+[[fonts]]
+family_name = "Inter"
+path = "Inter/Inter-Regular.otf"
+
+[packs.academic-basic]
+description = "Papers"
+families = ["Libertinus Serif"]
+
+[[packs.academic-basic.fonts]]
+family_name = "Inter"
+weight = [400, 700]
+"#,
+        )
+        .unwrap();
+        let pack = &packs["academic-basic"];
+        assert_eq!(pack.description.as_deref(), Some("Papers"));
+        assert_eq!(pack.families, ["Libertinus Serif"]);
+        assert_eq!(
+            pack.fonts,
+            [
+                font("Inter", FontStyle::Normal, 400),
+                font("Inter", FontStyle::Normal, 700)
+            ]
+        );
+
+        assert!(
+            parse_packs("[[fonts]]\nfamily_name = \"Inter\"\n")
+                .unwrap()
+                .is_empty()
+        );
+        assert!(parse_packs("[packs.broken]\nfamily = \"Inter\"\n").is_err());
+    }
+
+    #[test]
+    fn test_resolve_packs_takes_every_library_variant_of_a_family() {
+        let packs = parse_packs(
+            r#"
+[packs.academic-basic]
+families = ["Libertinus Serif", "Libertinus Math"]
+"#,
+        )
+        .unwrap();
+        let library = [
+            entry(font("Libertinus Serif", FontStyle::Normal, 400)),
+            entry(font("Libertinus Serif", FontStyle::Italic, 400)),
+            entry(font("Inter", FontStyle::Normal, 400)),
+        ];
+        let nodes = resolve_packs(&["academic-basic".to_string()], &packs, &library).unwrap();
+        assert_eq!(
+            nodes[0].fonts,
+            [
+                font("Libertinus Serif", FontStyle::Normal, 400),
+                font("Libertinus Serif", FontStyle::Italic, 400),
+                font("Libertinus Math", FontStyle::Normal, 400),
+            ]
+        );
+
+        let error = resolve_packs(&["cjk".to_string()], &packs, &library).unwrap_err();
+        assert!(error.contains("academic-basic"));
+    }
+
+    #[test]
+    fn test_included_packs_are_expanded_and_cycles_rejected() {
+        let packs = parse_packs(
+            r#"
+[packs.thesis]
+packs = ["academic-basic", "math-extra"]
+families = ["Inter"]
+
+[packs.academic-basic]
+families = ["Libertinus Serif"]
+
+[packs.math-extra]
+packs = ["academic-basic"]
+families = ["Libertinus Math"]
+"#,
+        )
+        .unwrap();
+        let nodes = resolve_packs(&["thesis".to_string()], &packs, &[]).unwrap();
+        let families = nodes[0]
+            .all_fonts()
+            .iter()
+            .map(|font| font.family_name.as_str())
+            .collect::<Vec<_>>();
+        // Packs included twice are expanded twice, but not in a cycle
+        assert_eq!(
+            families,
+            [
+                "Inter",
+                "Libertinus Serif",
+                "Libertinus Math",
+                "Libertinus Serif"
+            ]
+        );
+        let label = |font: &TypstFont| font.family_name.clone();
+        assert_eq!(
+            render_tree("font_config.toml", &[], &nodes, &label),
+            "font_config.toml
+└── pack thesis
+    ├── Inter
+    ├── pack academic-basic
+    │   └── Libertinus Serif
+    └── pack math-extra
+        ├── Libertinus Math
+        └── pack academic-basic
+            └── Libertinus Serif"
+        );
+
+        let mut packs = packs;
+        packs.get_mut("academic-basic").unwrap().packs = vec!["thesis".to_string()];
+        let error = resolve_packs(&["thesis".to_string()], &packs, &[]).unwrap_err();
+        assert_eq!(error, "Font pack cycle: thesis -> academic-basic -> thesis");
+    }
+
+    #[test]
+    fn test_local_packs_file_is_read_next_to_the_index() {
+        let dir = std::env::temp_dir().join(format!("typfont-packs-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join(PACKS_FILE),
+            "[packs.cjk-complete]\nfamilies = [\"Noto Sans CJK SC\"]\n",
+        )
+        .unwrap();
+
+        let packs = load_packs(&LibraryDirs::Local(vec![dir.clone()])).unwrap();
+        assert_eq!(packs["cjk-complete"].families, ["Noto Sans CJK SC"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
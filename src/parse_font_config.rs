@@ -1,13 +1,16 @@
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
 use std::fs::File;
 use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use toml::Value;
 use typst::text::{FontStretch, FontStyle, FontWeight};
 
+use crate::utils::path_utils::portable_path;
+
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Deserialize, Serialize)]
 pub struct TypstFont {
     pub(crate) family_name: String,
@@ -15,17 +18,16 @@ pub struct TypstFont {
     pub(crate) style: FontStyle,
     #[serde(default)]
     pub(crate) weight: FontWeight,
-    #[serde(default)]
+    #[serde(default, deserialize_with = "font_stretch_serde::deserialize")]
     pub(crate) stretch: FontStretch,
 }
 
-impl fmt::Display for TypstFont {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl TypstFont {
+    /// The variant part of the display string, without the family name
+    pub(crate) fn variant_string(&self) -> String {
         let stretch = (self.stretch.to_ratio().get() * 1000.0) as u16;
-        write!(
-            f,
-            "{:<30}    (style: {:?}, weight: {}, stretch: {})",
-            self.family_name,
+        format!(
+            "(style: {:?}, weight: {}, stretch: {})",
             self.style,
             self.weight.to_number(),
             stretch
@@ -33,6 +35,12 @@ impl fmt::Display for TypstFont {
     }
 }
 
+impl fmt::Display for TypstFont {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:<30}    {}", self.family_name, self.variant_string())
+    }
+}
+
 pub(crate) mod typst_font_serde {
     use serde::{Deserialize, Deserializer, Serializer};
     use typst::text::FontStyle;
@@ -68,26 +76,487 @@ pub(crate) mod typst_font_serde {
     }
 }
 
+/// Stretch written as permille (`1250`), a percentage (`"87.5%"`) or a CSS
+/// name (`"semi-expanded"`), always serialized as permille
+pub(crate) mod font_stretch_serde {
+    use serde::{Deserialize, Deserializer};
+    use typst::layout::Ratio;
+    use typst::text::FontStretch;
+
+    const NAMES: [(&str, FontStretch); 9] = [
+        ("ultra-condensed", FontStretch::ULTRA_CONDENSED),
+        ("extra-condensed", FontStretch::EXTRA_CONDENSED),
+        ("condensed", FontStretch::CONDENSED),
+        ("semi-condensed", FontStretch::SEMI_CONDENSED),
+        ("normal", FontStretch::NORMAL),
+        ("semi-expanded", FontStretch::SEMI_EXPANDED),
+        ("expanded", FontStretch::EXPANDED),
+        ("extra-expanded", FontStretch::EXTRA_EXPANDED),
+        ("ultra-expanded", FontStretch::ULTRA_EXPANDED),
+    ];
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Stretch {
+        Permille(i64),
+        Text(String),
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<FontStretch, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let permille = match Stretch::deserialize(deserializer)? {
+            Stretch::Permille(permille) => permille as f64,
+            Stretch::Text(text) => return parse(&text).map_err(serde::de::Error::custom),
+        };
+        from_permille(permille).ok_or_else(|| {
+            serde::de::Error::custom(format!(
+                "Invalid stretch {permille}, expected 500 to 2000 (‰), a percentage like \"87.5%\" or a name like \"condensed\""
+            ))
+        })
+    }
+
+    /// A stretch given as a name or percentage, e.g. "Semi-Expanded" or "87.5%"
+    pub(crate) fn parse(text: &str) -> Result<FontStretch, String> {
+        let text = text.trim();
+        if let Some(percent) = text.strip_suffix('%') {
+            return percent
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .and_then(|percent| from_permille(percent * 10.0))
+                .ok_or_else(|| format!("Invalid stretch {text:?}, expected 50% to 200%"));
+        }
+        let name = text.to_ascii_lowercase().replace(['_', ' '], "-");
+        NAMES
+            .iter()
+            .find(|(known, _)| *known == name || known.replace('-', "") == name)
+            .map(|(_, stretch)| *stretch)
+            .ok_or_else(|| {
+                let names = NAMES.map(|(name, _)| name).join(", ");
+                format!("Invalid stretch {text:?}, expected a percentage like \"87.5%\" or one of {names}")
+            })
+    }
+
+    /// The stretch of `permille`, rounded to a whole permille, if it is one
+    /// Typst can represent
+    fn from_permille(permille: f64) -> Option<FontStretch> {
+        let permille = permille.round();
+        (500.0..=2000.0)
+            .contains(&permille)
+            // Half a permille more, so that the conversion doesn't truncate below it
+            .then(|| FontStretch::from_ratio(Ratio::new((permille + 0.5) / 1000.0)))
+    }
+}
+
+/// Font file formats a project can require its installed fonts to use
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FontFormat {
+    Ttf,
+    Otf,
+}
+
+impl FontFormat {
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            FontFormat::Ttf => "ttf",
+            FontFormat::Otf => "otf",
+        }
+    }
+
+    /// The format of a font file judging by its extension, if it is a single-face TTF/OTF
+    pub(crate) fn of_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "ttf" => Some(FontFormat::Ttf),
+            "otf" => Some(FontFormat::Otf),
+            _ => None,
+        }
+    }
+}
+
+/// What to do when a font whose license doesn't allow redistributing it is about
+/// to be committed to a git repository, see `redistribution`
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum RedistributionPolicy {
+    /// Copy it without a word
+    Allow,
+    /// Copy it, but print a warning
+    #[default]
+    Warn,
+    /// Refuse to copy it
+    Deny,
+}
+
 // This struct represents the font configuration of a project, i.e. font_config.toml
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq)]
 pub struct FontConfig {
     #[serde(default)]
     pub(crate) font_dir: Option<String>, // Path to the font directory of the project
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) font_format: Option<FontFormat>, // Required file format of installed fonts
+    /// Policy for fonts that may not be redistributed, when the font directory
+    /// is tracked by git; warns by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) redistribution: Option<RedistributionPolicy>,
+    /// Font packs defined by the library whose members are required too, given
+    /// as `pack = "name"` or a list of names
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub(crate) pack: Vec<String>,
+    /// Whether the fonts declared by the Typst packages the project imports
+    /// are required too; they are unless set to false
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) package_fonts: Option<bool>,
+    /// Whether `update` installs fonts into `.incoming/` of the font directory
+    /// for review, see `promote`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) quarantine: Option<bool>,
+    /// Whether the config is the top of its hierarchy, inheriting nothing from
+    /// the font_config.toml files of parent directories
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) root: Option<bool>,
+    #[serde(default)]
     pub(crate) fonts: Vec<TypstFont>, // List of fonts required by the project
+    /// Families left out of the installed and library fonts, such as the emoji
+    /// fonts of an asset dump sharing the project or library directories
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) ignore_families: Vec<String>,
+    /// Priorities of the library sources, see [`SourcePriority`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) source_priority: Vec<SourcePriority>,
+    /// Library files chosen or avoided for fonts, from the `prefer_file` and
+    /// `avoid_file_glob` keys of their entries
+    #[serde(skip)]
+    pub(crate) file_choices: BTreeMap<TypstFont, FileChoice>,
+}
+
+/// A single string, or a list of them
+fn one_or_many<'de, D>(deserializer: D) -> std::result::Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(one) => vec![one],
+        OneOrMany::Many(many) => many,
+    })
+}
+
+/// A `[[source_priority]]` rule. When several library sources contain a font,
+/// the file is taken from the source with the highest priority; sources
+/// without a rule have priority 0. Rules listing `families` override the
+/// others for fonts of those families.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct SourcePriority {
+    /// Source ID, e.g. "github:owner/repo" or "local:/path/to/fonts", or the
+    /// directory or repository as given with `--library`
+    pub(crate) source: String,
+    pub(crate) priority: i32,
+    /// Family name globs (case-insensitive) the rule is limited to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) families: Vec<String>,
+}
+
+impl SourcePriority {
+    /// Whether the rule is limited to families and `family` is one of them
+    pub(crate) fn matches_family(&self, family: &str) -> bool {
+        let options = glob::MatchOptions {
+            case_sensitive: false,
+            ..Default::default()
+        };
+        self.families.iter().any(|glob| {
+            glob::Pattern::new(glob).is_ok_and(|pattern| pattern.matches_with(family, options))
+        })
+    }
+}
+
+/// Which library file to install for a font when several files satisfy it
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+pub(crate) struct FileChoice {
+    /// File name, or end of the library path, of the file to use if it satisfies the font
+    #[serde(default)]
+    pub(crate) prefer_file: Option<String>,
+    /// Files whose name matches this glob are never installed for the font
+    #[serde(default)]
+    pub(crate) avoid_file_glob: Option<String>,
+}
+
+impl FileChoice {
+    /// Whether `path` is the preferred file
+    pub(crate) fn prefers(&self, path: &Path) -> bool {
+        let Some(prefer_file) = &self.prefer_file else {
+            return false;
+        };
+        let path = portable_path(path);
+        let prefer_file = prefer_file.replace('\\', "/");
+        path == prefer_file || path.ends_with(&format!("/{prefer_file}"))
+    }
+
+    /// Whether the file name of `path` matches the avoided glob
+    pub(crate) fn avoids(&self, path: &Path) -> bool {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        self.avoid_file_glob
+            .as_deref()
+            .and_then(|glob| glob::Pattern::new(glob).ok())
+            .is_some_and(|pattern| pattern.matches(&file_name))
+    }
 }
 
+/// The `fonts` entries read again for their file choices only
+#[derive(Deserialize)]
+struct FileChoices {
+    #[serde(default)]
+    fonts: Vec<FileChoice>,
+}
+
+/// The fonts embedded in the Typst compiler, which never need to be installed
+pub(crate) const EMBEDDED_FONTS: &str = r#"
+[[fonts]]
+family_name = "DejaVu Sans Mono"
+style = "Normal"
+weight = [400, 700]
+stretch = 1000
+
+[[fonts]]
+family_name = "DejaVu Sans Mono"
+style = "Italic"
+weight = [400, 700]
+stretch = 1000
+
+[[fonts]]
+family_name = "Linux Libertine"
+style = "Normal"
+weight = [400, 700]
+stretch = 1000
+
+[[fonts]]
+family_name = "Linux Libertine"
+style = "Italic"
+weight = [400, 700]
+stretch = 1000
+
+[[fonts]]
+family_name = "New Computer Modern"
+style = "Normal"
+weight = [400, 700]
+stretch = 1000
+
+[[fonts]]
+family_name = "New Computer Modern"
+style = "Italic"
+weight = [400, 700]
+stretch = 1000
+
+[[fonts]]
+family_name = "New Computer Modern Math"
+style = "Normal"
+weight = [400, 450]
+stretch = 1000
+"#;
+
 /// Function to deserialize TOML string into a Vec of TypstFont
 pub fn deserialize_fonts_from_toml(toml_content: &str) -> Result<FontConfig> {
-    let font_config: FontConfig = toml::from_str(preprocess_font_config(toml_content)?.as_str())?;
+    let toml_content = preprocess_font_config(toml_content)?;
+    let mut font_config: FontConfig = toml::from_str(&toml_content)?;
+
+    for rule in &font_config.source_priority {
+        for glob in &rule.families {
+            glob::Pattern::new(glob).with_context(|| {
+                format!(
+                    "Invalid family glob {glob:?} in the source_priority of {}",
+                    rule.source
+                )
+            })?;
+        }
+    }
+
+    if let Some(font) = font_config
+        .fonts
+        .iter()
+        .find(|font| font_config.ignores_family(&font.family_name))
+    {
+        anyhow::bail!(
+            "Family {:?} is required, but listed in ignore_families",
+            font.family_name
+        );
+    }
+
+    let choices: FileChoices = toml::from_str(&toml_content)?;
+    for (font, choice) in font_config.fonts.iter().zip(choices.fonts) {
+        if let Some(glob) = &choice.avoid_file_glob {
+            glob::Pattern::new(glob)
+                .with_context(|| format!("Invalid avoid_file_glob {glob:?} of {font}"))?;
+        }
+        if choice != FileChoice::default() {
+            font_config.file_choices.insert(font.clone(), choice);
+        }
+    }
     Ok(font_config)
 }
 
 /// Function to read a TOML file and deserialize it into Vec<TypstFont>
 pub fn deserialize_fonts_from_file<P: AsRef<Path>>(file_path: P) -> Result<FontConfig> {
-    let mut file = File::open(file_path).expect("Font config file not found");
+    let mut file = File::open(&file_path)
+        .with_context(|| format!("Font config file {:?} not found", file_path.as_ref()))?;
     let mut content = String::new();
     file.read_to_string(&mut content)?;
-    deserialize_fonts_from_toml(&content)
+    let mut font_config = deserialize_fonts_from_toml(&content)?;
+
+    let project_root = file_path.as_ref().parent().unwrap_or(Path::new(""));
+    let project_root = std::path::absolute(project_root.join(""))?;
+    font_config
+        .expand_variables(&project_root, |name| std::env::var(name).ok())
+        .map_err(anyhow::Error::msg)?;
+
+    if font_config.root != Some(true)
+        && let Some(parent_file) = parent_config_files(file_path.as_ref()).first()
+    {
+        // Which inherits from its own parents in turn
+        let parent = deserialize_fonts_from_file(parent_file)
+            .with_context(|| format!("Failed to read the inherited config {parent_file:?}"))?;
+        font_config.inherit(&parent);
+    }
+    Ok(font_config)
+}
+
+/// The font_config.toml files of the parent directories of `config_file`, the
+/// nearest first, up to the root of its git repository. The config inherits
+/// the settings it leaves unset from the nearest one, see [`FontConfig::root`],
+/// so settings shared by the projects of a monorepo live in one place.
+pub(crate) fn parent_config_files(config_file: &Path) -> Vec<PathBuf> {
+    let Ok(config_file) = std::path::absolute(config_file) else {
+        return Vec::new();
+    };
+    let mut files = Vec::new();
+    let project_dir = config_file.parent().unwrap_or(Path::new(""));
+    if project_dir.join(".git").exists() {
+        return files;
+    }
+    for dir in project_dir.ancestors().skip(1) {
+        let file = dir.join("font_config.toml");
+        if file.is_file() {
+            files.push(file);
+        }
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+    files
+}
+
+impl FontConfig {
+    /// Take the policies this config leaves unset from `parent`, the config of a
+    /// parent directory, as well as its ignored families and its source
+    /// priorities, which rank after the config's own. The required fonts, packs
+    /// and font directory belong to each project and are never inherited.
+    pub(crate) fn inherit(&mut self, parent: &FontConfig) {
+        self.font_format = self.font_format.or(parent.font_format);
+        self.redistribution = self.redistribution.or(parent.redistribution);
+        self.package_fonts = self.package_fonts.or(parent.package_fonts);
+        self.quarantine = self.quarantine.or(parent.quarantine);
+        for family in &parent.ignore_families {
+            // A family the project requires stays required
+            let required = self
+                .fonts
+                .iter()
+                .any(|font| font.family_name.eq_ignore_ascii_case(family));
+            if !required && !self.ignores_family(family) {
+                self.ignore_families.push(family.clone());
+            }
+        }
+        self.source_priority
+            .extend(parent.source_priority.iter().cloned());
+    }
+
+    /// Whether `family` is listed in `ignore_families`, ignoring ASCII case
+    pub(crate) fn ignores_family(&self, family: &str) -> bool {
+        self.ignore_families
+            .iter()
+            .any(|ignored| ignored.eq_ignore_ascii_case(family))
+    }
+
+    /// Expand the variables in `font_dir` and the library sources of the
+    /// `source_priority` rules, see [`expand_variables`]
+    fn expand_variables(
+        &mut self,
+        project_root: &Path,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<(), String> {
+        if let Some(font_dir) = &mut self.font_dir {
+            *font_dir = expand_variables(font_dir, project_root, &env)
+                .map_err(|e| format!("{e} in font_dir"))?;
+        }
+        for rule in &mut self.source_priority {
+            rule.source = expand_variables(&rule.source, project_root, &env)
+                .map_err(|e| format!("{e} in the source_priority source {:?}", rule.source))?;
+        }
+        Ok(())
+    }
+}
+
+/// Expand `${PROJECT_ROOT}` (the directory of the font_config.toml), `${HOME}`
+/// and `${env:NAME}` in `value`, so a config can be shared between machines
+/// with different layouts. `$$` stands for a literal `$`.
+pub(crate) fn expand_variables(
+    value: &str,
+    project_root: &Path,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<String, String> {
+    let mut expanded = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+        if let Some(after) = rest.strip_prefix('$') {
+            expanded.push('$');
+            rest = after;
+            continue;
+        }
+        let Some(variable) = rest.strip_prefix('{') else {
+            return Err(
+                "A `$` that doesn't start a variable like ${NAME} must be written as `$$`"
+                    .to_string(),
+            );
+        };
+        let end = variable
+            .find('}')
+            .ok_or_else(|| format!("Unterminated variable ${{{variable}"))?;
+        let name = &variable[..end];
+        let value = match name.strip_prefix("env:") {
+            Some(var) => env(var)
+                .ok_or_else(|| format!("Environment variable {var} of ${{{name}}} is not set"))?,
+            None if name == "PROJECT_ROOT" => project_root
+                .to_str()
+                .ok_or_else(|| format!("Project root {project_root:?} is not valid UTF-8"))?
+                .trim_end_matches(['/', '\\'])
+                .to_string(),
+            None if name == "HOME" => env("HOME")
+                .or_else(|| env("USERPROFILE"))
+                .ok_or("${HOME} is used, but neither HOME nor USERPROFILE is set")?,
+            None => {
+                return Err(format!(
+                    "Undefined variable ${{{name}}}, expected ${{PROJECT_ROOT}}, ${{HOME}} or ${{env:NAME}}"
+                ));
+            }
+        };
+        expanded.push_str(&value);
+        rest = &variable[end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
 }
 
 #[allow(dead_code)]
@@ -103,38 +572,13 @@ fn preprocess_font_config(toml_str: &str) -> Result<String> {
     let mut toml_value: Value = toml::from_str(toml_str)?;
 
     // Process the TOML data
-    if let Some(fonts) = toml_value.get("fonts") {
-        // Extract the "fonts" section in the original TOML structure
-        if let Some(fonts_array) = fonts.as_array() {
-            let mut expanded_fonts = Vec::new();
-
-            // Iterate over each font entry
-            for font in fonts_array {
-                // Check if weight exists
-                if let Some(weight) = font.get("weight") {
-                    // If weight is an array, expand it
-                    if let Some(weights) = weight.as_array() {
-                        for w in weights {
-                            let mut new_font = font.clone();
-                            if let Some(map) = new_font.as_table_mut() {
-                                map.insert("weight".to_string(), w.clone());
-                            }
-                            expanded_fonts.push(Value::Table(new_font.as_table().unwrap().clone()));
-                        }
-                    } else {
-                        expanded_fonts.push(font.clone());
-                    }
-                } else {
-                    // If no weight field, just push the original font entry
-                    expanded_fonts.push(font.clone());
-                }
-            }
+    if let Some(fonts) = toml_value.get("fonts").and_then(Value::as_array) {
+        let expanded_fonts = expand_weight_arrays(fonts);
 
-            // Get a mutable reference of the TOML table
-            if let Some(table) = toml_value.as_table_mut() {
-                // Replace the original "fonts" section with the expanded fonts
-                table.insert("fonts".to_string(), Value::Array(expanded_fonts));
-            }
+        // Get a mutable reference of the TOML table
+        if let Some(table) = toml_value.as_table_mut() {
+            // Replace the original "fonts" section with the expanded fonts
+            table.insert("fonts".to_string(), Value::Array(expanded_fonts));
         }
     }
 
@@ -145,16 +589,338 @@ fn preprocess_font_config(toml_str: &str) -> Result<String> {
     Ok(new_toml_string)
 }
 
+/// Expand the font entries whose "weight" is an array into one entry per weight
+pub(crate) fn expand_weight_arrays(fonts: &[Value]) -> Vec<Value> {
+    let mut expanded_fonts = Vec::new();
+
+    // Iterate over each font entry
+    for font in fonts {
+        // If weight is an array, expand it
+        match font.get("weight").and_then(Value::as_array) {
+            Some(weights) => {
+                for w in weights {
+                    let mut new_font = font.clone();
+                    if let Some(map) = new_font.as_table_mut() {
+                        map.insert("weight".to_string(), w.clone());
+                    }
+                    expanded_fonts.push(new_font);
+                }
+            }
+            // If there is no weight array, just push the original font entry
+            None => expanded_fonts.push(font.clone()),
+        }
+    }
+    expanded_fonts
+}
+
+/// What [`add_font_entry`] did to a font config
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) enum ConfigEdit {
+    /// A new `[[fonts]]` entry was appended
+    Appended,
+    /// The weights were added to the entry of the same family, style and stretch
+    Merged(Vec<u16>),
+    /// The entry of the same family, style and stretch already lists the weights
+    Unchanged,
+}
+
+/// Add a `[[fonts]]` entry requiring `weights` of a family to the font config
+/// `content`. Weights missing from an entry of the same family (ignoring ASCII
+/// case), style and stretch are merged into it instead; the rest of the text,
+/// comments included, is kept as it is.
+pub(crate) fn add_font_entry(
+    content: &str,
+    family_name: &str,
+    style: FontStyle,
+    stretch: FontStretch,
+    weights: &[u16],
+) -> Result<(String, ConfigEdit)> {
+    let mut text = FontsText::parse(content)?;
+    let position = text.entries.iter().position(|entry| {
+        entry_variant(entry).is_some_and(|font| {
+            font.family_name.eq_ignore_ascii_case(family_name)
+                && font.style == style
+                && font.stretch == stretch
+        })
+    });
+    let edit = match position {
+        Some(index) => {
+            let existing = entry_weights(&text.entries[index]);
+            let added = weights
+                .iter()
+                .copied()
+                .filter(|weight| !existing.contains(weight))
+                .collect::<BTreeSet<_>>();
+            if added.is_empty() {
+                return Ok((content.to_string(), ConfigEdit::Unchanged));
+            }
+            let merged = existing
+                .iter()
+                .chain(&added)
+                .copied()
+                .collect::<BTreeSet<_>>();
+            text.set_weights(index, &Vec::from_iter(merged));
+            ConfigEdit::Merged(added.into_iter().collect())
+        }
+        None => {
+            let lines = &mut text.lines;
+            if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push("[[fonts]]".to_string());
+            lines.push(format!(
+                "family_name = {}",
+                Value::String(family_name.to_string())
+            ));
+            lines.push(format!("style = \"{style:?}\""));
+            let weights = weights.iter().copied().collect::<BTreeSet<_>>();
+            lines.push(format!(
+                "weight = {}",
+                weight_value(&Vec::from_iter(weights))
+            ));
+            if stretch != FontStretch::NORMAL {
+                let permille = (stretch.to_ratio().get() * 1000.0).round() as u16;
+                lines.push(format!("stretch = {permille}"));
+            }
+            ConfigEdit::Appended
+        }
+    };
+    Ok((text.finish(content)?, edit))
+}
+
+/// Remove `weights` of a family, of any style unless `style` is given, from
+/// the `[[fonts]]` entries of the font config `content`; no weights remove the
+/// entries of the family altogether, as does removing all of their weights.
+/// Returns the edited config and the fonts no longer required by it.
+pub(crate) fn remove_font_entries(
+    content: &str,
+    family_name: &str,
+    style: Option<FontStyle>,
+    weights: &[u16],
+) -> Result<(String, Vec<TypstFont>)> {
+    let mut text = FontsText::parse(content)?;
+    let mut removed = Vec::new();
+    // From the last entry, so that the lines of the earlier ones stay in place
+    for index in (0..text.entries.len()).rev() {
+        let Some(font) = entry_variant(&text.entries[index]) else {
+            continue;
+        };
+        if !font.family_name.eq_ignore_ascii_case(family_name)
+            || style.is_some_and(|style| style != font.style)
+        {
+            continue;
+        }
+        let existing = entry_weights(&text.entries[index]);
+        let (gone, kept): (Vec<u16>, Vec<u16>) = existing
+            .into_iter()
+            .partition(|weight| weights.is_empty() || weights.contains(weight));
+        if gone.is_empty() {
+            continue;
+        }
+        if kept.is_empty() {
+            text.remove_entry(index);
+        } else {
+            text.set_weights(index, &kept);
+        }
+        let fonts = gone.into_iter().map(|weight| TypstFont {
+            weight: FontWeight::from_number(weight),
+            ..font.clone()
+        });
+        removed.splice(0..0, fonts);
+    }
+    if removed.is_empty() {
+        return Ok((content.to_string(), removed));
+    }
+    Ok((text.finish(content)?, removed))
+}
+
+/// The lines of a font config with those of each `[[fonts]]` entry, for edits
+/// that keep comments and formatting
+struct FontsText {
+    lines: Vec<String>,
+    entries: Vec<Value>,
+    /// Index of the `[[fonts]]` line of each entry
+    headers: Vec<usize>,
+}
+
+impl FontsText {
+    fn parse(content: &str) -> Result<Self> {
+        let value: Value = toml::from_str(content)?;
+        let entries = value
+            .get("fonts")
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default();
+        let lines = content.lines().map(str::to_string).collect::<Vec<_>>();
+        let headers = (0..lines.len())
+            .filter(|&i| is_fonts_header(&lines[i]))
+            .collect::<Vec<_>>();
+        if headers.len() != entries.len() || lines.iter().any(|line| key_of(line) == Some("fonts"))
+        {
+            anyhow::bail!("The fonts are written as an inline array, which can't be edited");
+        }
+        Ok(Self {
+            lines,
+            entries,
+            headers,
+        })
+    }
+
+    /// The lines after the `[[fonts]]` line of entry `index`, up to the next table
+    fn body(&self, index: usize) -> std::ops::Range<usize> {
+        let start = self.headers[index] + 1;
+        let end = (start..self.lines.len())
+            .find(|&i| self.lines[i].trim_start().starts_with('['))
+            .unwrap_or(self.lines.len());
+        start..end
+    }
+
+    /// Write `weights` into the `weight` key of entry `index`, adding the key
+    /// after the family name if the entry has none
+    fn set_weights(&mut self, index: usize, weights: &[u16]) {
+        let body = self.body(index);
+        let weight_line = format!("weight = {}", weight_value(weights));
+        let lines = &mut self.lines;
+        match body.clone().find(|&i| key_of(&lines[i]) == Some("weight")) {
+            Some(first) => {
+                // An array spread over several lines ends at its `]`
+                let last = if lines[first].contains('[') {
+                    (first..body.end)
+                        .find(|&i| strip_comment(&lines[i]).contains(']'))
+                        .unwrap_or(first)
+                } else {
+                    first
+                };
+                let indent = &lines[first][..lines[first].len() - lines[first].trim_start().len()];
+                let line = match lines[last].find('#') {
+                    Some(at) => format!("{indent}{weight_line} {}", &lines[last][at..]),
+                    None => format!("{indent}{weight_line}"),
+                };
+                lines.splice(first..=last, [line]);
+            }
+            None => {
+                let after = body
+                    .clone()
+                    .find(|&i| key_of(&lines[i]) == Some("family_name"))
+                    .map_or(body.start, |i| i + 1);
+                lines.insert(after, weight_line);
+            }
+        }
+    }
+
+    /// Remove the lines of entry `index` with the comments right above them,
+    /// keeping the comments that lead into the next table
+    fn remove_entry(&mut self, index: usize) {
+        let is_comment = |line: &String| line.trim_start().starts_with('#');
+        let mut start = self.headers[index];
+        while start > 0 && is_comment(&self.lines[start - 1]) {
+            start -= 1;
+        }
+        let mut end = self.body(index).end;
+        if end < self.lines.len() {
+            while end > self.headers[index] + 1 && is_comment(&self.lines[end - 1]) {
+                end -= 1;
+            }
+        } else {
+            // Nothing follows, so neither do the blank lines before the entry
+            while start > 0 && self.lines[start - 1].trim().is_empty() {
+                start -= 1;
+            }
+        }
+        self.lines.drain(start..end);
+    }
+
+    /// The edited config, with the line endings of `content`, after checking
+    /// that it is still a valid font config
+    fn finish(self, content: &str) -> Result<String> {
+        let newline = if content.contains("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        };
+        let mut edited = self.lines.join(newline);
+        edited.push_str(newline);
+        deserialize_fonts_from_toml(&edited).context("The edited font config is invalid")?;
+        Ok(edited)
+    }
+}
+
+/// The family, style and stretch of a `[[fonts]]` entry, with the default weight
+fn entry_variant(entry: &Value) -> Option<TypstFont> {
+    let mut entry = entry.clone();
+    entry.as_table_mut()?.remove("weight");
+    entry.try_into().ok()
+}
+
+/// The weights of a `[[fonts]]` entry, `400` if it has none
+fn entry_weights(entry: &Value) -> Vec<u16> {
+    match entry.get("weight") {
+        None => vec![400],
+        Some(Value::Array(weights)) => weights
+            .iter()
+            .filter_map(Value::as_integer)
+            .map(|weight| weight as u16)
+            .collect(),
+        Some(weight) => weight
+            .as_integer()
+            .map(|weight| weight as u16)
+            .into_iter()
+            .collect(),
+    }
+}
+
+/// `400`, or `[400, 700]` for several weights
+fn weight_value(weights: &[u16]) -> String {
+    match weights {
+        [weight] => weight.to_string(),
+        weights => {
+            let weights = weights.iter().map(u16::to_string).collect::<Vec<_>>();
+            format!("[{}]", weights.join(", "))
+        }
+    }
+}
+
+/// A TOML line without its comment; the keys edited here have no strings
+/// that could contain a `#`
+fn strip_comment(line: &str) -> &str {
+    line.split('#').next().unwrap_or_default()
+}
+
+/// Whether `line` starts a `[[fonts]]` entry
+fn is_fonts_header(line: &str) -> bool {
+    strip_comment(line).replace([' ', '\t'], "") == "[[fonts]]"
+}
+
+/// The bare or quoted key of a `key = value` line
+fn key_of(line: &str) -> Option<&str> {
+    let (key, _) = line.split_once('=')?;
+    let key = key.trim();
+    if key.starts_with('#') || key.starts_with('[') {
+        return None;
+    }
+    Some(key.trim_matches(['"', '\'']))
+}
+
 // add test
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::path::PathBuf;
+    use typst::layout::Ratio;
 
     #[test]
     fn test_serialize_fonts_to_toml() {
         let fonts_config = FontConfig {
             font_dir: Some("fonts".into()),
+            font_format: None,
+            redistribution: None,
+            pack: Vec::new(),
+            package_fonts: None,
+            quarantine: None,
+            root: None,
+            file_choices: BTreeMap::new(),
+            source_priority: Vec::new(),
+            ignore_families: Vec::new(),
             fonts: vec![
                 TypstFont {
                     family_name: "Arial".to_string(),
@@ -239,6 +1005,265 @@ weight = [500, 700]
 
         assert_eq!(font_config.fonts, expected_fonts);
         assert_eq!(font_config.font_dir, None);
+        assert_eq!(font_config.font_format, None);
+    }
+
+    #[test]
+    fn test_stretch_accepts_names_and_percentages() {
+        let stretch = |value: &str| {
+            let toml_string = format!("[[fonts]]\nfamily_name = \"A\"\nstretch = {value}\n");
+            deserialize_fonts_from_toml(&toml_string).map(|config| config.fonts[0].stretch)
+        };
+        assert_eq!(stretch("\"condensed\"").unwrap(), FontStretch::CONDENSED);
+        assert_eq!(
+            stretch("\"Semi-Expanded\"").unwrap(),
+            FontStretch::SEMI_EXPANDED
+        );
+        assert_eq!(
+            stretch("\"ultra_condensed\"").unwrap(),
+            FontStretch::ULTRA_CONDENSED
+        );
+        assert_eq!(
+            stretch("\"extraexpanded\"").unwrap(),
+            FontStretch::EXTRA_EXPANDED
+        );
+        assert_eq!(stretch("\"87.5%\"").unwrap(), FontStretch::SEMI_CONDENSED);
+        assert_eq!(stretch("\"200%\"").unwrap(), FontStretch::ULTRA_EXPANDED);
+        assert_eq!(stretch("1250").unwrap(), FontStretch::EXPANDED);
+        assert_eq!(stretch("\"90%\"").unwrap().to_ratio(), Ratio::new(0.9),);
+
+        for invalid in ["\"narrow\"", "\"40%\"", "\"wide%\"", "2500", "0"] {
+            assert!(stretch(invalid).is_err(), "{invalid}");
+        }
+        let error = format!("{:#}", stretch("\"narrow\"").unwrap_err());
+        assert!(error.contains("semi-condensed"), "{error}");
+    }
+
+    #[test]
+    fn test_deserialize_font_format_policy() {
+        let font_config = deserialize_fonts_from_toml(
+            r#"font_format = "ttf"
+
+[[fonts]]
+family_name = "Noto Sans"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(font_config.font_format, Some(FontFormat::Ttf));
+        assert_eq!(
+            FontFormat::of_path(Path::new("fonts/NotoSans-Regular.OTF")),
+            Some(FontFormat::Otf)
+        );
+        assert_eq!(FontFormat::of_path(Path::new("fonts/NotoSans.ttc")), None);
+    }
+
+    #[test]
+    fn test_deserialize_redistribution_policy() {
+        let font_config =
+            deserialize_fonts_from_toml("redistribution = \"deny\"\nfonts = []\n").unwrap();
+        assert_eq!(font_config.redistribution, Some(RedistributionPolicy::Deny));
+        assert!(deserialize_fonts_from_toml("redistribution = \"never\"\nfonts = []\n").is_err());
+    }
+
+    #[test]
+    fn test_deserialize_pack_names() {
+        let config = deserialize_fonts_from_toml(r#"pack = "academic-basic""#).unwrap();
+        assert_eq!(config.pack, ["academic-basic"]);
+        assert!(config.fonts.is_empty());
+
+        let config = deserialize_fonts_from_toml(
+            r#"pack = ["academic-basic", "cjk-complete"]
+[[fonts]]
+family_name = "Inter"
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.pack, ["academic-basic", "cjk-complete"]);
+        assert_eq!(config.fonts.len(), 1);
+    }
+
+    #[test]
+    fn test_deserialize_file_choices() {
+        let font_config = deserialize_fonts_from_toml(
+            r#"[[fonts]]
+family_name = "Noto Sans"
+weight = [400, 700]
+prefer_file = "NotoSans[wght].ttf"
+avoid_file_glob = "*Hinted*"
+
+[[fonts]]
+family_name = "Lato"
+"#,
+        )
+        .unwrap();
+        assert_eq!(font_config.fonts.len(), 3);
+        assert_eq!(font_config.file_choices.len(), 2);
+
+        let choice = &font_config.file_choices[&font_config.fonts[1]];
+        assert!(choice.prefers(Path::new("noto/NotoSans[wght].ttf")));
+        assert!(!choice.prefers(Path::new("noto/OldNotoSans[wght].ttf")));
+        assert!(choice.avoids(Path::new("hinted/NotoSans-Hinted-Bold.ttf")));
+        assert!(!choice.avoids(Path::new("unhinted/NotoSans-Bold.ttf")));
+
+        assert!(
+            deserialize_fonts_from_toml(
+                "[[fonts]]\nfamily_name = \"Lato\"\navoid_file_glob = \"[\"\n"
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_deserialize_ignore_families() {
+        let font_config = deserialize_fonts_from_toml(
+            "ignore_families = [\"Noto Color Emoji\"]\n\n[[fonts]]\nfamily_name = \"Lato\"\n",
+        )
+        .unwrap();
+        assert!(font_config.ignores_family("noto color emoji"));
+        assert!(!font_config.ignores_family("Lato"));
+
+        let error = deserialize_fonts_from_toml(
+            "ignore_families = [\"Lato\"]\n\n[[fonts]]\nfamily_name = \"Lato\"\n",
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("ignore_families"), "{error}");
+    }
+
+    #[test]
+    fn test_deserialize_source_priority() {
+        let font_config = deserialize_fonts_from_toml(
+            r#"[[fonts]]
+family_name = "Noto Sans CJK SC"
+
+[[source_priority]]
+source = "github:gooduser/font_lib"
+priority = 5
+
+[[source_priority]]
+source = "github:corp/cjk-fonts"
+priority = 10
+families = ["Noto Sans CJK*", "Source Han *"]
+"#,
+        )
+        .unwrap();
+        let rules = &font_config.source_priority;
+        assert_eq!(rules.len(), 2);
+        assert!(!rules[0].matches_family("Noto Sans CJK SC"));
+        assert!(rules[1].matches_family("noto sans cjk sc"));
+        assert!(!rules[1].matches_family("Noto Sans"));
+    }
+
+    #[test]
+    fn test_expand_variables() {
+        let root = Path::new("/work/paper");
+        let env = |name: &str| match name {
+            "HOME" => Some("/home/u".to_string()),
+            "FONT_SHARE" => Some("/mnt/fonts".to_string()),
+            _ => None,
+        };
+        let expand = |value| expand_variables(value, root, env);
+        assert_eq!(
+            expand("${PROJECT_ROOT}/fonts").unwrap(),
+            "/work/paper/fonts"
+        );
+        assert_eq!(expand("${HOME}/.fonts").unwrap(), "/home/u/.fonts");
+        assert_eq!(
+            expand("local:${env:FONT_SHARE}/cjk").unwrap(),
+            "local:/mnt/fonts/cjk"
+        );
+        assert_eq!(expand("costs $$5").unwrap(), "costs $5");
+        assert_eq!(expand("fonts").unwrap(), "fonts");
+
+        assert!(expand("${env:MISSING}").unwrap_err().contains("MISSING"));
+        assert!(
+            expand("${ROOT}")
+                .unwrap_err()
+                .contains("Undefined variable ${ROOT}")
+        );
+        assert!(expand("${HOME").unwrap_err().contains("Unterminated"));
+        assert!(expand("$HOME").is_err());
+    }
+
+    #[test]
+    fn test_config_variables_are_expanded() {
+        let config = r#"font_dir = "${env:SHARE}/fonts"
+
+[[source_priority]]
+source = "local:${PROJECT_ROOT}/vendor"
+priority = 1
+"#;
+        let mut font_config = deserialize_fonts_from_toml(config).unwrap();
+        font_config
+            .expand_variables(Path::new("/work/paper/"), |_| Some("/srv".to_string()))
+            .unwrap();
+        assert_eq!(font_config.font_dir.as_deref(), Some("/srv/fonts"));
+        assert_eq!(
+            font_config.source_priority[0].source,
+            "local:/work/paper/vendor"
+        );
+
+        let error = deserialize_fonts_from_toml(config)
+            .unwrap()
+            .expand_variables(Path::new("/"), |_| None)
+            .unwrap_err();
+        assert!(error.ends_with("in font_dir"), "{error}");
+    }
+
+    #[test]
+    fn test_config_inherits_from_parent_directories() {
+        let dir = std::env::temp_dir().join(format!("typfont-inherit-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join(".git")).unwrap();
+        std::fs::create_dir_all(dir.join("papers/thesis")).unwrap();
+        let write = |path: &str, content: &str| std::fs::write(dir.join(path), content).unwrap();
+        write(
+            "font_config.toml",
+            "font_format = \"otf\"\nquarantine = true\nignore_families = [\"Noto Color Emoji\"]\n\n[[source_priority]]\nsource = \"local:${PROJECT_ROOT}/vendor\"\npriority = 1\n",
+        );
+        write(
+            "papers/font_config.toml",
+            "redistribution = \"deny\"\nquarantine = false\nignore_families = [\"Lato\"]\n\n[[fonts]]\nfamily_name = \"Inter\"\n",
+        );
+        write(
+            "papers/thesis/font_config.toml",
+            "font_dir = \"fonts\"\n\n[[source_priority]]\nsource = \"github:owner/fonts\"\npriority = 2\n\n[[fonts]]\nfamily_name = \"Lato\"\n",
+        );
+
+        let thesis = dir.join("papers/thesis/font_config.toml");
+        assert_eq!(
+            parent_config_files(&thesis),
+            [
+                dir.join("papers/font_config.toml"),
+                dir.join("font_config.toml")
+            ]
+        );
+        let config = deserialize_fonts_from_file(&thesis).unwrap();
+        assert_eq!(config.font_format, Some(FontFormat::Otf));
+        assert_eq!(config.redistribution, Some(RedistributionPolicy::Deny));
+        // The nearest config decides
+        assert_eq!(config.quarantine, Some(false));
+        // Lato is required by the thesis itself
+        assert_eq!(config.ignore_families, ["Noto Color Emoji"]);
+        let sources = config
+            .source_priority
+            .iter()
+            .map(|rule| rule.source.clone())
+            .collect::<Vec<_>>();
+        // Variables expand relative to the config that uses them
+        let vendor = format!(
+            "local:{}/vendor",
+            std::path::absolute(&dir).unwrap().display()
+        );
+        assert_eq!(sources, ["github:owner/fonts".to_string(), vendor]);
+        // The fonts of parent projects are their own
+        assert_eq!(config.fonts.len(), 1);
+
+        write("papers/font_config.toml", "root = true\n");
+        let config = deserialize_fonts_from_file(&thesis).unwrap();
+        assert_eq!(config.font_format, None);
+        assert_eq!(config.source_priority.len(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 
     #[test]
@@ -267,4 +1292,101 @@ weight = [500, 700]
         assert_eq!(font_config.fonts, expected_fonts);
         assert_eq!(font_config.font_dir.unwrap(), "fonts".to_string());
     }
+
+    #[test]
+    fn test_add_font_entry_merges_and_keeps_comments() {
+        let content = "# Fonts of the thesis\nfont_dir = \"fonts\"\n\n[[fonts]]\nfamily_name = \"Noto Sans\" # body text\nweight = [\n    400, # regular\n    700,\n] # headings\n\n[[fonts]]\nfamily_name = \"Lato\"\nstyle = \"Italic\"\n";
+
+        let (edited, edit) = add_font_entry(
+            content,
+            "noto sans",
+            FontStyle::Normal,
+            FontStretch::NORMAL,
+            &[300, 700],
+        )
+        .unwrap();
+        assert_eq!(edit, ConfigEdit::Merged(vec![300]));
+        assert_eq!(
+            edited,
+            "# Fonts of the thesis\nfont_dir = \"fonts\"\n\n[[fonts]]\nfamily_name = \"Noto Sans\" # body text\nweight = [300, 400, 700] # headings\n\n[[fonts]]\nfamily_name = \"Lato\"\nstyle = \"Italic\"\n"
+        );
+        let (_, edit) = add_font_entry(
+            &edited,
+            "Noto Sans",
+            FontStyle::Normal,
+            FontStretch::NORMAL,
+            &[400],
+        )
+        .unwrap();
+        assert_eq!(edit, ConfigEdit::Unchanged);
+
+        // An entry without a weight requires 400
+        let (edited, edit) = add_font_entry(
+            &edited,
+            "Lato",
+            FontStyle::Italic,
+            FontStretch::NORMAL,
+            &[700],
+        )
+        .unwrap();
+        assert_eq!(edit, ConfigEdit::Merged(vec![700]));
+        assert!(
+            edited.ends_with("family_name = \"Lato\"\nweight = [400, 700]\nstyle = \"Italic\"\n")
+        );
+
+        let (edited, edit) = add_font_entry(
+            &edited,
+            "Lato",
+            FontStyle::Normal,
+            FontStretch::CONDENSED,
+            &[700, 400],
+        )
+        .unwrap();
+        assert_eq!(edit, ConfigEdit::Appended);
+        assert!(edited.ends_with(
+            "style = \"Italic\"\n\n[[fonts]]\nfamily_name = \"Lato\"\nstyle = \"Normal\"\nweight = [400, 700]\nstretch = 750\n"
+        ));
+        assert_eq!(deserialize_fonts_from_toml(&edited).unwrap().fonts.len(), 7);
+
+        let inline = "fonts = [{ family_name = \"Lato\" }]\n";
+        assert!(
+            add_font_entry(
+                inline,
+                "Lato",
+                FontStyle::Normal,
+                FontStretch::NORMAL,
+                &[700]
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_remove_font_entries_keeps_other_entries_and_comments() {
+        let content = "font_dir = \"fonts\"\n\n# Body text\n[[fonts]]\nfamily_name = \"Lato\"\nweight = [400, 700] # regular and bold\n\n[[fonts]]\nfamily_name = \"Lato\"\nstyle = \"Italic\"\nweight = 700\n\n# Headings\n[[fonts]]\nfamily_name = \"Noto Sans\"\n";
+
+        let (edited, removed) = remove_font_entries(content, "lato", None, &[700]).unwrap();
+        let weights = removed
+            .iter()
+            .map(|font| (font.style, font.weight.to_number()));
+        assert_eq!(
+            weights.collect::<Vec<_>>(),
+            [(FontStyle::Normal, 700), (FontStyle::Italic, 700)]
+        );
+        assert_eq!(
+            edited,
+            "font_dir = \"fonts\"\n\n# Body text\n[[fonts]]\nfamily_name = \"Lato\"\nweight = 400 # regular and bold\n\n# Headings\n[[fonts]]\nfamily_name = \"Noto Sans\"\n"
+        );
+
+        let (edited, removed) = remove_font_entries(&edited, "Noto Sans", None, &[]).unwrap();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(
+            edited,
+            "font_dir = \"fonts\"\n\n# Body text\n[[fonts]]\nfamily_name = \"Lato\"\nweight = 400 # regular and bold\n"
+        );
+        let (unchanged, removed) =
+            remove_font_entries(&edited, "Lato", Some(FontStyle::Italic), &[]).unwrap();
+        assert!(removed.is_empty());
+        assert_eq!(unchanged, edited);
+    }
 }
@@ -0,0 +1,408 @@
+//! Fonts embedded in a PDF file: for `report --pdf`, which fonts a compiled
+//! document actually uses, checked against the fonts the project requires, and
+//! for `extract --pdf`, the complete font programs to recover into a library.
+
+use crate::color::Colorize;
+use lopdf::{Dictionary, Document, Object};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::DiscoveredFont;
+use crate::parse_font_config::{FontFormat, TypstFont};
+use crate::table::Table;
+
+/// A font as the PDF records it
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct PdfFont {
+    /// The PostScript name, without the tag of a subset
+    pub(crate) name: String,
+    /// Whether only the used glyphs are embedded
+    pub(crate) subset: bool,
+    /// The family name from the embedded font program, or else the font descriptor
+    pub(crate) family: Option<String>,
+    /// The font program, if the font is embedded
+    pub(crate) program: Option<Vec<u8>>,
+}
+
+/// The fonts of the PDF at `path`, by PostScript name
+pub(crate) fn read_pdf_fonts(path: &Path) -> Result<Vec<PdfFont>, String> {
+    let document = Document::load(path).map_err(|e| format!("Failed to read {path:?}: {e}"))?;
+    if document.is_encrypted() {
+        return Err(format!("{path:?} is encrypted"));
+    }
+
+    let mut fonts = BTreeMap::new();
+    for object in document.objects.values() {
+        let Some(dict) = dictionary(object) else {
+            continue;
+        };
+        if name(dict, b"Type") == Some("FontDescriptor") {
+            if let Some(font) = descriptor_font(&document, dict) {
+                fonts.insert(font.name.clone(), font);
+            }
+        } else if name(dict, b"Type") == Some("Font") && !dict.has(b"FontDescriptor") {
+            // Only fonts with a descriptor can be embedded; the standard 14
+            // fonts and composite fonts, whose descendants have one, are left
+            let is_composite = name(dict, b"Subtype") == Some("Type0");
+            if let (Some(base_font), false) = (name(dict, b"BaseFont"), is_composite) {
+                let (name, subset) = strip_subset_tag(base_font);
+                fonts.entry(name.to_string()).or_insert(PdfFont {
+                    name: name.to_string(),
+                    subset,
+                    family: None,
+                    program: None,
+                });
+            }
+        }
+    }
+    Ok(fonts.into_values().collect())
+}
+
+fn dictionary(object: &Object) -> Option<&Dictionary> {
+    match object {
+        Object::Dictionary(dict) => Some(dict),
+        Object::Stream(stream) => Some(&stream.dict),
+        _ => None,
+    }
+}
+
+fn name<'a>(dict: &'a Dictionary, key: &[u8]) -> Option<&'a str> {
+    dict.get(key)
+        .ok()?
+        .as_name()
+        .ok()
+        .and_then(|name| std::str::from_utf8(name).ok())
+}
+
+fn descriptor_font(document: &Document, descriptor: &Dictionary) -> Option<PdfFont> {
+    let (name, subset) = strip_subset_tag(self::name(descriptor, b"FontName")?);
+    let program = [&b"FontFile"[..], b"FontFile2", b"FontFile3"]
+        .into_iter()
+        .find_map(|key| {
+            let (_, object) = document.dereference(descriptor.get(key).ok()?).ok()?;
+            let stream = object.as_stream().ok()?;
+            stream
+                .decompressed_content()
+                .ok()
+                .or_else(|| Some(stream.content.clone()))
+        });
+    let family = program.as_deref().and_then(program_family).or_else(|| {
+        match descriptor.get(b"FontFamily").ok()? {
+            Object::String(family, _) => Some(String::from_utf8_lossy(family).into_owned()),
+            _ => None,
+        }
+    });
+    Some(PdfFont {
+        name: name.to_string(),
+        subset,
+        family,
+        program,
+    })
+}
+
+/// The family in the name table of an OpenType program. Bare CFF and Type 1
+/// programs have none.
+fn program_family(program: &[u8]) -> Option<String> {
+    let face = ttf_parser::Face::parse(program, 0).ok()?;
+    let names = face.names();
+    [
+        ttf_parser::name_id::TYPOGRAPHIC_FAMILY,
+        ttf_parser::name_id::FAMILY,
+    ]
+    .into_iter()
+    .find_map(|id| {
+        names
+            .into_iter()
+            .filter(|name| name.name_id == id && name.is_unicode())
+            .find_map(|name| name.to_string())
+    })
+}
+
+/// `ABCDEF+Name` is a subset of `Name`
+fn strip_subset_tag(name: &str) -> (&str, bool) {
+    match name.split_once('+') {
+        Some((tag, rest)) if tag.len() == 6 && tag.bytes().all(|b| b.is_ascii_uppercase()) => {
+            (rest, true)
+        }
+        _ => (name, false),
+    }
+}
+
+/// fsType bits of the OS/2 table that forbid installing an embedded font:
+/// restricted license and preview & print embedding
+const FS_TYPE_NO_INSTALL: u16 = 0x0002 | 0x0004;
+/// fsType bit that allows editable embedding, which permits installing
+const FS_TYPE_EDITABLE: u16 = 0x0008;
+
+/// The program of `font` and its file format, if it can be extracted into a
+/// library: it must be embedded in full as an OpenType program whose license
+/// (the OS/2 fsType) allows installing it. Otherwise the reason why not.
+pub(crate) fn extractable_program(font: &PdfFont) -> Result<(&[u8], FontFormat), String> {
+    let program = font.program.as_deref().ok_or("not embedded")?;
+    if font.subset {
+        return Err("only a subset is embedded".to_string());
+    }
+    let face = ttf_parser::Face::parse(program, 0)
+        .map_err(|_| "not an OpenType program (bare CFF or Type 1)".to_string())?;
+    let fs_type = face
+        .raw_face()
+        .table(ttf_parser::Tag::from_bytes(b"OS/2"))
+        .and_then(|os2| os2.get(8..10))
+        .map_or(0, |bytes| u16::from_be_bytes([bytes[0], bytes[1]]));
+    if fs_type & FS_TYPE_NO_INSTALL != 0 && fs_type & FS_TYPE_EDITABLE == 0 {
+        return Err(format!(
+            "its license doesn't allow installing it (fsType {fs_type:#06x})"
+        ));
+    }
+    let format = if face.tables().cff.is_some() || face.tables().cff2.is_some() {
+        FontFormat::Otf
+    } else {
+        FontFormat::Ttf
+    };
+    Ok((program, format))
+}
+
+/// The file name for an extracted font, from its PostScript name
+pub(crate) fn extracted_file_name(font: &PdfFont, format: FontFormat) -> String {
+    let stem = font
+        .name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    format!("{stem}.{}", format.extension())
+}
+
+/// The PostScript name of the face at `index` of a font file
+pub(crate) fn postscript_name(data: &[u8], index: u32) -> Option<String> {
+    let face = ttf_parser::Face::parse(data, index).ok()?;
+    face.names()
+        .into_iter()
+        .filter(|name| name.name_id == ttf_parser::name_id::POST_SCRIPT_NAME)
+        .find_map(|name| name.to_string())
+}
+
+/// How a font of the PDF relates to the project
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Usage<'a> {
+    /// One of the project's installed fonts, satisfying these required fonts
+    Installed(Vec<&'a TypstFont>),
+    /// Of a required family, but not one of the installed files
+    RequiredFamily(&'a str),
+    /// A font Typst fell back to
+    Unexpected,
+}
+
+/// Classify `font` against the installed fonts, given with their PostScript
+/// names, and the required fonts
+pub(crate) fn classify<'a>(
+    font: &PdfFont,
+    installed: &'a [(String, DiscoveredFont)],
+    required: &'a [TypstFont],
+) -> Usage<'a> {
+    if let Some((_, entry)) = installed.iter().find(|(name, _)| *name == font.name) {
+        let satisfied = required
+            .iter()
+            .filter(|required| crate::font_manager::font_entry_satisfies(entry, required))
+            .collect::<Vec<_>>();
+        if !satisfied.is_empty() {
+            return Usage::Installed(satisfied);
+        }
+    }
+    font.family
+        .as_deref()
+        .and_then(|family| {
+            required
+                .iter()
+                .find(|required| required.family_name.eq_ignore_ascii_case(family))
+        })
+        .map_or(Usage::Unexpected, |required| {
+            Usage::RequiredFamily(&required.family_name)
+        })
+}
+
+/// Print the fonts of the PDF and how each relates to the project, followed by
+/// the required fonts the PDF doesn't use. Returns the number of unexpected fonts.
+pub(crate) fn print_pdf_report(
+    fonts: &[PdfFont],
+    installed: &[(String, DiscoveredFont)],
+    required: &[TypstFont],
+) -> usize {
+    let mut table = Table::new(vec!["PDF font", "Family", "Embedded", "Status"]);
+    let mut used = Vec::new();
+    let mut unexpected = 0;
+    for font in fonts {
+        let embedded = match (&font.program, font.subset) {
+            (None, _) => "no",
+            (Some(_), true) => "subset",
+            (Some(_), false) => "full",
+        };
+        let status = match classify(font, installed, required) {
+            Usage::Installed(satisfied) => {
+                used.extend(satisfied);
+                "required".green()
+            }
+            Usage::RequiredFamily(family) => {
+                used.extend(required.iter().filter(|font| font.family_name == family));
+                "required family, not from the font directory".yellow()
+            }
+            Usage::Unexpected => {
+                unexpected += 1;
+                "not in font_config.toml".red()
+            }
+        };
+        table.push_row(vec![
+            font.name.as_str().into(),
+            font.family.as_deref().unwrap_or("-").into(),
+            embedded.into(),
+            status,
+        ]);
+    }
+    println!("{}", table.render());
+
+    let unused = required
+        .iter()
+        .filter(|font| !used.contains(font))
+        .collect::<Vec<_>>();
+    if !unused.is_empty() {
+        println!("\nRequired fonts not used by the PDF:");
+        for font in unused {
+            println!("  - {}", font.to_string().dimmed());
+        }
+    }
+    unexpected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process_font::FontMetadata;
+    use std::path::PathBuf;
+    use typst::text::{FontStretch, FontStyle, FontWeight};
+
+    fn font(family: &str) -> TypstFont {
+        TypstFont {
+            family_name: family.to_string(),
+            style: FontStyle::Normal,
+            weight: FontWeight::REGULAR,
+            stretch: FontStretch::NORMAL,
+        }
+    }
+
+    fn pdf_font(name: &str, family: Option<&str>) -> PdfFont {
+        PdfFont {
+            name: name.to_string(),
+            subset: true,
+            family: family.map(str::to_string),
+            program: None,
+        }
+    }
+
+    /// A PDF embedding `program` in full as `name`
+    pub(crate) fn write_test_pdf(path: &Path, name: &str, program: Vec<u8>) {
+        use lopdf::{Stream, dictionary};
+        let mut document = Document::with_version("1.7");
+        let program = document.add_object(Stream::new(dictionary! {}, program));
+        let descriptor = document.add_object(dictionary! {
+            "Type" => "FontDescriptor",
+            "FontName" => Object::Name(name.as_bytes().to_vec()),
+            "FontFile2" => program,
+        });
+        document.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "TrueType",
+            "BaseFont" => Object::Name(name.as_bytes().to_vec()),
+            "FontDescriptor" => descriptor,
+        });
+        document.save(path).unwrap();
+    }
+
+    #[test]
+    fn test_read_fonts_of_a_pdf() {
+        let Ok(data) = std::fs::read("/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf") else {
+            return;
+        };
+        let path = std::env::temp_dir().join(format!("typfont-pdf-{}.pdf", std::process::id()));
+        write_test_pdf(&path, "DejaVuSans", data.clone());
+
+        let fonts = read_pdf_fonts(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(fonts.len(), 1);
+        assert_eq!(fonts[0].name, "DejaVuSans");
+        assert!(!fonts[0].subset);
+        assert_eq!(fonts[0].family.as_deref(), Some("DejaVu Sans"));
+        assert_eq!(fonts[0].program.as_deref(), Some(&data[..]));
+    }
+
+    #[test]
+    fn test_only_complete_licensed_programs_are_extractable() {
+        let Ok(data) = std::fs::read("/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf") else {
+            return;
+        };
+        let mut font = PdfFont {
+            name: "DejaVu/Sans".to_string(),
+            subset: false,
+            family: None,
+            program: Some(data),
+        };
+        let (_, format) = extractable_program(&font).unwrap();
+        assert_eq!(format, FontFormat::Ttf);
+        assert_eq!(extracted_file_name(&font, format), "DejaVu_Sans.ttf");
+
+        // Restricted license embedding
+        let os2 = ttf_parser::RawFace::parse(font.program.as_ref().unwrap(), 0)
+            .unwrap()
+            .table(ttf_parser::Tag::from_bytes(b"OS/2"))
+            .unwrap()
+            .as_ptr() as usize
+            - font.program.as_ref().unwrap().as_ptr() as usize;
+        font.program.as_mut().unwrap()[os2 + 8..os2 + 10].copy_from_slice(&[0, 2]);
+        assert!(extractable_program(&font).unwrap_err().contains("license"));
+
+        font.subset = true;
+        assert_eq!(
+            extractable_program(&font).unwrap_err(),
+            "only a subset is embedded"
+        );
+        font.program = None;
+        assert_eq!(extractable_program(&font).unwrap_err(), "not embedded");
+    }
+
+    #[test]
+    fn test_strip_subset_tag() {
+        assert_eq!(strip_subset_tag("ITEVGX+DejaVuSans"), ("DejaVuSans", true));
+        assert_eq!(strip_subset_tag("DejaVuSans"), ("DejaVuSans", false));
+        assert_eq!(strip_subset_tag("abc+Name"), ("abc+Name", false));
+    }
+
+    #[test]
+    fn test_classify_pdf_fonts() {
+        let installed = [(
+            "DejaVuSans".to_string(),
+            DiscoveredFont {
+                font: font("DejaVu Sans"),
+                path: PathBuf::from("fonts/DejaVuSans.ttf"),
+                index: 0,
+                axes: Vec::new(),
+                metadata: FontMetadata::default(),
+            },
+        )];
+        let required = [font("DejaVu Sans"), font("Lato")];
+
+        let usage = classify(&pdf_font("DejaVuSans", None), &installed, &required);
+        assert_eq!(usage, Usage::Installed(vec![&required[0]]));
+        let usage = classify(
+            &pdf_font("Lato-Regular", Some("Lato")),
+            &installed,
+            &required,
+        );
+        assert_eq!(usage, Usage::RequiredFamily("Lato"));
+        let usage = classify(&pdf_font("NewCMMath-Book", None), &installed, &required);
+        assert_eq!(usage, Usage::Unexpected);
+    }
+}
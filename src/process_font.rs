@@ -18,7 +18,7 @@ use std::path::Path;
 use typst::text::FontInfo;
 //use typst::text::{Font};
 
-/// Holds details about the location of a font and lazily the font itself.
+// Holds details about the location of a font and lazily the font itself.
 // #[derive(Debug)]
 // pub struct FontSlot {
 //     /// The path at which the font can be found on the system.
@@ -61,11 +61,350 @@ use typst::text::FontInfo;
 //     }
 // }
 
+/// A capability of a face that library consumers can filter on.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    serde::Serialize,
+    serde::Deserialize,
+    clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum FontFeature {
+    /// Has an OpenType MATH table and can typeset equations.
+    Math,
+    /// Has color glyphs (COLR, SVG, CBDT or sbix tables).
+    Color,
+    /// Has variation axes.
+    Variable,
+    /// All glyphs have the same advance width.
+    Monospace,
+}
+
+impl FontFeature {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Math => "math",
+            Self::Color => "color",
+            Self::Variable => "variable",
+            Self::Monospace => "monospace",
+        }
+    }
+}
+
+/// Typographic category of a face.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+    clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum FontClass {
+    Serif,
+    Sans,
+    Script,
+    Decorative,
+    Symbol,
+}
+
+impl FontClass {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Serif => "serif",
+            Self::Sans => "sans",
+            Self::Script => "script",
+            Self::Decorative => "decorative",
+            Self::Symbol => "symbol",
+        }
+    }
+
+    /// The class given by the OS/2 `sFamilyClass` field, or else by the PANOSE
+    /// family type and serif style, both in the OS/2 table `os2`.
+    pub fn from_os2(os2: &[u8]) -> Option<Self> {
+        let class = match os2.get(30)? {
+            1..=5 | 7 => Some(Self::Serif),
+            8 => Some(Self::Sans),
+            9 => Some(Self::Decorative),
+            10 => Some(Self::Script),
+            12 => Some(Self::Symbol),
+            _ => None,
+        };
+        class.or_else(|| match (os2.get(32)?, os2.get(33)?) {
+            (2, 11..=13 | 15) => Some(Self::Sans),
+            (2, 2..=10 | 14) => Some(Self::Serif),
+            (3, _) => Some(Self::Script),
+            (4, _) => Some(Self::Decorative),
+            (5, _) => Some(Self::Symbol),
+            _ => None,
+        })
+    }
+
+    /// A guess from the family name, for fonts whose tables aren't available
+    /// or don't declare a class.
+    pub fn from_family_name(family: &str) -> Option<Self> {
+        let words = family
+            .split(|c: char| !c.is_alphanumeric())
+            .map(str::to_ascii_lowercase)
+            .collect::<Vec<_>>();
+        let has = |names: &[&str]| words.iter().any(|word| names.contains(&word.as_str()));
+        if has(&[
+            "sans",
+            "grotesk",
+            "grotesque",
+            "gothic",
+            "heros",
+            "helvetica",
+            "arial",
+        ]) {
+            Some(Self::Sans)
+        } else if has(&[
+            "serif", "roman", "antiqua", "mincho", "song", "times", "termes",
+        ]) {
+            Some(Self::Serif)
+        } else if has(&["script", "hand", "handwriting", "brush", "calligraphy"]) {
+            Some(Self::Script)
+        } else if has(&["math", "symbol", "symbols", "emoji", "icons"]) {
+            Some(Self::Symbol)
+        } else {
+            None
+        }
+    }
+}
+
+/// The kind of license a face declares in its name table.
+#[derive(
+    Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum FontLicense {
+    /// The SIL Open Font License
+    Ofl,
+    Apache,
+    /// The Ubuntu Font Licence
+    Ufl,
+    Gpl,
+    Mit,
+    /// Dedicated to the public domain, e.g. under CC0
+    PublicDomain,
+    /// A license that isn't recognized as a free one
+    Proprietary,
+}
+
+impl FontLicense {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Ofl => "ofl",
+            Self::Apache => "apache",
+            Self::Ufl => "ufl",
+            Self::Gpl => "gpl",
+            Self::Mit => "mit",
+            Self::PublicDomain => "public-domain",
+            Self::Proprietary => "proprietary",
+        }
+    }
+
+    /// The license named by the license description and license URL entries of
+    /// the name table. Text that doesn't name a known free license counts as
+    /// proprietary, so that it is never published by mistake.
+    pub fn from_names(description: Option<&str>, url: Option<&str>) -> Option<Self> {
+        let text = [description, url]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase();
+        if text.trim().is_empty() {
+            return None;
+        }
+        let mentions = |names: &[&str]| names.iter().any(|name| text.contains(name));
+        Some(
+            if mentions(&["open font license", "sil.org/ofl", "openfontlicense.org"]) {
+                Self::Ofl
+            } else if mentions(&["apache license", "apache.org/licenses"]) {
+                Self::Apache
+            } else if mentions(&["ubuntu font licence", "ubuntu font license"]) {
+                Self::Ufl
+            } else if mentions(&["general public license", "gnu.org/licenses/gpl"]) {
+                Self::Gpl
+            } else if mentions(&["mit license", "opensource.org/licenses/mit"]) {
+                Self::Mit
+            } else if mentions(&["cc0", "creativecommons.org/publicdomain"]) {
+                Self::PublicDomain
+            } else {
+                Self::Proprietary
+            },
+        )
+    }
+}
+
+/// The embedding permission of a face, from the low bits of the OS/2 `fsType`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Embedding {
+    /// May be embedded and permanently installed
+    Installable,
+    /// May be embedded in documents that are edited
+    Editable,
+    /// May be embedded in documents that are only viewed and printed
+    PreviewOnly,
+    /// Must not be embedded at all
+    Restricted,
+}
+
+impl Embedding {
+    /// `fsType` bit: only bitmaps of the face may be embedded
+    pub const BITMAP_ONLY: u16 = 0x0200;
+    /// `fsType` bit: the face must be embedded in full, not subset
+    pub const NO_SUBSETTING: u16 = 0x0100;
+
+    /// The permission given by `fs_type`. Of several bits set, the least
+    /// restrictive one wins, as the specification requires.
+    pub fn from_fs_type(fs_type: u16) -> Self {
+        if fs_type & 0x0008 != 0 {
+            Self::Editable
+        } else if fs_type & 0x0004 != 0 {
+            Self::PreviewOnly
+        } else if fs_type & 0x0002 != 0 {
+            Self::Restricted
+        } else {
+            Self::Installable
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Installable => "installable",
+            Self::Editable => "editable",
+            Self::PreviewOnly => "preview-only",
+            Self::Restricted => "restricted",
+        }
+    }
+
+    /// Whether `fs_type` forbids embedding the outlines of the face in a PDF
+    pub fn forbids_pdf_embedding(fs_type: u16) -> bool {
+        Self::from_fs_type(fs_type) == Self::Restricted || fs_type & Self::BITMAP_ONLY != 0
+    }
+
+    /// The permission and restrictions of `fs_type`, e.g. "editable, no subsetting"
+    pub fn describe(fs_type: u16) -> String {
+        let mut description = Self::from_fs_type(fs_type).name().to_string();
+        if fs_type & Self::NO_SUBSETTING != 0 {
+            description.push_str(", no subsetting");
+        }
+        if fs_type & Self::BITMAP_ONLY != 0 {
+            description.push_str(", bitmap only");
+        }
+        description
+    }
+}
+
+/// Naming details and capabilities of a face that [`FontInfo`] doesn't carry.
+#[derive(Clone, Debug, Default, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct FontMetadata {
+    /// The designer recorded in the name table.
+    pub designer: Option<String>,
+    /// The manufacturer (foundry) recorded in the name table.
+    pub manufacturer: Option<String>,
+    /// The four-character vendor ID from the OS/2 table.
+    pub vendor_id: Option<String>,
+    /// Capabilities of the face, in the order of [`FontFeature`].
+    pub features: Vec<FontFeature>,
+    /// The typographic category declared in the OS/2 table.
+    pub class: Option<FontClass>,
+    /// The PANOSE classification from the OS/2 table, unless it is left unset.
+    pub panose: Option<[u8; 10]>,
+    /// The license declared in the name table.
+    pub license: Option<FontLicense>,
+    /// The embedding permissions (`fsType`) from the OS/2 table.
+    pub fs_type: Option<u16>,
+}
+
+impl FontMetadata {
+    /// Read the metadata of the face at `index` in the font file `data`.
+    pub fn parse(data: &[u8], index: u32) -> Self {
+        let Ok(face) = ttf_parser::Face::parse(data, index) else {
+            return Self::default();
+        };
+
+        let name = |name_id| {
+            face.names()
+                .into_iter()
+                .filter(|name| name.name_id == name_id && name.is_unicode())
+                .find_map(|name| name.to_string())
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+        };
+
+        let os2 = face.raw_face().table(ttf_parser::Tag::from_bytes(b"OS/2"));
+        let vendor_id = os2
+            .and_then(|os2| os2.get(58..62))
+            .map(|vendor| String::from_utf8_lossy(vendor).trim().to_string())
+            .filter(|vendor| !vendor.is_empty() && vendor.chars().all(|c| c.is_ascii_graphic()));
+
+        // Color tables are detected by their presence alone, so that they count
+        // even when only the headers of a remote font were downloaded
+        let has_table = |tag| {
+            face.raw_face()
+                .table(ttf_parser::Tag::from_bytes(tag))
+                .is_some()
+        };
+        let features = [
+            (FontFeature::Math, face.tables().math.is_some()),
+            (
+                FontFeature::Color,
+                [b"COLR", b"SVG ", b"CBDT", b"sbix"]
+                    .into_iter()
+                    .any(has_table),
+            ),
+            (FontFeature::Variable, face.is_variable()),
+            (FontFeature::Monospace, face.is_monospaced()),
+        ]
+        .into_iter()
+        .filter_map(|(feature, present)| present.then_some(feature))
+        .collect();
+
+        Self {
+            designer: name(ttf_parser::name_id::DESIGNER),
+            manufacturer: name(ttf_parser::name_id::MANUFACTURER),
+            vendor_id,
+            features,
+            class: os2.and_then(FontClass::from_os2),
+            panose: os2
+                .and_then(|os2| os2.get(32..42))
+                .and_then(|panose| <[u8; 10]>::try_from(panose).ok())
+                .filter(|panose| panose.iter().any(|&digit| digit != 0)),
+            license: FontLicense::from_names(
+                name(ttf_parser::name_id::LICENSE).as_deref(),
+                name(ttf_parser::name_id::LICENSE_URL).as_deref(),
+            ),
+            fs_type: os2
+                .and_then(|os2| os2.get(8..10))
+                .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]])),
+        }
+    }
+}
+
 /// The result of a font search, created by calling [`FontSearcher::search`].
 #[derive(Debug)]
 pub struct Fonts {
     /// Metadata in discovery order.
     pub infos: Vec<FontInfo>,
+    /// Naming details, parallel to `infos`.
+    pub metadata: Vec<FontMetadata>,
+    /// Face indices inside their (collection) files, parallel to `infos`.
+    pub indices: Vec<u32>,
     ///// Slots that the fonts are loaded into.
     //pub fonts: Vec<FontSlot>,
 }
@@ -87,6 +426,8 @@ impl Fonts {
 pub struct FontSearcher {
     db: Database,
     infos: Vec<FontInfo>,
+    metadata: Vec<FontMetadata>,
+    indices: Vec<u32>,
     //fonts: Vec<FontSlot>,
 }
 
@@ -97,33 +438,35 @@ impl FontSearcher {
         Self {
             db: Database::new(),
             infos: vec![],
+            metadata: vec![],
+            indices: vec![],
             //fonts: vec![],
         }
     }
 
-    /// Start searching for and loading fonts. To additionally load fonts
-    /// from specific directories, use [`search_with`][Self::search_with].
-    ///
-    /// # Examples
-    /// ```no_run
-    /// # use typst_kit::fonts::FontSearcher;
-    /// let fonts = FontSearcher::new()
-    ///     .include_system_fonts(true)
-    ///     .search();
-    /// ```
+    // Start searching for and loading fonts. To additionally load fonts
+    // from specific directories, use [`search_with`][Self::search_with].
+    //
+    // # Examples
+    // ```no_run
+    // # use typst_kit::fonts::FontSearcher;
+    // let fonts = FontSearcher::new()
+    //     .include_system_fonts(true)
+    //     .search();
+    // ```
     // pub fn search(&mut self) -> Fonts {
     //     self.search_dirs::<_, &str>([])
     // }
 
-    /// Start searching for and loading fonts, with additional directories.
-    ///
-    /// # Examples
-    /// ```no_run
-    /// # use typst_kit::fonts::FontSearcher;
-    /// let fonts = FontSearcher::new()
-    ///     .include_system_fonts(true)
-    ///     .search_with(["./assets/fonts/"]);
-    /// ```
+    // Start searching for and loading fonts, with additional directories.
+    //
+    // # Examples
+    // ```no_run
+    // # use typst_kit::fonts::FontSearcher;
+    // let fonts = FontSearcher::new()
+    //     .include_system_fonts(true)
+    //     .search_with(["./assets/fonts/"]);
+    // ```
     // pub fn search_dirs<I, P>(&mut self, font_dirs: I) -> Fonts
     // where
     //     I: IntoIterator<Item=P>,
@@ -165,7 +508,14 @@ impl FontSearcher {
 
     pub fn search_file<P: AsRef<Path>>(&mut self, font_path: P) -> Fonts {
         // Font paths have the highest priority.
-        self.db.load_font_file(&font_path).unwrap();
+        // Unreadable files simply contain no fonts
+        if self.db.load_font_file(&font_path).is_err() {
+            return Fonts {
+                infos: Vec::new(),
+                metadata: Vec::new(),
+                indices: Vec::new(),
+            };
+        }
 
         for face in self.db.faces() {
             // let path = match &face.source {
@@ -175,13 +525,17 @@ impl FontSearcher {
             //     Source::Binary(_) => continue,
             // };
 
-            let info = self
+            let (info, metadata) = self
                 .db
-                .with_face_data(face.id, FontInfo::new)
+                .with_face_data(face.id, |data, index| {
+                    (FontInfo::new(data, index), FontMetadata::parse(data, index))
+                })
                 .expect("database must contain this font");
 
             if let Some(info) = info {
                 self.infos.push(info);
+                self.metadata.push(metadata);
+                self.indices.push(face.index);
                 // self.fonts.push(FontSlot {
                 //     path: Some(path.clone()),
                 //     index: face.index,
@@ -192,6 +546,8 @@ impl FontSearcher {
 
         Fonts {
             infos: std::mem::take(&mut self.infos),
+            metadata: std::mem::take(&mut self.metadata),
+            indices: std::mem::take(&mut self.indices),
             //fonts: std::mem::take(&mut self.fonts),
         }
     }
@@ -0,0 +1,265 @@
+//! Review of downloaded fonts before they enter the project, for policies that
+//! forbid unreviewed binaries in a repository. With `quarantine = true` in
+//! font_config.toml, or `update --quarantine`, fonts are installed into
+//! `.incoming/` in the font directory instead, recorded in a lockfile of its
+//! own and listed in a `SUMMARY.md` for the reviewer. `promote` moves the
+//! approved files into the font directory and their records into
+//! font_config.lock.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use typst::text::FontInfo;
+
+use crate::color::Colorize;
+use crate::lockfile::{FontLock, HashAlgo, LockedFile, SourceType, hash_files, lockfile_path};
+use crate::process_font::{FontLicense, FontMetadata};
+use crate::progress;
+use crate::redistribution;
+use crate::table::format_size;
+
+pub(crate) const INCOMING_DIR: &str = ".incoming";
+const INCOMING_LOCK: &str = "incoming.lock";
+const SUMMARY_FILE: &str = "SUMMARY.md";
+const GITIGNORE_FILE: &str = ".gitignore";
+
+/// The quarantine of the font directory `font_dir`
+pub(crate) fn incoming_dir(font_dir: &Path) -> PathBuf {
+    font_dir.join(INCOMING_DIR)
+}
+
+/// Create the quarantine of `font_dir`, with a `.gitignore` that keeps the
+/// files awaiting review out of the repository
+pub(crate) fn create_incoming_dir(font_dir: &Path) -> Result<(), String> {
+    let dir = incoming_dir(font_dir);
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create directory {dir:?}: {e}"))?;
+    let path = dir.join(GITIGNORE_FILE);
+    fs::write(&path, "*\n").map_err(|e| format!("Failed to write {path:?}: {e}"))
+}
+
+/// The lockfile recording the files awaiting review below `font_dir`
+pub(crate) fn incoming_lock_path(font_dir: &Path) -> PathBuf {
+    incoming_dir(font_dir).join(INCOMING_LOCK)
+}
+
+/// The records of the files awaiting review below `font_dir`
+pub(crate) fn awaiting(font_dir: &Path) -> Result<FontLock, String> {
+    FontLock::load(&incoming_lock_path(font_dir)).map(Option::unwrap_or_default)
+}
+
+/// The review summary of the files awaiting review below `font_dir`, as Markdown
+pub(crate) fn summary(font_dir: &Path, lock: &FontLock) -> String {
+    let mut summary = String::from("# Fonts awaiting review\n\n");
+    if lock.files.is_empty() {
+        summary.push_str("No fonts are awaiting review.\n");
+        return summary;
+    }
+    summary.push_str(
+        "Move the approved files into the font directory with `typfont promote --file FILE...`, \
+         or all of them with `typfont promote --all`.\n\n",
+    );
+    summary.push_str("| File | Faces | Source | License | Size | SHA-256 |\n");
+    summary.push_str("| --- | --- | --- | --- | --- | --- |\n");
+    for file in &lock.files {
+        let data = fs::read(incoming_dir(font_dir).join(&file.file)).unwrap_or_default();
+        let count = ttf_parser::fonts_in_collection(&data).unwrap_or(1);
+        let faces = (0..count)
+            .filter_map(|index| FontInfo::new(&data, index))
+            .map(|info| {
+                let variant = info.variant;
+                format!(
+                    "{} {:?} {}",
+                    info.family,
+                    variant.style,
+                    variant.weight.to_number()
+                )
+            })
+            .collect::<Vec<_>>();
+        let metadata = FontMetadata::parse(&data, 0);
+        let mut license = metadata
+            .license
+            .map_or("unknown", FontLicense::name)
+            .to_string();
+        if let Some(reason) = redistribution::restriction(&metadata) {
+            let _ = write!(license, ", may not be redistributed: {reason}");
+        }
+        let source = match &file.provenance {
+            Some(provenance) if provenance.source_type == SourceType::Mirror => {
+                format!("{} (local mirror)", file.source)
+            }
+            Some(provenance) => provenance.url.clone().unwrap_or(file.source.clone()),
+            None => file.source.clone(),
+        };
+        let _ = writeln!(
+            summary,
+            "| {} | {} | {source} | {license} | {} | `{}` |",
+            file.file,
+            if faces.is_empty() {
+                "unreadable".to_string()
+            } else {
+                faces.join("<br>")
+            },
+            format_size(data.len() as u64),
+            file.sha256
+        );
+    }
+    summary
+}
+
+/// Write the records and review summary of the files awaiting review below
+/// `font_dir`, or remove them once there are none
+pub(crate) fn write_summary(font_dir: &Path, lock: &FontLock) -> Result<(), String> {
+    let dir = incoming_dir(font_dir);
+    if lock.files.is_empty() {
+        let _ = fs::remove_file(dir.join(INCOMING_LOCK));
+        let _ = fs::remove_file(dir.join(SUMMARY_FILE));
+        let _ = fs::remove_file(dir.join(GITIGNORE_FILE));
+        // Left in place if anything else is in it
+        let _ = fs::remove_dir(&dir);
+        return Ok(());
+    }
+    create_incoming_dir(font_dir)?;
+    lock.save(&dir.join(INCOMING_LOCK))?;
+    let path = dir.join(SUMMARY_FILE);
+    fs::write(&path, summary(font_dir, lock)).map_err(|e| format!("Failed to write {path:?}: {e}"))
+}
+
+/// Tell where the files awaiting review are and how to promote them
+pub(crate) fn print_summary(font_dir: &Path, lock: &FontLock) {
+    if lock.files.is_empty() {
+        return;
+    }
+    progress!(
+        "\n{}",
+        format!(
+            "{} files await review in {:?}, see {SUMMARY_FILE}",
+            lock.files.len(),
+            incoming_dir(font_dir)
+        )
+        .yellow()
+        .bold()
+    );
+    for file in &lock.files {
+        progress!("  {} {}", "?".yellow(), file.file);
+    }
+    progress!("Approve them with `typfont promote --file FILE...` or `typfont promote --all`");
+}
+
+/// Move the `files` awaiting review below `font_dir`, or all of them if none are
+/// given, into the font directory, and their records into the lockfile of
+/// `config_file`. Every file must still have the hash recorded when it was
+/// installed, verified with `algo` where it is recorded, so that the reviewed
+/// bytes are the ones promoted.
+pub(crate) fn promote(
+    config_file: &Path,
+    font_dir: &Path,
+    files: &[String],
+    algo: HashAlgo,
+) -> Result<Vec<LockedFile>, String> {
+    let dir = incoming_dir(font_dir);
+    let mut incoming = awaiting(font_dir)?;
+    if let Some(unknown) = files
+        .iter()
+        .find(|name| !incoming.files.iter().any(|file| &file.file == *name))
+    {
+        return Err(format!("{unknown} isn't awaiting review in {dir:?}"));
+    }
+    let (promoted, kept): (Vec<_>, Vec<_>) = incoming
+        .files
+        .drain(..)
+        .partition(|file| files.is_empty() || files.contains(&file.file));
+    incoming.files = kept;
+
+    let jobs = promoted
+        .iter()
+        .map(|file| (dir.join(&file.file), file.recorded_digest(algo).0))
+        .collect::<Vec<_>>();
+    for ((file, (path, algo)), hash) in promoted.iter().zip(&jobs).zip(hash_files(&jobs)) {
+        let digest = hash.map_err(|e| format!("Failed to read {path:?}: {e}"))?;
+        let recorded = file.recorded_digest(*algo).1;
+        if digest != recorded {
+            return Err(format!(
+                "{path:?} has changed since it was installed: {} {digest} instead of {recorded}",
+                algo.name()
+            ));
+        }
+    }
+
+    let lock_path = lockfile_path(config_file);
+    let mut lock = FontLock::load(&lock_path)?.unwrap_or_default();
+    for file in &promoted {
+        let from = dir.join(&file.file);
+        let to = font_dir.join(&file.file);
+        fs::rename(&from, &to).map_err(|e| format!("Failed to move {from:?} to {to:?}: {e}"))?;
+        lock.set_file(file.clone());
+    }
+    lock.save(&lock_path)?;
+    write_summary(font_dir, &incoming)?;
+    Ok(promoted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_promote_moves_reviewed_files_and_their_records() {
+        let dir = std::env::temp_dir().join(format!("typfont-quarantine-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let font_dir = dir.join("fonts");
+        fs::create_dir_all(incoming_dir(&font_dir)).unwrap();
+        let config_file = dir.join("font_config.toml");
+        let mut incoming = FontLock::default();
+        for (name, data) in [("A.ttf", b"a"), ("B.ttf", b"b")] {
+            fs::write(incoming_dir(&font_dir).join(name), data).unwrap();
+            incoming.set_file(LockedFile {
+                file: name.to_string(),
+                source: format!("lib/{name}"),
+                faces: Vec::new(),
+                converted_from: None,
+                sha256: crate::lockfile::sha256_hex(data),
+                blake3: Some(HashAlgo::Blake3.hex(data)),
+                original_sha256: None,
+                provenance: None,
+            });
+        }
+        write_summary(&font_dir, &incoming).unwrap();
+        let gitignore = incoming_dir(&font_dir).join(GITIGNORE_FILE);
+        assert_eq!(fs::read_to_string(&gitignore).unwrap(), "*\n");
+        assert!(summary(&font_dir, &incoming).contains("| A.ttf | unreadable | lib/A.ttf |"));
+
+        assert!(
+            promote(
+                &config_file,
+                &font_dir,
+                &["C.ttf".to_string()],
+                HashAlgo::Sha256
+            )
+            .is_err()
+        );
+        let promoted = promote(
+            &config_file,
+            &font_dir,
+            &["A.ttf".to_string()],
+            HashAlgo::Sha256,
+        )
+        .unwrap();
+        assert_eq!(promoted.len(), 1);
+        assert!(font_dir.join("A.ttf").exists());
+        let lock = FontLock::load(&lockfile_path(&config_file))
+            .unwrap()
+            .unwrap();
+        assert_eq!(lock.files, promoted);
+        assert_eq!(awaiting(&font_dir).unwrap().files.len(), 1);
+
+        // A file changed after the review is refused
+        fs::write(incoming_dir(&font_dir).join("B.ttf"), b"changed").unwrap();
+        assert!(promote(&config_file, &font_dir, &[], HashAlgo::Blake3).is_err());
+        assert!(!font_dir.join("B.ttf").exists());
+
+        fs::write(incoming_dir(&font_dir).join("B.ttf"), b"b").unwrap();
+        promote(&config_file, &font_dir, &[], HashAlgo::Blake3).unwrap();
+        assert!(!incoming_dir(&font_dir).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,97 @@
+//! `--limit-rate`: cap the download bandwidth used by `update`, so that fetching
+//! fonts over a slow or shared link leaves room for everything else.
+
+use std::fmt;
+use std::io::Read;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::table::{format_size, parse_size};
+
+/// Bytes read between checks of the elapsed time
+const CHUNK_LEN: usize = 16 * 1024;
+
+/// A download rate in bytes per second, written like `2MiB/s`, `500K` or `100000`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct RateLimit(u64);
+
+impl RateLimit {
+    pub(crate) fn bytes_per_second(self) -> u64 {
+        self.0
+    }
+}
+
+impl FromStr for RateLimit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = s.trim();
+        let value = value.strip_suffix("/s").unwrap_or(value);
+        let bytes_per_second = parse_size(value)
+            .map_err(|_| format!("invalid rate {s:?}, expected e.g. 2MiB/s or 500K"))?;
+        if bytes_per_second == 0 {
+            return Err(format!("rate {s:?} must be greater than zero"));
+        }
+        Ok(RateLimit(bytes_per_second))
+    }
+}
+
+impl fmt::Display for RateLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/s", format_size(self.0))
+    }
+}
+
+/// Read `reader` to the end, sleeping as needed to stay below `limit` on average
+pub(crate) fn read_limited<R: Read>(
+    mut reader: R,
+    limit: Option<RateLimit>,
+) -> std::io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    let Some(limit) = limit else {
+        reader.read_to_end(&mut data)?;
+        return Ok(data);
+    };
+
+    let start = Instant::now();
+    let mut chunk = vec![0; CHUNK_LEN];
+    loop {
+        let read = reader.read(&mut chunk)?;
+        if read == 0 {
+            return Ok(data);
+        }
+        data.extend_from_slice(&chunk[..read]);
+
+        let due = Duration::from_secs_f64(data.len() as f64 / limit.bytes_per_second() as f64);
+        if let Some(wait) = due.checked_sub(start.elapsed()) {
+            thread::sleep(wait);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rate_limit() {
+        let rate = |s: &str| s.parse::<RateLimit>().map(RateLimit::bytes_per_second);
+        assert_eq!(rate("2MiB/s"), Ok(2 * 1024 * 1024));
+        assert_eq!(rate("500K"), Ok(500 * 1024));
+        assert_eq!(rate("1.5 mb/s"), Ok(1536 * 1024));
+        assert_eq!(rate("100000"), Ok(100_000));
+        assert!(rate("0").is_err());
+        assert!(rate("fast").is_err());
+        assert_eq!(RateLimit(2 * 1024 * 1024).to_string(), "2.0 MiB/s");
+    }
+
+    #[test]
+    fn test_read_limited_throttles() {
+        let data = vec![7; 64 * 1024];
+        let start = Instant::now();
+        let read = read_limited(data.as_slice(), Some(RateLimit(256 * 1024))).unwrap();
+        assert_eq!(read, data);
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+}
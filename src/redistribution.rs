@@ -0,0 +1,139 @@
+//! The redistribution guard. A font whose license is proprietary, or whose OS/2
+//! `fsType` marks it as restricted, must not be committed to a repository by
+//! accident: neither as a project font nor as part of a published library
+//! index. The guard only applies to directories tracked by git, and the
+//! [`RedistributionPolicy`] decides whether it warns or refuses.
+
+use crate::color::Colorize;
+use std::path::Path;
+use std::process::Command;
+
+use crate::parse_font_config::RedistributionPolicy;
+use crate::process_font::{Embedding, FontLicense, FontMetadata};
+use crate::{progress, t};
+
+/// Why the face described by `metadata` may not be redistributed, if it may not
+pub(crate) fn restriction(metadata: &FontMetadata) -> Option<String> {
+    if metadata.license == Some(FontLicense::Proprietary) {
+        return Some("its license is proprietary".to_string());
+    }
+    let fs_type = metadata.fs_type?;
+    (Embedding::from_fs_type(fs_type) == Embedding::Restricted)
+        .then(|| format!("its fsType ({fs_type:#06x}) marks it as restricted"))
+}
+
+/// Whether files in `dir` would be tracked by git: `dir` is inside a work tree
+/// and not ignored. If git can't tell, the directory is taken to be tracked.
+pub(crate) fn is_git_tracked(dir: &Path) -> bool {
+    let Ok(dir) = std::path::absolute(dir) else {
+        return false;
+    };
+    let Some(work_tree) = dir
+        .ancestors()
+        .find(|ancestor| ancestor.join(".git").exists())
+    else {
+        return false;
+    };
+    // `check-ignore` succeeds for ignored paths and fails with 1 for others. The
+    // trailing separator lets patterns like `fonts/` match a directory that
+    // doesn't exist yet.
+    let ignored = Command::new("git")
+        .arg("-C")
+        .arg(work_tree)
+        .args(["check-ignore", "-q", "--no-index"])
+        .arg(dir.join(""))
+        .status();
+    !matches!(ignored, Ok(status) if status.success())
+}
+
+/// Apply `policy` to the files that may not be redistributed, given with the
+/// reason, before they end up in `target`: warn about them, or refuse them all
+pub(crate) fn guard(
+    policy: RedistributionPolicy,
+    restricted: &[(String, String)],
+    target: &Path,
+) -> Result<(), String> {
+    if restricted.is_empty() || policy == RedistributionPolicy::Allow {
+        return Ok(());
+    }
+    let files = restricted
+        .iter()
+        .map(|(file, reason)| format!("{file} ({reason})"))
+        .collect::<Vec<_>>();
+    if policy == RedistributionPolicy::Deny {
+        return Err(format!(
+            "Refusing to put fonts that may not be redistributed into {target:?}, \
+             which is tracked by git: {}",
+            files.join(", ")
+        ));
+    }
+    for file in files {
+        progress!(
+            "{} {file} may not be redistributed, but {target:?} is tracked by git",
+            t!("warning").yellow().bold()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restriction_from_license_and_fs_type() {
+        let metadata = |license, fs_type| FontMetadata {
+            license,
+            fs_type,
+            ..FontMetadata::default()
+        };
+        assert!(restriction(&metadata(Some(FontLicense::Ofl), Some(0))).is_none());
+        assert!(restriction(&metadata(None, Some(0x0008))).is_none());
+        assert!(
+            restriction(&metadata(Some(FontLicense::Proprietary), None))
+                .unwrap()
+                .contains("proprietary")
+        );
+        assert!(
+            restriction(&metadata(None, Some(0x0202)))
+                .unwrap()
+                .contains("0x0202")
+        );
+    }
+
+    #[test]
+    fn test_embedding_permissions() {
+        assert_eq!(Embedding::from_fs_type(0), Embedding::Installable);
+        assert_eq!(Embedding::from_fs_type(0x0004), Embedding::PreviewOnly);
+        // The least restrictive bit wins
+        assert_eq!(Embedding::from_fs_type(0x000a), Embedding::Editable);
+        assert!(Embedding::forbids_pdf_embedding(0x0002));
+        assert!(Embedding::forbids_pdf_embedding(0x0208));
+        assert!(!Embedding::forbids_pdf_embedding(0x0104));
+        assert_eq!(Embedding::describe(0x0108), "editable, no subsetting");
+    }
+
+    #[test]
+    fn test_guard_policies() {
+        let restricted = [("Minion.otf".to_string(), "proprietary".to_string())];
+        let target = Path::new("fonts");
+        assert!(guard(RedistributionPolicy::Allow, &restricted, target).is_ok());
+        assert!(guard(RedistributionPolicy::Warn, &restricted, target).is_ok());
+        let error = guard(RedistributionPolicy::Deny, &restricted, target).unwrap_err();
+        assert!(error.contains("Minion.otf (proprietary)"));
+        assert!(guard(RedistributionPolicy::Deny, &[], target).is_ok());
+    }
+
+    #[test]
+    fn test_directories_outside_git_are_not_tracked() {
+        let dir = std::env::temp_dir().join(format!("typfont-untracked-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        if !std::env::temp_dir()
+            .ancestors()
+            .any(|ancestor| ancestor.join(".git").exists())
+        {
+            assert!(!is_git_tracked(&dir));
+        }
+        std::fs::remove_dir(&dir).unwrap();
+    }
+}
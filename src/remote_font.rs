@@ -0,0 +1,318 @@
+//! Reading remote font files through HTTP Range requests. Only the table
+//! directories and the tables Typst needs to describe a face are downloaded;
+//! the outlines and bitmaps that make up most of a font file are left out.
+//! Servers that don't support ranges simply send the whole file.
+
+use std::ops::Range;
+use std::path::Path;
+
+use crate::github::{self, GitHubSource};
+use crate::http::header::{CONTENT_RANGE, RANGE};
+use crate::http::{self, Client, StatusCode};
+use crate::progress;
+use crate::table::format_size;
+use crate::{DiscoveredFont, discover_faces};
+
+/// Large tables that neither Typst's font info nor the library metadata read
+const SKIPPED_TABLES: [&[u8; 4]; 15] = [
+    b"glyf", b"loca", b"CFF ", b"CFF2", b"gvar", b"CBDT", b"sbix", b"SVG ", b"EBDT", b"hvgl",
+    b"GPOS", b"GSUB", b"kern", b"hmtx", b"vmtx",
+];
+
+/// Font files that can be read in parts. WOFF2 files are compressed as a whole.
+const PARTIAL_EXTENSIONS: [&str; 4] = ["ttf", "otf", "ttc", "otc"];
+
+/// Kilobytes requested first by default. Most fonts keep their table directory
+/// and small tables such as `head`, `OS/2` and `name` within them.
+pub(crate) const DEFAULT_PREFIX_KIB: usize = 16;
+
+/// Ranges closer than this are fetched in a single request
+const MERGE_GAP: usize = 16 * 1024;
+
+/// A remote font file of which only some parts were downloaded. The rest of
+/// `data` is zeroed, so offsets are the same as in the real file.
+pub(crate) struct PartialFont {
+    pub(crate) data: Vec<u8>,
+    /// Number of bytes actually downloaded
+    pub(crate) downloaded: usize,
+}
+
+/// Index the fonts of a GitHub library without cloning it, reading only the
+/// headers of each font file. Paths are relative to the repository root.
+pub(crate) fn index_github_library(
+    source: &Path,
+    prefix_len: usize,
+) -> Result<Vec<DiscoveredFont>, String> {
+    let source = GitHubSource::parse(source)?;
+    let client = http::client()?;
+
+    let paths = github::fetch_file_paths(&client, source.repo, source.reference)?
+        .into_iter()
+        .filter(|path| is_partially_readable(Path::new(path)))
+        .collect::<Vec<_>>();
+
+    let mut fonts = Vec::new();
+    let (mut downloaded, mut total) = (0, 0);
+    for path in &paths {
+        let url = source.file_url(path);
+        match fetch_partial_font(&client, &url, prefix_len) {
+            Ok(font) => {
+                downloaded += font.downloaded;
+                total += font.data.len();
+                fonts.extend(discover_faces(Path::new(path), &font.data));
+            }
+            Err(e) => progress!("  Skipping {path}: {e}"),
+        }
+    }
+
+    progress!(
+        "  Indexed {} font files of {} by downloading {} of {}",
+        paths.len(),
+        source,
+        format_size(downloaded as u64),
+        format_size(total as u64)
+    );
+    Ok(fonts)
+}
+
+/// Whether only parts of the font file at `path` can be downloaded
+pub(crate) fn is_partially_readable(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            PARTIAL_EXTENSIONS
+                .iter()
+                .any(|font_extension| extension.eq_ignore_ascii_case(font_extension))
+        })
+}
+
+/// Download the parts of the TrueType/OpenType font or collection at `url`
+/// that are needed to index it, starting with its first `prefix_len` bytes
+pub(crate) fn fetch_partial_font(
+    client: &Client,
+    url: &str,
+    prefix_len: usize,
+) -> Result<PartialFont, String> {
+    let (header, total_len) = fetch_range(client, url, 0..prefix_len.max(12))?;
+    let Some(total_len) = total_len else {
+        // The server ignored the Range header and sent the whole file
+        let downloaded = header.len();
+        return Ok(PartialFont {
+            data: header,
+            downloaded,
+        });
+    };
+
+    let mut font = PartialFont {
+        data: vec![0; total_len],
+        downloaded: 0,
+    };
+    let header_len = header.len().min(total_len);
+    font.data[..header_len].copy_from_slice(&header[..header_len]);
+    font.downloaded = header_len;
+    let mut fetched = Vec::new();
+    fetched.push(0..header_len);
+
+    // Each structure tells where the next one is: the collection header, the
+    // offset table of each face, its table records and finally the tables
+    if font.data.get(0..4) == Some(b"ttcf") {
+        let num_fonts = read_u32(&font.data, 8)? as usize;
+        fetch_missing(
+            client,
+            url,
+            &mut font,
+            &mut fetched,
+            std::iter::once(12..12 + num_fonts * 4),
+        )?;
+    }
+    let face_offsets = face_offsets(&font.data)?;
+    let offset_tables = face_offsets.iter().map(|&offset| offset..offset + 12);
+    fetch_missing(client, url, &mut font, &mut fetched, offset_tables)?;
+
+    let mut directories = Vec::new();
+    for &offset in &face_offsets {
+        let num_tables = read_u16(&font.data, offset + 4)? as usize;
+        directories.push(offset + 12..offset + 12 + num_tables * 16);
+    }
+    fetch_missing(client, url, &mut font, &mut fetched, directories.clone())?;
+
+    let mut tables = Vec::new();
+    for directory in directories {
+        for record in directory.step_by(16) {
+            let tag: [u8; 4] = font.data[record..record + 4].try_into().unwrap();
+            let offset = read_u32(&font.data, record + 8)? as usize;
+            let length = read_u32(&font.data, record + 12)? as usize;
+            if !SKIPPED_TABLES.contains(&&tag) && offset + length <= total_len {
+                tables.push(offset..offset + length);
+            }
+        }
+    }
+    fetch_missing(client, url, &mut font, &mut fetched, tables)?;
+
+    Ok(font)
+}
+
+/// Offsets of the offset tables of all faces in the font
+fn face_offsets(data: &[u8]) -> Result<Vec<usize>, String> {
+    match data.get(0..4) {
+        Some(b"ttcf") => {}
+        Some(b"\x00\x01\x00\x00" | b"OTTO" | b"true") => return Ok(vec![0]),
+        _ => return Err("not a TrueType/OpenType font".to_string()),
+    }
+    let num_fonts = read_u32(data, 8)? as usize;
+    (0..num_fonts)
+        .map(|index| read_u32(data, 12 + index * 4).map(|offset| offset as usize))
+        .collect()
+}
+
+/// Download the parts of `wanted` that are not `fetched` yet, merging nearby
+/// ranges to save requests
+fn fetch_missing(
+    client: &Client,
+    url: &str,
+    font: &mut PartialFont,
+    fetched: &mut Vec<Range<usize>>,
+    wanted: impl IntoIterator<Item = Range<usize>>,
+) -> Result<(), String> {
+    for range in merge_ranges(wanted.into_iter().collect(), fetched) {
+        let (bytes, total_len) = fetch_range(client, url, range.clone())?;
+        if total_len.is_none() {
+            // The server sent the whole file after all
+            font.downloaded += bytes.len();
+            font.data = bytes;
+            fetched.push(0..font.data.len());
+            return Ok(());
+        }
+        let end = (range.start + bytes.len()).min(font.data.len());
+        font.data[range.start..end].copy_from_slice(&bytes[..end - range.start]);
+        font.downloaded += end - range.start;
+        fetched.push(range.start..end);
+    }
+    Ok(())
+}
+
+/// Sort and coalesce `wanted`, leaving out what is already `fetched`
+fn merge_ranges(mut wanted: Vec<Range<usize>>, fetched: &[Range<usize>]) -> Vec<Range<usize>> {
+    for range in &mut wanted {
+        for done in fetched {
+            if done.start <= range.start && range.start < done.end {
+                range.start = done.end.min(range.end);
+            }
+        }
+    }
+    wanted.retain(|range| !range.is_empty());
+    wanted.sort_by_key(|range| range.start);
+
+    let mut merged: Vec<Range<usize>> = Vec::new();
+    for range in wanted {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end + MERGE_GAP => {
+                last.end = last.end.max(range.end);
+            }
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// Request `range` of `url`. Returns the body and, if the server honored the
+/// range, the total length of the file.
+fn fetch_range(
+    client: &Client,
+    url: &str,
+    range: Range<usize>,
+) -> Result<(Vec<u8>, Option<usize>), String> {
+    let response = http::get(client, url)?
+        .header(RANGE, format!("bytes={}-{}", range.start, range.end - 1))
+        .send()
+        .map_err(|e| format!("Failed to download {url}: {e}"))?;
+
+    let status = response.status();
+    let total_len = match status {
+        StatusCode::PARTIAL_CONTENT => response
+            .headers()
+            .get(CONTENT_RANGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(content_range_total),
+        StatusCode::OK => None,
+        // Some servers refuse ranges of empty or tiny files
+        StatusCode::RANGE_NOT_SATISFIABLE => return Ok((fetch_whole(client, url)?, None)),
+        _ => return Err(format!("Failed to download {url}: HTTP {status}")),
+    };
+
+    // A partial response of unknown total length can't be placed in the file
+    if status == StatusCode::PARTIAL_CONTENT && total_len.is_none() {
+        return Ok((fetch_whole(client, url)?, None));
+    }
+
+    let body = response
+        .bytes()
+        .map_err(|e| format!("Failed to download {url}: {e}"))?;
+    Ok((body.to_vec(), total_len))
+}
+
+/// Download the whole file at `url`
+pub(crate) fn fetch_whole(client: &Client, url: &str) -> Result<Vec<u8>, String> {
+    let response = http::get(client, url)?
+        .send()
+        .map_err(|e| format!("Failed to download {url}: {e}"))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Failed to download {url}: HTTP {}",
+            response.status()
+        ));
+    }
+    response
+        .bytes()
+        .map(|bytes| bytes.to_vec())
+        .map_err(|e| format!("Failed to download {url}: {e}"))
+}
+
+/// The total length in a `Content-Range: bytes 0-4095/123456` header
+fn content_range_total(value: &str) -> Option<usize> {
+    value.rsplit_once('/')?.1.trim().parse().ok()
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, String> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_be_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| "truncated font header".to_string())
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, String> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| "truncated font header".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_ranges_coalesces_nearby_ranges() {
+        let merged = merge_ranges(
+            vec![100_000..100_100, 0..100, 200..300, 50_000..60_000, 100..200],
+            &[0..2048, 2048..4096],
+        );
+        assert_eq!(merged, [50_000..60_000, 100_000..100_100]);
+
+        let merged = merge_ranges(vec![5000..6000, 7000..8000], &[]);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0], 5000..8000);
+    }
+
+    #[test]
+    fn test_only_sfnt_fonts_are_partially_readable() {
+        assert!(is_partially_readable(Path::new("fonts/NotoSansCJK.TTC")));
+        assert!(is_partially_readable(Path::new("Lato-Regular.otf")));
+        assert!(!is_partially_readable(Path::new("Lato-Regular.woff2")));
+        assert!(!is_partially_readable(Path::new("README")));
+    }
+
+    #[test]
+    fn test_content_range_total() {
+        assert_eq!(content_range_total("bytes 0-4095/123456"), Some(123456));
+        assert_eq!(content_range_total("bytes 0-4095/*"), None);
+    }
+}
@@ -0,0 +1,178 @@
+//! The `repair` subcommand: find the installed fonts whose content no longer
+//! matches the hash font_config.lock records for them, e.g. after bit rot or a
+//! bad merge, and install them again from the library sources they came from.
+//! Healthy files are left alone.
+
+use crate::color::Colorize;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::lockfile::{FontLock, HashAlgo, LockedFile, hash_files, sha256_file};
+use crate::workspace::Workspace;
+
+/// What is wrong with an installed file
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Damage {
+    Missing,
+    /// The file has a different hash
+    Modified {
+        algo: HashAlgo,
+        digest: String,
+    },
+}
+
+impl fmt::Display for Damage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing => write!(f, "missing"),
+            Self::Modified { algo, digest } => {
+                write!(f, "modified ({} {})", algo.name(), &digest[..12])
+            }
+        }
+    }
+}
+
+/// The files recorded in `lock` that are missing from `font_dir` or differ from
+/// the recorded hash, verified with `algo` where the lockfile records its digest.
+/// Files recorded without a hash can't be verified.
+pub(crate) fn damaged_files<'l>(
+    lock: &'l FontLock,
+    font_dir: &Path,
+    algo: HashAlgo,
+) -> Vec<(&'l LockedFile, Damage)> {
+    let files = lock
+        .files
+        .iter()
+        .filter(|file| !file.sha256.is_empty())
+        .collect::<Vec<_>>();
+    let jobs = files
+        .iter()
+        .map(|file| (font_dir.join(&file.file), file.recorded_digest(algo).0))
+        .collect::<Vec<_>>();
+    files
+        .into_iter()
+        .zip(hash_files(&jobs))
+        .filter_map(|(file, hash)| {
+            let (algo, recorded) = file.recorded_digest(algo);
+            let damage = match hash {
+                Ok(digest) if digest == recorded => return None,
+                Ok(digest) => Damage::Modified { algo, digest },
+                Err(_) => Damage::Missing,
+            };
+            Some((file, damage))
+        })
+        .collect()
+}
+
+/// Print the damaged files, with the source each is installed from again
+pub(crate) fn print_damaged_files(damaged: &[(&LockedFile, Damage)], font_dir: &str) {
+    for (file, damage) in damaged {
+        println!(
+            "  {} {}/{}: {damage}, from {}",
+            "✗".red(),
+            font_dir,
+            file.file,
+            file.source
+        );
+    }
+}
+
+/// Remove the damaged `file` from `font_dir`, so that `update` installs it again.
+/// In a workspace, a stored copy with the wrong content is removed as well,
+/// rather than linked again.
+pub(crate) fn remove_damaged_file(
+    file: &LockedFile,
+    font_dir: &Path,
+    workspace: Option<&Workspace>,
+) -> Result<(), String> {
+    let path = font_dir.join(&file.file);
+    if fs::symlink_metadata(&path).is_ok() {
+        fs::remove_file(&path).map_err(|e| format!("Failed to remove {path:?}: {e}"))?;
+    }
+    if let Some(workspace) = workspace {
+        let stored = workspace.stored_path(&file.sha256, &file.file);
+        if stored.is_file() && sha256_file(&stored).is_ok_and(|sha256| sha256 != file.sha256) {
+            fs::remove_file(&stored).map_err(|e| format!("Failed to remove {stored:?}: {e}"))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lockfile::sha256_hex;
+
+    fn locked(file: &str, data: &[u8]) -> LockedFile {
+        LockedFile {
+            file: file.to_string(),
+            source: format!("../library/{file}"),
+            faces: Vec::new(),
+            converted_from: None,
+            sha256: sha256_hex(data),
+            blake3: None,
+            original_sha256: None,
+            provenance: None,
+        }
+    }
+
+    #[test]
+    fn test_damaged_files_skip_healthy_ones() {
+        let dir = std::env::temp_dir().join(format!("typfont-repair-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("Healthy.ttf"), b"healthy").unwrap();
+        fs::write(dir.join("Rotten.ttf"), b"rotten").unwrap();
+        let lock = FontLock {
+            sources: Vec::new(),
+            files: vec![
+                locked("Healthy.ttf", b"healthy"),
+                locked("Rotten.ttf", b"original"),
+                locked("Gone.ttf", b"gone"),
+            ],
+        };
+
+        let damaged = damaged_files(&lock, &dir, HashAlgo::Sha256)
+            .into_iter()
+            .map(|(file, damage)| (file.file.as_str(), damage))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            damaged,
+            [
+                (
+                    "Rotten.ttf",
+                    Damage::Modified {
+                        algo: HashAlgo::Sha256,
+                        digest: sha256_hex(b"rotten")
+                    }
+                ),
+                ("Gone.ttf", Damage::Missing),
+            ]
+        );
+
+        // BLAKE3 verifies the files that record it, and SHA-256 the others
+        let mut lock = lock;
+        lock.files[1].blake3 = Some(HashAlgo::Blake3.hex(b"original"));
+        let damaged = damaged_files(&lock, &dir, HashAlgo::Blake3);
+        assert_eq!(damaged.len(), 2);
+        assert_eq!(
+            damaged[0].1,
+            Damage::Modified {
+                algo: HashAlgo::Blake3,
+                digest: HashAlgo::Blake3.hex(b"rotten")
+            }
+        );
+        lock.files[0].blake3 = Some(HashAlgo::Blake3.hex(b"healthy"));
+        assert_eq!(damaged_files(&lock, &dir, HashAlgo::Blake3).len(), 2);
+
+        remove_damaged_file(
+            damaged_files(&lock, &dir, HashAlgo::Sha256)[0].0,
+            &dir,
+            None,
+        )
+        .unwrap();
+        assert!(!dir.join("Rotten.ttf").exists());
+        assert!(dir.join("Healthy.ttf").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
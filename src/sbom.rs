@@ -0,0 +1,330 @@
+//! The `sbom` subcommand: an SPDX 2.3 document listing every font file shipped
+//! in the project's font directory, so that documents built in regulated
+//! environments can include their fonts in a software bill of materials.
+
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use time::OffsetDateTime;
+use walkdir::WalkDir;
+
+use crate::font_manager::FontManager;
+use crate::github::format_timestamp;
+use crate::lockfile::{FontLock, HashAlgo, LockedFile, hash_files, lockfile_path, sha256_hex};
+use crate::utils::font_utils::is_font_file;
+
+/// Written where SPDX requires a value that the font doesn't provide
+const NOASSERTION: &str = "NOASSERTION";
+
+/// Well-known font licenses, matched against the license description and URL
+/// of the name table (IDs 13 and 14)
+const KNOWN_LICENSES: [(&str, &str); 8] = [
+    ("scripts.sil.org/ofl", "OFL-1.1"),
+    ("openfontlicense.org", "OFL-1.1"),
+    ("sil open font license", "OFL-1.1"),
+    ("apache.org/licenses/license-2.0", "Apache-2.0"),
+    ("apache license, version 2.0", "Apache-2.0"),
+    ("ubuntu font licen", "Ubuntu-font-1.0"),
+    ("bitstream vera", "Bitstream-Vera"),
+    ("gust font license", "LPPL-1.3c"),
+];
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxDocument {
+    spdx_version: &'static str,
+    data_license: &'static str,
+    #[serde(rename = "SPDXID")]
+    spdx_id: &'static str,
+    name: String,
+    document_namespace: String,
+    creation_info: CreationInfo,
+    packages: Vec<SpdxPackage>,
+    relationships: Vec<Relationship>,
+}
+
+#[derive(Debug, Serialize)]
+struct CreationInfo {
+    created: String,
+    creators: Vec<String>,
+}
+
+/// One font file. Fonts are described as packages rather than SPDX files, so
+/// that their download location, version and supplier can be recorded.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxPackage {
+    #[serde(rename = "SPDXID")]
+    spdx_id: String,
+    name: String,
+    version_info: Option<String>,
+    package_file_name: String,
+    supplier: String,
+    download_location: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source_info: Option<String>,
+    files_analyzed: bool,
+    checksums: Vec<Checksum>,
+    license_concluded: &'static str,
+    license_declared: String,
+    copyright_text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    comment: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Checksum {
+    algorithm: &'static str,
+    #[serde(rename = "checksumValue")]
+    checksum_value: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Relationship {
+    spdx_element_id: &'static str,
+    relationship_type: &'static str,
+    related_spdx_element: String,
+}
+
+/// What the name table of a font says about it
+#[derive(Debug, Default, PartialEq)]
+struct FontLegalInfo {
+    family: Option<String>,
+    version: Option<String>,
+    copyright: Option<String>,
+    manufacturer: Option<String>,
+    license: Option<String>,
+    license_url: Option<String>,
+}
+
+impl FontLegalInfo {
+    fn parse(data: &[u8]) -> Self {
+        let Ok(face) = ttf_parser::Face::parse(data, 0) else {
+            return Self::default();
+        };
+        let name = |name_id| {
+            face.names()
+                .into_iter()
+                .filter(|name| name.name_id == name_id && name.is_unicode())
+                .find_map(|name| name.to_string())
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+        };
+        Self {
+            family: name(ttf_parser::name_id::TYPOGRAPHIC_FAMILY)
+                .or_else(|| name(ttf_parser::name_id::FAMILY)),
+            version: name(ttf_parser::name_id::VERSION),
+            copyright: name(ttf_parser::name_id::COPYRIGHT_NOTICE),
+            manufacturer: name(ttf_parser::name_id::MANUFACTURER),
+            license: name(ttf_parser::name_id::LICENSE),
+            license_url: name(ttf_parser::name_id::LICENSE_URL),
+        }
+    }
+
+    /// The SPDX identifier of the license, if it is one of the well-known ones
+    fn spdx_license(&self) -> Option<&'static str> {
+        let text = [&self.license_url, &self.license]
+            .into_iter()
+            .flatten()
+            .map(|text| text.to_lowercase())
+            .collect::<Vec<_>>()
+            .join(" ");
+        KNOWN_LICENSES
+            .iter()
+            .find(|(pattern, _)| text.contains(pattern))
+            .map(|(_, id)| *id)
+    }
+}
+
+/// The version in the name table of the font file `data`, e.g. "Version 4.000"
+pub(crate) fn font_version(data: &[u8]) -> Option<String> {
+    FontLegalInfo::parse(data).version
+}
+
+/// Build the SPDX document of the fonts in the project configured by `config_file`,
+/// with checksums computed by `hash_algo`
+pub(crate) fn generate_sbom(config_file: &Path, hash_algo: HashAlgo) -> Result<String, String> {
+    let font_dir = FontManager::project_font_dir(config_file)?;
+    let lock = FontLock::load(&lockfile_path(config_file))?.unwrap_or_default();
+
+    let mut files = WalkDir::new(&font_dir)
+        .into_iter()
+        // Leaving out hidden directories, such as fonts awaiting review
+        .filter_entry(|entry| {
+            entry.depth() == 0 || !entry.file_name().to_string_lossy().starts_with('.')
+        })
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file() && is_font_file(entry.path()))
+        .map(|entry| entry.into_path())
+        .collect::<Vec<_>>();
+    files.sort();
+
+    // The checksums are computed on all cores up front
+    let jobs = files
+        .iter()
+        .map(|path| (path.clone(), hash_algo))
+        .collect::<Vec<_>>();
+    let mut packages = Vec::new();
+    for ((number, path), checksum) in files.iter().enumerate().zip(hash_files(&jobs)) {
+        let checksum = checksum.map_err(|e| format!("Failed to read {path:?}: {e}"))?;
+        let data = fs::read(path).map_err(|e| format!("Failed to read {path:?}: {e}"))?;
+        let file_name = path
+            .strip_prefix(&font_dir)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .replace('\\', "/");
+        let locked = lock.files.iter().find(|locked| locked.file == file_name);
+        packages.push(font_package(
+            number + 1,
+            &file_name,
+            &data,
+            locked,
+            hash_algo,
+            checksum,
+        ));
+    }
+
+    let project_name = config_file
+        .canonicalize()
+        .ok()
+        .and_then(|path| Some(path.parent()?.file_name()?.to_string_lossy().to_string()))
+        .unwrap_or_else(|| "project".to_string());
+    // The namespace must be unique per document; derive it from the contents
+    let contents = packages
+        .iter()
+        .map(|package| package.checksums[0].checksum_value.as_str())
+        .collect::<String>();
+
+    let document = SpdxDocument {
+        spdx_version: "SPDX-2.3",
+        data_license: "CC0-1.0",
+        spdx_id: "SPDXRef-DOCUMENT",
+        name: format!("{project_name}-fonts"),
+        document_namespace: format!(
+            "https://spdx.org/spdxdocs/typfont-{project_name}-{}",
+            &sha256_hex(contents.as_bytes())[..16]
+        ),
+        creation_info: CreationInfo {
+            created: spdx_timestamp(OffsetDateTime::now_utc()),
+            creators: vec![format!("Tool: typfont-{}", env!("CARGO_PKG_VERSION"))],
+        },
+        relationships: packages
+            .iter()
+            .map(|package| Relationship {
+                spdx_element_id: "SPDXRef-DOCUMENT",
+                relationship_type: "DESCRIBES",
+                related_spdx_element: package.spdx_id.clone(),
+            })
+            .collect(),
+        packages,
+    };
+
+    serde_json::to_string_pretty(&document).map_err(|e| format!("Failed to encode SBOM: {e}"))
+}
+
+fn font_package(
+    number: usize,
+    file_name: &str,
+    data: &[u8],
+    locked: Option<&LockedFile>,
+    hash_algo: HashAlgo,
+    checksum: String,
+) -> SpdxPackage {
+    let info = FontLegalInfo::parse(data);
+    let provenance = locked.and_then(|locked| locked.provenance.as_ref());
+
+    let source_info = provenance.and_then(|provenance| {
+        match (&provenance.repo, &provenance.commit, &provenance.local_path) {
+            (Some(repo), Some(commit), _) => Some(format!("GitHub repository {repo} at {commit}")),
+            (Some(repo), None, _) => Some(format!("GitHub repository {repo}")),
+            (None, _, Some(local_path)) => Some(format!("local font library file {local_path}")),
+            _ => None,
+        }
+    });
+    let license_comment = info
+        .license
+        .as_ref()
+        .filter(|_| info.spdx_license().is_none())
+        .map(|license| format!("License text in the font: {license}"));
+
+    SpdxPackage {
+        spdx_id: format!("SPDXRef-Font-{number}"),
+        name: info.family.clone().unwrap_or_else(|| file_name.to_string()),
+        version_info: info.version.clone(),
+        package_file_name: file_name.to_string(),
+        supplier: info.manufacturer.as_ref().map_or_else(
+            || NOASSERTION.to_string(),
+            |manufacturer| format!("Organization: {manufacturer}"),
+        ),
+        download_location: provenance
+            .and_then(|provenance| provenance.url.clone())
+            .unwrap_or_else(|| NOASSERTION.to_string()),
+        source_info,
+        files_analyzed: false,
+        checksums: vec![Checksum {
+            algorithm: match hash_algo {
+                HashAlgo::Sha256 => "SHA256",
+                HashAlgo::Blake3 => "BLAKE3",
+            },
+            checksum_value: checksum,
+        }],
+        license_concluded: NOASSERTION,
+        license_declared: info.spdx_license().unwrap_or(NOASSERTION).to_string(),
+        copyright_text: info.copyright.unwrap_or_else(|| NOASSERTION.to_string()),
+        comment: license_comment,
+    }
+}
+
+/// SPDX timestamps are RFC 3339 in UTC without fractional seconds
+fn spdx_timestamp(timestamp: OffsetDateTime) -> String {
+    format_timestamp(timestamp.replace_nanosecond(0).unwrap_or(timestamp))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_well_known_licenses_get_spdx_identifiers() {
+        let info = |license: &str, url: &str| FontLegalInfo {
+            license: Some(license.to_string()),
+            license_url: Some(url.to_string()),
+            ..FontLegalInfo::default()
+        };
+        assert_eq!(
+            info(
+                "This Font Software is licensed under the SIL Open Font License, Version 1.1.",
+                "https://openfontlicense.org"
+            )
+            .spdx_license(),
+            Some("OFL-1.1")
+        );
+        assert_eq!(
+            info("Licensed under the Apache License, Version 2.0", "").spdx_license(),
+            Some("Apache-2.0")
+        );
+        assert_eq!(info("All rights reserved.", "").spdx_license(), None);
+        assert_eq!(
+            spdx_timestamp(OffsetDateTime::from_unix_timestamp(0).unwrap()),
+            "1970-01-01T00:00:00Z"
+        );
+    }
+
+    #[test]
+    fn test_fonts_awaiting_review_are_left_out() {
+        let dir = std::env::temp_dir().join(format!("typfont-sbom-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("fonts/.incoming")).unwrap();
+        let config_file = dir.join("font_config.toml");
+        fs::write(&config_file, "[[fonts]]\nfamily_name = \"A\"\n").unwrap();
+        let font = include_bytes!("../fixtures/fonts/TypfontFixture-Regular.ttf");
+        fs::write(dir.join("fonts/Shipped.ttf"), font).unwrap();
+        fs::write(dir.join("fonts/.incoming/Unreviewed.ttf"), font).unwrap();
+
+        let sbom = generate_sbom(&config_file, HashAlgo::Sha256).unwrap();
+        assert!(sbom.contains("Shipped.ttf"));
+        assert!(!sbom.contains("Unreviewed.ttf"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
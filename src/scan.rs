@@ -0,0 +1,271 @@
+//! Required fonts inferred from a project's Typst sources. The `font` arguments
+//! of `text`, in set rules such as `#set text(font: "Noto Serif")`, in show
+//! rules such as `#show heading: set text(font: ("Inter", "Noto Sans"))` and in
+//! calls such as `#text(font: "Fira Code")[...]`, name the families a document
+//! uses. Those of a `show math.equation` rule are the math fonts.
+//!
+//! A family without a `weight` next to it is taken in regular and bold, which
+//! strong text and headings use; a math font only in regular. The families
+//! embedded in Typst are left out, since they never need to be installed.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use typst::syntax::ast::{self, Arg, ArrayItem, DictItem, Expr};
+use typst::syntax::{LinkedNode, parse};
+use typst::text::{FontStretch, FontStyle, FontWeight};
+use walkdir::WalkDir;
+
+use crate::parse_font_config::{EMBEDDED_FONTS, TypstFont, deserialize_fonts_from_toml};
+
+/// A family named by the `font` argument of a `text` set rule or call
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct FontUsage {
+    pub(crate) family: String,
+    pub(crate) style: FontStyle,
+    pub(crate) weights: Vec<u16>,
+    pub(crate) math: bool,
+    /// 1-based line of the argument in its source
+    pub(crate) line: usize,
+}
+
+/// The fonts the `text` set rules and calls of a Typst source name
+pub(crate) fn scan_source(source: &str) -> Vec<FontUsage> {
+    let root = parse(source);
+    let mut usages = Vec::new();
+    visit(&LinkedNode::new(&root), source, false, &mut usages);
+    usages
+}
+
+fn visit(node: &LinkedNode, source: &str, math: bool, usages: &mut Vec<FontUsage>) {
+    let mut math = math;
+    if let Some(rule) = node.cast::<ast::ShowRule>() {
+        math |= rule.selector().is_some_and(is_math_equation);
+    }
+    let args = if let Some(rule) = node.cast::<ast::SetRule>() {
+        is_text(rule.target()).then(|| rule.args())
+    } else if let Some(call) = node.cast::<ast::FuncCall>() {
+        is_text(call.callee()).then(|| call.args())
+    } else {
+        None
+    };
+    if let Some(args) = args {
+        let line = source[..node.offset()].matches('\n').count() + 1;
+        usages.extend(text_fonts(args, math, line));
+    }
+    for child in node.children() {
+        visit(&child, source, math, usages);
+    }
+}
+
+fn is_text(expr: Expr) -> bool {
+    matches!(expr, Expr::Ident(ident) if ident.as_str() == "text")
+}
+
+fn is_math_equation(expr: Expr) -> bool {
+    match expr {
+        Expr::FieldAccess(access) => {
+            access.field().as_str() == "equation"
+                && matches!(access.target(), Expr::Ident(ident) if ident.as_str() == "math")
+        }
+        _ => false,
+    }
+}
+
+/// The families of the `font` argument among `args`, with the `weight` and
+/// `style` given next to it
+fn text_fonts(args: ast::Args, math: bool, line: usize) -> Vec<FontUsage> {
+    let mut families = Vec::new();
+    let mut weight = None;
+    let mut style = FontStyle::Normal;
+    for arg in args.items() {
+        let Arg::Named(named) = arg else { continue };
+        match named.name().as_str() {
+            "font" => families = font_families(named.expr()),
+            "weight" => weight = weight_number(named.expr()),
+            "style" => {
+                if let Expr::Str(value) = named.expr() {
+                    style = match value.get().as_str() {
+                        "italic" => FontStyle::Italic,
+                        "oblique" => FontStyle::Oblique,
+                        _ => FontStyle::Normal,
+                    };
+                }
+            }
+            _ => {}
+        }
+    }
+    let weights = match weight {
+        Some(weight) => vec![weight],
+        None if math => vec![400],
+        None => vec![400, 700],
+    };
+    families
+        .into_iter()
+        .map(|family| FontUsage {
+            family,
+            style,
+            weights: weights.clone(),
+            math,
+            line,
+        })
+        .collect()
+}
+
+/// The families of a `font` value: a name, a fallback list of names, or
+/// dictionaries with a `name`, as in `(name: "Inter", covers: "latin-in-cjk")`
+fn font_families(expr: Expr) -> Vec<String> {
+    match expr {
+        Expr::Str(name) => vec![name.get().to_string()],
+        Expr::Dict(dict) => dict
+            .items()
+            .filter_map(|item| match item {
+                DictItem::Named(named) if named.name().as_str() == "name" => match named.expr() {
+                    Expr::Str(name) => Some(name.get().to_string()),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect(),
+        Expr::Array(array) => array
+            .items()
+            .flat_map(|item| match item {
+                ArrayItem::Pos(expr) => font_families(expr),
+                ArrayItem::Spread(_) => Vec::new(),
+            })
+            .collect(),
+        Expr::Parenthesized(inner) => font_families(inner.expr()),
+        _ => Vec::new(),
+    }
+}
+
+fn weight_number(expr: Expr) -> Option<u16> {
+    let number = match expr {
+        Expr::Int(number) => u16::try_from(number.get()).ok()?,
+        Expr::Str(name) => match name.get().as_str() {
+            "thin" => 100,
+            "extralight" => 200,
+            "light" => 300,
+            "regular" => 400,
+            "medium" => 500,
+            "semibold" => 600,
+            "bold" => 700,
+            "extrabold" => 800,
+            "black" => 900,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    (100..=900).contains(&number).then_some(number)
+}
+
+/// The fonts named in the `.typ` files below `project_dir`, by file, leaving
+/// out hidden directories and the font directory `font_dir`
+pub(crate) fn scan_project(project_dir: &Path, font_dir: &Path) -> Vec<(PathBuf, Vec<FontUsage>)> {
+    WalkDir::new(project_dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| {
+            entry.depth() == 0
+                || !(entry.file_name().to_string_lossy().starts_with('.')
+                    || entry.path() == font_dir)
+        })
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry.file_type().is_file() && entry.path().extension().is_some_and(|ext| ext == "typ")
+        })
+        .filter_map(|entry| {
+            let source = fs::read_to_string(entry.path()).ok()?;
+            let usages = scan_source(&source);
+            (!usages.is_empty()).then(|| (entry.into_path(), usages))
+        })
+        .collect()
+}
+
+/// The fonts to require for `usages`, merging the weights of each family and
+/// style and leaving out the families embedded in Typst. Typst matches family
+/// names case-insensitively, so the first spelling of a family is kept.
+pub(crate) fn required_fonts<'a>(
+    usages: impl IntoIterator<Item = &'a FontUsage>,
+) -> Vec<TypstFont> {
+    let embedded = deserialize_fonts_from_toml(EMBEDDED_FONTS)
+        .map(|config| config.fonts)
+        .unwrap_or_default();
+    let is_embedded = |family: &str| {
+        embedded
+            .iter()
+            .any(|font| font.family_name.eq_ignore_ascii_case(family))
+    };
+    let mut spellings = BTreeMap::<String, String>::new();
+    let mut fonts = Vec::new();
+    for usage in usages {
+        if is_embedded(&usage.family) {
+            continue;
+        }
+        let family = spellings
+            .entry(usage.family.to_lowercase())
+            .or_insert_with(|| usage.family.clone());
+        for &weight in &usage.weights {
+            let font = TypstFont {
+                family_name: family.clone(),
+                style: usage.style,
+                weight: FontWeight::from_number(weight),
+                stretch: FontStretch::NORMAL,
+            };
+            if !fonts.contains(&font) {
+                fonts.push(font);
+            }
+        }
+    }
+    fonts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_source_finds_text_and_math_fonts() {
+        let source = r#"
+#set text(font: "Noto Serif", lang: "en")
+#show heading: set text(font: ("Inter", (name: "Noto Sans SC", covers: "latin-in-cjk")), weight: "semibold")
+#show math.equation: set text(font: "STIX Two Math")
+// #set text(font: "Commented Out")
+#let code(body) = text(font: "Fira Code", style: "italic", size: 9pt, body)
+#text(font: "New Computer Modern")[embedded]
+#set par(justify: true)
+"#;
+        let usages = scan_source(source);
+        let found = usages
+            .iter()
+            .map(|usage| {
+                (
+                    usage.family.as_str(),
+                    usage.weights.clone(),
+                    usage.math,
+                    usage.line,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![
+                ("Noto Serif", vec![400, 700], false, 2),
+                ("Inter", vec![600], false, 3),
+                ("Noto Sans SC", vec![600], false, 3),
+                ("STIX Two Math", vec![400], true, 4),
+                ("Fira Code", vec![400, 700], false, 6),
+                ("New Computer Modern", vec![400, 700], false, 7),
+            ]
+        );
+        assert_eq!(usages[4].style, FontStyle::Italic);
+
+        let fonts = required_fonts(&usages);
+        assert_eq!(fonts.len(), 7);
+        assert!(
+            fonts
+                .iter()
+                .all(|font| font.family_name != "New Computer Modern")
+        );
+    }
+}
@@ -0,0 +1,394 @@
+//! Queries over a font library: `search`, `info` and `stats`.
+
+use crate::color::{ColoredString, Colorize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::DiscoveredFont;
+use crate::font_manager::format_discovered_variant;
+use crate::process_font::{Embedding, FontClass, FontFeature, FontMetadata};
+use crate::table::Table;
+use typst::text::StandardAxes;
+
+const MATCH_OPTIONS: glob::MatchOptions = glob::MatchOptions {
+    case_sensitive: false,
+    require_literal_separator: false,
+    require_literal_leading_dot: false,
+};
+
+/// Name under which a font's foundry is reported: the manufacturer from the
+/// name table, falling back to the OS/2 vendor ID
+pub(crate) fn foundry_name(metadata: &FontMetadata) -> &str {
+    metadata
+        .manufacturer
+        .as_deref()
+        .or(metadata.vendor_id.as_deref())
+        .unwrap_or("Unknown")
+}
+
+/// Family and foundry patterns, required features and class selecting library entries
+pub(crate) struct LibraryQuery {
+    family: glob::Pattern,
+    foundry: Option<glob::Pattern>,
+    features: Vec<FontFeature>,
+    class: Option<FontClass>,
+}
+
+impl LibraryQuery {
+    pub(crate) fn new(
+        family: &str,
+        foundry: Option<&str>,
+        features: &[FontFeature],
+        class: Option<FontClass>,
+    ) -> Result<Self, String> {
+        let family =
+            glob::Pattern::new(family).map_err(|e| format!("Invalid family pattern: {e}"))?;
+        let foundry = foundry
+            .map(glob::Pattern::new)
+            .transpose()
+            .map_err(|e| format!("Invalid foundry pattern: {e}"))?;
+        Ok(Self {
+            family,
+            foundry,
+            features: features.to_vec(),
+            class,
+        })
+    }
+
+    /// The foundry pattern matches if any of designer, manufacturer or vendor ID matches
+    pub(crate) fn matches(&self, entry: &DiscoveredFont) -> bool {
+        if !self
+            .family
+            .matches_with(&entry.font.family_name, MATCH_OPTIONS)
+        {
+            return false;
+        }
+        if !self
+            .features
+            .iter()
+            .all(|feature| entry.metadata.features.contains(feature))
+        {
+            return false;
+        }
+        if self
+            .class
+            .is_some_and(|class| entry.metadata.class != Some(class))
+        {
+            return false;
+        }
+
+        let Some(foundry) = &self.foundry else {
+            return true;
+        };
+        let metadata = &entry.metadata;
+        [
+            &metadata.designer,
+            &metadata.manufacturer,
+            &metadata.vendor_id,
+        ]
+        .into_iter()
+        .flatten()
+        .any(|value| foundry.matches_with(value, MATCH_OPTIONS))
+    }
+}
+
+/// The standard weights (multiples of 100) a family has, counting every one in
+/// the range of a variable font
+fn family_weights<'a>(
+    entries: impl IntoIterator<Item = &'a DiscoveredFont>,
+) -> BTreeMap<&'a str, BTreeSet<u16>> {
+    let mut weights = BTreeMap::<&str, BTreeSet<u16>>::new();
+    for entry in entries {
+        let family = weights.entry(&entry.font.family_name).or_default();
+        match StandardAxes::parse(&entry.axes).wght {
+            Some(axis) => family.extend(
+                (100..=900)
+                    .step_by(100)
+                    .filter(|&weight| (axis.min.0..=axis.max.0).contains(&f32::from(weight))),
+            ),
+            None => {
+                family.insert(entry.font.weight.to_number());
+            }
+        }
+    }
+    weights
+}
+
+/// The entries of families that have at least `min_weights` weights among `entries`
+pub(crate) fn with_min_weights(
+    entries: Vec<&DiscoveredFont>,
+    min_weights: usize,
+) -> Vec<&DiscoveredFont> {
+    let weights = family_weights(entries.iter().copied());
+    entries
+        .into_iter()
+        .filter(|entry| weights[entry.font.family_name.as_str()].len() >= min_weights)
+        .collect()
+}
+
+/// Print the matching library entries as a table
+pub(crate) fn print_search_results(mut entries: Vec<&DiscoveredFont>) {
+    entries.sort_by_key(|entry| {
+        (
+            entry.font.family_name.to_lowercase(),
+            entry.font.style,
+            entry.font.weight,
+            entry.font.stretch,
+        )
+    });
+
+    if entries.is_empty() {
+        println!("No fonts found");
+        return;
+    }
+
+    let mut table = Table::new(vec![
+        "Family",
+        "Variant",
+        "Class",
+        "Designer",
+        "Foundry",
+        "Features",
+        "Embedding",
+        "Path",
+    ]);
+    for entry in &entries {
+        table.push_row(vec![
+            entry.font.family_name.as_str().normal(),
+            format_discovered_variant(entry).normal(),
+            entry.metadata.class.map_or("-", FontClass::name).normal(),
+            entry.metadata.designer.as_deref().unwrap_or("-").normal(),
+            foundry_name(&entry.metadata).normal(),
+            format_features(&entry.metadata.features).normal(),
+            format_embedding(entry.metadata.fs_type),
+            entry.display_path().dimmed(),
+        ]);
+    }
+    println!("{}", table.render());
+    println!("\n{} font variants found", entries.len());
+}
+
+/// The embedding permission of a face for the search table, red if it forbids
+/// embedding the face in a PDF
+fn format_embedding(fs_type: Option<u16>) -> ColoredString {
+    match fs_type {
+        Some(fs_type) if Embedding::forbids_pdf_embedding(fs_type) => {
+            Embedding::from_fs_type(fs_type).name().red()
+        }
+        Some(fs_type) => Embedding::from_fs_type(fs_type).name().normal(),
+        None => "-".normal(),
+    }
+}
+
+/// Print every variant of `family` in the library together with its metadata
+pub(crate) fn print_family_info(family: &str, entries: &[DiscoveredFont]) {
+    let variants = entries
+        .iter()
+        .filter(|entry| entry.font.family_name.eq_ignore_ascii_case(family))
+        .collect::<Vec<_>>();
+
+    let Some(first) = variants.first() else {
+        println!("Family {family:?} not found in the font library");
+        return;
+    };
+
+    println!("{}", first.font.family_name.bold());
+    for entry in variants {
+        println!("  - {}", format_discovered_variant(entry));
+        println!("    Path:         {}", entry.display_path());
+        let metadata = [
+            ("Designer", &entry.metadata.designer),
+            ("Manufacturer", &entry.metadata.manufacturer),
+            ("Vendor ID", &entry.metadata.vendor_id),
+        ];
+        for (label, value) in metadata {
+            if let Some(value) = value {
+                println!("    {:<14}{value}", format!("{label}:"));
+            }
+        }
+        if let Some(class) = entry.metadata.class {
+            println!("    {:<14}{}", "Class:", class.name());
+        }
+        if let Some(panose) = entry.metadata.panose {
+            let digits = panose.map(|digit| digit.to_string());
+            println!("    {:<14}{}", "PANOSE:", digits.join("-"));
+        }
+        if let Some(fs_type) = entry.metadata.fs_type {
+            println!("    {:<14}{}", "Embedding:", Embedding::describe(fs_type));
+        }
+        if !entry.metadata.features.is_empty() {
+            println!(
+                "    {:<14}{}",
+                "Features:",
+                format_features(&entry.metadata.features)
+            );
+        }
+    }
+}
+
+fn format_features(features: &[FontFeature]) -> String {
+    if features.is_empty() {
+        return "-".to_string();
+    }
+    features
+        .iter()
+        .map(|feature| feature.name())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[derive(Debug, Default, Eq, PartialEq)]
+pub(crate) struct FoundryStats {
+    pub(crate) families: BTreeSet<String>,
+    pub(crate) variants: usize,
+}
+
+/// Number of families and variants per foundry, see [`foundry_name`]
+pub(crate) fn library_stats(entries: &[DiscoveredFont]) -> BTreeMap<String, FoundryStats> {
+    let mut stats = BTreeMap::<String, FoundryStats>::new();
+    for entry in entries {
+        let foundry = stats
+            .entry(foundry_name(&entry.metadata).to_string())
+            .or_default();
+        foundry.families.insert(entry.font.family_name.clone());
+        foundry.variants += 1;
+    }
+    stats
+}
+
+pub(crate) fn print_library_stats(entries: &[DiscoveredFont]) {
+    let families = entries
+        .iter()
+        .map(|entry| entry.font.family_name.to_lowercase())
+        .collect::<BTreeSet<_>>();
+    let files = entries
+        .iter()
+        .map(|entry| &entry.path)
+        .collect::<BTreeSet<_>>();
+    println!(
+        "{} families, {} variants in {} files\n",
+        families.len(),
+        entries.len(),
+        files.len()
+    );
+
+    let mut stats = library_stats(entries).into_iter().collect::<Vec<_>>();
+    stats.sort_by(|(a_name, a), (b_name, b)| {
+        b.variants.cmp(&a.variants).then_with(|| a_name.cmp(b_name))
+    });
+
+    let mut table = Table::new(vec!["Foundry", "Families", "Variants"]);
+    for (name, foundry) in stats {
+        table.push_row(vec![
+            name.normal(),
+            foundry.families.len().to_string().normal(),
+            foundry.variants.to_string().normal(),
+        ]);
+    }
+    println!("{}", table.render());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_font_config::TypstFont;
+    use std::path::PathBuf;
+    use typst::text::{FontStretch, FontStyle, FontWeight};
+
+    fn entry(family: &str, manufacturer: Option<&str>, vendor_id: Option<&str>) -> DiscoveredFont {
+        DiscoveredFont {
+            font: TypstFont {
+                family_name: family.to_string(),
+                style: FontStyle::Normal,
+                weight: FontWeight::REGULAR,
+                stretch: FontStretch::NORMAL,
+            },
+            path: PathBuf::from(format!("{family}.otf")),
+            index: 0,
+            axes: Vec::new(),
+            metadata: FontMetadata {
+                designer: None,
+                manufacturer: manufacturer.map(str::to_string),
+                vendor_id: vendor_id.map(str::to_string),
+                features: Vec::new(),
+                class: None,
+                panose: None,
+                license: None,
+                fs_type: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_query_matches_foundry_fields() {
+        let query = LibraryQuery::new("*", Some("adobe*"), &[], None).unwrap();
+
+        assert!(query.matches(&entry("Source Serif 4", Some("Adobe Systems"), None)));
+        assert!(!query.matches(&entry("Noto Serif", Some("Google"), Some("GOOG"))));
+
+        let query = LibraryQuery::new("*", Some("adbe"), &[], None).unwrap();
+        assert!(query.matches(&entry("Minion Pro", None, Some("ADBE"))));
+
+        let query = LibraryQuery::new("source*", None, &[], None).unwrap();
+        assert!(query.matches(&entry("Source Serif 4", None, None)));
+        assert!(!query.matches(&entry("Noto Serif", None, None)));
+    }
+
+    #[test]
+    fn test_query_requires_all_features() {
+        let mut math = entry("STIX Two Math", None, None);
+        math.metadata.features = vec![FontFeature::Math];
+        let query = LibraryQuery::new("*", None, &[FontFeature::Math], None).unwrap();
+
+        assert!(query.matches(&math));
+        assert!(!query.matches(&entry("STIX Two Text", None, None)));
+
+        let features = [FontFeature::Math, FontFeature::Monospace];
+        let query = LibraryQuery::new("*", None, &features, None).unwrap();
+        assert!(!query.matches(&math));
+    }
+
+    #[test]
+    fn test_filter_by_class_and_weight_count() {
+        let mut serif = entry("Source Serif 4", None, None);
+        serif.metadata.class = Some(FontClass::Serif);
+        let query = LibraryQuery::new("*", None, &[], Some(FontClass::Serif)).unwrap();
+        assert!(query.matches(&serif));
+        assert!(!query.matches(&entry("Source Sans 3", None, None)));
+
+        let mut bold = entry("Source Serif 4", None, None);
+        bold.font.weight = FontWeight::BOLD;
+        let mut variable = entry("Inter", None, None);
+        variable.axes = vec![typst::text::FontAxis {
+            tag: StandardAxes::WGHT,
+            min: typst::text::AxisValue(100.0),
+            max: typst::text::AxisValue(900.0),
+            default: typst::text::AxisValue(400.0),
+        }];
+        let entries = [serif, bold, variable, entry("Lato", None, None)];
+        let families = |min_weights| {
+            with_min_weights(entries.iter().collect(), min_weights)
+                .into_iter()
+                .map(|entry| entry.font.family_name.as_str())
+                .collect::<BTreeSet<_>>()
+        };
+        assert_eq!(families(2), BTreeSet::from(["Inter", "Source Serif 4"]));
+        assert_eq!(families(4), BTreeSet::from(["Inter"]));
+    }
+
+    #[test]
+    fn test_library_stats_groups_by_foundry() {
+        let entries = [
+            entry("Source Serif 4", Some("Adobe"), Some("ADBE")),
+            entry("Source Sans 3", Some("Adobe"), Some("ADBE")),
+            entry("Source Sans 3", Some("Adobe"), Some("ADBE")),
+            entry("Noto Serif", None, Some("GOOG")),
+            entry("Mystery", None, None),
+        ];
+
+        let stats = library_stats(&entries);
+        assert_eq!(stats["Adobe"].families.len(), 2);
+        assert_eq!(stats["Adobe"].variants, 3);
+        assert_eq!(stats["GOOG"].variants, 1);
+        assert_eq!(stats["Unknown"].variants, 1);
+    }
+}
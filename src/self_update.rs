@@ -0,0 +1,272 @@
+//! `self update`: replace the running executable with the binary of the latest
+//! GitHub release. The binary is verified against the `SHA256SUMS` file
+//! published with the release, whose minisign signature is checked with the
+//! public key built into typfont, and nothing is replaced without a matching
+//! checksum.
+
+use minisign_verify::{PublicKey, Signature};
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::http;
+
+const RELEASES_API: &str = "https://api.github.com/repos/hooyuser/typst_font_manager/releases";
+/// Name of the checksum file attached to each release
+const CHECKSUMS_ASSET: &str = "SHA256SUMS";
+/// Name of the minisign signature of the checksum file
+const SIGNATURE_ASSET: &str = "SHA256SUMS.minisig";
+/// Public key of the secret key the release workflow signs `SHA256SUMS` with
+const RELEASE_PUBLIC_KEY: &str = "RWS97E/cqDI2Wcv9yMHnwzk1kR4oufSEtbhEpNHrjzimCHl9JLfA5ewd";
+
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+    html_url: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+/// Name of the release binary for the platform this build runs on
+fn platform_asset() -> Result<&'static str, String> {
+    match (env::consts::OS, env::consts::ARCH) {
+        ("linux", "x86_64") => Ok("typfont_linux"),
+        ("macos", "x86_64") => Ok("typfont_macos-intel"),
+        ("macos", "aarch64") => Ok("typfont_macos-apple-silicon"),
+        ("windows", "x86_64") => Ok("typfont_windows.exe"),
+        (os, arch) => Err(format!(
+            "No release binary is published for {os} on {arch}; update with `cargo install typst_font_manager` instead"
+        )),
+    }
+}
+
+/// The numeric part of a version or release tag, e.g. `(0, 3, 1)` for
+/// `v0.3.1+20250101120000`
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let version = version.trim_start_matches('v');
+    let version = version.split(['+', '-']).next()?;
+    let mut numbers = version.split('.').map(|number| number.parse().ok());
+    let version = (numbers.next()??, numbers.next()??, numbers.next()??);
+    numbers.next().is_none().then_some(version)
+}
+
+/// The checksum of `name` in the contents of a `SHA256SUMS` file, as written by
+/// `sha256sum`
+fn find_checksum<'a>(checksums: &'a str, name: &str) -> Option<&'a str> {
+    checksums.lines().find_map(|line| {
+        let (hash, file) = line.split_once(char::is_whitespace)?;
+        let file = file.trim_start().trim_start_matches('*');
+        let file = file.rsplit('/').next().unwrap_or(file);
+        (file == name).then_some(hash)
+    })
+}
+
+/// `self update`: install the latest release if it is newer than this build,
+/// or with `check_only`, just report whether there is one
+pub(crate) fn self_update(check_only: bool) -> Result<(), String> {
+    let client = http::client()?;
+    let url = format!("{RELEASES_API}/latest");
+    let response = http::get(&client, &url)?
+        .header("Accept", "application/vnd.github+json")
+        .send()
+        .map_err(|e| format!("Failed to query the latest release: {e}"))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Failed to query the latest release: HTTP {}",
+            response.status()
+        ));
+    }
+    let release: Release = response
+        .text()
+        .map_err(|e| format!("Failed to read the latest release: {e}"))
+        .and_then(|text| {
+            serde_json::from_str(&text).map_err(|e| format!("Unexpected release response: {e}"))
+        })?;
+
+    let current = env!("CARGO_PKG_VERSION");
+    let latest = parse_version(&release.tag_name)
+        .ok_or_else(|| format!("Unexpected release tag {:?}", release.tag_name))?;
+    if parse_version(current).is_some_and(|current| current >= latest) {
+        println!("typfont {current} is up to date");
+        return Ok(());
+    }
+    println!(
+        "typfont {} is available (installed: {current}): {}",
+        release.tag_name, release.html_url
+    );
+    if check_only {
+        println!("Run `typfont self update` to install it");
+        return Ok(());
+    }
+
+    let asset_name = platform_asset()?;
+    let asset = release
+        .assets
+        .iter()
+        .find(|asset| asset.name == asset_name)
+        .ok_or_else(|| format!("Release {} has no {asset_name}", release.tag_name))?;
+    let expected_sha256 = release_checksum(&client, &release, asset)?;
+
+    println!("  Downloading {}", asset.browser_download_url);
+    let fetched = http::fetch_verified(
+        &client,
+        &asset.browser_download_url,
+        Some(&expected_sha256),
+        None,
+    )?;
+    println!("  Checksum verified ({expected_sha256})");
+
+    let executable =
+        env::current_exe().map_err(|e| format!("Failed to locate the running executable: {e}"))?;
+    replace_executable(&executable, &fetched.data)?;
+    println!("Updated {} to {}", executable.display(), release.tag_name);
+    Ok(())
+}
+
+/// The expected SHA-256 of `asset`, from the release's `SHA256SUMS` once its
+/// signature is verified
+fn release_checksum(
+    client: &http::Client,
+    release: &Release,
+    asset: &Asset,
+) -> Result<String, String> {
+    let download = |name: &str| {
+        let asset = release
+            .assets
+            .iter()
+            .find(|asset| asset.name == name)
+            .ok_or_else(|| {
+                format!(
+                    "Release {} publishes no {name}, refusing to install it",
+                    release.tag_name
+                )
+            })?;
+        http::fetch_verified(client, &asset.browser_download_url, None, None)
+            .map(|fetched| fetched.data)
+    };
+    let checksums = download(CHECKSUMS_ASSET)?;
+    let signature = download(SIGNATURE_ASSET)?;
+    verify_signature(RELEASE_PUBLIC_KEY, &checksums, &signature).map_err(|e| {
+        format!(
+            "{CHECKSUMS_ASSET} of {} failed verification, refusing to install it: {e}",
+            release.tag_name
+        )
+    })?;
+    let checksums = String::from_utf8_lossy(&checksums);
+    find_checksum(&checksums, &asset.name)
+        .map(str::to_ascii_lowercase)
+        .ok_or_else(|| {
+            format!(
+                "{CHECKSUMS_ASSET} of {} lists no {}",
+                release.tag_name, asset.name
+            )
+        })
+}
+
+/// Check the minisign `signature` of `data` against the base64 `public_key`
+fn verify_signature(public_key: &str, data: &[u8], signature: &[u8]) -> Result<(), String> {
+    let public_key = PublicKey::from_base64(public_key).map_err(|e| e.to_string())?;
+    let signature = std::str::from_utf8(signature)
+        .map_err(|e| e.to_string())
+        .and_then(|signature| Signature::decode(signature).map_err(|e| e.to_string()))?;
+    public_key
+        .verify(data, &signature, false)
+        .map_err(|e| e.to_string())
+}
+
+/// Write `data` next to `executable` and move it into place. A running
+/// executable can't be overwritten on Windows, but it can be renamed, so it is
+/// moved aside first.
+fn replace_executable(executable: &Path, data: &[u8]) -> Result<(), String> {
+    let staged = sibling(executable, "new");
+    fs::write(&staged, data).map_err(|e| format!("Failed to write {staged:?}: {e}"))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&staged, fs::Permissions::from_mode(0o755))
+            .map_err(|e| format!("Failed to make {staged:?} executable: {e}"))?;
+    }
+
+    if cfg!(windows) {
+        let old = sibling(executable, "old");
+        let _ = fs::remove_file(&old);
+        fs::rename(executable, &old)
+            .map_err(|e| format!("Failed to move {executable:?} aside: {e}"))?;
+    }
+    fs::rename(&staged, executable).map_err(|e| {
+        let _ = fs::remove_file(&staged);
+        format!("Failed to replace {executable:?}: {e}")
+    })
+}
+
+/// `path` with `suffix` appended to its file name, e.g. `typfont.new`
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_release_versions() {
+        assert_eq!(parse_version("v0.3.1"), Some((0, 3, 1)));
+        assert_eq!(parse_version("v0.3.1+20250101120000"), Some((0, 3, 1)));
+        assert!(parse_version(env!("CARGO_PKG_VERSION")).is_some());
+        assert_eq!(parse_version("nightly"), None);
+        assert!(parse_version("v0.10.0") > parse_version("v0.9.9"));
+    }
+
+    #[test]
+    fn test_replace_executable() {
+        let dir = env::temp_dir().join(format!("typfont-self-update-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let executable = dir.join("typfont");
+        fs::write(&executable, b"old").unwrap();
+
+        replace_executable(&executable, b"new").unwrap();
+        assert_eq!(fs::read(&executable).unwrap(), b"new");
+        assert!(!sibling(&executable, "new").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_find_checksum_in_sha256sums() {
+        let checksums = "\
+0123abcd  typfont_linux
+4567ef01 *release_artifacts/typfont_windows.exe
+";
+        assert_eq!(find_checksum(checksums, "typfont_linux"), Some("0123abcd"));
+        assert_eq!(
+            find_checksum(checksums, "typfont_windows.exe"),
+            Some("4567ef01")
+        );
+        assert_eq!(find_checksum(checksums, "typfont_macos-intel"), None);
+    }
+
+    #[test]
+    fn test_verify_checksums_signature() {
+        // Signed with a throwaway key by `minisign -S -t "typfont v0.3.0"`
+        let public_key = "RWSMFmQh74CZuAH0FxalgSC07VGtsFVtc2hgBEhaV8zpF7J3wRtl8ok1";
+        let signature = b"\
+untrusted comment: signature from minisign secret key
+RUSMFmQh74CZuBJMIBa/Wt9bYaPS5+OwHRM4++0kj2LiN1C4ZD5pBwKOTE/W6H0visgCraxeXAMhI75ZSiG2F5jhzn1RFqpe9wE=
+trusted comment: typfont v0.3.0
+Oq8MghaT8Kylzxt21VtYZjlBk3FkRoIoI68kED4C2jL0JmKWKKGpXb7jWGYQicI2mXhfkmUPa7jLk7DK4lmQAQ==
+";
+        let checksums = b"0123abcd  typfont_linux\n";
+        assert!(verify_signature(public_key, checksums, signature).is_ok());
+        assert!(verify_signature(public_key, b"4567ef01  typfont_linux\n", signature).is_err());
+        assert!(verify_signature(RELEASE_PUBLIC_KEY, checksums, signature).is_err());
+        assert!(verify_signature(public_key, checksums, b"not a signature").is_err());
+    }
+}
@@ -0,0 +1,132 @@
+//! Directories shared by several users, such as a system-wide cache on a build
+//! server (`/var/cache/typst-font-manager`) or a workspace store that CI
+//! runners under different accounts update. Whatever typfont creates in them
+//! is writable by the group, so that the next user can replace or remove it:
+//! directories get mode `2770` (setgid, so that their entries keep the group of
+//! the shared directory) and files `660`, plus the permissions of other users
+//! that the umask allows. Files are written to a temporary file first and then
+//! renamed, so that a concurrent reader sees either the old or the new file.
+//!
+//! Permissions are left to the system outside of Unix.
+
+use std::fs;
+use std::path::Path;
+
+/// The process umask. Reading it with umask(2) would change it, so it is
+/// taken from /proc where available and assumed to be the common 022 elsewhere.
+#[cfg(unix)]
+fn umask() -> u32 {
+    fs::read_to_string("/proc/self/status")
+        .ok()
+        .and_then(|status| {
+            let line = status.lines().find(|line| line.starts_with("Umask:"))?;
+            u32::from_str_radix(line["Umask:".len()..].trim(), 8).ok()
+        })
+        .unwrap_or(0o022)
+}
+
+#[cfg(not(unix))]
+fn umask() -> u32 {
+    0o022
+}
+
+/// Mode of a shared directory: full access for the owner and the group, and
+/// what the umask leaves of it for others
+fn dir_mode(umask: u32) -> u32 {
+    0o2770 | (0o007 & !umask)
+}
+
+/// Mode of a shared file, see [`dir_mode`]
+fn file_mode(umask: u32) -> u32 {
+    0o660 | (0o006 & !umask)
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+        .map_err(|e| format!("Failed to set the permissions of {path:?}: {e}"))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> Result<(), String> {
+    Ok(())
+}
+
+/// Create `dir` and its missing parents, shared with the group. Directories
+/// that exist already keep their permissions, as they may belong to others.
+pub(crate) fn create_dir_all(dir: &Path) -> Result<(), String> {
+    if dir.is_dir() {
+        return Ok(());
+    }
+    if let Some(parent) = dir.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        create_dir_all(parent)?;
+    }
+    match fs::create_dir(dir) {
+        Ok(()) => set_mode(dir, dir_mode(umask())),
+        // Created by a concurrent process
+        Err(_) if dir.is_dir() => Ok(()),
+        Err(e) => Err(format!("Failed to create directories {dir:?}: {e}")),
+    }
+}
+
+/// Make the file at `path`, created by this process, writable by the group
+pub(crate) fn share_file(path: &Path) -> Result<(), String> {
+    set_mode(path, file_mode(umask()))
+}
+
+/// Write `content` to `path` through a temporary file in the same directory,
+/// which is shared with the group and then renamed to `path`
+pub(crate) fn write_file(path: &Path, content: &[u8]) -> Result<(), String> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| format!("Invalid file path {path:?}"))?
+        .to_string_lossy();
+    let temp = path.with_file_name(format!(".{file_name}.{}.tmp", std::process::id()));
+    let result = fs::write(&temp, content)
+        .map_err(|e| format!("Failed to write {temp:?}: {e}"))
+        .and_then(|_| share_file(&temp))
+        .and_then(|_| {
+            fs::rename(&temp, path).map_err(|e| format!("Failed to replace {path:?}: {e}"))
+        });
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_modes_follow_the_umask_for_others() {
+        assert_eq!(dir_mode(0o022), 0o2775);
+        assert_eq!(file_mode(0o022), 0o664);
+        assert_eq!(dir_mode(0o077), 0o2770);
+        assert_eq!(file_mode(0o077), 0o660);
+        assert_eq!(file_mode(0o002), 0o664);
+        assert_eq!(dir_mode(0o000), 0o2777);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_created_entries_are_group_writable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("typfont-shared-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let nested = dir.join("indexes");
+        create_dir_all(&nested).unwrap();
+        let file = nested.join("index.json");
+        write_file(&file, b"{}").unwrap();
+        write_file(&file, b"[]").unwrap();
+
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o7777;
+        assert_eq!(mode(&nested) & 0o2770, 0o2770);
+        assert_eq!(mode(&file) & 0o660, 0o660);
+        assert_eq!(fs::read(&file).unwrap(), b"[]");
+        assert_eq!(fs::read_dir(&nested).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,282 @@
+//! The status endpoint of `check --watch --status-port PORT`, for build
+//! dashboards scraping the font status of long-lived agents. It listens on
+//! 127.0.0.1 only and answers two requests:
+//!
+//! ```text
+//! GET /status    the status posted by --notify-webhook, as JSON, with the
+//!                number of checks and the time of the last one
+//! GET /metrics   the same counts in the Prometheus text format
+//! ```
+//!
+//! The watch loop records the result of every check, see [`StatusServer::record`].
+
+use serde_json::{Value, json};
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::font_manager::{FontManager, StatusSummary};
+
+/// The result of the latest check of the watched project
+#[derive(Debug, Default)]
+struct WatchStatus {
+    checks: u64,
+    errors: u64,
+    /// Seconds since the Unix epoch
+    last_check: u64,
+    counts: Option<StatusSummary>,
+    /// The JSON report of the latest successful check
+    report: Value,
+    /// Why the latest check failed, if it did
+    error: Option<String>,
+}
+
+pub(crate) struct StatusServer {
+    addr: SocketAddr,
+    status: Arc<Mutex<WatchStatus>>,
+}
+
+impl StatusServer {
+    /// Serve the status of the project configured by `project` on `port` of
+    /// 127.0.0.1, or on a free port if it is 0
+    pub(crate) fn start(port: u16, project: String) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        let addr = listener.local_addr()?;
+        let status = Arc::new(Mutex::new(WatchStatus::default()));
+        {
+            let status = status.clone();
+            let project = Arc::<str>::from(project);
+            // Each connection on a thread of its own, so that a slow client holds up no other
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let (project, status) = (project.clone(), status.clone());
+                    thread::spawn(move || handle(stream, &project, &status));
+                }
+            });
+        }
+        Ok(Self { addr, status })
+    }
+
+    /// The base URL, e.g. `http://127.0.0.1:9464`
+    pub(crate) fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Record the result of a check of the watched project
+    pub(crate) fn record(&self, result: Result<&FontManager, String>) {
+        let mut status = self.status.lock().unwrap();
+        status.checks += 1;
+        status.last_check = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        match result {
+            Ok(font_manager) => {
+                let report = font_manager.status_report();
+                status.counts = Some(report.counts);
+                status.report = serde_json::to_value(&report).unwrap_or_default();
+                status.error = None;
+            }
+            Err(e) => {
+                status.errors += 1;
+                status.error = Some(e);
+            }
+        }
+    }
+
+    #[cfg(test)]
+    fn get(&self, path: &str) -> String {
+        let mut stream = TcpStream::connect(self.addr).unwrap();
+        write!(stream, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        io::Read::read_to_string(&mut stream, &mut response).unwrap();
+        response
+    }
+}
+
+/// How long a client may take to send its request or read the response
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn handle(stream: TcpStream, project: &str, status: &Mutex<WatchStatus>) -> io::Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    // The headers are of no interest
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+    let mut parts = line.split_whitespace();
+    let method = parts.next();
+    let path = parts.next().and_then(|target| target.split('?').next());
+    let status = status.lock().unwrap();
+    let (code, content_type, body) = match (method, path) {
+        (Some("GET"), Some("/status")) => (
+            "200 OK",
+            "application/json",
+            status_json(project, &status).to_string(),
+        ),
+        (Some("GET"), Some("/metrics")) => (
+            "200 OK",
+            "text/plain; version=0.0.4",
+            metrics(project, &status),
+        ),
+        (Some("GET"), _) => ("404 Not Found", "text/plain", "404 Not Found\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "405 Method Not Allowed\n".to_string(),
+        ),
+    };
+    drop(status);
+    write_response(stream, code, content_type, &body)
+}
+
+fn write_response(
+    mut stream: TcpStream,
+    code: &str,
+    content_type: &str,
+    body: &str,
+) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {code}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+fn status_json(project: &str, status: &WatchStatus) -> Value {
+    let mut value = match &status.report {
+        Value::Object(_) => status.report.clone(),
+        _ => json!({ "project": project }),
+    };
+    value["checks"] = json!(status.checks);
+    value["last_check"] = json!(status.last_check);
+    value["error"] = json!(status.error);
+    value
+}
+
+/// The status in the Prometheus text exposition format
+fn metrics(project: &str, status: &WatchStatus) -> String {
+    let label = format!("{{project=\"{}\"}}", escape_label(project));
+    let mut metrics = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, value: u64| {
+        let _ = writeln!(metrics, "# HELP typfont_{name} {help}");
+        let _ = writeln!(metrics, "# TYPE typfont_{name} {kind}");
+        let _ = writeln!(metrics, "typfont_{name}{label} {value}");
+    };
+    metric(
+        "up",
+        "gauge",
+        "Whether the latest check of the project succeeded",
+        u64::from(status.checks > 0 && status.error.is_none()),
+    );
+    metric(
+        "checks_total",
+        "counter",
+        "Checks of the project since watching started",
+        status.checks,
+    );
+    metric(
+        "check_errors_total",
+        "counter",
+        "Checks of the project that failed",
+        status.errors,
+    );
+    metric(
+        "last_check_timestamp_seconds",
+        "gauge",
+        "Time of the latest check, in seconds since the Unix epoch",
+        status.last_check,
+    );
+    if let Some(counts) = status.counts {
+        for (name, help, value) in [
+            (
+                "missing_fonts",
+                "Required fonts missing from the project",
+                counts.missing,
+            ),
+            (
+                "faux_fonts",
+                "Missing variants of families present in the project",
+                counts.faux,
+            ),
+            (
+                "fixable_fonts",
+                "Missing fonts the library provides",
+                counts.fixable,
+            ),
+            (
+                "redundant_fonts",
+                "Installed fonts no required font uses",
+                counts.redundant,
+            ),
+        ] {
+            metric(name, "gauge", help, value as u64);
+        }
+    }
+    metrics
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_server_reports_the_latest_check() {
+        let server = StatusServer::start(0, "dir/font_config.toml".to_string()).unwrap();
+        // A client that never sends its request doesn't hold up the others
+        let _idle = TcpStream::connect(server.addr).unwrap();
+        let metrics = server.get("/metrics");
+        assert!(metrics.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(metrics.contains("typfont_up{project=\"dir/font_config.toml\"} 0\n"));
+        assert!(!metrics.contains("typfont_missing_fonts"));
+
+        server.record(Err("font_config.toml: invalid weight".to_string()));
+        let status = server.get("/status");
+        let body = status.split("\r\n\r\n").nth(1).unwrap();
+        let value: Value = serde_json::from_str(body).unwrap();
+        assert_eq!(value["checks"], 1);
+        assert_eq!(value["error"], "font_config.toml: invalid weight");
+        assert!(
+            server
+                .get("/metrics")
+                .contains("typfont_check_errors_total{project=\"dir/font_config.toml\"} 1\n")
+        );
+
+        {
+            let mut status = server.status.lock().unwrap();
+            status.counts = Some(StatusSummary {
+                missing: 3,
+                faux: 0,
+                fixable: 2,
+                redundant: 1,
+            });
+            status.error = None;
+        }
+        let metrics = server.get("/metrics");
+        assert!(metrics.contains("typfont_up{project=\"dir/font_config.toml\"} 1\n"));
+        assert!(metrics.contains("# TYPE typfont_missing_fonts gauge\n"));
+        assert!(metrics.contains("typfont_missing_fonts{project=\"dir/font_config.toml\"} 3\n"));
+        assert!(server.get("/other").starts_with("HTTP/1.1 404 Not Found"));
+    }
+
+    #[test]
+    fn test_escape_label() {
+        assert_eq!(escape_label("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
@@ -0,0 +1,259 @@
+//! Removal of sfnt tables that Typst never reads, such as TrueType bytecode
+//! hinting and digital signatures, to shrink installed font files, and
+//! extraction of single faces from font collections.
+
+/// Tables dropped by `update --strip`. Typst neither hints glyphs nor verifies
+/// signatures, and PDF output only embeds the outlines and metrics it uses.
+pub(crate) const STRIPPED_TABLES: [&[u8; 4]; 9] = [
+    b"DSIG", b"fpgm", b"prep", b"cvt ", b"hdmx", b"VDMX", b"LTSH", b"PCLT", b"gasp",
+];
+
+const HEAD_CHECKSUM_ADJUSTMENT_OFFSET: usize = 8;
+const CHECKSUM_MAGIC: u32 = 0xB1B0_AFBA;
+const COLLECTION_TAG: u32 = 0x7474_6366; // "ttcf"
+const OPENTYPE_CFF_VERSION: u32 = 0x4F54_544F; // "OTTO"
+
+struct TableRecord<'a> {
+    tag: [u8; 4],
+    data: &'a [u8],
+}
+
+/// Rebuild a single-face TrueType/OpenType font without the [`STRIPPED_TABLES`].
+/// Returns `Ok(None)` if the font contains none of them.
+pub(crate) fn strip_font_tables(data: &[u8]) -> Result<Option<Vec<u8>>, String> {
+    let sfnt_version = read_u32(data, 0)?;
+    if !is_sfnt_version(sfnt_version) {
+        return Err("only single-face TrueType/OpenType fonts can be stripped".to_string());
+    }
+
+    let mut tables = read_table_directory(data, 0)?;
+    let before = tables.len();
+    tables.retain(|table| !STRIPPED_TABLES.contains(&&table.tag));
+    if tables.len() == before {
+        return Ok(None);
+    }
+
+    Ok(Some(write_sfnt(sfnt_version, &mut tables)))
+}
+
+/// Rebuild the face at `index` of a .ttc/.otc collection as a standalone font.
+/// Returns the font and whether it has CFF outlines, i.e. should be saved as .otf.
+pub(crate) fn extract_face(data: &[u8], index: u32) -> Result<(Vec<u8>, bool), String> {
+    if read_u32(data, 0)? != COLLECTION_TAG {
+        return Err("not a font collection".to_string());
+    }
+
+    let num_fonts = read_u32(data, 8)?;
+    if index >= num_fonts {
+        return Err(format!(
+            "face {index} does not exist, the collection has {num_fonts} faces"
+        ));
+    }
+
+    let offset = read_u32(data, 12 + index as usize * 4)? as usize;
+    let sfnt_version = read_u32(data, offset)?;
+    if !is_sfnt_version(sfnt_version) {
+        return Err(format!("face {index} is not a TrueType/OpenType font"));
+    }
+
+    // Table offsets inside a collection are relative to the start of the file
+    let mut tables = read_table_directory(data, offset)?;
+    Ok((
+        write_sfnt(sfnt_version, &mut tables),
+        sfnt_version == OPENTYPE_CFF_VERSION,
+    ))
+}
+
+fn is_sfnt_version(version: u32) -> bool {
+    matches!(version, 0x0001_0000 | OPENTYPE_CFF_VERSION | 0x7472_7565)
+}
+
+/// Read the table directory of the font whose offset table starts at `start`
+fn read_table_directory(data: &[u8], start: usize) -> Result<Vec<TableRecord<'_>>, String> {
+    let num_tables = read_u16(data, start + 4)? as usize;
+    let mut tables = Vec::with_capacity(num_tables);
+    for index in 0..num_tables {
+        let record = start + 12 + index * 16;
+        let tag = data
+            .get(record..record + 4)
+            .ok_or("truncated table directory")?;
+        let offset = read_u32(data, record + 8)? as usize;
+        let length = read_u32(data, record + 12)? as usize;
+        let table = data
+            .get(offset..offset + length)
+            .ok_or("table extends past the end of the file")?;
+        tables.push(TableRecord {
+            tag: tag.try_into().unwrap(),
+            data: table,
+        });
+    }
+    Ok(tables)
+}
+
+fn write_sfnt(sfnt_version: u32, tables: &mut [TableRecord]) -> Vec<u8> {
+    tables.sort_by_key(|table| table.tag);
+
+    let num_tables = tables.len() as u16;
+    let entry_selector = (num_tables as f64).log2().floor() as u16;
+    let search_range = 16 * (1u16 << entry_selector);
+
+    let mut font = Vec::new();
+    font.extend_from_slice(&sfnt_version.to_be_bytes());
+    font.extend_from_slice(&num_tables.to_be_bytes());
+    font.extend_from_slice(&search_range.to_be_bytes());
+    font.extend_from_slice(&entry_selector.to_be_bytes());
+    font.extend_from_slice(&(num_tables * 16 - search_range).to_be_bytes());
+
+    let mut offset = 12 + tables.len() * 16;
+    let mut head_offset = None;
+    for table in tables.iter() {
+        let mut data = table.data.to_vec();
+        if &table.tag == b"head" && data.len() >= HEAD_CHECKSUM_ADJUSTMENT_OFFSET + 4 {
+            data[HEAD_CHECKSUM_ADJUSTMENT_OFFSET..HEAD_CHECKSUM_ADJUSTMENT_OFFSET + 4].fill(0);
+            head_offset = Some(offset);
+        }
+
+        font.extend_from_slice(&table.tag);
+        font.extend_from_slice(&checksum(&data).to_be_bytes());
+        font.extend_from_slice(&(offset as u32).to_be_bytes());
+        font.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        offset += padded_len(data.len());
+    }
+
+    for table in tables.iter() {
+        font.extend_from_slice(table.data);
+        font.resize(padded_len(font.len()), 0);
+    }
+
+    if let Some(head_offset) = head_offset {
+        let start = head_offset + HEAD_CHECKSUM_ADJUSTMENT_OFFSET;
+        font[start..start + 4].fill(0);
+        let adjustment = CHECKSUM_MAGIC.wrapping_sub(checksum(&font));
+        font[start..start + 4].copy_from_slice(&adjustment.to_be_bytes());
+    }
+
+    font
+}
+
+fn padded_len(len: usize) -> usize {
+    len.div_ceil(4) * 4
+}
+
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, String> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_be_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| "truncated font header".to_string())
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, String> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| "truncated font header".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_font() -> Vec<u8> {
+        let mut tables = vec![
+            TableRecord {
+                tag: *b"head",
+                data: &[0; 54],
+            },
+            TableRecord {
+                tag: *b"DSIG",
+                data: &[0, 0, 0, 1, 0, 0, 0, 0],
+            },
+            TableRecord {
+                tag: *b"name",
+                data: &[1, 2, 3],
+            },
+        ];
+        write_sfnt(0x0001_0000, &mut tables)
+    }
+
+    #[test]
+    fn test_strip_font_tables_drops_signature() {
+        let font = sample_font();
+        let stripped = strip_font_tables(&font).unwrap().unwrap();
+
+        assert_eq!(read_u16(&stripped, 4).unwrap(), 2);
+        assert!(stripped.len() < font.len());
+        assert_eq!(checksum(&stripped), CHECKSUM_MAGIC);
+        assert_eq!(strip_font_tables(&stripped).unwrap(), None);
+    }
+
+    /// Wrap single-face fonts into a collection. Their table offsets are shifted
+    /// by the size of the collection header.
+    fn collection(fonts: &[Vec<u8>]) -> Vec<u8> {
+        let header_len = 12 + 4 * fonts.len();
+        let mut data = Vec::new();
+        data.extend_from_slice(b"ttcf");
+        data.extend_from_slice(&0x0001_0000u32.to_be_bytes());
+        data.extend_from_slice(&(fonts.len() as u32).to_be_bytes());
+        let mut offset = header_len;
+        for font in fonts {
+            data.extend_from_slice(&(offset as u32).to_be_bytes());
+            offset += font.len();
+        }
+        for font in fonts {
+            let base = data.len();
+            let mut font = font.clone();
+            let num_tables = read_u16(&font, 4).unwrap() as usize;
+            for table in 0..num_tables {
+                let record = 12 + table * 16 + 8;
+                let shifted = read_u32(&font, record).unwrap() + base as u32;
+                font[record..record + 4].copy_from_slice(&shifted.to_be_bytes());
+            }
+            data.extend_from_slice(&font);
+        }
+        data
+    }
+
+    #[test]
+    fn test_extract_face_from_collection() {
+        let font = sample_font();
+        let data = collection(&[font.clone(), font.clone()]);
+
+        let (extracted, cff) = extract_face(&data, 1).unwrap();
+        assert!(!cff);
+        assert_eq!(extracted, font);
+        assert!(extract_face(&data, 2).is_err());
+        assert!(extract_face(&font, 0).is_err());
+    }
+
+    #[cfg(feature = "test-fixtures")]
+    #[test]
+    fn test_stripped_fixture_font_still_parses() {
+        let (_, font) = crate::fixtures::FONTS[0];
+        assert_eq!(strip_font_tables(font).unwrap(), None);
+
+        // The fixture with a hinting program and a signature added
+        let mut tables = read_table_directory(font, 0).unwrap();
+        tables.push(TableRecord {
+            tag: *b"fpgm",
+            data: &[0xB0, 0x00],
+        });
+        tables.push(TableRecord {
+            tag: *b"DSIG",
+            data: &[0, 0, 0, 1, 0, 0, 0, 0],
+        });
+        let hinted = write_sfnt(read_u32(font, 0).unwrap(), &mut tables);
+        let stripped = strip_font_tables(&hinted).unwrap().unwrap();
+
+        assert!(stripped.len() < hinted.len());
+        assert_eq!(read_u16(&stripped, 4), read_u16(font, 4));
+        assert_eq!(checksum(&stripped), CHECKSUM_MAGIC);
+        let info = typst::text::FontInfo::new(&stripped, 0).unwrap();
+        assert_eq!(info, typst::text::FontInfo::new(font, 0).unwrap());
+    }
+}
@@ -0,0 +1,252 @@
+//! The `suggest` subcommand: for required families that no library source has,
+//! propose available families of the same typographic class that cover the
+//! same variants, as a starting point for picking a replacement.
+
+use crate::color::Colorize;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use crate::DiscoveredFont;
+use crate::font_manager::font_entry_satisfies;
+use crate::parse_font_config::TypstFont;
+use crate::process_font::{FontClass, FontFeature};
+use crate::table::Table;
+
+/// What is known about the look of a family
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct Profile {
+    pub(crate) class: Option<FontClass>,
+    /// Whether the class was declared by the font rather than guessed from the name
+    pub(crate) declared: bool,
+    pub(crate) monospace: Option<bool>,
+}
+
+impl Profile {
+    /// The profile of `family` from its faces in `entries`, falling back to its
+    /// name for what the faces don't tell
+    pub(crate) fn of(family: &str, entries: &[&DiscoveredFont]) -> Self {
+        let declared = entries.iter().find_map(|entry| entry.metadata.class);
+        let monospace = (!entries.is_empty()).then(|| {
+            entries
+                .iter()
+                .any(|entry| entry.metadata.features.contains(&FontFeature::Monospace))
+        });
+        let guessed_monospace = || {
+            let family = family.to_ascii_lowercase();
+            (family.contains("mono") || family.contains("code")).then_some(true)
+        };
+        Profile {
+            class: declared.or_else(|| FontClass::from_family_name(family)),
+            declared: declared.is_some(),
+            monospace: monospace
+                .filter(|&monospace| monospace)
+                .or_else(guessed_monospace),
+        }
+    }
+}
+
+/// An available family proposed as a replacement
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) struct Suggestion<'a> {
+    pub(crate) family: &'a str,
+    pub(crate) profile: Profile,
+    /// Number of the missing variants it has
+    pub(crate) covered: usize,
+}
+
+/// Up to `limit` families of `entries` resembling `target`, best first. They are
+/// ranked by class, then by whether they are monospaced like the target, by the
+/// number of the `missing` variants they have, and by words shared with the
+/// target's name. Families of another class, and symbol fonts unless the target
+/// is one, are left out.
+pub(crate) fn suggest_families<'a>(
+    target: &str,
+    profile: Profile,
+    missing: &[&TypstFont],
+    entries: &'a [DiscoveredFont],
+    limit: usize,
+) -> Vec<Suggestion<'a>> {
+    let mut families = BTreeMap::<&str, Vec<&DiscoveredFont>>::new();
+    for entry in entries {
+        families
+            .entry(entry.font.family_name.as_str())
+            .or_default()
+            .push(entry);
+    }
+
+    let target_words = name_words(target);
+    let mut suggestions = families
+        .into_iter()
+        .filter(|(family, _)| !family.eq_ignore_ascii_case(target))
+        .filter_map(|(family, faces)| {
+            let candidate = Profile::of(family, &faces);
+            let class_match = match (profile.class, candidate.class) {
+                (Some(wanted), Some(class)) if wanted != class => return None,
+                (Some(_), Some(_)) => 2,
+                _ if candidate.class == Some(FontClass::Symbol) => return None,
+                _ => 1,
+            };
+            let covered = missing
+                .iter()
+                .filter(|font| {
+                    let font = TypstFont {
+                        family_name: family.to_string(),
+                        ..(**font).clone()
+                    };
+                    faces.iter().any(|face| font_entry_satisfies(face, &font))
+                })
+                .count();
+            if covered == 0 {
+                return None;
+            }
+            let shared_words = name_words(family)
+                .iter()
+                .filter(|word| target_words.contains(word))
+                .count();
+            let rank = (
+                class_match,
+                profile.monospace.unwrap_or(false) == candidate.monospace.unwrap_or(false),
+                covered,
+                shared_words,
+            );
+            Some((
+                rank,
+                Suggestion {
+                    family,
+                    profile: candidate,
+                    covered,
+                },
+            ))
+        })
+        .collect::<Vec<_>>();
+    suggestions.sort_by_key(|(rank, suggestion)| (Reverse(*rank), suggestion.family));
+    suggestions
+        .into_iter()
+        .take(limit)
+        .map(|(_, suggestion)| suggestion)
+        .collect()
+}
+
+fn name_words(family: &str) -> Vec<String> {
+    family
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect()
+}
+
+fn describe_profile(profile: Profile) -> String {
+    let mut description = match profile.class {
+        Some(class) if profile.declared => class.name().to_string(),
+        Some(class) => format!("{} (from name)", class.name()),
+        None => "unknown class".to_string(),
+    };
+    if profile.monospace == Some(true) {
+        description.push_str(", monospace");
+    }
+    description
+}
+
+/// Print the suggestions for the missing variants of `family`
+pub(crate) fn print_suggestions(
+    family: &str,
+    profile: Profile,
+    missing: &[&TypstFont],
+    suggestions: &[Suggestion],
+) {
+    println!(
+        "\n{} — {} variant{} not found in any source ({})",
+        family.bold(),
+        missing.len(),
+        if missing.len() == 1 { "" } else { "s" },
+        describe_profile(profile)
+    );
+    if suggestions.is_empty() {
+        println!("  No similar family is available");
+        return;
+    }
+    let mut table = Table::new(vec!["Family", "Class", "Variants"]);
+    for suggestion in suggestions {
+        let variants = format!("{}/{}", suggestion.covered, missing.len());
+        table.push_row(vec![
+            suggestion.family.into(),
+            describe_profile(suggestion.profile).into(),
+            if suggestion.covered == missing.len() {
+                variants.green()
+            } else {
+                variants.yellow()
+            },
+        ]);
+    }
+    println!("{}", table.render());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process_font::FontMetadata;
+    use std::path::PathBuf;
+    use typst::text::{FontStretch, FontStyle, FontWeight};
+
+    fn font(family: &str, weight: u16) -> TypstFont {
+        TypstFont {
+            family_name: family.to_string(),
+            style: FontStyle::Normal,
+            weight: FontWeight::from_number(weight),
+            stretch: FontStretch::NORMAL,
+        }
+    }
+
+    fn entry(family: &str, weight: u16, class: Option<FontClass>) -> DiscoveredFont {
+        DiscoveredFont {
+            font: font(family, weight),
+            path: PathBuf::from(format!("{family}-{weight}.otf")),
+            index: 0,
+            axes: Vec::new(),
+            metadata: FontMetadata {
+                class,
+                ..FontMetadata::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_suggestions_match_class_and_coverage() {
+        let entries = [
+            entry("Source Sans 3", 400, Some(FontClass::Sans)),
+            entry("Source Sans 3", 700, Some(FontClass::Sans)),
+            entry("Roboto", 400, Some(FontClass::Sans)),
+            entry("Source Serif 4", 400, Some(FontClass::Serif)),
+            entry("Source Serif 4", 700, Some(FontClass::Serif)),
+            entry("STIX Two Math", 400, Some(FontClass::Symbol)),
+        ];
+        let (regular, bold) = (font("Lato", 400), font("Lato", 700));
+        let missing = [&regular, &bold];
+        let profile = Profile {
+            class: Some(FontClass::Sans),
+            declared: true,
+            monospace: Some(false),
+        };
+
+        let suggestions = suggest_families("Lato", profile, &missing, &entries, 5);
+        let families = suggestions
+            .iter()
+            .map(|suggestion| (suggestion.family, suggestion.covered))
+            .collect::<Vec<_>>();
+        assert_eq!(families, [("Source Sans 3", 2), ("Roboto", 1)]);
+
+        // Without a known class, anything but symbol fonts may do
+        let suggestions = suggest_families("Lato", Profile::default(), &missing, &entries, 5);
+        assert_eq!(suggestions.len(), 3);
+        assert!(suggestions.iter().all(|s| s.family != "STIX Two Math"));
+    }
+
+    #[test]
+    fn test_class_is_guessed_from_the_name() {
+        let profile = Profile::of("Noto Sans Mono", &[]);
+        assert_eq!(profile.class, Some(FontClass::Sans));
+        assert!(!profile.declared);
+        assert_eq!(profile.monospace, Some(true));
+        assert_eq!(Profile::of("Lato", &[]).class, None);
+    }
+}
@@ -0,0 +1,187 @@
+use crate::color::{ColoredString, Colorize};
+use unicode_width::UnicodeWidthStr;
+
+/// A plain-text table whose columns are aligned by display width, so that wide
+/// (e.g. CJK) family names don't break the layout
+pub(crate) struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<ColoredString>>,
+}
+
+impl Table {
+    pub(crate) fn new<S: Into<String>>(headers: Vec<S>) -> Self {
+        Self {
+            headers: headers.into_iter().map(Into::into).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub(crate) fn push_row(&mut self, row: Vec<ColoredString>) {
+        debug_assert_eq!(row.len(), self.headers.len());
+        self.rows.push(row);
+    }
+
+    fn column_widths(&self) -> Vec<usize> {
+        self.headers
+            .iter()
+            .enumerate()
+            .map(|(column, header)| {
+                self.rows
+                    .iter()
+                    .map(|row| row[column].width())
+                    .chain([header.as_str().width()])
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect()
+    }
+
+    pub(crate) fn render(&self) -> String {
+        let widths = self.column_widths();
+        let mut lines = Vec::with_capacity(self.rows.len() + 2);
+
+        let header = self
+            .headers
+            .iter()
+            .map(|header| header.bold())
+            .collect::<Vec<_>>();
+        lines.push(render_row(&header, &widths));
+        lines.push(
+            widths
+                .iter()
+                .map(|width| "─".repeat(*width))
+                .collect::<Vec<_>>()
+                .join("  "),
+        );
+        lines.extend(self.rows.iter().map(|row| render_row(row, &widths)));
+
+        lines
+            .into_iter()
+            .map(|line| format!("  {}", line.trim_end()))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+fn render_row(cells: &[ColoredString], widths: &[usize]) -> String {
+    cells
+        .iter()
+        .zip(widths)
+        .map(|(cell, width)| {
+            // Pad by display width; the cell itself may carry color codes
+            let padding = width.saturating_sub(cell.width());
+            format!("{cell}{}", " ".repeat(padding))
+        })
+        .collect::<Vec<_>>()
+        .join("  ")
+}
+
+trait DisplayWidth {
+    fn width(&self) -> usize;
+}
+
+impl DisplayWidth for ColoredString {
+    fn width(&self) -> usize {
+        UnicodeWidthStr::width(&**self)
+    }
+}
+
+impl DisplayWidth for &str {
+    fn width(&self) -> usize {
+        UnicodeWidthStr::width(*self)
+    }
+}
+
+/// Format a file size in bytes for humans, e.g. `1.5 MiB`
+pub(crate) fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+/// Parse a size in bytes written by humans, e.g. `2GiB`, `500K`, `1.5 MB` or
+/// `4096`. Units are binary, whether written `K`, `KB` or `KiB`.
+pub(crate) fn parse_size(s: &str) -> Result<u64, String> {
+    let value = s.trim();
+    let value = value
+        .strip_suffix(['B', 'b'])
+        .map_or(value, |value| value.strip_suffix('i').unwrap_or(value));
+
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" => 1,
+        "k" => 1024,
+        "m" => 1024 * 1024,
+        "g" => 1024 * 1024 * 1024,
+        "t" => 1024 * 1024 * 1024 * 1024,
+        _ => return Err(format!("invalid size {s:?}, expected e.g. 2GiB or 500K")),
+    };
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid size {s:?}, expected e.g. 2GiB or 500K"))?;
+    Ok((number * multiplier as f64) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `text` without ANSI color escapes, which the header gets when colors are
+    /// enabled
+    fn strip_ansi(text: &str) -> String {
+        let mut plain = String::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c == '\u{1b}' {
+                chars.by_ref().find(|c| *c == 'm');
+            } else {
+                plain.push(c);
+            }
+        }
+        plain
+    }
+
+    #[test]
+    fn test_table_aligns_wide_characters() {
+        let mut table = Table::new(vec!["Family", "Weight"]);
+        table.push_row(vec!["思源宋体".normal(), "400".normal()]);
+        table.push_row(vec!["Noto Serif".normal(), "700".normal()]);
+
+        let rendered = strip_ansi(&table.render());
+        let lines = rendered.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines[2], "  思源宋体    400");
+        assert_eq!(lines[3], "  Noto Serif  700");
+        assert_eq!(strip_ansi(&"Family".bold().to_string()), "Family");
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MiB");
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("2GiB"), Ok(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("500K"), Ok(500 * 1024));
+        assert_eq!(parse_size("1.5 MB"), Ok(1536 * 1024));
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert!(parse_size("lots").is_err());
+    }
+}
@@ -0,0 +1,270 @@
+//! The `new` subcommand: bootstrap a Typst project with a starter
+//! `font_config.toml`, a `main.typ` using its fonts, `.gitignore` entries for
+//! the installed fonts and, optionally, a GitHub Actions workflow.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::http;
+use crate::parse_font_config::deserialize_fonts_from_toml;
+
+/// A built-in project template
+pub(crate) struct Template {
+    pub(crate) name: &'static str,
+    pub(crate) description: &'static str,
+    font_config: &'static str,
+    main_typ: &'static str,
+}
+
+pub(crate) const TEMPLATES: [Template; 3] = [
+    Template {
+        name: "article",
+        description: "Libertinus Serif with New Computer Modern Math",
+        font_config: r#"font_dir = "fonts"
+
+[[fonts]]
+family_name = "Libertinus Serif"
+style = "Normal"
+weight = [400, 700]
+
+[[fonts]]
+family_name = "Libertinus Serif"
+style = "Italic"
+weight = [400, 700]
+
+[[fonts]]
+family_name = "New Computer Modern Math"
+weight = 400
+"#,
+        main_typ: r#"#set text(font: "Libertinus Serif")
+#show math.equation: set text(font: "New Computer Modern Math")
+
+= Introduction
+
+Fonts are managed with `typfont`; see `font_config.toml`.
+"#,
+    },
+    Template {
+        name: "thesis",
+        description: "Source Serif 4 and Source Sans 3 with STIX Two Math",
+        font_config: r#"font_dir = "fonts"
+
+[[fonts]]
+family_name = "Source Serif 4"
+style = "Normal"
+weight = [400, 700]
+
+[[fonts]]
+family_name = "Source Serif 4"
+style = "Italic"
+weight = [400, 700]
+
+[[fonts]]
+family_name = "Source Sans 3"
+style = "Normal"
+weight = [400, 700]
+
+[[fonts]]
+family_name = "STIX Two Math"
+weight = 400
+"#,
+        main_typ: r#"#set text(font: "Source Serif 4")
+#show heading: set text(font: "Source Sans 3")
+#show math.equation: set text(font: "STIX Two Math")
+
+= Introduction
+
+Fonts are managed with `typfont`; see `font_config.toml`.
+"#,
+    },
+    Template {
+        name: "cjk",
+        description: "Noto Serif CJK SC and Noto Sans CJK SC",
+        font_config: r#"font_dir = "fonts"
+
+[[fonts]]
+family_name = "Noto Serif CJK SC"
+style = "Normal"
+weight = [400, 700]
+
+[[fonts]]
+family_name = "Noto Sans CJK SC"
+style = "Normal"
+weight = [400, 700]
+"#,
+        main_typ: r#"#set text(font: "Noto Serif CJK SC", lang: "zh")
+#show heading: set text(font: "Noto Sans CJK SC")
+
+= 引言
+
+Fonts are managed with `typfont`; see `font_config.toml`.
+"#,
+    },
+];
+
+/// Lines appended to the project's `.gitignore`
+const GITIGNORE_LINES: [&str; 2] = ["# Fonts installed by `typfont update`", "/fonts/"];
+
+/// The font config of `template`: the name of a built-in template, or the URL
+/// of a `font_config.toml`
+pub(crate) fn template_font_config(
+    template: &str,
+) -> Result<(String, Option<&'static str>), String> {
+    if let Some(builtin) = TEMPLATES.iter().find(|builtin| builtin.name == template) {
+        return Ok((builtin.font_config.to_string(), Some(builtin.main_typ)));
+    }
+    if !template.starts_with("https://") && !template.starts_with("http://") {
+        let names = TEMPLATES
+            .iter()
+            .map(|builtin| format!("\n  {:<8} {}", builtin.name, builtin.description))
+            .collect::<String>();
+        return Err(format!(
+            "Unknown template {template:?}; use the URL of a font_config.toml or one of:{names}"
+        ));
+    }
+
+    let client = http::client()?;
+    let response = http::get(&client, template)?
+        .send()
+        .map_err(|e| format!("Failed to download template {template}: {e}"))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Failed to download template {template}: HTTP {}",
+            response.status()
+        ));
+    }
+    let font_config = response
+        .text()
+        .map_err(|e| format!("Failed to download template {template}: {e}"))?;
+    deserialize_fonts_from_toml(&font_config)
+        .map_err(|e| format!("Template {template} is not a valid font_config.toml: {e:#}"))?;
+    Ok((font_config, None))
+}
+
+/// Create the project files in `dir`, returning the paths written. Existing
+/// files other than `.gitignore` are left alone, but a directory that already
+/// has a `font_config.toml` is refused.
+pub(crate) fn create_project(
+    dir: &Path,
+    font_config: &str,
+    main_typ: Option<&str>,
+    workflow: Option<&str>,
+) -> Result<Vec<PathBuf>, String> {
+    let config_path = dir.join("font_config.toml");
+    if config_path.exists() {
+        return Err(format!("{config_path:?} already exists"));
+    }
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create directory {dir:?}: {e}"))?;
+
+    let mut written = Vec::new();
+    let mut write = |path: PathBuf, content: &str| -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create directory {parent:?}: {e}"))?;
+        }
+        fs::write(&path, content).map_err(|e| format!("Failed to write {path:?}: {e}"))?;
+        written.push(path);
+        Ok(())
+    };
+
+    write(config_path, font_config)?;
+    let main_path = dir.join("main.typ");
+    if !main_path.exists() {
+        write(main_path, main_typ.unwrap_or(DEFAULT_MAIN_TYP))?;
+    }
+    if let Some(workflow) = workflow {
+        let workflow_path = dir.join(".github/workflows/fonts.yml");
+        if !workflow_path.exists() {
+            write(workflow_path, workflow)?;
+        }
+    }
+
+    let gitignore_path = dir.join(".gitignore");
+    let gitignore = fs::read_to_string(&gitignore_path).unwrap_or_default();
+    let additions = gitignore_additions(&gitignore);
+    if !additions.is_empty() {
+        write(gitignore_path, &(gitignore + &additions))?;
+    }
+
+    Ok(written)
+}
+
+const DEFAULT_MAIN_TYP: &str =
+    "= Introduction\n\nFonts are managed with `typfont`; see `font_config.toml`.\n";
+
+/// The lines of [`GITIGNORE_LINES`] missing from `gitignore`, ready to append
+fn gitignore_additions(gitignore: &str) -> String {
+    let missing = GITIGNORE_LINES
+        .iter()
+        .filter(|line| !gitignore.lines().any(|existing| existing.trim() == **line))
+        .collect::<Vec<_>>();
+    if missing.iter().all(|line| line.starts_with('#')) {
+        return String::new();
+    }
+
+    let mut additions = String::new();
+    if !gitignore.is_empty() && !gitignore.ends_with('\n') {
+        additions.push('\n');
+    }
+    for line in missing {
+        additions.push_str(line);
+        additions.push('\n');
+    }
+    additions
+}
+
+/// A GitHub Actions workflow installing the fonts from `library` and compiling
+/// `main.typ`
+pub(crate) fn ci_workflow(library: &str) -> String {
+    format!(
+        r#"name: Fonts
+
+on:
+  push:
+  pull_request:
+
+jobs:
+  build:
+    runs-on: ubuntu-latest
+    steps:
+      - uses: actions/checkout@v4
+
+      - name: Install typfont
+        run: |
+          curl -fsSL -o typfont https://github.com/hooyuser/typst_font_manager/releases/latest/download/typfont_linux
+          chmod +x typfont
+
+      - name: Install fonts
+        run: ./typfont update -l "{library}" -g
+
+      - uses: typst-community/setup-typst@v4
+
+      - name: Compile
+        run: typst compile --font-path fonts main.typ
+"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_templates_are_valid_configs() {
+        for template in &TEMPLATES {
+            let (font_config, main_typ) = template_font_config(template.name).unwrap();
+            assert!(deserialize_fonts_from_toml(&font_config).is_ok());
+            assert!(main_typ.is_some());
+        }
+        assert!(template_font_config("poster").is_err());
+    }
+
+    #[test]
+    fn test_gitignore_additions_skip_existing_lines() {
+        assert_eq!(
+            gitignore_additions("target/"),
+            "\n# Fonts installed by `typfont update`\n/fonts/\n"
+        );
+        assert_eq!(gitignore_additions("/fonts/\n"), "");
+    }
+}
@@ -0,0 +1,170 @@
+//! `--trace-out`: record spans of the expensive steps of a run, scanning
+//! library directories, parsing font files, resolving the project, downloading
+//! and copying files, and write them in the Chrome trace event format. The file
+//! opens in `chrome://tracing`, Perfetto or speedscope (as a flamegraph), and
+//! can be attached to bug reports about slow runs.
+
+use serde::Serialize;
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+static TRACE: OnceLock<Trace> = OnceLock::new();
+
+struct Trace {
+    start: Instant,
+    events: Mutex<Vec<Event>>,
+}
+
+/// A complete event ("ph": "X") of the trace event format
+#[derive(Debug, Serialize)]
+struct Event {
+    name: String,
+    cat: &'static str,
+    ph: &'static str,
+    /// Start, in microseconds since tracing was enabled
+    ts: u64,
+    /// Duration in microseconds
+    dur: u64,
+    pid: u32,
+    tid: u64,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    args: BTreeMap<&'static str, String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TraceFile<'a> {
+    trace_events: &'a [Event],
+    display_time_unit: &'static str,
+}
+
+/// Start recording spans
+pub(crate) fn enable() {
+    let _ = TRACE.set(Trace {
+        start: Instant::now(),
+        events: Mutex::new(Vec::new()),
+    });
+}
+
+/// A span of category `cat`, recorded when it is dropped. Without
+/// `--trace-out`, spans record nothing.
+pub(crate) fn span(cat: &'static str, name: impl Into<String>) -> Span {
+    Span(TRACE.get().map(|_| OpenSpan {
+        name: name.into(),
+        cat,
+        start: Instant::now(),
+        args: BTreeMap::new(),
+    }))
+}
+
+pub(crate) struct Span(Option<OpenSpan>);
+
+struct OpenSpan {
+    name: String,
+    cat: &'static str,
+    start: Instant,
+    args: BTreeMap<&'static str, String>,
+}
+
+impl Span {
+    /// Attach a value shown with the span, such as the file it works on
+    pub(crate) fn arg(mut self, key: &'static str, value: impl Display) -> Self {
+        if let Some(span) = &mut self.0 {
+            span.args.insert(key, value.to_string());
+        }
+        self
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let (Some(span), Some(trace)) = (self.0.take(), TRACE.get()) else {
+            return;
+        };
+        let event = Event {
+            name: span.name,
+            cat: span.cat,
+            ph: "X",
+            ts: micros(span.start.saturating_duration_since(trace.start)),
+            dur: micros(span.start.elapsed()),
+            pid: std::process::id(),
+            tid: thread_id(),
+            args: span.args,
+        };
+        if let Ok(mut events) = trace.events.lock() {
+            events.push(event);
+        }
+    }
+}
+
+fn micros(duration: std::time::Duration) -> u64 {
+    duration.as_micros().try_into().unwrap_or(u64::MAX)
+}
+
+/// A small number identifying the current thread in the trace
+fn thread_id() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    thread_local! {
+        static ID: Cell<u64> = const { Cell::new(0) };
+    }
+    ID.with(|id| {
+        if id.get() == 0 {
+            id.set(NEXT.fetch_add(1, Ordering::Relaxed));
+        }
+        id.get()
+    })
+}
+
+/// Write the spans recorded so far to `path`
+pub(crate) fn write(path: &Path) -> Result<(), String> {
+    let Some(trace) = TRACE.get() else {
+        return Ok(());
+    };
+    let events = trace.events.lock().map_err(|e| e.to_string())?;
+    let json = serde_json::to_string(&TraceFile {
+        trace_events: &events,
+        display_time_unit: "ms",
+    })
+    .map_err(|e| e.to_string())?;
+    fs::write(path, json).map_err(|e| format!("Failed to write trace {path:?}: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spans_are_written_as_complete_events() {
+        enable();
+        {
+            let _outer = span("command", "update");
+            let _inner = span("copy", "Inter.ttf").arg("path", "lib/Inter.ttf");
+        }
+        let path = std::env::temp_dir().join(format!("typfont-trace-{}.json", std::process::id()));
+        write(&path).unwrap();
+        let trace: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let events = trace["traceEvents"].as_array().unwrap();
+        let copy = events
+            .iter()
+            .find(|event| event["name"] == "Inter.ttf")
+            .unwrap();
+        assert_eq!(copy["ph"], "X");
+        assert_eq!(copy["cat"], "copy");
+        assert_eq!(copy["args"]["path"], "lib/Inter.ttf");
+        let update = events
+            .iter()
+            .find(|event| event["name"] == "update")
+            .unwrap();
+        // The inner span is dropped first and lies within the outer one
+        assert!(copy["ts"].as_u64() >= update["ts"].as_u64());
+    }
+}
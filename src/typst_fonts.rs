@@ -0,0 +1,247 @@
+//! Parsing of the `typst fonts --variants` listing, used to bootstrap a font
+//! configuration or library index from the fonts a Typst installation sees.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{IsTerminal, Read};
+use std::path::Path;
+use std::process::Command;
+
+use typst::layout::Ratio;
+use typst::text::{FontStretch, FontStyle, FontWeight};
+
+use crate::parse_font_config::TypstFont;
+
+/// Obtain the `typst fonts --variants` listing, preferring piped standard input
+/// and otherwise running the given Typst executable.
+pub(crate) fn read_typst_fonts_listing(
+    input: Option<&Path>,
+    typst: &Path,
+) -> Result<String, String> {
+    if let Some(input) = input {
+        if input == Path::new("-") {
+            return read_stdin();
+        }
+        return std::fs::read_to_string(input)
+            .map_err(|e| format!("Failed to read {:?}: {}", input, e));
+    }
+
+    if !std::io::stdin().is_terminal() {
+        let piped = read_stdin()?;
+        if !piped.trim().is_empty() {
+            return Ok(piped);
+        }
+    }
+
+    let output = Command::new(typst)
+        .args(["fonts", "--variants"])
+        .output()
+        .map_err(|e| {
+            format!(
+                "Failed to run `{} fonts --variants`: {}",
+                typst.display(),
+                e
+            )
+        })?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "`{} fonts --variants` failed: {}",
+            typst.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    String::from_utf8(output.stdout).map_err(|_| "`typst fonts` printed invalid UTF-8".to_string())
+}
+
+fn read_stdin() -> Result<String, String> {
+    let mut content = String::new();
+    std::io::stdin()
+        .read_to_string(&mut content)
+        .map_err(|e| format!("Failed to read standard input: {e}"))?;
+    Ok(content)
+}
+
+/// Parse the listing into font variants. Family names are unindented lines,
+/// variants are lines like `- Style: Normal, Weight: 400, Stretch: 100%`.
+/// Both the `Debug` (`FontWeight(400)`, `FontStretch(1000)`) and `Display`
+/// renderings of weight and stretch printed by different Typst versions are accepted.
+pub(crate) fn parse_typst_fonts_listing(listing: &str) -> Result<Vec<TypstFont>, String> {
+    let mut fonts = Vec::new();
+    let mut family: Option<&str> = None;
+
+    for (index, line) in listing.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        let Some(variant) = trimmed.strip_prefix("- ") else {
+            family = Some(trimmed);
+            continue;
+        };
+
+        let family_name = family
+            .ok_or_else(|| format!("Line {}: font variant listed before any family", index + 1))?;
+
+        fonts.push(
+            parse_variant(family_name, variant).map_err(|e| format!("Line {}: {e}", index + 1))?,
+        );
+    }
+
+    Ok(fonts)
+}
+
+fn parse_variant(family_name: &str, variant: &str) -> Result<TypstFont, String> {
+    let mut font = TypstFont {
+        family_name: family_name.to_string(),
+        style: FontStyle::Normal,
+        weight: FontWeight::REGULAR,
+        stretch: FontStretch::NORMAL,
+    };
+
+    for field in variant.split(',') {
+        let Some((key, value)) = field.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+
+        match key.trim() {
+            "Style" => {
+                font.style = match value.to_lowercase().as_str() {
+                    "normal" => FontStyle::Normal,
+                    "italic" => FontStyle::Italic,
+                    "oblique" => FontStyle::Oblique,
+                    _ => return Err(format!("invalid style {value:?}")),
+                }
+            }
+            "Weight" => {
+                let number = unwrap_debug(value, "FontWeight")
+                    .parse::<u16>()
+                    .map_err(|_| format!("invalid weight {value:?}"))?;
+                font.weight = FontWeight::from_number(number);
+            }
+            "Stretch" => font.stretch = parse_stretch(value)?,
+            _ => {}
+        }
+    }
+
+    Ok(font)
+}
+
+fn parse_stretch(value: &str) -> Result<FontStretch, String> {
+    let invalid = || format!("invalid stretch {value:?}");
+    let value = unwrap_debug(value, "FontStretch");
+
+    if let Some(percent) = value.strip_suffix('%') {
+        let percent = percent.trim().parse::<f64>().map_err(|_| invalid())?;
+        return Ok(FontStretch::from_ratio(Ratio::new(percent / 100.0)));
+    }
+
+    value
+        .parse::<u16>()
+        .map(|permille| FontStretch::from_ratio(Ratio::new(permille as f64 / 1000.0)))
+        .map_err(|_| invalid())
+}
+
+fn unwrap_debug<'a>(value: &'a str, name: &str) -> &'a str {
+    value
+        .strip_prefix(name)
+        .and_then(|rest| rest.strip_prefix('('))
+        .and_then(|rest| rest.strip_suffix(')'))
+        .unwrap_or(value)
+}
+
+/// Render the fonts as a font_config.toml skeleton, collapsing the weights of
+/// each family/style/stretch combination into a single entry.
+pub(crate) fn to_config_toml(fonts: &[TypstFont]) -> String {
+    let mut grouped = BTreeMap::<(String, FontStyle, FontStretch), Vec<u16>>::new();
+    for font in fonts {
+        grouped
+            .entry((font.family_name.clone(), font.style, font.stretch))
+            .or_default()
+            .push(font.weight.to_number());
+    }
+
+    let mut toml = String::from("font_dir = \"fonts\"\n");
+
+    for ((family_name, style, stretch), mut weights) in grouped {
+        weights.sort_unstable();
+        weights.dedup();
+
+        toml.push_str("\n[[fonts]]\n");
+        writeln!(toml, "family_name = {}", toml::Value::String(family_name)).unwrap();
+        writeln!(toml, "style = \"{style:?}\"").unwrap();
+
+        match weights.as_slice() {
+            [weight] => writeln!(toml, "weight = {weight}").unwrap(),
+            weights => {
+                let weights = weights
+                    .iter()
+                    .map(u16::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                writeln!(toml, "weight = [{weights}]").unwrap();
+            }
+        }
+
+        if stretch != FontStretch::NORMAL {
+            writeln!(
+                toml,
+                "stretch = {}",
+                (stretch.to_ratio().get() * 1000.0).round() as u16
+            )
+            .unwrap();
+        }
+    }
+
+    toml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_font_config::deserialize_fonts_from_toml;
+
+    const LISTING: &str = "\
+DejaVu Sans
+- Style: Normal, Weight: FontWeight(400), Stretch: FontStretch(1000)
+- Style: Normal, Weight: FontWeight(700), Stretch: FontStretch(1000)
+- Style: Oblique, Weight: 400, Stretch: 87.5%
+New Computer Modern
+- Style: Italic, Weight: 400, Stretch: 100%
+";
+
+    #[test]
+    fn test_parse_typst_fonts_listing_accepts_debug_and_display_values() {
+        let fonts = parse_typst_fonts_listing(LISTING).unwrap();
+
+        assert_eq!(fonts.len(), 4);
+        assert_eq!(fonts[1].weight, FontWeight::BOLD);
+        assert_eq!(fonts[2].style, FontStyle::Oblique);
+        assert_eq!(fonts[2].stretch, FontStretch::SEMI_CONDENSED);
+        assert_eq!(fonts[3].family_name, "New Computer Modern");
+        assert_eq!(fonts[3].stretch, FontStretch::NORMAL);
+    }
+
+    #[test]
+    fn test_parse_typst_fonts_listing_rejects_orphan_variant() {
+        assert!(parse_typst_fonts_listing("- Style: Normal, Weight: 400").is_err());
+    }
+
+    #[test]
+    fn test_config_skeleton_round_trips() {
+        let fonts = parse_typst_fonts_listing(LISTING).unwrap();
+        let toml = to_config_toml(&fonts);
+
+        assert!(toml.contains("weight = [400, 700]"));
+        assert!(toml.contains("stretch = 875"));
+
+        let mut parsed = deserialize_fonts_from_toml(&toml).unwrap().fonts;
+        let mut expected = fonts;
+        parsed.sort();
+        expected.sort();
+        assert_eq!(parsed, expected);
+    }
+}
@@ -0,0 +1,320 @@
+//! Fonts required by the Typst packages a project imports. A package declares
+//! them in a `[tool.typfont]` section of its `typst.toml`, written like the
+//! `fonts` of a font_config.toml:
+//!
+//! ```toml
+//! [[tool.typfont.fonts]]
+//! family_name = "Libertinus Serif"
+//! weight = [400, 700]
+//! ```
+//!
+//! The imports (`#import "@preview/charged-ieee:0.1.2"`) of the project's `.typ`
+//! files, and in turn of the packages, are looked up in Typst's local package
+//! directories, where Typst keeps the packages it downloaded. Each package
+//! requiring fonts becomes a node of the requirement tree, like a font pack.
+
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::Value;
+use walkdir::WalkDir;
+
+use crate::packs::{NodeKind, PackNode};
+use crate::parse_font_config::{TypstFont, expand_weight_arrays};
+
+/// A package import such as `@preview/charged-ieee:0.1.2`
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct PackageSpec {
+    pub(crate) namespace: String,
+    pub(crate) name: String,
+    pub(crate) version: String,
+}
+
+impl PackageSpec {
+    pub(crate) fn parse(spec: &str) -> Option<Self> {
+        let (namespace, rest) = spec.strip_prefix('@')?.split_once('/')?;
+        let (name, version) = rest.split_once(':')?;
+        let valid = |part: &str, extra: char| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == extra)
+        };
+        (valid(namespace, '-') && valid(name, '-') && valid(version, '.')).then(|| Self {
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+            version: version.to_string(),
+        })
+    }
+
+    fn relative_dir(&self) -> PathBuf {
+        [&self.namespace, &self.name, &self.version]
+            .iter()
+            .collect()
+    }
+}
+
+impl fmt::Display for PackageSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "@{}/{}:{}", self.namespace, self.name, self.version)
+    }
+}
+
+/// The package specs among the string literals of a Typst source
+pub(crate) fn find_imports(source: &str) -> Vec<PackageSpec> {
+    source
+        .split('"')
+        .skip(1)
+        .step_by(2)
+        .filter_map(PackageSpec::parse)
+        .collect()
+}
+
+/// Typst's local package directories: the one of packages installed by hand
+/// (`$TYPST_PACKAGE_PATH`, or `typst/packages` in the user data directory) and
+/// the cache of downloaded ones (`$TYPST_PACKAGE_CACHE_PATH`, or
+/// `typst/packages` in the user cache directory)
+pub(crate) fn package_dirs() -> Vec<PathBuf> {
+    let var = |name| env::var_os(name).filter(|value| !value.is_empty());
+    let home = || var("HOME").map(PathBuf::from);
+    let data = var("TYPST_PACKAGE_PATH").map(PathBuf::from).or_else(|| {
+        let base = if cfg!(target_os = "windows") {
+            var("APPDATA").map(PathBuf::from)
+        } else if cfg!(target_os = "macos") {
+            home().map(|home| home.join("Library/Application Support"))
+        } else {
+            var("XDG_DATA_HOME")
+                .map(PathBuf::from)
+                .or_else(|| home().map(|home| home.join(".local/share")))
+        };
+        base.map(|base| base.join("typst/packages"))
+    });
+    let cache = var("TYPST_PACKAGE_CACHE_PATH")
+        .map(PathBuf::from)
+        .or_else(|| {
+            let base = if cfg!(target_os = "windows") {
+                var("LOCALAPPDATA").map(PathBuf::from)
+            } else if cfg!(target_os = "macos") {
+                home().map(|home| home.join("Library/Caches"))
+            } else {
+                var("XDG_CACHE_HOME")
+                    .map(PathBuf::from)
+                    .or_else(|| home().map(|home| home.join(".cache")))
+            };
+            base.map(|base| base.join("typst/packages"))
+        });
+    data.into_iter().chain(cache).collect()
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    tool: Tool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Tool {
+    #[serde(default)]
+    typfont: Option<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PackageFonts {
+    #[serde(default)]
+    fonts: Vec<TypstFont>,
+}
+
+/// The fonts the `[tool.typfont]` section of a package's `typst.toml` requires
+pub(crate) fn parse_manifest_fonts(content: &str) -> Result<Vec<TypstFont>, String> {
+    let manifest: Manifest = toml::from_str(content).map_err(|e| e.to_string())?;
+    let Some(mut section) = manifest.tool.typfont else {
+        return Ok(Vec::new());
+    };
+    if let Some(table) = section.as_table_mut()
+        && let Some(fonts) = table.get("fonts").and_then(Value::as_array)
+    {
+        let fonts = expand_weight_arrays(fonts);
+        table.insert("fonts".to_string(), Value::Array(fonts));
+    }
+    let section = PackageFonts::deserialize(section).map_err(|e| format!("[tool.typfont]: {e}"))?;
+    Ok(section.fonts)
+}
+
+/// The packages imported by the `.typ` files below `project_dir` that require
+/// fonts, themselves or through the packages they import. `skip` is left out
+/// of the search, e.g. the project font directory.
+pub(crate) fn package_tree(project_dir: &Path, skip: &Path) -> Result<Vec<PackNode>, String> {
+    let package_dirs = package_dirs();
+    let skip = skip.canonicalize().unwrap_or_else(|_| skip.to_path_buf());
+    let imports = imports_below(project_dir, Some(&skip));
+    let mut visited = BTreeSet::new();
+    let mut nodes = Vec::new();
+    for spec in imports {
+        if let Some(node) = resolve(&spec, &package_dirs, &mut visited)? {
+            nodes.push(node);
+        }
+    }
+    Ok(nodes)
+}
+
+/// The imports of the `.typ` files below `dir`, in order and without duplicates
+fn imports_below(dir: &Path, skip: Option<&Path>) -> Vec<PackageSpec> {
+    let files = WalkDir::new(dir)
+        .into_iter()
+        .filter_entry(|entry| {
+            let hidden = entry.depth() > 0 && entry.file_name().to_string_lossy().starts_with('.');
+            let skipped = entry.file_type().is_dir()
+                && skip.is_some_and(|skip| {
+                    entry.path() == skip || entry.path().canonicalize().is_ok_and(|dir| dir == skip)
+                });
+            !hidden && !skipped
+        })
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry.file_type().is_file() && entry.path().extension().is_some_and(|ext| ext == "typ")
+        });
+    let mut imports = Vec::new();
+    for entry in files {
+        let Ok(source) = fs::read_to_string(entry.path()) else {
+            continue;
+        };
+        for spec in find_imports(&source) {
+            if !imports.contains(&spec) {
+                imports.push(spec);
+            }
+        }
+    }
+    imports
+}
+
+/// The node of `spec` with the packages it imports, or `None` if neither
+/// require fonts. Packages that aren't in the local package directories yet
+/// (Typst downloads them when compiling) are skipped. Each package is resolved
+/// once, so that import cycles end.
+fn resolve(
+    spec: &PackageSpec,
+    package_dirs: &[PathBuf],
+    visited: &mut BTreeSet<PackageSpec>,
+) -> Result<Option<PackNode>, String> {
+    if !visited.insert(spec.clone()) {
+        return Ok(None);
+    }
+    let Some(dir) = package_dirs
+        .iter()
+        .map(|root| root.join(spec.relative_dir()))
+        .find(|dir| dir.is_dir())
+    else {
+        if crate::verbose() {
+            eprintln!("Package {spec} isn't in the local package directories, skipping it");
+        }
+        return Ok(None);
+    };
+
+    let manifest = dir.join("typst.toml");
+    let fonts = match fs::read_to_string(&manifest) {
+        Ok(content) => {
+            parse_manifest_fonts(&content).map_err(|e| format!("{}: {e}", manifest.display()))?
+        }
+        Err(_) => Vec::new(),
+    };
+    let mut packs = Vec::new();
+    for import in imports_below(&dir, None) {
+        if let Some(node) = resolve(&import, package_dirs, visited)? {
+            packs.push(node);
+        }
+    }
+    if fonts.is_empty() && packs.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(PackNode {
+        name: spec.to_string(),
+        kind: NodeKind::Package,
+        fonts,
+        packs,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_imports() {
+        let source = r#"#import "@preview/charged-ieee:0.1.2": ieee
+#import "template.typ": *
+#let s = "@local/my_pkg:1.0.0-beta"
+#let not-a-spec = "@preview/missing-version""#;
+        let imports = find_imports(source)
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            imports,
+            ["@preview/charged-ieee:0.1.2", "@local/my_pkg:1.0.0-beta"]
+        );
+    }
+
+    #[test]
+    fn test_parse_manifest_fonts() {
+        let manifest = r#"[package]
+name = "thesis"
+version = "0.1.0"
+entrypoint = "lib.typ"
+
+[[tool.typfont.fonts]]
+family_name = "Libertinus Serif"
+weight = [400, 700]
+"#;
+        let fonts = parse_manifest_fonts(manifest).unwrap();
+        assert_eq!(fonts.len(), 2);
+        assert_eq!(fonts[1].weight.to_number(), 700);
+        assert!(
+            parse_manifest_fonts("[package]\nname = \"x\"\n")
+                .unwrap()
+                .is_empty()
+        );
+        assert!(parse_manifest_fonts("[tool.typfont]\nfont = []\n").is_err());
+    }
+
+    #[test]
+    fn test_package_tree_follows_imports() {
+        let dir = std::env::temp_dir().join(format!("typfont-packages-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let packages = dir.join("packages/preview");
+        let write = |path: &Path, content: &str| {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        };
+        write(
+            &packages.join("report/1.0.0/lib.typ"),
+            "#import \"@preview/fonts-only:0.2.0\": *\n#import \"@preview/plain:1.0.0\"",
+        );
+        write(&packages.join("plain/1.0.0/lib.typ"), "");
+        write(
+            &packages.join("fonts-only/0.2.0/typst.toml"),
+            "[[tool.typfont.fonts]]\nfamily_name = \"Inter\"\n",
+        );
+        write(
+            &dir.join("project/main.typ"),
+            "#import \"@preview/report:1.0.0\": *\n#import \"@preview/absent:1.0.0\"",
+        );
+
+        let package_dirs = [dir.join("packages")];
+        let mut visited = BTreeSet::new();
+        let imports = imports_below(&dir.join("project"), None);
+        let nodes = imports
+            .iter()
+            .filter_map(|spec| resolve(spec, &package_dirs, &mut visited).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].name, "@preview/report:1.0.0");
+        assert!(nodes[0].fonts.is_empty());
+        assert_eq!(nodes[0].packs[0].name, "@preview/fonts-only:0.2.0");
+        assert_eq!(nodes[0].packs[0].fonts[0].family_name, "Inter");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,449 @@
+//! The per-user settings file, `typfont/config.toml` in the platform's config
+//! directory, for settings that belong to a machine rather than to a project,
+//! such as the HTTP headers an internal font mirror requires:
+//!
+//! ```toml
+//! # Run by `typfont` without a subcommand, instead of printing the help
+//! default_command = "check --strict"
+//!
+//! [http.headers."fonts.example-corp.com"]
+//! X-Api-Key = "..."
+//! User-Agent = "corp-fonts-client/1.0"
+//!
+//! [http]
+//! allow_hosts = ["example-corp.com"]
+//!
+//! [[http.mirrors]]
+//! prefix = "https://raw.githubusercontent.com/"
+//! urls = ["https://fonts.example-corp.com/github/", "https://raw.githubusercontent.com/"]
+//!
+//! [cache]
+//! shared_dir = "/var/cache/typst-font-manager"
+//!
+//! [library.local_mirrors]
+//! "gooduser/font_lib" = "/mnt/nas/font_lib"
+//!
+//! [credentials.work]
+//! token_env = "WORK_GITHUB_TOKEN"
+//!
+//! [credentials.personal]
+//! token = "github_pat_..."
+//!
+//! [library.credentials]
+//! "example-corp/*" = "work"
+//! "gooduser/font_lib" = "personal"
+//! ```
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::http::header::{HeaderName, HeaderValue};
+
+/// Overrides the location of the settings file
+const CONFIG_FILE_VAR: &str = "TYPFONT_CONFIG";
+
+static USER_CONFIG: OnceLock<UserConfig> = OnceLock::new();
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct UserConfig {
+    /// Subcommand with its arguments, run when typfont is given none
+    pub(crate) default_command: Option<String>,
+    #[serde(default)]
+    pub(crate) http: HttpSettings,
+    #[serde(default)]
+    pub(crate) cache: CacheSettings,
+    #[serde(default)]
+    pub(crate) library: LibrarySettings,
+    /// Credential profiles by name, see [`LibrarySettings::credentials`]
+    #[serde(default)]
+    pub(crate) credentials: BTreeMap<String, Credentials>,
+}
+
+impl UserConfig {
+    /// The token of the credential profile assigned to the GitHub repository
+    /// `repo` ("owner/repo"), if there is one
+    pub(crate) fn github_token(&self, repo: &str) -> Result<Option<String>, String> {
+        let Some(profile) = self.library.credential_profile(repo) else {
+            return Ok(None);
+        };
+        match self.credentials.get(profile) {
+            Some(credentials) => credentials.token(profile).map(Some),
+            None => Err(format!("No credential profile {profile:?}")),
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        self.http.validate()?;
+        for (name, credentials) in &self.credentials {
+            if credentials.token.is_some() == credentials.token_env.is_some() {
+                return Err(format!(
+                    "The credential profile {name:?} needs either token or token_env"
+                ));
+            }
+        }
+        for (repo, profile) in &self.library.credentials {
+            glob::Pattern::new(repo).map_err(|e| {
+                format!("Invalid repository glob {repo:?} in library.credentials: {e}")
+            })?;
+            if !self.credentials.contains_key(profile) {
+                return Err(format!(
+                    "{repo:?} uses the credential profile {profile:?}, which isn't defined"
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A `[credentials.<profile>]` table: the token sent to the GitHub libraries
+/// using the profile, given itself or by the environment variable holding it
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Credentials {
+    #[serde(default)]
+    pub(crate) token: Option<String>,
+    #[serde(default)]
+    pub(crate) token_env: Option<String>,
+}
+
+impl Credentials {
+    fn token(&self, profile: &str) -> Result<String, String> {
+        if let Some(token) = &self.token {
+            return Ok(token.clone());
+        }
+        let var = self.token_env.as_deref().unwrap_or_default();
+        env::var(var)
+            .ok()
+            .filter(|token| !token.is_empty())
+            .ok_or_else(|| {
+                format!(
+                    "The credential profile {profile:?} reads its token from {var}, which isn't set"
+                )
+            })
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct LibrarySettings {
+    /// Local copies of GitHub libraries by repository ("owner/repo"), such as a
+    /// clone on the office NAS. Their index and files are used first; what they
+    /// lack is downloaded from GitHub.
+    #[serde(default)]
+    pub(crate) local_mirrors: BTreeMap<String, PathBuf>,
+    /// Credential profiles of GitHub libraries, by repository ("owner/repo") or
+    /// glob ("owner/*"), so that work and personal libraries each get their own
+    /// token. The longest matching key wins.
+    #[serde(default)]
+    pub(crate) credentials: BTreeMap<String, String>,
+}
+
+impl LibrarySettings {
+    /// The local mirror of the GitHub repository `repo`, ignoring ASCII case
+    pub(crate) fn local_mirror(&self, repo: &str) -> Option<&Path> {
+        self.local_mirrors
+            .iter()
+            .find(|(mirrored, _)| mirrored.eq_ignore_ascii_case(repo))
+            .map(|(_, dir)| dir.as_path())
+    }
+
+    /// The credential profile of the GitHub repository `repo`, ignoring ASCII case
+    pub(crate) fn credential_profile(&self, repo: &str) -> Option<&str> {
+        let options = glob::MatchOptions {
+            case_sensitive: false,
+            ..Default::default()
+        };
+        self.credentials
+            .iter()
+            .filter(|(glob, _)| {
+                glob::Pattern::new(glob).is_ok_and(|pattern| pattern.matches_with(repo, options))
+            })
+            .max_by_key(|(glob, _)| glob.len())
+            .map(|(_, profile)| profile.as_str())
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct CacheSettings {
+    /// Cache shared by the users of the machine, such as CI runners on a build
+    /// server, instead of the per-user one
+    #[serde(default)]
+    pub(crate) shared_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct HttpSettings {
+    /// Headers sent with every request to a domain and its subdomains, by domain
+    #[serde(default)]
+    pub(crate) headers: BTreeMap<String, BTreeMap<String, String>>,
+    /// If not empty, only these domains and their subdomains may be contacted
+    #[serde(default)]
+    pub(crate) allow_hosts: Vec<String>,
+    /// Domains that may never be contacted, even if allowed
+    #[serde(default)]
+    pub(crate) deny_hosts: Vec<String>,
+    /// Alternative locations of downloaded files
+    #[serde(default)]
+    pub(crate) mirrors: Vec<Mirror>,
+}
+
+/// Files whose URL starts with `prefix` are downloaded from the first of `urls`
+/// that serves the expected content, with `prefix` replaced by that URL
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Mirror {
+    pub(crate) prefix: String,
+    pub(crate) urls: Vec<String>,
+}
+
+impl HttpSettings {
+    /// The headers to send to `host`. Headers of more specific domains come
+    /// last, so that they replace those of their parent domains.
+    pub(crate) fn headers_for(&self, host: &str) -> Vec<(&str, &str)> {
+        let mut domains = self
+            .headers
+            .keys()
+            .filter(|domain| host_matches(host, domain))
+            .collect::<Vec<_>>();
+        domains.sort_by_key(|domain| domain.len());
+        domains
+            .into_iter()
+            .flat_map(|domain| &self.headers[domain])
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect()
+    }
+
+    /// The URLs to try for `url`, in order: those of the first mirror whose prefix
+    /// it starts with, or `url` itself
+    pub(crate) fn mirror_urls(&self, url: &str) -> Vec<String> {
+        self.mirrors
+            .iter()
+            .find_map(|mirror| {
+                let rest = url.strip_prefix(&mirror.prefix)?;
+                Some(
+                    mirror
+                        .urls
+                        .iter()
+                        .map(|base| format!("{base}{rest}"))
+                        .collect(),
+                )
+            })
+            .filter(|urls: &Vec<String>| !urls.is_empty())
+            .unwrap_or_else(|| vec![url.to_string()])
+    }
+
+    /// Whether requests to `host` are permitted by `allow_hosts` and `deny_hosts`
+    pub(crate) fn check_host(&self, host: &str) -> Result<(), String> {
+        if let Some(domain) = self
+            .deny_hosts
+            .iter()
+            .find(|domain| host_matches(host, domain))
+        {
+            return Err(format!(
+                "Refusing to contact {host}: {domain} is listed in deny_hosts"
+            ));
+        }
+        if !self.allow_hosts.is_empty()
+            && !self
+                .allow_hosts
+                .iter()
+                .any(|domain| host_matches(host, domain))
+        {
+            return Err(format!(
+                "Refusing to contact {host}: it is not listed in allow_hosts"
+            ));
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (domain, headers) in &self.headers {
+            for (name, value) in headers {
+                HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|_| format!("Invalid header name {name:?} for {domain}"))?;
+                HeaderValue::from_str(value)
+                    .map_err(|_| format!("Invalid value of header {name:?} for {domain}"))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether `host` is `domain` or one of its subdomains
+pub(crate) fn host_matches(host: &str, domain: &str) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+    host == domain || host.ends_with(&format!(".{domain}"))
+}
+
+/// `$TYPFONT_CONFIG`, or `typfont/config.toml` in the platform's config directory
+pub(crate) fn config_path() -> Option<PathBuf> {
+    let var = |name| env::var_os(name).filter(|value| !value.is_empty());
+    if let Some(path) = var(CONFIG_FILE_VAR) {
+        return Some(PathBuf::from(path));
+    }
+    let base = if cfg!(target_os = "windows") {
+        var("APPDATA").map(PathBuf::from)?
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from(var("HOME")?).join("Library/Application Support")
+    } else {
+        var("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| Some(PathBuf::from(var("HOME")?).join(".config")))?
+    };
+    Some(base.join("typfont").join("config.toml"))
+}
+
+/// Read the settings file, if there is one. A broken file is an error rather
+/// than being ignored, as requests would otherwise fail in confusing ways.
+pub(crate) fn init() -> Result<(), String> {
+    let config = match config_path() {
+        Some(path) if path.is_file() => {
+            let content =
+                fs::read_to_string(&path).map_err(|e| format!("Failed to read {path:?}: {e}"))?;
+            let config: UserConfig =
+                toml::from_str(&content).map_err(|e| format!("Failed to parse {path:?}: {e}"))?;
+            config.validate().map_err(|e| format!("{e} in {path:?}"))?;
+            config
+        }
+        _ => UserConfig::default(),
+    };
+    let _ = USER_CONFIG.set(config);
+    Ok(())
+}
+
+/// The settings read by [`init`], or the defaults if it wasn't called
+pub(crate) fn get() -> &'static UserConfig {
+    USER_CONFIG.get_or_init(UserConfig::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headers_of_parent_and_sub_domains() {
+        let config: UserConfig = toml::from_str(
+            r#"
+[http.headers."example-corp.com"]
+User-Agent = "corp"
+X-Api-Key = "parent"
+
+[http.headers."fonts.example-corp.com"]
+X-Api-Key = "child"
+"#,
+        )
+        .unwrap();
+        config.http.validate().unwrap();
+
+        assert_eq!(
+            config.http.headers_for("fonts.example-corp.com"),
+            [
+                ("User-Agent", "corp"),
+                ("X-Api-Key", "parent"),
+                ("X-Api-Key", "child")
+            ]
+        );
+        assert!(config.http.headers_for("notexample-corp.com").is_empty());
+        assert!(host_matches("Fonts.Example-Corp.com", "example-corp.com"));
+    }
+
+    #[test]
+    fn test_mirror_urls_replace_the_prefix() {
+        let config: UserConfig = toml::from_str(
+            r#"
+[[http.mirrors]]
+prefix = "https://raw.githubusercontent.com/"
+urls = ["https://fonts.example-corp.com/gh/", "https://raw.githubusercontent.com/"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config
+                .http
+                .mirror_urls("https://raw.githubusercontent.com/o/r/main/A.ttf"),
+            [
+                "https://fonts.example-corp.com/gh/o/r/main/A.ttf",
+                "https://raw.githubusercontent.com/o/r/main/A.ttf"
+            ]
+        );
+        assert_eq!(
+            config.http.mirror_urls("https://example.com/A.ttf"),
+            ["https://example.com/A.ttf"]
+        );
+    }
+
+    #[test]
+    fn test_local_mirror_of_repository() {
+        let config: UserConfig = toml::from_str(
+            r#"
+[library.local_mirrors]
+"GoodUser/font_lib" = "/mnt/nas/font_lib"
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.library.local_mirror("gooduser/font_lib"),
+            Some(Path::new("/mnt/nas/font_lib"))
+        );
+        assert_eq!(config.library.local_mirror("gooduser/other"), None);
+    }
+
+    #[test]
+    fn test_credential_profiles_of_repositories() {
+        let config: UserConfig = toml::from_str(
+            r#"
+[credentials.work]
+token_env = "TYPFONT_TEST_UNSET_TOKEN"
+
+[credentials.personal]
+token = "personal-token"
+
+[library.credentials]
+"example-corp/*" = "work"
+"example-corp/public-fonts" = "personal"
+"GoodUser/font_lib" = "personal"
+"#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+        assert_eq!(
+            config.github_token("gooduser/font_lib").unwrap().as_deref(),
+            Some("personal-token")
+        );
+        assert_eq!(
+            config
+                .github_token("example-corp/public-fonts")
+                .unwrap()
+                .as_deref(),
+            Some("personal-token")
+        );
+        let error = config.github_token("example-corp/brand").unwrap_err();
+        assert!(error.contains("TYPFONT_TEST_UNSET_TOKEN"), "{error}");
+        assert_eq!(config.github_token("other/fonts").unwrap(), None);
+
+        let undefined: UserConfig =
+            toml::from_str("[library.credentials]\n\"a/*\" = \"missing\"\n").unwrap();
+        assert!(undefined.validate().is_err());
+    }
+
+    #[test]
+    fn test_deny_hosts_take_precedence_over_allow_hosts() {
+        let settings = HttpSettings {
+            allow_hosts: vec!["example-corp.com".to_string()],
+            deny_hosts: vec!["legacy.example-corp.com".to_string()],
+            ..HttpSettings::default()
+        };
+        assert!(settings.check_host("github.example-corp.com").is_ok());
+        assert!(settings.check_host("raw.githubusercontent.com").is_err());
+        assert!(settings.check_host("cdn.legacy.example-corp.com").is_err());
+        assert!(HttpSettings::default().check_host("api.github.com").is_ok());
+    }
+}
@@ -1,4 +1,8 @@
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 
 pub fn get_system_font_directories() -> Vec<PathBuf> {
     let mut font_dirs = Vec::new();
@@ -15,13 +19,19 @@ pub fn get_system_font_directories() -> Vec<PathBuf> {
             PathBuf::from("/Library/Fonts"),
             PathBuf::from(std::env::var("HOME").unwrap_or_default()).join("Library/Fonts"),
         ]);
+        // Activated downloadable fonts
+        font_dirs.extend(macos_asset_font_dirs(Path::new("/System/Library/AssetsV2")));
     } else if cfg!(target_os = "linux") {
-        // Linux font directories
-        font_dirs.extend_from_slice(&[
-            PathBuf::from("/usr/share/fonts"),
-            PathBuf::from("/usr/local/share/fonts"),
-            PathBuf::from(std::env::var("HOME").unwrap_or_default()).join(".fonts"),
-        ]);
+        font_dirs.extend(linux_font_directories(|name| std::env::var_os(name)));
+        // Fonts shipped by snaps
+        if let Ok(snaps) = std::fs::read_dir("/snap") {
+            let mut snap_dirs = snaps
+                .filter_map(Result::ok)
+                .map(|snap| snap.path().join("current/usr/share/fonts"))
+                .collect::<Vec<_>>();
+            snap_dirs.sort();
+            font_dirs.extend(snap_dirs);
+        }
     }
 
     // Filter out directories that don't exist
@@ -29,3 +39,199 @@ pub fn get_system_font_directories() -> Vec<PathBuf> {
 
     font_dirs
 }
+
+/// The font asset catalogs of macOS below `root`, such as
+/// `com_apple_MobileAsset_Font7`, holding the downloadable system fonts
+pub fn macos_asset_font_dirs(root: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(root) else {
+        return Vec::new();
+    };
+    let mut dirs = entries
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with("com_apple_MobileAsset_Font")
+        })
+        .map(|entry| entry.path())
+        .collect::<Vec<_>>();
+    dirs.sort();
+    dirs
+}
+
+/// The Linux font directories: those of the XDG base directory specification,
+/// `~/.fonts`, and the fonts exported by flatpak, without duplicates. `env`
+/// looks up an environment variable.
+fn linux_font_directories(env: impl Fn(&str) -> Option<OsString>) -> Vec<PathBuf> {
+    // Unset or empty variables take their default values
+    let var = |name| env(name).filter(|value| !value.is_empty());
+    let home = PathBuf::from(var("HOME").unwrap_or_default());
+
+    let data_home = var("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home.join(".local/share"));
+    let data_dirs = var("XDG_DATA_DIRS").unwrap_or_else(|| "/usr/local/share:/usr/share".into());
+
+    let mut font_dirs = vec![data_home.join("fonts"), home.join(".fonts")];
+    font_dirs.extend(
+        std::env::split_paths(&data_dirs)
+            .filter(|dir| dir.is_absolute())
+            .map(|dir| dir.join("fonts")),
+    );
+    font_dirs.extend([
+        PathBuf::from("/usr/share/fonts"),
+        PathBuf::from("/usr/local/share/fonts"),
+        data_home.join("flatpak/exports/share/fonts"),
+        PathBuf::from("/var/lib/flatpak/exports/share/fonts"),
+    ]);
+
+    let mut seen = HashSet::new();
+    font_dirs.retain(|dir| seen.insert(dir.clone()));
+    font_dirs
+}
+
+/// Extensions of the font files considered when scanning directories
+pub const FONT_EXTENSIONS: [&str; 5] = ["ttf", "otf", "ttc", "otc", "woff2"];
+
+/// Whether `path` has one of the [`FONT_EXTENSIONS`], ignoring case
+pub fn has_font_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            FONT_EXTENSIONS
+                .iter()
+                .any(|font_extension| extension.eq_ignore_ascii_case(font_extension))
+        })
+}
+
+/// Whether `path` is a font collection (.ttc/.otc), which holds several faces
+pub fn is_font_collection(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            extension.eq_ignore_ascii_case("ttc") || extension.eq_ignore_ascii_case("otc")
+        })
+}
+
+/// Font formats Typst can't load, recognized so that they are reported
+/// instead of being skipped like any other file
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum LegacyFormat {
+    /// PostScript Type 1 outlines (.pfb/.pfa), convertible to OpenType
+    Type1,
+    /// Bitmap-only formats, e.g. the X11 .pcf fonts and Windows .fon files
+    Bitmap,
+}
+
+impl LegacyFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            LegacyFormat::Type1 => "Type 1",
+            LegacyFormat::Bitmap => "bitmap",
+        }
+    }
+}
+
+/// The legacy format of `path` judging by its extension, also recognizing
+/// compressed files such as `helvR12.pcf.gz`
+pub fn legacy_font_format(path: &Path) -> Option<LegacyFormat> {
+    let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+    let name = name.strip_suffix(".gz").unwrap_or(&name);
+    match Path::new(name).extension()?.to_str()? {
+        "pfb" | "pfa" | "t1" => Some(LegacyFormat::Type1),
+        "pcf" | "bdf" | "snf" | "fon" | "fnt" | "otb" => Some(LegacyFormat::Bitmap),
+        _ => None,
+    }
+}
+
+/// Leading bytes of TrueType, OpenType, collection and WOFF2 files
+const FONT_MAGIC_BYTES: [&[u8; 4]; 5] = [b"\x00\x01\x00\x00", b"OTTO", b"true", b"ttcf", b"wOF2"];
+
+/// Cheaply check whether `path` looks like a font file by its extension and
+/// magic bytes, so stray text and cache files are never handed to the parser
+pub fn is_font_file(path: &Path) -> bool {
+    if !has_font_extension(path) {
+        return false;
+    }
+
+    let mut magic = [0u8; 4];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok_and(|()| FONT_MAGIC_BYTES.contains(&&magic))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_linux_font_directories_follow_xdg() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| OsString::from(value))
+            }
+        };
+
+        let dirs = linux_font_directories(env(&[("HOME", "/home/u")]));
+        assert_eq!(
+            dirs,
+            [
+                "/home/u/.local/share/fonts",
+                "/home/u/.fonts",
+                "/usr/local/share/fonts",
+                "/usr/share/fonts",
+                "/home/u/.local/share/flatpak/exports/share/fonts",
+                "/var/lib/flatpak/exports/share/fonts",
+            ]
+            .map(PathBuf::from)
+        );
+
+        let dirs = linux_font_directories(env(&[
+            ("HOME", "/home/u"),
+            ("XDG_DATA_HOME", "/data"),
+            ("XDG_DATA_DIRS", "/opt/share:relative:/usr/share"),
+        ]));
+        assert_eq!(dirs[0], PathBuf::from("/data/fonts"));
+        assert_eq!(dirs[2], PathBuf::from("/opt/share/fonts"));
+        assert_eq!(dirs[3], PathBuf::from("/usr/share/fonts"));
+        assert!(dirs.contains(&PathBuf::from("/data/flatpak/exports/share/fonts")));
+    }
+
+    #[test]
+    fn test_is_font_file_checks_extension_and_magic() {
+        let test_dir = PathBuf::from(
+            std::env::var("CARGO_TARGET_DIR").unwrap_or_else(|_| "target".to_string()),
+        )
+        .join("is_font_file");
+        fs::create_dir_all(&test_dir).unwrap();
+
+        let font = test_dir.join("Font.OTF");
+        fs::write(&font, b"OTTO\x00\x0a").unwrap();
+        let text = test_dir.join("fonts.ttf");
+        fs::write(&text, b"not a font").unwrap();
+        let cache = test_dir.join("fonts.cache-7");
+        fs::write(&cache, b"OTTO").unwrap();
+
+        assert!(is_font_file(&font));
+        assert!(!is_font_file(&text));
+        assert!(!is_font_file(&cache));
+        assert!(!is_font_file(&test_dir.join("absent.ttf")));
+    }
+
+    #[test]
+    fn test_legacy_font_formats() {
+        let format = |name: &str| legacy_font_format(Path::new(name));
+        assert_eq!(
+            format("type1/Utopia-Regular.PFB"),
+            Some(LegacyFormat::Type1)
+        );
+        assert_eq!(format("X11/misc/6x13.pcf.gz"), Some(LegacyFormat::Bitmap));
+        assert_eq!(format("Fonts/serife.fon"), Some(LegacyFormat::Bitmap));
+        assert_eq!(format("NotoSansCJK.otc"), None);
+        assert_eq!(format("fonts.dir.gz"), None);
+    }
+}
@@ -1 +1,2 @@
 pub mod font_utils;
+pub mod path_utils;
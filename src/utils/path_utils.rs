@@ -0,0 +1,89 @@
+use std::path::{Component, Path, PathBuf};
+
+/// `path` with `/` as separator, as written to lockfiles and generated TOML so
+/// that they are identical on every OS
+pub fn portable_path(path: &Path) -> String {
+    let mut portable = String::new();
+    for component in path.components() {
+        if component == Component::RootDir {
+            portable.push('/');
+            continue;
+        }
+        if !portable.is_empty() && !portable.ends_with('/') {
+            portable.push('/');
+        }
+        portable.push_str(&component.as_os_str().to_string_lossy());
+    }
+    portable.replace('\\', "/")
+}
+
+/// Whether a path written to a lockfile or generated TOML is absolute on any OS,
+/// i.e. starts with `/`, `\` or a drive letter such as `C:`
+pub fn is_absolute_anywhere(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    path.starts_with(['/', '\\'])
+        || (bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':')
+}
+
+/// The path leading from the directory `base` to `path`, using `..` where
+/// needed. Both are made absolute first, resolving symbolic links where they
+/// exist. `None` if they have no common root, e.g. are on different drives.
+pub fn relative_path(path: &Path, base: &Path) -> Option<PathBuf> {
+    let resolve = |path: &Path| {
+        path.canonicalize()
+            .or_else(|_| std::path::absolute(path))
+            .ok()
+    };
+    let (path, base) = (resolve(path)?, resolve(base)?);
+
+    let mut path_components = path.components().peekable();
+    let mut base_components = base.components().peekable();
+    if path_components.peek() != base_components.peek() {
+        return None;
+    }
+    while let (Some(a), Some(b)) = (path_components.peek(), base_components.peek()) {
+        if a != b {
+            break;
+        }
+        path_components.next();
+        base_components.next();
+    }
+
+    let mut relative = PathBuf::new();
+    for component in base_components {
+        if matches!(component, Component::Normal(_)) {
+            relative.push("..");
+        }
+    }
+    relative.extend(path_components);
+    Some(relative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_project_relative_paths() {
+        assert_eq!(
+            relative_path(Path::new("/srv/fonts/A.ttf"), Path::new("/srv/thesis")),
+            Some(PathBuf::from("../fonts/A.ttf"))
+        );
+        assert_eq!(
+            relative_path(
+                Path::new("/srv/thesis/fonts/A.ttf"),
+                Path::new("/srv/thesis")
+            ),
+            Some(PathBuf::from("fonts/A.ttf"))
+        );
+        assert_eq!(portable_path(Path::new("../fonts/A.ttf")), "../fonts/A.ttf");
+        assert_eq!(
+            portable_path(Path::new("/srv/thesis/font_config.toml")),
+            "/srv/thesis/font_config.toml"
+        );
+        assert!(is_absolute_anywhere("/usr/share/fonts/A.ttf"));
+        assert!(is_absolute_anywhere("C:\\Windows\\Fonts\\A.ttf"));
+        assert!(!is_absolute_anywhere("../fonts/A.ttf"));
+        assert!(!is_absolute_anywhere("owner/repo/A.ttf"));
+    }
+}
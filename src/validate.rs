@@ -0,0 +1,307 @@
+//! `check-lib --validate`: verify that a published font_library.toml matches
+//! the font files of its library, so that broken indexes are caught in the
+//! CI of the library repository instead of by its consumers.
+
+use crate::color::Colorize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::font_manager::{format_discovered_variant, parse_font_library_entries};
+use crate::github::{self, DEFAULT_INDEX_PATH, GitHubSource};
+use crate::http;
+use crate::remote_font;
+use crate::table::format_size;
+use crate::utils::font_utils::{has_font_extension, is_font_collection};
+use crate::{DiscoveredFont, create_font_entries, discover_faces, strip_font_entry_root_paths};
+
+/// What is wrong with an index entry or a library file
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Problem {
+    /// The entry points to a file that doesn't exist
+    MissingFile,
+    /// The file exists but contains no face Typst can read
+    Unparsable,
+    /// The file has no face with the entry's index
+    MissingFace,
+    /// The same file and face are listed more than once
+    DuplicateEntry,
+    /// The entry describes a different font than the face it points to
+    Mismatch { declared: String, actual: String },
+    /// The face exists in the library but has no entry
+    Unindexed { actual: String },
+}
+
+/// A problem found at a file, or at one face of a collection
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Finding {
+    pub(crate) path: PathBuf,
+    pub(crate) index: u32,
+    pub(crate) problem: Problem,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if is_font_collection(&self.path) {
+            write!(f, " [face {}]", self.index)?;
+        }
+        match &self.problem {
+            Problem::MissingFile => write!(f, ": file does not exist"),
+            Problem::Unparsable => write!(f, ": file contains no readable font"),
+            Problem::MissingFace => write!(f, ": file has no such face"),
+            Problem::DuplicateEntry => write!(f, ": listed more than once"),
+            Problem::Mismatch { declared, actual } => {
+                write!(f, ": declared as {declared}, but the face is {actual}")
+            }
+            Problem::Unindexed { actual } => write!(f, ": {actual} is not listed in the index"),
+        }
+    }
+}
+
+/// The outcome of validating one index
+pub(crate) struct ValidationReport {
+    pub(crate) index: String,
+    pub(crate) entries: usize,
+    pub(crate) files: usize,
+    pub(crate) findings: Vec<Finding>,
+}
+
+impl ValidationReport {
+    pub(crate) fn print(&self) {
+        println!(
+            "\n- Validating {} ({} entries, {} font files):",
+            self.index, self.entries, self.files
+        );
+        if self.findings.is_empty() {
+            println!("  {} The index matches the library files", "✓".green());
+        }
+        for finding in &self.findings {
+            println!("  {} {finding}", "✗".red());
+        }
+    }
+}
+
+/// Validate the font_library.toml at the root of the local library `dir`
+pub(crate) fn validate_local_library(dir: &Path) -> Result<ValidationReport, String> {
+    let index_path = dir.join(DEFAULT_INDEX_PATH);
+    let content = fs::read_to_string(&index_path)
+        .map_err(|e| format!("Failed to read {:?}: {e}", index_path))?;
+    let declared = parse_font_library_entries(&content)?;
+
+    let present_files = WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file() && has_font_extension(entry.path()))
+        .filter_map(|entry| Some(entry.path().strip_prefix(dir).ok()?.to_path_buf()))
+        .collect::<BTreeSet<_>>();
+
+    let mut actual = create_font_entries(dir);
+    strip_font_entry_root_paths(&mut actual, dir);
+
+    Ok(ValidationReport {
+        index: format!("{index_path:?}"),
+        entries: declared.len(),
+        files: present_files.len(),
+        findings: validate_entries(&declared, &actual, &present_files),
+    })
+}
+
+/// Validate the index of a GitHub library. Only the headers of TrueType/OpenType
+/// fonts are downloaded, see [`remote_font`]; other font files are downloaded whole.
+pub(crate) fn validate_github_library(
+    source: &Path,
+    prefix_len: usize,
+) -> Result<ValidationReport, String> {
+    let source = GitHubSource::parse(source)?;
+    let client = http::client()?;
+
+    let index_url = source.index_url();
+    let content = String::from_utf8(remote_font::fetch_whole(&client, &index_url)?)
+        .map_err(|e| format!("{} is not valid UTF-8: {e}", source.index_path))?;
+    let declared = parse_font_library_entries(&content)?;
+
+    let present_files = github::fetch_file_paths(&client, source.repo, source.reference)?
+        .into_iter()
+        .map(PathBuf::from)
+        .filter(|path| has_font_extension(path))
+        .collect::<BTreeSet<_>>();
+
+    let mut actual = Vec::new();
+    let (mut downloaded, mut total) = (0, 0);
+    for path in &present_files {
+        let url = source.file_url(&path.to_string_lossy());
+        let data = if remote_font::is_partially_readable(path) {
+            let font = remote_font::fetch_partial_font(&client, &url, prefix_len)?;
+            downloaded += font.downloaded;
+            font.data
+        } else {
+            let data = remote_font::fetch_whole(&client, &url)?;
+            downloaded += data.len();
+            data
+        };
+        total += data.len();
+        actual.extend(discover_faces(path, &data));
+    }
+    println!(
+        "  Read {} font files of {} by downloading {} of {}",
+        present_files.len(),
+        source,
+        format_size(downloaded as u64),
+        format_size(total as u64)
+    );
+
+    Ok(ValidationReport {
+        index: format!("{source}::{}", source.index_path),
+        entries: declared.len(),
+        files: present_files.len(),
+        findings: validate_entries(&declared, &actual, &present_files),
+    })
+}
+
+fn describe(entry: &DiscoveredFont) -> String {
+    format!(
+        "{} {}",
+        entry.font.family_name,
+        format_discovered_variant(entry)
+    )
+}
+
+/// Compare the `declared` index entries with the `actual` faces parsed from the
+/// `present_files` of the library. All paths are relative to the library root.
+pub(crate) fn validate_entries(
+    declared: &[DiscoveredFont],
+    actual: &[DiscoveredFont],
+    present_files: &BTreeSet<PathBuf>,
+) -> Vec<Finding> {
+    let actual_faces = actual
+        .iter()
+        .map(|entry| ((entry.path.as_path(), entry.index), entry))
+        .collect::<BTreeMap<_, _>>();
+    let parsed_files = actual
+        .iter()
+        .map(|entry| entry.path.as_path())
+        .collect::<BTreeSet<_>>();
+
+    let mut findings = Vec::new();
+    let finding = |path: &Path, index, problem| Finding {
+        path: path.to_path_buf(),
+        index,
+        problem,
+    };
+
+    let mut listed = BTreeSet::new();
+    for entry in declared {
+        let path = entry.path.as_path();
+        let problem = if !listed.insert((path, entry.index)) {
+            Some(Problem::DuplicateEntry)
+        } else if !present_files.contains(path) {
+            Some(Problem::MissingFile)
+        } else if !parsed_files.contains(path) {
+            // Reported once per file below
+            None
+        } else {
+            match actual_faces.get(&(path, entry.index)) {
+                None => Some(Problem::MissingFace),
+                Some(face) if describe(face) != describe(entry) => Some(Problem::Mismatch {
+                    declared: describe(entry),
+                    actual: describe(face),
+                }),
+                Some(_) => None,
+            }
+        };
+        if let Some(problem) = problem {
+            findings.push(finding(path, entry.index, problem));
+        }
+    }
+
+    for path in present_files {
+        if !parsed_files.contains(path.as_path()) {
+            findings.push(finding(path, 0, Problem::Unparsable));
+        }
+    }
+
+    for ((path, index), face) in &actual_faces {
+        if !listed.contains(&(*path, *index)) {
+            findings.push(finding(
+                path,
+                *index,
+                Problem::Unindexed {
+                    actual: describe(face),
+                },
+            ));
+        }
+    }
+
+    findings.sort_by(|a, b| (&a.path, a.index).cmp(&(&b.path, b.index)));
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_font_config::TypstFont;
+    use crate::process_font::FontMetadata;
+    use typst::text::{FontStretch, FontStyle, FontWeight};
+
+    fn face(family: &str, weight: u16, path: &str, index: u32) -> DiscoveredFont {
+        DiscoveredFont {
+            font: TypstFont {
+                family_name: family.to_string(),
+                style: FontStyle::Normal,
+                weight: FontWeight::from_number(weight),
+                stretch: FontStretch::NORMAL,
+            },
+            path: PathBuf::from(path),
+            index,
+            axes: Vec::new(),
+            metadata: FontMetadata::default(),
+        }
+    }
+
+    #[test]
+    fn test_validate_entries_reports_each_problem() {
+        let declared = [
+            face("Lato", 400, "Lato/Lato-Regular.ttf", 0),
+            face("Lato", 400, "Lato/Lato-Bold.ttf", 0),
+            face("Lato", 400, "Lato/Lato-Regular.ttf", 0),
+            face("Gone", 400, "Gone.ttf", 0),
+            face("CJK", 400, "CJK.ttc", 3),
+            face("Broken", 400, "Broken.otf", 0),
+        ];
+        let actual = [
+            face("Lato", 400, "Lato/Lato-Regular.ttf", 0),
+            face("Lato", 700, "Lato/Lato-Bold.ttf", 0),
+            face("CJK", 400, "CJK.ttc", 0),
+        ];
+        let present_files = [
+            "Lato/Lato-Regular.ttf",
+            "Lato/Lato-Bold.ttf",
+            "CJK.ttc",
+            "Broken.otf",
+        ]
+        .into_iter()
+        .map(PathBuf::from)
+        .collect();
+
+        let findings = validate_entries(&declared, &actual, &present_files)
+            .into_iter()
+            .map(|finding| finding.to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            findings,
+            [
+                "Broken.otf: file contains no readable font",
+                "CJK.ttc [face 0]: CJK (style: Normal, weight: 400, stretch: 1000) is not listed in the index",
+                "CJK.ttc [face 3]: file has no such face",
+                "Gone.ttf: file does not exist",
+                "Lato/Lato-Bold.ttf: declared as Lato (style: Normal, weight: 400, stretch: 1000), \
+                 but the face is Lato (style: Normal, weight: 700, stretch: 1000)",
+                "Lato/Lato-Regular.ttf: listed more than once",
+            ]
+        );
+    }
+}
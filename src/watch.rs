@@ -0,0 +1,213 @@
+//! File system notifications for `check --watch`. Local library directories
+//! are scanned once into a [`LibraryIndex`], which is then updated file by file
+//! from the events the operating system reports (inotify, FSEvents or
+//! ReadDirectoryChangesW), so a check after a change costs a scan of the
+//! project font directory only, however large the libraries are.
+
+#[cfg(feature = "watch")]
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "watch")]
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+use crate::font_manager::LibraryDirs;
+use crate::utils::font_utils::is_font_file;
+use crate::{DiscoveredFont, create_font_entries, create_font_entries_from_dirs};
+
+/// How long to wait for more events after one arrived, so that copying a
+/// directory of fonts triggers a single check
+#[cfg(feature = "watch")]
+const SETTLE_DELAY: Duration = Duration::from_millis(300);
+
+/// The faces of local library directories by file
+#[derive(Debug, Default)]
+pub(crate) struct LibraryIndex {
+    dirs: Vec<PathBuf>,
+    files: BTreeMap<PathBuf, Vec<DiscoveredFont>>,
+}
+
+impl LibraryIndex {
+    pub(crate) fn scan(dirs: &[PathBuf]) -> Result<Self, crate::error::Error> {
+        let mut index = Self {
+            dirs: dirs.to_vec(),
+            files: BTreeMap::new(),
+        };
+        let entries = create_font_entries_from_dirs(&LibraryDirs::Local(dirs.to_vec()))?;
+        index.insert(entries);
+        Ok(index)
+    }
+
+    /// The faces of all files, in the order of their paths
+    pub(crate) fn entries(&self) -> Vec<DiscoveredFont> {
+        self.files.values().flatten().cloned().collect()
+    }
+
+    /// The path of `path`, as reported by the watcher, below the library
+    /// directory it is in, if any. Watchers may report absolute or canonical
+    /// paths for directories given as relative ones.
+    pub(crate) fn library_path(&self, path: &Path) -> Option<PathBuf> {
+        self.dirs.iter().find_map(|dir| {
+            if path.starts_with(dir) {
+                return Some(path.to_path_buf());
+            }
+            [std::path::absolute(dir), dir.canonicalize()]
+                .into_iter()
+                .flatten()
+                .find_map(|root| Some(dir.join(path.strip_prefix(root).ok()?)))
+        })
+    }
+
+    /// Bring the index up to date with a change of `path`: a file or
+    /// directory that was created, modified, moved or removed
+    pub(crate) fn refresh(&mut self, path: &Path) {
+        let below = self
+            .files
+            .range(path.to_path_buf()..)
+            .take_while(|(file, _)| file.starts_with(path))
+            .map(|(file, _)| file.clone())
+            .collect::<Vec<_>>();
+        for file in below {
+            self.files.remove(&file);
+        }
+        if path.is_dir() {
+            self.insert(create_font_entries(path));
+        } else if is_font_file(path) {
+            let mut faces = Vec::new();
+            crate::font_entries_update(&mut faces, path);
+            self.insert(faces);
+        }
+    }
+
+    fn insert(&mut self, entries: Vec<DiscoveredFont>) {
+        for entry in entries {
+            self.files
+                .entry(entry.path.clone())
+                .or_default()
+                .push(entry);
+        }
+    }
+}
+
+/// Changed paths below the watched directories, see [`FsWatcher::wait`]
+#[derive(Debug, Default, Eq, PartialEq)]
+pub(crate) struct Changes {
+    pub(crate) paths: Vec<PathBuf>,
+    /// The system dropped events, e.g. after an inotify queue overflow, so
+    /// the changes are unknown and everything has to be scanned again
+    pub(crate) rescan: bool,
+}
+
+/// Watches directories through the notification API of the operating system
+#[cfg(feature = "watch")]
+pub(crate) struct FsWatcher {
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+}
+
+#[cfg(feature = "watch")]
+impl FsWatcher {
+    /// Watch the `recursive` directories with everything below them, and the
+    /// direct entries of the `flat` ones. Paths that don't exist are skipped.
+    pub(crate) fn new(recursive: &[PathBuf], flat: &[PathBuf]) -> Result<Self, String> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)
+            .map_err(|e| format!("Failed to start watching files: {e}"))?;
+        let modes = recursive
+            .iter()
+            .map(|path| (path, RecursiveMode::Recursive))
+            .chain(flat.iter().map(|path| (path, RecursiveMode::NonRecursive)));
+        for (path, mode) in modes {
+            if path.exists() {
+                watcher
+                    .watch(path, mode)
+                    .map_err(|e| format!("Failed to watch {path:?}: {e}"))?;
+            }
+        }
+        Ok(Self {
+            _watcher: watcher,
+            events,
+        })
+    }
+
+    /// Wait up to `timeout` for a change, then for the changes following it
+    /// within [`SETTLE_DELAY`]. No changes are returned on timeout.
+    pub(crate) fn wait(&self, timeout: Duration) -> Result<Changes, String> {
+        let mut changes = Changes::default();
+        let mut wait = timeout;
+        loop {
+            match self.events.recv_timeout(wait) {
+                Ok(Ok(event)) => {
+                    changes.rescan |= event.need_rescan();
+                    changes.paths.extend(event.paths);
+                }
+                Ok(Err(_)) => changes.rescan = true,
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err("The file watcher stopped".to_string());
+                }
+            }
+            wait = SETTLE_DELAY;
+        }
+        changes.paths.sort();
+        changes.paths.dedup();
+        Ok(changes)
+    }
+}
+
+/// Stands in for the watcher in builds without the `watch` feature, which
+/// check periodically instead
+#[cfg(not(feature = "watch"))]
+pub(crate) enum FsWatcher {}
+
+#[cfg(not(feature = "watch"))]
+impl FsWatcher {
+    pub(crate) fn new(_recursive: &[PathBuf], _flat: &[PathBuf]) -> Result<Self, String> {
+        Err("typfont was built without file system notifications".to_string())
+    }
+
+    pub(crate) fn wait(&self, _timeout: Duration) -> Result<Changes, String> {
+        match *self {}
+    }
+}
+
+#[cfg(all(test, feature = "test-fixtures", feature = "watch"))]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_index_follows_file_changes() {
+        let dir = std::env::temp_dir().join(format!("typfont-watch-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let library = dir.join("lib");
+        fs::create_dir_all(&library).unwrap();
+        crate::fixtures::write_fonts(&library).unwrap();
+        let mut index = LibraryIndex::scan(std::slice::from_ref(&library)).unwrap();
+        assert_eq!(index.entries().len(), 2);
+
+        let watcher = FsWatcher::new(std::slice::from_ref(&library), &[]).unwrap();
+        let moved = dir.join("lib/sub");
+        fs::create_dir_all(&moved).unwrap();
+        let (name, _) = crate::fixtures::FONTS[1];
+        fs::rename(library.join(name), moved.join(name)).unwrap();
+        let (removed, _) = crate::fixtures::FONTS[0];
+        fs::remove_file(library.join(removed)).unwrap();
+
+        let changes = watcher.wait(Duration::from_secs(5)).unwrap();
+        assert!(!changes.paths.is_empty());
+        for path in changes.paths {
+            if let Some(path) = index.library_path(&path) {
+                index.refresh(&path);
+            }
+        }
+        let paths = index
+            .entries()
+            .into_iter()
+            .map(|entry| entry.path)
+            .collect::<Vec<_>>();
+        assert_eq!(paths, [moved.join(name)]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,450 @@
+//! Workspaces of sibling projects sharing one font store. A `font_workspace.toml`
+//! in a parent directory of the projects declares the store:
+//!
+//! ```toml
+//! store = ".fonts-store"   # relative to the workspace file, the default
+//! link = "hardlink"        # or "symlink"
+//! shared = true             # the store is used by several user accounts
+//! ```
+//!
+//! `update` moves every installed file into the store, under the sha256 of its
+//! content, and links it back into the project's font directory, so a font used
+//! by many projects is stored once. `store gc` removes the stored files that no
+//! project lockfile in the workspace refers to any more, and `store verify`
+//! checks that the stored files still have their content hash.
+
+use crate::color::Colorize;
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::file_lock;
+use crate::lockfile::{FontLock, HashAlgo, hash_files};
+use crate::shared;
+use crate::table::format_size;
+use crate::utils::path_utils::relative_path;
+
+pub(crate) const WORKSPACE_FILE: &str = "font_workspace.toml";
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum LinkKind {
+    #[default]
+    Hardlink,
+    Symlink,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WorkspaceConfig {
+    #[serde(default = "default_store")]
+    store: PathBuf,
+    #[serde(default)]
+    link: LinkKind,
+    #[serde(default)]
+    shared: bool,
+}
+
+fn default_store() -> PathBuf {
+    PathBuf::from(".fonts-store")
+}
+
+#[derive(Debug)]
+pub(crate) struct Workspace {
+    /// The directory of `font_workspace.toml`
+    pub(crate) root: PathBuf,
+    pub(crate) store: PathBuf,
+    pub(crate) link: LinkKind,
+    /// Whether the store is written by several users, see [`crate::shared`]
+    pub(crate) shared: bool,
+}
+
+impl Workspace {
+    /// The workspace of the project at `config_file`: the nearest
+    /// `font_workspace.toml` in the project directory or above
+    pub(crate) fn find(config_file: &Path) -> Result<Option<Self>, String> {
+        let config_file = std::path::absolute(config_file)
+            .map_err(|e| format!("Failed to resolve {config_file:?}: {e}"))?;
+        match config_file.parent() {
+            Some(dir) => Self::find_from(dir),
+            None => Ok(None),
+        }
+    }
+
+    /// The nearest workspace declared in `dir` or above
+    pub(crate) fn find_from(dir: &Path) -> Result<Option<Self>, String> {
+        let dir =
+            std::path::absolute(dir).map_err(|e| format!("Failed to resolve {dir:?}: {e}"))?;
+        for dir in dir.ancestors() {
+            if dir.join(WORKSPACE_FILE).is_file() {
+                return Self::load(dir).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// The workspace declared by the `font_workspace.toml` in `root`
+    pub(crate) fn load(root: &Path) -> Result<Self, String> {
+        let path = root.join(WORKSPACE_FILE);
+        let content =
+            fs::read_to_string(&path).map_err(|e| format!("Failed to read {path:?}: {e}"))?;
+        let config: WorkspaceConfig =
+            toml::from_str(&content).map_err(|e| format!("Failed to parse {path:?}: {e}"))?;
+        Ok(Workspace {
+            root: root.to_path_buf(),
+            store: root.join(config.store),
+            link: config.link,
+            shared: config.shared,
+        })
+    }
+
+    fn lock_store(&self) -> Result<file_lock::DirLock, String> {
+        if self.shared {
+            file_lock::lock_shared_dir(&self.store)
+        } else {
+            file_lock::lock_dir(&self.store)
+        }
+    }
+
+    /// Where the store keeps the file named `file_name` with content hash `sha256`
+    pub(crate) fn stored_path(&self, sha256: &str, file_name: &str) -> PathBuf {
+        self.store.join(sha256).join(file_name)
+    }
+
+    /// Move the installed file at `path`, whose content hash is `sha256`, into
+    /// the store and replace it with a link. If the store already has the file,
+    /// the installed copy is dropped; returns whether that was the case.
+    pub(crate) fn store_file(&self, path: &Path, sha256: &str) -> Result<bool, String> {
+        let file_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| format!("Invalid font file name {path:?}"))?;
+        let stored = self.stored_path(sha256, file_name);
+        // Projects of the workspace may be updated at the same time
+        let _lock = self.lock_store()?;
+        let already_stored = stored.is_file();
+        if already_stored {
+            fs::remove_file(path).map_err(|e| format!("Failed to remove {path:?}: {e}"))?;
+        } else {
+            let dir = stored.parent().unwrap();
+            if self.shared {
+                shared::create_dir_all(dir)?;
+            } else {
+                fs::create_dir_all(dir).map_err(|e| format!("Failed to create {dir:?}: {e}"))?;
+            }
+            // Renaming fails across file systems, where the copy is needed anyway
+            if fs::rename(path, &stored).is_err() {
+                fs::copy(path, &stored)
+                    .and_then(|_| fs::remove_file(path))
+                    .map_err(|e| format!("Failed to move {path:?} to {stored:?}: {e}"))?;
+            }
+            if self.shared {
+                shared::share_file(&stored)?;
+            }
+        }
+        self.link_file(&stored, path)?;
+        Ok(already_stored)
+    }
+
+    /// The hashes of the installed files recorded by the lockfiles of the
+    /// projects below the workspace root. Any `*.lock` next to a `*.toml` of the
+    /// same name counts as a project lockfile, and one that can't be read is an
+    /// error, so that its files are never taken for unused.
+    pub(crate) fn referenced_hashes(&self) -> Result<BTreeSet<String>, String> {
+        let locks = self.project_locks()?;
+        Ok(locks
+            .into_iter()
+            .flat_map(|lock| lock.files)
+            .map(|file| file.sha256)
+            .collect())
+    }
+
+    /// The lockfiles of the projects below the workspace root, see
+    /// [`Self::referenced_hashes`]
+    fn project_locks(&self) -> Result<Vec<FontLock>, String> {
+        let mut locks = Vec::new();
+        let walker = WalkDir::new(&self.root)
+            .into_iter()
+            .filter_entry(|entry| entry.path() != self.store && entry.file_name() != ".git");
+        for entry in walker.filter_map(Result::ok) {
+            let path = entry.path();
+            if !entry.file_type().is_file()
+                || path.extension().is_none_or(|extension| extension != "lock")
+                || !path.with_extension("toml").is_file()
+            {
+                continue;
+            }
+            locks.extend(FontLock::load(path)?);
+        }
+        Ok(locks)
+    }
+
+    /// The store entries, one directory per content hash, with their sizes
+    fn stored_entries(&self) -> Vec<(String, PathBuf, u64)> {
+        let Ok(dirs) = fs::read_dir(&self.store) else {
+            return Vec::new();
+        };
+        let mut entries = dirs
+            .filter_map(Result::ok)
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| {
+                let size = WalkDir::new(entry.path())
+                    .into_iter()
+                    .filter_map(Result::ok)
+                    .filter_map(|file| file.metadata().ok())
+                    .filter(|metadata| metadata.is_file())
+                    .map(|metadata| metadata.len())
+                    .sum();
+                let hash = entry.file_name().to_string_lossy().to_string();
+                (hash, entry.path(), size)
+            })
+            .collect::<Vec<_>>();
+        entries.sort();
+        entries
+    }
+
+    fn link_file(&self, stored: &Path, path: &Path) -> Result<(), String> {
+        match self.link {
+            LinkKind::Hardlink => fs::hard_link(stored, path).map_err(|e| {
+                format!(
+                    "Failed to hardlink {path:?} to {stored:?}: {e} \
+                     (set `link = \"symlink\"` in {WORKSPACE_FILE} if the store is \
+                     on another file system)"
+                )
+            }),
+            LinkKind::Symlink => {
+                // Relative links keep working when the workspace is moved
+                let target = path
+                    .parent()
+                    .and_then(|dir| relative_path(stored, dir))
+                    .unwrap_or_else(|| stored.to_path_buf());
+                symlink_file(&target, path)
+                    .map_err(|e| format!("Failed to symlink {path:?} to {stored:?}: {e}"))
+            }
+        }
+    }
+}
+
+/// `store gc`: remove the store entries of the workspace containing `dir` that
+/// no project lockfile refers to
+pub(crate) fn collect_garbage(dir: &Path, dry_run: bool) -> Result<(), String> {
+    let workspace = Workspace::find_from(dir)?
+        .ok_or_else(|| format!("No {WORKSPACE_FILE} found in {dir:?} or above"))?;
+    let _lock = (!dry_run).then(|| workspace.lock_store()).transpose()?;
+    let referenced = workspace.referenced_hashes()?;
+    let entries = workspace.stored_entries();
+
+    let mut removed = 0;
+    let mut freed = 0;
+    for (hash, path, size) in &entries {
+        if referenced.contains(hash) {
+            continue;
+        }
+        if dry_run {
+            println!("  Would remove {}", path.display());
+        } else {
+            fs::remove_dir_all(path).map_err(|e| format!("Failed to remove {path:?}: {e}"))?;
+        }
+        removed += 1;
+        freed += size;
+    }
+
+    let total = entries.iter().map(|(_, _, size)| size).sum::<u64>();
+    println!(
+        "{} {} of {} stored files ({}); the store {} {}",
+        if dry_run { "Would remove" } else { "Removed" },
+        removed,
+        entries.len(),
+        format_size(freed),
+        if dry_run { "would take" } else { "takes" },
+        format_size(total - freed)
+    );
+    Ok(())
+}
+
+/// `store verify`: check that every file in the store of the workspace containing
+/// `dir` still has the content hash it is stored under, hashing on all cores.
+/// With BLAKE3, files are checked against the BLAKE3 the project lockfiles record
+/// for them, and against their SHA-256 where none does.
+pub(crate) fn verify_store(dir: &Path, algo: HashAlgo) -> Result<(), String> {
+    let workspace = Workspace::find_from(dir)?
+        .ok_or_else(|| format!("No {WORKSPACE_FILE} found in {dir:?} or above"))?;
+    let _lock = workspace.lock_store()?;
+    let blake3 = workspace
+        .project_locks()?
+        .into_iter()
+        .flat_map(|lock| lock.files)
+        .filter_map(|file| Some((file.sha256, file.blake3?)))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut expected = Vec::new();
+    let mut jobs = Vec::new();
+    for (sha256, dir, _) in workspace.stored_entries() {
+        let files = WalkDir::new(&dir)
+            .min_depth(1)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file());
+        for entry in files {
+            let (algo, digest) = match (algo, blake3.get(&sha256)) {
+                (HashAlgo::Blake3, Some(blake3)) => (HashAlgo::Blake3, blake3.clone()),
+                _ => (HashAlgo::Sha256, sha256.clone()),
+            };
+            expected.push(digest);
+            jobs.push((entry.into_path(), algo));
+        }
+    }
+
+    let mut corrupt = 0;
+    for ((digest, (path, algo)), hash) in expected.iter().zip(&jobs).zip(hash_files(&jobs)) {
+        let problem = match hash {
+            Ok(hash) if hash == *digest => continue,
+            Ok(hash) => format!(
+                "{} {} instead of {}",
+                algo.name(),
+                &hash[..12],
+                &digest[..12]
+            ),
+            Err(e) => format!("unreadable: {e}"),
+        };
+        println!("  {} {}: {problem}", "✗".red(), path.display());
+        corrupt += 1;
+    }
+    if corrupt > 0 {
+        return Err(format!(
+            "{corrupt} of {} stored files don't match their hash; run `typfont repair` in \
+             the projects using them",
+            jobs.len()
+        ));
+    }
+    println!(
+        "All {} stored files match their hash ({})",
+        jobs.len(),
+        algo.name()
+    );
+    Ok(())
+}
+
+#[cfg(unix)]
+fn symlink_file(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink_file(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(target, link)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_workspace(name: &str, config: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("typfont-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for project in ["a", "b"] {
+            fs::create_dir_all(root.join(project).join("fonts")).unwrap();
+            fs::write(root.join(project).join("font_config.toml"), "").unwrap();
+        }
+        fs::write(root.join(WORKSPACE_FILE), config).unwrap();
+        root
+    }
+
+    #[test]
+    fn test_workspace_is_found_above_the_project() {
+        let root = temp_workspace("workspace-find", "link = \"symlink\"\n");
+        let workspace = Workspace::find(&root.join("a").join("font_config.toml"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(workspace.store, root.join(".fonts-store"));
+        assert_eq!(workspace.link, LinkKind::Symlink);
+
+        fs::write(root.join(WORKSPACE_FILE), "stor = \"x\"\n").unwrap();
+        assert!(Workspace::find(&root.join("a").join("font_config.toml")).is_err());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_projects_share_stored_files() {
+        for (name, config) in [("hardlink", ""), ("symlink", "link = \"symlink\"\n")] {
+            let root = temp_workspace(&format!("workspace-{name}"), config);
+            let workspace = Workspace::load(&root).unwrap();
+            let files = ["a", "b"].map(|project| root.join(project).join("fonts/Font.ttf"));
+            for file in &files {
+                fs::write(file, b"font data").unwrap();
+            }
+
+            assert!(!workspace.store_file(&files[0], "abc").unwrap());
+            assert!(workspace.store_file(&files[1], "abc").unwrap());
+            let stored = workspace.stored_path("abc", "Font.ttf");
+            assert_eq!(fs::read(&stored).unwrap(), b"font data");
+            for file in &files {
+                assert_eq!(fs::read(file).unwrap(), b"font data");
+                let is_symlink = fs::symlink_metadata(file).unwrap().is_symlink();
+                assert_eq!(is_symlink, name == "symlink");
+            }
+            fs::remove_dir_all(&root).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_gc_keeps_referenced_files() {
+        let root = temp_workspace("workspace-gc", "");
+        let workspace = Workspace::load(&root).unwrap();
+        for hash in ["used", "unused"] {
+            let path = root.join("a/fonts/Font.ttf");
+            fs::write(&path, hash).unwrap();
+            workspace.store_file(&path, hash).unwrap();
+            fs::remove_file(&path).unwrap();
+        }
+        fs::write(
+            root.join("a/font_config.lock"),
+            "[[files]]\nfile = \"Font.ttf\"\nsource = \"lib/Font.ttf\"\nsha256 = \"used\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            workspace.referenced_hashes().unwrap(),
+            BTreeSet::from(["used".to_string()])
+        );
+        collect_garbage(&root.join("b"), true).unwrap();
+        assert!(workspace.stored_path("unused", "Font.ttf").exists());
+        collect_garbage(&root.join("b"), false).unwrap();
+        assert!(workspace.stored_path("used", "Font.ttf").exists());
+        assert!(!workspace.stored_path("unused", "Font.ttf").exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_verify_store_finds_corrupt_files() {
+        let root = temp_workspace("workspace-verify", "");
+        let workspace = Workspace::load(&root).unwrap();
+        let sha256 = HashAlgo::Sha256.hex(b"font");
+        let path = root.join("a/fonts/Font.ttf");
+        fs::write(&path, b"font").unwrap();
+        workspace.store_file(&path, &sha256).unwrap();
+        fs::write(
+            root.join("a/font_config.lock"),
+            format!(
+                "[[files]]\nfile = \"Font.ttf\"\nsource = \"lib/Font.ttf\"\n\
+                 sha256 = \"{sha256}\"\nblake3 = \"{}\"\n",
+                HashAlgo::Blake3.hex(b"font")
+            ),
+        )
+        .unwrap();
+
+        for algo in [HashAlgo::Sha256, HashAlgo::Blake3] {
+            verify_store(&root, algo).unwrap();
+        }
+        fs::remove_file(&path).unwrap();
+        fs::write(workspace.stored_path(&sha256, "Font.ttf"), b"rotten").unwrap();
+        for algo in [HashAlgo::Sha256, HashAlgo::Blake3] {
+            let error = verify_store(&root, algo).unwrap_err();
+            assert!(error.starts_with("1 of 1 stored files"), "{error}");
+        }
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
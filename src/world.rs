@@ -0,0 +1,216 @@
+//! Fonts of a project for tools that embed the Typst compiler: a [`FontBook`]
+//! and lazily loaded [`FontSlot`]s, shaped like those of `typst-kit`, holding
+//! exactly the installed fonts that `font_config.toml` requires. A custom
+//! `World` can serve `book()` and `font()` straight from [`ProjectFonts`].
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use typst::foundations::Bytes;
+use typst::text::{Font, FontBook, FontInfo};
+use walkdir::WalkDir;
+
+use crate::parse_font_config::{
+    EMBEDDED_FONTS, FontConfig, TypstFont, deserialize_fonts_from_file, deserialize_fonts_from_toml,
+};
+use crate::utils::font_utils::is_font_file;
+
+/// Holds details about the location of a font and lazily the font itself.
+#[derive(Debug)]
+pub struct FontSlot {
+    /// The path at which the font can be found.
+    path: PathBuf,
+    /// The index of the font in its collection. Zero if the path does not point
+    /// to a collection.
+    index: u32,
+    /// The lazily loaded font.
+    font: OnceLock<Option<Font>>,
+}
+
+impl FontSlot {
+    /// Returns the path at which the font can be found.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the index of the font in its collection. Zero if the path does
+    /// not point to a collection.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Get the font for this slot. This loads the font into memory on first
+    /// access.
+    pub fn get(&self) -> Option<Font> {
+        self.font
+            .get_or_init(|| {
+                let data = fs::read(&self.path).ok()?;
+                Font::new(Bytes::new(data), self.index)
+            })
+            .clone()
+    }
+}
+
+/// The fonts of a project, in the same order in `book` and `fonts`.
+#[derive(Debug)]
+pub struct ProjectFonts {
+    /// Metadata about the fonts, for `World::book`.
+    pub book: FontBook,
+    /// Slots that the fonts are loaded into, for `World::font`.
+    pub fonts: Vec<FontSlot>,
+}
+
+/// Resolve the fonts required by the `font_config.toml` at `config_file` in
+/// the project's font directory.
+///
+/// Only faces that satisfy a required font are included: those of a required
+/// family with its exact variant, and variable faces of a required family.
+/// Fonts embedded in the Typst compiler are not part of the result, but they
+/// don't have to be installed either; add them from `typst-assets` if the
+/// project uses them. Fails if any other required font isn't installed, as
+/// Typst would silently fall back to another font.
+pub fn project_fonts(config_file: &Path) -> Result<ProjectFonts, String> {
+    let ProjectConfig {
+        font_config,
+        font_dir,
+        embedded,
+    } = ProjectConfig::load(config_file)?;
+
+    let mut book = FontBook::new();
+    let mut fonts = Vec::new();
+    let mut satisfied = BTreeSet::new();
+    for path in installed_font_files(&font_dir) {
+        let Ok(data) = fs::read(&path) else {
+            continue;
+        };
+        let count = ttf_parser::fonts_in_collection(&data).unwrap_or(1);
+        for index in 0..count {
+            let Some(info) = FontInfo::new(&data, index) else {
+                continue;
+            };
+            let matched = font_config
+                .fonts
+                .iter()
+                .filter(|font| info_satisfies(&info, font))
+                .collect::<Vec<_>>();
+            if matched.is_empty() {
+                continue;
+            }
+            satisfied.extend(matched);
+            book.push(info);
+            fonts.push(FontSlot {
+                path: path.clone(),
+                index,
+                font: OnceLock::new(),
+            });
+        }
+    }
+
+    let missing = font_config
+        .fonts
+        .iter()
+        .filter(|font| !satisfied.contains(font) && !embedded.contains(*font))
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        return Err(format!(
+            "Fonts not installed in {font_dir:?}, run `typfont update` first: {}",
+            missing.join(", ")
+        ));
+    }
+    Ok(ProjectFonts { book, fonts })
+}
+
+/// The parts of a project's `font_config.toml` that decide which fonts it needs
+pub(crate) struct ProjectConfig {
+    pub(crate) font_config: FontConfig,
+    /// The project font directory, relative to the working directory
+    pub(crate) font_dir: PathBuf,
+    /// The fonts embedded in the Typst compiler, which needn't be installed
+    pub(crate) embedded: BTreeSet<TypstFont>,
+}
+
+impl ProjectConfig {
+    pub(crate) fn load(config_file: &Path) -> Result<Self, String> {
+        let font_config = deserialize_fonts_from_file(config_file)
+            .map_err(|e| format!("Failed to parse {config_file:?}: {e:#}"))?;
+        let font_dir = font_config.font_dir.as_deref().unwrap_or("fonts");
+        let font_dir = config_file
+            .parent()
+            .unwrap_or(Path::new("."))
+            .join(font_dir);
+        let embedded = deserialize_fonts_from_toml(EMBEDDED_FONTS)
+            .map_err(|e| format!("Failed to parse the embedded fonts: {e:#}"))?
+            .fonts
+            .into_iter()
+            .collect();
+        Ok(Self {
+            font_config,
+            font_dir,
+            embedded,
+        })
+    }
+}
+
+/// The font files below `font_dir`, in a stable order
+pub(crate) fn installed_font_files(font_dir: &Path) -> Vec<PathBuf> {
+    let mut files = WalkDir::new(font_dir)
+        .into_iter()
+        // Leaving out hidden directories, such as fonts awaiting review
+        .filter_entry(|entry| {
+            entry.depth() == 0 || !entry.file_name().to_string_lossy().starts_with('.')
+        })
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file() && is_font_file(entry.path()))
+        .map(|entry| entry.into_path())
+        .collect::<Vec<_>>();
+    files.sort();
+    files
+}
+
+pub(crate) fn info_satisfies(info: &FontInfo, font: &TypstFont) -> bool {
+    if info.family != font.family_name {
+        return false;
+    }
+    let variant = info.variant;
+    (variant.style == font.style
+        && variant.weight == font.weight
+        && variant.stretch == font.stretch)
+        || !info.axes.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_project_fonts_are_restricted_to_the_config() {
+        let system = Path::new("/usr/share/fonts/truetype/dejavu");
+        if !system.join("DejaVuSerif.ttf").is_file() {
+            return;
+        }
+        let project = std::env::temp_dir().join(format!("typfont-world-{}", std::process::id()));
+        let font_dir = project.join("fonts");
+        fs::create_dir_all(&font_dir).unwrap();
+        for file in ["DejaVuSerif.ttf", "DejaVuSans.ttf"] {
+            fs::copy(system.join(file), font_dir.join(file)).unwrap();
+        }
+        let config = project.join("font_config.toml");
+        fs::write(
+            &config,
+            "[[fonts]]\nfamily_name = \"DejaVu Serif\"\n\n[[fonts]]\nfamily_name = \"New Computer Modern\"\n",
+        )
+        .unwrap();
+
+        let fonts = project_fonts(&config).unwrap();
+        assert_eq!(fonts.fonts.len(), 1);
+        assert_eq!(fonts.book.info(0).unwrap().family, "DejaVu Serif");
+        assert!(fonts.fonts[0].get().is_some());
+
+        fs::write(&config, "[[fonts]]\nfamily_name = \"Lato\"\n").unwrap();
+        assert!(project_fonts(&config).unwrap_err().contains("Lato"));
+        fs::remove_dir_all(&project).unwrap();
+    }
+}
@@ -0,0 +1,333 @@
+//! `typfont demo` runs the whole check and update cycle on the bundled fonts
+
+#![cfg(feature = "test-fixtures")]
+
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn test_demo_installs_the_fixture_fonts() {
+    let dir = std::env::temp_dir().join(format!("typfont-demo-test-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let output = Command::new(env!("CARGO_BIN_EXE_typfont"))
+        .args(["demo", "--lang", "en", "--dir"])
+        .arg(&dir)
+        .env("TYPFONT_CONFIG", dir.with_extension("toml"))
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{stdout}");
+    assert!(stdout.contains("The demo finished"), "{stdout}");
+
+    for file in ["TypfontFixture-Regular.ttf", "TypfontFixture-Bold.ttf"] {
+        assert_eq!(
+            fs::read(dir.join("project/fonts").join(file)).unwrap(),
+            fs::read(dir.join("library").join(file)).unwrap()
+        );
+    }
+    assert!(dir.join("project/font_config.lock").exists());
+
+    // A directory in use is refused
+    let output = Command::new(env!("CARGO_BIN_EXE_typfont"))
+        .args(["demo", "--dir"])
+        .arg(&dir)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(8));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_check_format_json_prints_a_single_document() {
+    let dir = std::env::temp_dir().join(format!("typfont-format-test-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let output = Command::new(env!("CARGO_BIN_EXE_typfont"))
+        .args(["demo", "--lang", "en", "--dir"])
+        .arg(&dir)
+        .env("TYPFONT_CONFIG", dir.with_extension("toml"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_typfont"))
+        .arg("check")
+        .arg(dir.join("project"))
+        .arg("--library")
+        .arg(dir.join("library"))
+        .args(["--format", "json"])
+        .env("TYPFONT_CONFIG", dir.with_extension("toml"))
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{stdout}");
+    let report: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(report["counts"]["missing"], 0);
+    assert_eq!(report["required"].as_array().unwrap().len(), 2);
+    assert!(
+        report["fonts"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|row| row["status"] == "present")
+    );
+    assert_eq!(report["library"].as_array().unwrap().len(), 2);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_update_format_json_keeps_warnings_off_stdout() {
+    let dir = std::env::temp_dir().join(format!("typfont-restricted-test-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let output = Command::new(env!("CARGO_BIN_EXE_typfont"))
+        .args(["demo", "--lang", "en", "--dir"])
+        .arg(&dir)
+        .env("TYPFONT_CONFIG", dir.with_extension("toml"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // The bold face becomes restricted: fsType 0x0002 in its OS/2 table
+    let font_path = dir.join("library/TypfontFixture-Bold.ttf");
+    let mut font = fs::read(&font_path).unwrap();
+    let num_tables = u16::from_be_bytes([font[4], font[5]]) as usize;
+    let record = (0..num_tables)
+        .map(|table| 12 + table * 16)
+        .find(|&record| &font[record..record + 4] == b"OS/2")
+        .unwrap();
+    let os2 = u32::from_be_bytes(font[record + 8..record + 12].try_into().unwrap()) as usize;
+    font[os2 + 8..os2 + 10].copy_from_slice(&2u16.to_be_bytes());
+    fs::write(&font_path, font).unwrap();
+    fs::remove_file(dir.join("project/fonts/TypfontFixture-Bold.ttf")).unwrap();
+    // A work tree, so that the font directory is tracked
+    let init = Command::new("git").arg("init").arg("-q").arg(&dir).status();
+    assert!(init.unwrap().success());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_typfont"))
+        .args(["--no-cache", "update"])
+        .arg(dir.join("project"))
+        .arg("--library")
+        .arg(dir.join("library"))
+        .args(["--format", "json"])
+        .env("TYPFONT_CONFIG", dir.with_extension("toml"))
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    assert!(stderr.contains("may not be redistributed"), "{stderr}");
+    let report: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(report["installed"].as_array().unwrap().len(), 1);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_check_emits_make_deps_of_the_installed_fonts() {
+    let dir = std::env::temp_dir().join(format!("typfont-deps-test-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let output = Command::new(env!("CARGO_BIN_EXE_typfont"))
+        .args(["demo", "--lang", "en", "--dir"])
+        .arg(&dir)
+        .env("TYPFONT_CONFIG", dir.with_extension("toml"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // The project is up to date since the demo, which doesn't skip the file
+    let deps = dir.join("fonts.d");
+    let output = Command::new(env!("CARGO_BIN_EXE_typfont"))
+        .arg("check")
+        .arg(dir.join("project"))
+        .arg("--library")
+        .arg(dir.join("library"))
+        .arg("--emit")
+        .arg(format!("make-deps={}", deps.display()))
+        .args(["--deps-target", "out/report.pdf"])
+        .env("TYPFONT_CONFIG", dir.with_extension("toml"))
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{stdout}");
+    let fonts = dir.join("project/fonts");
+    let bold = fonts.join("TypfontFixture-Bold.ttf").display().to_string();
+    let regular = fonts
+        .join("TypfontFixture-Regular.ttf")
+        .display()
+        .to_string();
+    assert_eq!(
+        fs::read_to_string(&deps).unwrap(),
+        format!("out/report.pdf: \\\n  {bold} \\\n  {regular}\n\n{bold}:\n\n{regular}:\n")
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_typfont"))
+        .arg("check")
+        .arg(dir.join("project"))
+        .args(["--emit", "json=deps.json"])
+        .env("TYPFONT_CONFIG", dir.with_extension("toml"))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(8));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_blake3_is_recorded_and_verified() {
+    let dir = std::env::temp_dir().join(format!("typfont-blake3-test-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let typfont = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_typfont"))
+            .args(args)
+            .env("TYPFONT_CONFIG", dir.with_extension("toml"))
+            .current_dir(&dir)
+            .output()
+            .unwrap()
+    };
+    fs::create_dir_all(&dir).unwrap();
+    let output = typfont(&["demo", "--lang", "en", "--dir", "sandbox"]);
+    assert!(output.status.success());
+
+    let font = dir.join("sandbox/project/fonts/TypfontFixture-Bold.ttf");
+    fs::remove_file(&font).unwrap();
+    let output = typfont(&[
+        "update",
+        "sandbox/project",
+        "-l",
+        "sandbox/library",
+        "--hash-algo",
+        "blake3",
+    ]);
+    assert!(output.status.success());
+    let lock = fs::read_to_string(dir.join("sandbox/project/font_config.lock")).unwrap();
+    assert_eq!(lock.matches("blake3 = ").count(), 1, "{lock}");
+
+    fs::write(&font, b"rotten").unwrap();
+    let output = typfont(&[
+        "repair",
+        "sandbox/project",
+        "--dry-run",
+        "--hash-algo",
+        "blake3",
+    ]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{stdout}");
+    assert!(
+        stdout.contains("TypfontFixture-Bold.ttf: modified (BLAKE3 "),
+        "{stdout}"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_history_rollback_restores_the_fonts_of_a_run() {
+    let dir = std::env::temp_dir().join(format!("typfont-rollback-test-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let typfont = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_typfont"))
+            .args(args)
+            .args(["--lang", "en"])
+            .env("TYPFONT_CONFIG", dir.with_extension("toml"))
+            .current_dir(&dir)
+            .output()
+            .unwrap()
+    };
+    fs::create_dir_all(&dir).unwrap();
+    let output = typfont(&["demo", "--dir", "sandbox"]);
+    assert!(output.status.success());
+
+    // Run 1 installs the Regular, run 2 the Bold
+    let project = dir.join("project");
+    fs::create_dir_all(&project).unwrap();
+    for weights in ["400", "400, 700"] {
+        fs::write(
+            project.join("font_config.toml"),
+            format!("[[fonts]]\nfamily_name = \"Typfont Fixture\"\nweight = [{weights}]\n"),
+        )
+        .unwrap();
+        let output = typfont(&["update", "project", "-l", "sandbox/library"]);
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
+    let regular = project.join("fonts/TypfontFixture-Regular.ttf");
+    let bold = project.join("fonts/TypfontFixture-Bold.ttf");
+    assert!(regular.exists() && bold.exists());
+
+    fs::write(&regular, b"rotten").unwrap();
+    let output = typfont(&[
+        "history",
+        "project",
+        "rollback",
+        "1",
+        "-l",
+        "sandbox/library",
+    ]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{stdout}");
+    assert!(!bold.exists(), "{stdout}");
+    assert_eq!(
+        fs::read(&regular).unwrap(),
+        fs::read(dir.join("sandbox/library/TypfontFixture-Regular.ttf")).unwrap()
+    );
+    let lock = fs::read_to_string(project.join("font_config.lock")).unwrap();
+    assert!(!lock.contains("TypfontFixture-Bold.ttf"), "{lock}");
+
+    let output = typfont(&["history", "project", "rollback", "3"]);
+    assert_eq!(output.status.code(), Some(8));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_update_refuses_a_missing_converter_and_cleans_up_failed_conversions() {
+    let dir = std::env::temp_dir().join(format!("typfont-convert-test-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let typfont = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_typfont"))
+            .args(args)
+            .args(["--lang", "en"])
+            .env("TYPFONT_CONFIG", dir.with_extension("toml"))
+            .current_dir(&dir)
+            .output()
+            .unwrap()
+    };
+    fs::create_dir_all(dir.join("project")).unwrap();
+    let output = typfont(&["demo", "--dir", "sandbox"]);
+    assert!(output.status.success());
+    fs::write(
+        dir.join("project/font_config.toml"),
+        "font_format = \"otf\"\n\n[[fonts]]\nfamily_name = \"Typfont Fixture\"\n",
+    )
+    .unwrap();
+    let update = |converter: &str| {
+        typfont(&[
+            "update",
+            "project",
+            "-l",
+            "sandbox/library",
+            "--convert",
+            "--converter",
+            converter,
+        ])
+    };
+
+    let output = update("typfont-missing-converter {input} {output}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("isn't installed"), "{stderr}");
+    assert!(
+        !dir.join("project/fonts/TypfontFixture-Regular.ttf")
+            .exists()
+    );
+
+    // The copy of a file that failed to convert is removed again
+    let output = update("false {input} {output}");
+    assert!(!output.status.success());
+    let installed = fs::read_dir(dir.join("project/fonts"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .filter(|name| !name.to_string_lossy().starts_with('.'))
+        .collect::<Vec<_>>();
+    assert!(installed.is_empty(), "{installed:?}");
+    fs::remove_dir_all(&dir).unwrap();
+}
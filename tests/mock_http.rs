@@ -0,0 +1,536 @@
+//! Runs typfont against a local mock of GitHub, see `src/mock_server.rs`, so that
+//! GitHub libraries are tested without the network.
+
+#![cfg(feature = "network")]
+
+#[path = "../src/mock_server.rs"]
+mod mock_server;
+
+use mock_server::MockServer;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+const FONT: &[u8] = include_bytes!("../fixtures/fonts/TypfontFixture-Regular.ttf");
+
+/// A served library `owner/fonts` holding one font, and a project requiring it
+struct Fixture {
+    dir: PathBuf,
+    server: MockServer,
+}
+
+impl Fixture {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("typfont-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        // Raw files, and the contents API for requests with a token
+        for repo in ["srv/owner/fonts/main", "srv/api/repos/owner/fonts/contents"] {
+            let repo = dir.join(repo);
+            fs::create_dir_all(repo.join("Fixture")).unwrap();
+            fs::write(repo.join("Fixture/TypfontFixture-Regular.ttf"), FONT).unwrap();
+            fs::write(
+                repo.join("font_library.toml"),
+                "[[fonts]]\nfamily_name = \"Typfont Fixture\"\npath = \"Fixture/TypfontFixture-Regular.ttf\"\n",
+            )
+            .unwrap();
+        }
+        let api = dir.join("srv/api/repos/owner/fonts/commits");
+        fs::create_dir_all(&api).unwrap();
+        fs::write(
+            api.join("main"),
+            r#"{"sha": "0123abcd", "commit": {"committer": {"date": "2026-01-02T03:04:05Z"}}}"#,
+        )
+        .unwrap();
+        fs::create_dir_all(dir.join("proj")).unwrap();
+        fs::write(
+            dir.join("proj/font_config.toml"),
+            "[[fonts]]\nfamily_name = \"Typfont Fixture\"\n",
+        )
+        .unwrap();
+
+        let server = MockServer::start(&dir.join("srv")).unwrap();
+        Self { dir, server }
+    }
+
+    fn served_font(&self) -> PathBuf {
+        self.dir
+            .join("srv/owner/fonts/main/Fixture/TypfontFixture-Regular.ttf")
+    }
+
+    fn installed_font(&self) -> PathBuf {
+        self.dir.join("proj/fonts/TypfontFixture-Regular.ttf")
+    }
+
+    /// Run `typfont <command> proj -g -l owner/fonts` with its own cache and settings
+    fn run(&self, command: &str, settings: Option<&str>) -> Output {
+        self.run_library(command, "owner/fonts", &[], settings)
+    }
+
+    /// Run `typfont <command> proj -g -l <library> <args>` with its own cache and
+    /// settings, and no token from the environment
+    fn run_library(
+        &self,
+        command: &str,
+        library: &str,
+        args: &[&str],
+        settings: Option<&str>,
+    ) -> Output {
+        let config = self.dir.join("config.toml");
+        fs::write(&config, settings.unwrap_or_default()).unwrap();
+        Command::new(env!("CARGO_BIN_EXE_typfont"))
+            .args([command, "proj", "-g", "-l", library, "--lang", "en"])
+            .args(args)
+            .arg("--mock-server")
+            .arg(self.server.url())
+            .current_dir(&self.dir)
+            .env("TYPFONT_CACHE_DIR", self.dir.join("cache"))
+            .env("TYPFONT_CONFIG", &config)
+            .env_remove("GITHUB_TOKEN")
+            .env_remove("GH_TOKEN")
+            .output()
+            .unwrap()
+    }
+
+    /// Paths of the requests received so far
+    fn requested_paths(&self) -> Vec<String> {
+        self.server
+            .requests()
+            .into_iter()
+            .map(|request| request.path)
+            .collect()
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn output_text(output: &Output) -> String {
+    format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    )
+}
+
+#[test]
+fn test_update_installs_fonts_from_the_mock_library() {
+    let fixture = Fixture::new("mock-update");
+    let output = fixture.run("update", None);
+    assert!(output.status.success(), "{}", output_text(&output));
+
+    assert_eq!(
+        fs::read(fixture.installed_font()).unwrap(),
+        fs::read(fixture.served_font()).unwrap()
+    );
+    let lock = fs::read_to_string(fixture.dir.join("proj/font_config.lock")).unwrap();
+    assert!(lock.contains(&fixture.server.url()), "{lock}");
+    let paths = fixture.requested_paths();
+    for path in [
+        "/owner/fonts/main/font_library.toml",
+        "/owner/fonts/main/Fixture/TypfontFixture-Regular.ttf",
+        "/api/repos/owner/fonts/commits/main",
+    ] {
+        assert!(paths.iter().any(|requested| requested == path), "{paths:?}");
+    }
+}
+
+impl Fixture {
+    /// Move the served library from the default branch to `reference`, leaving
+    /// an empty index on the default branch
+    fn pin_library(&self, reference: &str, sha: &str) {
+        let main = self.dir.join("srv/owner/fonts/main");
+        let pinned = self.dir.join("srv/owner/fonts").join(reference);
+        fs::create_dir_all(pinned.parent().unwrap()).unwrap();
+        fs::rename(&main, &pinned).unwrap();
+        fs::create_dir_all(&main).unwrap();
+        fs::write(main.join("font_library.toml"), "").unwrap();
+        let commit = self
+            .dir
+            .join("srv/api/repos/owner/fonts/commits")
+            .join(reference);
+        fs::create_dir_all(commit.parent().unwrap()).unwrap();
+        fs::write(
+            commit,
+            format!(r#"{{"sha": "{sha}", "commit": {{"committer": {{"date": "2025-06-07T08:09:10Z"}}}}}}"#),
+        )
+        .unwrap();
+    }
+}
+
+#[test]
+fn test_pinned_library_is_read_at_its_tag() {
+    let fixture = Fixture::new("mock-pinned");
+    // The tag holds the font, the default branch doesn't anymore
+    fixture.pin_library("v1.2.0", "4567cdef");
+
+    let output = fixture.run_library("update", "owner/fonts@v1.2.0", &[], None);
+    assert!(output.status.success(), "{}", output_text(&output));
+    assert!(fixture.installed_font().exists());
+
+    let lock = fs::read_to_string(fixture.dir.join("proj/font_config.lock")).unwrap();
+    assert!(
+        lock.contains("source = \"owner/fonts/Fixture/TypfontFixture-Regular.ttf\""),
+        "{lock}"
+    );
+    assert!(lock.contains("reference = \"v1.2.0\""), "{lock}");
+    assert!(lock.contains("commit = \"4567cdef\""), "{lock}");
+    let paths = fixture.requested_paths();
+    assert!(
+        paths.contains(&"/owner/fonts/v1.2.0/Fixture/TypfontFixture-Regular.ttf".to_string()),
+        "{paths:?}"
+    );
+    assert!(
+        !paths.iter().any(|path| path.contains("/main")),
+        "{paths:?}"
+    );
+
+    // The lockfile pins the revision too, even where the default branch has
+    // the same file
+    let served = fixture.dir.join("srv/owner/fonts");
+    fs::copy(
+        served.join("v1.2.0/font_library.toml"),
+        served.join("main/font_library.toml"),
+    )
+    .unwrap();
+    fs::remove_file(fixture.installed_font()).unwrap();
+    let output = fixture.run_library("update", "owner/fonts", &["--locked"], None);
+    assert!(!output.status.success(), "{}", output_text(&output));
+    assert!(
+        output_text(&output)
+            .contains("from \"v1.2.0\", but the library is read from the default branch"),
+        "{}",
+        output_text(&output)
+    );
+}
+
+#[test]
+fn test_pinned_library_is_read_at_a_branch_containing_a_slash() {
+    let fixture = Fixture::new("mock-pinned-slash");
+    fixture.pin_library("release/2024", "89abcdef");
+
+    let output = fixture.run_library("update", "owner/fonts@release/2024", &[], None);
+    assert!(output.status.success(), "{}", output_text(&output));
+    assert!(fixture.installed_font().exists());
+
+    let lock = fs::read_to_string(fixture.dir.join("proj/font_config.lock")).unwrap();
+    assert!(
+        lock.contains("source = \"owner/fonts/Fixture/TypfontFixture-Regular.ttf\""),
+        "{lock}"
+    );
+    assert!(lock.contains("reference = \"release/2024\""), "{lock}");
+    assert!(lock.contains("commit = \"89abcdef\""), "{lock}");
+    // The branch is a single segment of the URLs
+    let paths = fixture.requested_paths();
+    assert!(
+        paths.contains(
+            &"/owner/fonts/release%2F2024/Fixture/TypfontFixture-Regular.ttf".to_string()
+        ),
+        "{paths:?}"
+    );
+
+    fs::remove_file(fixture.installed_font()).unwrap();
+    let output = fixture.run_library("update", "owner/fonts@release/2024", &["--locked"], None);
+    assert!(output.status.success(), "{}", output_text(&output));
+    assert!(fixture.installed_font().exists());
+}
+
+#[test]
+fn test_cached_index_is_revalidated_by_etag() {
+    let fixture = Fixture::new("mock-etag");
+    for _ in 0..2 {
+        let output = fixture.run("check", None);
+        // The font is missing, and the library has it
+        assert_eq!(output.status.code(), Some(1), "{}", output_text(&output));
+    }
+    let index_requests = fixture
+        .server
+        .requests()
+        .into_iter()
+        .filter(|request| request.path.ends_with("font_library.toml"))
+        .collect::<Vec<_>>();
+    assert_eq!(index_requests.len(), 2);
+    assert!(index_requests[0].header("If-None-Match").is_none());
+    assert!(index_requests[1].header("If-None-Match").is_some());
+}
+
+#[test]
+fn test_changed_file_is_refused_by_its_locked_checksum() {
+    let fixture = Fixture::new("mock-checksum");
+    assert!(fixture.run("update", None).status.success());
+
+    // The library serves other content than the lockfile recorded
+    fs::remove_file(fixture.installed_font()).unwrap();
+    let mut font = fs::read(fixture.served_font()).unwrap();
+    font.push(0);
+    fs::write(fixture.served_font(), font).unwrap();
+
+    let output = fixture.run("update", None);
+    let text = output_text(&output);
+    assert!(!output.status.success(), "{text}");
+    assert!(text.contains("does not match the expected hash"), "{text}");
+    assert!(!fixture.installed_font().exists());
+}
+
+#[test]
+fn test_configured_headers_authorize_requests() {
+    let fixture = Fixture::new("mock-auth");
+    fixture.server.require_authorization("Bearer secret");
+
+    let output = fixture.run("update", None);
+    assert!(!output.status.success());
+    assert!(output_text(&output).contains("401"));
+
+    let settings = "[http.headers.\"127.0.0.1\"]\nAuthorization = \"Bearer secret\"\n";
+    let output = fixture.run("update", Some(settings));
+    assert!(output.status.success(), "{}", output_text(&output));
+    assert!(fixture.installed_font().exists());
+}
+
+#[test]
+fn test_server_errors_are_reported() {
+    let fixture = Fixture::new("mock-error");
+    fixture.server.fail_next(1);
+    let output = fixture.run("update", None);
+    let text = output_text(&output);
+    assert!(!output.status.success(), "{text}");
+    assert!(text.contains("503"), "{text}");
+    assert!(!fixture.installed_font().exists());
+}
+
+#[test]
+fn test_rate_limited_requests_are_retried() {
+    let fixture = Fixture::new("mock-rate-limit");
+    fixture.server.rate_limit_next(2);
+    let output = fixture.run("update", None);
+    let text = output_text(&output);
+    assert!(output.status.success(), "{text}");
+    assert!(
+        text.contains("Rate limited by 127.0.0.1, retrying in 0s"),
+        "{text}"
+    );
+    assert!(fixture.installed_font().exists());
+
+    let paths = fixture.requested_paths();
+    let first = paths[0].clone();
+    assert_eq!(
+        paths.iter().filter(|path| **path == first).count(),
+        3,
+        "{paths:?}"
+    );
+}
+
+#[test]
+fn test_token_downloads_through_the_contents_api() {
+    let fixture = Fixture::new("mock-token");
+    fixture.server.require_authorization("Bearer secret");
+    let output = fixture.run_library("update", "owner/fonts", &["--token", "secret"], None);
+    assert!(output.status.success(), "{}", output_text(&output));
+    assert_eq!(fs::read(fixture.installed_font()).unwrap(), FONT);
+
+    let paths = fixture.requested_paths();
+    for path in [
+        "/api/repos/owner/fonts/contents/font_library.toml",
+        "/api/repos/owner/fonts/contents/Fixture/TypfontFixture-Regular.ttf",
+    ] {
+        assert!(paths.iter().any(|requested| requested == path), "{paths:?}");
+    }
+    assert!(
+        !paths.iter().any(|path| path.starts_with("/owner/")),
+        "{paths:?}"
+    );
+}
+
+#[test]
+fn test_local_mirror_is_preferred_over_the_remote_library() {
+    let fixture = Fixture::new("mock-mirror");
+    let mirror = fixture.dir.join("nas/fonts");
+    fs::create_dir_all(mirror.join("Fixture")).unwrap();
+    fs::copy(
+        fixture.dir.join("srv/owner/fonts/main/font_library.toml"),
+        mirror.join("font_library.toml"),
+    )
+    .unwrap();
+    let settings = format!(
+        "[library.local_mirrors]\n\"owner/fonts\" = {:?}\n",
+        mirror.display().to_string()
+    );
+    let font_requests = || {
+        fixture
+            .requested_paths()
+            .into_iter()
+            .filter(|path| path.ends_with(".ttf"))
+            .count()
+    };
+
+    // The mirror lacks the file, so it is downloaded
+    let output = fixture.run("update", Some(&settings));
+    assert!(output.status.success(), "{}", output_text(&output));
+    assert_eq!(font_requests(), 1);
+    let lock = fs::read_to_string(fixture.dir.join("proj/font_config.lock")).unwrap();
+    assert!(lock.contains("source_type = \"github\""), "{lock}");
+
+    fs::remove_file(fixture.installed_font()).unwrap();
+    fs::copy(
+        fixture.served_font(),
+        mirror.join("Fixture/TypfontFixture-Regular.ttf"),
+    )
+    .unwrap();
+    let output = fixture.run("update", Some(&settings));
+    assert!(output.status.success(), "{}", output_text(&output));
+    assert_eq!(font_requests(), 1);
+    assert!(fixture.installed_font().exists());
+    let lock = fs::read_to_string(fixture.dir.join("proj/font_config.lock")).unwrap();
+    assert!(lock.contains("source_type = \"mirror\""), "{lock}");
+    assert!(
+        lock.contains("source = \"owner/fonts/Fixture/TypfontFixture-Regular.ttf\""),
+        "{lock}"
+    );
+}
+
+#[test]
+fn test_update_pr_pushes_a_branch_and_opens_a_pull_request() {
+    let fixture = Fixture::new("mock-pr");
+    let git = |args: &[&str]| {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(fixture.dir.join("proj"))
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", output_text(&output));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+    let origin = fixture.dir.join("origin.git");
+    let status = Command::new("git")
+        .args(["init", "-q", "--bare"])
+        .arg(&origin)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    git(&["init", "-q", "-b", "main"]);
+    git(&["config", "user.name", "Font Bot"]);
+    git(&["config", "user.email", "bot@example.com"]);
+    git(&["add", "font_config.toml"]);
+    git(&["commit", "-q", "-m", "Add the font config"]);
+    // The repository is taken from the URL, the push goes to the local remote
+    let remote = "https://github.com/owner/template.git";
+    git(&["remote", "add", "origin", remote]);
+    git(&[
+        "config",
+        &format!("url.{}.pushInsteadOf", origin.display()),
+        remote,
+    ]);
+    let pulls = fixture.dir.join("srv/api/repos/owner/template");
+    fs::create_dir_all(&pulls).unwrap();
+    fs::write(
+        pulls.join("pulls"),
+        r#"{"html_url": "https://github.com/owner/template/pull/7"}"#,
+    )
+    .unwrap();
+
+    let config = fixture.dir.join("config.toml");
+    fs::write(&config, "").unwrap();
+    let update_pr = || {
+        Command::new(env!("CARGO_BIN_EXE_typfont"))
+            .args([
+                "update",
+                "proj",
+                "-g",
+                "-l",
+                "owner/fonts",
+                "--lang",
+                "en",
+                "--pr",
+            ])
+            .arg("--mock-server")
+            .arg(fixture.server.url())
+            .current_dir(&fixture.dir)
+            .env("TYPFONT_CACHE_DIR", fixture.dir.join("cache"))
+            .env("TYPFONT_CONFIG", &config)
+            .env("GITHUB_TOKEN", "secret")
+            .output()
+            .unwrap()
+    };
+
+    // Changes the user staged don't go into the pull request
+    fs::write(fixture.dir.join("proj/notes.txt"), "notes").unwrap();
+    git(&["add", "notes.txt"]);
+    let output = update_pr();
+    let text = output_text(&output);
+    assert!(!output.status.success(), "{text}");
+    assert!(text.contains("unstage the staged changes"), "{text}");
+    git(&["reset", "-q", "notes.txt"]);
+
+    let incoming = fixture.dir.join("proj/fonts/.incoming");
+    fs::create_dir_all(&incoming).unwrap();
+    fs::write(incoming.join("Unreviewed.ttf"), "font").unwrap();
+    let output = update_pr();
+    let text = output_text(&output);
+    assert!(output.status.success(), "{text}");
+    assert!(
+        text.contains("https://github.com/owner/template/pull/7"),
+        "{text}"
+    );
+
+    assert_eq!(git(&["rev-parse", "--abbrev-ref", "HEAD"]).trim(), "main");
+    let branch = git(&["branch", "--list", "typfont/*", "--format=%(refname:short)"]);
+    assert!(branch.starts_with("typfont/update-fonts-"), "{branch}");
+    let committed = git(&["show", "--name-only", "--format=%B", branch.trim()]);
+    for file in ["fonts/TypfontFixture-Regular.ttf", "font_config.lock"] {
+        assert!(committed.contains(file), "{committed}");
+    }
+    for file in ["notes.txt", "Unreviewed.ttf"] {
+        assert!(!committed.contains(file), "{committed}");
+    }
+    assert!(
+        committed.contains("| fonts/TypfontFixture-Regular.ttf | added |"),
+        "{committed}"
+    );
+    let pushed = Command::new("git")
+        .arg("-C")
+        .arg(&origin)
+        .args(["branch", "--list", branch.trim()])
+        .output()
+        .unwrap();
+    assert!(!pushed.stdout.is_empty());
+    let request = fixture
+        .server
+        .requests()
+        .into_iter()
+        .find(|request| request.method == "POST")
+        .unwrap();
+    assert_eq!(request.path, "/api/repos/owner/template/pulls");
+    assert_eq!(request.header("Authorization"), Some("Bearer secret"));
+}
+
+#[test]
+fn test_credential_profile_authorizes_the_requests_of_its_repositories() {
+    let fixture = Fixture::new("mock-credentials");
+    let settings = r#"
+[credentials.work]
+token = "work-token"
+
+[credentials.personal]
+token_env = "TYPFONT_TEST_PERSONAL_TOKEN"
+
+[library.credentials]
+"owner/*" = "work"
+"gooduser/*" = "personal"
+"#;
+    let output = fixture.run("update", Some(settings));
+    assert!(output.status.success(), "{}", output_text(&output));
+
+    let requests = fixture.server.requests();
+    assert!(!requests.is_empty());
+    for request in requests {
+        assert_eq!(
+            request.header("Authorization"),
+            Some("Bearer work-token"),
+            "{}",
+            request.path
+        );
+    }
+}